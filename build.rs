@@ -0,0 +1,44 @@
+//! Records the build's enabled features, and the version of `deno_core` it resolved to, for
+//! `rustyscript::build_info`
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    // Cargo sets `CARGO_FEATURE_<NAME>` for every enabled feature
+    // Groups such as `safe_extensions` are left out - the features they enable are listed instead
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| Some(key.strip_prefix("CARGO_FEATURE_")?.to_lowercase()))
+        .filter(|name| {
+            name != "default" && !name.ends_with("_extensions") && !name.ends_with("_features")
+        })
+        .collect();
+    features.sort();
+    println!(
+        "cargo:rustc-env=RUSTYSCRIPT_FEATURES={}",
+        features.join(",")
+    );
+
+    println!("cargo:rerun-if-changed=build.rs");
+    let version = deno_core_version().unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=RUSTYSCRIPT_DENO_CORE_VERSION={version}");
+}
+
+/// Reads the version of `deno_core` from the nearest `Cargo.lock` - the crate's own, or the
+/// workspace's it is built in
+fn deno_core_version() -> Option<String> {
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+    let lockfile = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())?;
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+
+    let lock = fs::read_to_string(lockfile).ok()?;
+    let mut lines = lock.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if line == r#"name = "deno_core""# {
+            let version = lines.next()?.strip_prefix("version = ")?;
+            return Some(version.trim_matches('"').to_string());
+        }
+    }
+    None
+}
@@ -0,0 +1,217 @@
+//! Introspection of the work a runtime's event loop is waiting on
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use deno_core::{
+    stats::{RuntimeActivity, RuntimeActivityStatsFilter},
+    JsRuntime, ResourceId, ResourceTable,
+};
+
+/// The kind of work a pending entry represents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PendingOpKind {
+    /// An async op that has been dispatched but has not yet completed
+    AsyncOp,
+
+    /// A resource held open in the resource table, such as a file or socket
+    Resource,
+
+    /// A pending `setTimeout` timer
+    Timer,
+
+    /// An active `setInterval` timer
+    Interval,
+}
+
+/// Information about a piece of work the event loop is waiting on
+///
+/// See [`crate::Runtime::pending_ops`]
+#[derive(Debug, Clone)]
+pub struct PendingOpInfo {
+    /// Name of the op or resource - `timer` and `interval` for timers
+    pub name: String,
+
+    /// The kind of work this entry represents
+    pub kind: PendingOpKind,
+
+    /// Time since the runtime first observed this entry
+    ///
+    /// Activity is sampled when it is inspected (and by the watchdog, if configured),
+    /// so this is a lower bound on the true age
+    pub age: Duration,
+
+    /// The resource id, for [`PendingOpKind::Resource`] entries
+    ///
+    /// `deno_core` does not associate in-flight async ops with the resource they act on,
+    /// so this is `None` for other kinds
+    pub rid: Option<ResourceId>,
+
+    pub(crate) key: ActivityKey,
+}
+
+/// An open entry in the runtime's resource table, such as a file, socket or stream
+///
+/// See [`crate::Runtime::resources`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceInfo {
+    /// The resource id, as seen by JS
+    pub rid: ResourceId,
+
+    /// The name of the resource type, such as `fsFile` or `tcpStream`
+    pub name: String,
+}
+
+/// Identifies a single piece of pending activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ActivityKey {
+    AsyncOp(i32),
+    Resource(ResourceId),
+    Timer(usize),
+    Interval(usize),
+}
+
+/// Captures pending activity, remembering when each entry was first seen
+#[derive(Default)]
+pub(crate) struct ActivityTracker {
+    first_seen: HashMap<ActivityKey, Instant>,
+}
+
+impl ActivityTracker {
+    /// Capture the activity the event loop is currently waiting on
+    pub fn capture(&mut self, runtime: &JsRuntime) -> Vec<PendingOpInfo> {
+        let now = Instant::now();
+        let activity = runtime
+            .runtime_activity_stats_factory()
+            .capture(&RuntimeActivityStatsFilter::all())
+            .dump()
+            .active;
+
+        let mut pending: Vec<_> = activity
+            .into_iter()
+            .map(|activity| {
+                let (kind, key, name, rid) = match activity {
+                    RuntimeActivity::AsyncOp(id, _, name) => (
+                        PendingOpKind::AsyncOp,
+                        ActivityKey::AsyncOp(id),
+                        name.to_string(),
+                        None,
+                    ),
+                    RuntimeActivity::Resource(rid, name) => (
+                        PendingOpKind::Resource,
+                        ActivityKey::Resource(rid),
+                        name,
+                        Some(rid),
+                    ),
+                    RuntimeActivity::Timer(id, _) => (
+                        PendingOpKind::Timer,
+                        ActivityKey::Timer(id),
+                        "timer".to_string(),
+                        None,
+                    ),
+                    RuntimeActivity::Interval(id, _) => (
+                        PendingOpKind::Interval,
+                        ActivityKey::Interval(id),
+                        "interval".to_string(),
+                        None,
+                    ),
+                };
+
+                let first_seen = *self.first_seen.entry(key).or_insert(now);
+                PendingOpInfo {
+                    name,
+                    kind,
+                    age: now.duration_since(first_seen),
+                    rid,
+                    key,
+                }
+            })
+            .collect();
+
+        // Forget anything that has completed since the last capture
+        self.first_seen
+            .retain(|key, _| pending.iter().any(|info| info.key == *key));
+
+        pending.sort_by_key(|info| info.key);
+        pending
+    }
+}
+
+/// Lists the open entries in a resource table
+pub(crate) fn resources(table: &ResourceTable) -> Vec<ResourceInfo> {
+    table
+        .names()
+        .map(|(rid, name)| ResourceInfo {
+            rid,
+            name: name.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_pending_ops() {
+        let timers = |runtime: &mut Runtime| {
+            runtime
+                .pending_ops()
+                .into_iter()
+                .filter(|op| op.kind == PendingOpKind::Timer)
+                .collect::<Vec<_>>()
+        };
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        assert!(timers(&mut runtime).is_empty());
+
+        let module = Module::new(
+            "test.js",
+            "export function f() { setTimeout(() => {}, 10000); }",
+        );
+        let module = runtime.load_module(&module).unwrap();
+        runtime
+            .call_function_immediate::<crate::Undefined>(Some(&module), "f", json_args!())
+            .unwrap();
+
+        let pending = timers(&mut runtime);
+        assert_eq!(1, pending.len());
+        assert_eq!(PendingOpKind::Timer, pending[0].kind);
+        assert_eq!(None, pending[0].rid);
+
+        std::thread::sleep(Duration::from_millis(20));
+        let pending = timers(&mut runtime);
+        assert!(pending[0].age >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_resources() {
+        struct TestResource;
+        impl deno_core::Resource for TestResource {
+            fn name(&self) -> std::borrow::Cow<str> {
+                "testResource".into()
+            }
+        }
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let rid = runtime
+            .deno_runtime()
+            .op_state()
+            .borrow_mut()
+            .resource_table
+            .add(TestResource);
+
+        assert!(runtime.resources().unwrap().contains(&ResourceInfo {
+            rid,
+            name: "testResource".to_string()
+        }));
+
+        runtime.close_resource(rid).unwrap();
+        assert!(!runtime.resources().unwrap().iter().any(|r| r.rid == rid));
+        runtime
+            .close_resource(rid)
+            .expect_err("Closed a resource twice");
+    }
+}
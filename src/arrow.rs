@@ -0,0 +1,245 @@
+//! Apache Arrow record batches, passed between Rust and JS as columnar buffers
+//!
+//! An [`ArrowTable`] reaches scripts as an object holding the table's schema, its row count, and
+//! an `ipc` `Uint8Array` with the batches in the Arrow IPC stream format. The buffer is handed to
+//! V8 without copying, and arrow-js reads it in place with `tableFromIPC(table.ipc)`
+//!
+//! Scripts return tables the same way - any object with an `ipc` buffer, such as
+//! `{ ipc: tableToIPC(table) }`, decodes into an [`ArrowTable`]
+use std::collections::HashMap;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_schema::{ArrowError, SchemaRef};
+use deno_core::{JsBuffer, ToJsBuffer};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Error;
+
+/// A table of Arrow record batches sharing a schema
+///
+/// Can be passed to and returned from JS functions like any other value:
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use rustyscript::{
+///     arrow_array::{Int64Array, RecordBatch},
+///     ArrowTable, Module, Runtime,
+/// };
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let ids = Int64Array::from(vec![1, 2, 3]);
+/// let batch = RecordBatch::try_from_iter([("id", Arc::new(ids) as _)]).unwrap();
+///
+/// let module = Module::new(
+///     "test.js",
+///     "export const describe = (table) => `${table.numRows} rows of ${table.schema.fields[0].name}`;",
+/// );
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let module = runtime.load_module(&module)?;
+/// let description: String =
+///     runtime.call_function(Some(&module), "describe", &(ArrowTable::from(batch),))?;
+/// assert_eq!(description, "3 rows of id");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrowTable {
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+}
+
+impl ArrowTable {
+    /// Create a table from batches matching the given schema
+    ///
+    /// # Errors
+    /// Will return an error if any of the batches has a different schema
+    pub fn new(schema: SchemaRef, batches: Vec<RecordBatch>) -> Result<Self, Error> {
+        if let Some(batch) = batches.iter().find(|batch| batch.schema() != schema) {
+            return Err(Error::Runtime(format!(
+                "Record batch schema does not match the table: {}",
+                batch.schema()
+            )));
+        }
+
+        Ok(Self { schema, batches })
+    }
+
+    /// Decode a table from bytes in the Arrow IPC stream format
+    ///
+    /// # Errors
+    /// Will return an error if the bytes are not a valid Arrow IPC stream
+    pub fn from_ipc(bytes: &[u8]) -> Result<Self, Error> {
+        let reader = StreamReader::try_new(bytes, None).map_err(arrow_error)?;
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().map_err(arrow_error)?;
+        Ok(Self { schema, batches })
+    }
+
+    /// Encode the table as bytes in the Arrow IPC stream format
+    ///
+    /// # Errors
+    /// Will return an error if the batches cannot be encoded
+    pub fn to_ipc(&self) -> Result<Vec<u8>, Error> {
+        let mut writer = StreamWriter::try_new(Vec::new(), &self.schema).map_err(arrow_error)?;
+        for batch in &self.batches {
+            writer.write(batch).map_err(arrow_error)?;
+        }
+        writer.into_inner().map_err(arrow_error)
+    }
+
+    /// The schema shared by the table's batches
+    #[must_use]
+    pub fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    /// The table's record batches
+    #[must_use]
+    pub fn batches(&self) -> &[RecordBatch] {
+        &self.batches
+    }
+
+    /// Consume the table, returning its record batches
+    #[must_use]
+    pub fn into_batches(self) -> Vec<RecordBatch> {
+        self.batches
+    }
+
+    /// The total number of rows across the table's batches
+    #[must_use]
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(RecordBatch::num_rows).sum()
+    }
+}
+
+impl From<RecordBatch> for ArrowTable {
+    fn from(batch: RecordBatch) -> Self {
+        Self {
+            schema: batch.schema(),
+            batches: vec![batch],
+        }
+    }
+}
+
+fn arrow_error(error: ArrowError) -> Error {
+    Error::Runtime(format!("Invalid Arrow data: {error}"))
+}
+
+/// The schema as seen by scripts
+#[derive(Serialize)]
+struct SchemaInfo {
+    fields: Vec<FieldInfo>,
+    metadata: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct FieldInfo {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+    nullable: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TableOut {
+    schema: SchemaInfo,
+    num_rows: usize,
+    ipc: ToJsBuffer,
+}
+
+#[derive(Deserialize)]
+struct TableIn {
+    ipc: JsBuffer,
+}
+
+impl Serialize for ArrowTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| FieldInfo {
+                name: field.name().clone(),
+                data_type: field.data_type().to_string(),
+                nullable: field.is_nullable(),
+            })
+            .collect();
+
+        TableOut {
+            schema: SchemaInfo {
+                fields,
+                metadata: self.schema.metadata().clone(),
+            },
+            num_rows: self.num_rows(),
+            ipc: self.to_ipc().map_err(ser::Error::custom)?.into(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArrowTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let table = TableIn::deserialize(deserializer)?;
+        Self::from_ipc(&table.ipc).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow_array::{Float64Array, StringArray};
+
+    use super::*;
+    use crate::{serde_json, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_arrow_table() {
+        let names = StringArray::from(vec![Some("a"), None, Some("c")]);
+        let scores = Float64Array::from(vec![1.5, 2.0, 3.25]);
+        let batch = RecordBatch::try_from_iter([
+            ("name", Arc::new(names) as _),
+            ("score", Arc::new(scores) as _),
+        ])
+        .unwrap();
+        let table = ArrowTable::from(batch);
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const describe = (table) => ({
+                rows: table.numRows,
+                fields: table.schema.fields.map((f) => `${f.name}: ${f.type}`),
+                isBuffer: table.ipc instanceof Uint8Array,
+            });
+            export const identity = (table) => ({ ipc: table.ipc });
+            export const corrupt = () => ({ ipc: new Uint8Array([1, 2, 3]) });
+            ",
+        );
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime.load_module(&module).unwrap();
+
+        let description: serde_json::Value = runtime
+            .call_function(Some(&module), "describe", &(&table,))
+            .unwrap();
+        assert_eq!(
+            description,
+            serde_json::json!({
+                "rows": 3,
+                "fields": ["name: Utf8", "score: Float64"],
+                "isBuffer": true,
+            })
+        );
+
+        let returned: ArrowTable = runtime
+            .call_function(Some(&module), "identity", &(&table,))
+            .unwrap();
+        assert_eq!(returned, table);
+
+        runtime
+            .call_function::<ArrowTable>(Some(&module), "corrupt", &())
+            .unwrap_err();
+    }
+}
@@ -1,8 +1,8 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
 use tokio_util::sync::CancellationToken;
 
-use crate::Error;
+use crate::{heartbeat::CallDeadline, Error};
 
 /// A wrapper around the tokio runtime allowing for borrowed usage
 ///
@@ -48,6 +48,7 @@ pub struct AsyncBridge {
     tokio: TokioRuntime,
     timeout: std::time::Duration,
     heap_exhausted_token: CancellationToken,
+    deadline: CallDeadline,
 }
 
 impl AsyncBridge {
@@ -75,6 +76,7 @@ impl AsyncBridge {
             tokio,
             timeout,
             heap_exhausted_token,
+            deadline: CallDeadline::default(),
         }
     }
 
@@ -89,6 +91,7 @@ impl AsyncBridge {
             tokio,
             timeout,
             heap_exhausted_token,
+            deadline: CallDeadline::default(),
         }
     }
 
@@ -117,25 +120,58 @@ impl AsyncBridge {
     pub fn heap_exhausted_token(&self) -> CancellationToken {
         self.heap_exhausted_token.clone()
     }
+
+    /// Returns the deadline of the call currently being blocked on
+    /// Heartbeats from the script can move it - see [`crate::HeartbeatOptions`]
+    pub(crate) fn deadline(&self) -> CallDeadline {
+        self.deadline.clone()
+    }
 }
 
 pub trait AsyncBridgeExt {
     fn bridge(&self) -> &AsyncBridge;
 
+    /// Labels to attach to errors raised while blocking
+    fn error_labels(&self) -> Option<Rc<HashMap<String, String>>> {
+        None
+    }
+
     fn block_on<'a, Out, F, Fut>(&'a mut self, f: F) -> Result<Out, Error>
     where
         Fut: std::future::Future<Output = Result<Out, Error>>,
         F: FnOnce(&'a mut Self) -> Fut,
     {
-        let timeout = self.bridge().timeout();
         let rt = self.bridge().tokio_runtime();
         let heap_exhausted_token = self.bridge().heap_exhausted_token();
+        let deadline = self.bridge().deadline();
+        let labels = self.error_labels();
 
         rt.block_on(async move {
-            tokio::select! {
-                result = tokio::time::timeout(timeout, f(self)) => result?,
+            deadline.start(self.bridge().timeout());
+            let call = f(self);
+            tokio::pin!(call);
+
+            let result = tokio::select! {
+                biased;
                 () = heap_exhausted_token.cancelled() => Err(Error::HeapExhausted),
-            }
+                error = deadline.expired() => {
+                    // Abort the call's signal, and give the script a chance to clean up
+                    tokio::select! {
+                        biased;
+                        _ = &mut call => {},
+                        () = deadline.abort_signal(&error) => {},
+                    }
+                    Err(error)
+                }
+                result = &mut call => result,
+            };
+
+            deadline.finish();
+            result
+        })
+        .map_err(|e| match labels {
+            Some(labels) => e.with_labels(&labels),
+            None => e,
         })
     }
 }
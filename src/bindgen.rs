@@ -0,0 +1,440 @@
+//! Generation of typed Rust clients for a module's exported functions
+//!
+//! Intended for build scripts: point [`Bindgen`] at a module, or at a `.d.ts` describing one, and
+//! write the generated source to `OUT_DIR`. Each export becomes a method wrapping
+//! [`crate::Runtime::call_function`], with argument and return types taken from the TypeScript
+//! signature - see [`crate::Module::exported_functions`] for how signatures are read
+use std::collections::HashSet;
+
+use deno_core::serde_json::Value;
+
+use crate::{Error, FunctionDescriptor, Module, ParamDescriptor};
+
+/// Rust keywords that cannot be used as method or argument names without a raw identifier
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// Methods every generated client has - exports with these names are renamed
+const RESERVED_METHODS: &[&str] = &["new", "load", "handle"];
+
+/// Names used by the body of every generated method - parameters with these names are renamed
+const RESERVED_PARAMS: &[&str] = &["runtime", "args", "value"];
+
+/// Generates a typed client struct for a module's exports
+///
+/// ```rust
+/// use rustyscript::{Bindgen, Module};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let module = Module::new(
+///     "calculator.d.ts",
+///     "export declare function add(a: number, b?: number): number;",
+/// );
+/// let source = Bindgen::new("Calculator").generate(&module)?;
+/// assert!(source.contains("pub fn add(&self, runtime: &mut rustyscript::Runtime, a: f64, b: Option<f64>)"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// In a build script, write the source to `OUT_DIR`, and pull it into the crate with
+/// `include!(concat!(env!("OUT_DIR"), "/calculator.rs"));`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bindgen {
+    name: String,
+    crate_path: String,
+}
+
+impl Bindgen {
+    /// Generate a client struct with the given name
+    #[must_use]
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            crate_path: "rustyscript".to_string(),
+        }
+    }
+
+    /// Set the path generated code uses to refer to this crate - `rustyscript` by default
+    ///
+    /// Useful when it is re-exported, as in `my_crate::rustyscript`
+    #[must_use]
+    pub fn with_crate_path(mut self, path: impl ToString) -> Self {
+        self.crate_path = path.to_string();
+        self
+    }
+
+    /// Returns the source of a client for the functions the module exports
+    ///
+    /// The module is parsed but not run - a `.d.ts` file can stand in for the module it describes
+    ///
+    /// # Errors
+    /// Will return an error if the module cannot be parsed
+    pub fn generate(&self, module: &Module) -> Result<String, Error> {
+        let functions = module.exported_functions()?;
+        let rs = &self.crate_path;
+        let name = &self.name;
+
+        let mut source = format!(
+            "/// Typed client for `{filename}`, generated by `{rs}::Bindgen` - do not edit
+#[derive(Debug, Clone)]
+pub struct {name}({rs}::ModuleHandle);
+
+#[allow(dead_code, clippy::too_many_arguments)]
+impl {name} {{
+    /// Wrap a handle to the loaded module
+    pub fn new(handle: {rs}::ModuleHandle) -> Self {{
+        Self(handle)
+    }}
+
+    /// Load the module into the runtime, and wrap the handle to it
+    pub fn load(runtime: &mut {rs}::Runtime, module: &{rs}::Module) -> Result<Self, {rs}::Error> {{
+        runtime.load_module(module).map(Self)
+    }}
+
+    /// Returns the handle to the loaded module
+    pub fn handle(&self) -> &{rs}::ModuleHandle {{
+        &self.0
+    }}
+",
+            filename = module.filename().display(),
+        );
+
+        // Overloads and re-declarations share a name - only the first signature is used
+        let mut seen = HashSet::new();
+        let mut taken = RESERVED_METHODS.iter().map(ToString::to_string).collect();
+        for function in functions.iter().filter(|f| seen.insert(f.name.clone())) {
+            let method = unique(ident(&function.name), &mut taken);
+            source.push('\n');
+            source.push_str(&self.method(&method, function));
+        }
+        source.push_str("}\n");
+        Ok(source)
+    }
+
+    /// Generate the source, and write it to the given path if it changed
+    ///
+    /// Leaving unchanged files alone keeps cargo from rebuilding the crate including them
+    ///
+    /// # Errors
+    /// Will return an error if the module cannot be parsed, or the file cannot be written
+    pub fn write(&self, module: &Module, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let source = self.generate(module)?;
+        let path = path.as_ref();
+        if std::fs::read_to_string(path).is_ok_and(|current| current == source) {
+            return Ok(());
+        }
+        std::fs::write(path, source)?;
+        Ok(())
+    }
+
+    fn method(&self, method: &str, function: &FunctionDescriptor) -> String {
+        let rs = &self.crate_path;
+        let returns = RustType::from_schema(&function.returns).owned(rs);
+
+        let mut taken = RESERVED_PARAMS.iter().map(ToString::to_string).collect();
+        let names: Vec<_> = function
+            .params
+            .iter()
+            .map(|p| unique(ident(&p.name), &mut taken))
+            .collect();
+
+        let mut params = String::new();
+        for (param, name) in function.params.iter().zip(&names) {
+            params.push_str(&format!(", {name}: {}", param_type(param, rs)));
+        }
+
+        let call = |args: &str| {
+            format!(
+                "runtime.call_function(Some(&self.0), {:?}, {args})",
+                function.name
+            )
+        };
+        let required = function
+            .params
+            .iter()
+            .take_while(|p| !p.optional && !p.rest)
+            .count();
+
+        // Without optional or rest parameters, arguments are passed as a tuple - saving a
+        // round-trip through `serde_json::Value`
+        let body = if required == function.params.len() {
+            match names.as_slice() {
+                [] => call("&()"),
+                [name] => call(&format!("&({name},)")),
+                names => call(&format!("&({})", names.join(", "))),
+            }
+        } else {
+            let mut body = "let mut args = Vec::new();\n".to_string();
+            let mut rest = None;
+            for (param, name) in function.params.iter().zip(&names) {
+                if param.rest {
+                    rest = Some(name);
+                } else {
+                    body.push_str(&format!(
+                        "        args.push({rs}::serde_json::to_value(&{name})?);\n"
+                    ));
+                }
+            }
+
+            // Omitted trailing arguments are left off, so the function's defaults apply
+            let trim = format!(
+                "while args.len() > {required} && args.last().is_some_and({rs}::serde_json::Value::is_null) {{ args.pop(); }}"
+            );
+            match rest {
+                Some(rest) => body.push_str(&format!(
+                    "        if {rest}.is_empty() {{ {trim} }}\n        for value in {rest} {{ args.push({rs}::serde_json::to_value(value)?); }}\n"
+                )),
+                None => body.push_str(&format!("        {trim}\n")),
+            }
+            body.push_str(&format!("        {}", call("&args")));
+            body
+        };
+
+        format!(
+            "    /// Calls `{js_name}`{is_async}
+    pub fn {name}(&self, runtime: &mut {rs}::Runtime{params}) -> Result<{returns}, {rs}::Error> {{
+        {body}
+    }}
+",
+            js_name = function.name,
+            is_async = if function.is_async {
+                ", resolving the promise it returns"
+            } else {
+                ""
+            },
+            name = method,
+        )
+    }
+}
+
+/// Returns `name`, with underscores appended until it does not clash with any name in `taken`
+fn unique(mut name: String, taken: &mut HashSet<String>) -> String {
+    while taken.contains(&name) {
+        // `r#type_` is not an identifier, but `type_` is
+        if let Some(raw) = name.strip_prefix("r#") {
+            name = raw.to_string();
+        }
+        name.push('_');
+    }
+    taken.insert(name.clone());
+    name
+}
+
+/// The Rust type used for a parameter - borrowed where that saves the caller a clone
+fn param_type(param: &ParamDescriptor, rs: &str) -> String {
+    if param.rest {
+        let items = param.schema.get("items").unwrap_or(&Value::Null);
+        return format!("&[{}]", RustType::from_schema(items).owned(rs));
+    }
+
+    let ty = RustType::from_schema(&param.schema);
+    if param.optional && !matches!(ty, RustType::Option(_)) {
+        RustType::Option(Box::new(ty)).borrowed(rs)
+    } else {
+        ty.borrowed(rs)
+    }
+}
+
+/// A method or argument name for a JS identifier - `fetchAll` becomes `fetch_all`
+fn ident(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            // `getURL` becomes `get_url`, not `get_u_r_l`
+            if prev.is_some_and(|p: char| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            snake.push(c);
+        } else {
+            snake.push('_');
+        }
+        prev = Some(c);
+    }
+
+    if snake.starts_with(|c: char| c.is_ascii_digit()) {
+        snake.insert(0, '_');
+    }
+    match snake.as_str() {
+        "self" | "Self" | "super" | "crate" => format!("{snake}_"),
+        name if KEYWORDS.contains(&name) => format!("r#{snake}"),
+        _ => snake,
+    }
+}
+
+/// The Rust equivalent of a JSON schema produced by [`crate::FunctionDescriptor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RustType {
+    Unit,
+    Bool,
+    Integer,
+    Number,
+    String,
+    Vec(Box<RustType>),
+    Map(Box<RustType>),
+    Option(Box<RustType>),
+    Value,
+}
+
+impl RustType {
+    fn from_schema(schema: &Value) -> Self {
+        if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+            let is_null = |o: &Value| o.get("type").and_then(Value::as_str) == Some("null");
+            let rest: Vec<_> = options.iter().filter(|o| !is_null(o)).collect();
+            return match rest.as_slice() {
+                [only] if rest.len() < options.len() => {
+                    Self::Option(Box::new(Self::from_schema(only).non_optional()))
+                }
+                _ => Self::Value,
+            };
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            return if values.iter().all(Value::is_string) {
+                Self::String
+            } else {
+                Self::Value
+            };
+        }
+
+        if let Some(value) = schema.get("const") {
+            return match value {
+                Value::String(_) => Self::String,
+                Value::Bool(_) => Self::Bool,
+                Value::Number(_) => Self::Number,
+                _ => Self::Value,
+            };
+        }
+
+        match schema.get("type").and_then(Value::as_str) {
+            Some("null") => Self::Unit,
+            Some("boolean") => Self::Bool,
+            Some("integer") => Self::Integer,
+            Some("number") => Self::Number,
+            Some("string") => Self::String,
+            Some("array") if schema.get("prefixItems").is_none() => {
+                let items = schema.get("items").unwrap_or(&Value::Null);
+                Self::Vec(Box::new(Self::from_schema(items)))
+            }
+            Some("object") if schema.get("properties").is_none() => {
+                match schema.get("additionalProperties") {
+                    Some(values) if values.is_object() => {
+                        Self::Map(Box::new(Self::from_schema(values)))
+                    }
+                    _ => Self::Value,
+                }
+            }
+            _ => Self::Value,
+        }
+    }
+
+    /// `Option<Option<T>>` has no meaning to JS - collapse it
+    fn non_optional(self) -> Self {
+        match self {
+            Self::Option(inner) => *inner,
+            ty => ty,
+        }
+    }
+
+    fn owned(&self, rs: &str) -> String {
+        match self {
+            Self::Unit => "()".to_string(),
+            Self::Bool => "bool".to_string(),
+            Self::Integer => "i64".to_string(),
+            Self::Number => "f64".to_string(),
+            Self::String => "String".to_string(),
+            Self::Vec(items) => format!("Vec<{}>", items.owned(rs)),
+            Self::Map(values) => format!("std::collections::HashMap<String, {}>", values.owned(rs)),
+            Self::Option(inner) => format!("Option<{}>", inner.owned(rs)),
+            Self::Value => format!("{rs}::serde_json::Value"),
+        }
+    }
+
+    fn borrowed(&self, rs: &str) -> String {
+        match self {
+            Self::Unit | Self::Bool | Self::Integer | Self::Number => self.owned(rs),
+            Self::String => "&str".to_string(),
+            Self::Vec(items) => format!("&[{}]", items.owned(rs)),
+            Self::Option(inner) => format!("Option<{}>", inner.borrowed(rs)),
+            Self::Map(_) | Self::Value => format!("&{}", self.owned(rs)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bindgen() {
+        let module = Module::new(
+            "service.d.ts",
+            "
+            interface User { id: number; name: string }
+            export declare function getUserByID(id: number): Promise<User | null>;
+            export declare function rename(id: number, name: string, notify?: boolean): void;
+            export declare function sum(...values: number[]): number;
+            export declare function tags(): Record<string, string[]>;
+            export declare function type(kind: 'a' | 'b'): string;
+            ",
+        );
+        let source = Bindgen::new("Service").generate(&module).unwrap();
+
+        assert!(source.contains("pub struct Service(rustyscript::ModuleHandle);"));
+        assert!(source.contains(
+            "pub fn get_user_by_id(&self, runtime: &mut rustyscript::Runtime, id: f64) -> Result<Option<rustyscript::serde_json::Value>, rustyscript::Error> {
+        runtime.call_function(Some(&self.0), \"getUserByID\", &(id,))"
+        ));
+        assert!(source.contains(
+            "pub fn rename(&self, runtime: &mut rustyscript::Runtime, id: f64, name: &str, notify: Option<bool>) -> Result<rustyscript::serde_json::Value, rustyscript::Error>"
+        ));
+        assert!(source.contains("while args.len() > 2 && args.last()"));
+        assert!(source.contains("values: &[f64]) -> Result<f64, rustyscript::Error>"));
+        assert!(source.contains("for value in values {"));
+        assert!(source.contains(
+            "-> Result<std::collections::HashMap<String, Vec<String>>, rustyscript::Error>"
+        ));
+        assert!(
+            source.contains("pub fn r#type(&self, runtime: &mut rustyscript::Runtime, kind: &str)")
+        );
+    }
+
+    #[test]
+    fn test_bindgen_collisions() {
+        let module = Module::new(
+            "service.d.ts",
+            "
+            export declare function load(runtime: string, args?: number[]): void;
+            export declare function handle(value: number, ...values: number[]): number;
+            export declare function fetchAll(): void;
+            export declare function fetch_all(): void;
+            export declare function type(): void;
+            export declare function Type(): void;
+            ",
+        );
+        let source = Bindgen::new("Service").generate(&module).unwrap();
+
+        assert!(source.contains(
+            "pub fn load_(&self, runtime: &mut rustyscript::Runtime, runtime_: &str, args_: Option<&[f64]>)"
+        ));
+        assert!(source.contains("args.push(rustyscript::serde_json::to_value(&args_)?);"));
+        assert!(source.contains(
+            "pub fn handle_(&self, runtime: &mut rustyscript::Runtime, value_: f64, values: &[f64])"
+        ));
+        assert!(source.contains("pub fn fetch_all(&self"));
+        assert!(source.contains("pub fn fetch_all_(&self"));
+        assert!(source.contains("pub fn r#type(&self"));
+        assert!(source.contains("pub fn type_(&self"));
+
+        // The client's own methods are left alone
+        assert!(source.contains("pub fn load(runtime: &mut rustyscript::Runtime"));
+        assert!(source.contains("pub fn handle(&self) -> &rustyscript::ModuleHandle"));
+    }
+}
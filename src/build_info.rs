@@ -0,0 +1,114 @@
+//! Introspection of the features and versions rustyscript was built with
+//!
+//! Lets hosts log the environment scripts run in, and gate functionality on what was compiled in
+use serde::Serialize;
+
+/// The deno_core release this crate is built against, read from `Cargo.lock` by the build script
+const DENO_CORE_VERSION: &str = env!("RUSTYSCRIPT_DENO_CORE_VERSION");
+
+/// The enabled cargo features, comma-separated, as recorded by the build script
+const ENABLED_FEATURES: &str = env!("RUSTYSCRIPT_FEATURES");
+
+/// Versions and features of the build, as returned by [`build_info`] and [`crate::Runtime::build_info`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    /// The version of rustyscript
+    pub version: &'static str,
+
+    /// The version of `deno_core` the runtime is built on
+    pub deno_core_version: &'static str,
+
+    /// The version of V8 embedded by `deno_core`
+    pub v8_version: &'static str,
+
+    /// Enabled cargo features, such as `web` or `worker` - feature groups like `all_extensions` are
+    /// listed as the features they enable
+    pub features: Vec<&'static str>,
+
+    /// Names of the extensions loaded into a runtime, in load order
+    ///
+    /// From [`build_info`], only the built-in extensions - from [`crate::Runtime::build_info`],
+    /// also those given in [`crate::RuntimeOptions::extensions`]
+    pub extensions: Vec<String>,
+
+    /// True if the runtime was started from a snapshot - always false from [`build_info`]
+    pub snapshot: bool,
+}
+
+/// Returns the versions and features this build of rustyscript was compiled with
+///
+/// ```rust
+/// let info = rustyscript::build_info();
+/// println!("rustyscript {} on V8 {}", info.version, info.v8_version);
+/// assert!(info.extensions.iter().any(|name| name == "rustyscript"));
+/// ```
+#[must_use]
+pub fn build_info() -> BuildInfo {
+    let extensions =
+        crate::ext::all_extensions(vec![], crate::ExtensionOptions::default(), None, false);
+    with_extensions(
+        extensions.iter().map(|e| e.name.to_string()).collect(),
+        false,
+    )
+}
+
+/// [`build_info`], for a runtime created with the given extensions
+pub(crate) fn with_extensions(extensions: Vec<String>, snapshot: bool) -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        deno_core_version: DENO_CORE_VERSION,
+        v8_version: deno_core::v8_version(),
+        features: ENABLED_FEATURES
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .collect(),
+        extensions,
+        snapshot,
+    }
+}
+
+/// The subset of a runtime's [`BuildInfo`] scripts may read, as `rustyscript.buildInfo()`
+///
+/// Extensions and features are left out, so scripts learn nothing of how the host configured them
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScriptBuildInfo {
+    version: &'static str,
+    deno_core_version: &'static str,
+    v8_version: &'static str,
+}
+
+impl From<&BuildInfo> for ScriptBuildInfo {
+    fn from(info: &BuildInfo) -> Self {
+        Self {
+            version: info.version,
+            deno_core_version: info.deno_core_version,
+            v8_version: info.v8_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.features.contains(&"worker"), cfg!(feature = "worker"));
+        assert_eq!(info.extensions[0], "rustyscript");
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let runtime_info = runtime.build_info().unwrap();
+        assert_eq!(runtime_info, info);
+
+        let version: String = runtime.eval("rustyscript.buildInfo().version").unwrap();
+        assert_eq!(version, info.version);
+        let keys: Vec<String> = runtime
+            .eval("Object.keys(rustyscript.buildInfo())")
+            .unwrap();
+        assert_eq!(keys, vec!["version", "denoCoreVersion", "v8Version"]);
+    }
+}
@@ -0,0 +1,213 @@
+//! Cooperative cancellation of calls
+//!
+//! Every call the host makes into the runtime has an `AbortSignal`, read by scripts as
+//! `rustyscript.currentCall.signal` - or passed as the last argument of every function call, with
+//! [`crate::RuntimeOptions::call_signal_argument`].
+//!
+//! The signal is aborted when the call times out, is aborted by the heartbeat handler, or is
+//! cancelled with a [`CallCanceller`]. The runtime then waits for up to
+//! [`crate::RuntimeOptions::cancel_grace`] for the script to clean up before failing the call
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tokio_util::sync::CancellationToken;
+
+/// Cancels the call a runtime is currently running, from any thread - see [`crate::Runtime::canceller`]
+///
+/// ```rust
+/// use rustyscript::{json_args, Error, Module, Runtime};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let module = Module::new(
+///     "test.js",
+///     "
+///     export const wait = () => new Promise((_, reject) => {
+///         rustyscript.currentCall.signal.addEventListener('abort', () => reject('stopped'));
+///     });
+///     ",
+/// );
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let module = runtime.load_module(&module)?;
+///
+/// let canceller = runtime.canceller();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(50));
+///     canceller.cancel();
+/// });
+///
+/// let error = runtime
+///     .call_function::<()>(Some(&module), "wait", json_args!())
+///     .unwrap_err();
+/// assert!(matches!(error, Error::Cancelled(_)));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CallCanceller(Arc<Mutex<CancellationToken>>);
+
+impl CallCanceller {
+    /// Cancels the call the runtime is currently blocked on
+    ///
+    /// Has no effect if the runtime is not running a call
+    pub fn cancel(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .cancel();
+    }
+
+    /// Replace the token for a new call, returning it
+    pub(crate) fn renew(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = token.clone();
+        token
+    }
+}
+
+/// The script-visible signal of the current call
+///
+/// Scripts wait on it with `op_call_signal`, which resolves with the reason for the abort,
+/// or `None` once the call has finished without one
+#[derive(Default)]
+pub(crate) struct CallSignal {
+    id: Cell<u64>,
+    current: RefCell<Rc<SignalState>>,
+}
+
+#[derive(Default)]
+struct SignalState {
+    token: CancellationToken,
+    reason: RefCell<Option<String>>,
+}
+
+impl CallSignal {
+    /// Begin a new call - signals handed out for earlier calls are left behind
+    pub fn begin(&self) {
+        self.id.set(self.id.get() + 1);
+        *self.current.borrow_mut() = Rc::default();
+    }
+
+    /// The id of the current call
+    pub fn id(&self) -> u64 {
+        self.id.get()
+    }
+
+    /// Abort the current call's signal
+    pub fn abort(&self, reason: String) {
+        let current = self.current.borrow();
+        current.reason.borrow_mut().get_or_insert(reason);
+        current.token.cancel();
+    }
+
+    /// Release any script still waiting on the current call's signal
+    pub fn finish(&self) {
+        self.current.borrow().token.cancel();
+    }
+
+    /// Resolves with the reason the call was aborted, or `None` if it finished normally
+    ///
+    /// Resolves immediately with `None` if `id` is not the current call
+    pub fn wait(&self, id: u64) -> impl std::future::Future<Output = Option<String>> + 'static {
+        let current = (id == self.id.get()).then(|| self.current.borrow().clone());
+        async move {
+            let current = current?;
+            current.token.cancelled().await;
+            let reason = current.reason.borrow().clone();
+            reason
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::{json_args, Error, Module, Runtime, RuntimeOptions, Undefined};
+
+    #[test]
+    fn test_call_signal() {
+        let module = Module::new(
+            "test.js",
+            "
+            export let cleanedUp = null;
+            export const isCurrent = (signal) => signal === rustyscript.currentCall.signal && !signal.aborted;
+            export const wait = (signal) => new Promise((resolve) => {
+                signal.addEventListener('abort', () => {
+                    cleanedUp = signal.reason.name;
+                    resolve();
+                });
+            });
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            cancel_grace: Duration::from_secs(5),
+            call_signal_argument: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let current: bool = runtime
+            .call_function(Some(&handle), "isCurrent", json_args!())
+            .unwrap();
+        assert!(current);
+
+        // The script cleans up as soon as the signal is aborted, without using all of its grace period
+        let start = Instant::now();
+        let error = runtime
+            .call_function::<Undefined>(Some(&handle), "wait", json_args!())
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let cleaned_up: String = runtime.get_value(Some(&handle), "cleanedUp").unwrap();
+        assert_eq!(cleaned_up, "TimeoutError");
+
+        // Each call gets a fresh signal
+        let current: bool = runtime
+            .call_function(Some(&handle), "isCurrent", json_args!())
+            .unwrap();
+        assert!(current);
+    }
+
+    #[test]
+    fn test_call_signal_without_grace() {
+        let module = Module::new(
+            "test.js",
+            "
+            export let cleanedUp = false;
+            export const wait = (signal) => new Promise(() => {
+                signal.addEventListener('abort', () => cleanedUp = true);
+            });
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            cancel_grace: Duration::ZERO,
+            call_signal_argument: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        // Replacing the global does not change the signal passed to the function
+        runtime
+            .eval::<Undefined>("globalThis.rustyscript = {}")
+            .unwrap();
+
+        let error = runtime
+            .call_function::<Undefined>(Some(&handle), "wait", json_args!())
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+
+        let cleaned_up: bool = runtime.get_value(Some(&handle), "cleanedUp").unwrap();
+        assert!(cleaned_up);
+    }
+}
@@ -0,0 +1,245 @@
+//! Per-host circuit breaking for outbound network requests
+//!
+//! A [`CircuitBreaker`] counts consecutive failures for each host a script talks to. Once a host
+//! reaches the failure threshold its circuit opens, and requests to it fail at once with
+//! [`Error::CircuitOpen`] - thrown into JS as a `rustyscript.CircuitOpenError` - until the cooldown
+//! has passed. The next request after that is let through as a trial: success closes the circuit
+//! again, failure reopens it for another cooldown.
+//!
+//! Once a runtime has a breaker (see [`crate::RuntimeOptions::circuit_breaker`]), it guards:
+//! - `fetch` - connection failures and `5xx` responses count as failures
+//! - `Deno.connect` and `Deno.connectTls` - failures to connect count as failures
+//!
+//! Aborted requests and refused permissions are not counted either way.
+//!
+//! Clones of a breaker share its state - give the same breaker to several runtimes to protect a
+//! service from all of them at once
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+/// The state of a host's circuit, as reported by [`CircuitBreaker::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are let through - contains the consecutive failures counted so far
+    Closed(u32),
+
+    /// Requests fail at once - contains the time left until a trial request is allowed
+    Open(Duration),
+
+    /// The cooldown has passed - the next request decides whether the circuit closes or reopens
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed(u32),
+    Open(Instant),
+}
+
+/// Short-circuits requests to hosts that keep failing
+///
+/// ```rust
+/// use rustyscript::{CircuitBreaker, CircuitState};
+/// use std::time::Duration;
+///
+/// let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+/// breaker.record_failure("api.internal");
+/// breaker.record_failure("api.internal");
+///
+/// assert!(matches!(breaker.state("api.internal"), CircuitState::Open(_)));
+/// assert!(breaker.check("api.internal").is_err());
+/// assert!(breaker.check("example.com").is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+
+impl Default for CircuitBreaker {
+    /// Opens after 5 consecutive failures, for 30 seconds
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a breaker that opens a host's circuit after `failure_threshold` consecutive
+    /// failures, keeping it open for `cooldown`
+    ///
+    /// A threshold of 0 is treated as 1
+    #[must_use]
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            circuits: Arc::default(),
+        }
+    }
+
+    /// The consecutive failures after which a host's circuit opens
+    #[must_use]
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold
+    }
+
+    /// How long a host's circuit stays open
+    #[must_use]
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Returns the state of a host's circuit
+    #[must_use]
+    pub fn state(&self, host: &str) -> CircuitState {
+        match self.circuits().get(host) {
+            None => CircuitState::Closed(0),
+            Some(Circuit::Closed(failures)) => CircuitState::Closed(*failures),
+            Some(Circuit::Open(until)) => match until.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => CircuitState::Open(remaining),
+                _ => CircuitState::HalfOpen,
+            },
+        }
+    }
+
+    /// Returns the hosts whose circuits are open or half-open
+    #[must_use]
+    pub fn tripped_hosts(&self) -> Vec<String> {
+        self.circuits()
+            .iter()
+            .filter(|(_, circuit)| matches!(circuit, Circuit::Open(_)))
+            .map(|(host, _)| host.clone())
+            .collect()
+    }
+
+    /// Fails if requests to a host should be short-circuited
+    ///
+    /// # Errors
+    /// Will return [`Error::CircuitOpen`] if the host's circuit is open
+    pub fn check(&self, host: &str) -> Result<(), Error> {
+        match self.state(host) {
+            CircuitState::Open(_) => Err(Error::CircuitOpen(host.to_string())),
+            CircuitState::Closed(_) | CircuitState::HalfOpen => Ok(()),
+        }
+    }
+
+    /// Records a successful request, closing the host's circuit
+    pub fn record_success(&self, host: &str) {
+        self.circuits().remove(host);
+    }
+
+    /// Records a failed request, opening the host's circuit if it reaches the threshold
+    ///
+    /// A failure while half-open reopens the circuit at once
+    pub fn record_failure(&self, host: &str) {
+        let mut circuits = self.circuits();
+        let circuit = circuits
+            .entry(host.to_string())
+            .or_insert(Circuit::Closed(0));
+
+        let failures = match circuit {
+            Circuit::Closed(failures) => *failures + 1,
+            Circuit::Open(_) => self.failure_threshold,
+        };
+        *circuit = if failures >= self.failure_threshold {
+            Circuit::Open(Instant::now() + self.cooldown)
+        } else {
+            Circuit::Closed(failures)
+        };
+    }
+
+    /// Closes a host's circuit and forgets its failures
+    pub fn reset(&self, host: &str) {
+        self.record_success(host);
+    }
+
+    /// Closes every circuit
+    pub fn reset_all(&self) {
+        self.circuits().clear();
+    }
+
+    fn circuits(&self) -> MutexGuard<'_, HashMap<String, Circuit>> {
+        self.circuits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(50));
+        let shared = breaker.clone();
+
+        breaker.record_failure("a");
+        breaker.record_failure("a");
+        assert_eq!(breaker.state("a"), CircuitState::Closed(2));
+
+        // A success forgets earlier failures
+        breaker.record_success("a");
+        assert_eq!(breaker.state("a"), CircuitState::Closed(0));
+
+        for _ in 0..3 {
+            shared.record_failure("a");
+        }
+        assert!(matches!(breaker.state("a"), CircuitState::Open(_)));
+        assert!(matches!(breaker.check("a"), Err(Error::CircuitOpen(host)) if host == "a"));
+        assert!(breaker.check("b").is_ok());
+        assert_eq!(breaker.tripped_hosts(), vec!["a".to_string()]);
+
+        // After the cooldown a single failure reopens the circuit
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state("a"), CircuitState::HalfOpen);
+        assert!(breaker.check("a").is_ok());
+        breaker.record_failure("a");
+        assert!(matches!(breaker.state("a"), CircuitState::Open(_)));
+
+        // ... and a success closes it
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.record_success("a");
+        assert_eq!(breaker.state("a"), CircuitState::Closed(0));
+        assert!(breaker.tripped_hosts().is_empty());
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_script_circuit_breaker() {
+        use crate::{Runtime, RuntimeOptions};
+
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let mut runtime = Runtime::new(RuntimeOptions {
+            circuit_breaker: Some(breaker.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Nothing listens on port 9 of the loopback address, so each connection fails
+        let errors: Vec<String> = runtime
+            .eval(
+                "(async () => {
+                    const errors = [];
+                    for (let i = 0; i < 3; i++) {
+                        await fetch('http://127.0.0.1:9/').catch((e) => errors.push(e.name));
+                    }
+                    return errors;
+                })()",
+            )
+            .unwrap();
+        assert_eq!(errors[2], "CircuitOpenError");
+        assert!(matches!(breaker.state("127.0.0.1"), CircuitState::Open(_)));
+
+        let is_class: bool = runtime
+            .eval(
+                "fetch('http://127.0.0.1:9/').catch((e) => e instanceof rustyscript.CircuitOpenError)",
+            )
+            .unwrap();
+        assert!(is_class);
+    }
+}
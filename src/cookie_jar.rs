@@ -0,0 +1,441 @@
+//! A host-controlled cookie store for `fetch`
+//!
+//! Once a runtime has a [`CookieJar`] (see [`crate::RuntimeOptions::cookie_jar`]), fetches that do
+//! not set `credentials: 'omit'`, or a `Cookie` header of their own, send the jar's cookies for
+//! the URL, and cookies set by responses are stored in it. Without a jar, cookies are never kept.
+//!
+//! Clones of a jar share its cookies - the host can seed, inspect or clear them between
+//! invocations, so a pooled runtime does not leak one session into the next.
+//!
+//! Cookies set by redirect responses are not seen - `fetch` follows redirects internally
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use deno_core::url::Url;
+
+/// A cookie, as stored in a [`CookieJar`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    /// The cookie's name
+    pub name: String,
+
+    /// The cookie's value
+    pub value: String,
+
+    /// The host the cookie belongs to, without a leading dot
+    pub domain: String,
+
+    /// If false, the cookie is also sent to subdomains of `domain`
+    pub host_only: bool,
+
+    /// The cookie is only sent to this path, and paths below it
+    pub path: String,
+
+    /// When the cookie expires - `None` keeps it until the jar is cleared
+    pub expires: Option<SystemTime>,
+
+    /// If true, the cookie is only sent over `https`
+    pub secure: bool,
+
+    /// Recorded for the host's benefit - scripts never read cookies directly
+    pub http_only: bool,
+}
+
+impl Cookie {
+    /// A cookie for exactly one host, sent to every path
+    #[must_use]
+    pub fn new(name: impl ToString, value: impl ToString, host: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: host.to_string().trim_start_matches('.').to_lowercase(),
+            host_only: true,
+            path: "/".to_string(),
+            expires: None,
+            secure: false,
+            http_only: false,
+        }
+    }
+
+    /// Also send the cookie to subdomains of its host
+    #[must_use]
+    pub fn with_subdomains(mut self) -> Self {
+        self.host_only = false;
+        self
+    }
+
+    /// Only send the cookie to the given path, and paths below it
+    #[must_use]
+    pub fn with_path(mut self, path: impl ToString) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Expire the cookie at the given time
+    #[must_use]
+    pub fn with_expiry(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Only send the cookie over `https`
+    #[must_use]
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Returns true if the cookie has expired
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= SystemTime::now())
+    }
+
+    /// Returns true if the cookie would be sent with a request to the URL
+    #[must_use]
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        let host = host.to_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+
+        domain_matches
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && !self.is_expired()
+    }
+
+    fn key(&self) -> (String, String, String) {
+        (self.domain.clone(), self.path.clone(), self.name.clone())
+    }
+}
+
+/// A cookie as parsed by the sandbox from a `Set-Cookie` header
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetCookie {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+
+    /// Milliseconds since the unix epoch
+    expires: Option<f64>,
+
+    /// Seconds from now
+    max_age: Option<i64>,
+    secure: bool,
+    http_only: bool,
+}
+
+/// Cookies shared between the host and `fetch`
+///
+/// ```rust
+/// use rustyscript::{Cookie, CookieJar};
+///
+/// let jar = CookieJar::new().with_cookie(Cookie::new("session", "abc123", "example.com"));
+/// let url = "https://example.com/account".parse().unwrap();
+/// assert_eq!(jar.cookie_header(&url), Some("session=abc123".to_string()));
+///
+/// jar.clear();
+/// assert!(jar.cookies().is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    enabled: Arc<AtomicBool>,
+    cookies: Arc<Mutex<HashMap<(String, String, String), Cookie>>>,
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            cookies: Arc::default(),
+        }
+    }
+}
+
+impl CookieJar {
+    /// Create an empty jar
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a cookie, replacing any with the same name, domain and path
+    #[must_use]
+    pub fn with_cookie(self, cookie: Cookie) -> Self {
+        self.insert(cookie);
+        self
+    }
+
+    /// Add a cookie, replacing any with the same name, domain and path
+    ///
+    /// Adding an expired cookie removes the one it would replace
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.lock();
+        if cookie.is_expired() {
+            cookies.remove(&cookie.key());
+        } else {
+            cookies.insert(cookie.key(), cookie);
+        }
+    }
+
+    /// Remove every cookie with the given name and domain
+    pub fn remove(&self, name: &str, domain: &str) {
+        self.lock()
+            .retain(|_, cookie| cookie.name != name || cookie.domain != domain);
+    }
+
+    /// Remove every cookie
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns every unexpired cookie in the jar
+    #[must_use]
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let mut cookies = self.lock();
+        cookies.retain(|_, cookie| !cookie.is_expired());
+        cookies.values().cloned().collect()
+    }
+
+    /// Returns the cookies that would be sent with a request to the URL, most specific path first
+    #[must_use]
+    pub fn cookies_for(&self, url: &Url) -> Vec<Cookie> {
+        let mut cookies: Vec<_> = self
+            .lock()
+            .values()
+            .filter(|cookie| cookie.matches(url))
+            .cloned()
+            .collect();
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        cookies
+    }
+
+    /// Returns the `Cookie` header that would be sent with a request to the URL, if any
+    #[must_use]
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let cookies = self.cookies_for(url);
+        if cookies.is_empty() {
+            return None;
+        }
+
+        let pairs: Vec<_> = cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Stop, or resume, sending and storing cookies - the jar's contents are kept either way
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns true if fetches send and store cookies
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Stores a cookie set by a response from the URL
+    ///
+    /// Cookies for a domain the URL does not belong to are ignored
+    #[cfg_attr(not(feature = "web"), allow(dead_code))]
+    pub(crate) fn store(&self, url: &Url, set: SetCookie) {
+        let Some(host) = url.host_str().map(str::to_lowercase) else {
+            return;
+        };
+
+        let (domain, host_only) = match set.domain.as_deref().map(|d| d.trim_start_matches('.')) {
+            Some(domain) if !domain.is_empty() => (domain.to_lowercase(), false),
+            _ => (host.clone(), true),
+        };
+        if !domain_matches(&host, &domain) {
+            return;
+        }
+
+        let path = match set.path {
+            Some(path) if path.starts_with('/') => path,
+            _ => default_path(url.path()),
+        };
+
+        let expires = match (set.max_age, set.expires) {
+            (Some(max_age), _) => Some(
+                u64::try_from(max_age)
+                    .ok()
+                    .and_then(|secs| SystemTime::now().checked_add(Duration::from_secs(secs)))
+                    .unwrap_or(UNIX_EPOCH),
+            ),
+            (None, Some(millis)) => {
+                let since_epoch =
+                    Duration::try_from_secs_f64(millis.max(0.0) / 1000.0).unwrap_or(Duration::MAX);
+                UNIX_EPOCH.checked_add(since_epoch)
+            }
+            (None, None) => None,
+        };
+
+        self.insert(Cookie {
+            name: set.name,
+            value: set.value,
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: set.secure,
+            http_only: set.http_only,
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<(String, String, String), Cookie>> {
+        self.cookies.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns true if the host is the domain, or one of its subdomains
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Returns true if the request path is the cookie path, or below it
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The path a cookie applies to when the response did not set one - its URL's directory
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => request_path[..i].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        s.parse().unwrap()
+    }
+
+    fn set_cookie(name: &str, domain: Option<&str>, path: Option<&str>) -> SetCookie {
+        SetCookie {
+            name: name.to_string(),
+            value: "1".to_string(),
+            domain: domain.map(str::to_string),
+            path: path.map(str::to_string),
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+        }
+    }
+
+    #[test]
+    fn test_cookie_matching() {
+        let jar = CookieJar::new();
+        let origin = url("https://api.example.com/v1/users");
+
+        jar.store(&origin, set_cookie("host", None, None));
+        jar.store(
+            &origin,
+            set_cookie("shared", Some(".example.com"), Some("/")),
+        );
+        jar.store(&origin, set_cookie("foreign", Some("other.com"), None));
+
+        let names = |u: &str| -> Vec<String> {
+            jar.cookies_for(&url(u))
+                .into_iter()
+                .map(|c| c.name)
+                .collect()
+        };
+        assert_eq!(
+            names("https://api.example.com/v1/users/2"),
+            ["host", "shared"]
+        );
+        assert_eq!(names("https://api.example.com/v2"), ["shared"]);
+        assert_eq!(names("https://www.example.com/"), ["shared"]);
+        assert!(names("https://notexample.com/").is_empty());
+        assert_eq!(jar.cookies().len(), 2);
+
+        // A max-age of 0 deletes the cookie
+        let mut expired = set_cookie("shared", Some("example.com"), Some("/"));
+        expired.max_age = Some(0);
+        jar.store(&origin, expired);
+        assert_eq!(names("https://www.example.com/"), Vec::<String>::new());
+
+        let secure = Cookie::new("token", "x", "example.com").secure();
+        jar.insert(secure);
+        assert_eq!(
+            jar.cookie_header(&url("https://example.com/")),
+            Some("token=x".to_string())
+        );
+        assert_eq!(jar.cookie_header(&url("http://example.com/")), None);
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_fetch_cookies() {
+        use crate::{Runtime, RuntimeOptions};
+
+        let jar = CookieJar::new().with_cookie(Cookie::new("session", "abc", "127.0.0.1"));
+        let mut runtime = Runtime::new(RuntimeOptions {
+            cookie_jar: Some(jar.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Cookies are parsed by the sandbox, then stored against the response's URL
+        runtime
+            .eval::<()>(
+                "const cookie = (name, attributes) => ({
+                    name, value: '1', domain: null, path: null, expires: null, maxAge: null,
+                    secure: false, httpOnly: false, ...attributes,
+                });
+                Deno.core.ops.op_fetch_store_cookies('http://127.0.0.1/app/login', [
+                    cookie('theme', { path: '/', maxAge: 3600 }),
+                    cookie('scoped'),
+                    cookie('bad', { domain: 'example.com' }),
+                ])",
+            )
+            .unwrap();
+
+        let cookies = jar.cookies_for(&url("http://127.0.0.1/app/page"));
+        let names: Vec<_> = cookies.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names.len(), 3, "{names:?}");
+        assert!(!names.contains(&"bad"));
+
+        let header: Option<String> = runtime
+            .eval("Deno.core.ops.op_fetch_cookie_header('http://127.0.0.1/')")
+            .unwrap();
+        assert!(header.unwrap().contains("session=abc"));
+
+        jar.set_enabled(false);
+        let header: Option<String> = runtime
+            .eval("Deno.core.ops.op_fetch_cookie_header('http://127.0.0.1/')")
+            .unwrap();
+        assert_eq!(header, None);
+    }
+}
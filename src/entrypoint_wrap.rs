@@ -0,0 +1,214 @@
+//! Host-side view of functions wrapped with `rustyscript.wrap`
+//!
+//! Scripts opt their entrypoints into consistent handling with
+//! `rustyscript.wrap(handler, { name, schema, timeout })`, which:
+//! - Validates the arguments against `schema` - one JSON Schema per argument, using the same subset
+//!   as [`crate::HostAction::with_schema`]. Missing arguments are checked as `null`, so optional
+//!   ones should allow it, as in `{ type: ['string', 'null'] }`
+//! - Rejects async handlers that take longer than `timeout` milliseconds
+//! - Throws every failure as a `rustyscript.EntrypointError`, which decodes into an [`EntrypointFailure`]
+//! - Records the time taken by every call, read back with [`crate::Runtime::entrypoint_stats`]
+//!
+//! ```rust
+//! use rustyscript::{
+//!     json_args, serde_json::json, EntrypointFailure, EntrypointFailureKind, Module, Runtime,
+//! };
+//!
+//! # fn main() -> Result<(), rustyscript::Error> {
+//! let module = Module::new(
+//!     "test.js",
+//!     "
+//!     const item = { type: 'object', properties: { price: { type: 'number' } } };
+//!     const order = { type: 'object', properties: { items: { type: 'array', items: item } } };
+//!
+//!     export const total = rustyscript.wrap(
+//!         (order) => order.items.reduce((sum, item) => sum + item.price, 0),
+//!         { name: 'total', schema: [order] },
+//!     );
+//!     ",
+//! );
+//! let mut runtime = Runtime::new(Default::default())?;
+//! let module = runtime.load_module(&module)?;
+//!
+//! let order = json!({ "items": [{ "price": 1 }, { "price": "2" }] });
+//! let error = runtime
+//!     .call_function::<f64>(Some(&module), "total", json_args!(order))
+//!     .unwrap_err();
+//! let failure: EntrypointFailure = error.try_downcast_thrown().unwrap();
+//! assert_eq!(failure.kind, EntrypointFailureKind::Validation);
+//! assert_eq!(failure.path.as_deref(), Some("args/0/items/1/price"));
+//! # Ok(())
+//! # }
+//! ```
+use std::{collections::HashMap, time::Duration};
+
+use serde::Deserialize;
+
+/// Timings of the calls made to a wrapped function - see [`crate::Runtime::entrypoint_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntrypointStats {
+    /// Calls made, including failed ones
+    pub calls: u64,
+
+    /// Calls that failed validation, timed out, or threw
+    pub failures: u64,
+
+    /// Time spent in all calls
+    pub total: Duration,
+
+    /// Time spent in the slowest call
+    pub max: Duration,
+}
+
+impl EntrypointStats {
+    /// The average time spent in a call
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total / calls,
+            #[allow(clippy::cast_precision_loss)]
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.calls as f64),
+        }
+    }
+}
+
+/// Why a wrapped function failed - see [`EntrypointFailure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntrypointFailureKind {
+    /// An argument did not match the function's schema
+    Validation,
+
+    /// The function's promise did not settle within its timeout
+    Timeout,
+
+    /// The function threw, or its promise rejected
+    Error,
+}
+
+/// A failure thrown by a function wrapped with `rustyscript.wrap`
+///
+/// Decode one from the error a call returned with [`crate::Error::try_downcast_thrown`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EntrypointFailure {
+    /// The wrapped function's name
+    pub entrypoint: String,
+
+    /// Why the call failed
+    pub kind: EntrypointFailureKind,
+
+    /// A description of the failure, prefixed with the function's name
+    pub message: String,
+
+    /// For validation failures, the path to the offending argument, such as `args/0/items/1/price`
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Time spent in the call before it failed
+    #[serde(with = "crate::serde_adapters::duration")]
+    pub elapsed: Duration,
+}
+
+/// The most wrapped functions timed by a runtime - calls to any others are not recorded
+///
+/// Scripts choose the names, so without a cap they could grow the table without bound
+pub(crate) const MAX_ENTRYPOINTS: usize = 256;
+
+/// Timings of every wrapped function called so far, by name
+#[derive(Default)]
+pub(crate) struct EntrypointTimings(pub HashMap<String, EntrypointStats>);
+
+impl EntrypointTimings {
+    pub fn record(&mut self, name: &str, elapsed: f64, success: bool) {
+        if self.0.len() >= MAX_ENTRYPOINTS && !self.0.contains_key(name) {
+            return;
+        }
+
+        let elapsed = Duration::try_from_secs_f64(elapsed / 1000.0).unwrap_or_default();
+        let stats = self.0.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        if !success {
+            stats.failures += 1;
+        }
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Error, Module, Runtime, RuntimeOptions};
+
+    fn failure(error: &Error) -> EntrypointFailure {
+        error.try_downcast_thrown().expect("not an EntrypointError")
+    }
+
+    #[test]
+    fn test_wrapped_entrypoints() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const double = rustyscript.wrap((n) => n * 2, { name: 'double', schema: [{ type: 'integer' }] });
+            export const hang = rustyscript.wrap(() => new Promise(() => {}), { name: 'hang', timeout: 10 });
+            export const fail = rustyscript.wrap(async () => { throw new Error('oops'); }, { name: 'fail' });
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let value: i64 = runtime
+            .call_function(Some(&handle), "double", json_args!(21))
+            .unwrap();
+        assert_eq!(value, 42);
+
+        let error = runtime
+            .call_function::<i64>(Some(&handle), "double", json_args!("21"))
+            .unwrap_err();
+        let invalid = failure(&error);
+        assert_eq!(invalid.entrypoint, "double");
+        assert_eq!(invalid.kind, EntrypointFailureKind::Validation);
+        assert_eq!(invalid.path.as_deref(), Some("args/0"));
+        assert_eq!(invalid.message, "double: args/0: expected integer");
+
+        let error = runtime
+            .call_function::<()>(Some(&handle), "hang", json_args!())
+            .unwrap_err();
+        assert_eq!(failure(&error).kind, EntrypointFailureKind::Timeout);
+
+        let error = runtime
+            .call_function::<()>(Some(&handle), "fail", json_args!())
+            .unwrap_err();
+        let failed = failure(&error);
+        assert_eq!(failed.kind, EntrypointFailureKind::Error);
+        assert_eq!(failed.message, "fail: oops");
+
+        let stats = runtime.entrypoint_stats().unwrap();
+        assert_eq!(stats["double"].calls, 2);
+        assert_eq!(stats["double"].failures, 1);
+        assert_eq!(stats["hang"].failures, 1);
+        assert_eq!(stats["hang"].calls, 1);
+        assert_eq!(stats["fail"].failures, 1);
+    }
+
+    #[test]
+    fn test_entrypoint_cap() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime
+            .eval::<()>(format!(
+                "for (let i = 0; i < {}; i++) rustyscript.wrap(() => i, {{ name: `f${{i}}` }})();",
+                MAX_ENTRYPOINTS + 10
+            ))
+            .unwrap();
+        runtime
+            .eval::<()>("rustyscript.wrap(() => 0, { name: 'f0' })()")
+            .unwrap();
+
+        let stats = runtime.entrypoint_stats().unwrap();
+        assert_eq!(stats.len(), MAX_ENTRYPOINTS);
+        assert_eq!(stats["f0"].calls, 2);
+        assert!(!stats.contains_key(&format!("f{MAX_ENTRYPOINTS}")));
+    }
+}
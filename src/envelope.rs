@@ -0,0 +1,190 @@
+//! The `{ ok: value }` / `{ err: error }` result convention - see [`Envelope`]
+use deno_core::serde_json;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Error, RsFunction};
+
+/// A result passed between rust and JS as `{ ok: value }` or `{ err: error }`
+///
+/// Lets a script report an expected failure without throwing, and the host read it as a
+/// `Result<T, E>` - deserialize a function's return value into an `Envelope`, then call
+/// [`Envelope::into_result`]. Going the other way, [`Envelope::function`] turns a rust
+/// function returning a `Result` into one that returns an envelope to the script.
+///
+/// An object with neither key, or both, fails to deserialize
+///
+/// ```rust
+/// use rustyscript::{json_args, Envelope, Module, Runtime};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_function(
+///     "parse",
+///     Envelope::function(|args| match args[0].as_str().map(str::parse::<u32>) {
+///         Some(Ok(n)) => Ok(n),
+///         _ => Err("not a number"),
+///     }),
+/// )?;
+///
+/// let module = Module::new(
+///     "test.js",
+///     "
+///     export function double(s) {
+///         const parsed = rustyscript.functions.parse(s);
+///         return 'ok' in parsed ? { ok: parsed.ok * 2 } : parsed;
+///     }
+///     ",
+/// );
+/// let module = runtime.load_module(&module)?;
+///
+/// let result: Envelope<u32, String> = runtime.call_function(Some(&module), "double", json_args!("21"))?;
+/// assert_eq!(result.into_result(), Ok(42));
+///
+/// let result: Envelope<u32, String> = runtime.call_function(Some(&module), "double", json_args!("x"))?;
+/// assert_eq!(result.into_result(), Err("not a number".to_string()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Envelope<T, E> {
+    /// Encoded as `{ ok: value }`
+    Ok(T),
+
+    /// Encoded as `{ err: error }`
+    Err(E),
+}
+
+impl<T, E> Envelope<T, E> {
+    /// Converts the envelope into the result it represents
+    ///
+    /// # Errors
+    /// Returns the envelope's `err` value
+    pub fn into_result(self) -> Result<T, E> {
+        self.into()
+    }
+
+    /// Returns true if the envelope holds an `ok` value
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok(_))
+    }
+}
+
+impl<T, E> Envelope<T, E>
+where
+    T: Serialize,
+    E: Serialize,
+{
+    /// Wraps a function returning a `Result`, for [`crate::Runtime::register_function`], so that
+    /// it returns an envelope to the script instead of throwing on failure
+    ///
+    /// Errors from converting the envelope to JSON are still thrown
+    pub fn function<F>(callback: F) -> impl RsFunction
+    where
+        F: Fn(&[serde_json::Value]) -> Result<T, E> + 'static,
+    {
+        move |args: &[serde_json::Value]| -> Result<serde_json::Value, Error> {
+            Ok(serde_json::to_value(Self::from(callback(args)))?)
+        }
+    }
+}
+
+impl<T, E> Envelope<T, E>
+where
+    T: DeserializeOwned,
+    E: DeserializeOwned,
+{
+    /// Reads an envelope from a JSON value, such as an argument to a registered function
+    ///
+    /// # Errors
+    /// Will return an error if the value is not an envelope, or its contents do not fit `T` or `E`
+    pub fn from_value(value: serde_json::Value) -> Result<Self, Error> {
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl<T, E> From<Result<T, E>> for Envelope<T, E> {
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Ok(value) => Self::Ok(value),
+            Err(error) => Self::Err(error),
+        }
+    }
+}
+
+impl<T, E> From<Envelope<T, E>> for Result<T, E> {
+    fn from(envelope: Envelope<T, E>) -> Self {
+        match envelope {
+            Envelope::Ok(value) => Ok(value),
+            Envelope::Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Declined {
+        reason: String,
+    }
+
+    #[test]
+    fn test_envelope() {
+        let ok: Envelope<u32, Declined> =
+            Envelope::from_value(serde_json::json!({ "ok": 5 })).unwrap();
+        assert_eq!(ok.into_result(), Ok(5));
+
+        let err: Envelope<u32, Declined> =
+            Envelope::from_value(serde_json::json!({ "err": { "reason": "limit" } })).unwrap();
+        assert_eq!(
+            err.into_result(),
+            Err(Declined {
+                reason: "limit".to_string()
+            })
+        );
+
+        assert!(Envelope::<u32, Declined>::from_value(serde_json::json!({})).is_err());
+        assert!(Envelope::<u32, Declined>::from_value(serde_json::json!(5)).is_err());
+
+        let encoded = serde_json::to_value(Envelope::<u32, Declined>::from(Ok(1))).unwrap();
+        assert_eq!(encoded, serde_json::json!({ "ok": 1 }));
+    }
+
+    #[test]
+    fn test_envelope_function() {
+        let mut runtime = Runtime::new(Default::default()).unwrap();
+        runtime
+            .register_function(
+                "charge",
+                Envelope::function(
+                    |args| match args.first().and_then(serde_json::Value::as_u64) {
+                        Some(n) if n <= 100 => Ok(n),
+                        _ => Err(Declined {
+                            reason: "limit".to_string(),
+                        }),
+                    },
+                ),
+            )
+            .unwrap();
+
+        let module = Module::new(
+            "test.js",
+            "export const charge = (n) => rustyscript.functions.charge(n);",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        let result: Envelope<u64, Declined> = runtime
+            .call_function(Some(&module), "charge", json_args!(50))
+            .unwrap();
+        assert!(result.is_ok());
+
+        let result: Envelope<u64, Declined> = runtime
+            .call_function(Some(&module), "charge", json_args!(500))
+            .unwrap();
+        assert_eq!(result.into_result().unwrap_err().reason, "limit");
+    }
+}
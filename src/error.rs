@@ -1,8 +1,8 @@
 //! Contains the error type for the runtime
 //! And some associated utilities
-use std::path::PathBuf;
+use std::{borrow::Cow, collections::HashMap, path::PathBuf};
 
-use deno_core::error::CoreErrorKind;
+use deno_core::{error::CoreErrorKind, serde_json};
 use thiserror::Error;
 
 use crate::Module;
@@ -70,50 +70,465 @@ pub enum Error {
     #[error("value could not be deserialized: {0}")]
     JsonDecode(String),
 
+    /// Triggers when part of a value has the wrong type for the requested rust type
+    ///
+    /// Contains the path to the offending part of the value (`.items[3].price`),
+    /// the type that was expected there, and the type that was found
+    #[class(generic)]
+    #[error("expected {1} at {0}, found {2}")]
+    TypeMismatch(String, String, String),
+
     /// Triggers when a module could not be loaded from the filesystem
     #[class(generic)]
     #[error("{0}")]
     ModuleNotFound(String),
 
+    /// Triggers when an I/O operation fails for any reason other than a missing file,
+    /// such as a reset connection or a denied permission
+    ///
+    /// Contains the kind of failure, and its message
+    #[class(generic)]
+    #[error("{1}")]
+    Io(#[serde(with = "io_error_kind")] std::io::ErrorKind, String),
+
     /// Triggers when attempting to use a worker that has already been shutdown
     #[class(generic)]
     #[error("This worker has been destroyed")]
     WorkerHasStopped,
 
+    /// Triggers when a worker's queue is full, and the query was not queued
+    ///
+    /// Contains the number of queries already waiting - see [`crate::worker::WorkerQueueOptions`]
+    #[class(generic)]
+    #[error("Worker is overloaded: {0} queries already queued")]
+    Overloaded(usize),
+
+    /// Triggers when an operation is refused by a [`crate::RateLimiter`]
+    ///
+    /// Contains the key whose bucket was empty
+    #[class(generic)]
+    #[error("Rate limit exceeded for `{0}`")]
+    RateLimited(String),
+
+    /// Triggers when a request is short-circuited by a [`crate::CircuitBreaker`]
+    ///
+    /// Contains the host whose circuit is open - thrown into JS as a `rustyscript.CircuitOpenError`
+    #[class("CircuitOpenError")]
+    #[error("Circuit open for `{0}`: too many recent failures")]
+    CircuitOpen(String),
+
+    /// Triggers when a call exceeds the quota of its module handle - see [`crate::CallQuota`]
+    #[class(generic)]
+    #[error("Call quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     /// Triggers on runtime issues during execution of a module
     #[class(generic)]
     #[error("{0}")]
     Runtime(String),
 
     /// Runtime error we successfully downcast
+    ///
+    /// Derefs to the underlying [`deno_core::error::JsError`] - see [`ScriptError`]
     #[class(generic)]
     #[error("{0}")]
-    JsError(Box<deno_core::error::JsError>),
+    JsError(Box<ScriptError>),
 
     /// Triggers when a module times out before finishing
     #[class(generic)]
     #[error("Module timed out: {0}")]
     Timeout(String),
 
+    /// Triggers when the host cancels a call with a [`crate::CallCanceller`]
+    #[class(generic)]
+    #[error("Call cancelled: {0}")]
+    Cancelled(String),
+
+    /// Triggers when a script exceeds the stack limit (see `RuntimeOptions::stack_size`)
+    ///
+    /// Unlike heap exhaustion this is recoverable - the runtime can continue to be used
+    #[class(generic)]
+    #[error("Stack overflow: {0}")]
+    StackOverflow(Box<deno_core::error::JsError>),
+
     /// Triggers when the heap (via `max_heap_size`) is exhausted during execution
     #[class(generic)]
     #[error("Heap exhausted")]
     HeapExhausted,
+
+    /// Triggers when the child process behind a `sandbox_process::ProcessRuntime` has exited
+    ///
+    /// Contains a description of how it exited, such as its exit status or signal
+    #[class(generic)]
+    #[error("Sandbox process exited: {0}")]
+    ProcessExited(String),
+
+    /// Triggers when a module exceeds the limits attached with [`crate::Module::with_budget`]
+    ///
+    /// Contains the module's filename, and the limit that was exceeded
+    #[class(generic)]
+    #[error("{0} exceeded its budget: {1}")]
+    ModuleBudgetExceeded(String, String),
+
+    /// Triggers when loading a module would exceed the runtime's [`crate::ModuleLimits`]
+    ///
+    /// Contains the limit, and the specifier of the module that was refused
+    #[class(generic)]
+    #[error("{1} exceeds the runtime's {0}")]
+    ModuleLimitExceeded(crate::ModuleLimit, String),
+
+    /// Triggers when a script schedules a timer beyond the runtime's [`crate::TimerLimits`]
+    ///
+    /// Contains the limit, and a description of the timer that was refused
+    #[class("TimerLimitError")]
+    #[error("{1} exceeds the runtime's {0}")]
+    TimerLimitExceeded(crate::TimerLimit, String),
+
+    /// Triggers when a script stops the runtime with `Deno.exit(code)`
+    ///
+    /// The runtime should not be used after this - see [`crate::Runtime::exit_code`]
+    #[class(generic)]
+    #[error("Script exited with code {0}")]
+    Exit(i32),
+
+    /// Triggers when a stored value is used with a runtime other than the one it was created in
+    #[class(generic)]
+    #[error("value belongs to runtime {0}, but was used with runtime {1}")]
+    WrongRuntime(crate::RuntimeId, crate::RuntimeId),
+
+    /// Triggers when a warm-up script fails - see [`crate::RuntimeOptions::warmup_scripts`]
+    ///
+    /// Contains the script's filename, and the error it raised
+    #[class(generic)]
+    #[error("Warm-up script {0} failed: {1}")]
+    Warmup(String, Box<Error>),
+
+    /// An error returned by a registered function, to be thrown into JS as a specific class
+    ///
+    /// See [`HostError`] and [`IntoJsError`]
+    #[class(inherit)]
+    #[error("{0}")]
+    Host(HostError),
+}
+
+/// An exception thrown by a script, as carried by [`Error::JsError`]
+///
+/// Derefs to `deno_core`'s [`deno_core::error::JsError`], adding the value the script threw
+/// (see [`Error::thrown_value`]) and the labels of the runtime it was thrown in (see [`Error::labels`])
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScriptError {
+    error: deno_core::error::JsError,
+    thrown: Option<serde_json::Value>,
+    labels: HashMap<String, String>,
+}
+
+impl ScriptError {
+    /// Consumes the exception, returning `deno_core`'s representation of it
+    #[must_use]
+    pub fn into_inner(self) -> deno_core::error::JsError {
+        self.error
+    }
+}
+
+impl From<deno_core::error::JsError> for ScriptError {
+    fn from(error: deno_core::error::JsError) -> Self {
+        Self {
+            error,
+            thrown: None,
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl std::ops::Deref for ScriptError {
+    type Target = deno_core::error::JsError;
+    fn deref(&self) -> &Self::Target {
+        &self.error
+    }
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T: IntoJsError> From<T> for Error {
+    fn from(err: T) -> Self {
+        Error::Host(err.into_js_error())
+    }
+}
+
+/// An error thrown into JS as a chosen class, so scripts can `catch` and branch on its type
+///
+/// Return one from a registered function; the built-in classes (`TypeError`, `RangeError`, ...)
+/// are thrown as-is, and [`HostError::new`] throws a `rustyscript.HostError`.  
+/// A `code`, if set, becomes a property of the thrown error:
+///
+/// ```rust
+/// use rustyscript::{json_args, HostError, Module, Runtime};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_function("charge", |_| {
+///     Err(HostError::new("Card declined").with_code("DECLINED").into())
+/// })?;
+///
+/// let module = Module::new(
+///     "test.js",
+///     "
+///     export function pay() {
+///         try {
+///             rustyscript.functions.charge();
+///         } catch (e) {
+///             if (e instanceof rustyscript.HostError) return e.code;
+///             throw e;
+///         }
+///     }
+///     ",
+/// );
+/// let module = runtime.load_module(&module)?;
+/// let code: String = runtime.call_function(Some(&module), "pay", json_args!())?;
+/// assert_eq!(code, "DECLINED");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HostError {
+    class: String,
+    message: String,
+    code: Option<String>,
+}
+
+impl HostError {
+    /// An error thrown as a `rustyscript.HostError`
+    #[must_use]
+    pub fn new(message: impl ToString) -> Self {
+        Self::with_class("HostError", message)
+    }
+
+    /// An error thrown as a plain `Error`
+    #[must_use]
+    pub fn error(message: impl ToString) -> Self {
+        Self::with_class("Error", message)
+    }
+
+    /// An error thrown as a `TypeError`
+    #[must_use]
+    pub fn type_error(message: impl ToString) -> Self {
+        Self::with_class("TypeError", message)
+    }
+
+    /// An error thrown as a `RangeError`
+    #[must_use]
+    pub fn range_error(message: impl ToString) -> Self {
+        Self::with_class("RangeError", message)
+    }
+
+    /// An error thrown as a `SyntaxError`
+    #[must_use]
+    pub fn syntax_error(message: impl ToString) -> Self {
+        Self::with_class("SyntaxError", message)
+    }
+
+    /// An error thrown as a `ReferenceError`
+    #[must_use]
+    pub fn reference_error(message: impl ToString) -> Self {
+        Self::with_class("ReferenceError", message)
+    }
+
+    fn with_class(class: &str, message: impl ToString) -> Self {
+        Self {
+            class: class.to_string(),
+            message: message.to_string(),
+            code: None,
+        }
+    }
+
+    /// Sets the `code` property of the thrown error
+    #[must_use]
+    pub fn with_code(mut self, code: impl ToString) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// The name of the JS class the error is thrown as
+    #[must_use]
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// The error's message
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The `code` property of the thrown error, if any
+    #[must_use]
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+}
+
+impl std::fmt::Display for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{}: {} [{code}]", self.class, self.message),
+            None => write!(f, "{}: {}", self.class, self.message),
+        }
+    }
+}
+
+impl std::error::Error for HostError {}
+
+impl deno_error::JsErrorClass for HostError {
+    fn get_class(&self) -> Cow<'static, str> {
+        Cow::Owned(self.class.clone())
+    }
+
+    fn get_message(&self) -> Cow<'static, str> {
+        Cow::Owned(self.message.clone())
+    }
+
+    fn get_additional_properties(&self) -> deno_error::AdditionalProperties {
+        let code = self.code.clone().map(|code| {
+            (
+                Cow::Borrowed("code"),
+                deno_error::PropertyValue::String(code.into()),
+            )
+        });
+        Box::new(code.into_iter())
+    }
+
+    fn get_ref(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+}
+
+/// Converts a host error type into the JS error it should be thrown as
+///
+/// Implementing it lets registered functions return the type with `?`, through
+/// `From<T> for Error`, and have scripts see a [`HostError`] with a meaningful class and code
+pub trait IntoJsError {
+    /// The JS error this should be thrown as
+    fn into_js_error(self) -> HostError;
+}
+
+impl IntoJsError for HostError {
+    fn into_js_error(self) -> HostError {
+        self
+    }
 }
 
 impl From<deno_core::error::JsError> for Error {
     fn from(err: deno_core::error::JsError) -> Self {
-        Self::JsError(Box::new(err))
+        Box::new(err).into()
     }
 }
 
 impl From<Box<deno_core::error::JsError>> for Error {
     fn from(err: Box<deno_core::error::JsError>) -> Self {
-        Self::JsError(err)
+        // V8 reports stack overflows as an ordinary RangeError
+        let is_overflow = err.name.as_deref() == Some("RangeError")
+            && err.message.as_deref() == Some("Maximum call stack size exceeded");
+        if is_overflow {
+            Self::StackOverflow(err)
+        } else {
+            Self::JsError(Box::new(ScriptError::from(*err)))
+        }
     }
 }
 
 impl Error {
+    /// Returns the labels of the runtime a script threw this error in, if any were configured
+    ///
+    /// Only script exceptions ([`Error::JsError`]) carry labels - the host can find those of
+    /// the runtime behind any other error with [`crate::Runtime::labels`]
+    #[must_use]
+    pub fn labels(&self) -> Option<&HashMap<String, String>> {
+        match self {
+            Error::JsError(e) if !e.labels.is_empty() => Some(&e.labels),
+            _ => None,
+        }
+    }
+
+    /// Returns the value a script threw to cause this error, if it was preserved
+    ///
+    /// Values are preserved when a function called from rust throws, or the promise it returns
+    /// rejects, with anything other than a plain `Error`. For `Error` objects, the value holds
+    /// their own enumerable fields, along with their `name` and `message`.  
+    /// Errors carrying a value are always [`Error::JsError`]
+    #[must_use]
+    pub fn thrown_value(&self) -> Option<&serde_json::Value> {
+        match self {
+            Error::JsError(e) => e.thrown.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Deserializes the value a script threw to cause this error into a host type
+    ///
+    /// Returns `None` if no value was preserved (see [`Error::thrown_value`]), or it does not fit `T`
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Module, Runtime};
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct PaymentError {
+    ///     code: u32,
+    /// }
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new(
+    ///     "test.js",
+    ///     "
+    ///     class PaymentError extends Error {
+    ///         constructor({ code }) { super('Payment failed'); this.code = code; }
+    ///     }
+    ///     export function pay() { throw new PaymentError({ code: 42 }); }
+    ///     ",
+    /// );
+    /// let module = runtime.load_module(&module)?;
+    /// let e = runtime
+    ///     .call_function::<()>(Some(&module), "pay", json_args!())
+    ///     .unwrap_err();
+    /// let payment: PaymentError = e.try_downcast_thrown().unwrap();
+    /// assert_eq!(payment.code, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn try_downcast_thrown<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+        let value = self.thrown_value()?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Attach a set of labels to the error
+    /// Does nothing if the error is not a script exception, or is already labeled
+    pub(crate) fn with_labels(self, labels: &HashMap<String, String>) -> Self {
+        match self {
+            Error::JsError(mut e) if e.labels.is_empty() => {
+                e.labels.clone_from(labels);
+                Error::JsError(e)
+            }
+            e => e,
+        }
+    }
+
+    /// Attach the value a script threw to the error it caused
+    /// Does nothing if the error is not a script exception
+    pub(crate) fn with_thrown(self, value: serde_json::Value) -> Self {
+        match self {
+            Error::JsError(mut e) => {
+                e.thrown = Some(value);
+                Error::JsError(e)
+            }
+            e => e,
+        }
+    }
+
     /// Formats an error for display in a terminal
     /// If the error is a `JsError`, it will attempt to highlight the source line
     /// in this format:
@@ -126,7 +541,12 @@ impl Error {
     /// Otherwise, it will just display the error message normally
     #[must_use]
     pub fn as_highlighted(&self, options: ErrorFormattingOptions) -> String {
-        let mut e = if let Error::JsError(e) = self {
+        let source = match self {
+            Error::JsError(e) => Some(&e.error),
+            Error::StackOverflow(e) => Some(&**e),
+            _ => None,
+        };
+        let mut e = if let Some(e) = source {
             // Extract basic information about position
             let (filename, row, col) = match e.frames.first() {
                 Some(f) => (
@@ -221,6 +641,52 @@ impl Error {
     }
 }
 
+/// Serializes the [`std::io::ErrorKind`] of [`Error::Io`] by name
+mod io_error_kind {
+    use std::io::ErrorKind;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Kinds that survive a round-trip - any other deserializes as `Other`
+    const KINDS: &[ErrorKind] = &[
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::ConnectionRefused,
+        ErrorKind::ConnectionReset,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::HostUnreachable,
+        ErrorKind::NetworkUnreachable,
+        ErrorKind::NotConnected,
+        ErrorKind::AddrInUse,
+        ErrorKind::AddrNotAvailable,
+        ErrorKind::NetworkDown,
+        ErrorKind::BrokenPipe,
+        ErrorKind::AlreadyExists,
+        ErrorKind::WouldBlock,
+        ErrorKind::InvalidInput,
+        ErrorKind::InvalidData,
+        ErrorKind::TimedOut,
+        ErrorKind::WriteZero,
+        ErrorKind::Interrupted,
+        ErrorKind::Unsupported,
+        ErrorKind::UnexpectedEof,
+        ErrorKind::OutOfMemory,
+    ];
+
+    pub fn serialize<S: Serializer>(kind: &ErrorKind, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{kind:?}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ErrorKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(KINDS
+            .iter()
+            .copied()
+            .find(|kind| format!("{kind:?}") == name)
+            .unwrap_or(ErrorKind::Other))
+    }
+}
+
 #[macro_use]
 mod error_macro {
     /// Maps one error type to another
@@ -245,12 +711,16 @@ map_error!(deno_ast::TranspileError, |e| Error::Runtime(e.to_string()));
 map_error!(deno_core::error::CoreError, |e| {
     let e = e.into_kind();
     match e {
-        CoreErrorKind::Js(js_error) => Error::JsError(js_error),
+        CoreErrorKind::Js(js_error) => Error::from(js_error),
         _ => Error::Runtime(e.to_string()),
     }
 });
+map_error!(std::cell::BorrowError, |e| Error::Runtime(e.to_string()));
 map_error!(std::cell::BorrowMutError, |e| Error::Runtime(e.to_string()));
-map_error!(std::io::Error, |e| Error::ModuleNotFound(e.to_string()));
+map_error!(std::io::Error, |e| match e.kind() {
+    std::io::ErrorKind::NotFound => Error::ModuleNotFound(e.to_string()),
+    kind => Error::Io(kind, e.to_string()),
+});
 map_error!(deno_core::v8::DataError, |e| Error::Runtime(e.to_string()));
 map_error!(deno_core::ModuleResolutionError, |e| Error::Runtime(
     e.to_string()
@@ -265,6 +735,23 @@ map_error!(deno_core::serde_v8::Error, |e| Error::JsonDecode(
     e.to_string()
 ));
 
+#[cfg(feature = "codecs")]
+map_error!(rmp_serde::encode::Error, |e| Error::JsonDecode(
+    e.to_string()
+));
+#[cfg(feature = "codecs")]
+map_error!(rmp_serde::decode::Error, |e| Error::JsonDecode(
+    e.to_string()
+));
+#[cfg(feature = "codecs")]
+map_error!(ciborium::ser::Error<std::io::Error>, |e| Error::JsonDecode(
+    e.to_string()
+));
+#[cfg(feature = "codecs")]
+map_error!(ciborium::de::Error<std::io::Error>, |e| Error::JsonDecode(
+    e.to_string()
+));
+
 map_error!(deno_core::anyhow::Error, |e| {
     Error::Runtime(e.to_string())
 });
@@ -323,4 +810,66 @@ mod test {
             "= Uncaught (in promise) ReferenceError: x is not defined"
         ));
     }
+
+    #[test]
+    fn test_labels() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            labels: [("tenant".to_string(), "acme".to_string())].into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let e = runtime.eval::<Undefined>("1 + x").unwrap_err();
+        assert_eq!(e.labels().unwrap().get("tenant").unwrap(), "acme");
+        assert!(matches!(e, crate::Error::JsError(_)));
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let e = runtime.eval::<Undefined>("1 + x").unwrap_err();
+        assert!(e.labels().is_none());
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        const RECURSE: &str = "globalThis.depth = (n) => n === 0 ? 0 : 1 + depth(n - 1);";
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            stack_size: Some(256 * 1024),
+            ..Default::default()
+        })
+        .unwrap();
+        runtime.eval::<Undefined>(RECURSE).unwrap();
+
+        let e = runtime.eval::<usize>("depth(1e6)").unwrap_err();
+        assert!(matches!(e, crate::Error::StackOverflow(_)), "{e}");
+
+        // Catchable from JS, and the runtime remains usable
+        let caught: bool = runtime
+            .eval("try { depth(1e6); false } catch (e) { e instanceof RangeError }")
+            .unwrap();
+        assert!(caught);
+        assert_eq!(runtime.eval::<usize>("depth(100)").unwrap(), 100);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_stack_size_beyond_thread_stack() {
+        // Far more than the thread has - clamped, so the overflow is still caught
+        let handle = std::thread::Builder::new()
+            .stack_size(4 * 1024 * 1024)
+            .spawn(|| {
+                let mut runtime = Runtime::new(RuntimeOptions {
+                    stack_size: Some(1024 * 1024 * 1024),
+                    ..Default::default()
+                })
+                .unwrap();
+                runtime
+                    .eval::<Undefined>("globalThis.depth = (n) => n === 0 ? 0 : 1 + depth(n - 1);")
+                    .unwrap();
+                runtime.eval::<usize>("depth(1e7)").unwrap_err()
+            })
+            .unwrap();
+
+        let e = handle.join().unwrap();
+        assert!(matches!(e, crate::Error::StackOverflow(_)), "{e}");
+    }
 }
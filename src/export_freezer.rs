@@ -0,0 +1,223 @@
+//! Deep-freezing of module exports - see [`crate::RuntimeOptions::freeze_exports`]
+use std::collections::HashMap;
+
+use deno_core::{v8, JsRuntime, ModuleId};
+
+use crate::Error;
+
+const OWN_KEYS: v8::GetPropertyNamesArgs = v8::GetPropertyNamesArgs {
+    mode: v8::KeyCollectionMode::OwnOnly,
+    property_filter: v8::PropertyFilter::ALL_PROPERTIES,
+    index_filter: v8::IndexFilter::IncludeIndices,
+    key_conversion: v8::KeyConversionMode::ConvertToString,
+};
+
+/// Freezes the values exported by a module, and every object reachable through their properties
+///
+/// Built-ins are left alone, so that exporting `Math` or `Object` does not freeze them for every
+/// other script. They are recorded when the runtime is created - before any user code runs, so a
+/// script cannot exempt its exports by attaching them to `globalThis`
+pub(crate) struct ExportFreezer {
+    builtins: Vec<v8::Global<v8::Object>>,
+}
+
+impl ExportFreezer {
+    /// Records the current properties of `globalThis`, and their prototypes, as built-ins
+    pub fn new(runtime: &mut JsRuntime) -> Self {
+        deno_core::scope!(scope, runtime);
+        let global = scope.get_current_context().global(scope);
+        let mut builtins = vec![v8::Global::new(scope, global)];
+
+        for value in own_values(scope, global) {
+            let Ok(object) = v8::Local::<v8::Object>::try_from(value) else {
+                continue;
+            };
+            builtins.push(v8::Global::new(scope, object));
+
+            if let Some(prototype) = v8::String::new(scope, "prototype")
+                .and_then(|key| object.get_own_property_descriptor(scope, key.into()))
+                .and_then(|descriptor| descriptor_field(scope, descriptor, "value"))
+                .and_then(|value| v8::Local::<v8::Object>::try_from(value).ok())
+            {
+                builtins.push(v8::Global::new(scope, prototype));
+            }
+        }
+
+        Self { builtins }
+    }
+
+    /// Freezes everything reachable from the module's exports
+    ///
+    /// Proxies, module namespaces and non-empty typed arrays cannot be frozen without running
+    /// script code, or failing - they are left as they are
+    pub fn freeze(&self, runtime: &mut JsRuntime, module_id: ModuleId) -> Result<(), Error> {
+        let namespace = runtime.get_module_namespace(module_id)?;
+        deno_core::scope!(scope, runtime);
+        v8::tc_scope!(let scope, scope);
+
+        let mut seen = HashMap::new();
+        for builtin in &self.builtins {
+            mark(&mut seen, v8::Local::new(scope, builtin));
+        }
+
+        // The namespace itself is already immutable - start from its exports
+        let namespace = v8::Local::new(scope, namespace);
+        let mut pending = vec![];
+        if let Some(keys) = namespace.get_own_property_names(scope, OWN_KEYS) {
+            for i in 0..keys.length() {
+                let Some(key) = keys.get_index(scope, i) else {
+                    continue;
+                };
+                match namespace.get(scope, key) {
+                    Some(value) => pending.push(value),
+
+                    // An export still in its temporal dead zone
+                    None => scope.reset(),
+                }
+            }
+        }
+
+        while let Some(value) = pending.pop() {
+            let Ok(object) = v8::Local::<v8::Object>::try_from(value) else {
+                continue;
+            };
+            if object.is_proxy() || object.is_module_namespace_object() {
+                continue;
+            }
+            if v8::Local::<v8::ArrayBufferView>::try_from(value).is_ok_and(|v| v.byte_length() > 0)
+            {
+                continue;
+            }
+            if !mark(&mut seen, object) {
+                continue;
+            }
+
+            pending.extend(own_values(scope, object));
+            if object
+                .set_integrity_level(scope, v8::IntegrityLevel::Frozen)
+                .is_none()
+            {
+                scope.reset();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Records an object as visited, returning false if it already was
+fn mark<'s>(
+    seen: &mut HashMap<i32, Vec<v8::Local<'s, v8::Object>>>,
+    object: v8::Local<'s, v8::Object>,
+) -> bool {
+    let bucket = seen.entry(object.get_identity_hash().get()).or_default();
+    if bucket.iter().any(|o| o.strict_equals(object.into())) {
+        return false;
+    }
+    bucket.push(object);
+    true
+}
+
+/// The objects held by an object's own properties - values, getters and setters
+///
+/// Read through property descriptors, so that no getter is called
+fn own_values<'a, 'i>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    object: v8::Local<'a, v8::Object>,
+) -> Vec<v8::Local<'a, v8::Value>> {
+    let mut values = vec![];
+    let Some(keys) = object.get_own_property_names(scope, OWN_KEYS) else {
+        return values;
+    };
+
+    for i in 0..keys.length() {
+        let Some(descriptor) = keys
+            .get_index(scope, i)
+            .and_then(|key| v8::Local::<v8::Name>::try_from(key).ok())
+            .and_then(|key| object.get_own_property_descriptor(scope, key))
+        else {
+            continue;
+        };
+
+        for field in ["value", "get", "set"] {
+            if let Some(value) = descriptor_field(scope, descriptor, field) {
+                if value.is_object() {
+                    values.push(value);
+                }
+            }
+        }
+    }
+
+    values
+}
+
+/// Reads a field of a property descriptor, without consulting its prototype
+fn descriptor_field<'a, 'i>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    descriptor: v8::Local<'a, v8::Value>,
+    field: &str,
+) -> Option<v8::Local<'a, v8::Value>> {
+    let descriptor = v8::Local::<v8::Object>::try_from(descriptor).ok()?;
+    let field = v8::String::new(scope, field)?;
+    if descriptor.has_own_property(scope, field.into())? {
+        descriptor.get(scope, field.into())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{js_value::Function, json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_freeze_exports() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            freeze_exports: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const config = { limits: { max: 5 } };
+            export function handler() { return config.limits.max; }
+            export const builtin = Math;
+            ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+        let handler: Function = runtime.get_value(Some(&module), "handler").unwrap();
+
+        // Later scripts cannot patch the exports
+        let patch = Module::new(
+            "patch.js",
+            "
+            import { config, handler } from './test.js';
+            export const attempts = [
+                () => { config.limits.max = 100; },
+                () => { handler.call = () => 100; },
+            ].map((patch) => {
+                try {
+                    patch();
+                    return 'patched';
+                } catch (e) {
+                    return e.name;
+                }
+            });
+            ",
+        );
+        let patch = runtime.load_module(&patch).unwrap();
+        let attempts: Vec<String> = runtime.get_value(Some(&patch), "attempts").unwrap();
+        assert_eq!(attempts, vec!["TypeError", "TypeError"]);
+
+        let max: usize = runtime
+            .call_stored_function(Some(&module), &handler, json_args!())
+            .unwrap();
+        assert_eq!(max, 5);
+
+        // Built-ins are left alone
+        let math: bool = runtime.eval("Object.isFrozen(Math)").unwrap();
+        assert!(!math);
+    }
+}
@@ -0,0 +1,7 @@
+import { op_actions_invoke, op_actions_list } from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+namespaces.actions = Object.freeze({
+    invoke: (name, payload) => op_actions_invoke(String(name), payload ?? null),
+    list: () => op_actions_list(),
+});
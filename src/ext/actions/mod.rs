@@ -0,0 +1,272 @@
+//! Host-mediated outbound actions, exposed to scripts as `rustyscript.actions`
+//!
+//! Rather than granting raw network access for common side effects - sending an email, posting a
+//! webhook, publishing a message - the host registers a [`HostAction`] for each, and scripts
+//! trigger them by name. Payloads are checked against the action's schema, and invocations against
+//! its rate limit, before the handler runs
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use deno_core::{extension, op2, serde_json, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+pub(crate) mod schema;
+
+type ActionHandler = Box<
+    dyn Fn(serde_json::Value) -> Pin<Box<dyn Future<Output = Result<serde_json::Value, Error>>>>,
+>;
+
+/// An action the host carries out on behalf of scripts
+///
+/// See [`crate::Runtime::register_action`]
+///
+/// ```rust
+/// use rustyscript::{serde_json::json, HostAction};
+/// use std::time::Duration;
+///
+/// let send_email = HostAction::new(|payload| async move {
+///     println!("Sending email to {}", payload["to"]);
+///     Ok(json!({ "queued": true }))
+/// })
+/// .with_schema(json!({
+///     "type": "object",
+///     "required": ["to", "body"],
+///     "properties": {
+///         "to": { "type": "string" },
+///         "body": { "type": "string", "maxLength": 10000 },
+///     },
+/// }))
+/// .with_rate_limit(10, Duration::from_secs(60));
+/// ```
+pub struct HostAction {
+    handler: ActionHandler,
+    schema: Option<serde_json::Value>,
+    rate_limit: Option<(usize, Duration)>,
+}
+
+impl HostAction {
+    /// Create an action from an async handler, called with the payload passed by the script
+    ///
+    /// The handler's result is returned to the script
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Fut + 'static,
+        Fut: Future<Output = Result<serde_json::Value, Error>> + 'static,
+    {
+        Self {
+            handler: Box::new(move |payload| Box::pin(handler(payload))),
+            schema: None,
+            rate_limit: None,
+        }
+    }
+
+    /// Reject payloads not matching this JSON Schema before the handler is called
+    ///
+    /// A subset of JSON Schema is supported: `type`, `enum`, `const`, `properties`, `required`,
+    /// `additionalProperties`, `items`, `minLength`, `maxLength`, `minimum`, `maximum` and `maxItems`
+    #[must_use]
+    pub fn with_schema(mut self, schema: serde_json::Value) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Allow at most `max` invocations in any window of length `per`
+    ///
+    /// Invocations over the limit fail with [`Error::RateLimited`], without calling the handler
+    #[must_use]
+    pub fn with_rate_limit(mut self, max: usize, per: Duration) -> Self {
+        self.rate_limit = Some((max, per));
+        self
+    }
+}
+
+/// A registered action, along with the times of its recent invocations
+struct RegisteredAction {
+    action: HostAction,
+    recent: RefCell<VecDeque<Instant>>,
+}
+
+impl RegisteredAction {
+    /// Checks the payload and rate limit, recording the invocation if both pass
+    fn admit(&self, name: &str, payload: &serde_json::Value) -> Result<(), Error> {
+        if let Some(schema) = &self.action.schema {
+            schema::validate(schema, payload)
+                .map_err(|e| Error::Runtime(format!("Invalid payload for action `{name}`: {e}")))?;
+        }
+
+        if let Some((max, per)) = self.action.rate_limit {
+            let now = Instant::now();
+            let mut recent = self.recent.borrow_mut();
+            while recent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= per)
+            {
+                recent.pop_front();
+            }
+            if recent.len() >= max {
+                return Err(Error::RateLimited(name.to_string()));
+            }
+            recent.push_back(now);
+        }
+
+        Ok(())
+    }
+}
+
+/// The actions registered with a runtime
+#[derive(Default)]
+pub(crate) struct ActionRegistry(HashMap<String, Rc<RegisteredAction>>);
+
+impl ActionRegistry {
+    pub fn insert(&mut self, name: &str, action: HostAction) {
+        let action = RegisteredAction {
+            action,
+            recent: RefCell::default(),
+        };
+        self.0.insert(name.to_string(), Rc::new(action));
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.0.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[op2(async)]
+#[serde]
+async fn op_actions_invoke(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+    #[serde] payload: serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let action = state
+        .try_borrow()?
+        .try_borrow::<ActionRegistry>()
+        .and_then(|registry| registry.0.get(&name).cloned())
+        .ok_or_else(|| Error::Runtime(format!("No action named `{name}` is registered")))?;
+
+    action.admit(&name, &payload)?;
+    (action.action.handler)(payload).await
+}
+
+#[op2]
+#[serde]
+fn op_actions_list(state: &mut OpState) -> Vec<String> {
+    state
+        .try_borrow::<ActionRegistry>()
+        .map(ActionRegistry::names)
+        .unwrap_or_default()
+}
+
+extension!(
+    init_actions,
+    deps = [rustyscript],
+    ops = [op_actions_invoke, op_actions_list],
+    esm_entry_point = "ext:init_actions/init_actions.js",
+    esm = [ dir "src/ext/actions", "init_actions.js" ],
+);
+impl ExtensionTrait<()> for init_actions {
+    fn init((): ()) -> Extension {
+        init_actions::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_actions::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{serde_json::json, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_actions() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let handler_sent = sent.clone();
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime
+            .register_action(
+                "email",
+                HostAction::new(move |payload| {
+                    let sent = handler_sent.clone();
+                    async move {
+                        sent.borrow_mut().push(payload);
+                        Ok(json!({ "id": sent.borrow().len() }))
+                    }
+                })
+                .with_schema(json!({
+                    "type": "object",
+                    "required": ["to"],
+                    "properties": { "to": { "type": "string" } },
+                }))
+                .with_rate_limit(2, Duration::from_secs(60)),
+            )
+            .unwrap();
+
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                const attempt = async (payload) => {
+                    try {
+                        return await rustyscript.actions.invoke('email', payload);
+                    } catch (e) {
+                        return e.message;
+                    }
+                };
+
+                export const names = rustyscript.actions.list();
+                export const results = [
+                    await attempt({ to: 'a@example.com' }),
+                    await attempt({ to: 5 }),
+                    await attempt({ to: 'b@example.com' }),
+                    await attempt({ to: 'c@example.com' }),
+                ];
+                export const unknown = await rustyscript.actions.invoke('sms', {}).catch((e) => e.message);
+            ",
+            ))
+            .unwrap();
+
+        let names: Vec<String> = runtime.get_value(Some(&module), "names").unwrap();
+        assert_eq!(names, vec!["email"]);
+
+        let results: Vec<serde_json::Value> = runtime.get_value(Some(&module), "results").unwrap();
+        assert_eq!(results[0], json!({ "id": 1 }));
+        assert!(results[1]
+            .as_str()
+            .unwrap()
+            .contains("/to: expected string"));
+        assert_eq!(results[2], json!({ "id": 2 }));
+        let limited = Error::RateLimited("email".to_string()).to_string();
+        assert_eq!(results[3], json!(limited));
+
+        let unknown: String = runtime.get_value(Some(&module), "unknown").unwrap();
+        assert!(unknown.contains("No action named `sms`"));
+
+        assert_eq!(sent.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_action_rate_limit() {
+        let action = RegisteredAction {
+            action: HostAction::new(|_| async { Ok(json!(null)) })
+                .with_rate_limit(1, Duration::from_secs(60)),
+            recent: RefCell::default(),
+        };
+
+        action.admit("email", &json!({})).unwrap();
+        let error = action.admit("email", &json!({})).unwrap_err();
+        assert!(matches!(error, Error::RateLimited(name) if name == "email"));
+    }
+}
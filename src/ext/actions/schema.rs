@@ -0,0 +1,224 @@
+//! Validation of action payloads, and the arguments of functions wrapped with `rustyscript.wrap`,
+//! against a subset of JSON Schema
+//!
+//! Supports `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+//! `minLength`/`maxLength`, `minimum`/`maximum` and `maxItems` - enough to describe the payloads
+//! hosts typically accept. Unknown keywords are ignored
+use deno_core::serde_json::Value;
+use serde::Serialize;
+
+/// The first part of a value that did not match its schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mismatch {
+    /// The path to the offending part, such as `/cc/0`
+    pub path: String,
+
+    /// What was wrong with it
+    pub message: String,
+}
+
+/// Checks a value against a schema, returning a description of the first mismatch
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    check(schema, value, "").map_err(|m| format!("{}: {}", display(&m.path), m.message))
+}
+
+/// Checks a value against a schema, with paths in the mismatch starting from `path`
+pub fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), Mismatch> {
+    check(schema, value, path)
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => false,
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), Mismatch> {
+    let fail = |message: String| {
+        Err(Mismatch {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts anything, `false` nothing
+        return match schema {
+            Value::Bool(false) => fail("no value is allowed".to_string()),
+            _ => Ok(()),
+        };
+    };
+
+    match schema.get("type") {
+        Some(Value::String(name)) if !type_matches(name, value) => {
+            return fail(format!("expected {name}"));
+        }
+        Some(Value::Array(names))
+            if !names
+                .iter()
+                .any(|n| n.as_str().is_some_and(|n| type_matches(n, value))) =>
+        {
+            return fail(format!("expected one of {}", Value::Array(names.clone())));
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return fail(format!("expected one of {}", Value::Array(options.clone())));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return fail(format!("expected {expected}"));
+        }
+    }
+
+    match value {
+        Value::String(s) => {
+            let len = s.chars().count();
+            if bound(schema, "minLength").is_some_and(|min| len < min) {
+                return fail("string is too short".to_string());
+            }
+            if bound(schema, "maxLength").is_some_and(|max| len > max) {
+                return fail("string is too long".to_string());
+            }
+        }
+
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    return fail(format!("must be at least {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    return fail(format!("must be at most {max}"));
+                }
+            }
+        }
+
+        Value::Array(items) => {
+            if let Some(max) = bound(schema, "maxItems") {
+                if items.len() > max {
+                    return fail(format!("must have at most {max} items"));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{path}/{i}"))?;
+                }
+            }
+        }
+
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        return fail(format!("missing required property `{key}`"));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, item) in object {
+                let item_path = format!("{path}/{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property) => check(property, item, &item_path)?,
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            check(additional, item, &item_path)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Value::Null | Value::Bool(_) => {}
+    }
+
+    Ok(())
+}
+
+/// A length or count keyword, such as `maxItems`
+fn bound(schema: &deno_core::serde_json::Map<String, Value>, keyword: &str) -> Option<usize> {
+    let bound = schema.get(keyword)?.as_u64()?;
+    Some(usize::try_from(bound).unwrap_or(usize::MAX))
+}
+
+fn display(path: &str) -> &str {
+    if path.is_empty() {
+        "payload"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deno_core::serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["to", "subject"],
+            "properties": {
+                "to": { "type": "string", "minLength": 3 },
+                "subject": { "type": "string", "maxLength": 10 },
+                "priority": { "enum": ["low", "high"] },
+                "cc": { "type": "array", "items": { "type": "string" }, "maxItems": 2 },
+                "retries": { "type": "integer", "minimum": 0, "maximum": 3 },
+            },
+            "additionalProperties": false,
+        });
+
+        let ok = json!({ "to": "a@b.c", "subject": "hi", "cc": ["x"], "retries": 2 });
+        assert!(validate(&schema, &ok).is_ok());
+
+        let cases = [
+            (json!("nope"), "payload: expected object"),
+            (
+                json!({ "to": "a@b.c" }),
+                "missing required property `subject`",
+            ),
+            (
+                json!({ "to": "a", "subject": "hi" }),
+                "/to: string is too short",
+            ),
+            (
+                json!({ "to": "a@b.c", "subject": "hi", "priority": "urgent" }),
+                "/priority: expected one of",
+            ),
+            (
+                json!({ "to": "a@b.c", "subject": "hi", "cc": [1] }),
+                "/cc/0: expected string",
+            ),
+            (
+                json!({ "to": "a@b.c", "subject": "hi", "retries": 1.5 }),
+                "/retries: expected integer",
+            ),
+            (
+                json!({ "to": "a@b.c", "subject": "hi", "retries": 4 }),
+                "/retries: must be at most 3",
+            ),
+            (
+                json!({ "to": "a@b.c", "subject": "hi", "bcc": "x" }),
+                "/bcc: no value is allowed",
+            ),
+        ];
+        for (value, expected) in cases {
+            let e = validate(&schema, &value).unwrap_err();
+            assert!(e.contains(expected), "{e} does not contain {expected}");
+        }
+    }
+}
@@ -0,0 +1,25 @@
+import { op_cli_args, op_cli_exit } from "ext:core/ops";
+import { applyToDeno, getterOnly, nonEnumerable } from 'ext:rustyscript/rustyscript.js';
+
+let args;
+
+// Stops the runtime, without affecting the host process
+// The code is reported to the host with `Runtime::exit_code`
+function exit(code = 0) {
+    code = Number(code);
+    if (!Number.isInteger(code)) {
+        throw new TypeError('Exit code must be an integer');
+    }
+
+    if (!op_cli_exit(code)) {
+        throw new Error('Deno.exit is not available in this context');
+    }
+
+    // Execution is already terminating - make sure nothing runs in the meantime
+    for (;;) {}
+}
+
+applyToDeno({
+    args: getterOnly(() => (args ??= Object.freeze(op_cli_args()))),
+    exit: nonEnumerable(exit),
+});
@@ -0,0 +1,97 @@
+use std::{cell::Cell, rc::Rc};
+
+use deno_core::{extension, op2, v8, Extension, OpState};
+
+use super::ExtensionTrait;
+
+/// State backing `Deno.args` and `Deno.exit`
+pub(crate) struct CliState {
+    pub argv: Vec<String>,
+    pub exit_code: Rc<Cell<Option<i32>>>,
+    pub isolate: v8::IsolateHandle,
+}
+
+/// Returns the arguments set with `RuntimeOptions::argv`
+#[op2]
+#[serde]
+fn op_cli_args(state: &mut OpState) -> Vec<String> {
+    state
+        .try_borrow::<CliState>()
+        .map(|cli| cli.argv.clone())
+        .unwrap_or_default()
+}
+
+/// Records the exit code, and stops the runtime - the host process is unaffected
+///
+/// Returns false if the runtime cannot be stopped
+#[op2(fast)]
+fn op_cli_exit(state: &mut OpState, code: i32) -> bool {
+    let Some(cli) = state.try_borrow::<CliState>() else {
+        return false;
+    };
+
+    cli.exit_code.set(Some(code));
+    cli.isolate.terminate_execution()
+}
+
+extension!(
+    init_cli,
+    deps = [rustyscript],
+    ops = [op_cli_args, op_cli_exit],
+    esm_entry_point = "ext:init_cli/init_cli.js",
+    esm = [ dir "src/ext/cli", "init_cli.js" ],
+);
+impl ExtensionTrait<()> for init_cli {
+    fn init((): ()) -> Extension {
+        init_cli::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_cli::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_argv() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            argv: vec!["--name".to_string(), "test".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let args: Vec<String> = runtime.eval("Deno.args").unwrap();
+        assert_eq!(args, vec!["--name", "test"]);
+        assert_eq!(runtime.exit_code(), None);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            export function main() {
+                try {
+                    Deno.exit(3);
+                } catch {
+                    return 'caught';
+                }
+                return 'continued';
+            }
+        ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        let result = runtime.call_function::<String>(Some(&module), "main", crate::json_args!());
+        assert!(matches!(result, Err(Error::Exit(3))));
+        assert_eq!(runtime.exit_code(), Some(3));
+
+        // Only the interrupted call fails with `Exit` - later errors keep their own variant
+        let result = runtime.get_value::<String>(Some(&module), "missing");
+        assert!(matches!(result, Err(Error::ValueNotFound(_))));
+    }
+}
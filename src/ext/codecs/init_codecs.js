@@ -0,0 +1,439 @@
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+const MAX_SAFE = BigInt(Number.MAX_SAFE_INTEGER);
+
+// 64-bit integers are returned as numbers where they can be held exactly, and BigInts otherwise
+const integer = (n) => (n >= -MAX_SAFE && n <= MAX_SAFE ? Number(n) : n);
+
+const asBytes = (value) => {
+    if (value instanceof Uint8Array) return value;
+    if (value instanceof ArrayBuffer) return new Uint8Array(value);
+    if (ArrayBuffer.isView(value)) return new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+    throw new TypeError('Expected a Uint8Array or ArrayBuffer');
+};
+
+const isBinary = (value) => value instanceof Uint8Array || value instanceof ArrayBuffer;
+
+// Values are prepared as JSON.stringify would - `toJSON` is honoured, and undefined properties skipped
+const prepare = (value) => (value !== null && typeof value?.toJSON === 'function' ? value.toJSON() : value);
+
+const entriesOf = (value) => {
+    const entries = value instanceof Map ? [...value] : Object.entries(value);
+    return entries.filter(([, v]) => v !== undefined && typeof v !== 'function');
+};
+
+const setEntry = (object, key, value) => {
+    if (key === '__proto__') {
+        Object.defineProperty(object, key, { value, enumerable: true, writable: true, configurable: true });
+    } else {
+        object[key] = value;
+    }
+};
+
+// A growable buffer of big-endian values
+class Writer {
+    #bytes = new Uint8Array(256);
+    #view = new DataView(this.#bytes.buffer);
+    #length = 0;
+
+    #reserve(n) {
+        if (this.#length + n <= this.#bytes.length) return this.#length;
+        let size = this.#bytes.length * 2;
+        while (size < this.#length + n) size *= 2;
+        const bytes = new Uint8Array(size);
+        bytes.set(this.#bytes.subarray(0, this.#length));
+        this.#bytes = bytes;
+        this.#view = new DataView(bytes.buffer);
+        return this.#length;
+    }
+
+    #put(n, write) {
+        const offset = this.#reserve(n);
+        write(this.#view, offset);
+        this.#length += n;
+    }
+
+    u8(n) { this.#put(1, (v, o) => v.setUint8(o, n)); }
+    u16(n) { this.#put(2, (v, o) => v.setUint16(o, n)); }
+    u32(n) { this.#put(4, (v, o) => v.setUint32(o, n)); }
+    i8(n) { this.#put(1, (v, o) => v.setInt8(o, n)); }
+    i16(n) { this.#put(2, (v, o) => v.setInt16(o, n)); }
+    i32(n) { this.#put(4, (v, o) => v.setInt32(o, n)); }
+    f64(n) { this.#put(8, (v, o) => v.setFloat64(o, n)); }
+
+    u64(n) {
+        n = BigInt(n);
+        if (BigInt.asUintN(64, n) !== n) throw new RangeError(`${n} does not fit in 64 bits`);
+        this.#put(8, (v, o) => v.setBigUint64(o, n));
+    }
+
+    i64(n) {
+        n = BigInt(n);
+        if (BigInt.asIntN(64, n) !== n) throw new RangeError(`${n} does not fit in 64 bits`);
+        this.#put(8, (v, o) => v.setBigInt64(o, n));
+    }
+
+    bytes(bytes) {
+        const offset = this.#reserve(bytes.length);
+        this.#bytes.set(bytes, offset);
+        this.#length += bytes.length;
+    }
+
+    finish() {
+        return this.#bytes.slice(0, this.#length);
+    }
+}
+
+// Reads big-endian values from a buffer, throwing if it runs out
+class Reader {
+    #bytes;
+    #view;
+    #offset = 0;
+
+    constructor(bytes) {
+        this.#bytes = asBytes(bytes);
+        this.#view = new DataView(this.#bytes.buffer, this.#bytes.byteOffset, this.#bytes.byteLength);
+    }
+
+    #take(n) {
+        if (this.#offset + n > this.#bytes.length) throw new RangeError('Unexpected end of input');
+        const offset = this.#offset;
+        this.#offset += n;
+        return offset;
+    }
+
+    u8() { return this.#view.getUint8(this.#take(1)); }
+    u16() { return this.#view.getUint16(this.#take(2)); }
+    u32() { return this.#view.getUint32(this.#take(4)); }
+    u64() { return integer(this.#view.getBigUint64(this.#take(8))); }
+    i8() { return this.#view.getInt8(this.#take(1)); }
+    i16() { return this.#view.getInt16(this.#take(2)); }
+    i32() { return this.#view.getInt32(this.#take(4)); }
+    i64() { return integer(this.#view.getBigInt64(this.#take(8))); }
+    f32() { return this.#view.getFloat32(this.#take(4)); }
+    f64() { return this.#view.getFloat64(this.#take(8)); }
+
+    bytes(n) {
+        const offset = this.#take(n);
+        return this.#bytes.slice(offset, offset + n);
+    }
+
+    string(n) {
+        const offset = this.#take(n);
+        return Deno.core.decode(this.#bytes.subarray(offset, offset + n));
+    }
+
+    end() {
+        if (this.#offset !== this.#bytes.length) throw new RangeError('Unexpected data after the encoded value');
+    }
+}
+
+//
+// MessagePack - https://github.com/msgpack/msgpack/blob/master/spec.md
+//
+
+function msgpackInt(w, n) {
+    if (n >= 0) {
+        if (n < 0x80) return w.u8(Number(n));
+        if (n <= 0xff) return w.u8(0xcc), w.u8(Number(n));
+        if (n <= 0xffff) return w.u8(0xcd), w.u16(Number(n));
+        if (n <= 0xffffffff) return w.u8(0xce), w.u32(Number(n));
+        return w.u8(0xcf), w.u64(n);
+    }
+    if (n >= -32) return w.i8(Number(n));
+    if (n >= -0x80) return w.u8(0xd0), w.i8(Number(n));
+    if (n >= -0x8000) return w.u8(0xd1), w.i16(Number(n));
+    if (n >= -0x80000000) return w.u8(0xd2), w.i32(Number(n));
+    return w.u8(0xd3), w.i64(n);
+}
+
+// Writes a length, in the smallest of the forms available - [fixed marker, limit], 8, 16 or 32 bits
+function msgpackLength(w, length, [fix, limit], m8, m16, m32) {
+    if (fix !== null && length < limit) return w.u8(fix | length);
+    if (m8 !== null && length <= 0xff) return w.u8(m8), w.u8(length);
+    if (length <= 0xffff) return w.u8(m16), w.u16(length);
+    return w.u8(m32), w.u32(length);
+}
+
+function encodeMsgpack(w, value) {
+    value = prepare(value);
+    if (value === null || value === undefined) return w.u8(0xc0);
+    if (value === false) return w.u8(0xc2);
+    if (value === true) return w.u8(0xc3);
+
+    switch (typeof value) {
+        case 'number':
+            if (Number.isSafeInteger(value)) return msgpackInt(w, value);
+            return w.u8(0xcb), w.f64(value);
+        case 'bigint':
+            return msgpackInt(w, value);
+        case 'string': {
+            const bytes = Deno.core.encode(value);
+            msgpackLength(w, bytes.length, [0xa0, 32], 0xd9, 0xda, 0xdb);
+            return w.bytes(bytes);
+        }
+    }
+
+    if (isBinary(value)) {
+        const bytes = asBytes(value);
+        msgpackLength(w, bytes.length, [null, 0], 0xc4, 0xc5, 0xc6);
+        return w.bytes(bytes);
+    }
+
+    if (Array.isArray(value)) {
+        msgpackLength(w, value.length, [0x90, 16], null, 0xdc, 0xdd);
+        for (const item of value) encodeMsgpack(w, item);
+        return;
+    }
+
+    const entries = entriesOf(value);
+    msgpackLength(w, entries.length, [0x80, 16], null, 0xde, 0xdf);
+    for (const [k, v] of entries) {
+        encodeMsgpack(w, k);
+        encodeMsgpack(w, v);
+    }
+}
+
+function msgpackArray(r, length) {
+    const array = new Array(length);
+    for (let i = 0; i < length; i++) array[i] = decodeMsgpack(r);
+    return array;
+}
+
+function msgpackMap(r, length) {
+    const object = {};
+    for (let i = 0; i < length; i++) {
+        const key = decodeMsgpack(r);
+        setEntry(object, key, decodeMsgpack(r));
+    }
+    return object;
+}
+
+function decodeMsgpack(r) {
+    const b = r.u8();
+    if (b < 0x80) return b;
+    if (b < 0x90) return msgpackMap(r, b & 0x0f);
+    if (b < 0xa0) return msgpackArray(r, b & 0x0f);
+    if (b < 0xc0) return r.string(b & 0x1f);
+    if (b >= 0xe0) return b - 0x100;
+
+    switch (b) {
+        case 0xc0: return null;
+        case 0xc2: return false;
+        case 0xc3: return true;
+        case 0xc4: return r.bytes(r.u8());
+        case 0xc5: return r.bytes(r.u16());
+        case 0xc6: return r.bytes(r.u32());
+        case 0xca: return r.f32();
+        case 0xcb: return r.f64();
+        case 0xcc: return r.u8();
+        case 0xcd: return r.u16();
+        case 0xce: return r.u32();
+        case 0xcf: return r.u64();
+        case 0xd0: return r.i8();
+        case 0xd1: return r.i16();
+        case 0xd2: return r.i32();
+        case 0xd3: return r.i64();
+        case 0xd9: return r.string(r.u8());
+        case 0xda: return r.string(r.u16());
+        case 0xdb: return r.string(r.u32());
+        case 0xdc: return msgpackArray(r, r.u16());
+        case 0xdd: return msgpackArray(r, r.u32());
+        case 0xde: return msgpackMap(r, r.u16());
+        case 0xdf: return msgpackMap(r, r.u32());
+        default: throw new TypeError(`Unsupported MessagePack type 0x${b.toString(16)}`);
+    }
+}
+
+//
+// CBOR - https://www.rfc-editor.org/rfc/rfc8949
+//
+
+// Marks the end of an indefinite-length item
+const BREAK = Symbol('break');
+
+function cborHead(w, major, n) {
+    const m = major << 5;
+    if (n < 24) return w.u8(m | Number(n));
+    if (n <= 0xff) return w.u8(m | 24), w.u8(Number(n));
+    if (n <= 0xffff) return w.u8(m | 25), w.u16(Number(n));
+    if (n <= 0xffffffff) return w.u8(m | 26), w.u32(Number(n));
+    return w.u8(m | 27), w.u64(n);
+}
+
+function encodeCbor(w, value) {
+    value = prepare(value);
+    if (value === null || value === undefined) return w.u8(0xf6);
+    if (value === false) return w.u8(0xf4);
+    if (value === true) return w.u8(0xf5);
+
+    switch (typeof value) {
+        case 'number':
+            if (!Number.isSafeInteger(value)) return w.u8(0xfb), w.f64(value);
+            return value >= 0 ? cborHead(w, 0, value) : cborHead(w, 1, -1 - value);
+        case 'bigint':
+            return value >= 0n ? cborHead(w, 0, value) : cborHead(w, 1, -1n - value);
+        case 'string': {
+            const bytes = Deno.core.encode(value);
+            cborHead(w, 3, bytes.length);
+            return w.bytes(bytes);
+        }
+    }
+
+    if (isBinary(value)) {
+        const bytes = asBytes(value);
+        cborHead(w, 2, bytes.length);
+        return w.bytes(bytes);
+    }
+
+    if (Array.isArray(value)) {
+        cborHead(w, 4, value.length);
+        for (const item of value) encodeCbor(w, item);
+        return;
+    }
+
+    const entries = entriesOf(value);
+    cborHead(w, 5, entries.length);
+    for (const [k, v] of entries) {
+        encodeCbor(w, k);
+        encodeCbor(w, v);
+    }
+}
+
+function cborArgument(r, info) {
+    if (info < 24) return info;
+    switch (info) {
+        case 24: return r.u8();
+        case 25: return r.u16();
+        case 26: return r.u32();
+        case 27: return r.u64();
+        default: throw new TypeError(`Invalid CBOR argument ${info}`);
+    }
+}
+
+// Returns -1 for an indefinite length
+function cborLength(r, info) {
+    if (info === 31) return -1;
+    const length = cborArgument(r, info);
+    if (typeof length !== 'number') throw new RangeError('CBOR item is too long');
+    return length;
+}
+
+function half(bits) {
+    const sign = bits & 0x8000 ? -1 : 1;
+    const exponent = (bits >> 10) & 0x1f;
+    const fraction = bits & 0x3ff;
+    if (exponent === 0) return sign * fraction * 2 ** -24;
+    if (exponent === 0x1f) return fraction ? NaN : sign * Infinity;
+    return sign * (1 + fraction / 1024) * 2 ** (exponent - 15);
+}
+
+function bignum(bytes) {
+    let n = 0n;
+    for (const byte of bytes) n = (n << 8n) | BigInt(byte);
+    return n;
+}
+
+// Reads the chunks of an indefinite-length string
+function cborChunks(r, major) {
+    const chunks = [];
+    for (let chunk; (chunk = decodeCbor(r, true)) !== BREAK;) {
+        if (typeof chunk !== (major === 2 ? 'object' : 'string')) throw new TypeError('Invalid CBOR string chunk');
+        chunks.push(chunk);
+    }
+    if (major === 3) return chunks.join('');
+
+    const bytes = new Uint8Array(chunks.reduce((length, chunk) => length + chunk.length, 0));
+    let offset = 0;
+    for (const chunk of chunks) {
+        bytes.set(chunk, offset);
+        offset += chunk.length;
+    }
+    return bytes;
+}
+
+function decodeCbor(r, allowBreak = false) {
+    const b = r.u8();
+    const major = b >> 5;
+    const info = b & 0x1f;
+
+    switch (major) {
+        case 0:
+            return cborArgument(r, info);
+        case 1: {
+            const n = cborArgument(r, info);
+            return typeof n === 'bigint' ? -1n - n : integer(-1n - BigInt(n));
+        }
+        case 2:
+        case 3: {
+            const length = cborLength(r, info);
+            if (length < 0) return cborChunks(r, major);
+            return major === 2 ? r.bytes(length) : r.string(length);
+        }
+        case 4: {
+            const length = cborLength(r, info);
+            const array = [];
+            if (length < 0) {
+                for (let item; (item = decodeCbor(r, true)) !== BREAK;) array.push(item);
+            } else {
+                for (let i = 0; i < length; i++) array.push(decodeCbor(r));
+            }
+            return array;
+        }
+        case 5: {
+            const length = cborLength(r, info);
+            const object = {};
+            for (let i = 0; length < 0 || i < length; i++) {
+                const key = decodeCbor(r, length < 0);
+                if (key === BREAK) break;
+                setEntry(object, key, decodeCbor(r));
+            }
+            return object;
+        }
+        case 6: {
+            const tag = cborArgument(r, info);
+            const value = decodeCbor(r);
+            if (tag === 2) return integer(bignum(value));
+            if (tag === 3) return integer(-1n - bignum(value));
+            return value;
+        }
+    }
+
+    switch (info) {
+        case 20: return false;
+        case 21: return true;
+        case 22: return null;
+        case 23: return undefined;
+        case 25: return half(r.u16());
+        case 26: return r.f32();
+        case 27: return r.f64();
+        case 31:
+            if (allowBreak) return BREAK;
+            throw new TypeError('Unexpected CBOR break');
+        default: throw new TypeError(`Unsupported CBOR simple value ${info}`);
+    }
+}
+
+const binaryCodec = (encodeValue, decodeValue) => Object.freeze({
+    encode(value) {
+        const writer = new Writer();
+        encodeValue(writer, value);
+        return writer.finish();
+    },
+
+    decode(bytes) {
+        const reader = new Reader(bytes);
+        const value = decodeValue(reader);
+        reader.end();
+        return value;
+    },
+});
+
+namespaces.codecs = Object.freeze({
+    msgpack: binaryCodec(encodeMsgpack, decodeMsgpack),
+    cbor: binaryCodec(encodeCbor, (reader) => decodeCbor(reader)),
+    json: Object.freeze({
+        encode: (value) => Deno.core.encode(JSON.stringify(value) ?? 'null'),
+        decode: (bytes) => JSON.parse(Deno.core.decode(asBytes(bytes))),
+    }),
+});
@@ -0,0 +1,166 @@
+//! Binary encodings for arguments and return values, exposed to scripts as `rustyscript.codecs`
+//!
+//! Calling a function through [`crate::Runtime::call_function_encoded`] passes its arguments as a
+//! single `Uint8Array`, rather than converting them value by value into JS objects. The script
+//! decodes them with the matching `rustyscript.codecs.<name>.decode`, and returns its result
+//! encoded with `rustyscript.codecs.<name>.encode` - which for payloads heavy in binary data or
+//! deeply nested structures is cheaper than building the equivalent V8 values up front
+//!
+//! Protocol Buffers are not included - messages need a schema on both sides, so are better
+//! encoded by the host and script themselves and passed as plain bytes
+use deno_core::{extension, serde_json, Extension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// An encoding for values passed to and from [`crate::Runtime::call_function_encoded`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// [MessagePack](https://msgpack.org) - `rustyscript.codecs.msgpack`
+    ///
+    /// Structs are encoded as maps, keyed by field name
+    #[default]
+    Msgpack,
+
+    /// [CBOR](https://cbor.io) - `rustyscript.codecs.cbor`
+    Cbor,
+
+    /// UTF-8 encoded JSON text - `rustyscript.codecs.json`
+    Json,
+}
+
+impl Codec {
+    /// The name of the codec's namespace under `rustyscript.codecs`
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Msgpack => "msgpack",
+            Self::Cbor => "cbor",
+            Self::Json => "json",
+        }
+    }
+
+    /// Encode a value with this codec
+    ///
+    /// # Errors
+    /// Will return an error if the value cannot be represented in this encoding
+    pub fn encode(&self, value: &impl Serialize) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Msgpack => Ok(rmp_serde::to_vec_named(value)?),
+            Self::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes)?;
+                Ok(bytes)
+            }
+            Self::Json => Ok(serde_json::to_vec(value)?),
+        }
+    }
+
+    /// Decode a value encoded with this codec
+    ///
+    /// # Errors
+    /// Will return an error if the bytes are not valid in this encoding,
+    /// or do not hold a value of type `T`
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error> {
+        match self {
+            Self::Msgpack => Ok(rmp_serde::from_slice(bytes)?),
+            Self::Cbor => Ok(ciborium::from_reader(bytes)?),
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+extension!(
+    init_codecs,
+    deps = [rustyscript],
+    esm_entry_point = "ext:init_codecs/init_codecs.js",
+    esm = [ dir "src/ext/codecs", "init_codecs.js" ],
+);
+impl ExtensionTrait<()> for init_codecs {
+    fn init((): ()) -> Extension {
+        init_codecs::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_codecs::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        samples: Vec<f64>,
+        tags: HashMap<String, i64>,
+        error: Option<String>,
+        big: u64,
+    }
+
+    #[test]
+    fn test_codecs() {
+        let reading = Reading {
+            sensor: "intake-π".to_string(),
+            samples: vec![0.5, -1.25, 1e300],
+            tags: HashMap::from([("site".to_string(), -40_000), ("rack".to_string(), 7)]),
+            error: None,
+            big: 1 << 40,
+        };
+
+        let module = Module::new(
+            "test.js",
+            "
+            const summarize = (codec) => (payload) => {
+                const { encode, decode } = rustyscript.codecs[codec];
+                const [reading, scale] = decode(payload);
+                return encode({
+                    ...reading,
+                    samples: reading.samples.map((s) => s * scale),
+                    big: reading.big + 1,
+                });
+            };
+            export const msgpack = summarize('msgpack');
+            export const cbor = summarize('cbor');
+            export const json = summarize('json');
+
+            export const roundTrip = (codec, value) => {
+                const { encode, decode } = rustyscript.codecs[codec];
+                return decode(encode(value));
+            };
+            ",
+        );
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime.load_module(&module).unwrap();
+
+        for codec in [Codec::Msgpack, Codec::Cbor, Codec::Json] {
+            let result: Reading = runtime
+                .call_function_encoded(codec, Some(&module), codec.name(), &(&reading, 2))
+                .unwrap();
+            assert_eq!(result.sensor, reading.sensor, "{codec}");
+            assert_eq!(result.samples, vec![1.0, -2.5, 2e300], "{codec}");
+            assert_eq!(result.tags, reading.tags, "{codec}");
+            assert_eq!(result.big, (1 << 40) + 1, "{codec}");
+            assert_eq!(result.error, None, "{codec}");
+
+            let value = serde_json::json!({ "a": [1, 300, 70_000, -5, -200, -40_000, 1.5, "x".repeat(300), null, true, {}] });
+            let returned: serde_json::Value = runtime
+                .call_function(Some(&module), "roundTrip", &(codec.name(), &value))
+                .unwrap();
+            assert_eq!(returned, value, "{codec}");
+        }
+    }
+}
@@ -0,0 +1,33 @@
+import { op_database_query, op_database_execute } from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+const toObjects = ({ columns, rows }) => rows.map((row) => {
+    const object = {};
+    columns.forEach((column, i) => object[column] = row[i]);
+    return object;
+});
+
+// A named database - the host decides what it connects to
+class Connection {
+    #name;
+
+    constructor(name) {
+        this.#name = String(name);
+    }
+
+    get name() {
+        return this.#name;
+    }
+
+    async query(sql, params = []) {
+        return toObjects(await op_database_query(this.#name, String(sql), [...params]));
+    }
+
+    execute(sql, params = []) {
+        return op_database_execute(this.#name, String(sql), [...params]);
+    }
+}
+
+namespaces.database = Object.freeze({
+    connect: (name = 'default') => new Connection(name),
+});
@@ -0,0 +1,223 @@
+//! Host-provided SQL databases, exposed to scripts as `rustyscript.database`
+//!
+//! Connections are established and pooled by the host through a [`DbProvider`] - scripts only
+//! ever see a database's name, never its connection string or credentials
+use std::{cell::RefCell, rc::Rc};
+
+use deno_core::{extension, op2, serde_json, Extension, OpState};
+use serde::{Deserialize, Serialize};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// Rows returned by a query, as positional values
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DbRows {
+    /// The names of the columns, in order
+    pub columns: Vec<String>,
+
+    /// One entry per row, holding a value for each column
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Connects scripts to the host's databases
+///
+/// Implementations own the connections - typically a pool per database, built from connection
+/// strings that never enter the sandbox. Parameters arrive as JSON values, and are bound by the
+/// implementation in whatever form its driver expects.
+///
+/// ```rust
+/// use rustyscript::{serde_json::Value, DbProvider, DbRows, Error};
+///
+/// struct Static;
+///
+/// #[rustyscript::async_trait::async_trait(?Send)]
+/// impl DbProvider for Static {
+///     async fn query(&self, database: &str, sql: &str, params: Vec<Value>) -> Result<DbRows, Error> {
+///         Ok(DbRows {
+///             columns: vec!["database".to_string()],
+///             rows: vec![vec![Value::String(database.to_string())]],
+///         })
+///     }
+///
+///     async fn execute(&self, database: &str, sql: &str, params: Vec<Value>) -> Result<u64, Error> {
+///         Ok(0)
+///     }
+/// }
+/// ```
+#[async_trait::async_trait(?Send)]
+pub trait DbProvider {
+    /// Run a statement that returns rows
+    ///
+    /// # Errors
+    /// Should return an error if the database is unknown, or if the statement fails
+    async fn query(
+        &self,
+        database: &str,
+        sql: &str,
+        params: Vec<serde_json::Value>,
+    ) -> Result<DbRows, Error>;
+
+    /// Run a statement, returning the number of rows it affected
+    ///
+    /// # Errors
+    /// Should return an error if the database is unknown, or if the statement fails
+    async fn execute(
+        &self,
+        database: &str,
+        sql: &str,
+        params: Vec<serde_json::Value>,
+    ) -> Result<u64, Error>;
+}
+
+fn provider(state: &Rc<RefCell<OpState>>) -> Result<Rc<dyn DbProvider>, Error> {
+    state
+        .try_borrow()?
+        .try_borrow::<Rc<dyn DbProvider>>()
+        .cloned()
+        .ok_or_else(|| Error::Runtime("No database provider is configured".to_string()))
+}
+
+#[op2(async)]
+#[serde]
+async fn op_database_query(
+    state: Rc<RefCell<OpState>>,
+    #[string] database: String,
+    #[string] sql: String,
+    #[serde] params: Vec<serde_json::Value>,
+) -> Result<DbRows, Error> {
+    provider(&state)?.query(&database, &sql, params).await
+}
+
+#[op2(async)]
+#[number]
+async fn op_database_execute(
+    state: Rc<RefCell<OpState>>,
+    #[string] database: String,
+    #[string] sql: String,
+    #[serde] params: Vec<serde_json::Value>,
+) -> Result<u64, Error> {
+    provider(&state)?.execute(&database, &sql, params).await
+}
+
+extension!(
+    init_database,
+    deps = [rustyscript],
+    ops = [op_database_query, op_database_execute],
+    esm_entry_point = "ext:init_database/init_database.js",
+    esm = [ dir "src/ext/database", "init_database.js" ],
+    options = {
+        provider: Option<Rc<dyn DbProvider>>
+    },
+    state = |state, config| {
+        if let Some(provider) = config.provider {
+            state.put(provider);
+        }
+    },
+);
+impl ExtensionTrait<Option<Rc<dyn DbProvider>>> for init_database {
+    fn init(provider: Option<Rc<dyn DbProvider>>) -> Extension {
+        init_database::init(provider)
+    }
+}
+
+pub fn extensions(provider: Option<Rc<dyn DbProvider>>, is_snapshot: bool) -> Vec<Extension> {
+    vec![init_database::build(provider, is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ExtensionOptions, Module, Runtime, RuntimeOptions};
+
+    /// Records every statement, answering queries by echoing their parameters back
+    #[derive(Default)]
+    struct Echo(RefCell<Vec<(String, String)>>);
+
+    #[async_trait::async_trait(?Send)]
+    impl DbProvider for Echo {
+        async fn query(
+            &self,
+            database: &str,
+            sql: &str,
+            params: Vec<serde_json::Value>,
+        ) -> Result<DbRows, Error> {
+            if database != "main" {
+                return Err(Error::Runtime(format!("Unknown database {database}")));
+            }
+            self.0
+                .borrow_mut()
+                .push((database.to_string(), sql.to_string()));
+            Ok(DbRows {
+                columns: (0..params.len()).map(|i| format!("p{i}")).collect(),
+                rows: vec![params],
+            })
+        }
+
+        async fn execute(
+            &self,
+            database: &str,
+            sql: &str,
+            params: Vec<serde_json::Value>,
+        ) -> Result<u64, Error> {
+            self.0
+                .borrow_mut()
+                .push((database.to_string(), sql.to_string()));
+            Ok(params.len() as u64)
+        }
+    }
+
+    #[test]
+    fn test_database() {
+        let echo = Rc::new(Echo::default());
+        let mut runtime = Runtime::new(RuntimeOptions {
+            extension_options: ExtensionOptions {
+                db_provider: Some(echo.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                const db = rustyscript.database.connect('main');
+                export const rows = await db.query('SELECT $1, $2', [1, 'two']);
+                export const changed = await db.execute('DELETE FROM t WHERE id = $1', [5]);
+
+                export let unknown;
+                try {
+                    await rustyscript.database.connect('other').query('SELECT 1');
+                } catch (e) {
+                    unknown = e.message;
+                }
+            ",
+            ))
+            .unwrap();
+
+        let rows: serde_json::Value = runtime.get_value(Some(&module), "rows").unwrap();
+        assert_eq!(rows, serde_json::json!([{ "p0": 1, "p1": "two" }]));
+        let changed: u64 = runtime.get_value(Some(&module), "changed").unwrap();
+        assert_eq!(changed, 1);
+        let unknown: String = runtime.get_value(Some(&module), "unknown").unwrap();
+        assert!(unknown.contains("Unknown database other"));
+
+        assert_eq!(
+            echo.0
+                .borrow()
+                .iter()
+                .map(|(_, sql)| sql.as_str())
+                .collect::<Vec<_>>(),
+            vec!["SELECT $1, $2", "DELETE FROM t WHERE id = $1"]
+        );
+
+        // Without a provider
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let e = runtime
+            .eval::<()>("rustyscript.database.connect('main').query('SELECT 1')")
+            .unwrap_err();
+        assert!(e.to_string().contains("No database provider"), "{e}");
+    }
+}
@@ -0,0 +1,29 @@
+import { op_env_get, op_env_set, op_env_delete, op_env_to_object } from "ext:core/ops";
+import { applyToDeno, readOnly } from 'ext:rustyscript/rustyscript.js';
+
+// A `Deno.env` compatible view over the host-provided virtual environment
+const env = {
+    get(key) {
+        return op_env_get(String(key)) ?? undefined;
+    },
+
+    set(key, value) {
+        op_env_set(String(key), String(value));
+    },
+
+    delete(key) {
+        op_env_delete(String(key));
+    },
+
+    has(key) {
+        return env.get(key) !== undefined;
+    },
+
+    toObject() {
+        return op_env_to_object();
+    },
+};
+
+applyToDeno({
+    env: readOnly(Object.freeze(env)),
+});
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use deno_core::{extension, op2, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// A virtualized set of environment variables, backing `Deno.env` in the runtime
+///
+/// Scripts read and write this map instead of the real process environment.  
+/// The handle is cheap to clone and shared with the runtime, so the host can
+/// inspect any changes a script has made after it runs
+///
+/// ```rust
+/// use rustyscript::{Runtime, RuntimeOptions, VirtualEnv, Undefined};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let env = VirtualEnv::from_iter([("GREETING", "hello")]);
+///
+/// let mut options = RuntimeOptions::default();
+/// options.extension_options.env = env.clone();
+/// let mut runtime = Runtime::new(options)?;
+///
+/// runtime.eval::<Undefined>("Deno.env.set('REPLY', Deno.env.get('GREETING') + ' back')")?;
+/// assert_eq!(env.get("REPLY").as_deref(), Some("hello back"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VirtualEnv(Arc<RwLock<HashMap<String, String>>>);
+impl VirtualEnv {
+    /// Create a new, empty environment
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an environment holding a copy of the host process's variables
+    #[must_use]
+    pub fn from_process() -> Self {
+        std::env::vars().collect()
+    }
+
+    /// Get the value of a variable
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.read().get(key).cloned()
+    }
+
+    /// Set the value of a variable
+    pub fn set(&self, key: impl ToString, value: impl ToString) {
+        self.write().insert(key.to_string(), value.to_string());
+    }
+
+    /// Remove a variable, returning its previous value
+    pub fn remove(&self, key: &str) -> Option<String> {
+        self.write().remove(key)
+    }
+
+    /// Returns a copy of all variables
+    #[must_use]
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.read().clone()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, String>> {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl<K: ToString, V: ToString> FromIterator<(K, V)> for VirtualEnv {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let vars = iter
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self(Arc::new(RwLock::new(vars)))
+    }
+}
+
+/// Consults the runtime's permissions (`WebPermissions::check_env`), if any are configured
+#[allow(unused_variables)]
+fn check_env(state: &OpState, key: &str) -> Result<(), Error> {
+    #[cfg(feature = "web")]
+    if let Some(permissions) = state.try_borrow::<super::web::PermissionsContainer>() {
+        permissions
+            .0
+            .check_env(key)
+            .map_err(|e| Error::Runtime(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+#[op2]
+#[string]
+fn op_env_get(state: &mut OpState, #[string] key: &str) -> Result<Option<String>, Error> {
+    check_env(state, key)?;
+    Ok(state.borrow::<VirtualEnv>().get(key))
+}
+
+#[op2]
+fn op_env_set(
+    state: &mut OpState,
+    #[string] key: &str,
+    #[string] value: &str,
+) -> Result<(), Error> {
+    check_env(state, key)?;
+    state.borrow::<VirtualEnv>().set(key, value);
+    Ok(())
+}
+
+#[op2]
+fn op_env_delete(state: &mut OpState, #[string] key: &str) -> Result<(), Error> {
+    check_env(state, key)?;
+    state.borrow::<VirtualEnv>().remove(key);
+    Ok(())
+}
+
+/// Returns every variable the script is permitted to read
+#[op2]
+#[serde]
+fn op_env_to_object(state: &mut OpState) -> HashMap<String, String> {
+    let mut vars = state.borrow::<VirtualEnv>().to_map();
+    vars.retain(|key, _| check_env(state, key).is_ok());
+    vars
+}
+
+extension!(
+    init_env,
+    deps = [rustyscript],
+    ops = [op_env_get, op_env_set, op_env_delete, op_env_to_object],
+    esm_entry_point = "ext:init_env/init_env.js",
+    esm = [ dir "src/ext/env", "init_env.js" ],
+    options = {
+        env: VirtualEnv
+    },
+    state = |state, config| state.put(config.env),
+);
+impl ExtensionTrait<VirtualEnv> for init_env {
+    fn init(env: VirtualEnv) -> Extension {
+        init_env::init(env)
+    }
+}
+
+pub fn extensions(env: VirtualEnv, is_snapshot: bool) -> Vec<Extension> {
+    vec![init_env::build(env, is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_virtual_env() {
+        let env = VirtualEnv::from_iter([("A", "1"), ("B", "2")]);
+        let mut options = RuntimeOptions::default();
+        options.extension_options.env = env.clone();
+        let mut runtime = Runtime::new(options).unwrap();
+
+        let value: Option<String> = runtime.eval("Deno.env.get('A')").unwrap();
+        assert_eq!(value.as_deref(), Some("1"));
+
+        let value: bool = runtime.eval("Deno.env.has('MISSING')").unwrap();
+        assert!(!value);
+
+        runtime
+            .eval::<crate::Undefined>("Deno.env.set('C', 3); Deno.env.delete('B')")
+            .unwrap();
+        assert_eq!(env.get("C").as_deref(), Some("3"));
+        assert_eq!(env.get("B"), None);
+
+        let value: HashMap<String, String> = runtime.eval("Deno.env.toObject()").unwrap();
+        assert_eq!(value, env.to_map());
+
+        // The real process environment is untouched
+        assert!(std::env::var("C").is_err());
+    }
+}
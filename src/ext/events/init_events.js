@@ -0,0 +1,241 @@
+import { op_events_listener_added, op_events_bind_dispatcher } from "ext:core/ops";
+import { applyToGlobal, nonEnumerable, background } from 'ext:rustyscript/rustyscript.js';
+
+// Errors thrown by listeners during a host-initiated dispatch
+let collectedErrors = null;
+
+// Without the `web` extension the global scope is not an EventTarget - give it a minimal one,
+// so scripts can still declare listeners for host-dispatched events
+if (typeof globalThis.addEventListener !== 'function') {
+    const listeners = new Map();
+    const callbackOf = (listener) => (typeof listener === 'function' ? listener : listener?.handleEvent);
+
+    globalThis.addEventListener = function addEventListener(type, listener, options) {
+        if (listener === null || listener === undefined) return;
+        type = String(type);
+        if (!listeners.has(type)) listeners.set(type, []);
+
+        const entries = listeners.get(type);
+        if (entries.some((entry) => entry.listener === listener)) return;
+        entries.push({ listener, once: typeof options === 'object' && !!options?.once });
+    };
+
+    globalThis.removeEventListener = function removeEventListener(type, listener) {
+        const entries = listeners.get(String(type));
+        if (!entries) return;
+
+        const index = entries.findIndex((entry) => entry.listener === listener);
+        if (index !== -1) entries.splice(index, 1);
+    };
+
+    globalThis.dispatchEvent = function dispatchEvent(event) {
+        const entries = [...(listeners.get(event.type) ?? [])];
+        for (const { listener, once } of entries) {
+            if (once) globalThis.removeEventListener(event.type, listener);
+            try {
+                callbackOf(listener)?.call(listener, event);
+            } catch (e) {
+                if (collectedErrors === null) queueMicrotask(() => { throw e; });
+                else collectedErrors.push(e);
+            }
+        }
+        return !event.defaultPrevented;
+    };
+}
+
+// Finds the module that called into `addEventListener`, skipping the runtime's own frames
+function callerModule() {
+    const frames = (new Error().stack ?? '').split('\n').slice(1);
+    for (const frame of frames) {
+        const match = frame.match(/(?:\(|at )([^\s()]+):\d+:\d+\)?$/);
+        if (match && !match[1].startsWith('ext:') && !match[1].startsWith('node:')) {
+            return match[1];
+        }
+    }
+    return null;
+}
+
+// Global listeners are wrapped, so the host can count them and await the promises they return
+const kListeners = Symbol('listeners');
+const wrappers = new WeakMap();
+function wrapListener(listener) {
+    if ((typeof listener !== 'function' && typeof listener !== 'object') || listener === null) {
+        return listener;
+    }
+
+    if (!wrappers.has(listener)) {
+        wrappers.set(listener, function (event) {
+            const isFunction = typeof listener === 'function';
+            const callback = isFunction ? listener : listener.handleEvent;
+            if (typeof callback !== 'function') return;
+
+            if (event instanceof ExtendableEvent) event[kListeners]++;
+            const result = callback.call(isFunction ? this : listener, event);
+            if (event instanceof ExtendableEvent && typeof result?.then === 'function') {
+                event.waitUntil(result);
+            }
+            return result;
+        });
+    }
+    return wrappers.get(listener);
+}
+
+// Registrations still in place, tracked for `Runtime::report_leaks`, by listener
+const registrations = new WeakMap();
+const registrationKey = (type, options) => {
+    const capture = typeof options === 'boolean' ? options : !!options?.capture;
+    return `${capture}:${type}`;
+};
+function untrackListener(listener, key) {
+    const tokens = registrations.get(listener);
+    background.untrack(tokens?.get(key));
+    tokens?.delete(key);
+}
+
+// Report registrations to the host - see `RuntimeOptions::on_event_listener`
+const addEventListener = globalThis.addEventListener;
+const removeEventListener = globalThis.removeEventListener;
+globalThis.addEventListener = function addEventListener_(type, listener, options) {
+    const result = addEventListener.call(this ?? globalThis, type, wrapListener(listener), options);
+    if (listener === null || listener === undefined) return result;
+    type = String(type);
+    op_events_listener_added(type, callerModule());
+
+    // Registering the same listener again has no effect
+    const key = registrationKey(type, options);
+    if (!registrations.has(listener)) registrations.set(listener, new Map());
+    const tokens = registrations.get(listener);
+    if (!tokens.has(key) && !options?.signal?.aborted) {
+        const clear = () => globalThis.removeEventListener(type, listener, options);
+        tokens.set(key, background.track('listener', type, clear));
+
+        // Listeners removed by the runtime itself are untracked as they go
+        if (options?.once) addEventListener.call(globalThis, type, () => untrackListener(listener, key), { once: true });
+        options?.signal?.addEventListener('abort', () => untrackListener(listener, key), { once: true });
+    }
+    return result;
+};
+globalThis.removeEventListener = function removeEventListener_(type, listener, options) {
+    const isListener = (typeof listener === 'object' || typeof listener === 'function') && listener !== null;
+    if (isListener) untrackListener(listener, registrationKey(String(type), options));
+
+    const wrapped = isListener ? wrappers.get(listener) ?? listener : listener;
+    return removeEventListener.call(this ?? globalThis, type, wrapped, options);
+};
+Object.defineProperty(globalThis.addEventListener, 'name', { value: 'addEventListener' });
+Object.defineProperty(globalThis.removeEventListener, 'name', { value: 'removeEventListener' });
+
+// Events dispatched by the host
+// The web extension's `Event` is used where available, so listeners see a regular DOM event
+const BaseEvent = globalThis.Event ?? class Event {
+    #type;
+    #defaultPrevented = false;
+    constructor(type) {
+        this.#type = String(type);
+    }
+    get type() { return this.#type; }
+    get defaultPrevented() { return this.#defaultPrevented; }
+    preventDefault() { this.#defaultPrevented = true; }
+};
+
+const kLifetime = Symbol('lifetime');
+class ExtendableEvent extends BaseEvent {
+    [kLifetime] = [];
+    [kListeners] = 0;
+
+    // Work the event depends on, which the host finishes by driving the event loop
+    waitUntil(promise) {
+        this[kLifetime].push(Promise.resolve(promise));
+    }
+}
+
+const kResponse = Symbol('response');
+class FetchEvent extends ExtendableEvent {
+    #request;
+    [kResponse] = null;
+
+    constructor(type, init) {
+        super(type);
+        this.#request = init?.request;
+    }
+
+    get request() { return this.#request; }
+
+    respondWith(response) {
+        if (this[kResponse] !== null) throw new TypeError('respondWith has already been called');
+        this[kResponse] = Promise.resolve(response);
+    }
+}
+
+// Dispatches an event to the global listeners, returning any errors they threw
+// With the web extension, those errors would otherwise be reported as uncaught
+function dispatchCollecting(event) {
+    const errors = [];
+    const onError = (e) => {
+        errors.push(e.error);
+        e.preventDefault();
+    };
+
+    const native = BaseEvent === globalThis.Event && typeof globalThis.ErrorEvent === 'function';
+    if (native) addEventListener.call(globalThis, 'error', onError);
+    const previous = collectedErrors;
+    collectedErrors = errors;
+    try {
+        globalThis.dispatchEvent(event);
+    } finally {
+        collectedErrors = previous;
+        if (native) removeEventListener.call(globalThis, 'error', onError);
+    }
+    return errors;
+}
+
+// Used by `Runtime::dispatch_fetch_event` - resolves to the `Response` passed to `respondWith`
+const dispatchFetch = (request) => {
+    const event = new FetchEvent('fetch', { request });
+    const errors = dispatchCollecting(event);
+    if (event[kResponse] === null) {
+        throw errors[0] ?? new Error('No fetch listener responded to the request');
+    }
+    return event[kResponse];
+};
+
+// Payload-carrying events, such as `queue`, dispatched with `Runtime::dispatch_event`
+class HostEvent extends ExtendableEvent {
+    #data;
+    constructor(type, data) {
+        super(type);
+        this.#data = data;
+    }
+
+    get data() { return this.#data; }
+
+    // Rejections are collected by the dispatcher, rather than reported as unhandled
+    waitUntil(promise) {
+        super.waitUntil(promise);
+        this[kLifetime].at(-1).catch(() => {});
+    }
+}
+
+// Resolves once every listener, and the work passed to `waitUntil`, has completed
+const dispatchHost = async (type, data) => {
+    const event = new HostEvent(type, data);
+    const errors = dispatchCollecting(event);
+
+    // Listeners may extend the event further while it is pending
+    for (let i = 0; i < event[kLifetime].length; i++) {
+        try {
+            await event[kLifetime][i];
+        } catch (e) {
+            errors.push(e);
+        }
+    }
+
+    return { listeners: event[kListeners], errors };
+};
+
+op_events_bind_dispatcher(dispatchFetch, dispatchHost);
+
+applyToGlobal({
+    ExtendableEvent: nonEnumerable(ExtendableEvent),
+    FetchEvent: nonEnumerable(FetchEvent),
+});
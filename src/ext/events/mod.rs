@@ -0,0 +1,313 @@
+use std::collections::BTreeSet;
+
+use deno_core::{
+    error::JsError, extension, op2, serde_v8::GlobalValue, v8, Extension, JsRuntime, OpState,
+};
+use serde::Deserialize;
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// A listener registered by a script on the global scope, such as `addEventListener('fetch', ...)`
+///
+/// See [`crate::RuntimeOptions::on_event_listener`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventListenerInfo {
+    /// The event type, such as `fetch` or `message`
+    pub event: String,
+
+    /// Specifier of the module that registered the listener, if it could be determined
+    pub module: Option<String>,
+}
+
+/// The result of an event dispatched with [`crate::Runtime::dispatch_event`]
+#[derive(Debug)]
+pub struct EventDispatchOutcome {
+    /// Number of listeners the event was delivered to
+    pub listeners: usize,
+
+    /// Errors thrown by listeners, or from the promises they returned or passed to `waitUntil`
+    pub errors: Vec<Error>,
+}
+
+impl EventDispatchOutcome {
+    /// Returns true if every listener completed without error
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns true if at least one listener received the event
+    #[must_use]
+    pub fn is_handled(&self) -> bool {
+        self.listeners > 0
+    }
+}
+
+/// An [`EventDispatchOutcome`], as returned by the dispatcher
+#[derive(Deserialize)]
+pub(crate) struct WireOutcome {
+    listeners: usize,
+    errors: Vec<GlobalValue>,
+}
+
+impl WireOutcome {
+    /// Converts the thrown values into errors
+    pub fn decode(self, runtime: &mut JsRuntime) -> EventDispatchOutcome {
+        deno_core::scope!(scope, runtime);
+        let errors = self
+            .errors
+            .into_iter()
+            .map(|error| {
+                let error = v8::Local::new(scope, error.v8_value);
+                JsError::from_v8_exception(scope, error).into()
+            })
+            .collect();
+
+        EventDispatchOutcome {
+            listeners: self.listeners,
+            errors,
+        }
+    }
+}
+
+/// Records the global event types scripts have registered listeners for
+#[derive(Default)]
+pub(crate) struct EventListenerRegistry {
+    pub seen: BTreeSet<EventListenerInfo>,
+    pub hook: Option<Box<dyn Fn(&EventListenerInfo)>>,
+}
+
+impl EventListenerRegistry {
+    /// Returns the distinct event types registered by the given module, or by any module
+    pub fn events(&self, module: Option<&str>) -> Vec<String> {
+        let events: BTreeSet<_> = self
+            .seen
+            .iter()
+            .filter(|info| module.is_none() || info.module.as_deref() == module)
+            .map(|info| info.event.clone())
+            .collect();
+        events.into_iter().collect()
+    }
+}
+
+/// JS functions used to dispatch host events to the global scope
+#[derive(Clone)]
+pub(crate) struct EventDispatchers {
+    pub fetch: v8::Global<v8::Function>,
+    pub event: v8::Global<v8::Function>,
+}
+
+impl EventDispatchers {
+    /// Get the dispatchers from the runtime's state
+    pub fn get(state: &OpState) -> Result<Self, Error> {
+        state
+            .try_borrow::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::Runtime("Event dispatchers not initialized".to_string()))
+    }
+}
+
+/// Registers the functions used by the host to dispatch events
+#[op2]
+fn op_events_bind_dispatcher(
+    state: &mut OpState,
+    #[global] fetch: v8::Global<v8::Function>,
+    #[global] event: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(
+        state,
+        "The event dispatchers",
+        EventDispatchers { fetch, event },
+    )
+}
+
+/// Called when a script registers a global event listener
+///
+/// The host hook only fires the first time a module registers a given event type
+#[op2]
+fn op_events_listener_added(
+    state: &mut OpState,
+    #[string] event: String,
+    #[serde] module: Option<String>,
+) {
+    let Some(registry) = state.try_borrow_mut::<EventListenerRegistry>() else {
+        return;
+    };
+
+    let info = EventListenerInfo { event, module };
+    if registry.seen.insert(info.clone()) {
+        if let Some(hook) = &registry.hook {
+            hook(&info);
+        }
+    }
+}
+
+extension!(
+    init_events,
+    deps = [rustyscript],
+    ops = [op_events_listener_added, op_events_bind_dispatcher],
+    esm_entry_point = "ext:init_events/init_events.js",
+    esm = [ dir "src/ext/events", "init_events.js" ],
+);
+impl ExtensionTrait<()> for init_events {
+    fn init((): ()) -> Extension {
+        init_events::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_events::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{Error, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_event_listeners() {
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let hook_reported = reported.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            on_event_listener: Some(Box::new(move |info: &EventListenerInfo| {
+                hook_reported.borrow_mut().push(info.clone());
+            })),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let worker = runtime
+            .load_module(&Module::new(
+                "worker.js",
+                "
+                addEventListener('fetch', () => {});
+                addEventListener('fetch', () => {});
+                globalThis.addEventListener('scheduled', { handleEvent() {} });
+            ",
+            ))
+            .unwrap();
+        let consumer = runtime
+            .load_module(&Module::new(
+                "consumer.js",
+                "addEventListener('queue', () => {});",
+            ))
+            .unwrap();
+
+        assert_eq!(
+            runtime.module_event_listeners(&worker).unwrap(),
+            vec!["fetch", "scheduled"]
+        );
+        assert_eq!(
+            runtime.module_event_listeners(&consumer).unwrap(),
+            vec!["queue"]
+        );
+        assert_eq!(
+            runtime.event_listeners().unwrap(),
+            vec!["fetch", "queue", "scheduled"]
+        );
+
+        // Reported once per module and event type
+        let reported = reported.borrow();
+        assert_eq!(reported.len(), 3);
+        assert!(reported[0]
+            .module
+            .as_deref()
+            .unwrap()
+            .ends_with("worker.js"));
+        assert_eq!(reported[2].event, "queue");
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_dispatch_fetch_event() {
+        use deno_core::futures::{stream, StreamExt};
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "worker.js",
+                "
+                export let logged = [];
+                addEventListener('fetch', (event) => {
+                    const url = new URL(event.request.url);
+                    if (url.pathname === '/throws') throw new Error('handler failed');
+                    if (url.pathname === '/ignored') return;
+
+                    event.waitUntil(Promise.resolve().then(() => logged.push(url.pathname)));
+                    event.respondWith((async () => {
+                        const body = await event.request.text();
+                        return new Response(`${event.request.method} ${url.pathname} ${body}`, { status: 202 });
+                    })());
+                });
+            ",
+            ))
+            .unwrap();
+
+        let body = || stream::iter(vec![Ok::<_, Error>(b"hello".to_vec())]);
+        let request = http::Request::post("/echo").body(body()).unwrap();
+        let response = runtime.dispatch_fetch_event(request).unwrap();
+        assert_eq!(response.status(), 202);
+
+        runtime
+            .block_on_event_loop(Default::default(), None)
+            .unwrap();
+        let chunks = runtime
+            .tokio_runtime()
+            .block_on(response.into_body().collect::<Vec<_>>());
+        let text: Vec<u8> = chunks.into_iter().flat_map(Result::unwrap).collect();
+        assert_eq!(text, b"POST /echo hello");
+
+        let logged: Vec<String> = runtime.get_value(Some(&module), "logged").unwrap();
+        assert_eq!(logged, vec!["/echo"]);
+
+        let request = http::Request::get("/throws").body(body()).unwrap();
+        let error = runtime.dispatch_fetch_event(request).unwrap_err();
+        assert!(error.to_string().contains("handler failed"));
+
+        let request = http::Request::get("/ignored").body(body()).unwrap();
+        runtime
+            .dispatch_fetch_event(request)
+            .expect_err("Dispatched without a response");
+    }
+
+    #[test]
+    fn test_dispatch_event() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "consumer.js",
+                "
+                export let processed = [];
+                addEventListener('queue', async (event) => {
+                    await Promise.resolve();
+                    processed.push(...event.data.messages);
+                });
+                addEventListener('queue', (event) => {
+                    event.waitUntil(Promise.reject(new Error('ack failed')));
+                });
+                addEventListener('queue', () => { throw new Error('listener failed'); });
+            ",
+            ))
+            .unwrap();
+
+        let payload = crate::serde_json::json!({ "messages": ["a", "b"] });
+        let outcome = runtime.dispatch_event("queue", &payload).unwrap();
+        assert!(outcome.is_handled());
+        assert_eq!(outcome.listeners, 3);
+        assert_eq!(outcome.errors.len(), 2);
+
+        let errors: Vec<_> = outcome.errors.iter().map(ToString::to_string).collect();
+        assert!(errors[0].contains("listener failed"));
+        assert!(errors[1].contains("ack failed"));
+
+        let processed: Vec<String> = runtime.get_value(Some(&module), "processed").unwrap();
+        assert_eq!(processed, vec!["a", "b"]);
+
+        let outcome = runtime.dispatch_event("scheduled", &()).unwrap();
+        assert!(!outcome.is_handled());
+        assert!(outcome.is_ok());
+    }
+}
@@ -1,4 +1,15 @@
 import * as fs from "ext:deno_fs/30_fs.js";
+import { op_temp_dir_resolve } from "ext:core/ops";
+
+// Temp files and directories are created in the runtime's temp sandbox, if one is configured
+const sandboxed = (makeTemp) => (options = {}) => {
+    const dir = op_temp_dir_resolve(
+        options.dir ?? null,
+        options.prefix == null ? null : String(options.prefix),
+        options.suffix == null ? null : String(options.suffix),
+    );
+    return makeTemp(dir === null ? options : { ...options, dir });
+};
 
 globalThis.Deno.writeFileSync = fs.writeFileSync;
 globalThis.Deno.writeFile = fs.writeFile;
@@ -7,17 +18,17 @@ globalThis.Deno.writeTextFile = fs.writeTextFile;
 globalThis.Deno.readTextFile = fs.readTextFile;
 globalThis.Deno.readTextFileSync = fs.readTextFileSync;
 globalThis.Deno.readFile = fs.readFile;
-globalThis.Deno.readFileSync = fs.readFileSync;
+globalThis.Deno.readFileSync = fs.readFileSync;
 globalThis.Deno.chmodSync = fs.chmodSync;
 globalThis.Deno.chmod = fs.chmod;
 globalThis.Deno.chown = fs.chown;
 globalThis.Deno.chownSync = fs.chownSync;
 globalThis.Deno.copyFileSync = fs.copyFileSync;
 globalThis.Deno.cwd = fs.cwd;
-globalThis.Deno.makeTempDirSync = fs.makeTempDirSync;
-globalThis.Deno.makeTempDir = fs.makeTempDir;
-globalThis.Deno.makeTempFileSync = fs.makeTempFileSync;
-globalThis.Deno.makeTempFile = fs.makeTempFile;
+globalThis.Deno.makeTempDirSync = sandboxed(fs.makeTempDirSync);
+globalThis.Deno.makeTempDir = sandboxed(fs.makeTempDir);
+globalThis.Deno.makeTempFileSync = sandboxed(fs.makeTempFileSync);
+globalThis.Deno.makeTempFile = sandboxed(fs.makeTempFile);
 globalThis.Deno.mkdirSync = fs.mkdirSync;
 globalThis.Deno.mkdir = fs.mkdir;
 globalThis.Deno.chdir = fs.chdir;
@@ -6,9 +6,15 @@ use deno_permissions::{CheckedPath, PermissionCheckError, PermissionDeniedError}
 
 use super::{web::PermissionsContainer, ExtensionTrait};
 
+mod temp_dir;
+use temp_dir::op_temp_dir_resolve;
+pub use temp_dir::TempDirOptions;
+pub(crate) use temp_dir::TempSandbox;
+
 extension!(
     init_fs,
     deps = [rustyscript],
+    ops = [op_temp_dir_resolve],
     esm_entry_point = "ext:init_fs/init_fs.js",
     esm = [ dir "src/ext/fs", "init_fs.js" ],
 );
@@ -0,0 +1,210 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use deno_core::{op2, OpState};
+
+use crate::Error;
+
+/// Options for a per-runtime scratch directory backing `Deno.makeTempFile` and `Deno.makeTempDir`
+///
+/// See [`crate::RuntimeOptions::temp_dir`]
+#[derive(Debug, Clone, Default)]
+pub struct TempDirOptions {
+    /// The directory in which the runtime's scratch directory is created
+    ///
+    /// Defaults to the system temp directory
+    pub parent: Option<PathBuf>,
+
+    /// The maximum total size, in bytes, of the scratch directory's contents
+    ///
+    /// Checked whenever a new temp file or directory is created - creation fails once the cap
+    /// has been reached. Writes to already-open files are not interrupted
+    pub max_size: Option<u64>,
+}
+
+/// A scratch directory owned by a single runtime, deleted when the runtime is dropped
+#[derive(Debug)]
+pub(crate) struct TempSandbox {
+    path: PathBuf,
+    max_size: Option<u64>,
+}
+
+impl TempSandbox {
+    /// Create the scratch directory for the runtime with the given id
+    pub fn new(options: TempDirOptions, id: crate::RuntimeId) -> Result<Rc<Self>, Error> {
+        let parent = options.parent.unwrap_or_else(std::env::temp_dir);
+        let path = parent.join(format!("rustyscript-{}-{id}", std::process::id()));
+        std::fs::create_dir_all(&path)
+            .map_err(|e| Error::Runtime(format!("Could not create {}: {e}", path.display())))?;
+
+        Ok(Rc::new(Self {
+            path,
+            max_size: options.max_size,
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Total size, in bytes, of the files in the directory
+    pub fn usage(&self) -> u64 {
+        fn walk(path: &Path) -> u64 {
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return 0;
+            };
+
+            entries
+                .flatten()
+                .map(|entry| match entry.metadata() {
+                    Ok(meta) if meta.is_dir() => walk(&entry.path()),
+                    Ok(meta) => meta.len(),
+                    Err(_) => 0,
+                })
+                .sum()
+        }
+
+        walk(&self.path)
+    }
+
+    /// Resolves the `dir` option for a new temp file or directory against the sandbox
+    ///
+    /// The option must be a relative path that stays within the sandbox, and the `prefix` and
+    /// `suffix` options must not contain path separators or `..`, which would let the name escape it
+    fn resolve(
+        &self,
+        dir: Option<&str>,
+        prefix: Option<&str>,
+        suffix: Option<&str>,
+    ) -> Result<PathBuf, Error> {
+        for affix in [prefix, suffix].into_iter().flatten() {
+            if affix.contains(['/', '\\']) || affix.contains("..") {
+                return Err(Error::Runtime(format!(
+                    "Temp file names cannot contain path separators or `..`: {affix}"
+                )));
+            }
+        }
+
+        if let Some(max_size) = self.max_size {
+            if self.usage() >= max_size {
+                return Err(Error::Runtime(format!(
+                    "Temp directory is full ({max_size} bytes)"
+                )));
+            }
+        }
+
+        let Some(dir) = dir else {
+            return Ok(self.path.clone());
+        };
+
+        let dir = Path::new(dir);
+        let contained = dir
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !contained {
+            return Err(Error::Runtime(format!(
+                "Temp paths must be relative to the runtime's temp directory: {}",
+                dir.display()
+            )));
+        }
+
+        Ok(self.path.join(dir))
+    }
+}
+
+impl Drop for TempSandbox {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+/// Returns the directory a new temp file or directory should be created in,
+/// or `None` if the runtime has no temp sandbox
+#[op2]
+#[string]
+#[allow(clippy::needless_pass_by_value)]
+pub fn op_temp_dir_resolve(
+    state: &mut OpState,
+    #[serde] dir: Option<String>,
+    #[serde] prefix: Option<String>,
+    #[serde] suffix: Option<String>,
+) -> Result<Option<String>, Error> {
+    let Some(sandbox) = state.try_borrow::<Rc<TempSandbox>>() else {
+        return Ok(None);
+    };
+
+    let path = sandbox.resolve(dir.as_deref(), prefix.as_deref(), suffix.as_deref())?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_temp_dir() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            temp_dir: Some(TempDirOptions {
+                max_size: Some(16),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+        let root = runtime.temp_dir().unwrap().to_path_buf();
+        assert!(root.is_dir());
+
+        let file: String = runtime
+            .eval(
+                "
+                (() => {
+                    const path = Deno.makeTempFileSync({ prefix: 'a' });
+                    Deno.writeTextFileSync(path, 'x'.repeat(16));
+                    return path;
+                })()
+            ",
+            )
+            .unwrap();
+        assert!(Path::new(&file).starts_with(&root));
+
+        runtime
+            .eval::<String>("Deno.makeTempFileSync()")
+            .expect_err("Exceeded the size cap");
+        runtime
+            .eval::<String>("Deno.makeTempDirSync({ dir: '../escape' })")
+            .expect_err("Escaped the temp directory");
+
+        drop(runtime);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_temp_dir_names() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            temp_dir: Some(TempDirOptions::default()),
+            ..Default::default()
+        })
+        .unwrap();
+        let root = runtime.temp_dir().unwrap().to_path_buf();
+
+        let file: String = runtime
+            .eval("Deno.makeTempFileSync({ prefix: 'log-', suffix: '.txt' })")
+            .unwrap();
+        assert_eq!(Path::new(&file).parent(), Some(root.as_path()));
+
+        for options in [
+            "{ prefix: '../escape' }",
+            "{ prefix: '..' }",
+            "{ suffix: '/../../escape' }",
+            "{ suffix: '\\\\escape' }",
+        ] {
+            let error = runtime
+                .eval::<String>(format!("Deno.makeTempFileSync({options})"))
+                .unwrap_err();
+            assert!(error.to_string().contains("path separators"), "{options}");
+        }
+    }
+}
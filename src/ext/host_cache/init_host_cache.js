@@ -0,0 +1,10 @@
+import { op_cache_get, op_cache_set, op_cache_delete, op_cache_expire } from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+// TTLs are in milliseconds
+namespaces.cache = Object.freeze({
+    get: (key) => op_cache_get(String(key)),
+    set: (key, value, { ttl } = {}) => op_cache_set(String(key), value ?? null, ttl ?? null),
+    delete: (key) => op_cache_delete(String(key)),
+    expire: (key, ttl) => op_cache_expire(String(key), Number(ttl)),
+});
@@ -0,0 +1,339 @@
+//! Host-managed key-value caches, exposed to scripts as `rustyscript.cache`
+//!
+//! The host supplies the backend - Redis, memcached, or the in-process [`MemoryCache`] - through a
+//! [`CacheProvider`]. Every key a script uses is prefixed with the runtime's namespace before it
+//! reaches the backend, so tenants sharing a backend cannot read or overwrite each other's entries
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use deno_core::{extension, op2, serde_json, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// The longest TTL a script can set - longer ones are clamped to it
+pub const MAX_CACHE_TTL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// A key-value backend for `rustyscript.cache`
+///
+/// Keys arrive already namespaced - implementations can store them as-is
+#[async_trait::async_trait(?Send)]
+pub trait CacheProvider {
+    /// Get the value stored under a key, if it exists and has not expired
+    ///
+    /// # Errors
+    /// Should return an error if the backend cannot be reached
+    async fn get(&self, key: &str) -> Result<Option<serde_json::Value>, Error>;
+
+    /// Store a value under a key, replacing any existing value
+    ///
+    /// If `ttl` is set, the entry expires after that long - at most [`MAX_CACHE_TTL`]
+    ///
+    /// # Errors
+    /// Should return an error if the backend cannot be reached
+    async fn set(
+        &self,
+        key: &str,
+        value: serde_json::Value,
+        ttl: Option<Duration>,
+    ) -> Result<(), Error>;
+
+    /// Remove a key, returning true if it existed
+    ///
+    /// # Errors
+    /// Should return an error if the backend cannot be reached
+    async fn delete(&self, key: &str) -> Result<bool, Error>;
+
+    /// Set a key to expire after `ttl`, returning true if it existed - `ttl` is at most [`MAX_CACHE_TTL`]
+    ///
+    /// # Errors
+    /// Should return an error if the backend cannot be reached
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error>;
+}
+
+/// A simple in-process [`CacheProvider`]
+///
+/// Expired entries are dropped lazily, when next accessed
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: RefCell<HashMap<String, (serde_json::Value, Option<Instant>)>>,
+}
+
+impl MemoryCache {
+    /// Create a new, empty cache
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of live entries in the cache, across all namespaces
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.entries
+            .borrow()
+            .values()
+            .filter(|(_, expires)| !expires.is_some_and(|expires| expires <= now))
+            .count()
+    }
+
+    /// Returns true if the cache has no live entries
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns when an entry set now with `ttl` expires, or `None` if that is too far away to represent
+    fn expiry(ttl: Duration) -> Option<Instant> {
+        Instant::now().checked_add(ttl.min(MAX_CACHE_TTL))
+    }
+
+    /// Removes the entry for a key if it has expired, returning true if a live entry remains
+    fn purge(&self, key: &str) -> bool {
+        let mut entries = self.entries.borrow_mut();
+        match entries.get(key) {
+            Some((_, Some(expires))) if *expires <= Instant::now() => {
+                entries.remove(key);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl CacheProvider for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<serde_json::Value>, Error> {
+        if !self.purge(key) {
+            return Ok(None);
+        }
+        Ok(self
+            .entries
+            .borrow()
+            .get(key)
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: serde_json::Value,
+        ttl: Option<Duration>,
+    ) -> Result<(), Error> {
+        let expires = ttl.and_then(Self::expiry);
+        self.entries
+            .borrow_mut()
+            .insert(key.to_string(), (value, expires));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, Error> {
+        let live = self.purge(key);
+        self.entries.borrow_mut().remove(key);
+        Ok(live)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        if !self.purge(key) {
+            return Ok(false);
+        }
+        if let Some((_, expires)) = self.entries.borrow_mut().get_mut(key) {
+            *expires = Self::expiry(ttl);
+        }
+        Ok(true)
+    }
+}
+
+/// The backend and namespace used by a runtime's `rustyscript.cache`
+///
+/// See [`crate::ExtensionOptions::host_cache`]
+#[derive(Clone)]
+pub struct HostCache {
+    provider: Rc<dyn CacheProvider>,
+    namespace: String,
+}
+
+impl HostCache {
+    /// Scripts using this cache see only the keys under `namespace`
+    ///
+    /// Give each tenant sharing a provider its own namespace
+    ///
+    /// # Errors
+    /// Will return an error if the namespace is empty or contains `:`, the separator placed
+    /// between it and each key - otherwise one namespace could be a prefix of another
+    pub fn new(provider: Rc<dyn CacheProvider>, namespace: impl ToString) -> Result<Self, Error> {
+        let namespace = namespace.to_string();
+        if namespace.is_empty() || namespace.contains(':') {
+            return Err(Error::Runtime(format!(
+                "Invalid cache namespace `{namespace}`: must be non-empty, without `:`"
+            )));
+        }
+        Ok(Self {
+            provider,
+            namespace,
+        })
+    }
+
+    /// Returns the namespace keys are stored under
+    #[must_use]
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The key as stored in the backend
+    fn key(&self, key: &str) -> Result<String, Error> {
+        if key.is_empty() {
+            return Err(Error::Runtime("Cache keys cannot be empty".to_string()));
+        }
+        Ok(format!("{}:{key}", self.namespace))
+    }
+}
+
+fn host_cache(state: &Rc<RefCell<OpState>>) -> Result<HostCache, Error> {
+    state
+        .try_borrow()?
+        .try_borrow::<HostCache>()
+        .cloned()
+        .ok_or_else(|| Error::Runtime("No cache provider is configured".to_string()))
+}
+
+fn ttl(ms: Option<f64>) -> Result<Option<Duration>, Error> {
+    ms.map(|ms| {
+        Duration::try_from_secs_f64(ms / 1000.0)
+            .map(|ttl| ttl.min(MAX_CACHE_TTL))
+            .map_err(|_| Error::Runtime(format!("Invalid cache TTL: {ms}ms")))
+    })
+    .transpose()
+}
+
+#[op2(async)]
+#[serde]
+async fn op_cache_get(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<Option<serde_json::Value>, Error> {
+    let cache = host_cache(&state)?;
+    cache.provider.get(&cache.key(&key)?).await
+}
+
+#[op2(async)]
+async fn op_cache_set(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+    #[serde] value: serde_json::Value,
+    #[serde] ttl_ms: Option<f64>,
+) -> Result<(), Error> {
+    let cache = host_cache(&state)?;
+    let ttl = ttl(ttl_ms)?;
+    cache.provider.set(&cache.key(&key)?, value, ttl).await
+}
+
+#[op2(async)]
+async fn op_cache_delete(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<bool, Error> {
+    let cache = host_cache(&state)?;
+    cache.provider.delete(&cache.key(&key)?).await
+}
+
+#[op2(async)]
+async fn op_cache_expire(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+    ttl_ms: f64,
+) -> Result<bool, Error> {
+    let cache = host_cache(&state)?;
+    let ttl = ttl(Some(ttl_ms))?.unwrap_or_default();
+    cache.provider.expire(&cache.key(&key)?, ttl).await
+}
+
+extension!(
+    init_host_cache,
+    deps = [rustyscript],
+    ops = [op_cache_get, op_cache_set, op_cache_delete, op_cache_expire],
+    esm_entry_point = "ext:init_host_cache/init_host_cache.js",
+    esm = [ dir "src/ext/host_cache", "init_host_cache.js" ],
+    options = {
+        cache: Option<HostCache>
+    },
+    state = |state, config| {
+        if let Some(cache) = config.cache {
+            state.put(cache);
+        }
+    },
+);
+impl ExtensionTrait<Option<HostCache>> for init_host_cache {
+    fn init(cache: Option<HostCache>) -> Extension {
+        init_host_cache::init(cache)
+    }
+}
+
+pub fn extensions(cache: Option<HostCache>, is_snapshot: bool) -> Vec<Extension> {
+    vec![init_host_cache::build(cache, is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ExtensionOptions, Module, Runtime, RuntimeOptions};
+
+    fn tenant(backend: &Rc<MemoryCache>, namespace: &str) -> Runtime {
+        Runtime::new(RuntimeOptions {
+            extension_options: ExtensionOptions {
+                host_cache: Some(HostCache::new(backend.clone(), namespace).unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_host_cache() {
+        let backend = Rc::new(MemoryCache::new());
+        let mut a = tenant(&backend, "a");
+        let mut b = tenant(&backend, "b");
+        assert!(HostCache::new(backend.clone(), "a:b").is_err());
+
+        let module = a
+            .load_module(&Module::new(
+                "test.js",
+                "
+                await rustyscript.cache.set('user', { name: 'alice' });
+                await rustyscript.cache.set('session', 'xyz', { ttl: 10 });
+                export const user = await rustyscript.cache.get('user');
+                export const expiring = await rustyscript.cache.expire('user', 60_000);
+                export const deleted = await rustyscript.cache.delete('nothing');
+                await rustyscript.cache.set('forever', 1, { ttl: Number.MAX_SAFE_INTEGER });
+                await rustyscript.cache.expire('forever', Number.MAX_SAFE_INTEGER);
+            ",
+            ))
+            .unwrap();
+
+        let user: serde_json::Value = a.get_value(Some(&module), "user").unwrap();
+        assert_eq!(user, serde_json::json!({ "name": "alice" }));
+        assert!(a.get_value::<bool>(Some(&module), "expiring").unwrap());
+        assert!(!a.get_value::<bool>(Some(&module), "deleted").unwrap());
+        assert_eq!(backend.len(), 3);
+
+        // Another tenant cannot see the entries
+        let other: Option<serde_json::Value> = b.eval("rustyscript.cache.get('user')").unwrap();
+        assert!(other.is_none());
+        b.eval::<()>("rustyscript.cache.set('user', 'bob')")
+            .unwrap();
+        let user: serde_json::Value = a.eval("rustyscript.cache.get('user')").unwrap();
+        assert_eq!(user, serde_json::json!({ "name": "alice" }));
+
+        // Expiry
+        std::thread::sleep(Duration::from_millis(20));
+        let session: Option<String> = a.eval("rustyscript.cache.get('session')").unwrap();
+        assert!(session.is_none());
+        assert_eq!(backend.len(), 3);
+    }
+}
@@ -2,6 +2,10 @@ use deno_core::{extension, Extension};
 
 use super::ExtensionTrait;
 
+mod stdio;
+pub use stdio::VirtualStdio;
+use stdio::{op_print_virtual, ConsolePipes};
+
 #[cfg(windows)]
 mod tty_windows;
 #[cfg(windows)]
@@ -17,10 +21,18 @@ extension!(
     deps = [rustyscript],
     esm_entry_point = "ext:init_io/init_io.js",
     esm = [ dir "src/ext/io", "init_io.js" ],
+    options = {
+        console: ConsolePipes
+    },
+    state = |state, config| state.put(config.console),
+    middleware = |op| match op.name {
+        "op_print" => op.with_implementation_from(&op_print_virtual()),
+        _ => op,
+    }
 );
-impl ExtensionTrait<()> for init_io {
-    fn init((): ()) -> Extension {
-        init_io::init()
+impl ExtensionTrait<ConsolePipes> for init_io {
+    fn init(console: ConsolePipes) -> Extension {
+        init_io::init(console)
     }
 }
 impl ExtensionTrait<Option<deno_io::Stdio>> for deno_io::deno_io {
@@ -34,10 +46,15 @@ impl ExtensionTrait<()> for tty::deno_tty {
     }
 }
 
-pub fn extensions(pipes: Option<deno_io::Stdio>, is_snapshot: bool) -> Vec<Extension> {
+pub fn extensions(
+    pipes: Option<deno_io::Stdio>,
+    stdio: Option<&VirtualStdio>,
+    is_snapshot: bool,
+) -> Vec<Extension> {
+    let console = stdio.map(|s| s.console.clone()).unwrap_or_default();
     vec![
         deno_io::deno_io::build(pipes, is_snapshot),
         tty::deno_tty::build((), is_snapshot),
-        init_io::build((), is_snapshot),
+        init_io::build(console, is_snapshot),
     ]
 }
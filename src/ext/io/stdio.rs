@@ -0,0 +1,249 @@
+use std::{fs::File, io::Write, sync::Arc};
+
+use deno_core::{op2, OpState};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Error;
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::Runtime(e.to_string())
+}
+
+type HostReader = Box<dyn AsyncRead + Send + Unpin>;
+type HostWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Host-provided endpoints for the script's standard streams
+///
+/// Any stream given here replaces the corresponding process stream for `Deno.stdin`,
+/// `Deno.stdout` and `Deno.stderr` - and for `console` output, which is written to the
+/// virtual stdout and stderr. Streams left unset keep the behaviour of `io_pipes`.
+///
+/// Data is moved between the host endpoints and the script by tasks on the runtime's tokio runtime,
+/// so the endpoints can be any tokio `AsyncRead`/`AsyncWrite` - a socket, a channel, or an in-memory buffer
+///
+/// ```rust
+/// use rustyscript::{Runtime, RuntimeOptions, VirtualStdio, Undefined};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let (stdout, mut captured) = tokio::io::duplex(1024);
+///
+/// let mut options = RuntimeOptions::default();
+/// options.extension_options.stdio = Some(VirtualStdio::new().with_stdout(stdout));
+/// let mut runtime = Runtime::new(options)?;
+///
+/// runtime.eval::<Undefined>("console.log('Hello')")?;
+///
+/// let mut output = vec![0; 6];
+/// runtime
+///     .tokio_runtime()
+///     .block_on(tokio::io::AsyncReadExt::read_exact(&mut captured, &mut output))
+///     .unwrap();
+/// assert_eq!(output, b"Hello\n");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct VirtualStdio {
+    stdin: Option<HostReader>,
+    stdout: Option<HostWriter>,
+    stderr: Option<HostWriter>,
+    pub(crate) console: ConsolePipes,
+}
+
+impl VirtualStdio {
+    /// Create a new set of virtual streams, with all streams left unset
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the source the script's stdin reads from
+    #[must_use]
+    pub fn with_stdin(mut self, stdin: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        self.stdin = Some(Box::new(stdin));
+        self
+    }
+
+    /// Set the destination for the script's stdout, including `console.log`
+    #[must_use]
+    pub fn with_stdout(mut self, stdout: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        self.stdout = Some(Box::new(stdout));
+        self
+    }
+
+    /// Set the destination for the script's stderr, including `console.error`
+    #[must_use]
+    pub fn with_stderr(mut self, stderr: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        self.stderr = Some(Box::new(stderr));
+        self
+    }
+
+    /// Connects the host endpoints to OS pipes the script can use, starting the copy tasks
+    ///
+    /// Streams that were not set are taken from `fallback`
+    pub(crate) fn attach(
+        &mut self,
+        handle: &tokio::runtime::Handle,
+        fallback: Option<deno_io::Stdio>,
+    ) -> Result<deno_io::Stdio, Error> {
+        let mut stdio = fallback.unwrap_or_default();
+
+        if let Some(mut source) = self.stdin.take() {
+            let (reader, writer) = std::io::pipe().map_err(io_error)?;
+            stdio.stdin = deno_io::StdioPipe::file(into_file(reader));
+
+            let mut writer = tokio::fs::File::from_std(into_file(writer));
+            handle.spawn(async move {
+                // Dropping the writer signals EOF to the script
+                tokio::io::copy(&mut source, &mut writer).await.ok();
+            });
+        }
+
+        if let Some(dest) = self.stdout.take() {
+            let writer = forward(handle, dest)?;
+            self.console.stdout = Some(Arc::new(writer.try_clone().map_err(io_error)?));
+            stdio.stdout = deno_io::StdioPipe::file(writer);
+        }
+
+        if let Some(dest) = self.stderr.take() {
+            let writer = forward(handle, dest)?;
+            self.console.stderr = Some(Arc::new(writer.try_clone().map_err(io_error)?));
+            stdio.stderr = deno_io::StdioPipe::file(writer);
+        }
+
+        Ok(stdio)
+    }
+}
+
+impl std::fmt::Debug for VirtualStdio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualStdio")
+            .field("stdin", &self.stdin.is_some())
+            .field("stdout", &self.stdout.is_some())
+            .field("stderr", &self.stderr.is_some())
+            .finish()
+    }
+}
+
+/// Creates a pipe whose contents are copied to `dest` as they are written, returning the write end
+fn forward(handle: &tokio::runtime::Handle, mut dest: HostWriter) -> Result<File, Error> {
+    let (reader, writer) = std::io::pipe().map_err(io_error)?;
+    let mut reader = tokio::fs::File::from_std(into_file(reader));
+    handle.spawn(async move {
+        // Flush every chunk so the host sees output as it is produced
+        let mut buf = vec![0; 8192];
+        while let Ok(n @ 1..) = reader.read(&mut buf).await {
+            if dest.write_all(&buf[..n]).await.is_err() || dest.flush().await.is_err() {
+                break;
+            }
+        }
+        dest.shutdown().await.ok();
+    });
+
+    Ok(into_file(writer))
+}
+
+#[cfg(unix)]
+fn into_file(pipe: impl Into<std::os::fd::OwnedFd>) -> File {
+    File::from(pipe.into())
+}
+
+#[cfg(windows)]
+fn into_file(pipe: impl Into<std::os::windows::io::OwnedHandle>) -> File {
+    File::from(pipe.into())
+}
+
+/// Destinations for `console` output, when the standard streams are virtualized
+#[derive(Clone, Default)]
+pub(crate) struct ConsolePipes {
+    stdout: Option<Arc<File>>,
+    stderr: Option<Arc<File>>,
+}
+
+/// Replaces `deno_core`'s `op_print`, sending output to the virtual streams when they are set
+#[op2(fast)]
+pub fn op_print_virtual(
+    state: &mut OpState,
+    #[string] msg: &str,
+    is_err: bool,
+) -> Result<(), Error> {
+    let pipes = state.borrow::<ConsolePipes>();
+    let pipe = if is_err { &pipes.stderr } else { &pipes.stdout };
+
+    match pipe {
+        Some(pipe) => pipe.as_ref().write_all(msg.as_bytes()).map_err(io_error)?,
+        None if is_err => {
+            let mut stderr = std::io::stderr();
+            stderr.write_all(msg.as_bytes()).map_err(io_error)?;
+            stderr.flush().map_err(io_error)?;
+        }
+        None => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(msg.as_bytes()).map_err(io_error)?;
+            stdout.flush().map_err(io_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions, Undefined};
+
+    #[test]
+    fn test_virtual_stdio() {
+        let (stdin, mut input) = tokio::io::duplex(1024);
+        let (stdout, mut output) = tokio::io::duplex(1024);
+        let (stderr, mut errors) = tokio::io::duplex(1024);
+
+        let mut options = RuntimeOptions::default();
+        options.extension_options.stdio = Some(
+            VirtualStdio::new()
+                .with_stdin(stdin)
+                .with_stdout(stdout)
+                .with_stderr(stderr),
+        );
+        let mut runtime = Runtime::new(options).unwrap();
+
+        runtime
+            .tokio_runtime()
+            .block_on(async { input.write_all(b"ping").await })
+            .unwrap();
+        drop(input);
+
+        let value: String = runtime
+            .eval(
+                "
+                (async () => {
+                    const buf = new Uint8Array(4);
+                    await Deno.stdin.read(buf);
+                    const text = new TextDecoder().decode(buf);
+                    console.log(text);
+                    console.error('oops');
+                    await Deno.stdout.write(new TextEncoder().encode('pong'));
+                    return text;
+                })()
+            ",
+            )
+            .unwrap();
+        assert_eq!(value, "ping");
+
+        let mut buf = vec![0; 9];
+        runtime
+            .tokio_runtime()
+            .block_on(async { output.read_exact(&mut buf).await })
+            .unwrap();
+        assert_eq!(buf, b"ping\npong");
+
+        let mut buf = vec![0; 5];
+        runtime
+            .tokio_runtime()
+            .block_on(async { errors.read_exact(&mut buf).await })
+            .unwrap();
+        assert_eq!(buf, b"oops\n");
+
+        runtime.eval::<Undefined>("console.log('done')").unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+import {
+    op_json_parser_new, op_json_parser_feed, op_json_parser_finish,
+    op_json_source_read, op_json_sink_write, op_json_sink_close,
+} from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+const toBytes = (chunk) => (typeof chunk === 'string' ? Deno.core.encode(chunk) : chunk);
+
+// Turns chunks of JSON text into values as each is completed
+class Parser {
+    #rid = op_json_parser_new();
+
+    // Returns the values completed by this chunk - a string, or UTF-8 bytes
+    feed(chunk) {
+        return op_json_parser_feed(this.#rid, toBytes(chunk));
+    }
+
+    // Returns the remaining values, and throws if the text ended partway through one
+    finish() {
+        return op_json_parser_finish(this.#rid);
+    }
+}
+
+namespaces.json = Object.freeze({
+    Parser,
+
+    // Yields the values of a host-registered source, reading it a chunk at a time
+    *read(name) {
+        name = String(name);
+        for (let values; (values = op_json_source_read(name)) !== null;) {
+            yield* values;
+        }
+    },
+
+    // Writes each of the values to a host-registered sink
+    write(name, values) {
+        name = String(name);
+        for (const value of values) op_json_sink_write(name, value);
+    },
+
+    // Closes a host-registered sink, returning the number of values written to it
+    close: (name) => op_json_sink_close(String(name)),
+
+    // Yields an iterable of values as the text of a JSON array, in chunks of about `chunkSize` characters
+    *stringify(values, chunkSize = 64 * 1024) {
+        let buffer = '[';
+        let first = true;
+        for (const value of values) {
+            buffer += (first ? '' : ',') + (JSON.stringify(value) ?? 'null');
+            first = false;
+            if (buffer.length >= chunkSize) {
+                yield buffer;
+                buffer = '';
+            }
+        }
+        yield buffer + ']';
+    },
+});
@@ -0,0 +1,455 @@
+//! Incremental JSON, exposed to scripts as `rustyscript.json`
+//!
+//! Multi-hundred-megabyte datasets need never exist as one contiguous string in V8:
+//! - `rustyscript.json.Parser` turns chunks of text into values as soon as each is complete
+//! - `rustyscript.json.stringify` serializes an iterable of values a chunk at a time
+//! - Hosts can stream values to and from scripts through named sources and sinks - see
+//!   [`crate::Runtime::register_json_source`] and [`crate::Runtime::register_json_sink`]
+//!
+//! Streams are either a single top-level array, whose elements are yielded one by one, or a
+//! sequence of values separated by whitespace, such as newline-delimited JSON. Each value is
+//! buffered until it is complete, so streaming pays off for payloads made of many values
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use deno_core::{extension, op2, serde_json, Extension, OpState, Resource, ResourceId};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// How many bytes are read from a source at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How values written to a JSON sink are laid out - see [`crate::Runtime::register_json_sink`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum JsonFormat {
+    /// One value per line - newline-delimited JSON
+    #[default]
+    Lines,
+
+    /// A single top-level array, closed when the script closes the sink
+    Array,
+}
+
+/// Where a [`StreamParser`] is in the stream's framing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// Nothing but whitespace seen yet
+    Start,
+
+    /// A sequence of whitespace-separated values
+    Sequence,
+
+    /// Inside a top-level array - true once an element has been read, and a `,` or `]` is due
+    Array(bool),
+
+    /// After the closing `]` of a top-level array
+    Closed,
+}
+
+/// Parses a stream of JSON text as it arrives, yielding each value once it is complete
+#[derive(Debug)]
+pub(crate) struct StreamParser {
+    buffer: Vec<u8>,
+    framing: Framing,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            framing: Framing::Start,
+        }
+    }
+}
+
+impl StreamParser {
+    /// Adds a chunk of text, returning the values it completed
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<serde_json::Value>, Error> {
+        self.buffer.extend_from_slice(chunk);
+        self.parse(false)
+    }
+
+    /// Ends the stream, returning the last of its values
+    ///
+    /// Fails if the text ends partway through a value, or an array is left open
+    pub fn finish(&mut self) -> Result<Vec<serde_json::Value>, Error> {
+        let values = self.parse(true)?;
+        if !self.buffer.iter().all(u8::is_ascii_whitespace) {
+            return Err(Error::JsonDecode(
+                "Unexpected end of JSON input".to_string(),
+            ));
+        }
+        if matches!(self.framing, Framing::Array(_)) {
+            return Err(Error::JsonDecode("Unterminated JSON array".to_string()));
+        }
+        Ok(values)
+    }
+
+    fn parse(&mut self, last: bool) -> Result<Vec<serde_json::Value>, Error> {
+        let mut values = vec![];
+        let mut pos = 0;
+        loop {
+            while self.buffer.get(pos).is_some_and(u8::is_ascii_whitespace) {
+                pos += 1;
+            }
+            let Some(&next) = self.buffer.get(pos) else {
+                break;
+            };
+
+            match (self.framing, next) {
+                (Framing::Start, b'[') => {
+                    self.framing = Framing::Array(false);
+                    pos += 1;
+                    continue;
+                }
+                (Framing::Start, _) => self.framing = Framing::Sequence,
+
+                (Framing::Array(_), b']') => {
+                    self.framing = Framing::Closed;
+                    pos += 1;
+                    continue;
+                }
+                (Framing::Array(true), b',') => {
+                    self.framing = Framing::Array(false);
+                    pos += 1;
+                    continue;
+                }
+                (Framing::Array(true), _) => {
+                    return Err(Error::JsonDecode(format!(
+                        "Expected ',' or ']' in JSON array, found '{}'",
+                        char::from(next)
+                    )));
+                }
+
+                (Framing::Closed, _) => {
+                    return Err(Error::JsonDecode(
+                        "Unexpected data after the end of a JSON array".to_string(),
+                    ));
+                }
+                (Framing::Sequence | Framing::Array(false), _) => {}
+            }
+
+            let mut stream = serde_json::Deserializer::from_slice(&self.buffer[pos..]).into_iter();
+            let value = match stream.next() {
+                Some(Ok(value)) => value,
+                Some(Err(e)) if e.is_eof() => break,
+                Some(Err(e)) => return Err(e.into()),
+                None => break,
+            };
+
+            // A number ending with the buffer may have more digits to come
+            let end = pos + stream.byte_offset();
+            if value.is_number() && end == self.buffer.len() && !last {
+                break;
+            }
+
+            values.push(value);
+            pos = end;
+            if let Framing::Array(_) = self.framing {
+                self.framing = Framing::Array(true);
+            }
+        }
+
+        self.buffer.drain(..pos);
+        Ok(values)
+    }
+}
+
+/// A script's `rustyscript.json.Parser`
+struct ParserResource(RefCell<StreamParser>);
+impl Resource for ParserResource {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "rustyscriptJsonParser".into()
+    }
+}
+
+/// A host-provided stream of values for scripts to read
+struct JsonSource {
+    reader: Box<dyn Read>,
+    parser: StreamParser,
+    exhausted: bool,
+}
+
+impl JsonSource {
+    /// Reads until at least one value is complete - returns `None` once the source is exhausted
+    fn next(&mut self) -> Result<Option<Vec<serde_json::Value>>, Error> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        while !self.exhausted {
+            let read = self.reader.read(&mut chunk)?;
+            let values = if read == 0 {
+                self.exhausted = true;
+                self.parser.finish()?
+            } else {
+                self.parser.feed(&chunk[..read])?
+            };
+
+            if !values.is_empty() {
+                return Ok(Some(values));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A host-provided destination for values written by scripts
+struct JsonSink {
+    writer: Box<dyn Write>,
+    format: JsonFormat,
+    written: usize,
+}
+
+impl JsonSink {
+    fn write(&mut self, value: &serde_json::Value) -> Result<(), Error> {
+        if self.format == JsonFormat::Array {
+            self.writer
+                .write_all(if self.written == 0 { b"[" } else { b"," })?;
+        }
+        serde_json::to_writer(&mut self.writer, value)?;
+        if self.format == JsonFormat::Lines {
+            self.writer.write_all(b"\n")?;
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<usize, Error> {
+        if self.format == JsonFormat::Array {
+            self.writer
+                .write_all(if self.written == 0 { b"[]" } else { b"]" })?;
+        }
+        self.writer.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// The sources and sinks registered with a runtime
+#[derive(Default)]
+pub(crate) struct JsonStreams {
+    sources: HashMap<String, JsonSource>,
+    sinks: HashMap<String, JsonSink>,
+}
+
+impl JsonStreams {
+    pub fn add_source(&mut self, name: &str, reader: Box<dyn Read>) {
+        let source = JsonSource {
+            reader,
+            parser: StreamParser::default(),
+            exhausted: false,
+        };
+        self.sources.insert(name.to_string(), source);
+    }
+
+    pub fn add_sink(&mut self, name: &str, writer: Box<dyn Write>, format: JsonFormat) {
+        let sink = JsonSink {
+            writer,
+            format,
+            written: 0,
+        };
+        self.sinks.insert(name.to_string(), sink);
+    }
+}
+
+#[op2(fast)]
+#[smi]
+fn op_json_parser_new(state: &mut OpState) -> ResourceId {
+    state
+        .resource_table
+        .add(ParserResource(RefCell::new(StreamParser::default())))
+}
+
+/// Feeds a chunk of text into a parser, returning the values it completed
+#[op2]
+#[serde]
+fn op_json_parser_feed(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[buffer] chunk: &[u8],
+) -> Result<Vec<serde_json::Value>, Error> {
+    let resource = state
+        .resource_table
+        .get::<ParserResource>(rid)
+        .map_err(|_| Error::Runtime("JSON parser is closed".to_string()))?;
+    let values = resource.0.borrow_mut().feed(chunk);
+    values
+}
+
+/// Ends a parser's stream, returning its remaining values
+#[op2]
+#[serde]
+fn op_json_parser_finish(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<Vec<serde_json::Value>, Error> {
+    let resource = state
+        .resource_table
+        .take::<ParserResource>(rid)
+        .map_err(|_| Error::Runtime("JSON parser is closed".to_string()))?;
+    let values = resource.0.borrow_mut().finish();
+    values
+}
+
+/// Reads the next values from a host source - `null` once it is exhausted
+#[op2]
+#[serde]
+fn op_json_source_read(
+    state: &mut OpState,
+    #[string] name: &str,
+) -> Result<Option<Vec<serde_json::Value>>, Error> {
+    let source = state
+        .try_borrow_mut::<JsonStreams>()
+        .and_then(|streams| streams.sources.get_mut(name))
+        .ok_or_else(|| Error::Runtime(format!("No JSON source named `{name}` is registered")))?;
+
+    let values = source.next()?;
+    if values.is_none() {
+        state.borrow_mut::<JsonStreams>().sources.remove(name);
+    }
+    Ok(values)
+}
+
+#[op2]
+fn op_json_sink_write(
+    state: &mut OpState,
+    #[string] name: &str,
+    #[serde] value: serde_json::Value,
+) -> Result<(), Error> {
+    state
+        .try_borrow_mut::<JsonStreams>()
+        .and_then(|streams| streams.sinks.get_mut(name))
+        .ok_or_else(|| Error::Runtime(format!("No JSON sink named `{name}` is registered")))?
+        .write(&value)
+}
+
+/// Closes a host sink, returning the number of values written to it
+#[op2(fast)]
+#[smi]
+fn op_json_sink_close(state: &mut OpState, #[string] name: &str) -> Result<u32, Error> {
+    let sink = state
+        .try_borrow_mut::<JsonStreams>()
+        .and_then(|streams| streams.sinks.remove(name))
+        .ok_or_else(|| Error::Runtime(format!("No JSON sink named `{name}` is registered")))?;
+    let written = sink.close()?;
+    Ok(u32::try_from(written).unwrap_or(u32::MAX))
+}
+
+extension!(
+    init_json,
+    deps = [rustyscript],
+    ops = [
+        op_json_parser_new,
+        op_json_parser_feed,
+        op_json_parser_finish,
+        op_json_source_read,
+        op_json_sink_write,
+        op_json_sink_close,
+    ],
+    esm_entry_point = "ext:init_json/init_json.js",
+    esm = [ dir "src/ext/json", "init_json.js" ],
+);
+impl ExtensionTrait<()> for init_json {
+    fn init((): ()) -> Extension {
+        init_json::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_json::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{serde_json::json, Module, Runtime, RuntimeOptions};
+
+    /// A writer the test can read back from
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_parser() {
+        let mut parser = StreamParser::default();
+        assert_eq!(
+            parser.feed(b" [ {\"a\": 1}, 2").unwrap(),
+            vec![json!({ "a": 1 })]
+        );
+        assert_eq!(parser.feed(b"3, \"x").unwrap(), vec![json!(23)]);
+        assert_eq!(parser.feed(b"y\"]").unwrap(), vec![json!("xy")]);
+        assert!(parser.finish().unwrap().is_empty());
+
+        let mut parser = StreamParser::default();
+        assert_eq!(
+            parser.feed(b"1\ntrue\n{").unwrap(),
+            vec![json!(1), json!(true)]
+        );
+        parser.finish().unwrap_err();
+
+        let mut parser = StreamParser::default();
+        parser.feed(b"[1 2]").unwrap_err();
+    }
+
+    #[test]
+    fn test_json_streams() {
+        let output = Shared::default();
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+
+        let input: Vec<u8> = (0..10_000)
+            .map(|i| format!("{{\"id\":{i}}}\n"))
+            .collect::<String>()
+            .into_bytes();
+        runtime
+            .register_json_source("users", std::io::Cursor::new(input))
+            .unwrap();
+        runtime
+            .register_json_sink("ids", output.clone(), JsonFormat::Array)
+            .unwrap();
+
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                let count = 0;
+                for (const user of rustyscript.json.read('users')) {
+                    if (user.id % 1000 === 0) rustyscript.json.write('ids', [user.id]);
+                    count++;
+                }
+                export const written = rustyscript.json.close('ids');
+                export { count };
+
+                const parser = new rustyscript.json.Parser();
+                export const parsed = [...parser.feed('[{\"a\": [1, 2'), ...parser.feed(']}, \"b\"]'), ...parser.finish()];
+                export const text = [...rustyscript.json.stringify([1, { b: 2 }], 4)];
+                ",
+            ))
+            .unwrap();
+
+        let count: usize = runtime.get_value(Some(&module), "count").unwrap();
+        assert_eq!(count, 10_000);
+        let written: usize = runtime.get_value(Some(&module), "written").unwrap();
+        assert_eq!(written, 10);
+
+        let ids: Vec<usize> = serde_json::from_slice(&output.0.borrow()).unwrap();
+        assert_eq!(ids, (0..10).map(|i| i * 1000).collect::<Vec<_>>());
+
+        let parsed: serde_json::Value = runtime.get_value(Some(&module), "parsed").unwrap();
+        assert_eq!(parsed, json!([{ "a": [1, 2] }, "b"]));
+        let text: Vec<String> = runtime.get_value(Some(&module), "text").unwrap();
+        assert_eq!(text.concat(), "[1,{\"b\":2}]");
+        assert!(text.len() > 1);
+    }
+}
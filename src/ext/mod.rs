@@ -8,6 +8,23 @@ use deno_core::{
 
 pub mod rustyscript;
 
+/// Stores the JS functions an extension binds for the host to call, refusing to replace them
+///
+/// The `op_*_bind_*` ops stay reachable through `Deno.core.ops` once the extension has loaded,
+/// so without this a script could swap in its own functions
+pub(crate) fn bind_once<T: 'static>(
+    state: &mut deno_core::OpState,
+    name: &str,
+    value: T,
+) -> Result<(), crate::Error> {
+    if state.has::<T>() {
+        return Err(crate::Error::Runtime(format!("{name} is already bound")));
+    }
+
+    state.put(value);
+    Ok(())
+}
+
 trait ExtensionTrait<A> {
     fn init(options: A) -> Extension;
 
@@ -31,6 +48,30 @@ trait ExtensionTrait<A> {
     }
 }
 
+pub mod actions;
+pub mod cli;
+
+#[cfg(feature = "codecs")]
+pub mod codecs;
+
+#[cfg(feature = "events")]
+pub mod events;
+
+#[cfg(feature = "json")]
+pub mod json;
+
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(all(feature = "env", not(feature = "node_experimental")))]
+pub mod env;
+
+#[cfg(all(feature = "signals", not(feature = "node_experimental")))]
+pub mod signals;
+
 #[cfg(feature = "webidl")]
 pub mod webidl;
 
@@ -82,6 +123,21 @@ pub mod kv;
 #[cfg(feature = "cron")]
 pub mod cron;
 
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(feature = "database")]
+pub mod database;
+
+#[cfg(feature = "host_cache")]
+pub mod host_cache;
+
+#[cfg(feature = "secrets")]
+pub mod secrets;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
 #[cfg(feature = "node_experimental")]
 pub mod napi;
 #[cfg(feature = "node_experimental")]
@@ -112,6 +168,14 @@ pub struct ExtensionOptions {
     #[cfg_attr(docsrs, doc(cfg(feature = "io")))]
     pub io_pipes: Option<deno_io::Stdio>,
 
+    /// Host-provided endpoints for the script's standard streams, including `console` output
+    /// Streams set here take precedence over `io_pipes`
+    ///
+    /// Requires the `io` feature to be enabled
+    #[cfg(feature = "io")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "io")))]
+    pub stdio: Option<io::VirtualStdio>,
+
     /// Optional path to the directory where the webstorage extension will store its data
     ///
     /// Requires the `webstorage` feature to be enabled
@@ -148,6 +212,30 @@ pub struct ExtensionOptions {
     #[cfg_attr(docsrs, doc(cfg(feature = "kv")))]
     pub kv_store: kv::KvStore,
 
+    /// Host-side connections for `rustyscript.database`
+    /// Without one, every query from a script fails
+    ///
+    /// Requires the `database` feature to be enabled
+    #[cfg(feature = "database")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "database")))]
+    pub db_provider: Option<std::rc::Rc<dyn database::DbProvider>>,
+
+    /// Host-managed backend and key namespace for `rustyscript.cache`
+    /// Without one, every cache call from a script fails
+    ///
+    /// Requires the `host_cache` feature to be enabled
+    #[cfg(feature = "host_cache")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "host_cache")))]
+    pub host_cache: Option<host_cache::HostCache>,
+
+    /// Secrets scripts may request through `rustyscript.secrets`
+    /// Without a store, every request fails
+    ///
+    /// Requires the `secrets` feature to be enabled
+    #[cfg(feature = "secrets")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secrets")))]
+    pub secrets: Option<secrets::SecretStore>,
+
     /// Package resolver for the `deno_node` extension
     /// `RustyResolver` allows you to select the base dir for modules
     /// as well as the filesystem implementation to use
@@ -156,6 +244,18 @@ pub struct ExtensionOptions {
     #[cfg(feature = "node_experimental")]
     #[cfg_attr(docsrs, doc(cfg(feature = "node_experimental")))]
     pub node_resolver: std::sync::Arc<node::resolvers::RustyResolver>,
+
+    /// Virtual environment backing `Deno.env`
+    /// Scripts never see the real process environment - use `VirtualEnv::from_process` to copy it in
+    ///
+    /// Requires the `env` feature. Not available with the `node_experimental` feature, which
+    /// provides its own `Deno.env`
+    #[cfg(all(feature = "env", not(feature = "node_experimental")))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "env", not(feature = "node_experimental"))))
+    )]
+    pub env: env::VirtualEnv,
 }
 
 impl Default for ExtensionOptions {
@@ -170,6 +270,9 @@ impl Default for ExtensionOptions {
             #[cfg(feature = "io")]
             io_pipes: Some(deno_io::Stdio::default()),
 
+            #[cfg(feature = "io")]
+            stdio: None,
+
             #[cfg(feature = "webstorage")]
             webstorage_origin_storage_dir: None,
 
@@ -185,8 +288,20 @@ impl Default for ExtensionOptions {
             #[cfg(feature = "kv")]
             kv_store: kv::KvStore::default(),
 
+            #[cfg(feature = "database")]
+            db_provider: None,
+
+            #[cfg(feature = "host_cache")]
+            host_cache: None,
+
+            #[cfg(feature = "secrets")]
+            secrets: None,
+
             #[cfg(feature = "node_experimental")]
             node_resolver: std::sync::Arc::new(node::resolvers::RustyResolver::default()),
+
+            #[cfg(all(feature = "env", not(feature = "node_experimental")))]
+            env: env::VirtualEnv::default(),
         }
     }
 }
@@ -227,7 +342,11 @@ pub(crate) fn all_extensions(
     extensions.extend(crypto::extensions(options.crypto_seed, is_snapshot));
 
     #[cfg(feature = "io")]
-    extensions.extend(io::extensions(options.io_pipes.clone(), is_snapshot));
+    extensions.extend(io::extensions(
+        options.io_pipes.clone(),
+        options.stdio.as_ref(),
+        is_snapshot,
+    ));
 
     #[cfg(feature = "webstorage")]
     extensions.extend(webstorage::extensions(
@@ -256,6 +375,24 @@ pub(crate) fn all_extensions(
     #[cfg(feature = "cron")]
     extensions.extend(cron::extensions(is_snapshot));
 
+    #[cfg(feature = "sqlite")]
+    extensions.extend(sqlite::extensions(is_snapshot));
+
+    #[cfg(feature = "database")]
+    extensions.extend(database::extensions(
+        options.db_provider.clone(),
+        is_snapshot,
+    ));
+
+    #[cfg(feature = "host_cache")]
+    extensions.extend(host_cache::extensions(
+        options.host_cache.clone(),
+        is_snapshot,
+    ));
+
+    #[cfg(feature = "secrets")]
+    extensions.extend(secrets::extensions(options.secrets.clone(), is_snapshot));
+
     #[cfg(feature = "node_experimental")]
     {
         extensions.extend(napi::extensions(is_snapshot));
@@ -268,6 +405,35 @@ pub(crate) fn all_extensions(
         ));
     }
 
+    #[cfg(feature = "otel")]
+    extensions.extend(otel::extensions(is_snapshot));
+
+    #[cfg(all(feature = "env", not(feature = "node_experimental")))]
+    extensions.extend(env::extensions(options.env.clone(), is_snapshot));
+
+    #[cfg(all(feature = "signals", not(feature = "node_experimental")))]
+    extensions.extend(signals::extensions(is_snapshot));
+
+    // Registered after `node_experimental`, to replace its process-wide `Deno.exit`
+    extensions.extend(cli::extensions(is_snapshot));
+
+    #[cfg(feature = "events")]
+    extensions.extend(events::extensions(is_snapshot));
+
+    #[cfg(feature = "wasm")]
+    extensions.extend(wasm::extensions(is_snapshot));
+
+    extensions.extend(actions::extensions(is_snapshot));
+
+    #[cfg(feature = "json")]
+    extensions.extend(json::extensions(is_snapshot));
+
+    #[cfg(feature = "codecs")]
+    extensions.extend(codecs::extensions(is_snapshot));
+
+    #[cfg(feature = "pipeline")]
+    extensions.extend(pipeline::extensions(is_snapshot));
+
     extensions.extend(user_extensions);
     extensions
 }
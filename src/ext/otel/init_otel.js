@@ -0,0 +1,15 @@
+import { op_otel_console_error } from "ext:core/ops";
+
+// Report console errors and warnings to the host's telemetry
+if (globalThis.console) {
+    for (const level of ['error', 'warn']) {
+        const inner = globalThis.console[level];
+        if (typeof inner !== 'function') continue;
+
+        globalThis.console[level] = function (...args) {
+            const message = args.map((a) => typeof a === 'string' ? a : globalThis.Deno.inspect?.(a) ?? String(a)).join(' ');
+            op_otel_console_error(level, message);
+            return inner.apply(this, args);
+        };
+    }
+}
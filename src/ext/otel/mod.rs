@@ -0,0 +1,31 @@
+use std::rc::Rc;
+
+use deno_core::{extension, op2, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::telemetry::Telemetry;
+
+/// Reports a console error or warning to the runtime's telemetry, if any
+#[op2(fast)]
+fn op_otel_console_error(state: &mut OpState, #[string] level: &str, #[string] message: &str) {
+    if let Some(telemetry) = state.try_borrow::<Rc<Telemetry>>() {
+        telemetry.console_error(level, message);
+    }
+}
+
+extension!(
+    init_otel,
+    deps = [rustyscript],
+    ops = [op_otel_console_error],
+    esm_entry_point = "ext:init_otel/init_otel.js",
+    esm = [ dir "src/ext/otel", "init_otel.js" ],
+);
+impl ExtensionTrait<()> for init_otel {
+    fn init((): ()) -> Extension {
+        init_otel::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_otel::build((), is_snapshot)]
+}
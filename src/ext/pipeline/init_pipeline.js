@@ -0,0 +1,93 @@
+import { op_bind_pipeline_runner, op_pipeline_input, op_pipeline_output } from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+// Free buffers beyond this many are left to the GC
+const MAX_POOLED = 16;
+
+// Reuses ArrayBuffers across invocations, handing out views of the requested length
+class BufferPool {
+    #free = [];
+
+    take(length) {
+        let best = -1;
+        for (let i = 0; i < this.#free.length; i++) {
+            const size = this.#free[i].byteLength;
+            if (size >= length && (best < 0 || size < this.#free[best].byteLength)) best = i;
+        }
+
+        const buffer = best < 0
+            ? new ArrayBuffer(Math.max(64, 2 ** Math.ceil(Math.log2(length || 1))))
+            : this.#free.splice(best, 1)[0];
+        return new Uint8Array(buffer, 0, length);
+    }
+
+    release(view) {
+        if (this.#free.length < MAX_POOLED) this.#free.push(view.buffer);
+    }
+
+    get size() {
+        return this.#free.length;
+    }
+}
+
+const pool = new BufferPool();
+const transforms = new Map();
+
+const asBytes = (result) => {
+    if (result instanceof ArrayBuffer) return new Uint8Array(result);
+    if (ArrayBuffer.isView(result)) return new Uint8Array(result.buffer, result.byteOffset, result.byteLength);
+    throw new TypeError('A transform must return a Uint8Array, ArrayBuffer or other buffer view');
+};
+
+// Runs a transform over the staged input, staging its result for the host
+op_bind_pipeline_runner((name, length) => {
+    const transform = transforms.get(name);
+    if (!transform) throw new Error(`No transform named '${name}' is registered`);
+
+    const leased = [];
+    const alloc = (length) => {
+        const buffer = pool.take(length);
+        leased.push(buffer);
+        return buffer;
+    };
+    const release = () => leased.forEach((buffer) => pool.release(buffer));
+
+    const finish = (result) => {
+        try {
+            op_pipeline_output(asBytes(result));
+        } finally {
+            release();
+        }
+    };
+    const fail = (error) => {
+        release();
+        throw error;
+    };
+
+    let result;
+    try {
+        const input = alloc(length);
+        op_pipeline_input(input);
+        result = transform(input, { alloc });
+    } catch (error) {
+        fail(error);
+    }
+
+    return result instanceof Promise ? result.then(finish, fail) : finish(result);
+});
+
+namespaces.pipeline = Object.freeze({
+    // Registers a transform - `(input, { alloc }) => Uint8Array`, or a promise resolving to one
+    // Buffers from `alloc`, and the input itself, return to the pool once the result is copied out,
+    // so must not be kept beyond the invocation
+    register(name, transform) {
+        if (typeof transform !== 'function') throw new TypeError('A transform must be a function');
+        transforms.set(String(name), transform);
+    },
+
+    unregister: (name) => transforms.delete(String(name)),
+    list: () => [...transforms.keys()],
+
+    // The number of free buffers held by the pool
+    pooled: () => pool.size,
+});
@@ -0,0 +1,134 @@
+//! Byte-buffer transforms, registered by scripts through `rustyscript.pipeline`
+//!
+//! Built for high-throughput media processing - the host submits a buffer to a named transform,
+//! and gets the transformed bytes back, with as little allocation on either side as possible:
+//! - Scripts receive their input in a buffer leased from a pool, and can lease output buffers
+//!   from the same pool, so steady-state invocations create no new `ArrayBuffer`s for the GC
+//! - The host's staging buffers are kept between invocations, and
+//!   [`crate::Runtime::run_transform_into`] swaps the result into a buffer the caller reuses
+use deno_core::{extension, op2, v8, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// The buffers an invocation's bytes are staged in, kept between invocations
+#[derive(Default)]
+pub(crate) struct PipelineIo {
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub written: bool,
+}
+
+/// The JS function that runs a named transform over the staged input
+pub(crate) struct PipelineRunner(pub v8::Global<v8::Function>);
+
+impl PipelineRunner {
+    /// Get the runner from the runtime's state
+    pub(crate) fn get(state: &OpState) -> Result<v8::Global<v8::Function>, Error> {
+        state
+            .try_borrow::<Self>()
+            .map(|runner| runner.0.clone())
+            .ok_or_else(|| Error::Runtime("Pipeline runner not initialized".to_string()))
+    }
+}
+
+/// Registers the function used by `Runtime::run_transform`
+#[op2]
+fn op_bind_pipeline_runner(
+    state: &mut OpState,
+    #[global] runner: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The pipeline runner", PipelineRunner(runner))
+}
+
+/// Copies the staged input into a buffer leased by the script
+#[op2]
+fn op_pipeline_input(state: &mut OpState, #[buffer] buffer: &mut [u8]) -> Result<(), Error> {
+    let io = state
+        .try_borrow::<PipelineIo>()
+        .filter(|io| io.input.len() == buffer.len())
+        .ok_or_else(|| Error::Runtime("No pipeline input is staged".to_string()))?;
+    buffer.copy_from_slice(&io.input);
+    Ok(())
+}
+
+/// Copies a transform's result into the host's staging buffer
+#[op2]
+fn op_pipeline_output(state: &mut OpState, #[buffer] buffer: &[u8]) -> Result<(), Error> {
+    let io = state
+        .try_borrow_mut::<PipelineIo>()
+        .ok_or_else(|| Error::Runtime("No pipeline invocation is running".to_string()))?;
+    io.output.clear();
+    io.output.extend_from_slice(buffer);
+    io.written = true;
+    Ok(())
+}
+
+extension!(
+    init_pipeline,
+    deps = [rustyscript],
+    ops = [op_bind_pipeline_runner, op_pipeline_input, op_pipeline_output],
+    esm_entry_point = "ext:init_pipeline/init_pipeline.js",
+    esm = [ dir "src/ext/pipeline", "init_pipeline.js" ],
+);
+impl ExtensionTrait<()> for init_pipeline {
+    fn init((): ()) -> Extension {
+        init_pipeline::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_pipeline::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_pipeline() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                // Inverts each byte in place
+                rustyscript.pipeline.register('invert', (input) => {
+                    for (let i = 0; i < input.length; i++) input[i] = 255 - input[i];
+                    return input;
+                });
+
+                // Doubles each byte into a leased buffer, asynchronously
+                rustyscript.pipeline.register('stretch', async (input, { alloc }) => {
+                    await null;
+                    const output = alloc(input.length * 2);
+                    input.forEach((byte, i) => output.fill(byte, i * 2, i * 2 + 2));
+                    return output;
+                });
+
+                rustyscript.pipeline.register('broken', () => 'not bytes');
+                ",
+            ))
+            .unwrap();
+
+        assert_eq!(
+            runtime.run_transform("invert", &[0, 1, 255]).unwrap(),
+            vec![255, 254, 0]
+        );
+
+        let mut output = Vec::new();
+        for frame in 0..10_u8 {
+            runtime
+                .run_transform_into("stretch", &[frame, 7], &mut output)
+                .unwrap();
+            assert_eq!(output, vec![frame, frame, 7, 7]);
+        }
+
+        // Input and output buffers are returned to the pool, rather than left to the GC
+        let pooled: usize = runtime.eval("rustyscript.pipeline.pooled()").unwrap();
+        assert_eq!(pooled, 2);
+
+        runtime.run_transform("broken", &[1]).unwrap_err();
+        runtime.run_transform("missing", &[1]).unwrap_err();
+    }
+}
@@ -165,7 +165,7 @@ impl WebWorkerCallbackOptions {
         Self {
             shared_array_buffer_store,
             node_resolver: options.node_resolver.clone(),
-            root_cert_store_provider: options.web.root_cert_store_provider.clone(),
+            root_cert_store_provider: options.web.effective_root_cert_store_provider(),
             broadcast_channel: options.broadcast_channel.clone(),
             unsafely_ignore_certificate_errors: options
                 .web
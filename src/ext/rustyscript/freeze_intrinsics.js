@@ -0,0 +1,38 @@
+// Deep-freezes the built-in constructors, prototypes and namespaces
+// Executed once after the runtime starts when `RuntimeOptions::freeze_intrinsics` is set
+(() => {
+    const intrinsics = [
+        Object, Function, Array, String, Number, Boolean, Symbol, BigInt, Date, RegExp,
+        Error, EvalError, RangeError, ReferenceError, SyntaxError, TypeError, URIError, AggregateError,
+        Map, Set, WeakMap, WeakSet, WeakRef, FinalizationRegistry, Promise, Proxy,
+        ArrayBuffer, SharedArrayBuffer, DataView,
+        Int8Array, Uint8Array, Uint8ClampedArray, Int16Array, Uint16Array,
+        Int32Array, Uint32Array, Float32Array, Float64Array, BigInt64Array, BigUint64Array,
+        JSON, Math, Reflect, Atomics,
+
+        // Intrinsics with no global binding
+        Object.getPrototypeOf(Int8Array),
+        Object.getPrototypeOf(function* () {}),
+        Object.getPrototypeOf(async function () {}),
+        Object.getPrototypeOf(async function* () {}),
+        Object.getPrototypeOf([][Symbol.iterator]()),
+    ];
+    if (typeof Intl !== 'undefined') intrinsics.push(Intl);
+
+    const seen = new Set();
+    const freeze = (value) => {
+        if ((typeof value !== 'object' && typeof value !== 'function') || value === null) return;
+        if (value === globalThis || seen.has(value)) return;
+        seen.add(value);
+
+        Object.freeze(value);
+        freeze(Object.getPrototypeOf(value));
+        for (const descriptor of Object.values(Object.getOwnPropertyDescriptors(value))) {
+            freeze(descriptor.value);
+            freeze(descriptor.get);
+            freeze(descriptor.set);
+        }
+    };
+
+    intrinsics.forEach(freeze);
+})();
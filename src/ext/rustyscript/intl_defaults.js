@@ -0,0 +1,226 @@
+// Applies per-runtime locale and timezone defaults to the locale-sensitive built-ins
+// Executed once after the runtime starts when `RuntimeOptions::default_locale` or `timezone` is set
+(({ locale, timeZone }) => {
+    if (typeof Intl === 'undefined') {
+        if (timeZone !== undefined) throw new Error('A runtime timezone requires the `intl` feature');
+        return;
+    }
+
+    const OriginalDate = Date;
+    const OriginalDateTimeFormat = Intl.DateTimeFormat;
+
+    const withLocale = (locales) => (locales === undefined ? locale : locales);
+    const withTimeZone = (options) => {
+        if (timeZone === undefined) return options;
+        if (options === undefined) return { timeZone };
+        if (options === null || typeof options !== 'object' || options.timeZone !== undefined) return options;
+        return { __proto__: options, timeZone };
+    };
+    const withNothing = (options) => options;
+
+    // Replace an Intl constructor, keeping `instanceof`, statics and call-without-new behaviour
+    const wrapConstructor = (name, withOptions = withNothing) => {
+        const Original = Intl[name];
+        if (typeof Original !== 'function') return;
+
+        const Wrapped = function (locales, options) {
+            const args = [withLocale(locales), withOptions(options)];
+            return new.target
+                ? Reflect.construct(Original, args, new.target === Wrapped ? Original : new.target)
+                : Original(...args);
+        };
+        Object.defineProperty(Wrapped, 'name', { value: name });
+        Object.defineProperty(Wrapped, 'length', { value: Original.length });
+        Object.setPrototypeOf(Wrapped, Original);
+        Wrapped.prototype = Original.prototype;
+        Object.defineProperty(Original.prototype, 'constructor', {
+            value: Wrapped, writable: true, enumerable: false, configurable: true,
+        });
+        Object.defineProperty(Intl, name, {
+            value: Wrapped, writable: true, enumerable: false, configurable: true,
+        });
+    };
+
+    // Replace a prototype method whose locales argument is at the given position
+    // Options, if any, are expected immediately after the locales
+    const wrapMethod = (proto, name, index, withOptions = withNothing) => {
+        const original = proto[name];
+        if (typeof original !== 'function') return;
+
+        const wrapped = {
+            [name](...args) {
+                args[index] = withLocale(args[index]);
+                if (withOptions !== withNothing) args[index + 1] = withOptions(args[index + 1]);
+                return Reflect.apply(original, this, args);
+            },
+        }[name];
+        Object.defineProperty(wrapped, 'length', { value: original.length });
+        Object.defineProperty(proto, name, {
+            value: wrapped, writable: true, enumerable: false, configurable: true,
+        });
+    };
+
+    if (timeZone !== undefined) {
+        installTimeZone(OriginalDate, OriginalDateTimeFormat, timeZone);
+    }
+
+    wrapConstructor('DateTimeFormat', withTimeZone);
+    [
+        'NumberFormat', 'Collator', 'PluralRules', 'RelativeTimeFormat',
+        'ListFormat', 'DisplayNames', 'Segmenter', 'DurationFormat',
+    ].forEach((name) => wrapConstructor(name));
+
+    wrapMethod(OriginalDate.prototype, 'toLocaleString', 0, withTimeZone);
+    wrapMethod(OriginalDate.prototype, 'toLocaleDateString', 0, withTimeZone);
+    wrapMethod(OriginalDate.prototype, 'toLocaleTimeString', 0, withTimeZone);
+    wrapMethod(Number.prototype, 'toLocaleString', 0);
+    wrapMethod(BigInt.prototype, 'toLocaleString', 0);
+    wrapMethod(String.prototype, 'localeCompare', 1);
+    wrapMethod(String.prototype, 'toLocaleUpperCase', 0);
+    wrapMethod(String.prototype, 'toLocaleLowerCase', 0);
+
+    // Re-implements local time for `Date` in terms of the given IANA zone, rather than the host's
+    function installTimeZone(OriginalDate, DateTimeFormat, timeZone) {
+        const proto = OriginalDate.prototype;
+        const getTime = proto.getTime;
+        const setTime = proto.setTime;
+        const hostOffset = proto.getTimezoneOffset;
+        const originalParse = OriginalDate.parse;
+
+        // Throws a RangeError for unknown zones
+        const wallClock = new DateTimeFormat('en-US', {
+            timeZone, hourCycle: 'h23', era: 'short',
+            year: 'numeric', month: 'numeric', day: 'numeric',
+            hour: 'numeric', minute: 'numeric', second: 'numeric',
+        });
+        const zoneName = new DateTimeFormat('en-US', { timeZone, timeZoneName: 'long' });
+
+        const partsOf = (formatter, t) => {
+            const parts = {};
+            for (const { type, value } of formatter.formatToParts(t)) parts[type] = value;
+            return parts;
+        };
+
+        // Milliseconds to add to a UTC time value to get the wall-clock time in the zone
+        const offsetAt = (t) => {
+            const parts = partsOf(wallClock, t);
+            let year = Number(parts.year);
+            if (parts.era === 'BC' || parts.era === 'B') year = 1 - year;
+
+            const wall = new OriginalDate(0);
+            proto.setUTCFullYear.call(wall, year, parts.month - 1, parts.day);
+            proto.setUTCHours.call(wall, parts.hour, parts.minute, parts.second, 0);
+            return getTime.call(wall) - (t - (((t % 1000) + 1000) % 1000));
+        };
+
+        // Converts a wall-clock time value in the zone back to UTC
+        const fromLocal = (wall) => {
+            if (!Number.isFinite(wall)) return NaN;
+            const guess = wall - offsetAt(wall);
+            return wall - offsetAt(guess);
+        };
+
+        const shifted = (date) => {
+            const t = getTime.call(date);
+            return new OriginalDate(Number.isNaN(t) ? NaN : t + offsetAt(t));
+        };
+
+        const define = (name, value) => {
+            Object.defineProperty(value, 'name', { value: name });
+            Object.defineProperty(proto, name, {
+                value, writable: true, enumerable: false, configurable: true,
+            });
+        };
+
+        for (const field of ['FullYear', 'Month', 'Date', 'Day', 'Hours', 'Minutes', 'Seconds', 'Milliseconds']) {
+            const get = proto[`getUTC${field}`];
+            define(`get${field}`, function () {
+                return get.call(shifted(this));
+            });
+
+            if (field === 'Day') continue;
+            const set = proto[`setUTC${field}`];
+            define(`set${field}`, function (...args) {
+                const t = getTime.call(this);
+                const local = (Number.isNaN(t) && field === 'FullYear') ? new OriginalDate(0) : shifted(this);
+                set.apply(local, args);
+                return setTime.call(this, fromLocal(getTime.call(local)));
+            });
+        }
+
+        define('getTimezoneOffset', function () {
+            const t = getTime.call(this);
+            return Number.isNaN(t) ? NaN : -offsetAt(t) / 60000;
+        });
+
+        const days = ['Sun', 'Mon', 'Tue', 'Wed', 'Thu', 'Fri', 'Sat'];
+        const months = ['Jan', 'Feb', 'Mar', 'Apr', 'May', 'Jun', 'Jul', 'Aug', 'Sep', 'Oct', 'Nov', 'Dec'];
+        const pad = (n, width = 2) => String(n).padStart(width, '0');
+
+        const dateString = (local) => {
+            const year = proto.getUTCFullYear.call(local);
+            const yearString = year < 0 ? `-${pad(-year, 6)}` : pad(year, 4);
+            return `${days[proto.getUTCDay.call(local)]} ${months[proto.getUTCMonth.call(local)]} ${pad(proto.getUTCDate.call(local))} ${yearString}`;
+        };
+        const timeString = (date, local) => {
+            const offset = offsetAt(getTime.call(date)) / 60000;
+            const sign = offset < 0 ? '-' : '+';
+            const name = partsOf(zoneName, getTime.call(date)).timeZoneName;
+            return `${pad(proto.getUTCHours.call(local))}:${pad(proto.getUTCMinutes.call(local))}:${pad(proto.getUTCSeconds.call(local))} ` +
+                `GMT${sign}${pad(Math.floor(Math.abs(offset) / 60))}${pad(Math.abs(offset) % 60)} (${name})`;
+        };
+
+        define('toString', function () {
+            const local = shifted(this);
+            return Number.isNaN(getTime.call(local)) ? 'Invalid Date' : `${dateString(local)} ${timeString(this, local)}`;
+        });
+        define('toDateString', function () {
+            const local = shifted(this);
+            return Number.isNaN(getTime.call(local)) ? 'Invalid Date' : dateString(local);
+        });
+        define('toTimeString', function () {
+            const local = shifted(this);
+            return Number.isNaN(getTime.call(local)) ? 'Invalid Date' : timeString(this, local);
+        });
+
+        // Strings with an explicit offset, and ISO date-only forms, are not local times
+        const hasZone = /(Z|[+-]\d\d:?\d\d|GMT|UTC)\s*(\(.*\))?$/i;
+        const isoDateOnly = /^[+-]?\d{4,6}(-\d\d(-\d\d)?)?$/;
+        const parse = (string) => {
+            string = String(string).trim();
+            const t = originalParse(string);
+            if (Number.isNaN(t) || hasZone.test(string) || isoDateOnly.test(string)) return t;
+
+            // Parsed as the host's local time - recover the wall-clock value, and reinterpret it
+            const wall = t - hostOffset.call(new OriginalDate(t)) * 60000;
+            return fromLocal(wall);
+        };
+
+        const Date = function Date(...args) {
+            if (!new.target) return proto.toString.call(new OriginalDate());
+
+            let value = args;
+            if (args.length >= 2) {
+                const [year, month, day = 1, hours = 0, minutes = 0, seconds = 0, ms = 0] = args.map(Number);
+                value = [fromLocal(OriginalDate.UTC(year, month, day, hours, minutes, seconds, ms))];
+            } else if (args.length === 1 && typeof args[0] === 'string') {
+                value = [parse(args[0])];
+            }
+
+            return Reflect.construct(OriginalDate, value, new.target === Date ? OriginalDate : new.target);
+        };
+        Object.defineProperty(Date, 'length', { value: 7 });
+        Object.setPrototypeOf(Date, OriginalDate);
+        Date.prototype = proto;
+        Date.parse = function parse_(string) {
+            return parse(string);
+        };
+        Object.defineProperty(Date.parse, 'name', { value: 'parse' });
+        Object.defineProperty(proto, 'constructor', {
+            value: Date, writable: true, enumerable: false, configurable: true,
+        });
+        Object.defineProperty(globalThis, 'Date', {
+            value: Date, writable: true, enumerable: false, configurable: true,
+        });
+    }
+})
@@ -1,12 +1,25 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
 use deno_core::{extension, op2, serde_json, v8, Extension, OpState};
 
 use super::ExtensionTrait;
-use crate::{error::Error, RsAsyncFunction, RsFunction};
+use crate::{
+    build_info::ScriptBuildInfo,
+    entrypoint_wrap::EntrypointTimings,
+    error::Error,
+    ext::actions::schema::{self, Mismatch},
+    heartbeat::{CallDeadline, HeartbeatMonitor},
+    script_events::EventSubscribers,
+    workflow::{StepOutcome, WorkflowState},
+    BuildInfo, CircuitBreaker, RateLimiter, RetryPolicy, RsAsyncFunction, RsFunction, TimerLimits,
+};
 
 type FnCache = HashMap<String, Box<dyn RsFunction>>;
-type AsyncFnCache = HashMap<String, Box<dyn RsAsyncFunction>>;
+type AsyncFnCache = HashMap<String, Rc<dyn RsAsyncFunction>>;
 
 mod callbacks;
 
@@ -20,6 +33,151 @@ fn op_register_entrypoint(state: &mut OpState, #[global] callback: v8::Global<v8
     state.put(callback);
 }
 
+/// Values registered by scripts under a stable name, for [`crate::js_value::DurableHandle`]
+#[derive(Default)]
+pub(crate) struct DurableRegistry(pub HashMap<String, v8::Global<v8::Value>>);
+
+/// Registers a JS value under a name that can be resolved with a `DurableHandle`
+///
+/// # Arguments
+/// * `state` - The runtime's state, into which the value will be put
+/// * `name` - The stable name for the value
+/// * `value` - The value to register
+#[op2]
+fn op_register_durable(
+    state: &mut OpState,
+    #[string] name: String,
+    #[global] value: v8::Global<v8::Value>,
+) {
+    if !state.has::<DurableRegistry>() {
+        state.put(DurableRegistry::default());
+    }
+    state.borrow_mut::<DurableRegistry>().0.insert(name, value);
+}
+
+/// Functions registered by scripts with `rustyscript.expose`, for [`crate::Runtime::call_exposed`]
+#[derive(Default)]
+pub(crate) struct ExposedFunctions(pub BTreeMap<String, v8::Global<v8::Function>>);
+
+impl ExposedFunctions {
+    /// Get the function exposed under a name
+    pub(crate) fn get(state: &OpState, name: &str) -> Result<v8::Global<v8::Function>, Error> {
+        state
+            .try_borrow::<Self>()
+            .and_then(|exposed| exposed.0.get(name))
+            .cloned()
+            .ok_or_else(|| Error::ValueNotFound(format!("exposed function `{name}`")))
+    }
+}
+
+/// Exposes a JS function to the host under a name, replacing any function already exposed under it
+///
+/// # Arguments
+/// * `state` - The runtime's state, into which the function will be put
+/// * `name` - The name the host will call the function by
+/// * `function` - The function to expose
+#[op2]
+fn op_expose_function(
+    state: &mut OpState,
+    #[string] name: String,
+    #[global] function: v8::Global<v8::Function>,
+) {
+    if !state.has::<ExposedFunctions>() {
+        state.put(ExposedFunctions::default());
+    }
+    state
+        .borrow_mut::<ExposedFunctions>()
+        .0
+        .insert(name, function);
+}
+
+/// Withdraws a function exposed with `rustyscript.expose` - returns false if there was none
+#[op2(fast)]
+fn op_unexpose_function(state: &mut OpState, #[string] name: &str) -> bool {
+    state
+        .try_borrow_mut::<ExposedFunctions>()
+        .is_some_and(|exposed| exposed.0.remove(name).is_some())
+}
+
+/// Sends a payload to the host's subscribers to an event, returning how many there were
+///
+/// See [`crate::Runtime::subscribe`]
+#[op2]
+#[smi]
+fn op_emit(state: &mut OpState, #[string] event: &str, #[serde] payload: serde_json::Value) -> u32 {
+    let delivered = state
+        .try_borrow_mut::<EventSubscribers>()
+        .map_or(0, |subscribers| subscribers.emit(event, &payload));
+    u32::try_from(delivered).unwrap_or(u32::MAX)
+}
+
+/// Returns true if the host is subscribed to an event - so scripts can skip building unwanted payloads
+#[op2(fast)]
+fn op_has_subscribers(state: &mut OpState, #[string] event: &str) -> bool {
+    state
+        .try_borrow::<EventSubscribers>()
+        .is_some_and(|subscribers| subscribers.has_subscribers(event))
+}
+
+/// Called by `rustyscript.heartbeat` and `rustyscript.progress` - see [`crate::HeartbeatOptions`]
+#[op2]
+fn op_heartbeat(
+    state: &mut OpState,
+    #[serde] progress: Option<f64>,
+    #[string] message: Option<String>,
+) -> Result<(), Error> {
+    match state.try_borrow::<HeartbeatMonitor>() {
+        Some(HeartbeatMonitor {
+            options: Some(options),
+            deadline,
+        }) => deadline.beat(options, progress, message),
+        _ => Ok(()),
+    }
+}
+
+/// The id of the call the host is currently running - see [`crate::cancellation`]
+#[op2(fast)]
+#[number]
+fn op_current_call(state: &mut OpState) -> u64 {
+    state
+        .try_borrow::<CallDeadline>()
+        .map_or(0, |deadline| deadline.signal().id())
+}
+
+/// Resolves with the reason a call was aborted, or `null` once it finished without being aborted
+#[op2(async)]
+#[serde]
+fn op_call_signal(
+    state: &mut OpState,
+    #[number] call: u64,
+) -> impl std::future::Future<Output = Option<String>> {
+    let signal = state
+        .try_borrow::<CallDeadline>()
+        .map(|deadline| deadline.signal().wait(call));
+    async move {
+        match signal {
+            Some(signal) => signal.await,
+            None => None,
+        }
+    }
+}
+
+/// Called by `rustyscript.step` - see [`crate::workflow`]
+///
+/// Returns the step's recorded outcome, or `None` if the workflow must suspend
+#[op2]
+#[serde]
+fn op_workflow_step(
+    state: &mut OpState,
+    #[string] name: String,
+    #[serde] args: Vec<serde_json::Value>,
+) -> Result<Option<StepOutcome>, Error> {
+    let workflow = state.try_borrow_mut::<WorkflowState>().ok_or_else(|| {
+        Error::Runtime("rustyscript.step can only be used within a workflow".to_string())
+    })?;
+    workflow.step(name, args)
+}
+
 #[op2]
 #[serde]
 #[allow(clippy::needless_pass_by_value)]
@@ -28,6 +186,10 @@ fn call_registered_function(
     #[serde] args: Vec<serde_json::Value>,
     state: &mut OpState,
 ) -> Result<serde_json::Value, Error> {
+    if let Some(limiter) = state.try_borrow::<RateLimiter>() {
+        limiter.check(RateLimiter::HOST_CALLS)?;
+    }
+
     if state.has::<FnCache>() {
         let table = state.borrow_mut::<FnCache>();
         if let Some(callback) = table.get(name) {
@@ -45,16 +207,200 @@ fn call_registered_function_async(
     #[serde] args: Vec<serde_json::Value>,
     state: &mut OpState,
 ) -> impl std::future::Future<Output = Result<serde_json::Value, Error>> {
-    if state.has::<AsyncFnCache>() {
-        let table = state.borrow_mut::<AsyncFnCache>();
-        if let Some(callback) = table.get(&name) {
-            return callback(args);
+    if let Some(limiter) = state.try_borrow::<RateLimiter>() {
+        if let Err(e) = limiter.check(RateLimiter::HOST_CALLS) {
+            return Box::pin(std::future::ready(Err(e)));
         }
     }
 
+    let callback = state
+        .try_borrow::<AsyncFnCache>()
+        .and_then(|table| table.get(&name).cloned());
+    if let Some(callback) = callback {
+        // Transient failures are retried, if the runtime has a policy
+        let Some(policy) = state.try_borrow::<RetryPolicy>().cloned() else {
+            return callback(args);
+        };
+        let retried: std::pin::Pin<Box<dyn std::future::Future<Output = _>>> =
+            Box::pin(async move { policy.run(|| callback(args.clone())).await });
+        return retried;
+    }
+
     Box::pin(std::future::ready(Err(Error::ValueNotCallable(name))))
 }
 
+/// Called by `rustyscript.rateLimit.acquire` - waits until the tokens are available
+///
+/// Keys are unlimited if the runtime has no [`RateLimiter`]
+#[op2(async)]
+async fn op_rate_limit_acquire(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+    tokens: u32,
+) -> Result<(), Error> {
+    let Some(limiter) = state.try_borrow()?.try_borrow::<RateLimiter>().cloned() else {
+        return Ok(());
+    };
+    limiter.acquire(&key, tokens).await
+}
+
+/// Called by `rustyscript.rateLimit.tryAcquire` - returns false if the tokens are not available
+#[op2(fast)]
+fn op_rate_limit_try_acquire(state: &mut OpState, #[string] key: &str, tokens: u32) -> bool {
+    match state.try_borrow::<RateLimiter>() {
+        Some(limiter) => limiter.try_acquire(key, tokens).is_ok(),
+        None => true,
+    }
+}
+
+/// Called before an outbound request - fails if the host's circuit is open
+///
+/// Hosts are never short-circuited if the runtime has no [`CircuitBreaker`]
+#[op2(fast)]
+fn op_circuit_check(state: &mut OpState, #[string] host: &str) -> Result<(), Error> {
+    match state.try_borrow::<CircuitBreaker>() {
+        Some(breaker) => breaker.check(host),
+        None => Ok(()),
+    }
+}
+
+/// Called once an outbound request has succeeded or failed
+#[op2(fast)]
+fn op_circuit_record(state: &mut OpState, #[string] host: &str, success: bool) {
+    if let Some(breaker) = state.try_borrow::<CircuitBreaker>() {
+        if success {
+            breaker.record_success(host);
+        } else {
+            breaker.record_failure(host);
+        }
+    }
+}
+
+/// Checks a new timer against the runtime's [`TimerLimits`], returning the delay to schedule it with
+#[op2(fast)]
+fn op_timer_check(
+    state: &mut OpState,
+    #[smi] pending: u32,
+    delay: f64,
+    interval: bool,
+) -> Result<f64, Error> {
+    match state.try_borrow::<TimerLimits>() {
+        Some(limits) => limits.check(
+            usize::try_from(pending).unwrap_or(usize::MAX),
+            delay,
+            interval,
+        ),
+        None => Ok(delay),
+    }
+}
+
+/// Called by functions wrapped with `rustyscript.wrap` to check their arguments against their
+/// schema, one JSON Schema per argument - missing arguments are checked as `null`
+///
+/// Returns the first mismatch, with a path such as `args/0/items/1`
+#[op2]
+#[serde]
+fn op_entrypoint_validate(
+    #[serde] schemas: Vec<serde_json::Value>,
+    #[serde] args: Vec<serde_json::Value>,
+) -> Option<Mismatch> {
+    schemas.iter().enumerate().find_map(|(i, item)| {
+        let value = args.get(i).unwrap_or(&serde_json::Value::Null);
+        schema::validate_at(item, value, &format!("args/{i}")).err()
+    })
+}
+
+/// Called by functions wrapped with `rustyscript.wrap` as each call completes
+#[op2(fast)]
+fn op_entrypoint_record(state: &mut OpState, #[string] name: &str, elapsed: f64, success: bool) {
+    if !state.has::<EntrypointTimings>() {
+        state.put(EntrypointTimings::default());
+    }
+    state
+        .borrow_mut::<EntrypointTimings>()
+        .record(name, elapsed, success);
+}
+
+/// The JS function that reports, and optionally clears, the work an invocation left alive
+pub(crate) struct LeakChecker(pub v8::Global<v8::Function>);
+
+impl LeakChecker {
+    /// Get the checker from the runtime's state
+    pub(crate) fn get(state: &OpState) -> Result<v8::Global<v8::Function>, Error> {
+        state
+            .try_borrow::<Self>()
+            .map(|checker| checker.0.clone())
+            .ok_or_else(|| Error::Runtime("Leak checker not initialized".to_string()))
+    }
+}
+
+/// The JS functions that enter and leave the context of a call made with
+/// `Runtime::call_function_with_context`
+pub(crate) struct CallContext {
+    pub enter: v8::Global<v8::Function>,
+    pub exit: v8::Global<v8::Function>,
+}
+
+impl CallContext {
+    /// Get the functions from the runtime's state
+    pub(crate) fn get(
+        state: &OpState,
+    ) -> Result<(v8::Global<v8::Function>, v8::Global<v8::Function>), Error> {
+        state
+            .try_borrow::<Self>()
+            .map(|context| (context.enter.clone(), context.exit.clone()))
+            .ok_or_else(|| Error::Runtime("Call context not initialized".to_string()))
+    }
+}
+
+/// Registers the functions used by `Runtime::call_function_with_context`
+#[op2]
+fn op_bind_call_context(
+    state: &mut OpState,
+    #[global] enter: v8::Global<v8::Function>,
+    #[global] exit: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The call context", CallContext { enter, exit })
+}
+
+/// The JS function returning the `AbortSignal` of the current call - see [`crate::cancellation`]
+pub(crate) struct CallSignalSource(pub v8::Global<v8::Function>);
+
+impl CallSignalSource {
+    /// Get the function from the runtime's state
+    pub(crate) fn get(state: &OpState) -> Result<v8::Global<v8::Function>, Error> {
+        state
+            .try_borrow::<Self>()
+            .map(|source| source.0.clone())
+            .ok_or_else(|| Error::Runtime("Call signal not initialized".to_string()))
+    }
+}
+
+/// Registers the function used to pass the call's signal with `RuntimeOptions::call_signal_argument`
+#[op2]
+fn op_bind_call_signal(
+    state: &mut OpState,
+    #[global] source: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The call signal", CallSignalSource(source))
+}
+
+/// Registers the function used by `Runtime::report_leaks` and `Runtime::clear_leaks`
+#[op2]
+fn op_bind_leak_checker(
+    state: &mut OpState,
+    #[global] checker: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The leak checker", LeakChecker(checker))
+}
+
+/// Returns the versions scripts may see - see [`crate::Runtime::build_info`]
+#[op2]
+#[serde]
+fn op_build_info(state: &mut OpState) -> Option<ScriptBuildInfo> {
+    state.try_borrow::<BuildInfo>().map(ScriptBuildInfo::from)
+}
+
 #[op2(fast)]
 fn op_panic2(#[string] msg: &str) -> Result<(), Error> {
     Err(Error::Runtime(msg.to_string()))
@@ -62,7 +408,15 @@ fn op_panic2(#[string] msg: &str) -> Result<(), Error> {
 
 extension!(
     rustyscript,
-    ops = [op_register_entrypoint, call_registered_function, call_registered_function_async],
+    ops = [
+        op_register_entrypoint, op_register_durable, op_workflow_step,
+        op_expose_function, op_unexpose_function, op_emit, op_has_subscribers,
+        op_heartbeat, op_current_call, op_call_signal,
+        call_registered_function, call_registered_function_async,
+        op_rate_limit_acquire, op_rate_limit_try_acquire,
+        op_circuit_check, op_circuit_record, op_timer_check, op_bind_leak_checker, op_build_info,
+        op_entrypoint_validate, op_entrypoint_record, op_bind_call_context, op_bind_call_signal
+    ],
     esm_entry_point = "ext:rustyscript/rustyscript.js",
     esm = [ dir "src/ext/rustyscript", "rustyscript.js" ],
     middleware = |op| match op.name {
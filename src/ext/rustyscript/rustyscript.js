@@ -25,10 +25,368 @@ const getterOnly = (getter) => {
 const applyToGlobal = (properties) => Object.defineProperties(globalThis, properties);
 const applyToDeno = (properties) => Object.defineProperties(globalThis.Deno, properties);
 
+// APIs added to `rustyscript` by optional extensions
+const namespaces = {};
+
+// Thrown by registered functions returning a `rustyscript::HostError` - the host may set `code`
+class HostError extends Error {
+    constructor(message) {
+        super(message);
+        this.name = 'HostError';
+    }
+}
+Deno.core.registerErrorClass('HostError', HostError);
+
+// Thrown by `fetch` and `Deno.connect` when the host's circuit is open - see `rustyscript::CircuitBreaker`
+class CircuitOpenError extends Error {
+    constructor(message) {
+        super(message);
+        this.name = 'CircuitOpenError';
+    }
+}
+Deno.core.registerErrorClass('CircuitOpenError', CircuitOpenError);
+
+// Thrown by `setTimeout` and `setInterval` when a timer is over the runtime's `rustyscript::TimerLimits`
+class TimerLimitError extends RangeError {
+    constructor(message) {
+        super(message);
+        this.name = 'TimerLimitError';
+    }
+}
+Deno.core.registerErrorClass('TimerLimitError', TimerLimitError);
+
+// Thrown by functions wrapped with `rustyscript.wrap` - decodes into a `rustyscript::EntrypointFailure`
+class EntrypointError extends Error {
+    constructor(entrypoint, kind, message, { path, elapsed, cause } = {}) {
+        super(`${entrypoint}: ${message}`, cause === undefined ? undefined : { cause });
+        this.name = 'EntrypointError';
+        this.entrypoint = entrypoint;
+        this.kind = kind;
+        if (path !== undefined) this.path = path;
+        this.elapsed = elapsed;
+    }
+}
+
+// Argument validation, timeouts, timing and error wrapping for entrypoints - see `rustyscript::EntrypointStats`
+const wrap = (() => {
+    const describeError = (e) => e instanceof Error ? e.message : String(e);
+
+    return (handler, options = {}) => {
+        if (typeof handler !== 'function') throw new TypeError('rustyscript.wrap expects a function');
+        const name = options.name ?? (handler.name || 'anonymous');
+        const { schema, timeout } = options;
+        if (schema !== undefined && !Array.isArray(schema)) {
+            throw new TypeError('rustyscript.wrap expects `schema` to be an array of JSON Schemas');
+        }
+
+        const record = (start, success) => {
+            const elapsed = Date.now() - start;
+            Deno.core.ops.op_entrypoint_record(name, elapsed, success);
+            return elapsed;
+        };
+        const fail = (start, kind, message, details = {}) => {
+            const elapsed = record(start, false);
+            return new EntrypointError(name, kind, message, { ...details, elapsed });
+        };
+
+        const wrapped = function (...args) {
+            const start = Date.now();
+            if (schema) {
+                let mismatch;
+                try {
+                    mismatch = Deno.core.ops.op_entrypoint_validate(schema, args.slice(0, schema.length));
+                } catch (e) {
+                    throw fail(start, 'validation', `arguments are not JSON values: ${describeError(e)}`, { path: 'args' });
+                }
+                if (mismatch) {
+                    const { path, message } = mismatch;
+                    throw fail(start, 'validation', `${path}: ${message}`, { path });
+                }
+            }
+
+            let result;
+            try {
+                result = Reflect.apply(handler, this, args);
+            } catch (e) {
+                throw fail(start, 'error', describeError(e), { cause: e });
+            }
+
+            // Synchronous handlers cannot be interrupted, so are never timed out
+            if (typeof result?.then !== 'function') {
+                record(start, true);
+                return result;
+            }
+
+            let timer, timedOut;
+            const settled = [Promise.resolve(result)];
+            if (timeout !== undefined) {
+                settled.push(new Promise((_, reject) => {
+                    timer = setTimeout(() => {
+                        timedOut = fail(start, 'timeout', `timed out after ${timeout}ms`);
+                        reject(timedOut);
+                    }, timeout);
+                }));
+            }
+
+            return Promise.race(settled).then(
+                (value) => {
+                    clearTimeout(timer);
+                    record(start, true);
+                    return value;
+                },
+                (e) => {
+                    clearTimeout(timer);
+                    if (e === timedOut) throw e;
+                    throw fail(start, 'error', describeError(e), { cause: e });
+                },
+            );
+        };
+
+        Object.defineProperty(wrapped, 'name', { value: name });
+        return wrapped;
+    };
+})();
+
+// Timers and listeners scripts leave alive, by the invocation that created them - see `Runtime::report_leaks`
+const background = (() => {
+    const work = new Map();
+    let invocation = 0;
+
+    return {
+        // Returns a token to untrack the work with, once it has finished or been cancelled
+        track(kind, name, clear) {
+            const token = {};
+            work.set(token, { kind, name, invocation, clear });
+            return token;
+        },
+
+        untrack(token) {
+            work.delete(token);
+        },
+
+        // Reports the work created since the last check that is still alive, then starts a new invocation
+        check(clear) {
+            const report = { timeouts: 0, intervals: 0, listeners: [] };
+            for (const [token, entry] of [...work]) {
+                if (entry.invocation !== invocation) continue;
+                if (entry.kind === 'timeout') report.timeouts++;
+                else if (entry.kind === 'interval') report.intervals++;
+                else report.listeners.push(entry.name);
+
+                if (clear) {
+                    entry.clear();
+                    work.delete(token);
+                }
+            }
+
+            invocation++;
+            return report;
+        },
+    };
+})();
+Deno.core.ops.op_bind_leak_checker(background.check);
+
+// Wraps a set of timer functions with the checks against `rustyscript::TimerLimits`
+// Timers are only counted while pending - until a timeout fires, or either kind is cleared
+const limitTimers = (timers) => {
+    const pending = new Map();
+    const release = (id) => {
+        background.untrack(pending.get(id));
+        pending.delete(id);
+    };
+
+    const schedule = (interval, callback, delay, args) => {
+        delay = Deno.core.ops.op_timer_check(pending.size, Number(delay) || 0, interval);
+        const run = typeof callback === 'function' ? callback : () => (0, eval)(String(callback));
+        const id = interval
+            ? timers.setInterval(run, delay, ...args)
+            : timers.setTimeout(function (...args) {
+                release(id);
+                return Reflect.apply(run, this, args);
+            }, delay, ...args);
+
+        const kind = interval ? 'interval' : 'timeout';
+        pending.set(id, background.track(kind, kind, () => {
+            pending.delete(id);
+            (interval ? timers.clearInterval : timers.clearTimeout)(id);
+        }));
+        return id;
+    };
+    const clear = (clearTimer) => (id) => {
+        release(id);
+        clearTimer(id);
+    };
+
+    return {
+        setTimeout: (callback, delay = 0, ...args) => schedule(false, callback, delay, args),
+        setInterval: (callback, delay = 0, ...args) => schedule(true, callback, delay, args),
+        clearTimeout: clear(timers.clearTimeout),
+        clearInterval: clear(timers.clearInterval),
+    };
+};
+
+// Helpers for the representations in `rustyscript::serde_adapters`
+const types = (() => {
+    const UUID = /^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$/i;
+
+    // Replaces `Date`s with ISO-8601 strings, however deeply nested
+    const toHost = (value) => {
+        if (value instanceof Date) return value.toISOString();
+        if (Array.isArray(value)) return value.map(toHost);
+        if (value !== null && typeof value === 'object' && Object.getPrototypeOf(value) === Object.prototype) {
+            return Object.fromEntries(Object.entries(value).map(([k, v]) => [k, toHost(v)]));
+        }
+        return value;
+    };
+
+    return Object.freeze({
+        // Date-times arrive as ISO-8601 strings - epoch milliseconds are also accepted
+        'date': (value) => value instanceof Date ? value : new Date(value),
+        'toHost': toHost,
+        'isUuid': (value) => typeof value === 'string' && UUID.test(value),
+    });
+})();
+
+// The host call being run - its signal is aborted when the call times out or is cancelled
+// See `rustyscript::CallCanceller`
+const calls = (() => {
+    // Stands in for `AbortController` when the `web` feature is disabled
+    class CallAbortController {
+        constructor() {
+            const listeners = new Set();
+            this.signal = {
+                aborted: false,
+                reason: undefined,
+                onabort: null,
+                addEventListener: (type, listener) => type === 'abort' && listeners.add(listener),
+                removeEventListener: (type, listener) => listeners.delete(listener),
+                throwIfAborted() {
+                    if (this.aborted) throw this.reason;
+                },
+            };
+            this.abort = (reason) => {
+                if (this.signal.aborted) return;
+                Object.assign(this.signal, { aborted: true, reason });
+                const event = { type: 'abort', target: this.signal };
+                this.signal.onabort?.(event);
+                for (const listener of listeners) {
+                    typeof listener === 'function' ? listener(event) : listener.handleEvent(event);
+                }
+            };
+        }
+    }
+
+    let current = null;
+    return {
+        current() {
+            const id = Deno.core.ops.op_current_call();
+            if (current?.id !== id) {
+                const controller = new (globalThis.AbortController ?? CallAbortController)();
+                const aborted = Deno.core.ops.op_call_signal(id);
+                Deno.core.unrefOpPromise(aborted);
+                aborted.then((reason) => {
+                    if (reason === null) return;
+                    const error = new Error(reason);
+                    error.name = reason.startsWith('Call cancelled') ? 'AbortError' : 'TimeoutError';
+                    controller.abort(error);
+                });
+
+                current = Object.freeze({ id, signal: controller.signal });
+            }
+            return current;
+        },
+    };
+})();
+
+// Context passed by `Runtime::call_function_with_context` - kept in the async context, so it
+// follows the call across `await`s and timers, and is not seen by work started by other calls
+const callContext = (() => {
+    const CONTEXT = Symbol('rustyscript.context');
+    const saved = [];
+
+    const freeze = (value) => {
+        if (value !== null && typeof value === 'object') {
+            Object.values(value).forEach(freeze);
+            Object.freeze(value);
+        }
+        return value;
+    };
+
+    // Keeps any entries others have put in the async context, such as `AsyncLocalStorage` stores
+    const frameWith = (value) => {
+        const current = Deno.core.getAsyncContext();
+        const frame = current instanceof Map
+            ? Object.setPrototypeOf(new Map(current), Object.getPrototypeOf(current))
+            : new Map();
+        frame.set(CONTEXT, value);
+        return frame;
+    };
+
+    return {
+        enter(value) {
+            saved.push(Deno.core.getAsyncContext());
+            Deno.core.setAsyncContext(frameWith(freeze(value)));
+        },
+        exit() {
+            Deno.core.setAsyncContext(saved.pop());
+        },
+        get() {
+            const frame = Deno.core.getAsyncContext();
+            return frame instanceof Map ? frame.get(CONTEXT) : undefined;
+        },
+    };
+})();
+Deno.core.ops.op_bind_call_context(callContext.enter, callContext.exit);
+Deno.core.ops.op_bind_call_signal(() => calls.current().signal);
+
 // Populate the global object
 globalThis.rustyscript = {
+    'HostError': HostError,
+    'CircuitOpenError': CircuitOpenError,
+    'TimerLimitError': TimerLimitError,
+    'EntrypointError': EntrypointError,
     'register_entrypoint': (f) => Deno.core.ops.op_register_entrypoint(f),
+    'wrap': wrap,
+    'durable': (name, value) => {
+        Deno.core.ops.op_register_durable(name, value);
+        return value;
+    },
     'bail': (msg) => { throw new Error(msg) },
+
+    // Functions the host can list and call by name - see `Runtime::call_exposed`
+    'expose': (name, f) => {
+        if (typeof f !== 'function') throw new TypeError('rustyscript.expose expects a function');
+        Deno.core.ops.op_expose_function(String(name), f);
+        return f;
+    },
+    'unexpose': (name) => Deno.core.ops.op_unexpose_function(String(name)),
+
+    // Pushes a payload to the host's subscribers - see `Runtime::subscribe`
+    'emit': (event, payload = null) => Deno.core.ops.op_emit(String(event), payload),
+    'hasSubscribers': (event) => Deno.core.ops.op_has_subscribers(String(event)),
+
+    // Tells the host the current call is still alive - it may extend or abort the call's deadline
+    // Throws if the host aborted the call
+    'heartbeat': (message) => Deno.core.ops.op_heartbeat(null, message == null ? null : String(message)),
+    'progress': (percent, message) => {
+        const progress = Number(percent);
+        if (!Number.isFinite(progress)) throw new TypeError('progress must be a finite number');
+        Deno.core.ops.op_heartbeat(progress, message == null ? null : String(message));
+    },
+
+    // The host call being run - `signal` is aborted if the call times out or is cancelled
+    get 'currentCall'() { return calls.current(); },
+
+    // The context passed with `Runtime::call_function_with_context`, or undefined
+    'context': () => callContext.get(),
+
+    // Durable workflow steps - suspends the workflow until the host has completed the step
+    'step': (name, ...args) => {
+        const outcome = Deno.core.ops.op_workflow_step(name, args);
+        if (outcome === null) return new Promise(() => {});
+        if ('error' in outcome) return Promise.reject(new Error(outcome.error));
+        return Promise.resolve(outcome.value);
+    },
     
     'functions': new Proxy({}, {
         get: function(_target, name) {
@@ -40,10 +398,47 @@ globalThis.rustyscript = {
         get: function(_target, name) {
             return (...args) => Deno.core.ops.call_registered_function_async(name, args);
         }
-    })
+    }),
+
+    // Token buckets configured by the host - keys it has not limited are never throttled
+    'rateLimit': Object.freeze({
+        'acquire': (key, tokens = 1) => Deno.core.ops.op_rate_limit_acquire(String(key), tokens),
+        'tryAcquire': (key, tokens = 1) => Deno.core.ops.op_rate_limit_try_acquire(String(key), tokens),
+    }),
+
+    // Versions of rustyscript, deno_core and V8 the runtime was built with
+    'buildInfo': () => Deno.core.ops.op_build_info(),
+
+    // Conversions for the date-times, durations and UUIDs passed by the host
+    'types': types,
+
+    // Actions registered by the host
+    get 'actions'() { return namespaces.actions; },
+
+    // Incremental JSON parsing and serialization, and host-registered streams
+    get 'json'() { return namespaces.json; },
+
+    // MessagePack, CBOR and JSON encoders, for `Runtime::call_function_encoded`
+    get 'codecs'() { return namespaces.codecs; },
+
+    // Named byte-buffer transforms, for `Runtime::run_transform`
+    get 'pipeline'() { return namespaces.pipeline; },
+
+    // Requires the `sqlite` feature
+    get 'sqlite'() { return namespaces.sqlite; },
+
+    // Requires the `database` feature
+    get 'database'() { return namespaces.database; },
+
+    // Requires the `secrets` feature
+    get 'secrets'() { return namespaces.secrets; },
+
+    // Requires the `host_cache` feature
+    get 'cache'() { return namespaces.cache; }
 };
 Object.freeze(globalThis.rustyscript);
 
 export {
-    nonEnumerable, readOnly, writeable, getterOnly, applyToGlobal, applyToDeno
+    nonEnumerable, readOnly, writeable, getterOnly, applyToGlobal, applyToDeno, namespaces, limitTimers,
+    background
 };
\ No newline at end of file
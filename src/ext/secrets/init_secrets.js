@@ -0,0 +1,7 @@
+import { op_secrets_get } from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+// Resolves to the secret's value, or null if the provider has no such secret
+namespaces.secrets = Object.freeze({
+    get: (name) => op_secrets_get(String(name)),
+});
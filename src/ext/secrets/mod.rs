@@ -0,0 +1,261 @@
+//! Host-resolved secrets, exposed to scripts as `rustyscript.secrets`
+//!
+//! Secrets are fetched from a [`SecretsProvider`] - a vault, KMS, or anything else the host uses -
+//! only when a script asks for one by name, and only if the runtime's [`SecretStore`] allows that
+//! name. Every request is reported to the store's audit hook, whether or not it was granted
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use deno_core::{extension, op2, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// Resolves secrets by name, on demand
+#[async_trait::async_trait(?Send)]
+pub trait SecretsProvider {
+    /// Fetch the value of a secret, or `None` if it does not exist
+    ///
+    /// # Errors
+    /// Should return an error if the secret could not be fetched
+    async fn get(&self, name: &str) -> Result<Option<String>, Error>;
+}
+
+/// How a request for a secret was resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecretOutcome {
+    /// The secret was returned to the script
+    Granted,
+
+    /// The runtime is not allowed to read the secret
+    Denied,
+
+    /// The provider has no secret by that name
+    NotFound,
+
+    /// The provider failed to fetch the secret
+    Failed,
+}
+
+/// A request for a secret, as reported to [`SecretStore::with_audit`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecretAccess {
+    /// The name of the requested secret
+    pub name: String,
+
+    /// How the request was resolved
+    pub outcome: SecretOutcome,
+}
+
+/// The secrets available to a runtime's scripts
+///
+/// See [`crate::ExtensionOptions::secrets`]
+///
+/// ```rust
+/// use rustyscript::{Error, SecretStore, SecretsProvider};
+/// use std::rc::Rc;
+///
+/// struct Vault;
+///
+/// #[rustyscript::async_trait::async_trait(?Send)]
+/// impl SecretsProvider for Vault {
+///     async fn get(&self, name: &str) -> Result<Option<String>, Error> {
+///         Ok((name == "api_key").then(|| "s3cr3t".to_string()))
+///     }
+/// }
+///
+/// let store = SecretStore::new(Rc::new(Vault))
+///     .allow("api_key")
+///     .with_audit(|access| println!("{} -> {:?}", access.name, access.outcome));
+/// ```
+#[derive(Clone)]
+pub struct SecretStore {
+    provider: Rc<dyn SecretsProvider>,
+    allowed: Option<HashSet<String>>,
+    audit: Option<Rc<dyn Fn(&SecretAccess)>>,
+}
+
+impl SecretStore {
+    /// Create a store that, until names are allowed with [`SecretStore::allow`], grants nothing
+    #[must_use]
+    pub fn new(provider: Rc<dyn SecretsProvider>) -> Self {
+        Self {
+            provider,
+            allowed: Some(HashSet::new()),
+            audit: None,
+        }
+    }
+
+    /// Allow scripts to read the secret with this name
+    #[must_use]
+    pub fn allow(mut self, name: impl ToString) -> Self {
+        if let Some(allowed) = &mut self.allowed {
+            allowed.insert(name.to_string());
+        }
+        self
+    }
+
+    /// Allow scripts to read any secret the provider has
+    #[must_use]
+    pub fn allow_all(mut self) -> Self {
+        self.allowed = None;
+        self
+    }
+
+    /// Call `hook` for every request a script makes for a secret
+    #[must_use]
+    pub fn with_audit(mut self, hook: impl Fn(&SecretAccess) + 'static) -> Self {
+        self.audit = Some(Rc::new(hook));
+        self
+    }
+
+    /// Returns true if scripts may read the secret with this name
+    #[must_use]
+    pub fn is_allowed(&self, name: &str) -> bool {
+        match &self.allowed {
+            Some(allowed) => allowed.contains(name),
+            None => true,
+        }
+    }
+
+    fn report(&self, name: &str, outcome: SecretOutcome) {
+        if let Some(audit) = &self.audit {
+            audit(&SecretAccess {
+                name: name.to_string(),
+                outcome,
+            });
+        }
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>, Error> {
+        if !self.is_allowed(name) {
+            self.report(name, SecretOutcome::Denied);
+            return Err(Error::Runtime(format!(
+                "Permission denied: cannot read secret `{name}`"
+            )));
+        }
+
+        let result = self.provider.get(name).await;
+        let outcome = match &result {
+            Ok(Some(_)) => SecretOutcome::Granted,
+            Ok(None) => SecretOutcome::NotFound,
+            Err(_) => SecretOutcome::Failed,
+        };
+        self.report(name, outcome);
+        result
+    }
+}
+
+#[op2(async)]
+#[serde]
+async fn op_secrets_get(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+) -> Result<Option<String>, Error> {
+    let store = state
+        .try_borrow()?
+        .try_borrow::<SecretStore>()
+        .cloned()
+        .ok_or_else(|| Error::Runtime("No secrets provider is configured".to_string()))?;
+    store.get(&name).await
+}
+
+extension!(
+    init_secrets,
+    deps = [rustyscript],
+    ops = [op_secrets_get],
+    esm_entry_point = "ext:init_secrets/init_secrets.js",
+    esm = [ dir "src/ext/secrets", "init_secrets.js" ],
+    options = {
+        store: Option<SecretStore>
+    },
+    state = |state, config| {
+        if let Some(store) = config.store {
+            state.put(store);
+        }
+    },
+);
+impl ExtensionTrait<Option<SecretStore>> for init_secrets {
+    fn init(store: Option<SecretStore>) -> Extension {
+        init_secrets::init(store)
+    }
+}
+
+pub fn extensions(store: Option<SecretStore>, is_snapshot: bool) -> Vec<Extension> {
+    vec![init_secrets::build(store, is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ExtensionOptions, Module, Runtime, RuntimeOptions};
+
+    struct Vault;
+
+    #[async_trait::async_trait(?Send)]
+    impl SecretsProvider for Vault {
+        async fn get(&self, name: &str) -> Result<Option<String>, Error> {
+            match name {
+                "api_key" => Ok(Some("s3cr3t".to_string())),
+                "broken" => Err(Error::Runtime("vault unreachable".to_string())),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn test_secrets() {
+        let audit = Rc::new(RefCell::new(Vec::new()));
+        let hook_audit = audit.clone();
+        let store = SecretStore::new(Rc::new(Vault))
+            .allow("api_key")
+            .allow("missing")
+            .allow("broken")
+            .with_audit(move |access| hook_audit.borrow_mut().push(access.clone()));
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            extension_options: ExtensionOptions {
+                secrets: Some(store),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                const attempt = (name) => rustyscript.secrets.get(name).catch((e) => e.message);
+                export const key = await attempt('api_key');
+                export const missing = await attempt('missing');
+                export const denied = await attempt('db_password');
+                export const broken = await attempt('broken');
+            ",
+            ))
+            .unwrap();
+
+        let key: String = runtime.get_value(Some(&module), "key").unwrap();
+        assert_eq!(key, "s3cr3t");
+        let missing: Option<String> = runtime.get_value(Some(&module), "missing").unwrap();
+        assert!(missing.is_none());
+        let denied: String = runtime.get_value(Some(&module), "denied").unwrap();
+        assert!(denied.contains("Permission denied"));
+        let broken: String = runtime.get_value(Some(&module), "broken").unwrap();
+        assert!(broken.contains("vault unreachable"));
+
+        let outcomes: Vec<_> = audit
+            .borrow()
+            .iter()
+            .map(|access| (access.name.clone(), access.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("api_key".to_string(), SecretOutcome::Granted),
+                ("missing".to_string(), SecretOutcome::NotFound),
+                ("db_password".to_string(), SecretOutcome::Denied),
+                ("broken".to_string(), SecretOutcome::Failed),
+            ]
+        );
+    }
+}
@@ -0,0 +1,52 @@
+import { op_signal_bind_dispatcher } from "ext:core/ops";
+import { applyToDeno, nonEnumerable } from 'ext:rustyscript/rustyscript.js';
+
+// Signals scripts may listen for - delivered by the host with `Runtime::raise_signal`
+// Nothing here touches the real process signal handlers
+const SIGNALS = new Set([
+    'SIGABRT', 'SIGALRM', 'SIGBREAK', 'SIGBUS', 'SIGCHLD', 'SIGCONT', 'SIGEMT', 'SIGFPE',
+    'SIGHUP', 'SIGILL', 'SIGINFO', 'SIGINT', 'SIGIO', 'SIGKILL', 'SIGPIPE', 'SIGPOLL',
+    'SIGPROF', 'SIGPWR', 'SIGQUIT', 'SIGSEGV', 'SIGSTKFLT', 'SIGSTOP', 'SIGSYS', 'SIGTERM',
+    'SIGTRAP', 'SIGTSTP', 'SIGTTIN', 'SIGTTOU', 'SIGURG', 'SIGUSR1', 'SIGUSR2', 'SIGVTALRM',
+    'SIGWINCH', 'SIGXCPU', 'SIGXFSZ',
+]);
+const UNCATCHABLE = new Set(['SIGKILL', 'SIGSTOP']);
+
+const listeners = new Map();
+
+function checkSignal(signal) {
+    if (!SIGNALS.has(signal)) {
+        throw new TypeError(`Unknown signal: ${signal}`);
+    }
+}
+
+function addSignalListener(signal, handler) {
+    checkSignal(signal);
+    if (UNCATCHABLE.has(signal)) {
+        throw new TypeError(`Binding to signal '${signal}' is not allowed`);
+    }
+    if (typeof handler !== 'function') {
+        throw new TypeError('Signal handler must be a function');
+    }
+
+    if (!listeners.has(signal)) listeners.set(signal, new Set());
+    listeners.get(signal).add(handler);
+}
+
+function removeSignalListener(signal, handler) {
+    checkSignal(signal);
+    listeners.get(signal)?.delete(handler);
+}
+
+// Returns true if at least one listener received the signal
+op_signal_bind_dispatcher((signal) => {
+    checkSignal(signal);
+    const handlers = [...(listeners.get(signal) ?? [])];
+    handlers.forEach((handler) => handler());
+    return handlers.length > 0;
+});
+
+applyToDeno({
+    addSignalListener: nonEnumerable(addSignalListener),
+    removeSignalListener: nonEnumerable(removeSignalListener),
+});
@@ -0,0 +1,83 @@
+use deno_core::{extension, op2, v8, Extension, OpState};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// The JS function that delivers a virtual signal to the script's listeners
+pub(crate) struct SignalDispatcher(pub v8::Global<v8::Function>);
+
+impl SignalDispatcher {
+    /// Get the dispatcher from the runtime's state
+    pub(crate) fn get(state: &OpState) -> Result<v8::Global<v8::Function>, Error> {
+        state
+            .try_borrow::<Self>()
+            .map(|dispatcher| dispatcher.0.clone())
+            .ok_or_else(|| Error::Runtime("Signal dispatcher not initialized".to_string()))
+    }
+}
+
+/// Registers the function used by `Runtime::raise_signal` to deliver signals
+#[op2]
+fn op_signal_bind_dispatcher(
+    state: &mut OpState,
+    #[global] dispatcher: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The signal dispatcher", SignalDispatcher(dispatcher))
+}
+
+extension!(
+    init_signals,
+    deps = [rustyscript],
+    ops = [op_signal_bind_dispatcher],
+    esm_entry_point = "ext:init_signals/init_signals.js",
+    esm = [ dir "src/ext/signals", "init_signals.js" ],
+);
+impl ExtensionTrait<()> for init_signals {
+    fn init((): ()) -> Extension {
+        init_signals::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_signals::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_virtual_signals() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            export let received = [];
+            const onTerm = () => received.push('SIGTERM');
+            Deno.addSignalListener('SIGTERM', onTerm);
+            Deno.addSignalListener('SIGINT', () => received.push('SIGINT'));
+            export const unbind = () => Deno.removeSignalListener('SIGTERM', onTerm);
+        ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        assert!(runtime.raise_signal("SIGTERM").unwrap());
+        assert!(runtime.raise_signal("SIGINT").unwrap());
+        assert!(!runtime.raise_signal("SIGHUP").unwrap());
+        runtime
+            .raise_signal("SIGNOPE")
+            .expect_err("Raised an unknown signal");
+
+        runtime
+            .call_function::<crate::Undefined>(Some(&module), "unbind", crate::json_args!())
+            .unwrap();
+        assert!(!runtime.raise_signal("SIGTERM").unwrap());
+
+        let received: Vec<String> = runtime.get_value(Some(&module), "received").unwrap();
+        assert_eq!(received, vec!["SIGTERM", "SIGINT"]);
+
+        let result =
+            runtime.eval::<crate::Undefined>("Deno.addSignalListener('SIGKILL', () => {})");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,88 @@
+import {
+    op_sqlite_open, op_sqlite_close, op_sqlite_exec,
+    op_sqlite_prepare, op_sqlite_run, op_sqlite_query,
+} from "ext:core/ops";
+import { namespaces } from 'ext:rustyscript/rustyscript.js';
+
+// Values without a JSON representation are tagged on the way in and out
+const encode = (value) => {
+    if (value === undefined) return null;
+    if (typeof value === 'bigint') return { $bigint: value.toString() };
+    if (ArrayBuffer.isView(value)) {
+        return { $blob: Array.from(new Uint8Array(value.buffer, value.byteOffset, value.byteLength)) };
+    }
+    if (value instanceof ArrayBuffer) return { $blob: Array.from(new Uint8Array(value)) };
+    return value;
+};
+
+const decode = (value) => {
+    if (value === null || typeof value !== 'object') return value;
+    if ('$bigint' in value) return BigInt(value.$bigint);
+    if ('$blob' in value) return new Uint8Array(value.$blob);
+    return value;
+};
+
+const decodeRows = ({ columns, rows }) => rows.map((row) => {
+    const object = {};
+    columns.forEach((column, i) => object[column] = decode(row[i]));
+    return object;
+});
+
+class Statement {
+    #db;
+    #sql;
+
+    constructor(db, sql) {
+        this.#db = db;
+        this.#sql = sql;
+        op_sqlite_prepare(db, sql);
+    }
+
+    get sql() {
+        return this.#sql;
+    }
+
+    run(...params) {
+        const { changes, lastInsertRowid } = op_sqlite_run(this.#db, this.#sql, params.map(encode));
+        return { changes, lastInsertRowid: decode(lastInsertRowid) };
+    }
+
+    all(...params) {
+        return decodeRows(op_sqlite_query(this.#db, this.#sql, params.map(encode), false));
+    }
+
+    get(...params) {
+        return decodeRows(op_sqlite_query(this.#db, this.#sql, params.map(encode), true))[0];
+    }
+
+    values(...params) {
+        const { rows } = op_sqlite_query(this.#db, this.#sql, params.map(encode), false);
+        return rows.map((row) => row.map(decode));
+    }
+}
+
+class Database {
+    #id;
+
+    constructor(path, { readonly = false } = {}) {
+        this.#id = op_sqlite_open(String(path), !!readonly);
+    }
+
+    exec(sql) {
+        op_sqlite_exec(this.#id, String(sql));
+    }
+
+    prepare(sql) {
+        return new Statement(this.#id, String(sql));
+    }
+
+    close() {
+        op_sqlite_close(this.#id);
+    }
+}
+
+namespaces.sqlite = Object.freeze({
+    open: (path, options) => new Database(path, options),
+    Database,
+    Statement,
+});
@@ -0,0 +1,392 @@
+//! A minimal SQLite API for scripts, exposed as `rustyscript.sqlite`
+//!
+//! Databases on disk are opened through the runtime's [`crate::WebPermissions::check_open`],
+//! so the same allowlist that governs file access governs which databases a script may open.
+//! Statements cannot reach other files through `ATTACH` or `VACUUM INTO`
+use std::{borrow::Cow, collections::HashMap, path::Path};
+
+use deno_core::{extension, op2, serde_json, Extension, OpState};
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    params_from_iter,
+    types::{Value, ValueRef},
+    Connection, OpenFlags,
+};
+use serde::Serialize;
+
+use super::{web::PermissionsContainer, ExtensionTrait};
+use crate::Error;
+
+/// Integers outside this range lose precision as JS numbers, and are passed as `BigInt`
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// The databases opened by scripts in this runtime
+#[derive(Default)]
+struct SqliteDatabases {
+    next_id: u32,
+    open: HashMap<u32, Connection>,
+}
+
+/// Returns an open database from the runtime's state
+fn database(state: &OpState, id: u32) -> Result<&Connection, Error> {
+    state
+        .try_borrow::<SqliteDatabases>()
+        .and_then(|databases| databases.open.get(&id))
+        .ok_or_else(|| Error::Runtime("Database is closed".to_string()))
+}
+
+/// The result of running a statement that does not return rows
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunResult {
+    changes: usize,
+    last_insert_rowid: serde_json::Value,
+}
+
+/// Rows returned by a query, as positional values
+#[derive(Serialize)]
+struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+}
+
+fn sql_error(e: rusqlite::Error) -> Error {
+    Error::Runtime(format!("SQLite: {e}"))
+}
+
+/// Converts a parameter encoded by `init_sqlite.js` into an SQLite value
+fn to_sql(value: serde_json::Value) -> Result<Value, Error> {
+    match value {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Integer(i64::from(b))),
+        serde_json::Value::String(s) => Ok(Value::Text(s)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(Value::Integer(i)),
+            None => Ok(Value::Real(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        serde_json::Value::Object(mut object) => {
+            if let Some(serde_json::Value::String(s)) = object.remove("$bigint") {
+                return s
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| Error::Runtime(format!("BigInt {s} is out of range for SQLite")));
+            }
+            if let Some(blob) = object.remove("$blob") {
+                let blob: Vec<u8> =
+                    serde_json::from_value(blob).map_err(|e| Error::Runtime(e.to_string()))?;
+                return Ok(Value::Blob(blob));
+            }
+            Err(Error::Runtime(
+                "SQLite parameters must be null, a boolean, number, bigint, string or Uint8Array"
+                    .to_string(),
+            ))
+        }
+        serde_json::Value::Array(_) => Err(Error::Runtime(
+            "SQLite parameters cannot be arrays".to_string(),
+        )),
+    }
+}
+
+/// Converts an SQLite value into the encoding decoded by `init_sqlite.js`
+fn from_sql(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => integer(i),
+        ValueRef::Real(f) => serde_json::json!(f),
+        ValueRef::Text(s) => serde_json::Value::String(String::from_utf8_lossy(s).to_string()),
+        ValueRef::Blob(b) => serde_json::json!({ "$blob": b }),
+    }
+}
+
+fn integer(i: i64) -> serde_json::Value {
+    if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&i) {
+        serde_json::json!(i)
+    } else {
+        serde_json::json!({ "$bigint": i.to_string() })
+    }
+}
+
+fn params(params: Vec<serde_json::Value>) -> Result<Vec<Value>, Error> {
+    params.into_iter().map(to_sql).collect()
+}
+
+/// Denies attaching any database backed by a file
+///
+/// `ATTACH` and `VACUUM INTO` would otherwise open paths that never went through `check_open`.
+/// Temporary and in-memory databases are still allowed, which plain `VACUUM` relies on
+fn authorize(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Attach { filename } if !matches!(filename, "" | ":memory:") => {
+            Authorization::Deny
+        }
+        _ => Authorization::Allow,
+    }
+}
+
+/// Opens a database, returning its id
+///
+/// `:memory:` databases are always allowed - anything else must pass the runtime's permissions
+#[op2(fast)]
+fn op_sqlite_open(state: &mut OpState, #[string] path: &str, readonly: bool) -> Result<u32, Error> {
+    let connection = if path == ":memory:" {
+        Connection::open_in_memory()
+    } else {
+        let permissions = state.borrow::<PermissionsContainer>();
+        let path = permissions
+            .0
+            .check_open(
+                true,
+                true,
+                !readonly,
+                Cow::Borrowed(Path::new(path)),
+                "rustyscript.sqlite.open",
+            )
+            .ok_or_else(|| Error::Runtime(format!("Permission denied: cannot open {path}")))?;
+
+        let flags = if readonly {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        };
+        Connection::open_with_flags(path, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+    }
+    .map_err(sql_error)?;
+    connection.authorizer(Some(authorize));
+
+    if !state.has::<SqliteDatabases>() {
+        state.put(SqliteDatabases::default());
+    }
+    let databases = state.borrow_mut::<SqliteDatabases>();
+    let id = databases.next_id;
+    databases.next_id += 1;
+    databases.open.insert(id, connection);
+    Ok(id)
+}
+
+/// Closes a database - statements prepared from it will fail from then on
+#[op2(fast)]
+fn op_sqlite_close(state: &mut OpState, id: u32) {
+    if let Some(databases) = state.try_borrow_mut::<SqliteDatabases>() {
+        databases.open.remove(&id);
+    }
+}
+
+/// Runs one or more statements, without parameters or results
+#[op2(fast)]
+fn op_sqlite_exec(state: &mut OpState, id: u32, #[string] sql: &str) -> Result<(), Error> {
+    database(state, id)?.execute_batch(sql).map_err(sql_error)
+}
+
+/// Compiles a statement, so syntax errors surface from `prepare` instead of the first run
+///
+/// Statements are cached per connection, and reused by later runs of the same SQL
+#[op2(fast)]
+fn op_sqlite_prepare(state: &mut OpState, id: u32, #[string] sql: &str) -> Result<(), Error> {
+    database(state, id)?
+        .prepare_cached(sql)
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+/// Runs a statement, returning the number of rows changed
+#[op2]
+#[serde]
+fn op_sqlite_run(
+    state: &mut OpState,
+    id: u32,
+    #[string] sql: &str,
+    #[serde] args: Vec<serde_json::Value>,
+) -> Result<RunResult, Error> {
+    let connection = database(state, id)?;
+    let mut statement = connection.prepare_cached(sql).map_err(sql_error)?;
+
+    let changes = statement
+        .execute(params_from_iter(params(args)?))
+        .map_err(sql_error)?;
+    Ok(RunResult {
+        changes,
+        last_insert_rowid: integer(connection.last_insert_rowid()),
+    })
+}
+
+/// Runs a statement, returning its rows - or only the first, if `first` is set
+#[op2]
+#[serde]
+fn op_sqlite_query(
+    state: &mut OpState,
+    id: u32,
+    #[string] sql: &str,
+    #[serde] args: Vec<serde_json::Value>,
+    first: bool,
+) -> Result<QueryResult, Error> {
+    let mut statement = database(state, id)?
+        .prepare_cached(sql)
+        .map_err(sql_error)?;
+
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut cursor = statement
+        .query(params_from_iter(params(args)?))
+        .map_err(sql_error)?;
+    while let Some(row) = cursor.next().map_err(sql_error)? {
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(from_sql))
+            .collect::<Result<_, _>>()
+            .map_err(sql_error)?;
+        rows.push(values);
+
+        if first {
+            break;
+        }
+    }
+
+    Ok(QueryResult { columns, rows })
+}
+
+extension!(
+    init_sqlite,
+    deps = [rustyscript],
+    ops = [
+        op_sqlite_open, op_sqlite_close, op_sqlite_exec,
+        op_sqlite_prepare, op_sqlite_run, op_sqlite_query,
+    ],
+    esm_entry_point = "ext:init_sqlite/init_sqlite.js",
+    esm = [ dir "src/ext/sqlite", "init_sqlite.js" ],
+);
+impl ExtensionTrait<()> for init_sqlite {
+    fn init((): ()) -> Extension {
+        init_sqlite::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_sqlite::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use deno_core::serde_json;
+
+    use crate::{
+        AllowlistWebPermissions, ExtensionOptions, Module, Runtime, RuntimeOptions, WebOptions,
+    };
+
+    #[test]
+    fn test_sqlite() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                const db = rustyscript.sqlite.open(':memory:');
+                db.exec('CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, size REAL, data BLOB)');
+
+                const insert = db.prepare('INSERT INTO items (name, size, data) VALUES (?, ?, ?)');
+                export const inserted = insert.run('a', 1.5, new Uint8Array([1, 2, 3]));
+                insert.run('b', null, null);
+
+                const select = db.prepare('SELECT * FROM items ORDER BY id');
+                export const rows = select.all();
+                export const first = select.get();
+                export const blob = first.data instanceof Uint8Array && [...first.data];
+                export const big = db.prepare('SELECT ? + 1 AS n').get(9007199254740993n).n === 9007199254740994n;
+                export const missing = db.prepare('SELECT * FROM items WHERE name = ?').get('z') === undefined;
+
+                export let syntax;
+                try { db.prepare('SELEC 1'); } catch (e) { syntax = e.message; }
+
+                db.close();
+                export let closed;
+                try { select.all(); } catch (e) { closed = e.message; }
+            ",
+            ))
+            .unwrap();
+
+        let inserted: serde_json::Value = runtime.get_value(Some(&module), "inserted").unwrap();
+        assert_eq!(
+            inserted,
+            serde_json::json!({ "changes": 1, "lastInsertRowid": 1 })
+        );
+
+        let rows: serde_json::Value = runtime.get_value(Some(&module), "rows").unwrap();
+        assert_eq!(rows[0]["name"], "a");
+        assert_eq!(rows[0]["size"], 1.5);
+        assert_eq!(rows[1]["size"], serde_json::Value::Null);
+        assert_eq!(rows.as_array().unwrap().len(), 2);
+
+        let blob: Vec<u8> = runtime.get_value(Some(&module), "blob").unwrap();
+        assert_eq!(blob, vec![1, 2, 3]);
+        assert!(runtime.get_value::<bool>(Some(&module), "big").unwrap());
+        assert!(runtime.get_value::<bool>(Some(&module), "missing").unwrap());
+
+        let syntax: String = runtime.get_value(Some(&module), "syntax").unwrap();
+        assert!(syntax.contains("syntax error"), "{syntax}");
+        let closed: String = runtime.get_value(Some(&module), "closed").unwrap();
+        assert!(closed.contains("closed"), "{closed}");
+    }
+
+    #[test]
+    fn test_sqlite_permissions() {
+        let dir = std::env::temp_dir().join("rustyscript_test_sqlite");
+        std::fs::create_dir_all(&dir).unwrap();
+        let allowed = dir.join("allowed.db");
+        let allowed = allowed.to_str().unwrap();
+
+        let permissions = AllowlistWebPermissions::new();
+        permissions.allow_open(allowed, true, true);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            extension_options: ExtensionOptions {
+                web: WebOptions {
+                    permissions: std::sync::Arc::new(permissions),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        runtime
+            .eval::<()>(&format!(
+                "rustyscript.sqlite.open({allowed:?}).exec('CREATE TABLE IF NOT EXISTS t (x)')"
+            ))
+            .unwrap();
+
+        let denied = dir.join("denied.db");
+        let e = runtime
+            .eval::<()>(&format!(
+                "rustyscript.sqlite.open({:?})",
+                denied.to_str().unwrap()
+            ))
+            .unwrap_err();
+        assert!(e.to_string().contains("Permission denied"), "{e}");
+        assert!(!denied.exists());
+
+        // Neither can be reached through an open database
+        for sql in [
+            format!("ATTACH '{}' AS other", denied.display()),
+            format!("VACUUM INTO '{}'", denied.display()),
+        ] {
+            let e = runtime
+                .eval::<()>(&format!(
+                    "rustyscript.sqlite.open({allowed:?}).exec({sql:?})"
+                ))
+                .unwrap_err();
+            assert!(e.to_string().contains("not authorized"), "{e}");
+            assert!(!denied.exists());
+        }
+        runtime
+            .eval::<()>(&format!(
+                "rustyscript.sqlite.open({allowed:?}).exec('VACUUM')"
+            ))
+            .unwrap();
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,105 @@
+import { op_wasm_check_module, op_wasm_check_descriptor } from "ext:core/ops";
+
+// Checks WebAssembly against the runtime's limits before V8 sees it
+const Wasm = globalThis.WebAssembly;
+if (Wasm) {
+    const { compile, instantiate, validate, Module, Memory, Table } = Wasm;
+
+    const bytesOf = (source) => {
+        if (ArrayBuffer.isView(source)) {
+            return new Uint8Array(source.buffer, source.byteOffset, source.byteLength);
+        }
+        if (source instanceof ArrayBuffer || source instanceof SharedArrayBuffer) {
+            return new Uint8Array(source);
+        }
+        return null;
+    };
+
+    const check = (source) => {
+        const bytes = bytesOf(source);
+        if (bytes) op_wasm_check_module(bytes);
+    };
+
+    const checkDescriptor = (kind, descriptor) => {
+        const initial = Number(descriptor?.initial ?? descriptor?.minimum ?? 0);
+        const maximum = descriptor?.maximum === undefined ? null : Number(descriptor.maximum);
+        op_wasm_check_descriptor(kind, initial, maximum);
+    };
+
+    const reject = (fn) => {
+        try {
+            fn();
+            return null;
+        } catch (e) {
+            return Promise.reject(e);
+        }
+    };
+
+    Wasm.compile = function (source) {
+        return reject(() => check(source)) ?? compile.call(this, source);
+    };
+
+    Wasm.instantiate = function (source, imports) {
+        // An already-compiled `WebAssembly.Module` was checked when it was compiled
+        return reject(() => check(source)) ?? instantiate.call(this, source, imports);
+    };
+
+    Wasm.validate = function (source) {
+        try {
+            check(source);
+        } catch {
+            return false;
+        }
+        return validate.call(this, source);
+    };
+
+    // Streaming variants buffer the response, so the whole binary can be checked
+    const streamed = (source) => Promise.resolve(source).then((response) => response.arrayBuffer());
+    if (Wasm.compileStreaming) {
+        Wasm.compileStreaming = function (source) {
+            return streamed(source).then((bytes) => Wasm.compile(bytes));
+        };
+    }
+    if (Wasm.instantiateStreaming) {
+        Wasm.instantiateStreaming = function (source, imports) {
+            return streamed(source).then((bytes) => Wasm.instantiate(bytes, imports));
+        };
+    }
+
+    const guard = (Target, kind) => {
+        const Guarded = new Proxy(Target, {
+            construct(target, args, newTarget) {
+                if (kind === 'module') check(args[0]);
+                else checkDescriptor(kind, args[0]);
+                return Reflect.construct(target, args, newTarget === Guarded ? target : newTarget);
+            },
+        });
+
+        // Otherwise `instance.constructor` would hand back the unchecked original
+        Object.defineProperty(Target.prototype, 'constructor', {
+            value: Guarded,
+            writable: true,
+            enumerable: false,
+            configurable: true,
+        });
+        return Guarded;
+    };
+
+    Wasm.Module = guard(Module, 'module');
+    Wasm.Memory = guard(Memory, 'memory');
+    Wasm.Table = guard(Table, 'table');
+
+    // Growing from JS is checked too - growth from within WebAssembly is bounded by the declared maximum
+    const { grow: growMemory } = Memory.prototype;
+    Memory.prototype.grow = function (delta) {
+        const pages = this.buffer.byteLength / 65536 + Number(delta);
+        op_wasm_check_descriptor('memory', pages, pages);
+        return growMemory.call(this, delta);
+    };
+    const { grow: growTable } = Table.prototype;
+    Table.prototype.grow = function (delta, value) {
+        const size = this.length + Number(delta);
+        op_wasm_check_descriptor('table', size, size);
+        return growTable.call(this, delta, value);
+    };
+}
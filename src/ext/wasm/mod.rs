@@ -0,0 +1,415 @@
+//! Per-runtime limits on WebAssembly, enforced by checking modules before V8 compiles them
+use deno_core::{extension, op2, Extension, OpState};
+use serde::{Deserialize, Serialize};
+
+use super::ExtensionTrait;
+use crate::Error;
+
+/// Limits on the WebAssembly a runtime will compile and instantiate
+///
+/// See [`crate::RuntimeOptions::wasm_limits`]
+///
+/// Memory and table limits are checked against what a module declares: a module whose memory
+/// or table has no declared maximum is refused when a limit is set, since it could grow unbounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WasmLimits {
+    /// Refuse to compile any WebAssembly at all
+    pub deny: bool,
+
+    /// Maximum size, in bytes, of a WebAssembly binary
+    pub max_module_size: Option<usize>,
+
+    /// Maximum number of 64KiB pages for any memory, whether declared by a module or created from JS
+    pub max_memory_pages: Option<u64>,
+
+    /// Maximum number of elements for any table, whether declared by a module or created from JS
+    pub max_table_size: Option<u64>,
+}
+
+impl WasmLimits {
+    /// Returns true if this places no restrictions on WebAssembly
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn check_module(&self, bytes: &[u8]) -> Result<(), Error> {
+        if self.deny {
+            return Err(Error::Runtime(
+                "WebAssembly is disabled in this runtime".to_string(),
+            ));
+        }
+
+        if let Some(max) = self.max_module_size {
+            if bytes.len() > max {
+                return Err(Error::Runtime(format!(
+                    "WebAssembly module is {} bytes, over the limit of {max}",
+                    bytes.len()
+                )));
+            }
+        }
+
+        if self.max_memory_pages.is_none() && self.max_table_size.is_none() {
+            return Ok(());
+        }
+
+        // Anything the reader cannot follow is refused, rather than let through unchecked
+        let Some(declared) = parse_limits(bytes) else {
+            return Err(Error::Runtime(
+                "WebAssembly module could not be checked against the runtime's limits".to_string(),
+            ));
+        };
+        for limits in declared.memories {
+            self.check_memory(limits.initial, limits.maximum)?;
+        }
+        for limits in declared.tables {
+            self.check_table(limits.initial, limits.maximum)?;
+        }
+        Ok(())
+    }
+
+    fn check_memory(&self, initial: u64, maximum: Option<u64>) -> Result<(), Error> {
+        check_bound("memory", "pages", self.max_memory_pages, initial, maximum)
+    }
+
+    fn check_table(&self, initial: u64, maximum: Option<u64>) -> Result<(), Error> {
+        check_bound("table", "elements", self.max_table_size, initial, maximum)
+    }
+}
+
+fn check_bound(
+    kind: &str,
+    unit: &str,
+    limit: Option<u64>,
+    initial: u64,
+    maximum: Option<u64>,
+) -> Result<(), Error> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+
+    match maximum {
+        _ if initial > limit => Err(Error::Runtime(format!(
+            "WebAssembly {kind} starts at {initial} {unit}, over the limit of {limit}"
+        ))),
+        None => Err(Error::Runtime(format!(
+            "WebAssembly {kind} must declare a maximum of at most {limit} {unit}"
+        ))),
+        Some(maximum) if maximum > limit => Err(Error::Runtime(format!(
+            "WebAssembly {kind} may grow to {maximum} {unit}, over the limit of {limit}"
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Limits {
+    initial: u64,
+    maximum: Option<u64>,
+}
+
+/// The memories and tables declared or imported by a module
+#[derive(Debug, Default)]
+struct DeclaredLimits {
+    memories: Vec<Limits>,
+    tables: Vec<Limits>,
+}
+
+/// A minimal reader for the parts of the binary format that declare memories and tables
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(byte)
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    /// Unsigned LEB128
+    fn leb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.leb()?).ok()
+    }
+
+    fn name(&mut self) -> Option<()> {
+        let len = self.len()?;
+        self.take(len).map(|_| ())
+    }
+
+    fn limits(&mut self) -> Option<Limits> {
+        let flags = self.byte()?;
+        let initial = self.leb()?;
+        let maximum = if flags & 0x01 == 0 {
+            None
+        } else {
+            Some(self.leb()?)
+        };
+        Some(Limits { initial, maximum })
+    }
+
+    fn table_type(&mut self) -> Option<Limits> {
+        self.byte()?; // Element type
+        self.limits()
+    }
+}
+
+fn parse_limits(bytes: &[u8]) -> Option<DeclaredLimits> {
+    let mut reader = Reader { bytes };
+    if reader.take(4)? != b"\0asm" {
+        return None;
+    }
+    reader.take(4)?; // Version
+
+    let mut declared = DeclaredLimits::default();
+    while !reader.bytes.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.len()?;
+        let mut section = Reader {
+            bytes: reader.take(size)?,
+        };
+
+        match id {
+            // Imports
+            2 => {
+                for _ in 0..section.leb()? {
+                    section.name()?;
+                    section.name()?;
+                    match section.byte()? {
+                        0 => {
+                            section.leb()?;
+                        }
+                        1 => declared.tables.push(section.table_type()?),
+                        2 => declared.memories.push(section.limits()?),
+                        3 => {
+                            section.take(2)?;
+                        }
+                        4 => {
+                            section.byte()?;
+                            section.leb()?;
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+
+            // Tables
+            4 => {
+                for _ in 0..section.leb()? {
+                    // Tables with an initializer expression are prefixed with 0x40 0x00
+                    if section.bytes.first() == Some(&0x40) {
+                        section.take(2)?;
+                        declared.tables.push(section.table_type()?);
+                        while section.byte()? != 0x0b {}
+                    } else {
+                        declared.tables.push(section.table_type()?);
+                    }
+                }
+                if !section.bytes.is_empty() {
+                    return None;
+                }
+            }
+
+            // Memories
+            5 => {
+                for _ in 0..section.leb()? {
+                    declared.memories.push(section.limits()?);
+                }
+                if !section.bytes.is_empty() {
+                    return None;
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Some(declared)
+}
+
+/// Checks a WebAssembly binary against the runtime's limits, before it is compiled
+#[op2(fast)]
+fn op_wasm_check_module(state: &mut OpState, #[buffer] bytes: &[u8]) -> Result<(), Error> {
+    match state.try_borrow::<WasmLimits>() {
+        Some(limits) => limits.check_module(bytes),
+        None => Ok(()),
+    }
+}
+
+/// Checks a `WebAssembly.Memory` or `WebAssembly.Table` created from JS against the runtime's limits
+#[op2]
+fn op_wasm_check_descriptor(
+    state: &mut OpState,
+    #[string] kind: &str,
+    #[number] initial: u64,
+    #[serde] maximum: Option<u64>,
+) -> Result<(), Error> {
+    let Some(limits) = state.try_borrow::<WasmLimits>() else {
+        return Ok(());
+    };
+
+    if limits.deny {
+        return Err(Error::Runtime(
+            "WebAssembly is disabled in this runtime".to_string(),
+        ));
+    }
+
+    match kind {
+        "memory" => limits.check_memory(initial, maximum),
+        _ => limits.check_table(initial, maximum),
+    }
+}
+
+extension!(
+    init_wasm,
+    deps = [rustyscript],
+    ops = [op_wasm_check_module, op_wasm_check_descriptor],
+    esm_entry_point = "ext:init_wasm/init_wasm.js",
+    esm = [ dir "src/ext/wasm", "init_wasm.js" ],
+);
+impl ExtensionTrait<()> for init_wasm {
+    fn init((): ()) -> Extension {
+        init_wasm::init()
+    }
+}
+
+pub fn extensions(is_snapshot: bool) -> Vec<Extension> {
+    vec![init_wasm::build((), is_snapshot)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    /// `(module (memory (export "mem") 1 <max>))`, with the maximum omitted if `None`
+    fn memory_module(max: Option<u8>) -> Vec<u8> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        let limits = match max {
+            Some(max) => vec![0x01, 0x01, max],
+            None => vec![0x00, 0x01],
+        };
+        let size = u8::try_from(1 + limits.len()).unwrap();
+        bytes.extend([0x05, size, 0x01]);
+        bytes.extend(limits);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let declared = parse_limits(&memory_module(Some(4))).unwrap();
+        assert_eq!(
+            declared.memories,
+            vec![Limits {
+                initial: 1,
+                maximum: Some(4)
+            }]
+        );
+        assert!(declared.tables.is_empty());
+        assert!(parse_limits(b"not wasm").is_none());
+    }
+
+    #[test]
+    fn test_wasm_limits() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            wasm_limits: WasmLimits {
+                max_memory_pages: Some(16),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                export const compile = async (bytes) => {
+                    try {
+                        await WebAssembly.instantiate(new Uint8Array(bytes));
+                        return 'ok';
+                    } catch (e) {
+                        return e.message;
+                    }
+                };
+                export const memory = (maximum) => {
+                    try {
+                        new WebAssembly.Memory({ initial: 1, maximum });
+                        return 'ok';
+                    } catch (e) {
+                        return e.message;
+                    }
+                };
+            ",
+            ))
+            .unwrap();
+
+        let mut compile = |bytes: Vec<u8>| -> String {
+            runtime
+                .call_function(Some(&module), "compile", &(bytes,))
+                .unwrap()
+        };
+        assert_eq!(compile(memory_module(Some(8))), "ok");
+        assert!(compile(memory_module(Some(32))).contains("over the limit"));
+        assert!(compile(memory_module(None)).contains("must declare a maximum"));
+
+        let ok: String = runtime
+            .call_function(Some(&module), "memory", &(16,))
+            .unwrap();
+        assert_eq!(ok, "ok");
+        let refused: String = runtime
+            .call_function(Some(&module), "memory", &(17,))
+            .unwrap();
+        assert!(refused.contains("over the limit"));
+
+        // The originals can't be reached through an instance's constructor
+        let refused: String = runtime
+            .eval(
+                "(() => {
+                    const memory = new WebAssembly.Memory({ initial: 1, maximum: 1 });
+                    try {
+                        new memory.constructor({ initial: 1, maximum: 17 });
+                        return 'ok';
+                    } catch (e) {
+                        return e.message;
+                    }
+                })()",
+            )
+            .unwrap();
+        assert!(refused.contains("over the limit"), "{refused}");
+
+        // Denied entirely
+        let mut runtime = Runtime::new(RuntimeOptions {
+            wasm_limits: WasmLimits {
+                deny: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        let e = runtime
+            .eval::<()>("new WebAssembly.Module(new Uint8Array([0, 97, 115, 109, 1, 0, 0, 0]))")
+            .unwrap_err();
+        assert!(e.to_string().contains("disabled"), "{e}");
+    }
+}
@@ -0,0 +1,380 @@
+//! Compression codecs shared by the sandbox's `CompressionStream` and the host
+//!
+//! `gzip`, `deflate` and `deflate-raw` are implemented natively by `deno_web` in the sandbox -
+//! the host-side codecs here produce compatible output.
+//! `brotli` is implemented here for both, behind the `brotli` feature
+use std::{cell::RefCell, io::Write};
+
+use deno_core::{
+    futures::{stream, Stream, StreamExt},
+    op2, OpState, Resource, ResourceId, ToJsBuffer,
+};
+use flate2::{
+    write::{DeflateDecoder, DeflateEncoder, GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder},
+    Compression,
+};
+
+use crate::Error;
+
+/// A compression format supported by `CompressionStream`, `DecompressionStream` and the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionFormat {
+    /// The gzip format - `gzip` in JS
+    Gzip,
+
+    /// The zlib format - `deflate` in JS
+    Deflate,
+
+    /// Raw deflate data, with no header - `deflate-raw` in JS
+    DeflateRaw,
+
+    /// The brotli format - `brotli` in JS
+    #[cfg(feature = "brotli")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "brotli")))]
+    Brotli,
+}
+
+impl CompressionFormat {
+    /// The name of the format, as passed to `CompressionStream`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::DeflateRaw => "deflate-raw",
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "brotli",
+        }
+    }
+
+    /// Looks up a format by the name passed to `CompressionStream`
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            "deflate-raw" => Some(Self::DeflateRaw),
+            #[cfg(feature = "brotli")]
+            "brotli" => Some(Self::Brotli),
+            _ => None,
+        }
+    }
+
+    /// Compresses a stream of chunks, such as an [`crate::HttpBody`]
+    pub fn compress<S, D, E>(self, stream: S) -> impl Stream<Item = Result<Vec<u8>, Error>>
+    where
+        S: Stream<Item = Result<D, E>>,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        transform(Codec::new(self, false), stream)
+    }
+
+    /// Decompresses a stream of chunks, such as an [`crate::HttpBody`]
+    pub fn decompress<S, D, E>(self, stream: S) -> impl Stream<Item = Result<Vec<u8>, Error>>
+    where
+        S: Stream<Item = Result<D, E>>,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        transform(Codec::new(self, true), stream)
+    }
+}
+
+impl std::fmt::Display for CompressionFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An in-progress compression or decompression
+enum Codec {
+    GzipEncoder(GzEncoder<Vec<u8>>),
+    GzipDecoder(GzDecoder<Vec<u8>>),
+    DeflateEncoder(ZlibEncoder<Vec<u8>>),
+    DeflateDecoder(ZlibDecoder<Vec<u8>>),
+    DeflateRawEncoder(DeflateEncoder<Vec<u8>>),
+    DeflateRawDecoder(DeflateDecoder<Vec<u8>>),
+
+    #[cfg(feature = "brotli")]
+    BrotliEncoder(Box<brotli::CompressorWriter<Vec<u8>>>),
+    #[cfg(feature = "brotli")]
+    BrotliDecoder(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+/// Size of the internal buffer used by the brotli codecs
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+impl Codec {
+    fn new(format: CompressionFormat, decompress: bool) -> Self {
+        let buffer = Vec::new();
+        match (format, decompress) {
+            (CompressionFormat::Gzip, false) => {
+                Self::GzipEncoder(GzEncoder::new(buffer, Compression::default()))
+            }
+            (CompressionFormat::Gzip, true) => Self::GzipDecoder(GzDecoder::new(buffer)),
+            (CompressionFormat::Deflate, false) => {
+                Self::DeflateEncoder(ZlibEncoder::new(buffer, Compression::default()))
+            }
+            (CompressionFormat::Deflate, true) => Self::DeflateDecoder(ZlibDecoder::new(buffer)),
+            (CompressionFormat::DeflateRaw, false) => {
+                Self::DeflateRawEncoder(DeflateEncoder::new(buffer, Compression::default()))
+            }
+            (CompressionFormat::DeflateRaw, true) => {
+                Self::DeflateRawDecoder(DeflateDecoder::new(buffer))
+            }
+
+            #[cfg(feature = "brotli")]
+            (CompressionFormat::Brotli, false) => Self::BrotliEncoder(Box::new(
+                brotli::CompressorWriter::new(buffer, BROTLI_BUFFER_SIZE, 6, 22),
+            )),
+            #[cfg(feature = "brotli")]
+            (CompressionFormat::Brotli, true) => Self::BrotliDecoder(Box::new(
+                brotli::DecompressorWriter::new(buffer, BROTLI_BUFFER_SIZE),
+            )),
+        }
+    }
+
+    /// Feeds a chunk into the codec, returning any output it produced
+    fn write(&mut self, chunk: &[u8]) -> Result<Vec<u8>, Error> {
+        macro_rules! write_chunk {
+            ($writer:expr) => {{
+                $writer.write_all(chunk).map_err(codec_error)?;
+                Ok(std::mem::take($writer.get_mut()))
+            }};
+        }
+
+        match self {
+            Self::GzipEncoder(w) => write_chunk!(w),
+            Self::GzipDecoder(w) => write_chunk!(w),
+            Self::DeflateEncoder(w) => write_chunk!(w),
+            Self::DeflateDecoder(w) => write_chunk!(w),
+            Self::DeflateRawEncoder(w) => write_chunk!(w),
+            Self::DeflateRawDecoder(w) => write_chunk!(w),
+
+            #[cfg(feature = "brotli")]
+            Self::BrotliEncoder(w) => write_chunk!(w),
+            #[cfg(feature = "brotli")]
+            Self::BrotliDecoder(w) => write_chunk!(w),
+        }
+    }
+
+    /// Ends the stream, returning the remaining output
+    fn finish(self) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::GzipEncoder(w) => w.finish().map_err(codec_error),
+            Self::GzipDecoder(w) => w.finish().map_err(codec_error),
+            Self::DeflateEncoder(w) => w.finish().map_err(codec_error),
+            Self::DeflateDecoder(w) => w.finish().map_err(codec_error),
+            Self::DeflateRawEncoder(w) => w.finish().map_err(codec_error),
+            Self::DeflateRawDecoder(w) => w.finish().map_err(codec_error),
+
+            #[cfg(feature = "brotli")]
+            Self::BrotliEncoder(w) => Ok(w.into_inner()),
+            #[cfg(feature = "brotli")]
+            Self::BrotliDecoder(mut w) => {
+                w.close().map_err(codec_error)?;
+                w.into_inner()
+                    .map_err(|_| Error::Runtime("Truncated brotli stream".to_string()))
+            }
+        }
+    }
+}
+
+fn codec_error(e: std::io::Error) -> Error {
+    Error::Runtime(format!("Compression error: {e}"))
+}
+
+/// Runs a stream of chunks through a codec
+fn transform<S, D, E>(codec: Codec, input: S) -> impl Stream<Item = Result<Vec<u8>, Error>>
+where
+    S: Stream<Item = Result<D, E>>,
+    D: Into<Vec<u8>>,
+    E: std::fmt::Display,
+{
+    let input = Box::pin(input);
+    stream::unfold(Some((codec, input)), |state| async move {
+        let (mut codec, mut input) = state?;
+        loop {
+            let output = match input.next().await {
+                Some(Ok(chunk)) => match codec.write(&chunk.into()) {
+                    Ok(output) if output.is_empty() => continue,
+                    Ok(output) => Ok(output),
+                    Err(e) => return Some((Err(e), None)),
+                },
+                Some(Err(e)) => return Some((Err(Error::Runtime(e.to_string())), None)),
+                None => {
+                    return match codec.finish() {
+                        Ok(output) if output.is_empty() => None,
+                        result => Some((result, None)),
+                    }
+                }
+            };
+
+            return Some((output, Some((codec, input))));
+        }
+    })
+}
+
+/// A sandbox stream's codec, for formats `deno_web` does not implement
+struct CodecResource(RefCell<Option<Codec>>);
+impl Resource for CodecResource {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "rustyscriptCompression".into()
+    }
+}
+
+/// Returns the formats implemented by the host, rather than natively by `deno_web`
+#[op2]
+#[serde]
+pub fn op_compression_host_formats() -> Vec<&'static str> {
+    vec![
+        #[cfg(feature = "brotli")]
+        CompressionFormat::Brotli.as_str(),
+    ]
+}
+
+/// Starts a compression or decompression stream
+#[op2]
+#[smi]
+pub fn op_compression_new(
+    state: &mut OpState,
+    #[string] format: &str,
+    decompress: bool,
+) -> Result<ResourceId, Error> {
+    let format = CompressionFormat::from_name(format)
+        .ok_or_else(|| Error::Runtime(format!("Unsupported compression format: {format}")))?;
+    let codec = Codec::new(format, decompress);
+    Ok(state
+        .resource_table
+        .add(CodecResource(RefCell::new(Some(codec)))))
+}
+
+/// Feeds a chunk into a compression stream, returning its output so far
+#[op2]
+#[serde]
+pub fn op_compression_write(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[buffer] chunk: &[u8],
+) -> Result<ToJsBuffer, Error> {
+    let resource = state
+        .resource_table
+        .get::<CodecResource>(rid)
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+    let mut codec = resource.0.borrow_mut();
+    let codec = codec
+        .as_mut()
+        .ok_or_else(|| Error::Runtime("Compression stream is closed".to_string()))?;
+    Ok(codec.write(chunk)?.into())
+}
+
+/// Ends a compression stream, returning the remaining output
+#[op2]
+#[serde]
+pub fn op_compression_finish(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<ToJsBuffer, Error> {
+    let resource = state
+        .resource_table
+        .take::<CodecResource>(rid)
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+    let codec = resource
+        .0
+        .borrow_mut()
+        .take()
+        .ok_or_else(|| Error::Runtime("Compression stream is closed".to_string()))?;
+    Ok(codec.finish()?.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    fn collect(stream: impl Stream<Item = Result<Vec<u8>, Error>>) -> Vec<u8> {
+        let chunks: Vec<_> = deno_core::futures::executor::block_on(stream.collect());
+        chunks.into_iter().flat_map(Result::unwrap).collect()
+    }
+
+    fn chunks(data: &[u8]) -> impl Stream<Item = Result<Vec<u8>, Error>> {
+        let chunks: Vec<_> = data.chunks(7).map(|c| Ok(c.to_vec())).collect();
+        stream::iter(chunks)
+    }
+
+    #[test]
+    fn test_host_codecs() {
+        let data = "hello world, hello world, hello world".repeat(10);
+        for format in [
+            CompressionFormat::Gzip,
+            CompressionFormat::Deflate,
+            CompressionFormat::DeflateRaw,
+            #[cfg(feature = "brotli")]
+            CompressionFormat::Brotli,
+        ] {
+            assert_eq!(CompressionFormat::from_name(format.as_str()), Some(format));
+
+            let compressed = collect(format.compress(chunks(data.as_bytes())));
+            assert!(compressed.len() < data.len());
+
+            let decompressed = collect(format.decompress(chunks(&compressed)));
+            assert_eq!(decompressed, data.as_bytes());
+        }
+
+        let error: Vec<_> = deno_core::futures::executor::block_on(
+            CompressionFormat::Gzip
+                .decompress(chunks(b"not gzip"))
+                .collect(),
+        );
+        assert!(error.iter().any(Result::is_err));
+    }
+
+    #[test]
+    fn test_compression_streams() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                export async function decompress(format, bytes) {
+                    const stream = new Blob([new Uint8Array(bytes)]).stream()
+                        .pipeThrough(new DecompressionStream(format));
+                    return await new Response(stream).text();
+                }
+
+                export async function roundtrip(format, text) {
+                    const stream = new Blob([text]).stream()
+                        .pipeThrough(new CompressionStream(format))
+                        .pipeThrough(new DecompressionStream(format));
+                    return await new Response(stream).text();
+                }
+            ",
+            ))
+            .unwrap();
+
+        let mut formats = vec!["gzip", "deflate", "deflate-raw"];
+        if cfg!(feature = "brotli") {
+            formats.push("brotli");
+        }
+
+        for name in formats {
+            let format = CompressionFormat::from_name(name).unwrap();
+            let compressed = collect(format.compress(chunks(b"hello from the host")));
+            let text: String = runtime
+                .call_function(Some(&module), "decompress", json_args!(name, compressed))
+                .unwrap();
+            assert_eq!(text, "hello from the host");
+
+            let text: String = runtime
+                .call_function(Some(&module), "roundtrip", json_args!(name, "hello"))
+                .unwrap();
+            assert_eq!(text, "hello");
+        }
+
+        runtime
+            .call_function::<String>(Some(&module), "roundtrip", json_args!("unknown", "hello"))
+            .expect_err("Used an unknown format");
+    }
+}
@@ -0,0 +1,181 @@
+//! The default HTTP client used by `fetch`, and its connection pool
+use deno_core::OpState;
+use deno_fetch::CreateHttpClientOptions;
+
+use super::{FetchHttpVersion, FetchPoolOptions, WebOptions};
+use crate::Error;
+
+/// Builds the client used by fetches that do not supply their own `Deno.HttpClient`
+///
+/// Only needed when the runtime restricts HTTP versions or tunes the pool - otherwise
+/// `deno_fetch` creates an equivalent client itself
+#[derive(Clone)]
+pub(crate) struct DefaultClientFactory {
+    options: WebOptions,
+}
+
+impl DefaultClientFactory {
+    /// Returns a factory if the options call for a client `deno_fetch` would not create
+    pub fn from_options(options: &WebOptions) -> Option<Self> {
+        let pool = options.fetch_pool;
+        let customized = options.fetch_http_version != FetchHttpVersion::Auto
+            || pool.max_idle_per_host.is_some()
+            || pool.idle_timeout.is_some();
+
+        customized.then(|| Self {
+            options: options.clone(),
+        })
+    }
+
+    /// Creates a new client, with an empty connection pool
+    pub fn create(&self) -> Result<deno_fetch::Client, Error> {
+        let options = &self.options;
+        let FetchPoolOptions {
+            max_idle_per_host,
+            idle_timeout,
+            ..
+        } = options.fetch_pool;
+
+        let root_cert_store = options
+            .effective_root_cert_store_provider()
+            .map(|provider| provider.get_or_try_init().cloned())
+            .transpose()
+            .map_err(|e| Error::Runtime(e.to_string()))?;
+
+        let client = deno_fetch::create_http_client(
+            &options.user_agent,
+            CreateHttpClientOptions {
+                root_cert_store,
+                ca_certs: vec![],
+                proxy: options.proxy.clone(),
+                dns_resolver: options.resolver.clone(),
+                unsafely_ignore_certificate_errors: options
+                    .unsafely_ignore_certificate_errors
+                    .clone(),
+                client_cert_chain_and_key: options
+                    .effective_client_cert_chain_and_key()
+                    .try_into()
+                    .unwrap_or_default(),
+                pool_max_idle_per_host: max_idle_per_host,
+                pool_idle_timeout: idle_timeout
+                    .map(|timeout| Some(u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX))),
+                http1: options.fetch_http_version != FetchHttpVersion::Http2Only,
+                http2: options.fetch_http_version != FetchHttpVersion::Http1Only,
+                local_address: None,
+                client_builder_hook: options.client_builder_hook,
+            },
+        )
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+
+        Ok(client)
+    }
+}
+
+/// Installs the runtime's default client, if it needs one of its own
+///
+/// A client that fails to build is left to `deno_fetch`, which reports the same error on the first fetch
+pub(crate) fn install(state: &mut OpState, factory: Option<DefaultClientFactory>) {
+    let Some(factory) = factory else {
+        return;
+    };
+
+    if let Ok(client) = factory.create() {
+        state.put(client);
+    }
+    state.put(factory);
+}
+
+/// Replaces the default client with a new one, dropping its pool of idle connections
+///
+/// Requests already in flight keep their connections until they complete
+pub(crate) fn close_idle_connections(state: &mut OpState) -> Result<(), Error> {
+    state.try_take::<deno_fetch::Client>();
+    if let Some(factory) = state.try_borrow::<DefaultClientFactory>() {
+        let client = factory.create()?;
+        state.put(client);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_fetch_pool() {
+        let mut options = RuntimeOptions::default();
+        assert!(DefaultClientFactory::from_options(&options.extension_options.web).is_none());
+
+        options.extension_options.web.fetch_pool = FetchPoolOptions {
+            max_idle_per_host: Some(1),
+            idle_timeout: Some(Duration::from_secs(5)),
+            max_concurrent_requests: Some(1),
+        };
+        assert!(DefaultClientFactory::from_options(&options.extension_options.web).is_some());
+        let mut runtime = Runtime::new(options).unwrap();
+
+        // Requests queue for the single slot, and each releases it when it fails
+        let failures: usize = runtime
+            .eval(
+                "Promise.allSettled([1, 2, 3].map(() => fetch('http://127.0.0.1:9/')))
+                    .then((results) => results.filter((r) => r.status === 'rejected').length)",
+            )
+            .unwrap();
+        assert_eq!(failures, 3);
+
+        runtime.close_idle_connections().unwrap();
+        let failed: bool = runtime
+            .eval("fetch('http://127.0.0.1:9/').then(() => false, (e) => e instanceof TypeError)")
+            .unwrap();
+        assert!(failed);
+    }
+
+    #[test]
+    fn test_http2_only() {
+        // A plaintext HTTP/2 server, answering each request with its version and gRPC-style trailers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        listener.set_nonblocking(true).unwrap();
+        std::thread::spawn(move || {
+            let server = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            server.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut connection = h2::server::handshake(stream).await.unwrap();
+                while let Some(request) = connection.accept().await {
+                    let (request, mut respond) = request.unwrap();
+                    let response = http::Response::builder()
+                        .header("content-type", "application/grpc")
+                        .body(())
+                        .unwrap();
+                    let mut body = respond.send_response(response, false).unwrap();
+                    let version = format!("{:?}", request.version());
+                    body.send_data(version.into(), false).unwrap();
+
+                    let mut trailers = http::HeaderMap::new();
+                    trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+                    body.send_trailers(trailers).unwrap();
+                }
+            });
+        });
+
+        let mut options = RuntimeOptions::default();
+        options.extension_options.web.fetch_http_version = FetchHttpVersion::Http2Only;
+        let mut runtime = Runtime::new(options).unwrap();
+
+        // Spoken with prior knowledge, and with the trailers intact
+        let (version, status): (String, String) = runtime
+            .eval(&format!(
+                "fetch({url:?}).then(async (r) => [await r.text(), (await r.trailer).get('grpc-status')])"
+            ))
+            .unwrap();
+        assert_eq!(version, "HTTP/2.0");
+        assert_eq!(status, "0");
+    }
+}
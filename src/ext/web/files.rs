@@ -0,0 +1,250 @@
+//! Exchange of `Blob`, `File` and `FormData` contents between the sandbox and the host
+use deno_core::{
+    futures::{Stream, StreamExt},
+    op2,
+    serde_v8::JsBuffer,
+    v8, OpState, ToJsBuffer,
+};
+use serde::Deserialize;
+
+use crate::Error;
+
+/// A file exchanged with the sandbox as a `Blob` or `File`
+///
+/// Contents are passed as raw bytes, with no base64 round-trip
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostFile {
+    /// The file name - `None` for a plain `Blob`
+    pub name: Option<String>,
+
+    /// The MIME type of the contents, or an empty string if unknown
+    pub content_type: String,
+
+    /// The file contents
+    pub data: Vec<u8>,
+}
+
+impl HostFile {
+    /// Creates a `Blob` with the given contents
+    #[must_use]
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the file contents from a stream
+    ///
+    /// # Errors
+    /// Will return an error if the stream yields an error
+    pub async fn from_stream<S, D, E>(stream: S) -> Result<Self, Error>
+    where
+        S: Stream<Item = Result<D, E>>,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        let mut data = Vec::new();
+        let mut stream = std::pin::pin!(stream);
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| Error::Runtime(e.to_string()))?;
+            data.extend(chunk.into());
+        }
+
+        Ok(Self::new(data))
+    }
+
+    /// Sets the file name, making the value a `File` rather than a `Blob`
+    #[must_use]
+    pub fn with_name(mut self, name: impl ToString) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the MIME type of the contents
+    #[must_use]
+    pub fn with_content_type(mut self, content_type: impl ToString) -> Self {
+        self.content_type = content_type.to_string();
+        self
+    }
+
+    pub(crate) fn into_args(self) -> (Option<String>, String, ToJsBuffer) {
+        (self.name, self.content_type, self.data.into())
+    }
+}
+
+/// A single entry read from a sandbox `FormData`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormDataEntry {
+    /// A plain text field
+    Text {
+        /// The field name
+        name: String,
+
+        /// The field value
+        value: String,
+    },
+
+    /// A file upload
+    File {
+        /// The field name
+        name: String,
+
+        /// The uploaded file
+        file: HostFile,
+    },
+}
+
+impl FormDataEntry {
+    /// The field name of the entry
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Text { name, .. } | Self::File { name, .. } => name,
+        }
+    }
+}
+
+/// A file as read back from the sandbox
+#[derive(Deserialize)]
+pub(crate) struct WireFile {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    content_type: String,
+    data: JsBuffer,
+}
+
+impl From<WireFile> for HostFile {
+    fn from(value: WireFile) -> Self {
+        Self {
+            name: value.name,
+            content_type: value.content_type,
+            data: value.data.to_vec(),
+        }
+    }
+}
+
+/// A `FormData` entry as read back from the sandbox
+#[derive(Deserialize)]
+pub(crate) struct WireEntry {
+    name: String,
+    value: Option<String>,
+    file: Option<WireFile>,
+}
+
+impl From<WireEntry> for FormDataEntry {
+    fn from(value: WireEntry) -> Self {
+        match value.file {
+            Some(file) => Self::File {
+                name: value.name,
+                file: file.into(),
+            },
+            None => Self::Text {
+                name: value.name,
+                value: value.value.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// JS functions used to create and read sandbox files
+#[derive(Clone)]
+pub(crate) struct FileHelpers {
+    pub create_file: v8::Global<v8::Function>,
+    pub read_file: v8::Global<v8::Function>,
+    pub form_data_entries: v8::Global<v8::Function>,
+}
+
+impl FileHelpers {
+    /// Returns the JS helpers for exchanging files
+    pub fn get(state: &OpState) -> Result<Self, Error> {
+        state
+            .try_borrow::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::Runtime("File conversions are not initialized".to_string()))
+    }
+}
+
+/// Registers the JS helpers for exchanging files
+#[op2]
+pub fn op_files_bind_helpers(
+    state: &mut OpState,
+    #[global] create_file: v8::Global<v8::Function>,
+    #[global] read_file: v8::Global<v8::Function>,
+    #[global] form_data_entries: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(
+        state,
+        "The file helpers",
+        FileHelpers {
+            create_file,
+            read_file,
+            form_data_entries,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{js_value::Value, json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_files() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                export const describe = async (file) => `${file.name}:${file.type}:${await file.text()}`;
+                export const upload = () => {
+                    const form = new FormData();
+                    form.append('title', 'report');
+                    form.append('attachment', new Blob([new Uint8Array([0, 1, 255])], { type: 'application/octet-stream' }), 'data.bin');
+                    return form;
+                };
+            ",
+            ))
+            .unwrap();
+
+        // Host to sandbox
+        let file = HostFile::new("hello")
+            .with_name("hello.txt")
+            .with_content_type("text/plain");
+        let file = runtime.create_file(file).unwrap();
+        let description: String = runtime
+            .call_function(Some(&module), "describe", json_args!(file))
+            .unwrap();
+        assert_eq!(description, "hello.txt:text/plain:hello");
+
+        let blob = runtime.create_file(HostFile::new(vec![1, 2, 3])).unwrap();
+        let blob = runtime.read_file(&blob).unwrap();
+        assert_eq!(blob, HostFile::new(vec![1, 2, 3]));
+
+        // Sandbox to host
+        let form: Value = runtime
+            .call_function(Some(&module), "upload", json_args!())
+            .unwrap();
+        let entries = runtime.form_data_entries(&form).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                FormDataEntry::Text {
+                    name: "title".to_string(),
+                    value: "report".to_string()
+                },
+                FormDataEntry::File {
+                    name: "attachment".to_string(),
+                    file: HostFile::new(vec![0, 1, 255])
+                        .with_name("data.bin")
+                        .with_content_type("application/octet-stream"),
+                },
+            ]
+        );
+
+        let value: Value = runtime.eval("1").unwrap();
+        runtime
+            .form_data_entries(&value)
+            .expect_err("Read entries from a non-FormData value");
+    }
+}
@@ -0,0 +1,435 @@
+//! Recorded responses for `fetch`, so runs of embedded scripts can be made hermetic
+//!
+//! With [`FetchMode::Record`], every response is read in full and saved to a [`FixtureStore`]
+//! before the script sees it. With [`FetchMode::Offline`], the network is never used: requests are
+//! answered from the store, and fail if nothing was recorded for them.
+//!
+//! Fixtures are keyed by method and URL - request headers and bodies are not compared.
+//! Replayed requests must still pass the runtime's permissions, as if they went to the network
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use deno_core::{op2, serde_json, OpState, ToJsBuffer};
+use serde::{Deserialize, Serialize};
+
+use super::PermissionsContainer;
+use crate::Error;
+
+/// How `fetch` uses a runtime's [`FetchFixtures`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FetchMode {
+    /// Requests go to the network, and nothing is recorded
+    #[default]
+    Live,
+
+    /// Requests go to the network, and their responses are saved to the store
+    Record,
+
+    /// Requests are answered from the store only - missing fixtures fail the request
+    Offline,
+}
+
+impl FetchMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Live => "live",
+            Self::Record => "record",
+            Self::Offline => "offline",
+        }
+    }
+}
+
+/// A response saved by [`FetchMode::Record`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedResponse {
+    /// The request method, in uppercase
+    pub method: String,
+
+    /// The request URL
+    pub url: String,
+
+    /// The response status
+    pub status: u16,
+
+    /// The response headers
+    pub headers: Vec<(String, String)>,
+
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+/// A backend for [`FetchFixtures`]
+pub trait FixtureStore: Send + Sync {
+    /// Get the response recorded for a request, if any
+    ///
+    /// # Errors
+    /// Should return an error if the store cannot be read
+    fn load(&self, method: &str, url: &str) -> Result<Option<RecordedResponse>, Error>;
+
+    /// Save a response, replacing any recorded for the same request
+    ///
+    /// # Errors
+    /// Should return an error if the store cannot be written
+    fn save(&self, response: &RecordedResponse) -> Result<(), Error>;
+}
+
+/// An in-memory [`FixtureStore`]
+#[derive(Debug, Default)]
+pub struct MemoryFixtureStore(Mutex<HashMap<(String, String), RecordedResponse>>);
+
+impl MemoryFixtureStore {
+    /// Create an empty store
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every recorded response
+    #[must_use]
+    pub fn responses(&self) -> Vec<RecordedResponse> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(String, String), RecordedResponse>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl FixtureStore for MemoryFixtureStore {
+    fn load(&self, method: &str, url: &str) -> Result<Option<RecordedResponse>, Error> {
+        Ok(self
+            .lock()
+            .get(&(method.to_string(), url.to_string()))
+            .cloned())
+    }
+
+    fn save(&self, response: &RecordedResponse) -> Result<(), Error> {
+        let key = (response.method.clone(), response.url.clone());
+        self.lock().insert(key, response.clone());
+        Ok(())
+    }
+}
+
+/// A [`FixtureStore`] keeping one JSON file per request in a directory - suitable for checking in
+///
+/// Text bodies are saved as strings, so fixtures can be reviewed and edited by hand
+#[derive(Debug, Clone)]
+pub struct DirectoryFixtureStore {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureFile {
+    method: String,
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bytes: Option<Vec<u8>>,
+}
+
+impl DirectoryFixtureStore {
+    /// Use the given directory, which is created when the first fixture is saved
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the file a request's fixture is kept in
+    #[must_use]
+    pub fn path_for(&self, method: &str, url: &str) -> PathBuf {
+        let readable = |s: &str, len: usize| -> String {
+            s.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .take(len)
+                .collect()
+        };
+        let method_name = readable(&method.to_lowercase(), 16);
+        let url_name = readable(url.split_once("://").map_or(url, |(_, rest)| rest), 64);
+
+        // FNV-1a, so names stay the same across platforms and compiler versions
+        let hash = format!("{method} {url}")
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+
+        self.dir
+            .join(format!("{method_name}_{url_name}_{hash:016x}.json"))
+    }
+}
+
+impl FixtureStore for DirectoryFixtureStore {
+    fn load(&self, method: &str, url: &str) -> Result<Option<RecordedResponse>, Error> {
+        let path = self.path_for(method, url);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let file: FixtureFile = serde_json::from_slice(&std::fs::read(path)?)?;
+        let body = match (file.text, file.bytes) {
+            (Some(text), _) => text.into_bytes(),
+            (None, bytes) => bytes.unwrap_or_default(),
+        };
+        Ok(Some(RecordedResponse {
+            method: file.method,
+            url: file.url,
+            status: file.status,
+            headers: file.headers,
+            body,
+        }))
+    }
+
+    fn save(&self, response: &RecordedResponse) -> Result<(), Error> {
+        let (text, bytes) = match String::from_utf8(response.body.clone()) {
+            Ok(text) => (Some(text), None),
+            Err(e) => (None, Some(e.into_bytes())),
+        };
+        let file = FixtureFile {
+            method: response.method.clone(),
+            url: response.url.clone(),
+            status: response.status,
+            headers: response.headers.clone(),
+            text,
+            bytes,
+        };
+
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path_for(&response.method, &response.url);
+        std::fs::write(path, serde_json::to_vec_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+/// Records responses to, or replays them from, a [`FixtureStore`] - see [`crate::WebOptions::fetch_fixtures`]
+///
+/// Clones share the same store and mode, so the mode can be switched per runtime from the host
+///
+/// ```rust
+/// use rustyscript::{DirectoryFixtureStore, FetchFixtures, FetchMode};
+///
+/// let fixtures = FetchFixtures::new(DirectoryFixtureStore::new("tests/fixtures/http"));
+/// fixtures.set_mode(if std::env::var("RECORD").is_ok() {
+///     FetchMode::Record
+/// } else {
+///     FetchMode::Offline
+/// });
+/// ```
+#[derive(Clone)]
+pub struct FetchFixtures {
+    store: Arc<dyn FixtureStore>,
+    mode: Arc<Mutex<FetchMode>>,
+}
+
+impl std::fmt::Debug for FetchFixtures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FetchFixtures")
+            .field("mode", &self.mode())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FetchFixtures {
+    /// Use the given store, starting in [`FetchMode::Offline`]
+    #[must_use]
+    pub fn new(store: impl FixtureStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            mode: Arc::new(Mutex::new(FetchMode::Offline)),
+        }
+    }
+
+    /// Start in the given mode
+    #[must_use]
+    pub fn with_mode(self, mode: FetchMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// Switch modes - takes effect from the next request
+    pub fn set_mode(&self, mode: FetchMode) {
+        *self.mode.lock().unwrap_or_else(PoisonError::into_inner) = mode;
+    }
+
+    /// Returns the current mode
+    #[must_use]
+    pub fn mode(&self) -> FetchMode {
+        *self.mode.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the store fixtures are kept in
+    #[must_use]
+    pub fn store(&self) -> &dyn FixtureStore {
+        self.store.as_ref()
+    }
+}
+
+/// Returns how fetch should use the runtime's fixtures - always `live` without any
+#[op2]
+#[string]
+pub fn op_fetch_fixture_mode(state: &mut OpState) -> &'static str {
+    state
+        .try_borrow::<FetchFixtures>()
+        .map_or(FetchMode::Live, FetchFixtures::mode)
+        .as_str()
+}
+
+/// Returns the response recorded for a request, if any
+///
+/// Fails if the runtime's permissions would not allow fetching the URL
+#[op2]
+#[serde]
+pub fn op_fetch_fixture_load(
+    state: &mut OpState,
+    #[string] method: &str,
+    #[string] url: &str,
+) -> Result<Option<(u16, Vec<(String, String)>, ToJsBuffer)>, Error> {
+    let Some(fixtures) = state.try_borrow::<FetchFixtures>() else {
+        return Ok(None);
+    };
+    state
+        .borrow::<PermissionsContainer>()
+        .check_fetch(url, "fetch()")?;
+
+    let response = fixtures.store.load(method, url)?;
+    Ok(response.map(|response| (response.status, response.headers, response.body.into())))
+}
+
+/// Saves a response read in full by the sandbox
+///
+/// Refused unless the runtime is recording, so scripts cannot plant fixtures for offline runs
+#[op2]
+pub fn op_fetch_fixture_save(
+    state: &mut OpState,
+    #[string] method: String,
+    #[string] url: String,
+    status: u16,
+    #[serde] headers: Vec<(String, String)>,
+    #[buffer(copy)] body: Vec<u8>,
+) -> Result<(), Error> {
+    let Some(fixtures) = state.try_borrow::<FetchFixtures>() else {
+        return Ok(());
+    };
+    if fixtures.mode() != FetchMode::Record {
+        return Err(Error::Runtime(
+            "Fixtures can only be saved while recording".to_string(),
+        ));
+    }
+
+    fixtures.store.save(&RecordedResponse {
+        method,
+        url,
+        status,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_directory_store() {
+        let dir = std::env::temp_dir().join(format!("rustyscript_fixtures_{}", std::process::id()));
+        let store = DirectoryFixtureStore::new(&dir);
+
+        let response = RecordedResponse {
+            method: "GET".to_string(),
+            url: "https://example.com/users?id=1".to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: b"{\"id\":1}".to_vec(),
+        };
+        store.save(&response).unwrap();
+        assert_eq!(store.load("GET", &response.url).unwrap(), Some(response));
+        assert_eq!(
+            store
+                .load("POST", "https://example.com/users?id=1")
+                .unwrap(),
+            None
+        );
+
+        let path = store.path_for("GET", "https://example.com/users?id=1");
+        assert!(path.starts_with(&dir));
+
+        // Methods are sanitized like URLs, so neither can leave the directory
+        let path = store.path_for("../../etc/passwd", "https://example.com");
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_offline_fetch() {
+        let store = MemoryFixtureStore::new();
+        store
+            .save(&RecordedResponse {
+                method: "GET".to_string(),
+                url: "https://example.com/greeting".to_string(),
+                status: 200,
+                headers: vec![("content-type".to_string(), "text/plain".to_string())],
+                body: b"hello".to_vec(),
+            })
+            .unwrap();
+
+        let mut options = RuntimeOptions::default();
+        options.extension_options.web.fetch_fixtures = Some(FetchFixtures::new(store));
+        let mut runtime = Runtime::new(options).unwrap();
+
+        let text: String = runtime
+            .eval("fetch('https://example.com/greeting').then((r) => r.text())")
+            .unwrap();
+        assert_eq!(text, "hello");
+
+        let missing: String = runtime
+            .eval("fetch('https://example.com/other').catch((e) => e.message)")
+            .unwrap();
+        assert!(missing.contains("No recorded response"), "{missing}");
+
+        let planted: String = runtime
+            .eval(
+                "(() => { try {
+                    Deno.core.ops.op_fetch_fixture_save('GET', 'https://example.com/x', 200, [], new Uint8Array());
+                } catch (e) { return e.message; } })()",
+            )
+            .unwrap();
+        assert!(
+            planted.contains("only be saved while recording"),
+            "{planted}"
+        );
+    }
+
+    #[test]
+    fn test_offline_fetch_permissions() {
+        let store = MemoryFixtureStore::new();
+        store
+            .save(&RecordedResponse {
+                method: "GET".to_string(),
+                url: "https://internal.example.com/".to_string(),
+                status: 200,
+                headers: vec![],
+                body: b"secret".to_vec(),
+            })
+            .unwrap();
+
+        let permissions = crate::AllowlistWebPermissions::new();
+        permissions.allow_url("https://example.com");
+        let mut options = RuntimeOptions::default();
+        options.extension_options.web.permissions = Arc::new(permissions);
+        options.extension_options.web.fetch_fixtures = Some(FetchFixtures::new(store));
+        let mut runtime = Runtime::new(options).unwrap();
+
+        let denied: String = runtime
+            .eval("fetch('https://internal.example.com/').then(() => '', (e) => e.message)")
+            .unwrap();
+        assert!(!denied.is_empty());
+    }
+}
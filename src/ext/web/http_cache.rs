@@ -0,0 +1,762 @@
+//! An HTTP cache for script-initiated fetches, honoring `Cache-Control` and validators
+//!
+//! Only `GET` requests are cached. A fresh response is served without touching the network; a
+//! stale one with an `ETag` or `Last-Modified` is revalidated with a conditional request, and
+//! served from the cache on `304 Not Modified`.
+//!
+//! The cache behaves as a shared cache, since its store may be shared between runtimes:
+//! responses marked `private` or `no-store`, or that set cookies, are never stored, and neither
+//! are responses to requests with an `Authorization` header, unless marked `public`.
+//!
+//! Scripts can only read from the cache. Responses are stored by the host, which sends the
+//! request itself and reads the response in full - so a script cannot plant a response for
+//! another runtime sharing the store
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use deno_core::{op2, OpState, ToJsBuffer};
+
+use super::PermissionsContainer;
+use crate::Error;
+
+type Headers = Vec<(String, String)>;
+
+/// Response statuses that may be stored
+const CACHEABLE_STATUSES: [u16; 10] = [200, 203, 204, 300, 301, 404, 405, 410, 414, 501];
+
+/// A stored response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    /// The response status
+    pub status: u16,
+
+    /// The response headers, with lowercase names
+    pub headers: Vec<(String, String)>,
+
+    /// The response body
+    pub body: Vec<u8>,
+
+    /// The request headers named by the response's `Vary` header, as sent with the request
+    pub vary: Vec<(String, Option<String>)>,
+
+    /// When the response was received, or last revalidated
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    /// Returns how long the response stays fresh after it was received
+    #[must_use]
+    pub fn freshness_lifetime(&self) -> Duration {
+        let directives = CacheControl::from_headers(&self.headers);
+        if let Some(seconds) = directives.s_maxage.or(directives.max_age) {
+            return Duration::from_secs(seconds);
+        }
+
+        let expires = self.header("expires").and_then(parse_http_date);
+        let date = self.header("date").and_then(parse_http_date);
+        match (expires, date.unwrap_or(self.stored_at)) {
+            (Some(expires), date) => expires.duration_since(date).unwrap_or_default(),
+            (None, _) => Duration::ZERO,
+        }
+    }
+
+    /// Returns the response's age - its `Age` header when received, plus the time since
+    ///
+    /// An age too large to represent is `Duration::MAX`, so the response is stale
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        let initial = self
+            .header("age")
+            .and_then(|age| age.trim().parse().ok())
+            .map_or(Duration::ZERO, Duration::from_secs);
+        initial
+            .checked_add(self.stored_at.elapsed().unwrap_or_default())
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Returns true if the response may be served without revalidating it
+    #[must_use]
+    pub fn is_fresh(&self) -> bool {
+        let directives = CacheControl::from_headers(&self.headers);
+        !directives.no_cache && self.freshness_lifetime() > self.age()
+    }
+
+    /// Returns true if the response was stored for a request with the same varying headers
+    fn varies_from(&self, request_headers: &Headers) -> bool {
+        self.vary
+            .iter()
+            .any(|(name, value)| header(request_headers, name) != value.as_deref())
+    }
+
+    fn size(&self) -> usize {
+        self.body.len()
+    }
+}
+
+/// A backend for an [`HttpCache`], storing responses by URL
+///
+/// Stores are shared between runtimes, and may be called from several threads at once
+pub trait HttpCacheStore: Send + Sync {
+    /// Get the response stored for a URL
+    fn get(&self, url: &str) -> Option<CachedResponse>;
+
+    /// Store a response for a URL, replacing any existing response
+    fn put(&self, url: &str, response: CachedResponse);
+
+    /// Remove the response stored for a URL
+    fn remove(&self, url: &str);
+
+    /// Remove every stored response
+    fn clear(&self);
+}
+
+/// An in-memory [`HttpCacheStore`], evicting the oldest responses once it is full
+#[derive(Debug)]
+pub struct MemoryHttpCacheStore {
+    max_size: usize,
+    entries: Mutex<MemoryEntries>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryEntries {
+    responses: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+    size: usize,
+}
+
+impl MemoryHttpCacheStore {
+    /// Create a store holding up to `max_size` bytes of response bodies
+    #[must_use]
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Returns the total size of the stored response bodies
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.lock().size
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryEntries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl MemoryEntries {
+    fn remove(&mut self, url: &str) {
+        if let Some(response) = self.responses.remove(url) {
+            self.size -= response.size();
+            self.order.retain(|key| key != url);
+        }
+    }
+}
+
+impl HttpCacheStore for MemoryHttpCacheStore {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.lock().responses.get(url).cloned()
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        let mut entries = self.lock();
+        entries.remove(url);
+        if response.size() > self.max_size {
+            return;
+        }
+
+        entries.size += response.size();
+        entries.order.push_back(url.to_string());
+        entries.responses.insert(url.to_string(), response);
+
+        while entries.size > self.max_size {
+            let Some(oldest) = entries.order.front().cloned() else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&self, url: &str) {
+        self.lock().remove(url);
+    }
+
+    fn clear(&self) {
+        *self.lock() = MemoryEntries::default();
+    }
+}
+
+/// A response cache for `fetch` - see [`crate::WebOptions::http_cache`]
+///
+/// Clones share the same store and on/off switch
+///
+/// ```rust
+/// use rustyscript::{HttpCache, RuntimeOptions, WebOptions};
+///
+/// // Up to 16MiB of responses, none larger than 1MiB
+/// let cache = HttpCache::in_memory(16 * 1024 * 1024).with_max_entry_size(1024 * 1024);
+/// let options = RuntimeOptions {
+///     extension_options: rustyscript::ExtensionOptions {
+///         web: WebOptions {
+///             http_cache: Some(cache.clone()),
+///             ..Default::default()
+///         },
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// // Between invocations
+/// cache.clear();
+/// ```
+#[derive(Clone)]
+pub struct HttpCache {
+    store: Arc<dyn HttpCacheStore>,
+    enabled: Arc<AtomicBool>,
+    max_entry_size: usize,
+}
+
+impl std::fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("enabled", &self.is_enabled())
+            .field("max_entry_size", &self.max_entry_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpCache {
+    /// The default for [`HttpCache::with_max_entry_size`] - 1MiB
+    pub const DEFAULT_MAX_ENTRY_SIZE: usize = 1024 * 1024;
+
+    /// Create a cache backed by the given store
+    #[must_use]
+    pub fn new(store: impl HttpCacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            enabled: Arc::new(AtomicBool::new(true)),
+            max_entry_size: Self::DEFAULT_MAX_ENTRY_SIZE,
+        }
+    }
+
+    /// Create a cache holding up to `max_size` bytes of responses in memory
+    #[must_use]
+    pub fn in_memory(max_size: usize) -> Self {
+        Self::new(MemoryHttpCacheStore::new(max_size))
+    }
+
+    /// Only store responses with bodies up to this many bytes
+    #[must_use]
+    pub fn with_max_entry_size(mut self, max_entry_size: usize) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// The largest response body that will be stored
+    #[must_use]
+    pub fn max_entry_size(&self) -> usize {
+        self.max_entry_size
+    }
+
+    /// Stop, or resume, using the cache - stored responses are kept either way
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns true if fetches use the cache
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the response stored for a URL, fresh or not
+    #[must_use]
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.store.get(url)
+    }
+
+    /// Remove the response stored for a URL
+    pub fn remove(&self, url: &str) {
+        self.store.remove(url);
+    }
+
+    /// Remove every stored response
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    /// Returns the largest body that may be stored for a response, or `None` if it is not storable
+    fn admits(&self, request_headers: &Headers, status: u16, headers: &Headers) -> Option<usize> {
+        let directives = CacheControl::from_headers(headers);
+        let vary = header(headers, "vary").unwrap_or_default();
+        let authorized = header(request_headers, "authorization").is_some();
+        let content_length = header(headers, "content-length").and_then(|n| n.trim().parse().ok());
+
+        let storable = self.is_enabled()
+            && CACHEABLE_STATUSES.contains(&status)
+            && !directives.no_store
+            && !directives.private
+            && (!authorized || directives.public || directives.s_maxage.is_some())
+            && header(headers, "set-cookie").is_none()
+            && vary.trim() != "*"
+            && content_length.is_none_or(|n: usize| n <= self.max_entry_size);
+
+        let response = CachedResponse {
+            status,
+            headers: headers.clone(),
+            body: vec![],
+            vary: vec![],
+            stored_at: SystemTime::now(),
+        };
+        let validated =
+            header(headers, "etag").is_some() || header(headers, "last-modified").is_some();
+        let useful = validated || !response.freshness_lifetime().is_zero();
+
+        (storable && useful).then_some(self.max_entry_size)
+    }
+
+    fn store(
+        &self,
+        url: &str,
+        request_headers: &Headers,
+        status: u16,
+        headers: Headers,
+        body: Vec<u8>,
+    ) {
+        if body.len() > self.max_entry_size
+            || self.admits(request_headers, status, &headers).is_none()
+        {
+            return;
+        }
+
+        let vary = header(&headers, "vary")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = header(request_headers, &name).map(str::to_string);
+                (name, value)
+            })
+            .collect();
+
+        self.store.put(
+            url,
+            CachedResponse {
+                status,
+                headers,
+                body,
+                vary,
+                stored_at: SystemTime::now(),
+            },
+        );
+    }
+
+    /// Sends a `GET` request from the host, storing the response if it may be stored
+    ///
+    /// A stale stored response is revalidated, unless `revalidate` is false,
+    /// and returned on `304 Not Modified`
+    async fn fill(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        request_headers: Headers,
+        revalidate: bool,
+    ) -> Result<CachedResponse, Error> {
+        let cached = revalidate
+            .then(|| self.lookup(url, &request_headers))
+            .flatten();
+
+        let mut request = client.get(url);
+        for (name, value) in &request_headers {
+            request = request.header(name, value);
+        }
+        if let Some(cached) = &cached {
+            if let Some(etag) = cached.header("etag") {
+                request = request.header("if-none-match", etag);
+            }
+            if let Some(last_modified) = cached.header("last-modified") {
+                request = request.header("if-modified-since", last_modified);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Runtime(e.to_string()))?;
+        let status = response.status().as_u16();
+        let headers: Headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.as_str().to_string(), value)
+            })
+            .collect();
+
+        if status == 304 && cached.is_some() {
+            if let Some(response) = self.revalidated(url, headers) {
+                return Ok(response);
+            }
+        }
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Runtime(e.to_string()))?
+            .to_vec();
+        let response = CachedResponse {
+            status,
+            headers: headers.clone(),
+            body: body.clone(),
+            vary: vec![],
+            stored_at: SystemTime::now(),
+        };
+        self.store(url, &request_headers, status, headers, body);
+        Ok(response)
+    }
+
+    fn lookup(&self, url: &str, request_headers: &Headers) -> Option<CachedResponse> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.store
+            .get(url)
+            .filter(|response| !response.varies_from(request_headers))
+    }
+
+    /// Refreshes a stored response after a `304 Not Modified`, merging in the new headers
+    fn revalidated(&self, url: &str, headers: Headers) -> Option<CachedResponse> {
+        let mut response = self.store.get(url)?;
+        for (name, value) in headers {
+            if name == "content-length" {
+                continue;
+            }
+            response.headers.retain(|(existing, _)| *existing != name);
+            response.headers.push((name, value));
+        }
+        response.stored_at = SystemTime::now();
+
+        self.store.put(url, response.clone());
+        Some(response)
+    }
+}
+
+/// The `Cache-Control` directives the cache understands
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn from_headers(headers: &Headers) -> Self {
+        let mut directives = Self::default();
+        let values = headers
+            .iter()
+            .filter(|(name, _)| name == "cache-control")
+            .flat_map(|(_, value)| value.split(','));
+
+        for directive in values {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = value.and_then(|v| v.parse().ok());
+
+            match name.trim().to_lowercase().as_str() {
+                "no-store" => directives.no_store = true,
+                "no-cache" => directives.no_cache = true,
+                "private" => directives.private = true,
+                "public" => directives.public = true,
+                "max-age" => directives.max_age = seconds,
+                "s-maxage" => directives.s_maxage = seconds,
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+/// Returns the first value of a header, by lowercase name
+fn header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(existing, _)| existing == name)
+        .map(|(_, value)| value.as_str())
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an HTTP date, in the `Sun, 06 Nov 1994 08:49:37 GMT` form servers are required to send
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let parts: Vec<_> = date.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+
+    let day: u32 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))?;
+    let year: i64 = year.parse().ok()?;
+
+    let mut clock = time.split(':').map(|n| n.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+
+    let days = u64::try_from(days_from_civil(year, u32::try_from(month).ok()? + 1, day)?).ok()?;
+    let since_epoch = days
+        .checked_mul(86_400)?
+        .checked_add(hours.checked_mul(3_600)?)?
+        .checked_add(minutes.checked_mul(60)?)?
+        .checked_add(seconds)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(since_epoch))
+}
+
+/// Days from 1970-01-01 to a date in the proleptic gregorian calendar, or `None` if out of range
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    let (month, day) = (i64::from(month), i64::from(day));
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era.checked_mul(146_097)?
+        .checked_add(day_of_era)?
+        .checked_sub(719_468)
+}
+
+/// A stored response, as sent to the sandbox
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheLookup {
+    status: u16,
+    headers: Headers,
+    body: ToJsBuffer,
+    fresh: bool,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl From<CachedResponse> for CacheLookup {
+    fn from(response: CachedResponse) -> Self {
+        Self {
+            fresh: response.is_fresh(),
+            etag: response.header("etag").map(str::to_string),
+            last_modified: response.header("last-modified").map(str::to_string),
+            status: response.status,
+            headers: response.headers,
+            body: response.body.into(),
+        }
+    }
+}
+
+/// The client the host sends cache fills with
+///
+/// Only sends the runtime's user agent - requests needing a `Deno.HttpClient` bypass the cache
+#[derive(Clone)]
+pub(crate) struct CacheFillClient(pub reqwest::Client);
+
+impl CacheFillClient {
+    pub fn new(user_agent: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .build()
+            .unwrap_or_default();
+        Self(client)
+    }
+}
+
+/// Returns true if the runtime has a cache, and it is enabled
+#[op2(fast)]
+pub fn op_fetch_cache_enabled(state: &mut OpState) -> bool {
+    state
+        .try_borrow::<HttpCache>()
+        .is_some_and(HttpCache::is_enabled)
+}
+
+/// Returns the stored response for a `GET` request, if the runtime has a cache holding one
+///
+/// Fails if the runtime's permissions would not allow fetching the URL
+#[op2]
+#[serde]
+pub fn op_fetch_cache_lookup(
+    state: &mut OpState,
+    #[string] url: &str,
+    #[serde] request_headers: Headers,
+) -> Result<Option<CacheLookup>, Error> {
+    let Some(cache) = state.try_borrow::<HttpCache>() else {
+        return Ok(None);
+    };
+    state
+        .borrow::<PermissionsContainer>()
+        .check_fetch(url, "fetch()")?;
+    Ok(cache.lookup(url, &request_headers).map(Into::into))
+}
+
+/// Sends a `GET` request from the host, storing the response if it may be stored, and returns it
+#[op2(async)]
+#[serde]
+pub async fn op_fetch_cache_fill(
+    state: Rc<RefCell<OpState>>,
+    #[string] url: String,
+    #[serde] request_headers: Headers,
+    revalidate: bool,
+) -> Result<CacheLookup, Error> {
+    let (cache, client) = {
+        let state = state.borrow();
+        state
+            .borrow::<PermissionsContainer>()
+            .check_fetch(&url, "fetch()")?;
+        let cache = state
+            .try_borrow::<HttpCache>()
+            .cloned()
+            .ok_or_else(|| Error::Runtime("The runtime has no HTTP cache".to_string()))?;
+        let client = state.borrow::<CacheFillClient>().clone();
+        (cache, client)
+    };
+
+    let response = cache
+        .fill(&client.0, &url, request_headers, revalidate)
+        .await?;
+    Ok(response.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_http_date() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(
+            date.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::from_secs(784_111_777)
+        );
+        assert!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT").is_none());
+
+        // Out of range dates and ages are rejected, or stale, rather than overflowing
+        assert!(parse_http_date("Sun, 06 Nov 9223372036854775807 08:49:37 GMT").is_none());
+        assert!(parse_http_date("Sun, 06 Nov 1994 99999999999999999:00:00 GMT").is_none());
+        let old = CachedResponse {
+            status: 200,
+            headers: headers(&[
+                ("cache-control", "max-age=60"),
+                ("age", "18446744073709551615"),
+            ]),
+            body: vec![],
+            vary: vec![],
+            stored_at: SystemTime::now() - Duration::from_secs(1),
+        };
+        assert_eq!(old.age(), Duration::MAX);
+        assert!(!old.is_fresh());
+    }
+
+    #[test]
+    fn test_http_cache() {
+        let cache = HttpCache::in_memory(10).with_max_entry_size(8);
+        let request = headers(&[("accept", "text/plain")]);
+        let url = "https://example.com/a";
+
+        let fresh = headers(&[("cache-control", "max-age=60"), ("vary", "Accept")]);
+        assert_eq!(cache.admits(&request, 200, &fresh), Some(8));
+        cache.store(url, &request, 200, fresh, b"hello".to_vec());
+
+        let hit = cache.lookup(url, &request).unwrap();
+        assert!(hit.is_fresh());
+        assert!(cache
+            .lookup(url, &headers(&[("accept", "text/html")]))
+            .is_none());
+
+        // Not storable
+        for response in [
+            headers(&[("cache-control", "no-store, max-age=60")]),
+            headers(&[("cache-control", "private, max-age=60")]),
+            headers(&[("cache-control", "max-age=60"), ("set-cookie", "a=1")]),
+            headers(&[]),
+        ] {
+            assert_eq!(cache.admits(&request, 200, &response), None);
+        }
+        let authorized = headers(&[("authorization", "Bearer x")]);
+        let public = headers(&[("cache-control", "public, max-age=60")]);
+        assert_eq!(
+            cache.admits(
+                &authorized,
+                200,
+                &headers(&[("cache-control", "max-age=60")])
+            ),
+            None
+        );
+        assert_eq!(cache.admits(&authorized, 200, &public), Some(8));
+
+        // Stale responses with validators are kept for revalidation
+        let stale = headers(&[("cache-control", "no-cache"), ("etag", "\"v1\"")]);
+        cache.store(
+            "https://example.com/b",
+            &request,
+            200,
+            stale,
+            b"abcdef".to_vec(),
+        );
+        let stored = cache.lookup("https://example.com/b", &request).unwrap();
+        assert!(!stored.is_fresh());
+
+        // ... evicting the oldest response once the store is full
+        assert!(cache.get(url).is_none());
+
+        cache.set_enabled(false);
+        assert!(cache.lookup("https://example.com/b", &request).is_none());
+    }
+
+    #[test]
+    fn test_http_cache_permissions() {
+        let cache = HttpCache::in_memory(1024);
+        cache.store(
+            "https://internal.example.com/",
+            &Headers::new(),
+            200,
+            headers(&[("cache-control", "max-age=60")]),
+            b"secret".to_vec(),
+        );
+
+        let permissions = crate::AllowlistWebPermissions::new();
+        permissions.allow_url("https://example.com");
+        let mut options = crate::RuntimeOptions::default();
+        options.extension_options.web.permissions = Arc::new(permissions);
+        options.extension_options.web.http_cache = Some(cache);
+        let mut runtime = crate::Runtime::new(options).unwrap();
+
+        let denied: String = runtime
+            .eval("fetch('https://internal.example.com/').then(() => '', (e) => e.message)")
+            .unwrap();
+        assert!(!denied.is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Conversions between the sandbox's fetch types and `http` crate types
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use deno_core::{
+    futures::{Stream, StreamExt},
+    op2, v8, AsyncRefCell, JsBuffer, OpState, RcRef, Resource, ResourceId, ToJsBuffer,
+};
+use tokio::sync::mpsc;
+
+use crate::Error;
+
+/// Number of chunks buffered between the script and the host before the script waits
+const BODY_BUFFER_CHUNKS: usize = 16;
+
+/// A streaming body read from a sandbox `Response`
+///
+/// Yields chunks as the script produces them. Chunks only arrive while the runtime's event loop
+/// is running - drive it (with `Runtime::await_event_loop`, or `Runtime::with_event_loop_future`)
+/// while consuming the body from the same thread
+///
+/// Dropping the body cancels the underlying stream in the sandbox
+pub struct HttpBody {
+    rx: mpsc::Receiver<Result<Vec<u8>, Error>>,
+}
+
+impl HttpBody {
+    fn channel() -> (BodySender, Self) {
+        let (tx, rx) = mpsc::channel(BODY_BUFFER_CHUNKS);
+        (BodySender(tx), Self { rx })
+    }
+}
+
+impl Stream for HttpBody {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl std::fmt::Debug for HttpBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpBody").finish_non_exhaustive()
+    }
+}
+
+/// The script's end of an [`HttpBody`]
+struct BodySender(mpsc::Sender<Result<Vec<u8>, Error>>);
+impl Resource for BodySender {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "rustyscriptHttpBody".into()
+    }
+}
+
+type HostStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>, Error>>>>;
+
+/// A host-provided request body, read by a sandbox `Request`
+struct HostBody(AsyncRefCell<HostStream>);
+impl Resource for HostBody {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "rustyscriptHostBody".into()
+    }
+}
+
+/// JS functions used to convert between sandbox and host types
+pub(crate) struct HttpInteropHelpers {
+    pub response_parts: v8::Global<v8::Function>,
+    pub create_request: v8::Global<v8::Function>,
+}
+
+/// Returns the JS helpers for converting sandbox types
+pub(crate) fn helpers(state: &OpState) -> Result<&HttpInteropHelpers, Error> {
+    state
+        .try_borrow::<HttpInteropHelpers>()
+        .ok_or_else(|| Error::Runtime("HTTP conversions are not initialized".to_string()))
+}
+
+/// Registers the JS helpers for converting sandbox types
+#[op2]
+pub fn op_http_bind_helpers(
+    state: &mut OpState,
+    #[global] response_parts: v8::Global<v8::Function>,
+    #[global] create_request: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(
+        state,
+        "The HTTP conversions",
+        HttpInteropHelpers {
+            response_parts,
+            create_request,
+        },
+    )
+}
+
+/// Reads the next chunk of a host-provided request body, or `None` at the end of the body
+#[op2(async)]
+#[serde]
+pub async fn op_http_host_body_read(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<Option<ToJsBuffer>, Error> {
+    let body = state
+        .borrow()
+        .resource_table
+        .get::<HostBody>(rid)
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+
+    let mut stream = RcRef::map(&body, |body| &body.0).borrow_mut().await;
+    let chunk = stream.next().await.transpose()?;
+    Ok(chunk.map(ToJsBuffer::from))
+}
+
+/// Releases a host-provided request body, once it has been read or cancelled
+#[op2(fast)]
+pub fn op_http_host_body_cancel(state: &mut OpState, #[smi] rid: ResourceId) {
+    state.resource_table.take::<HostBody>(rid).ok();
+}
+
+/// Sends a chunk of a response body to the host
+///
+/// Returns false if the host has dropped the body, and the stream should be cancelled
+#[op2(async)]
+pub async fn op_http_body_send(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[buffer] chunk: JsBuffer,
+) -> Result<bool, Error> {
+    let sender = state
+        .borrow()
+        .resource_table
+        .get::<BodySender>(rid)
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+    Ok(sender.0.send(Ok(chunk.to_vec())).await.is_ok())
+}
+
+/// Ends a response body, optionally with an error
+#[op2(async)]
+pub async fn op_http_body_close(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[string] error: Option<String>,
+) -> Result<(), Error> {
+    let sender = state
+        .borrow_mut()
+        .resource_table
+        .take::<BodySender>(rid)
+        .map_err(|e| Error::Runtime(e.to_string()))?;
+
+    if let Some(error) = error {
+        sender.0.send(Err(Error::Runtime(error))).await.ok();
+    }
+
+    Ok(())
+}
+
+/// Status and headers of a sandbox `Response`, as returned by the JS helper
+#[derive(serde::Deserialize)]
+pub(crate) struct ResponseParts {
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl ResponseParts {
+    pub fn into_response(self, body: HttpBody) -> Result<http::Response<HttpBody>, Error> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in self.headers {
+            builder = builder.header(name, value);
+        }
+
+        builder
+            .body(body)
+            .map_err(|e| Error::Runtime(format!("Invalid response: {e}")))
+    }
+}
+
+/// Prepares the conversion of a sandbox `Response`
+///
+/// Returns the JS helper to call with the response and the rid of the body stream,
+/// along with the host's end of that stream
+pub(crate) fn prepare_response(
+    state: &mut OpState,
+) -> Result<(v8::Global<v8::Function>, ResourceId, HttpBody), Error> {
+    let helper = helpers(state)?.response_parts.clone();
+
+    let (sender, body) = HttpBody::channel();
+    let rid = state.resource_table.add(sender);
+    Ok((helper, rid, body))
+}
+
+/// Method, url and headers of a host request, as passed to the JS helper
+#[derive(serde::Serialize)]
+pub(crate) struct RequestParts {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestParts {
+    /// Splits a host request into its parts and body
+    ///
+    /// Relative URIs, as seen by servers, are resolved against the `Host` header
+    pub fn new<B>(request: http::Request<B>) -> Result<(Self, B), Error> {
+        let (parts, body) = request.into_parts();
+
+        let mut headers = Vec::with_capacity(parts.headers.len());
+        for (name, value) in &parts.headers {
+            let value = value
+                .to_str()
+                .map_err(|e| Error::Runtime(format!("Invalid header `{name}`: {e}")))?;
+            headers.push((name.to_string(), value.to_string()));
+        }
+
+        let url = if parts.uri.scheme().is_some() {
+            parts.uri.to_string()
+        } else {
+            let host = parts
+                .headers
+                .get(http::header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .unwrap_or("localhost");
+            format!("http://{host}{}", parts.uri)
+        };
+
+        Ok((
+            Self {
+                method: parts.method.to_string(),
+                url,
+                headers,
+            },
+            body,
+        ))
+    }
+}
+
+/// Prepares the creation of a sandbox `Request` from a host request
+///
+/// Returns the JS helper, and the rid of the body - `None` for methods which cannot have one
+pub(crate) fn prepare_request<B, D, E>(
+    state: &mut OpState,
+    parts: &RequestParts,
+    body: B,
+) -> Result<(v8::Global<v8::Function>, Option<ResourceId>), Error>
+where
+    B: Stream<Item = Result<D, E>> + 'static,
+    D: Into<Vec<u8>>,
+    E: std::fmt::Display,
+{
+    let helper = helpers(state)?.create_request.clone();
+    if matches!(parts.method.as_str(), "GET" | "HEAD") {
+        return Ok((helper, None));
+    }
+
+    let stream = body.map(|chunk| {
+        chunk
+            .map(Into::into)
+            .map_err(|e| Error::Runtime(e.to_string()))
+    });
+    let rid = state
+        .resource_table
+        .add(HostBody(AsyncRefCell::new(Box::pin(stream))));
+    Ok((helper, Some(rid)))
+}
+
+#[cfg(test)]
+mod test {
+    use deno_core::futures::StreamExt;
+
+    use crate::{js_value::Value, json_args, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_into_http_response() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let response: Value = runtime
+            .eval(
+                "
+                (() => {
+                    const body = new ReadableStream({
+                        start(controller) {
+                            controller.enqueue(new TextEncoder().encode('data: 1\\n\\n'));
+                            controller.enqueue(new TextEncoder().encode('data: 2\\n\\n'));
+                            controller.close();
+                        },
+                    });
+                    return new Response(body, {
+                        status: 201,
+                        headers: { 'content-type': 'text/event-stream' },
+                    });
+                })()
+            ",
+            )
+            .unwrap();
+
+        let response = runtime.into_http_response(&response).unwrap();
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        // The script fills the body as the event loop runs
+        runtime
+            .block_on_event_loop(Default::default(), None)
+            .unwrap();
+        let chunks = runtime
+            .tokio_runtime()
+            .block_on(response.into_body().collect::<Vec<_>>());
+        let body: Vec<u8> = chunks.into_iter().flat_map(Result::unwrap).collect();
+        assert_eq!(body, b"data: 1\n\ndata: 2\n\n");
+
+        let value: Value = runtime.eval("({})").unwrap();
+        runtime
+            .into_http_response(&value)
+            .expect_err("Converted a non-Response value");
+    }
+
+    #[test]
+    fn test_create_request() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+            vec![Ok(b"hello ".to_vec()), Ok(b"world".to_vec())];
+        let request = http::Request::post("/echo")
+            .header("host", "example.com")
+            .header("x-test", "1")
+            .body(deno_core::futures::stream::iter(chunks))
+            .unwrap();
+        let request = runtime.create_request(request).unwrap();
+
+        let module = crate::Module::new(
+            "test.js",
+            "
+            export async function handle(request) {
+                return `${request.method} ${request.url} ${request.headers.get('x-test')} ${await request.text()}`;
+            }
+        ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+        let value: String = runtime
+            .call_function(Some(&module), "handle", json_args!(request))
+            .unwrap();
+        assert_eq!(value, "POST http://example.com/echo 1 hello world");
+    }
+}
@@ -1,5 +1,5 @@
 import * as headers from "ext:deno_fetch/20_headers.js";
-import * as formData from "ext:deno_fetch/21_formdata.js";
+import * as formData from "ext:deno_fetch/21_formdata.js";
 import * as httpClient from "ext:deno_fetch/22_http_client.js";
 import * as request from "ext:deno_fetch/23_request.js";
 import * as response from "ext:deno_fetch/23_response.js";
@@ -10,8 +10,374 @@ Deno.core.setWasmStreamingCallback(fetch.handleWasmStreaming);
 
 import {applyToGlobal, writeable, nonEnumerable} from 'ext:rustyscript/rustyscript.js';
 
+// Responses that must be constructed without a body
+const NULL_BODY_STATUSES = [101, 103, 204, 205, 304];
+
+// Requests are recorded to, or replayed from, the runtime's `FetchFixtures`, if it has any
+import { op_fetch_fixture_mode, op_fetch_fixture_load, op_fetch_fixture_save } from "ext:core/ops";
+async function recordedFetch(input, init = undefined) {
+    const mode = op_fetch_fixture_mode();
+    if (mode === 'live') {
+        return sendFetch(input, init);
+    }
+
+    const isRequest = input instanceof request.Request;
+    const method = String(init?.method ?? (isRequest ? input.method : 'GET')).toUpperCase();
+    const url = isRequest ? input.url : String(input);
+    if (mode === 'offline') {
+        const recorded = op_fetch_fixture_load(method, url);
+        if (recorded === null) {
+            throw new TypeError(`No recorded response for ${method} ${url}`);
+        }
+
+        const [status, headers, body] = recorded;
+        return new response.Response(NULL_BODY_STATUSES.includes(status) ? null : body, { status, headers });
+    }
+
+    const live = await sendFetch(input, init);
+    const body = new Uint8Array(await live.arrayBuffer());
+    op_fetch_fixture_save(method, url, live.status, [...live.headers], body);
+    return new response.Response(NULL_BODY_STATUSES.includes(live.status) ? null : body, {
+        status: live.status,
+        statusText: live.statusText,
+        headers: live.headers,
+    });
+}
+
+// Requests without their own client wait their turn under `FetchPoolOptions::max_concurrent_requests`
+// The client itself is configured host-side, by `WebOptions::fetch_http_version` and `fetch_pool`
+import { op_fetch_max_concurrent_requests } from "ext:core/ops";
+const maxConcurrent = op_fetch_max_concurrent_requests();
+const waiting = [];
+let inFlight = 0;
+async function pooledFetch(input, init = undefined) {
+    if (maxConcurrent === null || init?.client !== undefined) {
+        return recordedFetch(input, init);
+    }
+
+    if (inFlight < maxConcurrent) {
+        inFlight++;
+    } else {
+        await new Promise((resolve) => waiting.push(resolve));
+    }
+
+    try {
+        return await recordedFetch(input, init);
+    } finally {
+        // Hand the slot straight to the next waiting request, if any
+        const next = waiting.shift();
+        if (next) next(); else inFlight--;
+    }
+}
+
+// Cookies are sent from, and stored in, the runtime's `CookieJar`, if it has one
+import { op_fetch_cookie_header, op_fetch_store_cookies } from "ext:core/ops";
+const parseSetCookie = (header) => {
+    const [pair, ...attributes] = header.split(';');
+    const eq = pair.indexOf('=');
+    if (eq < 1) return null;
+
+    const cookie = {
+        name: pair.slice(0, eq).trim(), value: pair.slice(eq + 1).trim(),
+        domain: null, path: null, expires: null, maxAge: null, secure: false, httpOnly: false,
+    };
+    for (const attribute of attributes) {
+        const [key, ...rest] = attribute.split('=');
+        const value = rest.join('=').trim();
+        switch (key.trim().toLowerCase()) {
+            case 'domain': cookie.domain = value; break;
+            case 'path': cookie.path = value; break;
+            case 'expires': cookie.expires = Date.parse(value) || null; break;
+            case 'max-age': cookie.maxAge = Number.isInteger(Number(value)) ? Number(value) : null; break;
+            case 'secure': cookie.secure = true; break;
+            case 'httponly': cookie.httpOnly = true; break;
+        }
+    }
+    return cookie;
+};
+
+async function cookieFetch(input, init = undefined) {
+    const isRequest = input instanceof request.Request;
+    const credentials = init?.credentials ?? (isRequest ? input.credentials : 'same-origin');
+    if (credentials === 'omit') {
+        return pooledFetch(input, init);
+    }
+
+    const url = isRequest ? input.url : String(input);
+    const cookies = op_fetch_cookie_header(url);
+    if (cookies !== null) {
+        const requestHeaders = new headers.Headers(init?.headers ?? (isRequest ? input.headers : undefined));
+        if (!requestHeaders.has('cookie')) {
+            requestHeaders.set('cookie', cookies);
+            init = { ...init, headers: requestHeaders };
+        }
+    }
+
+    const response = await pooledFetch(input, init);
+    const set = response.headers.getSetCookie().map(parseSetCookie).filter((c) => c !== null);
+    if (set.length > 0) op_fetch_store_cookies(response.url || url, set);
+    return response;
+}
+
+// Hosts that keep failing are short-circuited by the runtime's `CircuitBreaker`, if it has one
+import { op_circuit_check, op_circuit_record } from "ext:core/ops";
+const hostOf = (input) => {
+    try {
+        return new URL(input instanceof request.Request ? input.url : String(input)).hostname || null;
+    } catch {
+        return null;
+    }
+};
+
+async function guardedFetch(input, init = undefined) {
+    const host = hostOf(input);
+    if (host === null) {
+        return cookieFetch(input, init);
+    }
+
+    op_circuit_check(host);
+    let response;
+    try {
+        response = await cookieFetch(input, init);
+    } catch (e) {
+        // Connection failures are TypeErrors - aborts and permission errors say nothing about the host
+        if (e instanceof TypeError) op_circuit_record(host, false);
+        throw e;
+    }
+
+    op_circuit_record(host, response.status < 500);
+    return response;
+}
+
+// Replayable requests are retried under the runtime's `RetryPolicy`, if it has one
+import { op_fetch_retry_policy, op_fetch_retry_delay } from "ext:core/ops";
+const REPLAYABLE_METHODS = ['GET', 'HEAD', 'OPTIONS', 'PUT', 'DELETE'];
+const isReplayable = (input, init) => {
+    const isRequest = input instanceof request.Request;
+    const method = String(init?.method ?? (isRequest ? input.method : 'GET')).toUpperCase();
+    const streamed = init?.body instanceof ReadableStream || (isRequest && input.body !== null);
+    return REPLAYABLE_METHODS.includes(method) && !streamed;
+};
+const backoff = (attempt) => new Promise((resolve) => setTimeout(resolve, op_fetch_retry_delay(attempt)));
+
+async function retryingFetch(input, init = undefined) {
+    const policy = op_fetch_retry_policy();
+    if (policy === null || !isReplayable(input, init)) {
+        return guardedFetch(input, init);
+    }
+
+    for (let attempt = 1; ; attempt++) {
+        const canRetry = attempt < policy.maxAttempts && !init?.signal?.aborted;
+        let response;
+        try {
+            response = await guardedFetch(input, init);
+        } catch (e) {
+            // Connection failures are TypeErrors - aborts, permission errors and open circuits are not retried
+            if (!canRetry || !(e instanceof TypeError)) throw e;
+            await backoff(attempt);
+            continue;
+        }
+
+        if (!canRetry || !policy.statuses.includes(response.status)) {
+            return response;
+        }
+        await response.body?.cancel();
+        await backoff(attempt);
+    }
+}
+
+// GET requests are answered by the runtime's `HttpCache` while fresh, and revalidated with it, if it has one
+// Responses are fetched and stored by the host, so scripts can read from the cache but never write to it
+import { op_fetch_cache_enabled, op_fetch_cache_lookup, op_fetch_cache_fill } from "ext:core/ops";
+const FILL_CACHE = Symbol('fillCache');
+const fromCache = ({ status, headers, body }) =>
+    new response.Response(NULL_BODY_STATUSES.includes(status) ? null : body, { status, headers });
+
+// Sends a request marked by `cachingFetch` from the host, once it reaches the bottom of the fetch chain
+async function fillCache(input, init) {
+    const req = new request.Request(input, init);
+    req.signal.throwIfAborted();
+
+    let answer;
+    try {
+        answer = await op_fetch_cache_fill(req.url, [...req.headers], init[FILL_CACHE].revalidate);
+    } catch (e) {
+        throw new TypeError(`Request to ${req.url} failed: ${e.message}`, { cause: e });
+    }
+    return fromCache(answer);
+}
+
+// Trailers sent after a live response's body, such as gRPC's `grpc-status`, resolve `response.trailer`
+// once the body has been read - other responses resolve it with empty headers
+import { op_fetch_trailers, op_fetch_bind_trailers } from "ext:core/ops";
+import { getReadableStreamResourceBacking } from "ext:deno_web/06_streams.js";
+const trailerRids = new WeakMap();
+const trailers = new WeakMap();
+Object.defineProperty(response.Response.prototype, 'trailer', {
+    get() {
+        if (!trailers.has(this)) {
+            const rid = trailerRids.get(this);
+            trailers.set(this, rid === undefined
+                ? Promise.resolve(new headers.Headers())
+                : op_fetch_trailers(rid).then((list) => new headers.Headers(list ?? [])));
+        }
+        return trailers.get(this);
+    },
+    configurable: true,
+});
+
+function withTrailer(resp) {
+    const stream = response.toInnerResponse(resp).body?.streamOrStatic;
+    const rid = stream ? getReadableStreamResourceBacking(stream)?.rid : undefined;
+    if (rid !== undefined) trailerRids.set(resp, rid);
+    return resp;
+}
+
+op_fetch_bind_trailers(async (value) => {
+    if (!(value instanceof response.Response)) throw new TypeError('Value is not a Response');
+    return [...await value.trailer];
+});
+
+const sendFetch = (input, init) => init?.[FILL_CACHE] === undefined ? fetch.fetch(input, init).then(withTrailer) : fillCache(input, init);
+
+async function cachingFetch(input, init = undefined) {
+    const isRequest = input instanceof request.Request;
+    const method = String(init?.method ?? (isRequest ? input.method : 'GET')).toUpperCase();
+    const mode = init?.cache ?? 'default';
+    const redirect = init?.redirect ?? (isRequest ? input.redirect : 'follow');
+    if (method !== 'GET' || mode === 'no-store' || redirect !== 'follow' || init?.client !== undefined || !op_fetch_cache_enabled()) {
+        return retryingFetch(input, init);
+    }
+
+    const url = isRequest ? input.url : String(input);
+    const requestHeaders = new headers.Headers(init?.headers ?? (isRequest ? input.headers : undefined));
+    const cached = mode === 'reload' ? null : op_fetch_cache_lookup(url, [...requestHeaders]);
+    if (cached !== null && (mode === 'force-cache' || (cached.fresh && mode !== 'no-cache'))) {
+        return fromCache(cached);
+    }
+
+    return retryingFetch(input, { ...init, [FILL_CACHE]: { revalidate: mode !== 'reload' } });
+}
+
+// Requests to custom schemes, such as `asset://`, are answered by the host - see `WebOptions::register_scheme`
+import { op_fetch_scheme_handled, op_fetch_scheme_request } from "ext:core/ops";
+async function schemeFetch(input, init = undefined) {
+    const url = input instanceof request.Request ? input.url : String(input);
+    const colon = url.indexOf(':');
+    if (colon < 1 || !op_fetch_scheme_handled(url.slice(0, colon).toLowerCase())) {
+        return cachingFetch(input, init);
+    }
+
+    const req = new request.Request(input, init);
+    req.signal.throwIfAborted();
+    const body = new Uint8Array(await req.arrayBuffer());
+
+    let answer;
+    try {
+        answer = op_fetch_scheme_request(req.method, req.url, [...req.headers], body);
+    } catch (e) {
+        throw new TypeError(`Request to ${req.url} failed: ${e.message}`, { cause: e });
+    }
+
+    const [status, responseHeaders, responseBody] = answer;
+    return new response.Response(NULL_BODY_STATUSES.includes(status) ? null : responseBody, {
+        status,
+        headers: responseHeaders,
+    });
+}
+Object.defineProperty(schemeFetch, 'name', { value: 'fetch' });
+
+// Host-side conversion of sandbox `Response` objects, used by `Runtime::into_http_response`
+import {
+    op_http_bind_helpers, op_http_body_send, op_http_body_close,
+    op_http_host_body_read, op_http_host_body_cancel,
+} from "ext:core/ops";
+async function pumpBody(body, rid) {
+    if (body === null) {
+        return op_http_body_close(rid, null);
+    }
+
+    try {
+        for await (const chunk of body) {
+            // The host dropped the body - cancel the stream
+            if (!await op_http_body_send(rid, chunk)) break;
+        }
+        await op_http_body_close(rid, null);
+    } catch (e) {
+        await op_http_body_close(rid, String(e?.message ?? e));
+    }
+}
+const responseParts = (value, rid) => {
+    if (!(value instanceof response.Response)) {
+        op_http_body_close(rid, null);
+        throw new TypeError('Value is not a Response');
+    }
+    if (value.bodyUsed) {
+        op_http_body_close(rid, null);
+        throw new TypeError('Response body has already been used');
+    }
+
+    pumpBody(value.body, rid);
+    return { status: value.status, headers: [...value.headers] };
+};
+
+// Host-side creation of sandbox `Request` objects, used by `Runtime::create_request`
+const createRequest = ({ method, url, headers }, rid) => {
+    let body = null;
+    if (rid !== null) {
+        body = new ReadableStream({
+            type: 'bytes',
+            async pull(controller) {
+                const chunk = await op_http_host_body_read(rid);
+                if (chunk === null) {
+                    op_http_host_body_cancel(rid);
+                    controller.close();
+                } else {
+                    controller.enqueue(chunk);
+                }
+            },
+            cancel() {
+                op_http_host_body_cancel(rid);
+            },
+        });
+    }
+
+    return new request.Request(url, { method, headers, body, duplex: 'half' });
+};
+
+op_http_bind_helpers(responseParts, createRequest);
+
+// Host-side exchange of `Blob`, `File` and `FormData` contents
+import { op_files_bind_helpers } from "ext:core/ops";
+import * as file from "ext:deno_web/09_file.js";
+const createFile = (name, type, data) => name === null
+    ? new file.Blob([data], { type })
+    : new file.File([data], name, { type });
+
+const readFile = async (value) => {
+    if (!(value instanceof file.Blob)) throw new TypeError('Value is not a Blob');
+    return {
+        name: value instanceof file.File ? value.name : null,
+        type: value.type,
+        data: new Uint8Array(await value.arrayBuffer()),
+    };
+};
+
+const formDataEntries = async (value) => {
+    if (!(value instanceof formData.FormData)) throw new TypeError('Value is not a FormData');
+    const entries = [];
+    for (const [name, entry] of value) {
+        entries.push(typeof entry === 'string'
+            ? { name, value: entry }
+            : { name, file: await readFile(entry) });
+    }
+    return entries;
+};
+
+op_files_bind_helpers(createFile, readFile, formDataEntries);
+
 applyToGlobal({
-    fetch: writeable(fetch.fetch),
+    fetch: writeable(schemeFetch),
     Request: nonEnumerable(request.Request),
     Response: nonEnumerable(response.Response),
     Headers: nonEnumerable(headers.Headers),
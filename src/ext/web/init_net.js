@@ -2,7 +2,27 @@
 import * as net from "ext:deno_net/01_net.js";
 import * as tls from "ext:deno_net/02_tls.js";
 
-globalThis.Deno.connect = net.connect;
+// Hosts that keep failing are short-circuited by the runtime's `CircuitBreaker`, if it has one
+import { op_circuit_check, op_circuit_record } from "ext:core/ops";
+const NOT_COUNTED = ['PermissionDenied', 'NotCapable'];
+const guarded = (connect) => async (options, ...rest) => {
+    if (options?.transport === 'unix') {
+        return connect(options, ...rest);
+    }
+
+    const host = options?.hostname ?? '127.0.0.1';
+    op_circuit_check(host);
+    try {
+        const conn = await connect(options, ...rest);
+        op_circuit_record(host, true);
+        return conn;
+    } catch (e) {
+        if (!NOT_COUNTED.includes(e?.name)) op_circuit_record(host, false);
+        throw e;
+    }
+};
+
+globalThis.Deno.connect = guarded(net.connect);
 globalThis.Deno.listen = net.listen;
 globalThis.Deno.resolveDns = net.resolveDns;
 
@@ -15,6 +35,8 @@ globalThis.Deno.listenDatagram = net.createListenDatagram(
     op_net_listen_unixpacket,
 );
 
-globalThis.Deno.connectTls = tls.connectTls;
+// Options not set by the script fall back to the embedder's `TlsProvider`
+import { op_tls_client_defaults } from "ext:core/ops";
+globalThis.Deno.connectTls = guarded((options) => tls.connectTls({ ...op_tls_client_defaults(), ...options }));
 globalThis.Deno.listenTls = tls.listenTls;
-globalThis.Deno.startTls = tls.startTls;
\ No newline at end of file
+globalThis.Deno.startTls = (conn, options) => tls.startTls(conn, { ...op_tls_client_defaults(), ...options });
\ No newline at end of file
@@ -11,6 +11,7 @@ import * as streams from 'ext:deno_web/06_streams.js';
 import * as encoding from 'ext:deno_web/08_text_encoding.js';
 import * as file from 'ext:deno_web/09_file.js';
 import * as fileReader from 'ext:deno_web/10_filereader.js';
+import 'ext:deno_web/11_blob_url.js'; // Adds `URL.createObjectURL` and `URL.revokeObjectURL`
 import * as location from 'ext:deno_web/12_location.js';
 import * as messagePort from 'ext:deno_web/13_message_port.js';
 import * as compression from 'ext:deno_web/14_compression.js';
@@ -19,10 +20,51 @@ import * as imageData from 'ext:deno_web/16_image_data.js';
 
 import * as errors from 'ext:init_web/init_errors.js';
 
+// Formats deno_web does not implement natively, such as brotli, are provided by the host
+import {
+    op_compression_host_formats, op_compression_new, op_compression_write, op_compression_finish,
+} from 'ext:core/ops';
+const hostFormats = op_compression_host_formats();
+function hostTransform(format, decompress) {
+    const rid = op_compression_new(format, decompress);
+    return new streams.TransformStream({
+        transform(chunk, controller) {
+            const bytes = ArrayBuffer.isView(chunk)
+                ? new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength)
+                : new Uint8Array(chunk);
+            const output = op_compression_write(rid, bytes);
+            if (output.byteLength) controller.enqueue(output);
+        },
+        flush(controller) {
+            const output = op_compression_finish(rid);
+            if (output.byteLength) controller.enqueue(output);
+        },
+    });
+}
+
+const withHostFormats = (Native, decompress) => {
+    if (!hostFormats.length) return Native;
+    const Wrapped = class {
+        #transform;
+        constructor(format) {
+            this.#transform = hostFormats.includes(format)
+                ? hostTransform(format, decompress)
+                : new Native(format);
+        }
+        get readable() { return this.#transform.readable; }
+        get writable() { return this.#transform.writable; }
+    };
+    Object.defineProperty(Wrapped, 'name', { value: Native.name });
+    return Wrapped;
+};
+const CompressionStream = withHostFormats(compression.CompressionStream, false);
+const DecompressionStream = withHostFormats(compression.DecompressionStream, true);
+
 globalThis.Deno.refTimer = timers.refTimer;
 globalThis.Deno.unrefTimer = timers.unrefTimer;
 
-import { applyToGlobal, nonEnumerable, writeable } from 'ext:rustyscript/rustyscript.js';
+import { applyToGlobal, nonEnumerable, writeable, limitTimers } from 'ext:rustyscript/rustyscript.js';
+const limitedTimers = limitTimers(timers);
 applyToGlobal({
     AbortController: nonEnumerable(abortSignal.AbortController),
     AbortSignal: nonEnumerable(abortSignal.AbortSignal),
@@ -31,12 +73,12 @@ applyToGlobal({
       streams.ByteLengthQueuingStrategy,
     ),
     CloseEvent: nonEnumerable(event.CloseEvent),
-    CompressionStream: nonEnumerable(compression.CompressionStream),
+    CompressionStream: nonEnumerable(CompressionStream),
     CountQueuingStrategy: nonEnumerable(
       streams.CountQueuingStrategy,
     ),
     CustomEvent: nonEnumerable(event.CustomEvent),
-    DecompressionStream: nonEnumerable(compression.DecompressionStream),
+    DecompressionStream: nonEnumerable(DecompressionStream),
     DOMException: nonEnumerable(DOMException),
     ErrorEvent: nonEnumerable(event.ErrorEvent),
     Event: nonEnumerable(event.Event),
@@ -85,16 +127,16 @@ applyToGlobal({
     ),
     atob: writeable(base64.atob),
     btoa: writeable(base64.btoa),
-    clearInterval: writeable(timers.clearInterval),
-    clearTimeout: writeable(timers.clearTimeout),
+    clearInterval: writeable(limitedTimers.clearInterval),
+    clearTimeout: writeable(limitedTimers.clearTimeout),
     performance: writeable(performance.performance),
     reportError: writeable(event.reportError),
-    setInterval: writeable(timers.setInterval),
-    setTimeout: writeable(timers.setTimeout),
+    setInterval: writeable(limitedTimers.setInterval),
+    setTimeout: writeable(limitedTimers.setTimeout),
     refTimer: writeable(timers.refTimer),
     setImmediate: writeable(timers.setImmediate),
-    setInterval: writeable(timers.setInterval),
-    setTimeout: writeable(timers.setTimeout),
+    setInterval: writeable(limitedTimers.setInterval),
+    setTimeout: writeable(limitedTimers.setTimeout),
     unrefTimer: writeable(timers.unrefTimer),
   
     structuredClone: writeable(messagePort.structuredClone),
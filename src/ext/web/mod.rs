@@ -5,46 +5,193 @@ use deno_core::{extension, Extension};
 use super::ExtensionTrait;
 
 mod options;
-pub use options::WebOptions;
+pub use options::{FetchHttpVersion, FetchPoolOptions, WebOptions};
 
 mod permissions;
-pub(crate) use permissions::PermissionsContainer;
 pub use permissions::{
     AllowlistWebPermissions, CheckedPath, DefaultWebPermissions, PermissionCheckError,
     PermissionDeniedError, SystemsPermissionKind, WebPermissions,
 };
+pub(crate) use permissions::{PermissionsContainer, RateLimitedPermissions};
 
-/// Stub for a node op deno_net expects to find
-/// We return None to show no cert available
+pub(crate) mod http_interop;
+pub use http_interop::HttpBody;
+use http_interop::{
+    op_http_bind_helpers, op_http_body_close, op_http_body_send, op_http_host_body_cancel,
+    op_http_host_body_read,
+};
+
+mod compression;
+pub use compression::CompressionFormat;
+use compression::{
+    op_compression_finish, op_compression_host_formats, op_compression_new, op_compression_write,
+};
+
+pub(crate) mod files;
+use files::op_files_bind_helpers;
+pub use files::{FormDataEntry, HostFile};
+
+pub(crate) mod fetch_client;
+
+mod fixtures;
+use fixtures::{op_fetch_fixture_load, op_fetch_fixture_mode, op_fetch_fixture_save};
+pub use fixtures::{
+    DirectoryFixtureStore, FetchFixtures, FetchMode, FixtureStore, MemoryFixtureStore,
+    RecordedResponse,
+};
+
+mod http_cache;
+use fetch_client::DefaultClientFactory;
+use http_cache::{
+    op_fetch_cache_enabled, op_fetch_cache_fill, op_fetch_cache_lookup, CacheFillClient,
+};
+pub use http_cache::{CachedResponse, HttpCache, HttpCacheStore, MemoryHttpCacheStore};
+
+mod schemes;
+use schemes::{op_fetch_scheme_handled, op_fetch_scheme_request, SchemeHandlers};
+pub use schemes::{SchemeHandlerFn, SchemeRequest, SchemeResponse};
+
+mod tls;
+use tls::{
+    op_tls_client_defaults, op_tls_key_provided, op_tls_peer_certificate, TlsProviderContainer,
+};
+pub use tls::{ClientCert, TlsProvider};
+
+pub(crate) mod trailers;
+use trailers::{op_fetch_bind_trailers, op_fetch_send_trailers, op_fetch_trailers, FetchTrailers};
+
+/// Returns the most fetches that may await a response at once, or `None` if unlimited
+#[deno_core::op2]
+#[serde]
+fn op_fetch_max_concurrent_requests(state: &mut deno_core::OpState) -> Option<usize> {
+    state
+        .try_borrow::<FetchPoolOptions>()
+        .and_then(|pool| pool.max_concurrent_requests)
+}
+
+/// The runtime's retry settings for fetch, or `None` if requests are not retried
 #[deno_core::op2]
 #[serde]
-pub fn op_tls_peer_certificate(
-    #[smi] _rid: u32,
-    _detailed: bool,
-) -> Option<deno_core::serde_json::Value> {
-    None
+fn op_fetch_retry_policy(state: &mut deno_core::OpState) -> Option<FetchRetryPolicy> {
+    state
+        .try_borrow::<crate::RetryPolicy>()
+        .map(|policy| FetchRetryPolicy {
+            max_attempts: policy.max_attempts,
+            statuses: policy.retry_statuses.clone(),
+        })
+}
+
+/// Returns the delay, in milliseconds, before retrying a fetch after the given attempt
+#[deno_core::op2(fast)]
+fn op_fetch_retry_delay(state: &mut deno_core::OpState, attempt: u32) -> f64 {
+    state
+        .try_borrow::<crate::RetryPolicy>()
+        .map_or(0.0, |policy| policy.delay(attempt).as_secs_f64() * 1000.0)
+}
+
+/// Returns the `Cookie` header for a request from the runtime's cookie jar, if it has one
+#[deno_core::op2]
+#[string]
+fn op_fetch_cookie_header(state: &mut deno_core::OpState, #[string] url: &str) -> Option<String> {
+    let jar = state.try_borrow::<crate::CookieJar>()?;
+    let url = deno_core::url::Url::parse(url).ok()?;
+    jar.is_enabled().then(|| jar.cookie_header(&url)).flatten()
+}
+
+/// Stores the cookies set by a response in the runtime's cookie jar, if it has one
+#[deno_core::op2]
+fn op_fetch_store_cookies(
+    state: &mut deno_core::OpState,
+    #[string] url: &str,
+    #[serde] cookies: Vec<crate::cookie_jar::SetCookie>,
+) {
+    let Some(jar) = state.try_borrow::<crate::CookieJar>() else {
+        return;
+    };
+    let Ok(url) = deno_core::url::Url::parse(url) else {
+        return;
+    };
+
+    if jar.is_enabled() {
+        for cookie in cookies {
+            jar.store(&url, cookie);
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FetchRetryPolicy {
+    max_attempts: u32,
+    statuses: Vec<u16>,
 }
 
 extension!(
     init_fetch,
     deps = [rustyscript],
+    ops = [
+        op_fetch_max_concurrent_requests, op_fetch_retry_policy, op_fetch_retry_delay,
+        op_fetch_cookie_header, op_fetch_store_cookies,
+        op_fetch_cache_enabled, op_fetch_cache_lookup, op_fetch_cache_fill,
+        op_fetch_fixture_mode, op_fetch_fixture_load, op_fetch_fixture_save,
+        op_fetch_scheme_handled, op_fetch_scheme_request,
+        op_http_bind_helpers, op_http_body_send, op_http_body_close,
+        op_http_host_body_read, op_http_host_body_cancel,
+        op_files_bind_helpers,
+        op_fetch_trailers, op_fetch_bind_trailers,
+    ],
     esm_entry_point = "ext:init_fetch/init_fetch.js",
     esm = [ dir "src/ext/web", "init_fetch.js" ],
+    middleware = |op| match op.name {
+        "op_fetch_send" => op.with_implementation_from(&op_fetch_send_trailers()),
+        _ => op,
+    },
+    options = {
+        pool: FetchPoolOptions,
+        client: Option<DefaultClientFactory>,
+        http_cache: Option<HttpCache>,
+        user_agent: String,
+        fixtures: Option<FetchFixtures>,
+        schemes: SchemeHandlers
+    },
+    state = |state, config| {
+        state.put(config.pool);
+        state.put(FetchTrailers::default());
+        fetch_client::install(state, config.client);
+        if let Some(cache) = config.http_cache {
+            state.put(cache);
+            state.put(CacheFillClient::new(&config.user_agent));
+        }
+        if let Some(fixtures) = config.fixtures {
+            state.put(fixtures);
+        }
+        if !config.schemes.0.is_empty() {
+            state.put(config.schemes);
+        }
+    },
 );
 impl ExtensionTrait<WebOptions> for init_fetch {
     fn init(options: WebOptions) -> Extension {
-        init_fetch::init()
+        let client = DefaultClientFactory::from_options(&options);
+        init_fetch::init(
+            options.fetch_pool,
+            client,
+            options.http_cache,
+            options.user_agent,
+            options.fetch_fixtures,
+            SchemeHandlers(options.scheme_handlers),
+        )
     }
 }
 impl ExtensionTrait<WebOptions> for deno_fetch::deno_fetch {
     fn init(options: WebOptions) -> Extension {
         let options = deno_fetch::Options {
             user_agent: options.user_agent.clone(),
-            root_cert_store_provider: options.root_cert_store_provider.clone(),
+            root_cert_store_provider: options.effective_root_cert_store_provider(),
             proxy: options.proxy.clone(),
             request_builder_hook: options.request_builder_hook,
             unsafely_ignore_certificate_errors: options.unsafely_ignore_certificate_errors.clone(),
-            client_cert_chain_and_key: options.client_cert_chain_and_key.clone(),
+            client_cert_chain_and_key: options.effective_client_cert_chain_and_key(),
             file_fetch_handler: options.file_fetch_handler.clone(),
             client_builder_hook: options.client_builder_hook,
             resolver: options.resolver.clone(),
@@ -54,35 +201,37 @@ impl ExtensionTrait<WebOptions> for deno_fetch::deno_fetch {
     }
 }
 
-#[cfg(not(feature = "node_experimental"))]
 extension!(
     init_net,
     deps = [rustyscript],
-    ops = [op_tls_peer_certificate],
-    esm_entry_point = "ext:init_net/init_net.js",
-    esm = [ dir "src/ext/web", "init_net.js" ],
-);
-
-#[cfg(feature = "node_experimental")]
-extension!(
-    init_net,
-    deps = [rustyscript],
-    ops = [tls_ops::op_tls_peer_certificate],
+    ops = [op_tls_peer_certificate, op_tls_client_defaults],
     esm_entry_point = "ext:init_net/init_net.js",
     esm = [ dir "src/ext/web", "init_net.js" ],
+    middleware = |op| match op.name {
+        "op_tls_key_null" => op.with_implementation_from(&op_tls_key_provided()),
+        _ => op,
+    },
+    options = {
+        tls_provider: Option<Arc<dyn TlsProvider>>
+    },
+    state = |state, config| {
+        if let Some(provider) = config.tls_provider {
+            state.put(TlsProviderContainer(provider));
+        }
+    },
 );
 
 impl ExtensionTrait<WebOptions> for init_net {
     fn init(options: WebOptions) -> Extension {
         let provider = rustls::crypto::aws_lc_rs::default_provider();
         let _ = rustls::crypto::CryptoProvider::install_default(provider); // Failure means already done for us
-        init_net::init()
+        init_net::init(options.tls_provider)
     }
 }
 impl ExtensionTrait<WebOptions> for deno_net::deno_net {
     fn init(options: WebOptions) -> Extension {
         deno_net::deno_net::init::<PermissionsContainer>(
-            options.root_cert_store_provider.clone(),
+            options.effective_root_cert_store_provider(),
             options.unsafely_ignore_certificate_errors.clone(),
         )
     }
@@ -109,6 +258,9 @@ impl ExtensionTrait<()> for deno_telemetry::deno_telemetry {
 extension!(
     init_web,
     deps = [rustyscript],
+    ops = [
+        op_compression_host_formats, op_compression_new, op_compression_write, op_compression_finish,
+    ],
     esm_entry_point = "ext:init_web/init_web.js",
     esm = [ dir "src/ext/web", "init_web.js", "init_errors.js" ],
     options = {
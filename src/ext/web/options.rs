@@ -1,9 +1,51 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use deno_fetch::dns::Resolver;
 use hyper_util::client::legacy::Builder;
 
-use super::{DefaultWebPermissions, WebPermissions};
+use super::{
+    tls::{ProviderRootCertStore, TlsProvider},
+    DefaultWebPermissions, WebPermissions,
+};
+
+/// The HTTP versions used by `fetch` for requests that do not specify their own `Deno.HttpClient`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FetchHttpVersion {
+    /// HTTP/1.1 or HTTP/2, negotiated with ALPN - HTTP/1.1 for plaintext connections
+    #[default]
+    Auto,
+
+    /// HTTP/1.1 only
+    Http1Only,
+
+    /// HTTP/2 only - negotiated with ALPN over TLS, and with prior knowledge (h2c) for plaintext
+    ///
+    /// Needed for gRPC and similar protocols against plaintext internal services
+    Http2Only,
+}
+
+/// Connection pool settings for fetches that do not supply their own `Deno.HttpClient`
+///
+/// Pooled runtimes keep idle connections open between invocations - limit them here, or close
+/// them with `Runtime::close_idle_connections`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FetchPoolOptions {
+    /// The most idle connections kept open to each host
+    ///
+    /// Default: unlimited
+    pub max_idle_per_host: Option<usize>,
+
+    /// How long an unused connection is kept open before it is closed
+    ///
+    /// Default: 90 seconds
+    pub idle_timeout: Option<Duration>,
+
+    /// The most fetches the runtime may have awaiting a response at once - further fetches wait
+    /// for one of them to finish
+    ///
+    /// Default: unlimited
+    pub max_concurrent_requests: Option<usize>,
+}
 
 /// Options for configuring the web related extensions
 #[derive(Clone)]
@@ -20,6 +62,29 @@ pub struct WebOptions {
     /// Proxy for fetch
     pub proxy: Option<deno_tls::Proxy>,
 
+    /// HTTP versions used by fetch, unless a request supplies its own `client`
+    ///
+    /// Also set by [`crate::RuntimeBuilder::with_fetch_http_version`]
+    pub fetch_http_version: FetchHttpVersion,
+
+    /// Connection pool settings for fetch, unless a request supplies its own `client`
+    pub fetch_pool: FetchPoolOptions,
+
+    /// Optional response cache for `GET` fetches, honoring `Cache-Control` and validators
+    ///
+    /// Clones share their store, so several runtimes can use the same one - see [`super::HttpCache`]
+    pub http_cache: Option<super::HttpCache>,
+
+    /// Optional recorded responses for `fetch` - replays them offline, or records live ones
+    ///
+    /// The mode can be switched from the host at any time - see [`super::FetchFixtures`]
+    pub fetch_fixtures: Option<super::FetchFixtures>,
+
+    /// Handlers for custom URL schemes, such as `asset://`, keyed by lowercase scheme name
+    ///
+    /// Fetches to these schemes are answered by the host - see [`WebOptions::register_scheme`]
+    pub scheme_handlers: HashMap<String, Arc<super::SchemeHandlerFn>>,
+
     /// Request builder hook for fetch
     #[allow(clippy::type_complexity)]
     pub request_builder_hook:
@@ -33,6 +98,12 @@ pub struct WebOptions {
     /// Client certificate and key for fetch
     pub client_cert_chain_and_key: deno_tls::TlsKeys,
 
+    /// Embedder-provided TLS configuration - root store, ALPN protocols and client certificate
+    ///
+    /// Takes precedence over `root_cert_store_provider` and `client_cert_chain_and_key`,
+    /// and also applies to `Deno.connectTls` and `Deno.startTls`
+    pub tls_provider: Option<Arc<dyn TlsProvider>>,
+
     /// File fetch handler for fetch
     pub file_fetch_handler: std::rc::Rc<dyn deno_fetch::FetchHandler>,
 
@@ -61,9 +132,15 @@ impl Default for WebOptions {
             user_agent: String::new(),
             root_cert_store_provider: None,
             proxy: None,
+            fetch_http_version: FetchHttpVersion::Auto,
+            fetch_pool: FetchPoolOptions::default(),
+            http_cache: None,
+            fetch_fixtures: None,
+            scheme_handlers: HashMap::new(),
             request_builder_hook: None,
             unsafely_ignore_certificate_errors: None,
             client_cert_chain_and_key: deno_tls::TlsKeys::Null,
+            tls_provider: None,
             file_fetch_handler: std::rc::Rc::new(deno_fetch::DefaultFileFetchHandler),
             permissions: Arc::new(DefaultWebPermissions),
             blob_store: Arc::new(deno_web::BlobStore::default()),
@@ -75,6 +152,55 @@ impl Default for WebOptions {
 }
 
 impl WebOptions {
+    /// The root store provider to use, taking `tls_provider` into account
+    pub(crate) fn effective_root_cert_store_provider(
+        &self,
+    ) -> Option<Arc<dyn deno_tls::RootCertStoreProvider>> {
+        match &self.tls_provider {
+            Some(provider) => Some(Arc::new(ProviderRootCertStore::new(provider.clone()))),
+            None => self.root_cert_store_provider.clone(),
+        }
+    }
+
+    /// The client certificate to use for fetch, taking `tls_provider` into account
+    pub(crate) fn effective_client_cert_chain_and_key(&self) -> deno_tls::TlsKeys {
+        self.tls_provider
+            .as_ref()
+            .and_then(|provider| provider.client_cert())
+            .map_or_else(
+                || self.client_cert_chain_and_key.clone(),
+                |cert| cert.tls_keys(),
+            )
+    }
+
+    /// Serve fetches to a custom URL scheme, such as `asset` or `app`, from the host
+    ///
+    /// The handler replaces any registered for the same scheme. It also takes precedence over
+    /// the built-in schemes, so registering `https` answers every such request from the host
+    ///
+    /// ```rust
+    /// use rustyscript::{SchemeRequest, SchemeResponse, WebOptions};
+    ///
+    /// let mut options = WebOptions::default();
+    /// options.register_scheme("asset", |request: &SchemeRequest| {
+    ///     Ok(match request.url.host_str() {
+    ///         Some("logo.svg") => SchemeResponse::new("<svg/>").with_header("content-type", "image/svg+xml"),
+    ///         _ => SchemeResponse::not_found(),
+    ///     })
+    /// });
+    /// ```
+    pub fn register_scheme(
+        &mut self,
+        scheme: &str,
+        handler: impl Fn(&super::SchemeRequest) -> Result<super::SchemeResponse, crate::Error>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        let scheme = scheme.trim_end_matches(':').to_ascii_lowercase();
+        self.scheme_handlers.insert(scheme, Arc::new(handler));
+    }
+
     /// Whitelist a domain or IP for ignoring certificate errors
     /// This is useful for testing with self-signed certificates
     pub fn whitelist_certificate_for(&mut self, domain_or_ip: impl ToString) {
@@ -1,10 +1,13 @@
 use std::{
     borrow::Cow,
     collections::HashSet,
+    net::{Ipv4Addr, Ipv6Addr},
     path::Path,
     sync::{Arc, RwLock},
 };
 
+use deno_core::url::Host;
+
 pub use deno_permissions::{CheckedPath, PermissionCheckError, PermissionDeniedError};
 
 pub fn oops(msg: impl std::fmt::Display) -> PermissionCheckError {
@@ -124,6 +127,165 @@ impl WebPermissions for DefaultWebPermissions {
     }
 }
 
+/// Wraps a permissions manager, drawing a token from a [`crate::RateLimiter`] for each URL or host
+/// that passes its checks
+///
+/// Every other check is forwarded unchanged
+#[derive(Debug)]
+pub(crate) struct RateLimitedPermissions {
+    inner: Arc<dyn WebPermissions>,
+    limiter: crate::RateLimiter,
+}
+impl RateLimitedPermissions {
+    pub fn wrap(
+        inner: Arc<dyn WebPermissions>,
+        limiter: crate::RateLimiter,
+    ) -> Arc<dyn WebPermissions> {
+        Arc::new(Self { inner, limiter })
+    }
+
+    fn acquire(&self, key: &str) -> Result<(), PermissionCheckError> {
+        self.limiter.check(key).map_err(oops)
+    }
+}
+impl WebPermissions for RateLimitedPermissions {
+    fn allow_hrtime(&self) -> bool {
+        self.inner.allow_hrtime()
+    }
+
+    fn check_url(
+        &self,
+        url: &deno_core::url::Url,
+        api_name: &str,
+    ) -> Result<(), PermissionCheckError> {
+        self.inner.check_url(url, api_name)?;
+        self.acquire(crate::RateLimiter::FETCH)
+    }
+
+    fn check_open<'a>(
+        &self,
+        resolved: bool,
+        read: bool,
+        write: bool,
+        path: Cow<'a, Path>,
+        api_name: &str,
+    ) -> Option<std::borrow::Cow<'a, Path>> {
+        self.inner.check_open(resolved, read, write, path, api_name)
+    }
+
+    fn check_read<'a>(
+        &self,
+        p: Cow<'a, Path>,
+        api_name: Option<&str>,
+    ) -> Result<Cow<'a, Path>, PermissionCheckError> {
+        self.inner.check_read(p, api_name)
+    }
+
+    fn check_read_all(&self, api_name: Option<&str>) -> Result<(), PermissionCheckError> {
+        self.inner.check_read_all(api_name)
+    }
+
+    fn check_read_blind(
+        &self,
+        p: &Path,
+        display: &str,
+        api_name: &str,
+    ) -> Result<(), PermissionCheckError> {
+        self.inner.check_read_blind(p, display, api_name)
+    }
+
+    fn check_write<'a>(
+        &self,
+        p: Cow<'a, Path>,
+        api_name: Option<&str>,
+    ) -> Result<Cow<'a, Path>, PermissionCheckError> {
+        self.inner.check_write(p, api_name)
+    }
+
+    fn check_write_all(&self, api_name: &str) -> Result<(), PermissionCheckError> {
+        self.inner.check_write_all(api_name)
+    }
+
+    fn check_write_blind(
+        &self,
+        p: &Path,
+        display: &str,
+        api_name: &str,
+    ) -> Result<(), PermissionCheckError> {
+        self.inner.check_write_blind(p, display, api_name)
+    }
+
+    fn check_write_partial<'a>(
+        &self,
+        path: Cow<'a, Path>,
+        api_name: &str,
+    ) -> Result<Cow<'a, Path>, PermissionCheckError> {
+        self.inner.check_write_partial(path, api_name)
+    }
+
+    fn check_host(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        api_name: &str,
+    ) -> Result<(), PermissionCheckError> {
+        self.inner.check_host(host, port, api_name)?;
+        self.acquire(crate::RateLimiter::NET)
+    }
+
+    fn check_vsock(&self, cid: u32, port: u32, api_name: &str) -> Result<(), PermissionCheckError> {
+        self.inner.check_vsock(cid, port, api_name)?;
+        self.acquire(crate::RateLimiter::NET)
+    }
+
+    fn check_sys(
+        &self,
+        kind: SystemsPermissionKind,
+        api_name: &str,
+    ) -> Result<(), PermissionCheckError> {
+        self.inner.check_sys(kind, api_name)
+    }
+
+    fn check_env(&self, var: &str) -> Result<(), PermissionCheckError> {
+        self.inner.check_env(var)
+    }
+
+    fn check_exec(&self) -> Result<(), PermissionCheckError> {
+        self.inner.check_exec()
+    }
+}
+
+/// Returns true if `host` is this machine, or an address on a private or link-local network
+fn is_private_host<S: AsRef<str>>(host: &Host<S>) -> bool {
+    let is_private_v4 = |ip: Ipv4Addr| {
+        let [a, b, ..] = ip.octets();
+        ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_unspecified()
+            || ip.is_broadcast()
+            || (a == 100 && (64..128).contains(&b)) // Carrier-grade NAT, 100.64.0.0/10
+    };
+
+    match host {
+        Host::Domain(domain) => {
+            let domain = domain.as_ref().trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Host::Ipv4(ip) => is_private_v4(*ip),
+        Host::Ipv6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || (first & 0xfe00) == 0xfc00 // Unique local, fc00::/7
+                    || (first & 0xffc0) == 0xfe80 // Link-local, fe80::/10
+            }
+        },
+    }
+}
+
 // Inner container for the allowlist permission set
 #[derive(Clone, Default, Debug)]
 #[allow(clippy::struct_excessive_bools)]
@@ -132,6 +294,8 @@ struct AllowlistWebPermissionsSet {
     pub exec: bool,
     pub read_all: bool,
     pub write_all: bool,
+    pub net_all: bool,
+    pub deny_private_net: bool,
     pub url: HashSet<String>,
     pub openr_paths: HashSet<String>,
     pub openw_paths: HashSet<String>,
@@ -165,6 +329,30 @@ impl AllowlistWebPermissions {
         self.0.write().expect("Could not lock permissions")
     }
 
+    /// OS-level rules mirroring the current set - see [`crate::Hardening::from_permissions`]
+    #[cfg(all(feature = "hardening", target_os = "linux"))]
+    pub(crate) fn hardening(&self) -> crate::Hardening {
+        let inst = self.borrow();
+        let paths = |all: bool, paths: &HashSet<String>, open: &HashSet<String>| {
+            if all {
+                vec!["/".into()]
+            } else {
+                paths.iter().chain(open).map(Into::into).collect()
+            }
+        };
+
+        crate::Hardening {
+            read_paths: paths(inst.read_all, &inst.read_paths, &inst.openr_paths),
+            write_paths: paths(inst.write_all, &inst.write_paths, &inst.openw_paths),
+            allow_network: inst.net_all
+                || !inst.hosts.is_empty()
+                || !inst.url.is_empty()
+                || !inst.vsock.is_empty(),
+            allow_exec: inst.exec,
+            ..Default::default()
+        }
+    }
+
     /// Set the `hrtime` permission
     ///
     /// If true, timers will be allowed to use high resolution time
@@ -193,6 +381,23 @@ impl AllowlistWebPermissions {
         self.borrow_mut().write_all = value;
     }
 
+    /// Set the `net_all` permission
+    ///
+    /// If true, every URL, host and virtual socket will be allowed, not just those whitelisted
+    pub fn set_net_all(&self, value: bool) {
+        self.borrow_mut().net_all = value;
+    }
+
+    /// Set the `deny_private_net` permission
+    ///
+    /// If true, `net_all` no longer covers this machine, or addresses on private and link-local
+    /// networks such as the `169.254.169.254` metadata service - they must be whitelisted individually
+    ///
+    /// Only the host as written is checked; a name that resolves to a private address is not caught
+    pub fn set_deny_private_net(&self, value: bool) {
+        self.borrow_mut().deny_private_net = value;
+    }
+
     /// Whitelist a path for opening
     ///
     /// If `read` is true, the path will be allowed to be opened for reading  
@@ -287,7 +492,16 @@ impl WebPermissions for AllowlistWebPermissions {
         port: Option<u16>,
         api_name: &str,
     ) -> Result<(), PermissionCheckError> {
-        if self.borrow().hosts.contains(host) {
+        let inst = self.borrow();
+        let public = || {
+            // Hosts that can't be parsed are treated as private
+            let parsed = match host.parse::<Ipv6Addr>() {
+                Ok(ip) => Ok(Host::Ipv6(ip)),
+                Err(_) => Host::parse(host),
+            };
+            !inst.deny_private_net || parsed.is_ok_and(|h| !is_private_host(&h))
+        };
+        if inst.hosts.contains(host) || (inst.net_all && public()) {
             Ok(())
         } else {
             Err(oops(host))
@@ -295,7 +509,8 @@ impl WebPermissions for AllowlistWebPermissions {
     }
 
     fn check_vsock(&self, cid: u32, port: u32, api_name: &str) -> Result<(), PermissionCheckError> {
-        if self.borrow().vsock.contains(&(cid, port)) {
+        let inst = self.borrow();
+        if inst.net_all || inst.vsock.contains(&(cid, port)) {
             Ok(())
         } else {
             Err(oops(format!("vsock: {cid}:{port}")))
@@ -307,7 +522,9 @@ impl WebPermissions for AllowlistWebPermissions {
         url: &deno_core::url::Url,
         api_name: &str,
     ) -> Result<(), PermissionCheckError> {
-        if self.borrow().url.contains(url.as_str()) {
+        let inst = self.borrow();
+        let public = || !inst.deny_private_net || !url.host().is_some_and(|h| is_private_host(&h));
+        if inst.url.contains(url.as_str()) || (inst.net_all && public()) {
             Ok(())
         } else {
             Err(oops(url))
@@ -635,6 +852,16 @@ impl_sys_permission_kinds!(
 
 #[derive(Clone, Debug)]
 pub struct PermissionsContainer(pub Arc<dyn WebPermissions>);
+impl PermissionsContainer {
+    /// Checks that a script may fetch `url`, for responses served without going to the network
+    pub(crate) fn check_fetch(&self, url: &str, api_name: &str) -> Result<(), crate::Error> {
+        let url = deno_core::url::Url::parse(url)
+            .map_err(|e| crate::Error::Runtime(format!("Invalid URL {url}: {e}")))?;
+        self.0
+            .check_url(&url, api_name)
+            .map_err(|e| crate::Error::Runtime(e.to_string()))
+    }
+}
 impl deno_web::TimersPermission for PermissionsContainer {
     fn allow_hrtime(&mut self) -> bool {
         self.0.allow_hrtime()
@@ -0,0 +1,175 @@
+//! Host-provided handlers for custom URL schemes, such as `asset://` or `app://`
+//!
+//! A `fetch` to a URL whose scheme has a handler never reaches the network - the handler is
+//! called with the request, and its response is returned to the script as-is.
+//! Such requests go around the runtime's cache, fixtures, cookies and circuit breaker
+use std::{collections::HashMap, sync::Arc};
+
+use deno_core::{op2, url::Url, OpState, ToJsBuffer};
+
+use crate::Error;
+
+/// A handler for a custom URL scheme - see [`crate::WebOptions::register_scheme`]
+pub type SchemeHandlerFn = dyn Fn(&SchemeRequest) -> Result<SchemeResponse, Error> + Send + Sync;
+
+/// A request made by `fetch` to a custom URL scheme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeRequest {
+    /// The request method, in uppercase
+    pub method: String,
+
+    /// The request URL
+    pub url: Url,
+
+    /// The request headers
+    pub headers: Vec<(String, String)>,
+
+    /// The request body - empty if there was none
+    pub body: Vec<u8>,
+}
+
+impl SchemeRequest {
+    /// Returns the value of a request header, if it is present
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The response to a [`SchemeRequest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeResponse {
+    /// The response status
+    pub status: u16,
+
+    /// The response headers
+    pub headers: Vec<(String, String)>,
+
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+impl SchemeResponse {
+    /// A `200 OK` response with the given body
+    #[must_use]
+    pub fn new(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// An empty `404 Not Found` response
+    #[must_use]
+    pub fn not_found() -> Self {
+        Self::new(Vec::new()).with_status(404)
+    }
+
+    /// Set the response status
+    #[must_use]
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Add a response header
+    #[must_use]
+    pub fn with_header(mut self, name: impl ToString, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// The runtime's handlers, by scheme
+#[derive(Clone, Default)]
+pub(crate) struct SchemeHandlers(pub HashMap<String, Arc<SchemeHandlerFn>>);
+
+/// Returns true if fetches to the given scheme are handled by the host
+#[op2(fast)]
+pub fn op_fetch_scheme_handled(state: &mut OpState, #[string] scheme: &str) -> bool {
+    state
+        .try_borrow::<SchemeHandlers>()
+        .is_some_and(|handlers| handlers.0.contains_key(scheme))
+}
+
+/// Calls the handler for a request's scheme
+#[op2]
+#[serde]
+pub fn op_fetch_scheme_request(
+    state: &mut OpState,
+    #[string] method: String,
+    #[string] url: &str,
+    #[serde] headers: Vec<(String, String)>,
+    #[buffer(copy)] body: Vec<u8>,
+) -> Result<(u16, Vec<(String, String)>, ToJsBuffer), Error> {
+    let url = Url::parse(url)?;
+    let handler = state
+        .try_borrow::<SchemeHandlers>()
+        .and_then(|handlers| handlers.0.get(url.scheme()).cloned())
+        .ok_or_else(|| Error::Runtime(format!("No handler for scheme '{}'", url.scheme())))?;
+
+    let request = SchemeRequest {
+        method,
+        url,
+        headers,
+        body,
+    };
+    let response = handler(&request)?;
+    Ok((response.status, response.headers, response.body.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_scheme_handlers() {
+        let mut options = RuntimeOptions::default();
+        options
+            .extension_options
+            .web
+            .register_scheme("asset", |request: &SchemeRequest| {
+                match (request.method.as_str(), request.url.host_str()) {
+                    ("GET", Some("logo.svg")) => {
+                        Ok(SchemeResponse::new("<svg/>")
+                            .with_header("content-type", "image/svg+xml"))
+                    }
+                    ("POST", Some("echo")) => Ok(SchemeResponse::new(request.body.clone())),
+                    ("GET", Some("broken")) => {
+                        Err(Error::Runtime("Asset store offline".to_string()))
+                    }
+                    _ => Ok(SchemeResponse::not_found()),
+                }
+            });
+        let mut runtime = Runtime::new(options).unwrap();
+
+        let logo: (String, String) = runtime
+            .eval(
+                "fetch('asset://logo.svg')
+                    .then(async (r) => [r.headers.get('content-type'), await r.text()])",
+            )
+            .unwrap();
+        assert_eq!(logo, ("image/svg+xml".to_string(), "<svg/>".to_string()));
+
+        let echo: String = runtime
+            .eval("fetch('asset://echo', { method: 'POST', body: 'ping' }).then((r) => r.text())")
+            .unwrap();
+        assert_eq!(echo, "ping");
+
+        let status: u16 = runtime
+            .eval("fetch(new Request('asset://missing.png')).then((r) => r.status)")
+            .unwrap();
+        assert_eq!(status, 404);
+
+        let error: String = runtime
+            .eval("fetch('asset://broken').catch((e) => `${e.name}: ${e.message}`)")
+            .unwrap();
+        assert!(error.starts_with("TypeError"), "{error}");
+        assert!(error.contains("Asset store offline"), "{error}");
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::{Arc, OnceLock};
+
+use deno_core::{op2, serde_json, OpState, ResourceId};
+use deno_tls::{rustls::RootCertStore, RootCertStoreProvider, TlsKey, TlsKeys, TlsKeysHolder};
+
+use crate::Error;
+
+/// Supplies TLS configuration for the runtime's outgoing connections
+///
+/// Used by `fetch`, `Deno.connectTls` and `Deno.startTls`. Every method has a default,
+/// so implementors only need to override what they wish to control
+///
+/// Set with [`super::WebOptions::tls_provider`]
+pub trait TlsProvider: Send + Sync {
+    /// The root certificates trusted for outgoing connections
+    ///
+    /// Called lazily, when a connection first needs it.  
+    /// Returning `None` uses the default (Mozilla) root store
+    fn root_cert_store(&self) -> Option<RootCertStore> {
+        None
+    }
+
+    /// ALPN protocols to offer, such as `h2` or `http/1.1`
+    ///
+    /// Used by `connectTls` and `startTls` when the script does not set `alpnProtocols`
+    fn alpn_protocols(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// A client certificate to present to servers that request one
+    ///
+    /// Used when the script does not supply its own `cert` and `key`. The private key never
+    /// reaches the script - it is handed to the connection from rust
+    fn client_cert(&self) -> Option<ClientCert> {
+        None
+    }
+}
+
+/// A client certificate chain and private key, for mutual TLS
+#[derive(Clone)]
+pub struct ClientCert {
+    cert_chain: String,
+    keys: TlsKey,
+}
+
+impl ClientCert {
+    /// Load a client certificate from a PEM-encoded certificate chain and private key
+    ///
+    /// # Errors
+    /// Will return an error if either the chain or the key cannot be parsed
+    pub fn from_pem(cert_chain: impl ToString, private_key: impl ToString) -> Result<Self, Error> {
+        let cert_chain = cert_chain.to_string();
+
+        let certs = deno_tls::load_certs(&mut cert_chain.as_bytes())
+            .map_err(|e| Error::Runtime(format!("Invalid certificate chain: {e}")))?;
+        if certs.is_empty() {
+            return Err(Error::Runtime("No certificates found".to_string()));
+        }
+        let key = deno_tls::load_private_keys(private_key.to_string().as_bytes())
+            .map_err(|e| Error::Runtime(format!("Invalid private key: {e}")))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::Runtime("No private key found".to_string()))?;
+
+        Ok(Self {
+            cert_chain,
+            keys: TlsKey(certs, key),
+        })
+    }
+
+    pub(crate) fn tls_keys(&self) -> TlsKeys {
+        TlsKeys::Static(self.keys.clone())
+    }
+}
+
+impl std::fmt::Debug for ClientCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCert")
+            .field("cert_chain", &self.cert_chain)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Adapts a [`TlsProvider`] into the root store provider used by `deno_fetch` and `deno_net`
+pub(crate) struct ProviderRootCertStore {
+    provider: Arc<dyn TlsProvider>,
+    store: OnceLock<RootCertStore>,
+}
+
+impl ProviderRootCertStore {
+    pub fn new(provider: Arc<dyn TlsProvider>) -> Self {
+        Self {
+            provider,
+            store: OnceLock::new(),
+        }
+    }
+}
+
+impl RootCertStoreProvider for ProviderRootCertStore {
+    fn get_or_try_init(&self) -> Result<&RootCertStore, deno_error::JsErrorBox> {
+        Ok(self.store.get_or_init(|| {
+            self.provider
+                .root_cert_store()
+                .unwrap_or_else(deno_tls::create_default_root_cert_store)
+        }))
+    }
+}
+
+/// The runtime's TLS provider, if one is configured
+pub(crate) struct TlsProviderContainer(pub Arc<dyn TlsProvider>);
+
+/// Returns the defaults a script's `connectTls` and `startTls` options are merged onto
+///
+/// The provider's client certificate is not among them - see [`op_tls_key_provided`]
+#[op2]
+#[serde]
+pub fn op_tls_client_defaults(state: &mut OpState) -> serde_json::Value {
+    let Some(provider) = state.try_borrow::<TlsProviderContainer>() else {
+        return serde_json::json!({});
+    };
+
+    let mut defaults = serde_json::Map::new();
+    let alpn = provider.0.alpn_protocols();
+    if !alpn.is_empty() {
+        defaults.insert("alpnProtocols".to_string(), alpn.into());
+    }
+
+    serde_json::Value::Object(defaults)
+}
+
+/// Replaces `op_tls_key_null`, used when a script connects without its own `cert` and `key`
+///
+/// Returns an opaque handle to the provider's client certificate, if it has one
+#[op2]
+#[cppgc]
+pub fn op_tls_key_provided(state: &mut OpState) -> TlsKeysHolder {
+    state
+        .try_borrow::<TlsProviderContainer>()
+        .and_then(|provider| provider.0.client_cert())
+        .map_or(TlsKeys::Null, |cert| cert.tls_keys())
+        .into()
+}
+
+/// Returns the certificates presented by the peer of a TLS connection
+///
+/// Also used by `deno_node` - the result is `{ raw }` for the leaf certificate, with the rest
+/// of the chain under `issuerCertificate` when `detailed` is set.  
+/// Returns `None` if the resource is not a TLS stream, or if the handshake has not completed
+#[op2]
+#[serde]
+pub fn op_tls_peer_certificate(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    detailed: bool,
+) -> Option<serde_json::Value> {
+    let resource = state
+        .resource_table
+        .get::<deno_net::ops_tls::TlsStreamResource>(rid)
+        .ok()?;
+    let certs = resource.peer_certificates()?;
+
+    let mut chain = certs
+        .iter()
+        .map(|cert| serde_json::json!({ "raw": cert.as_ref() }))
+        .rev()
+        .reduce(|issuer, mut cert| {
+            cert["issuerCertificate"] = issuer;
+            cert
+        })?;
+
+    if !detailed {
+        if let Some(chain) = chain.as_object_mut() {
+            chain.remove("issuerCertificate");
+        }
+    }
+
+    Some(chain)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    struct AlpnOnly;
+    impl TlsProvider for AlpnOnly {
+        fn alpn_protocols(&self) -> Vec<String> {
+            vec!["h2".to_string(), "http/1.1".to_string()]
+        }
+    }
+
+    #[test]
+    fn test_tls_provider() {
+        let mut options = RuntimeOptions::default();
+        options.extension_options.web.tls_provider = Some(Arc::new(AlpnOnly));
+        let mut runtime = Runtime::new(options).unwrap();
+
+        let defaults: serde_json::Value = runtime
+            .eval("Deno.core.ops.op_tls_client_defaults()")
+            .unwrap();
+        assert_eq!(
+            defaults,
+            serde_json::json!({ "alpnProtocols": ["h2", "http/1.1"] })
+        );
+
+        ClientCert::from_pem("not a cert", "not a key").expect_err("Parsed an invalid cert");
+    }
+}
@@ -0,0 +1,250 @@
+//! Trailers sent after the body of a `fetch` response, such as gRPC's `grpc-status`
+//!
+//! `deno_fetch` reads response bodies as a plain stream of bytes, dropping any trailers that follow.
+//! `op_fetch_send` is replaced to watch each body for them, so that scripts can read them with
+//! `response.trailer`, and the host with [`crate::Runtime::response_trailers`].
+//!
+//! Trailers are only known once the body has been read, or dropped
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use deno_core::{op2, v8, OpState, ResourceId};
+use deno_fetch::{FetchError, FetchResponse, FetchResponseReader, FetchResponseResource};
+use http_body_util::BodyExt;
+use tokio::sync::Notify;
+
+use crate::Error;
+
+/// How many responses to keep trailers for, if no script has asked for them
+///
+/// The oldest are forgotten first
+const MAX_UNCLAIMED: usize = 256;
+
+/// The trailers of a single response, filled in as its body is read
+#[derive(Default)]
+struct TrailerSlot {
+    trailers: Mutex<Option<http::HeaderMap>>,
+    finished: AtomicBool,
+    changed: Notify,
+}
+
+impl TrailerSlot {
+    /// Resolves with the trailers once the body is finished, or `None` if it had none
+    async fn wait(&self) -> Option<http::HeaderMap> {
+        loop {
+            let changed = self.changed.notified();
+            if self.finished.load(Ordering::Acquire) {
+                return self
+                    .trailers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take();
+            }
+            changed.await;
+        }
+    }
+}
+
+/// Finishes the slot once the body is dropped - after it has been read, or abandoned
+struct FinishOnDrop(Arc<TrailerSlot>);
+impl Drop for FinishOnDrop {
+    fn drop(&mut self) {
+        self.0.finished.store(true, Ordering::Release);
+        self.0.changed.notify_waiters();
+    }
+}
+
+/// Trailers of the runtime's `fetch` responses, keyed by the resource id of their body
+#[derive(Default)]
+pub(crate) struct FetchTrailers(BTreeMap<ResourceId, Arc<TrailerSlot>>);
+
+impl FetchTrailers {
+    fn insert(&mut self, rid: ResourceId, slot: Arc<TrailerSlot>) {
+        self.0.insert(rid, slot);
+        while self.0.len() > MAX_UNCLAIMED {
+            self.0.pop_first();
+        }
+    }
+}
+
+/// Replaces `deno_fetch`'s `op_fetch_send`, watching the response body for trailers
+#[op2(async)]
+#[serde]
+pub async fn op_fetch_send_trailers(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<FetchResponse, FetchError> {
+    let mut response = deno_fetch::op_fetch_send::call(state.clone(), rid).await?;
+
+    let mut state = state.borrow_mut();
+    let Ok(resource) = state
+        .resource_table
+        .take::<FetchResponseResource>(response.response_rid)
+    else {
+        return Ok(response);
+    };
+
+    // The body has not been touched yet - if the resource is shared after all, leave it be
+    let resource = match Rc::try_unwrap(resource) {
+        Ok(resource) => resource,
+        Err(resource) => {
+            response.response_rid = state.resource_table.add_rc(resource);
+            return Ok(response);
+        }
+    };
+
+    let size = resource.size;
+    let FetchResponseReader::Start(inner) = resource.response_reader.into_inner() else {
+        unreachable!("responses are not read before op_fetch_send returns");
+    };
+
+    let slot = Arc::new(TrailerSlot::default());
+    let finish = FinishOnDrop(slot.clone());
+    let (parts, body) = inner.into_parts();
+    let body = body.map_frame(move |frame| {
+        if let Some(trailers) = frame.trailers_ref() {
+            *finish
+                .0
+                .trailers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(trailers.clone());
+        }
+        frame
+    });
+
+    let body = http::Response::from_parts(parts, deno_fetch::ResBody::new(body));
+    response.response_rid = state
+        .resource_table
+        .add(FetchResponseResource::new(body, size));
+
+    state
+        .borrow_mut::<FetchTrailers>()
+        .insert(response.response_rid, slot);
+
+    Ok(response)
+}
+
+/// Resolves with the trailers of a `fetch` response once its body has been read or dropped
+///
+/// Returns `None` if the response had none, or its trailers were already claimed
+#[op2(async)]
+#[serde]
+pub async fn op_fetch_trailers(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Option<Vec<(String, String)>> {
+    let slot = state
+        .borrow_mut()
+        .try_borrow_mut::<FetchTrailers>()?
+        .0
+        .remove(&rid)?;
+    let trailers = slot.wait().await?;
+
+    Some(
+        trailers
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.to_string(), value)
+            })
+            .collect(),
+    )
+}
+
+/// JS function used to read the trailers of a sandbox `Response`
+#[derive(Clone)]
+pub(crate) struct TrailerHelper(pub v8::Global<v8::Function>);
+
+impl TrailerHelper {
+    /// Returns the JS helper for reading trailers
+    pub fn get(state: &OpState) -> Result<Self, Error> {
+        state
+            .try_borrow::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::Runtime("Response trailers are not initialized".to_string()))
+    }
+}
+
+/// Registers the JS helper for reading trailers
+#[op2]
+pub fn op_fetch_bind_trailers(
+    state: &mut OpState,
+    #[global] read_trailers: v8::Global<v8::Function>,
+) -> Result<(), Error> {
+    crate::ext::bind_once(state, "The trailer helper", TrailerHelper(read_trailers))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use crate::{js_value::Value, json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_trailers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\ntrailer: grpc-status\r\n\
+                    connection: close\r\n\r\n5\r\nhello\r\n0\r\ngrpc-status: 0\r\n\r\n",
+                )
+                .unwrap();
+        });
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                export const get = (url) => fetch(url, { headers: { te: 'trailers' } });
+                export const read = async (response) => {
+                    const text = await response.text();
+                    const trailer = await response.trailer;
+                    return [text, trailer.get('grpc-status')];
+                };
+                export const local = () => new Response('hello');
+            ",
+            ))
+            .unwrap();
+
+        // Scripts read them once the body is done
+        let response: Value = runtime
+            .call_function(Some(&module), "get", json_args!(url))
+            .unwrap();
+        let (text, status): (String, String) = runtime
+            .call_function(Some(&module), "read", json_args!(response))
+            .unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(status, "0");
+
+        // And so can the host
+        let trailers = runtime.response_trailers(&response).unwrap();
+        assert_eq!(trailers, vec![("grpc-status".to_string(), "0".to_string())]);
+
+        // Responses that did not come from the network have none
+        let local: Value = runtime
+            .call_function(Some(&module), "local", json_args!())
+            .unwrap();
+        assert!(runtime.response_trailers(&local).unwrap().is_empty());
+
+        let value: Value = runtime.eval("1").unwrap();
+        runtime
+            .response_trailers(&value)
+            .expect_err("Read trailers from a non-Response value");
+    }
+}
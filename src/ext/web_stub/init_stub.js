@@ -2,15 +2,16 @@ import * as DOMException from 'ext:deno_web/01_dom_exception.js';
 import * as timers from 'ext:deno_web/02_timers.js';
 import * as base64 from 'ext:deno_web/05_base64.js';
 
-import { applyToGlobal, nonEnumerable, writeable } from 'ext:rustyscript/rustyscript.js';
+import { applyToGlobal, nonEnumerable, writeable, limitTimers } from 'ext:rustyscript/rustyscript.js';
+const limitedTimers = limitTimers(timers);
 applyToGlobal({
     DOMException: nonEnumerable(DOMException),
 
     setImmediate: writeable(timers.setImmediate),
-    clearInterval: writeable(timers.clearInterval),
-    clearTimeout: writeable(timers.clearTimeout),
-    setInterval: writeable(timers.setInterval),
-    setTimeout: writeable(timers.setTimeout),
+    clearInterval: writeable(limitedTimers.clearInterval),
+    clearTimeout: writeable(limitedTimers.clearTimeout),
+    setInterval: writeable(limitedTimers.setInterval),
+    setTimeout: writeable(limitedTimers.setTimeout),
     refTimer: writeable(timers.refTimer),
     unrefTimer: writeable(timers.unrefTimer),
 
@@ -1,7 +1,14 @@
-use deno_core::{extension, url::Url, Extension};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc, time::SystemTime};
+
+use deno_core::{
+    extension, op2, serde_json, serde_v8, url::Url, v8, ByteString, Extension, JsBuffer, OpState,
+    ResourceId, ToJsBuffer,
+};
 use deno_permissions::PermissionCheckError;
+use deno_websocket::{CreateResponse, WebsocketError};
 
 use super::{web::PermissionsContainer, web::WebOptions, ExtensionTrait};
+use crate::Error;
 
 impl deno_websocket::WebSocketPermissions for PermissionsContainer {
     fn check_net_url(&mut self, url: &Url, api_name: &str) -> Result<(), PermissionCheckError> {
@@ -10,11 +17,363 @@ impl deno_websocket::WebSocketPermissions for PermissionsContainer {
     }
 }
 
+/// A `WebSocket` opened by a script, as listed by [`crate::Runtime::websockets`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketInfo {
+    /// Identifies the connection to [`crate::Runtime::close_websocket`]
+    pub id: u32,
+
+    /// The URL the script connected to
+    pub url: String,
+
+    /// When the script created the socket - it may still be connecting
+    pub created_at: SystemTime,
+}
+
+/// Which way a [`WebSocketFrame`] was travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketDirection {
+    /// Received from the server
+    Inbound,
+
+    /// Sent by the script
+    Outbound,
+}
+
+/// The contents of a [`WebSocketFrame`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketPayload {
+    /// A text message
+    Text(String),
+
+    /// A binary message
+    Binary(Vec<u8>),
+}
+
+/// A message passed to the tap set with [`crate::RuntimeOptions::on_websocket_frame`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketFrame {
+    /// The connection the message belongs to
+    pub id: u32,
+
+    /// The URL the script connected to
+    pub url: String,
+
+    /// Whether the message was sent or received
+    pub direction: WebSocketDirection,
+
+    /// The message itself
+    pub payload: WebSocketPayload,
+}
+
+/// A connection being tracked by the [`WebSocketRegistry`]
+struct TrackedSocket {
+    info: WebSocketInfo,
+
+    /// Aborts the connection while it is still being made
+    cancel: Option<ResourceId>,
+
+    /// The connection's resource, once it has opened
+    rid: Option<ResourceId>,
+}
+
+/// Tracks the `WebSocket` connections scripts have open
+///
+/// Sockets are tracked by the `deno_websocket` ops themselves, so every connection is seen -
+/// whichever class opened it, and whatever the script has done to the globals since
+#[derive(Default)]
+pub(crate) struct WebSocketRegistry {
+    next_id: u32,
+    sockets: BTreeMap<u32, TrackedSocket>,
+    pub tap: Option<Box<dyn Fn(&WebSocketFrame)>>,
+}
+
+impl WebSocketRegistry {
+    /// The connections that are still open, oldest first
+    ///
+    /// Drops any whose resource has been closed since
+    pub fn open(state: &mut OpState) -> Vec<WebSocketInfo> {
+        let closed: Vec<u32> = state
+            .borrow::<Self>()
+            .sockets
+            .values()
+            .filter(|socket| socket.rid.is_some_and(|rid| !state.resource_table.has(rid)))
+            .map(|socket| socket.info.id)
+            .collect();
+
+        let registry = state.borrow_mut::<Self>();
+        for id in closed {
+            registry.sockets.remove(&id);
+        }
+        registry
+            .sockets
+            .values()
+            .map(|socket| socket.info.clone())
+            .collect()
+    }
+
+    fn track(&mut self, url: String, cancel: Option<ResourceId>) -> u32 {
+        self.next_id += 1;
+
+        let id = self.next_id;
+        let info = WebSocketInfo {
+            id,
+            url,
+            created_at: SystemTime::now(),
+        };
+        self.sockets.insert(
+            id,
+            TrackedSocket {
+                info,
+                cancel,
+                rid: None,
+            },
+        );
+        id
+    }
+
+    /// Passes a message to the host's tap, if there is one - `payload` is only built if there is
+    fn report(
+        &self,
+        rid: ResourceId,
+        direction: WebSocketDirection,
+        payload: impl FnOnce() -> WebSocketPayload,
+    ) {
+        let Some(tap) = &self.tap else {
+            return;
+        };
+        let Some(socket) = self.sockets.values().find(|socket| socket.rid == Some(rid)) else {
+            return;
+        };
+
+        tap(&WebSocketFrame {
+            id: socket.info.id,
+            url: socket.info.url.clone(),
+            direction,
+            payload: payload(),
+        });
+    }
+}
+
+/// Closes a tracked connection on behalf of the host, as if the script had called `close(code, reason)`
+///
+/// A connection that is still being made is aborted instead
+///
+/// Returns false if the connection has already closed
+pub(crate) async fn close(
+    state: Rc<RefCell<OpState>>,
+    id: u32,
+    code: Option<u16>,
+    reason: String,
+) -> Result<bool, Error> {
+    let code = code.unwrap_or(1000);
+    if code != 1000 && !(3000..=4999).contains(&code) {
+        return Err(Error::Runtime(format!(
+            "WebSocket close code must be 1000, or between 3000 and 4999, not {code}"
+        )));
+    }
+    if reason.len() > 123 {
+        return Err(Error::Runtime(
+            "WebSocket close reason must be no longer than 123 bytes".to_string(),
+        ));
+    }
+
+    let socket = {
+        let mut state = state.try_borrow_mut()?;
+        WebSocketRegistry::open(&mut state);
+        state
+            .borrow::<WebSocketRegistry>()
+            .sockets
+            .get(&id)
+            .map(|socket| (socket.rid, socket.cancel))
+    };
+
+    match socket {
+        Some((Some(rid), _)) => {
+            deno_websocket::op_ws_close::call(state, rid, Some(code), Some(reason))
+                .await
+                .map_err(|e| Error::Runtime(e.to_string()))?;
+            Ok(true)
+        }
+        Some((None, Some(cancel))) => {
+            Ok(state.try_borrow_mut()?.resource_table.close(cancel).is_ok())
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Replaces `deno_websocket`'s `op_ws_create`, tracking the connection from the moment it is requested
+#[op2(async)]
+#[serde]
+async fn op_ws_create_tracked(
+    state: Rc<RefCell<OpState>>,
+    #[string] api_name: String,
+    #[string] url: String,
+    #[string] protocols: String,
+    #[smi] cancel_handle: Option<ResourceId>,
+    #[serde] headers: Option<Vec<(ByteString, ByteString)>>,
+) -> Result<CreateResponse, WebsocketError> {
+    let id = state
+        .borrow_mut()
+        .borrow_mut::<WebSocketRegistry>()
+        .track(url.clone(), cancel_handle);
+
+    let response = deno_websocket::op_ws_create::<PermissionsContainer>::call(
+        state.clone(),
+        api_name,
+        url,
+        protocols,
+        cancel_handle,
+        headers,
+    )
+    .await;
+
+    // The resource id is not public, but is serialized for the script
+    let rid = response
+        .as_ref()
+        .ok()
+        .and_then(|response| serde_json::to_value(response).ok())
+        .and_then(|response| response["rid"].as_u64())
+        .and_then(|rid| ResourceId::try_from(rid).ok());
+
+    let mut state = state.borrow_mut();
+    let registry = state.borrow_mut::<WebSocketRegistry>();
+    match (rid, registry.sockets.get_mut(&id)) {
+        (Some(rid), Some(socket)) => socket.rid = Some(rid),
+        _ => {
+            registry.sockets.remove(&id);
+        }
+    }
+
+    response
+}
+
+/// Replaces `deno_websocket`'s `op_ws_send_text`, reporting the message to the tap
+#[op2(fast)]
+fn op_ws_send_text_tapped(state: &mut OpState, #[smi] rid: ResourceId, #[string] data: String) {
+    state
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Outbound, || {
+            WebSocketPayload::Text(data.clone())
+        });
+    deno_websocket::op_ws_send_text::call(state, rid, data);
+}
+
+/// Replaces `deno_websocket`'s `op_ws_send_binary`, reporting the message to the tap
+#[op2(fast)]
+fn op_ws_send_binary_tapped(state: &mut OpState, #[smi] rid: ResourceId, #[anybuffer] data: &[u8]) {
+    state
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Outbound, || {
+            WebSocketPayload::Binary(data.to_vec())
+        });
+    deno_websocket::op_ws_send_binary::call(state, rid, data);
+}
+
+/// Replaces `deno_websocket`'s `op_ws_send_binary_ab`, reporting the message to the tap
+#[op2(fast)]
+fn op_ws_send_binary_ab_tapped(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[arraybuffer] data: &[u8],
+) {
+    state
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Outbound, || {
+            WebSocketPayload::Binary(data.to_vec())
+        });
+    deno_websocket::op_ws_send_binary_ab::call(state, rid, data);
+}
+
+/// Replaces `deno_websocket`'s `op_ws_send_text_async`, reporting the message to the tap
+#[op2(async)]
+async fn op_ws_send_text_async_tapped(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[string] data: String,
+) -> Result<(), WebsocketError> {
+    state
+        .borrow()
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Outbound, || {
+            WebSocketPayload::Text(data.clone())
+        });
+    deno_websocket::op_ws_send_text_async::call(state, rid, data).await
+}
+
+/// Replaces `deno_websocket`'s `op_ws_send_binary_async`, reporting the message to the tap
+#[op2(async)]
+async fn op_ws_send_binary_async_tapped(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[buffer] data: JsBuffer,
+) -> Result<(), WebsocketError> {
+    state
+        .borrow()
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Outbound, || {
+            WebSocketPayload::Binary(data.to_vec())
+        });
+    deno_websocket::op_ws_send_binary_async::call(state, rid, data).await
+}
+
+/// Replaces `deno_websocket`'s `op_ws_get_buffer_as_string`, reporting the message to the tap
+#[op2]
+#[string]
+fn op_ws_get_buffer_as_string_tapped(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Option<String> {
+    let text = deno_websocket::op_ws_get_buffer_as_string::call(state, rid)?;
+    state
+        .borrow::<WebSocketRegistry>()
+        .report(rid, WebSocketDirection::Inbound, || {
+            WebSocketPayload::Text(text.clone())
+        });
+    Some(text)
+}
+
+/// Replaces `deno_websocket`'s `op_ws_get_buffer`, reporting the message to the tap
+#[op2]
+fn op_ws_get_buffer_tapped<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    let buffer: Option<ToJsBuffer> = deno_websocket::op_ws_get_buffer::call(state, rid);
+    let buffer = serde_v8::to_v8(scope, buffer)?;
+
+    // The buffer's contents are only reachable once it is in the isolate
+    if let Ok(view) = v8::Local::<v8::ArrayBufferView>::try_from(buffer) {
+        state
+            .borrow::<WebSocketRegistry>()
+            .report(rid, WebSocketDirection::Inbound, || {
+                let mut data = vec![0; view.byte_length()];
+                view.copy_contents(&mut data);
+                WebSocketPayload::Binary(data)
+            });
+    }
+
+    Ok(buffer)
+}
+
 extension!(
     init_websocket,
     deps = [rustyscript],
     esm_entry_point = "ext:init_websocket/init_websocket.js",
     esm = [ dir "src/ext/websocket", "init_websocket.js" ],
+    middleware = |op| match op.name {
+        "op_ws_create" => op.with_implementation_from(&op_ws_create_tracked()),
+        "op_ws_send_text" => op.with_implementation_from(&op_ws_send_text_tapped()),
+        "op_ws_send_binary" => op.with_implementation_from(&op_ws_send_binary_tapped()),
+        "op_ws_send_binary_ab" => op.with_implementation_from(&op_ws_send_binary_ab_tapped()),
+        "op_ws_send_text_async" => op.with_implementation_from(&op_ws_send_text_async_tapped()),
+        "op_ws_send_binary_async" => op.with_implementation_from(&op_ws_send_binary_async_tapped()),
+        "op_ws_get_buffer_as_string" => op.with_implementation_from(&op_ws_get_buffer_as_string_tapped()),
+        "op_ws_get_buffer" => op.with_implementation_from(&op_ws_get_buffer_tapped()),
+        _ => op,
+    },
+    state = |state| state.put(WebSocketRegistry::default()),
 );
 impl ExtensionTrait<()> for init_websocket {
     fn init((): ()) -> Extension {
@@ -37,3 +396,44 @@ pub fn extensions(options: WebOptions, is_snapshot: bool) -> Vec<Extension> {
         init_websocket::build((), is_snapshot),
     ]
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions, Undefined};
+
+    #[test]
+    fn test_websocket_control() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        assert!(runtime.websockets().unwrap().is_empty());
+
+        // Nothing listens on port 9 of the loopback address, so the socket never opens
+        runtime
+            .eval::<Undefined>("void (globalThis.ws = new WebSocket('ws://127.0.0.1:9/'))")
+            .unwrap();
+        let sockets = runtime.websockets().unwrap();
+        assert_eq!(sockets.len(), 1);
+        assert_eq!(sockets[0].url, "ws://127.0.0.1:9/");
+
+        // Tracking happens below the JS classes, so scripts can neither forge nor hide frames
+        let exposed: bool = runtime
+            .eval("['op_websocket_tap_text', 'op_websocket_untrack'].some((op) => op in Deno.core.ops)")
+            .unwrap();
+        assert!(!exposed);
+
+        let id = sockets[0].id;
+        assert!(runtime.close_websocket(id, Some(1001), "").is_err());
+        assert!(runtime.close_websocket(id, None, "Too old").unwrap());
+        assert!(!runtime.close_websocket(id + 1, None, "").unwrap());
+
+        let closed: bool = runtime
+            .eval(
+                "new Promise((resolve) => ws.readyState === WebSocket.CLOSED
+                    ? resolve(true)
+                    : ws.addEventListener('close', () => resolve(true)))",
+            )
+            .unwrap();
+        assert!(closed);
+        assert!(runtime.websockets().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+//! OS-level hardening of the thread a runtime runs on (Linux only)
+//!
+//! Landlock hides the filesystem outside of the allowed paths, and can refuse TCP connections,
+//! while a seccomp filter refuses syscalls no script should need (process spawning, tracing,
+//! mounts, kernel modules, ...). Both apply to the calling thread and to any thread it starts
+//! afterwards, and cannot be lifted - so they back up the in-process permission checks even if
+//! those are bypassed
+//!
+//! Threads that already exist are not confined. That includes V8's platform worker threads, if
+//! any runtime was created in the process before hardening was applied, and the threads of a
+//! tokio runtime shared with [`crate::Runtime::with_tokio_runtime`]. Work those threads do
+//! for the runtime - background compilation, blocking tasks - runs unrestricted, so harden a
+//! thread before the process creates its first runtime, and give hardened runtimes their own
+//! tokio runtime
+use std::{collections::BTreeMap, path::PathBuf};
+
+use landlock::{
+    path_beneath_rules, Access, AccessFs, AccessNet, Ruleset, RulesetAttr, RulesetCreatedAttr,
+    RulesetStatus, ABI,
+};
+use seccompiler::{BpfProgram, SeccompAction, SeccompFilter, TargetArch};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// The newest landlock ABI rules are written against - older kernels enforce what they can
+const LANDLOCK_ABI: ABI = ABI::V4;
+
+/// Syscalls refused on every hardened thread
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_setns,
+    libc::SYS_unshare,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_kexec_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    // io_uring performs its operations outside of the syscall filter
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+];
+
+/// Syscalls refused unless [`Hardening::allow_exec`] is set
+const EXEC_SYSCALLS: &[libc::c_long] = &[libc::SYS_execve, libc::SYS_execveat];
+
+/// Syscalls refused unless [`Hardening::allow_network`] is set
+const NETWORK_SYSCALLS: &[libc::c_long] = &[libc::SYS_socket, libc::SYS_socketpair];
+
+/// Files read to resolve host names, readable whenever network access is allowed
+#[cfg(feature = "web")]
+const RESOLVER_FILES: &[&str] = &["/etc/resolv.conf", "/etc/hosts", "/etc/nsswitch.conf"];
+
+/// OS-level restrictions for the thread a runtime runs on
+///
+/// Apply with [`Hardening::apply`] on the runtime's own thread, before creating the runtime - or set
+/// it on `worker::DefaultWorkerOptions::hardening` or `sandbox_process::ProcessRuntimeOptions::hardening`
+/// to have it applied for you.
+///
+/// ```rust,no_run
+/// use rustyscript::{Hardening, Runtime, RuntimeOptions};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let thread = std::thread::spawn(|| -> Result<i64, rustyscript::Error> {
+///     Hardening {
+///         read_paths: vec!["/srv/scripts".into()],
+///         ..Default::default()
+///     }
+///     .apply()?;
+///
+///     let mut runtime = Runtime::new(RuntimeOptions::default())?;
+///     runtime.eval("1 + 1")
+/// });
+/// assert_eq!(thread.join().unwrap()?, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Hardening {
+    /// Paths that may be read, with everything beneath them
+    pub read_paths: Vec<PathBuf>,
+
+    /// Paths that may be read and written, with everything beneath them
+    pub write_paths: Vec<PathBuf>,
+
+    /// Whether sockets may be opened, and TCP connections made
+    pub allow_network: bool,
+
+    /// Whether other programs may be executed
+    pub allow_exec: bool,
+
+    /// Whether to restrict the filesystem and network with landlock
+    pub landlock: bool,
+
+    /// Whether to refuse unneeded syscalls with a seccomp filter
+    pub seccomp: bool,
+}
+
+impl Default for Hardening {
+    fn default() -> Self {
+        Self {
+            read_paths: Vec::new(),
+            write_paths: Vec::new(),
+            allow_network: false,
+            allow_exec: false,
+            landlock: true,
+            seccomp: true,
+        }
+    }
+}
+
+/// How much of a restriction the kernel enforced - see [`HardeningReport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enforcement {
+    /// Enforced in full
+    Full,
+
+    /// Enforced in part - the kernel does not support every rule
+    Partial,
+
+    /// Not enforced - the kernel does not support it, or it was disabled
+    None,
+}
+
+/// What a call to [`Hardening::apply`] managed to enforce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardeningReport {
+    /// Filesystem and network rules
+    pub landlock: Enforcement,
+
+    /// The syscall filter
+    pub seccomp: Enforcement,
+}
+
+impl Hardening {
+    /// Rules mirroring an allowlist - its readable, writable and openable paths, whether any network
+    /// access is allowed, and whether subprocesses are
+    ///
+    /// If network access is allowed, so is reading the files needed to resolve host names.
+    /// Later changes to the allowlist are not reflected, and cannot widen the rules once applied
+    #[cfg(feature = "web")]
+    #[must_use]
+    pub fn from_permissions(permissions: &crate::AllowlistWebPermissions) -> Self {
+        let mut hardening = permissions.hardening();
+        if hardening.allow_network {
+            let resolver_files = RESOLVER_FILES.iter().map(PathBuf::from);
+            hardening
+                .read_paths
+                .extend(resolver_files.filter(|path| path.exists()));
+        }
+        hardening
+    }
+
+    /// Restricts the calling thread, and any thread it starts from now on
+    ///
+    /// Threads that already exist, including V8's platform threads and a shared tokio runtime's
+    /// workers, are not restricted - see the [module documentation](self)
+    ///
+    /// Kernels without landlock support are reported as [`Enforcement::None`] rather than failing;
+    /// check the report if running unrestricted is not acceptable
+    ///
+    /// # Errors
+    /// Can fail if an allowed path cannot be opened, or if a restriction cannot be installed
+    pub fn apply(&self) -> Result<HardeningReport, Error> {
+        let landlock = if self.landlock {
+            self.apply_landlock()?
+        } else {
+            Enforcement::None
+        };
+
+        let seccomp = if self.seccomp {
+            self.apply_seccomp()?;
+            Enforcement::Full
+        } else {
+            Enforcement::None
+        };
+
+        Ok(HardeningReport { landlock, seccomp })
+    }
+
+    fn apply_landlock(&self) -> Result<Enforcement, Error> {
+        let mut ruleset = Ruleset::default()
+            .handle_access(AccessFs::from_all(LANDLOCK_ABI))
+            .map_err(hardening_error)?;
+        if !self.allow_network {
+            ruleset = ruleset
+                .handle_access(AccessNet::from_all(LANDLOCK_ABI))
+                .map_err(hardening_error)?;
+        }
+
+        let status = ruleset
+            .create()
+            .map_err(hardening_error)?
+            .add_rules(path_beneath_rules(
+                &self.read_paths,
+                AccessFs::from_read(LANDLOCK_ABI),
+            ))
+            .map_err(hardening_error)?
+            .add_rules(path_beneath_rules(
+                &self.write_paths,
+                AccessFs::from_all(LANDLOCK_ABI),
+            ))
+            .map_err(hardening_error)?
+            .restrict_self()
+            .map_err(hardening_error)?;
+
+        Ok(match status.ruleset {
+            RulesetStatus::FullyEnforced => Enforcement::Full,
+            RulesetStatus::PartiallyEnforced => Enforcement::Partial,
+            RulesetStatus::NotEnforced => Enforcement::None,
+        })
+    }
+
+    fn apply_seccomp(&self) -> Result<(), Error> {
+        let mut denied = DENIED_SYSCALLS.to_vec();
+        if !self.allow_exec {
+            denied.extend_from_slice(EXEC_SYSCALLS);
+        }
+        if !self.allow_network {
+            denied.extend_from_slice(NETWORK_SYSCALLS);
+        }
+
+        // An empty rule list matches the syscall unconditionally
+        let rules = denied
+            .into_iter()
+            .map(|syscall| (i64::from(syscall), Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+        let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(hardening_error)?;
+
+        #[allow(clippy::cast_sign_loss)]
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            arch,
+        )
+        .map_err(hardening_error)?;
+        let program: BpfProgram = filter.try_into().map_err(hardening_error)?;
+        seccompiler::apply_filter(&program).map_err(hardening_error)
+    }
+}
+
+fn hardening_error(error: impl std::fmt::Display) -> Error {
+    Error::Runtime(format!("Could not harden the runtime thread: {error}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hardening() {
+        let dir = std::env::temp_dir().join("rustyscript_hardening_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("allowed.txt"), "ok").unwrap();
+
+        let hardening = Hardening {
+            read_paths: vec![dir.clone()],
+            ..Default::default()
+        };
+        let thread = std::thread::spawn(move || {
+            let report = hardening.apply().unwrap();
+            let allowed = std::fs::read_to_string(dir.join("allowed.txt"));
+            let hidden = std::fs::read_to_string("/etc/hostname");
+            let spawned = std::process::Command::new("true").status();
+            (report, allowed, hidden, spawned)
+        });
+        let (report, allowed, hidden, spawned) = thread.join().unwrap();
+
+        assert_eq!(report.seccomp, Enforcement::Full);
+        assert!(spawned.is_err());
+        assert_eq!(allowed.unwrap(), "ok");
+        if report.landlock != Enforcement::None {
+            assert!(hidden.is_err());
+        }
+
+        // Only the hardened thread is affected
+        assert!(std::fs::metadata("/etc").is_ok());
+    }
+}
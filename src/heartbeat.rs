@@ -0,0 +1,340 @@
+//! Heartbeats from long-running calls
+//!
+//! Scripts report that they are still alive with `rustyscript.heartbeat(message?)`, or report
+//! how far along they are with `rustyscript.progress(percent, message?)`. Each report is passed to
+//! the host's [`HeartbeatOptions`] callback, which can push the call's deadline back or abort it.
+//!
+//! Combined with a short [`crate::RuntimeOptions::timeout`], that lets the host tell a slow call
+//! that is still making progress from one that has hung - see [`HeartbeatOptions::keep_alive`]
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cancellation::{CallCanceller, CallSignal},
+    Error,
+};
+
+/// A report sent by a script with `rustyscript.heartbeat` or `rustyscript.progress`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// Time since the host began the current call
+    pub elapsed: Duration,
+
+    /// Time left before the call's deadline, or `None` if it has none
+    pub remaining: Option<Duration>,
+
+    /// How many heartbeats the current call has sent, including this one
+    pub count: u64,
+
+    /// The percentage of the work done, from 0 to 100, if the script reported one
+    pub progress: Option<f64>,
+
+    /// A description of what the script is doing, if it sent one
+    pub message: Option<String>,
+}
+
+/// What to do with a call after a heartbeat - returned by the [`HeartbeatOptions`] callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatAction {
+    /// Leave the deadline where it is
+    Continue,
+
+    /// Push the deadline back so that at least this much time remains
+    ///
+    /// Never brings the deadline forward, and has no effect on calls without one
+    Extend(Duration),
+
+    /// Stop waiting on the call - it fails with [`Error::Timeout`]
+    Abort,
+}
+
+/// Options for handling heartbeats (see [`crate::RuntimeOptions::heartbeat`])
+pub struct HeartbeatOptions {
+    /// Callback invoked for every heartbeat a script sends
+    pub on_heartbeat: Box<dyn Fn(&Heartbeat) -> HeartbeatAction>,
+}
+
+impl HeartbeatOptions {
+    /// Create a new set of heartbeat options
+    pub fn new(on_heartbeat: impl Fn(&Heartbeat) -> HeartbeatAction + 'static) -> Self {
+        Self {
+            on_heartbeat: Box::new(on_heartbeat),
+        }
+    }
+
+    /// Extends the deadline by `grace` on every heartbeat
+    ///
+    /// With the runtime's timeout set to the same duration, calls may run for as long as they
+    /// keep sending heartbeats, but fail once they go quiet for longer than `grace`
+    #[must_use]
+    pub fn keep_alive(grace: Duration) -> Self {
+        Self::new(move |_| HeartbeatAction::Extend(grace))
+    }
+}
+
+/// The deadline of the call the host is currently blocking on
+///
+/// Shared between the async bridge, which enforces it, and `op_heartbeat`, which can move it.  
+/// Also tracks how the call can be cancelled - see [`crate::cancellation`]
+#[derive(Clone, Default)]
+pub(crate) struct CallDeadline(Rc<DeadlineState>);
+
+#[derive(Default)]
+struct DeadlineState {
+    started: Cell<Option<Instant>>,
+    at: Cell<Option<Instant>>,
+    beats: Cell<u64>,
+    aborted: Cell<bool>,
+    changed: Notify,
+
+    grace: Cell<Duration>,
+    canceller: CallCanceller,
+    cancelled: RefCell<CancellationToken>,
+    signal: CallSignal,
+}
+
+impl CallDeadline {
+    /// Begin a new call, due within `timeout`
+    pub fn start(&self, timeout: Duration) {
+        let now = Instant::now();
+        self.0.started.set(Some(now));
+        self.0.at.set(now.checked_add(timeout));
+        self.0.beats.set(0);
+        self.0.aborted.set(false);
+        *self.0.cancelled.borrow_mut() = self.0.canceller.renew();
+        self.0.signal.begin();
+    }
+
+    /// End the current call
+    pub fn finish(&self) {
+        self.0.signal.finish();
+    }
+
+    /// Resolves with the error to fail the call with, once the deadline passes,
+    /// or the call is aborted or cancelled
+    pub async fn expired(&self) -> Error {
+        let cancelled = self.0.cancelled.borrow().clone();
+        tokio::select! {
+            biased;
+            () = cancelled.cancelled() => Error::Cancelled(format!(
+                "cancelled by the host after {:?}",
+                self.elapsed()
+            )),
+            error = self.passed() => error,
+        }
+    }
+
+    /// Abort the script-visible signal of the current call, and wait for the grace period
+    /// during which the script can clean up
+    ///
+    /// Always yields once first, so that `abort` listeners run even without a grace period
+    pub async fn abort_signal(&self, error: &Error) {
+        self.0.signal.abort(error.to_string());
+        tokio::task::yield_now().await;
+        tokio::time::sleep(self.0.grace.get()).await;
+    }
+
+    /// Set how long scripts are given to clean up once their call's signal is aborted
+    pub fn set_grace(&self, grace: Duration) {
+        self.0.grace.set(grace);
+    }
+
+    /// A handle to cancel the current call from another thread
+    pub fn canceller(&self) -> CallCanceller {
+        self.0.canceller.clone()
+    }
+
+    /// The script-visible signal of the current call
+    pub fn signal(&self) -> &CallSignal {
+        &self.0.signal
+    }
+
+    async fn passed(&self) -> Error {
+        loop {
+            let changed = self.0.changed.notified();
+            if self.0.aborted.get() {
+                return Error::Timeout(format!(
+                    "call aborted by the heartbeat handler after {:?}",
+                    self.elapsed()
+                ));
+            }
+
+            let Some(at) = self.0.at.get() else {
+                changed.await;
+                continue;
+            };
+
+            tokio::select! {
+                () = changed => {}
+                () = tokio::time::sleep_until(at) => {
+                    if self.0.at.get() == Some(at) && !self.0.aborted.get() {
+                        return Error::Timeout(format!("deadline elapsed after {:?}", self.elapsed()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Record a heartbeat from the script, and apply the host's response to it
+    ///
+    /// Returns an error if the call was aborted
+    pub fn beat(
+        &self,
+        options: &HeartbeatOptions,
+        progress: Option<f64>,
+        message: Option<String>,
+    ) -> Result<(), Error> {
+        let count = self.0.beats.get() + 1;
+        self.0.beats.set(count);
+
+        let heartbeat = Heartbeat {
+            elapsed: self.elapsed(),
+            remaining: self
+                .0
+                .at
+                .get()
+                .map(|at| at.saturating_duration_since(Instant::now())),
+            count,
+            progress: progress.map(|p| p.clamp(0.0, 100.0)),
+            message,
+        };
+
+        match (options.on_heartbeat)(&heartbeat) {
+            HeartbeatAction::Continue => {}
+            HeartbeatAction::Extend(grace) => {
+                let extended = Instant::now().checked_add(grace);
+                if let (Some(at), Some(extended)) = (self.0.at.get(), extended) {
+                    self.0.at.set(Some(at.max(extended)));
+                } else {
+                    self.0.at.set(None);
+                }
+                self.0.changed.notify_waiters();
+            }
+            HeartbeatAction::Abort => {
+                self.0.aborted.set(true);
+                self.0.changed.notify_waiters();
+                return Err(Error::Timeout(
+                    "call aborted by the heartbeat handler".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.0
+            .started
+            .get()
+            .map(|started| started.elapsed())
+            .unwrap_or_default()
+    }
+}
+
+/// Heartbeat handling for a runtime, stored in its op state
+pub(crate) struct HeartbeatMonitor {
+    pub options: Option<HeartbeatOptions>,
+    pub deadline: CallDeadline,
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_heartbeat() {
+        let module = Module::new(
+            "test.js",
+            "
+            const sleep = (ms) => new Promise(r => setTimeout(r, ms));
+            export async function slow(steps) {
+                for (let i = 1; i <= steps; i++) {
+                    await sleep(40);
+                    rustyscript.progress(100 * i / steps, `step ${i}`);
+                }
+                return steps;
+            }
+            export async function hung() {
+                rustyscript.heartbeat();
+                await sleep(10000);
+            }
+            ",
+        );
+
+        let beats = Rc::new(RefCell::new(Vec::new()));
+        let beats_ref = beats.clone();
+        let keep_alive = HeartbeatOptions::keep_alive(Duration::from_millis(100));
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(100),
+            heartbeat: Some(HeartbeatOptions::new(move |beat| {
+                beats_ref.borrow_mut().push(beat.clone());
+                (keep_alive.on_heartbeat)(beat)
+            })),
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        // Runs for well past the timeout, but stays alive
+        let steps: u32 = runtime
+            .call_function(Some(&handle), "slow", json_args!(5))
+            .unwrap();
+        assert_eq!(steps, 5);
+        {
+            let beats = beats.borrow();
+            assert_eq!(beats.len(), 5);
+            assert_eq!(beats[4].count, 5);
+            assert_eq!(beats[4].progress, Some(100.0));
+            assert_eq!(beats[4].message.as_deref(), Some("step 5"));
+            assert!(beats[4].elapsed >= Duration::from_millis(200));
+        }
+
+        // Goes quiet, and times out
+        let error = runtime
+            .call_function::<()>(Some(&handle), "hung", json_args!())
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert_eq!(beats.borrow().last().unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_heartbeat_abort() {
+        let module = Module::new(
+            "test.js",
+            "
+            export async function cancelled() {
+                await new Promise(r => setTimeout(r, 10));
+                try { rustyscript.progress(50); } catch {}
+                await new Promise(r => setTimeout(r, 10000));
+            }
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            heartbeat: Some(HeartbeatOptions::new(|beat| {
+                if beat.progress.is_some() {
+                    HeartbeatAction::Abort
+                } else {
+                    HeartbeatAction::Continue
+                }
+            })),
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let error = runtime
+            .call_function::<()>(Some(&handle), "cancelled", json_args!())
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(message) if message.contains("aborted")));
+    }
+}
@@ -1,27 +1,38 @@
 use std::{
+    cell::Cell,
     collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     pin::Pin,
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     task::Poll,
     time::Duration,
 };
 
 use deno_core::{
-    futures::FutureExt, serde_json, serde_v8::from_v8, v8, JsRuntime, JsRuntimeForSnapshot,
-    PollEventLoopOptions,
+    futures::FutureExt, serde_json, v8, JsRuntime, JsRuntimeForSnapshot, PollEventLoopOptions,
 };
 use deno_features::FeatureChecker;
 use serde::de::DeserializeOwned;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    ext,
+    activity::{self, ActivityTracker, PendingOpInfo, ResourceInfo},
+    ext::{self, cli::CliState},
+    js_value::{decode, from_v8},
+    large_integers::{LargeIntegers, Precise},
+    module_budget::BudgetGuard,
+    module_handle::CallPermit,
     module_loader::{LoaderOptions, RustyLoader},
+    startup_trace::PhaseTimer,
     traits::{ToDefinedValue, ToModuleSpecifier, ToV8String},
     transpiler::transpile,
-    utilities, Error, ExtensionOptions, Module, ModuleHandle,
+    utilities,
+    watchdog::Watchdog,
+    Error, ExtensionOptions, Module, ModuleHandle,
 };
 
 /// Wrapper trait to make the `InnerRuntime` generic over the runtime types
@@ -82,14 +93,46 @@ impl<F> RsAsyncFunction for F where
 {
 }
 
+/// Represents a blocking function that can be registered with the runtime
+///
+/// It is run on a separate thread, so it must be `Send + Sync`
+pub trait RsBlockingFunction:
+    Fn(&[serde_json::Value]) -> Result<serde_json::Value, Error> + Send + Sync + 'static
+{
+}
+impl<F> RsBlockingFunction for F where
+    F: Fn(&[serde_json::Value]) -> Result<serde_json::Value, Error> + Send + Sync + 'static
+{
+}
+
+/// A callback supplying extra `import.meta` fields for a module - see [`RuntimeOptions::import_meta_provider`]
+pub type ImportMetaFn = dyn Fn(&deno_core::ModuleSpecifier) -> HashMap<String, serde_json::Value>;
+
+/// Gets the `AbortSignal` of the call being made, using the function bound by the extension
+///
+/// Not read from `globalThis.rustyscript`, which scripts can replace
+fn current_call_signal<'a, 'i>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    source: &v8::Global<v8::Function>,
+) -> Result<v8::Local<'a, v8::Value>, Error> {
+    let undefined = v8::undefined(scope).into();
+    v8::Local::new(scope, source)
+        .call(scope, undefined, &[])
+        .ok_or_else(|| Error::Runtime("Could not read the call signal".to_string()))
+}
+
 /// Decodes a set of arguments into a vector of v8 values
 /// This is used to pass arguments to a javascript function
 /// And is faster and more flexible than using `json_args!`
 fn decode_args<'a, 'i>(
     args: &impl serde::ser::Serialize,
+    large_integers: LargeIntegers,
     scope: &mut v8::PinScope<'a, 'i>,
 ) -> Result<Vec<v8::Local<'a, v8::Value>>, Error> {
-    let args = deno_core::serde_v8::to_v8(scope, args)?;
+    let args = match large_integers {
+        LargeIntegers::Number => crate::js_value::to_v8(scope, args)?,
+        mode => crate::js_value::to_v8(scope, Precise::new(args, mode))?,
+    };
     match v8::Local::<v8::Array>::try_from(args) {
         Ok(args) => {
             let len = args.length();
@@ -115,6 +158,62 @@ fn decode_args<'a, 'i>(
     }
 }
 
+/// Preserves the value a script threw in the error it caused - see [`Error::thrown_value`]
+///
+/// Plain `Error` objects, and values that cannot be represented as JSON, are left out.  
+/// Only script exceptions can carry the value, so an [`Error::Runtime`] is rebuilt as one
+fn attach_thrown<'a>(
+    scope: &mut v8::PinScope<'a, '_>,
+    exception: v8::Local<'a, v8::Value>,
+    error: Error,
+) -> Error {
+    if !matches!(error, Error::Runtime(_) | Error::JsError(_)) {
+        return error;
+    }
+    let Ok(mut value) = from_v8::<serde_json::Value>(scope, exception) else {
+        return error;
+    };
+
+    if exception.is_native_error() {
+        let (serde_json::Value::Object(fields), Ok(object)) =
+            (&mut value, v8::Local::<v8::Object>::try_from(exception))
+        else {
+            return error;
+        };
+        if fields.is_empty() {
+            return error;
+        }
+
+        // Not enumerable, so missed by the conversion
+        for key in ["name", "message"] {
+            let field = key
+                .to_v8_string(scope)
+                .ok()
+                .and_then(|k| object.get(scope, k.into()))
+                .filter(|v| v.is_string());
+            if let Some(field) = field {
+                let field = field.to_rust_string_lossy(scope);
+                fields.entry(key).or_insert(field.into());
+            }
+        }
+    }
+
+    let error = match error {
+        Error::Runtime(_) => deno_core::error::JsError::from_v8_exception(scope, exception).into(),
+        e => e,
+    };
+    error.with_thrown(value)
+}
+
+/// Executes a warm-up script as a classic script, reporting any failure as [`Error::Warmup`]
+fn run_warmup_script(rt: &mut JsRuntime, script: &Module) -> Result<(), Error> {
+    let name = script.filename().to_string_lossy().to_string();
+    match rt.execute_script(name.clone(), script.contents().to_string()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Warmup(name, Box::new(e.into()))),
+    }
+}
+
 /// Represents the set of options accepted by the runtime constructor
 pub struct RuntimeOptions {
     /// A set of `deno_core` extensions to add to the runtime
@@ -123,9 +222,25 @@ pub struct RuntimeOptions {
     /// Additional options for the built-in extensions
     pub extension_options: ext::ExtensionOptions,
 
+    /// The preset these options were created from, if any - see [`crate::Profile`]
+    ///
+    /// Runtime creation fails if the crate was compiled without a feature the profile needs
+    pub profile: Option<crate::Profile>,
+
     /// Function to use as entrypoint if the module does not provide one
     pub default_entrypoint: Option<String>,
 
+    /// How integers beyond `Number.MAX_SAFE_INTEGER` are passed to functions
+    ///
+    /// By default they are rounded to the nearest double - see [`crate::LargeIntegers`]
+    pub large_integers: LargeIntegers,
+
+    /// Whether a missing value or entrypoint is an error, or read as `undefined`
+    ///
+    /// With [`MissingValues::Undefined`], reading an `Option<T>` from a value that does not exist
+    /// gives `Ok(None)` - see [`MissingValues`]
+    pub missing_values: MissingValues,
+
     /// Amount of time to run for before killing the thread
     pub timeout: Duration,
 
@@ -137,6 +252,57 @@ pub struct RuntimeOptions {
     /// (~5mb with default features)
     pub max_heap_size: Option<usize>,
 
+    /// Optional stack size limit, in bytes, for scripts run by the runtime
+    ///
+    /// Exceeding it raises a catchable `RangeError`, surfaced as [`Error::StackOverflow`] if uncaught.  
+    /// The limit is measured from the point the runtime is created, so calls made from deeper in the
+    /// host's stack get slightly less. On Linux and macOS, it is clamped to leave a margin at the end
+    /// of the thread's actual stack - elsewhere, it must stay below the size of that stack
+    pub stack_size: Option<usize>,
+
+    /// Limits on the WebAssembly scripts may compile, or the memories and tables they may create
+    ///
+    /// Unlimited by default. Modules are checked before V8 compiles them - see [`crate::WasmLimits`]
+    ///
+    /// Requires the `wasm` feature to be enabled
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "wasm")))]
+    pub wasm_limits: crate::WasmLimits,
+
+    /// Limits on the number of timers scripts may have pending, and on their delays
+    ///
+    /// Unlimited by default. Lets pooled runtimes refuse or shorten timers that would keep them alive - see [`crate::TimerLimits`]
+    pub timer_limits: crate::TimerLimits,
+
+    /// Token buckets shared with scripts, through `rustyscript.rateLimit`
+    ///
+    /// Also throttles fetches, network connections and calls to registered functions - see [`crate::RateLimiter`]
+    pub rate_limiter: Option<crate::RateLimiter>,
+
+    /// Optional policy for retrying transient failures of `fetch` and of async registered functions
+    ///
+    /// See [`crate::RetryPolicy`] for what is retried, and when
+    pub retry_policy: Option<crate::RetryPolicy>,
+
+    /// Optional breaker that short-circuits `fetch` and `Deno.connect` to hosts that keep failing
+    ///
+    /// Clones share their state, so the host can observe it - see [`crate::CircuitBreaker`]
+    pub circuit_breaker: Option<crate::CircuitBreaker>,
+
+    /// Optional cookie store for `fetch` - without one, cookies are never sent or kept
+    ///
+    /// Clones share their cookies, so the host can seed or clear them - see [`crate::CookieJar`]
+    pub cookie_jar: Option<crate::CookieJar>,
+
+    /// If true, the values exported by each module are frozen once it has loaded
+    ///
+    /// Freezing is deep - objects reachable through the exports' properties, including the prototypes
+    /// of exported classes, are frozen too - so neither later scripts nor the module itself can patch
+    /// an export that the host has captured a handle to. Built-ins are left alone.
+    ///
+    /// Bindings declared with `export let` can still be reassigned by the module's own code
+    pub freeze_exports: bool,
+
     /// Optional cache provider for the module loader
     #[allow(deprecated)]
     pub module_cache: Option<Box<dyn crate::module_loader::ModuleCacheProvider>>,
@@ -144,6 +310,46 @@ pub struct RuntimeOptions {
     /// Optional import provider for the module loader
     pub import_provider: Option<Box<dyn crate::module_loader::ImportProvider>>,
 
+    /// Rules rewriting import specifiers before they are resolved
+    ///
+    /// Used to inject virtual modules, alias bare specifiers, or pin remote imports to a local mirror
+    pub specifier_rewrites: crate::module_loader::SpecifierRewrites,
+
+    /// Built-in modules supplied by the host, as `(specifier, source)` pairs
+    ///
+    /// Lets plugin authors write `import api from "host:api"` for an ES module wrapping the host's
+    /// registered functions, instead of reaching for `rustyscript.functions`
+    pub virtual_modules: Vec<(String, String)>,
+
+    /// Compile-time constants, substituted into every module before it is transpiled
+    ///
+    /// Keys are global identifiers or dotted paths, such as `__DEBUG__` or `process.env.NODE_ENV`,
+    /// and values are the JS expressions replacing them, such as `false` or `"production"`
+    pub defines: HashMap<String, String>,
+
+    /// Minify modules after they are transpiled, removing whitespace and comments
+    ///
+    /// Reduces the size of snapshots and module caches holding large vendored bundles. Minified
+    /// modules have no source map, so errors point into the minified code. Can be overridden for
+    /// modules loaded from rust - see [`crate::Module::with_minify`]
+    ///
+    /// Default: false
+    pub minify: bool,
+
+    /// Caps on the number and size of the modules the runtime will load
+    ///
+    /// Protects long-lived or pooled runtimes from being bloated by pathological dynamic-import chains
+    pub module_limits: crate::ModuleLimits,
+
+    /// Maximum number of module sources fetched at once while loading an import graph
+    ///
+    /// The imports of a module are fetched concurrently - from the filesystem, over the network,
+    /// or through [`crate::module_loader::ImportProvider::import_async`]. Caps how many are in
+    /// flight at a time, to spare remote servers and file descriptors on very large graphs
+    ///
+    /// Default: None - no cap
+    pub module_fetch_concurrency: Option<usize>,
+
     /// Optional snapshot to load into the runtime
     ///
     /// This will reduce load times, but requires the same extensions to be loaded as when the snapshot was created  
@@ -151,6 +357,21 @@ pub struct RuntimeOptions {
     /// WARNING: Snapshots MUST be used on the same system they were created on
     pub startup_snapshot: Option<&'static [u8]>,
 
+    /// Scripts executed, in order, as soon as the runtime's context is created
+    ///
+    /// Useful for installing polyfills, or calling hot functions once so they are compiled before
+    /// the first real invocation. They run as classic scripts, before `freeze_intrinsics` is
+    /// applied, so they may still patch the built-ins. A failing script is reported as
+    /// [`Error::Warmup`]
+    ///
+    /// When building a snapshot, use [`crate::SnapshotBuilder::with_warmup_script`] instead, so the
+    /// results are captured in the snapshot rather than recomputed for every runtime
+    pub warmup_scripts: Vec<Module>,
+
+    /// If true, the time spent creating the runtime and loading each module is recorded, and
+    /// broken down by phase - see [`crate::Runtime::startup_trace`]
+    pub trace_startup: bool,
+
     /// Optional configuration parameters for building the underlying v8 isolate
     ///
     /// This can be used to alter the behavior of the runtime.
@@ -167,6 +388,118 @@ pub struct RuntimeOptions {
     ///
     /// By default only `http`/`https` (`url_import` crate feature), and `file` (`fs_import` crate feature) are allowed
     pub schema_whlist: HashSet<String>,
+
+    /// Labels identifying this runtime, such as a tenant or deployment id
+    ///
+    /// They are attached to the exceptions its scripts throw (see [`Error::labels`])
+    /// and to any telemetry it produces
+    pub labels: HashMap<String, String>,
+
+    /// Optional callback invoked when the event loop fails while being driven in the background
+    ///
+    /// This covers errors from timers and unawaited promises that surface outside of an
+    /// active host call - i.e. during [`crate::Runtime::await_event_loop`], [`crate::Runtime::advance_event_loop`]
+    /// and similar. The error is still returned to the caller driving the event loop.
+    pub on_background_error: Option<Box<dyn Fn(&Error)>>,
+
+    /// Optional callback supplying extra `import.meta` fields, such as `env` or `tenant`, for each module
+    ///
+    /// Called with the module's specifier when the module is loaded, and embedded in that module alone. The fields are assigned
+    /// over the built-in ones, so a provider can also replace `import.meta.main` and the like.
+    /// Lets per-module metadata reach scripts without going through globals
+    pub import_meta_provider: Option<Box<ImportMetaFn>>,
+
+    /// Optional callback invoked when a script registers a listener on the global scope,
+    /// such as `addEventListener('fetch', ...)`
+    ///
+    /// Fires once per module and event type, so hosts can route work only to scripts that declared it.  
+    /// See also [`crate::Runtime::module_event_listeners`]
+    ///
+    /// Requires the `events` feature to be enabled
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    pub on_event_listener: Option<Box<dyn Fn(&crate::EventListenerInfo)>>,
+
+    /// Optional callback that sees every message sent or received by a script's `WebSocket`
+    ///
+    /// Called synchronously as each message is sent, and before the script's own listeners receive it,
+    /// so it can be used to audit traffic. Messages are copied out of the sandbox only when this is set.  
+    /// See also [`crate::Runtime::websockets`]
+    ///
+    /// Requires the `websocket` feature to be enabled
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub on_websocket_frame: Option<Box<dyn Fn(&crate::WebSocketFrame)>>,
+
+    /// Optional watchdog that detects when the event loop stops making progress
+    ///
+    /// If the set of pending ops, resources and timers stays unchanged for longer than
+    /// the configured stall timeout while the event loop is being driven, a diagnostic
+    /// callback is invoked with a dump of what the runtime is waiting on
+    pub watchdog: Option<crate::WatchdogOptions>,
+
+    /// Optional handler for heartbeats sent by scripts with `rustyscript.heartbeat` and `rustyscript.progress`
+    ///
+    /// The handler can extend the deadline of the call that sent the heartbeat, or abort it  
+    /// See [`crate::HeartbeatOptions`]
+    pub heartbeat: Option<crate::HeartbeatOptions>,
+
+    /// How long a script is given to clean up once the `AbortSignal` of its call is aborted,
+    /// before the call fails
+    ///
+    /// The signal is read with `rustyscript.currentCall.signal`, and is aborted when the call times
+    /// out or is cancelled - see [`crate::CallCanceller`]. Defaults to zero, which still lets
+    /// `abort` listeners run
+    pub cancel_grace: Duration,
+
+    /// Pass the `AbortSignal` of the current call as an extra, last argument to every function called
+    ///
+    /// The same signal as `rustyscript.currentCall.signal` - see [`RuntimeOptions::cancel_grace`]
+    pub call_signal_argument: bool,
+
+    /// Deep-freeze the built-in constructors and prototypes (`Object`, `Array`, `Function`, etc)
+    /// once the runtime has started
+    ///
+    /// Prevents prototype pollution from leaking between successive invocations sharing a context.  
+    /// Note that assigning to a property that shadows a frozen built-in (such as `obj.toString = ...`)
+    /// will then throw in strict mode code - use `Object.defineProperty` instead
+    pub freeze_intrinsics: bool,
+
+    /// The default locale (a BCP 47 tag, such as `fr-CA`) used by `Intl` and the `toLocale*` methods
+    /// when a script does not specify one
+    ///
+    /// By default, the host's locale is used
+    pub default_locale: Option<String>,
+
+    /// The timezone used for local time by `Date` and `Intl`, independently of the host's `TZ`
+    ///
+    /// Unknown zones cause runtime creation to fail. Requires the `intl` feature
+    pub timezone: Option<Tz>,
+
+    /// Command-line arguments for the script, surfaced as `Deno.args`
+    ///
+    /// Unlike a real process, the list does not include the program or script name
+    pub argv: Vec<String>,
+
+    /// Give the runtime its own scratch directory for `Deno.makeTempFile` and `Deno.makeTempDir`
+    ///
+    /// The directory is created with the runtime, and deleted along with its contents when the runtime is dropped.  
+    /// The `dir` option of those calls is then interpreted relative to it
+    ///
+    /// Requires the `fs` feature to be enabled
+    #[cfg(feature = "fs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+    pub temp_dir: Option<crate::TempDirOptions>,
+
+    /// Optional OpenTelemetry instrumentation for the runtime
+    ///
+    /// When set, host calls, module loads, op latencies, fetch requests and console errors
+    /// are reported through the global `opentelemetry` tracer and meter providers
+    ///
+    /// Requires the `otel` feature to be enabled
+    #[cfg(feature = "otel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otel")))]
+    pub otel: Option<crate::OtelOptions>,
 }
 
 impl Default for RuntimeOptions {
@@ -174,20 +507,185 @@ impl Default for RuntimeOptions {
         Self {
             extensions: Vec::default(),
             default_entrypoint: None,
+            missing_values: MissingValues::default(),
+            large_integers: LargeIntegers::default(),
             timeout: Duration::MAX,
             max_heap_size: None,
+            stack_size: None,
+
+            #[cfg(feature = "wasm")]
+            wasm_limits: crate::WasmLimits::default(),
+
+            timer_limits: crate::TimerLimits::default(),
+            rate_limiter: None,
+            retry_policy: None,
+            circuit_breaker: None,
+            cookie_jar: None,
+            freeze_exports: false,
             module_cache: None,
             import_provider: None,
+            specifier_rewrites: crate::module_loader::SpecifierRewrites::default(),
+            virtual_modules: Vec::new(),
+            defines: HashMap::new(),
+            minify: false,
+            module_limits: crate::ModuleLimits::default(),
+            module_fetch_concurrency: None,
             startup_snapshot: None,
+            warmup_scripts: Vec::new(),
+            trace_startup: false,
             isolate_params: None,
             shared_array_buffer_store: None,
             schema_whlist: HashSet::default(),
+            labels: HashMap::default(),
+            on_background_error: None,
+            import_meta_provider: None,
+
+            #[cfg(feature = "events")]
+            on_event_listener: None,
+
+            #[cfg(feature = "websocket")]
+            on_websocket_frame: None,
+            watchdog: None,
+            heartbeat: None,
+            cancel_grace: Duration::ZERO,
+            call_signal_argument: false,
+            freeze_intrinsics: false,
+            default_locale: None,
+            timezone: None,
+            argv: Vec::default(),
+
+            #[cfg(feature = "fs")]
+            temp_dir: None,
+
+            #[cfg(feature = "otel")]
+            otel: None,
 
             extension_options: ExtensionOptions::default(),
+            profile: None,
         }
     }
 }
 
+/// A unique identifier for a runtime instance within the process
+///
+/// Ids are never reused, so values from a dropped runtime can be told apart from
+/// those of its replacement
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct RuntimeId(u64);
+impl RuntimeId {
+    fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for RuntimeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How a runtime treats values that do not exist - see [`RuntimeOptions::missing_values`]
+///
+/// `undefined` always decodes to `None` for an `Option<T>`; this decides whether a missing value
+/// is read as `undefined` too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MissingValues {
+    /// Missing values are errors - [`Error::ValueNotFound`], or [`Error::MissingEntrypoint`]
+    #[default]
+    Error,
+
+    /// Missing values, and missing entrypoints, are read as `undefined`
+    ///
+    /// Targets that cannot be decoded from `undefined`, such as `String`, still get the error
+    Undefined,
+}
+
+/// The kind of garbage collection to request with [`crate::Runtime::request_gc`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GcKind {
+    /// A full collection of the heap
+    ///
+    /// Maps to a critical memory pressure notification on the isolate
+    Full,
+
+    /// A full collection which also compacts the heap and releases cached data (compiled code, etc)
+    ///
+    /// Slower, but returns as much memory as possible - best suited to isolates about to go idle
+    Compact,
+}
+
+/// An IANA timezone name, such as `America/Toronto` or `Europe/Paris`
+///
+/// See [`RuntimeOptions::timezone`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tz(std::borrow::Cow<'static, str>);
+impl Tz {
+    /// Coordinated Universal Time
+    pub const UTC: Tz = Tz(std::borrow::Cow::Borrowed("UTC"));
+
+    /// Create a timezone from its IANA name
+    ///
+    /// The name is validated when a runtime using it is created
+    pub fn new(name: impl Into<std::borrow::Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    /// Returns the IANA name of the timezone
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Tz {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// How much memory pressure the host is under - see [`crate::Runtime::notify_memory_pressure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryPressure {
+    /// Memory pressure has been relieved
+    None,
+
+    /// V8 should favour memory usage over performance, and collect more eagerly
+    Moderate,
+
+    /// V8 should free as much memory as possible, immediately
+    Critical,
+}
+
+impl From<MemoryPressure> for v8::MemoryPressureLevel {
+    fn from(value: MemoryPressure) -> Self {
+        match value {
+            MemoryPressure::None => v8::MemoryPressureLevel::None,
+            MemoryPressure::Moderate => v8::MemoryPressureLevel::Moderate,
+            MemoryPressure::Critical => v8::MemoryPressureLevel::Critical,
+        }
+    }
+}
+
+/// Stack kept free below [`RuntimeOptions::stack_size`]'s limit, for V8 to raise the `RangeError`
+/// and for the host frames that call into scripts
+const STACK_MARGIN: usize = 64 * 1024;
+
+/// The most functions remembered per module by the warm call path, before it starts over
+const MAX_WARM_FUNCTIONS: usize = 64;
+
+/// A function previously called by name, and where it was found
+pub struct WarmFunction {
+    /// The module namespace or global object the function was found on, and its name there
+    holder: v8::Global<v8::Object>,
+    key: v8::Global<v8::String>,
+
+    function: v8::Global<v8::Function>,
+    namespace: Option<v8::Global<v8::Object>>,
+}
+
 /// Deno `JsRuntime` wrapper providing helper functions needed
 /// by the public-facing Runtime API
 ///
@@ -195,38 +693,110 @@ impl Default for RuntimeOptions {
 /// It provides a set of async functions that can be used to interact with the
 /// underlying deno runtime instance
 pub struct InnerRuntime<RT: RuntimeTrait> {
+    pub id: RuntimeId,
     pub module_loader: Rc<RustyLoader>,
     pub deno_runtime: RT,
 
     pub cwd: PathBuf,
     pub default_entrypoint: Option<String>,
+    pub missing_values: MissingValues,
+    pub large_integers: LargeIntegers,
+    pub labels: Rc<HashMap<String, String>>,
+    pub declarations: Vec<(String, String)>,
+    pub on_background_error: Option<Box<dyn Fn(&Error)>>,
+    pub watchdog: Option<Watchdog>,
+    pub activity: ActivityTracker,
+    pub exit_code: Rc<Cell<Option<i32>>>,
+
+    /// Whether the call interrupted by `Deno.exit` has already failed with [`Error::Exit`]
+    pub exit_reported: Cell<bool>,
+
+    pub startup_trace: Option<crate::StartupTrace>,
+
+    /// Functions called by name so far, by module - see [`InnerRuntime::call_function_warm`]
+    pub warm_functions: HashMap<Option<deno_core::ModuleId>, HashMap<String, WarmFunction>>,
+
+    /// Whether functions are called with the signal of the current call as their last argument
+    pub call_signal_argument: bool,
+
+    /// Specifiers of the modules loaded so far, in order - their code is kept by the module loader
+    #[cfg(feature = "snapshot_builder")]
+    pub loaded_modules: Vec<deno_core::ModuleSpecifier>,
+
+    #[cfg(feature = "fs")]
+    pub temp_dir: Option<Rc<ext::fs::TempSandbox>>,
+
+    #[cfg(feature = "otel")]
+    pub telemetry: Option<Rc<crate::telemetry::Telemetry>>,
 }
 impl<RT: RuntimeTrait> InnerRuntime<RT> {
     pub fn new(
-        options: RuntimeOptions,
+        mut options: RuntimeOptions,
         heap_exhausted_token: CancellationToken,
     ) -> Result<Self, Error> {
+        let mut timer = PhaseTimer::new(options.trace_startup);
+        if let Some(profile) = options.profile {
+            profile.check_features()?;
+        }
+
         let cwd = std::env::current_dir()?;
         let module_loader = Rc::new(RustyLoader::new(LoaderOptions {
             cache_provider: options.module_cache,
             import_provider: options.import_provider,
             schema_whlist: options.schema_whlist,
             cwd: cwd.clone(),
+            module_limits: options.module_limits,
+            fetch_concurrency: options.module_fetch_concurrency,
+            defines: options.defines,
+            minify: options.minify,
+            import_meta: options.import_meta_provider,
+            rewrites: options.virtual_modules.into_iter().fold(
+                options.specifier_rewrites,
+                |rewrites, (specifier, source)| rewrites.virtual_module(specifier, source),
+            ),
 
             #[cfg(feature = "node_experimental")]
             node_resolver: options.extension_options.node_resolver.clone(),
 
+            #[cfg(feature = "web")]
+            blob_store: Some(options.extension_options.web.blob_store.clone()),
+
             ..Default::default()
         }));
 
+        // Fetches and connections draw from the rate limiter as they pass their permission checks
+        #[cfg(feature = "web")]
+        if let Some(limiter) = &options.rate_limiter {
+            let permissions = options.extension_options.web.permissions.clone();
+            options.extension_options.web.permissions =
+                ext::web::RateLimitedPermissions::wrap(permissions, limiter.clone());
+        }
+
+        // Only timed if this runtime is the one to initialize the platform
+        let extension_setup = timer.lap();
+        let initialized = crate::utilities::init_default_platform();
+        let platform_init = timer.lap();
+        let platform_init = if initialized {
+            platform_init
+        } else {
+            Duration::ZERO
+        };
+
         // If a snapshot is provided, do not reload ESM for extensions
-        let is_snapshot = options.startup_snapshot.is_some();
+        let startup_snapshot = options
+            .startup_snapshot
+            .or_else(crate::utilities::shared_startup_snapshot);
+        let is_snapshot = startup_snapshot.is_some();
         let extensions = ext::all_extensions(
             options.extensions,
             options.extension_options,
             options.shared_array_buffer_store.clone(),
             is_snapshot,
         );
+        let build_info = crate::build_info::with_extensions(
+            extensions.iter().map(|e| e.name.to_string()).collect(),
+            is_snapshot,
+        );
 
         // If a heap size is provided, set the isolate params (preserving any user-provided params otherwise)
         let isolate_params = match options.isolate_params {
@@ -247,18 +817,29 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
             }
         };
 
+        #[cfg(feature = "otel")]
+        let telemetry = options
+            .otel
+            .map(|otel| Rc::new(crate::telemetry::Telemetry::new(otel, &options.labels)));
+
+        let extension_setup = extension_setup + timer.lap();
         let mut deno_runtime = RT::try_new(deno_core::RuntimeOptions {
             module_loader: Some(module_loader.clone()),
 
+            #[cfg(feature = "otel")]
+            op_metrics_factory_fn: telemetry.as_ref().and_then(|t| t.op_metrics_factory()),
+
             extension_transpiler: Some(module_loader.as_extension_transpiler()),
+            import_meta_resolve_callback: Some(module_loader.as_import_meta_resolver()),
             create_params: isolate_params,
             shared_array_buffer_store: options.shared_array_buffer_store.clone(),
 
-            startup_snapshot: options.startup_snapshot,
+            startup_snapshot,
             extensions,
 
             ..Default::default()
         })?;
+        let isolate_init = timer.lap();
 
         let mut feature_checker = FeatureChecker::default();
         feature_checker.set_exit_cb(Box::new(|_, _| {}));
@@ -268,6 +849,108 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
             .borrow_mut()
             .put(Arc::new(feature_checker));
 
+        // Backs `Deno.args` and `Deno.exit`
+        let exit_code = Rc::new(Cell::new(None));
+        let cli = CliState {
+            argv: options.argv,
+            exit_code: exit_code.clone(),
+            isolate: deno_runtime.rt_mut().v8_isolate().thread_safe_handle(),
+        };
+        deno_runtime.rt_mut().op_state().borrow_mut().put(cli);
+        deno_runtime
+            .rt_mut()
+            .op_state()
+            .borrow_mut()
+            .put(build_info);
+
+        // Tracks `addEventListener` calls on the global scope
+        #[cfg(feature = "events")]
+        {
+            let events = ext::events::EventListenerRegistry {
+                hook: options.on_event_listener,
+                ..Default::default()
+            };
+            deno_runtime.rt_mut().op_state().borrow_mut().put(events);
+        }
+
+        // Called with each message a script's `WebSocket` sends or receives
+        #[cfg(feature = "websocket")]
+        if let Some(tap) = options.on_websocket_frame {
+            deno_runtime
+                .rt_mut()
+                .op_state()
+                .borrow_mut()
+                .borrow_mut::<ext::websocket::WebSocketRegistry>()
+                .tap = Some(tap);
+        }
+
+        // Checked by the `WebAssembly` wrappers before compiling or allocating
+        #[cfg(feature = "wasm")]
+        if !options.wasm_limits.is_unlimited() {
+            deno_runtime
+                .rt_mut()
+                .op_state()
+                .borrow_mut()
+                .put(options.wasm_limits);
+        }
+
+        // Checked by `setTimeout` and `setInterval` before scheduling
+        if !options.timer_limits.is_unlimited() {
+            deno_runtime
+                .rt_mut()
+                .op_state()
+                .borrow_mut()
+                .put(options.timer_limits);
+        }
+
+        // Built-ins must be recorded before any user code runs
+        if options.freeze_exports {
+            let freezer = crate::export_freezer::ExportFreezer::new(deno_runtime.rt_mut());
+            deno_runtime.rt_mut().op_state().borrow_mut().put(freezer);
+        }
+
+        // Drawn from by `rustyscript.rateLimit` and registered function calls
+        if let Some(limiter) = options.rate_limiter {
+            deno_runtime.rt_mut().op_state().borrow_mut().put(limiter);
+        }
+
+        // Read by registered async functions, and by `fetch`
+        if let Some(policy) = options.retry_policy {
+            deno_runtime.rt_mut().op_state().borrow_mut().put(policy);
+        }
+
+        // Consulted and updated around each `fetch` and `Deno.connect`
+        if let Some(breaker) = options.circuit_breaker {
+            deno_runtime.rt_mut().op_state().borrow_mut().put(breaker);
+        }
+
+        // Read from and written to by each `fetch`
+        if let Some(jar) = options.cookie_jar {
+            deno_runtime.rt_mut().op_state().borrow_mut().put(jar);
+        }
+
+        #[cfg(feature = "otel")]
+        if let Some(telemetry) = &telemetry {
+            deno_runtime
+                .rt_mut()
+                .op_state()
+                .borrow_mut()
+                .put(telemetry.clone());
+        }
+
+        // V8 takes the limit as the lowest address the stack may grow down to
+        // A limit past the end of the thread's real stack would crash the process instead of throwing
+        if let Some(stack_size) = options.stack_size {
+            let marker = 0u8;
+            let stack_top = std::ptr::addr_of!(marker) as usize;
+            let floor = crate::utilities::thread_stack_bottom()
+                .map_or(0, |bottom| bottom.saturating_add(STACK_MARGIN));
+            deno_runtime
+                .rt_mut()
+                .v8_isolate()
+                .set_stack_limit(stack_top.saturating_sub(stack_size).max(floor));
+        }
+
         // Add a callback to terminate the runtime if the max_heap_size limit is approached
         if options.max_heap_size.is_some() {
             let isolate_handle = deno_runtime.rt_mut().v8_isolate().thread_safe_handle();
@@ -286,12 +969,94 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
                 });
         }
 
+        if options.default_locale.is_some() || options.timezone.is_some() {
+            let mut defaults = serde_json::Map::new();
+            if let Some(locale) = options.default_locale {
+                defaults.insert("locale".to_string(), locale.into());
+            }
+            if let Some(timezone) = options.timezone {
+                defaults.insert("timeZone".to_string(), timezone.name().into());
+            }
+
+            let defaults = serde_json::Value::Object(defaults);
+            deno_runtime.rt_mut().execute_script(
+                "ext:rustyscript/intl_defaults.js",
+                format!(
+                    "{}({defaults});",
+                    include_str!("ext/rustyscript/intl_defaults.js").trim_end()
+                ),
+            )?;
+        }
+
+        for script in &options.warmup_scripts {
+            run_warmup_script(deno_runtime.rt_mut(), script)?;
+        }
+
+        // Must come last, so the steps above can still patch the built-ins
+        if options.freeze_intrinsics {
+            deno_runtime.rt_mut().execute_script(
+                "ext:rustyscript/freeze_intrinsics.js",
+                include_str!("ext/rustyscript/freeze_intrinsics.js"),
+            )?;
+        }
+
+        // Tag the isolate, so values decoded from it can be traced back to this runtime
+        let id = RuntimeId::next();
+        deno_runtime.rt_mut().v8_isolate().set_slot(id);
+
+        #[cfg(feature = "fs")]
+        let temp_dir = match options.temp_dir {
+            Some(temp_dir) => {
+                let sandbox = ext::fs::TempSandbox::new(temp_dir, id)?;
+                deno_runtime
+                    .rt_mut()
+                    .op_state()
+                    .borrow_mut()
+                    .put(sandbox.clone());
+                Some(sandbox)
+            }
+            None => None,
+        };
+
         let default_entrypoint = options.default_entrypoint;
+        let labels = Rc::new(options.labels);
+        let on_background_error = options.on_background_error;
+        let watchdog = options.watchdog.map(Watchdog::new);
+        let startup_trace = options.trace_startup.then(|| crate::StartupTrace {
+            platform_init,
+            extension_setup,
+            isolate_init,
+            from_snapshot: is_snapshot,
+            runtime_setup: timer.lap(),
+            modules: Vec::new(),
+        });
         Ok(Self {
+            id,
             module_loader,
             deno_runtime,
             cwd,
             default_entrypoint,
+            missing_values: options.missing_values,
+            large_integers: options.large_integers,
+            labels,
+            declarations: Vec::new(),
+            on_background_error,
+            watchdog,
+            activity: ActivityTracker::default(),
+            exit_code,
+            exit_reported: Cell::new(false),
+            startup_trace,
+            warm_functions: HashMap::new(),
+            call_signal_argument: options.call_signal_argument,
+
+            #[cfg(feature = "snapshot_builder")]
+            loaded_modules: Vec::new(),
+
+            #[cfg(feature = "fs")]
+            temp_dir,
+
+            #[cfg(feature = "otel")]
+            telemetry,
         })
     }
 
@@ -306,6 +1071,11 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         self.deno_runtime.rt_mut()
     }
 
+    /// Executes a warm-up script, reporting any failure as [`Error::Warmup`]
+    pub fn run_warmup_script(&mut self, script: &Module) -> Result<(), Error> {
+        run_warmup_script(self.deno_runtime(), script)
+    }
+
     /// Set the current working directory for the runtime
     /// This is used to resolve relative paths in the module loader
     pub fn set_current_dir(&mut self, path: impl AsRef<Path>) -> Result<&Path, Error> {
@@ -319,6 +1089,10 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         Ok(&self.cwd)
     }
 
+    pub fn module_usage(&self) -> crate::ModuleUsage {
+        self.module_loader.module_usage()
+    }
+
     pub fn current_dir(&self) -> &Path {
         &self.cwd
     }
@@ -361,18 +1135,90 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         let state = self.deno_runtime().op_state();
         let mut state = state.try_borrow_mut()?;
 
-        if !state.has::<HashMap<String, Box<dyn RsAsyncFunction>>>() {
-            state.put(HashMap::<String, Box<dyn RsAsyncFunction>>::new());
+        if !state.has::<HashMap<String, Rc<dyn RsAsyncFunction>>>() {
+            state.put(HashMap::<String, Rc<dyn RsAsyncFunction>>::new());
         }
 
         // Insert the callback into the state
         state
-            .borrow_mut::<HashMap<String, Box<dyn RsAsyncFunction>>>()
-            .insert(name.to_string(), Box::new(callback));
+            .borrow_mut::<HashMap<String, Rc<dyn RsAsyncFunction>>>()
+            .insert(name.to_string(), Rc::new(callback));
 
         Ok(())
     }
 
+    /// Register a blocking rust function
+    /// The function is run on tokio's blocking thread pool, and exposed to JS as an async function
+    pub fn register_blocking_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsBlockingFunction,
+    {
+        let callback = std::sync::Arc::new(callback);
+        let name_ = name.to_string();
+        self.register_async_function(name, move |args: Vec<serde_json::Value>| {
+            let callback = callback.clone();
+            let name = name_.clone();
+            Box::pin(async move {
+                tokio::task::spawn_blocking(move || callback(&args))
+                    .await
+                    .map_err(|e| {
+                        Error::Runtime(format!("Blocking function `{name}` failed: {e}"))
+                    })?
+            })
+        })
+    }
+
+    /// Register an action that scripts can invoke through `rustyscript.actions.invoke`
+    /// Replaces any action already registered under that name
+    pub fn register_action(&mut self, name: &str, action: crate::HostAction) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<ext::actions::ActionRegistry>() {
+            state.put(ext::actions::ActionRegistry::default());
+        }
+
+        state
+            .borrow_mut::<ext::actions::ActionRegistry>()
+            .insert(name, action);
+
+        Ok(())
+    }
+
+    /// Register a source of JSON values, read by scripts through `rustyscript.json.read`
+    #[cfg(feature = "json")]
+    pub fn register_json_source(
+        &mut self,
+        name: &str,
+        reader: impl std::io::Read + 'static,
+    ) -> Result<(), Error> {
+        self.json_streams(|streams| streams.add_source(name, Box::new(reader)))
+    }
+
+    /// Register a sink for JSON values, written by scripts through `rustyscript.json.write`
+    #[cfg(feature = "json")]
+    pub fn register_json_sink(
+        &mut self,
+        name: &str,
+        writer: impl std::io::Write + 'static,
+        format: crate::JsonFormat,
+    ) -> Result<(), Error> {
+        self.json_streams(|streams| streams.add_sink(name, Box::new(writer), format))
+    }
+
+    #[cfg(feature = "json")]
+    fn json_streams(&mut self, f: impl FnOnce(&mut ext::json::JsonStreams)) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<ext::json::JsonStreams>() {
+            state.put(ext::json::JsonStreams::default());
+        }
+
+        f(state.borrow_mut::<ext::json::JsonStreams>());
+        Ok(())
+    }
+
     /// Register a rust function
     /// The function must return a `serde_json::Value`
     /// and accept a slice of `serde_json::Value` as arguments
@@ -401,14 +1247,39 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         options: PollEventLoopOptions,
         timeout: Option<Duration>,
     ) -> Result<(), Error> {
-        if let Some(timeout) = timeout {
-            Ok(tokio::select! {
-                r = self.deno_runtime().run_event_loop(options) => r,
+        let result = if let Some(timeout) = timeout {
+            tokio::select! {
+                r = self.run_event_loop(options) => r,
                 () = tokio::time::sleep(timeout) => Ok(()),
-            }?)
+            }
         } else {
-            Ok(self.deno_runtime().run_event_loop(options).await?)
+            self.run_event_loop(options).await
+        };
+
+        self.report_background_error(result.map_err(Into::into))
+    }
+
+    /// Runs the JS event loop to completion, sampling the watchdog if one is configured
+    async fn run_event_loop(
+        &mut self,
+        options: PollEventLoopOptions,
+    ) -> Result<(), deno_core::error::CoreError> {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.reset();
         }
+
+        std::future::poll_fn(|cx| {
+            let runtime = self.deno_runtime.rt_mut();
+            if let Poll::Ready(result) = runtime.poll_event_loop(cx, options) {
+                return Poll::Ready(result);
+            }
+
+            if let Some(watchdog) = &mut self.watchdog {
+                watchdog.poll(cx, runtime, &mut self.activity);
+            }
+            Poll::Pending
+        })
+        .await
     }
 
     /// Advances the JS event loop by one tick
@@ -423,9 +1294,65 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
                 Poll::Pending => Ok(true),
             })
         })
-        .await?;
+        .await;
 
-        Ok(result)
+        self.report_background_error(result.map_err(Into::into))
+    }
+
+    /// Runs queued promise reactions until none remain, without polling ops or timers
+    pub fn drain_microtasks(&mut self) {
+        let rt = self.deno_runtime();
+        deno_core::scope!(scope, rt);
+        scope.perform_microtask_checkpoint();
+    }
+
+    /// Returns the ops, resources and timers the event loop is currently waiting on
+    pub fn pending_ops(&mut self) -> Vec<PendingOpInfo> {
+        self.activity.capture(self.deno_runtime.rt_mut())
+    }
+
+    /// Ask V8 to run a garbage collection of the given kind
+    pub fn request_gc(&mut self, kind: GcKind) {
+        let isolate = self.deno_runtime().v8_isolate();
+        match kind {
+            GcKind::Full => isolate.memory_pressure_notification(v8::MemoryPressureLevel::Critical),
+            GcKind::Compact => isolate.low_memory_notification(),
+        }
+    }
+
+    /// Inform V8 of the memory pressure the host is under
+    pub fn notify_memory_pressure(&mut self, level: MemoryPressure) {
+        self.deno_runtime()
+            .v8_isolate()
+            .memory_pressure_notification(level.into());
+    }
+
+    /// Returns the open entries in the runtime's resource table
+    pub fn resources(&mut self) -> Result<Vec<ResourceInfo>, Error> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        Ok(activity::resources(&state.resource_table))
+    }
+
+    /// Forcibly close a resource by its id
+    pub fn close_resource(&mut self, rid: deno_core::ResourceId) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+        let resource = state
+            .resource_table
+            .take_any(rid)
+            .map_err(|e| Error::Runtime(e.to_string()))?;
+        resource.close();
+        Ok(())
+    }
+
+    /// Passes an error surfaced by the event loop to the background error handler, if any
+    fn report_background_error<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        if let (Err(e), Some(handler)) = (&result, &self.on_background_error) {
+            handler(e);
+        }
+
+        result
     }
 
     /// Evaluate a piece of non-ECMAScript-module JavaScript code
@@ -502,12 +1429,26 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         &mut self,
         value: v8::Global<v8::Value>,
     ) -> Result<v8::Global<v8::Value>, Error> {
-        let future = self.deno_runtime().resolve(value);
+        let future = self.deno_runtime().resolve(value.clone());
         let result = self
             .deno_runtime()
             .with_event_loop_future(future, PollEventLoopOptions::default())
-            .await?;
-        Ok(result)
+            .await;
+        result.map_err(|e| self.attach_rejection(&value, e.into()))
+    }
+
+    /// Preserves the reason a promise was rejected with in the error it caused
+    fn attach_rejection(&mut self, promise: &v8::Global<v8::Value>, error: Error) -> Error {
+        let rt = self.deno_runtime();
+        deno_core::scope!(scope, rt);
+        let local = v8::Local::new(scope, promise);
+        match v8::Local::<v8::Promise>::try_from(local) {
+            Ok(promise) if promise.state() == v8::PromiseState::Rejected => {
+                let reason = promise.result(scope);
+                attach_thrown(scope, reason, error)
+            }
+            _ => error,
+        }
     }
 
     pub fn decode_value<T>(&mut self, value: v8::Global<v8::Value>) -> Result<T, Error>
@@ -517,7 +1458,27 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         let rt = self.deno_runtime();
         deno_core::scope!(scope, rt);
         let result = v8::Local::<v8::Value>::new(scope, value);
-        Ok(from_v8(scope, result)?)
+        decode(scope, result)
+    }
+
+    /// Decodes `undefined` in place of a value that does not exist, if [`MissingValues`] allows it
+    ///
+    /// Returns the original error otherwise, or if `T` cannot be decoded from `undefined`
+    pub fn decode_missing<T>(&mut self, error: Error) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        if self.missing_values == MissingValues::Error {
+            return Err(error);
+        }
+
+        let undefined = {
+            let rt = self.deno_runtime();
+            deno_core::scope!(scope, rt);
+            let undefined: v8::Local<v8::Value> = v8::undefined(scope).into();
+            v8::Global::new(scope, undefined)
+        };
+        self.decode_value(undefined).map_err(|_| error)
     }
 
     pub fn get_value_ref(
@@ -584,6 +1545,109 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
             None
         };
 
+        self.call_function_in(module_context, module_namespace, function, args)
+    }
+
+    /// Calls a function by name, as [`Self::get_function_by_name`] and [`Self::call_function_by_ref`]
+    /// would, but reusing the lookups made by earlier calls to the same function
+    ///
+    /// Each call still reads the name once, so a function that was reassigned is seen
+    pub fn call_function_warm(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let (function, module_namespace) = self.get_function_warm(module_context, name)?;
+        self.call_function_in(module_context, module_namespace, &function, args)
+    }
+
+    /// Resolves a function by name, along with the namespace it is called on, from the warm cache
+    /// if the name still refers to the function cached for it
+    fn get_function_warm(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+    ) -> Result<(v8::Global<v8::Function>, Option<v8::Global<v8::Object>>), Error> {
+        let module_id = module_context.map(ModuleHandle::id);
+        if let Some(warm) = self
+            .warm_functions
+            .get(&module_id)
+            .and_then(|f| f.get(name))
+        {
+            let rt = self.deno_runtime.rt_mut();
+            deno_core::scope!(scope, rt);
+            let holder = v8::Local::new(scope, &warm.holder);
+            let key = v8::Local::new(scope, &warm.key);
+            let function = v8::Local::new(scope, &warm.function);
+
+            // The module namespace always comes first, as in `get_function_by_name` - an export
+            // defined since the last call hides a global of the same name
+            let current = warm
+                .namespace
+                .as_ref()
+                .map(|namespace| v8::Local::new(scope, namespace))
+                .and_then(|namespace| namespace.get(scope, key.into()).if_defined())
+                .or_else(|| holder.get(scope, key.into()));
+            if current.is_some_and(|value| value.strict_equals(function.into())) {
+                return Ok((warm.function.clone(), warm.namespace.clone()));
+            }
+        }
+
+        // Cold path - look the function up as usual, and remember where it was found
+        let function = self.get_function_by_name(module_context, name)?;
+        let module_namespace = match module_context {
+            Some(module_context) => Some(
+                self.deno_runtime()
+                    .get_module_namespace(module_context.id())?,
+            ),
+            None => None,
+        };
+
+        let context = self.deno_runtime().main_context();
+        let rt = self.deno_runtime();
+        deno_core::scope!(scope, rt);
+        let key = name.to_v8_string(scope)?;
+        let namespace = module_namespace
+            .as_ref()
+            .map(|namespace| v8::Local::new(scope, namespace))
+            .filter(|namespace| namespace.get(scope, key.into()).if_defined().is_some());
+        let holder = match namespace {
+            Some(namespace) => namespace,
+            None => context.open(scope).global(scope),
+        };
+        let warm = WarmFunction {
+            holder: v8::Global::new(scope, holder),
+            key: v8::Global::new(scope, key),
+            function: function.clone(),
+            namespace: module_namespace.clone(),
+        };
+
+        let functions = self.warm_functions.entry(module_id).or_default();
+        if functions.len() >= MAX_WARM_FUNCTIONS {
+            functions.clear();
+        }
+        functions.insert(name.to_string(), warm);
+
+        Ok((function, module_namespace))
+    }
+
+    /// Calls a function with the given module namespace as `this`, or `undefined` if none
+    fn call_function_in(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        module_namespace: Option<v8::Global<v8::Object>>,
+        function: &v8::Global<v8::Function>,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let large_integers = self.large_integers;
+        let signal_source = if self.call_signal_argument {
+            let state = self.deno_runtime().op_state();
+            let state = state.try_borrow()?;
+            Some(ext::rustyscript::CallSignalSource::get(&state)?)
+        } else {
+            None
+        };
         let rt = self.deno_runtime();
         deno_core::scope!(scope, rt);
         v8::tc_scope!(let tc_scope, scope);
@@ -602,7 +1666,10 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         let function_instance = function.open(tc_scope);
 
         // Prep arguments
-        let args = decode_args(args, tc_scope)?;
+        let mut args = decode_args(args, large_integers, tc_scope)?;
+        if let Some(source) = &signal_source {
+            args.push(current_call_signal(tc_scope, source)?);
+        }
 
         // Call the function
         let result = function_instance.call(tc_scope, namespace, &args);
@@ -630,8 +1697,11 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
 
                 let msg = e.get(tc_scope).to_rust_string_lossy(tc_scope);
 
-                let s = format!("{filename}{msg}");
-                Err(Error::Runtime(s))
+                let error = Error::Runtime(format!("{filename}{msg}"));
+                match tc_scope.exception() {
+                    Some(exception) => Err(attach_thrown(tc_scope, exception, error)),
+                    None => Err(error),
+                }
             }
             None => Err(Error::Runtime(
                 "Unknown error during function execution".to_string(),
@@ -654,6 +1724,10 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         deno_core::error::AnyError: From<E>,
         Error: std::convert::From<E>,
     {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.reset();
+        }
+
         // Manually implement tokio::select
         std::future::poll_fn(|cx| {
             let evt_status = self.deno_runtime().poll_event_loop(cx, poll_options);
@@ -667,6 +1741,9 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
 
                 (_, Poll::Pending) => {
                     // Continue polling
+                    if let Some(watchdog) = &mut self.watchdog {
+                        watchdog.poll(cx, self.deno_runtime.rt_mut(), &mut self.activity);
+                    }
                     Poll::Pending
                 }
 
@@ -687,6 +1764,38 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         .await
     }
 
+    /// Admit a call through a module handle against its call quota, if it has one
+    pub fn admit_call(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+    ) -> Result<Option<CallPermit>, Error> {
+        match module_context {
+            Some(module_context) => module_context.admit_call(self.deno_runtime(), name),
+            None => Ok(None),
+        }
+    }
+
+    /// End a call admitted by [`InnerRuntime::admit_call`], given the value it returned
+    pub fn finish_call(&mut self, permit: Option<CallPermit>, value: &v8::Global<v8::Value>) {
+        if let Some(permit) = permit {
+            permit.finish(self.deno_runtime(), value);
+        }
+    }
+
+    /// Start a telemetry span for a host call into the runtime
+    #[cfg(feature = "otel")]
+    pub fn call_span(
+        &self,
+        kind: &'static str,
+        name: &str,
+        module_context: Option<&ModuleHandle>,
+    ) -> Option<crate::telemetry::CallSpan> {
+        self.telemetry
+            .as_ref()
+            .map(|t| t.call_span(kind, name, module_context))
+    }
+
     /// Get the entrypoint function for a module
     pub fn get_module_entrypoint(
         &mut self,
@@ -734,6 +1843,27 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
         &mut self,
         main_module: Option<&Module>,
         side_modules: Vec<&Module>,
+    ) -> Result<ModuleHandle, Error> {
+        #[cfg(feature = "otel")]
+        let span = self.telemetry.as_ref().and_then(|t| {
+            let module = main_module.or(side_modules.last().copied())?;
+            Some(t.module_span(&module.filename().to_string_lossy()))
+        });
+
+        let result = self.load_modules_inner(main_module, side_modules).await;
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span {
+            span.finish(&result);
+        }
+
+        result
+    }
+
+    async fn load_modules_inner(
+        &mut self,
+        main_module: Option<&Module>,
+        side_modules: Vec<&Module>,
     ) -> Result<ModuleHandle, Error> {
         if main_module.is_none() && side_modules.is_empty() {
             return Err(Error::Runtime(
@@ -745,66 +1875,25 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
 
         // Get additional modules first
         for side_module in side_modules {
-            let module_specifier = side_module.filename().to_module_specifier(&self.cwd)?;
-            let (code, sourcemap) = transpile(&module_specifier, side_module.contents())?;
-
-            // Now CJS translation, for node
-            #[cfg(feature = "node_experimental")]
-            let code = self
-                .module_loader
-                .translate_cjs(&module_specifier, &code)
-                .await?;
-
-            let fast_code = deno_core::FastString::from(code.clone());
-
-            let s_modid = self
-                .deno_runtime()
-                .load_side_es_module_from_code(&module_specifier, fast_code)
-                .await?;
-
-            // Update source map cache
-            self.module_loader.insert_source_map(
-                module_specifier.as_str(),
-                code,
-                sourcemap.map(|s| s.to_vec()),
-            );
-
-            let mod_load = self.deno_runtime().mod_evaluate(s_modid);
-            self.with_event_loop_future(mod_load, PollEventLoopOptions::default())
-                .await?;
+            let mut budget = BudgetGuard::start(self.deno_runtime(), side_module);
+            let result = self.load_module_code(side_module, false, &mut budget).await;
+            let s_modid = budget.finish(self.deno_runtime(), result)?;
+            self.freeze_exports(s_modid)?;
+            #[cfg(feature = "snapshot_builder")]
+            self.loaded_modules
+                .push(side_module.filename().to_module_specifier(&self.cwd)?);
             module_handle_stub = ModuleHandle::new(side_module, s_modid, None);
         }
 
         // Load main module
         if let Some(module) = main_module {
-            let module_specifier = module.filename().to_module_specifier(&self.cwd)?;
-            let (code, sourcemap) = transpile(&module_specifier, module.contents())?;
-
-            // Now CJS translation, for node
-            #[cfg(feature = "node_experimental")]
-            let code = self
-                .module_loader
-                .translate_cjs(&module_specifier, &code)
-                .await?;
-
-            let fast_code = deno_core::FastString::from(code.clone());
-
-            let module_id = self
-                .deno_runtime()
-                .load_main_es_module_from_code(&module_specifier, fast_code)
-                .await?;
-
-            // Update source map cache
-            self.module_loader.insert_source_map(
-                module_specifier.as_str(),
-                code,
-                sourcemap.map(|s| s.to_vec()),
-            );
-
-            // Finish execution
-            let mod_load = self.deno_runtime().mod_evaluate(module_id);
-            self.with_event_loop_future(mod_load, PollEventLoopOptions::default())
-                .await?;
+            let mut budget = BudgetGuard::start(self.deno_runtime(), module);
+            let result = self.load_module_code(module, true, &mut budget).await;
+            let module_id = budget.finish(self.deno_runtime(), result)?;
+            self.freeze_exports(module_id)?;
+            #[cfg(feature = "snapshot_builder")]
+            self.loaded_modules
+                .push(module.filename().to_module_specifier(&self.cwd)?);
             module_handle_stub = ModuleHandle::new(module, module_id, None);
         }
 
@@ -817,6 +1906,113 @@ impl<RT: RuntimeTrait> InnerRuntime<RT> {
             entrypoint,
         ))
     }
+
+    /// Deep-freeze a module's exports, if the runtime was created with `freeze_exports`
+    fn freeze_exports(&mut self, module_id: deno_core::ModuleId) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let Some(freezer) = state
+            .try_borrow_mut()?
+            .try_take::<crate::export_freezer::ExportFreezer>()
+        else {
+            return Ok(());
+        };
+
+        let result = freezer.freeze(self.deno_runtime(), module_id);
+        state.try_borrow_mut()?.put(freezer);
+        result
+    }
+
+    /// Transpile, load and evaluate a single module, within its budget
+    async fn load_module_code(
+        &mut self,
+        module: &Module,
+        is_main: bool,
+        budget: &mut BudgetGuard,
+    ) -> Result<deno_core::ModuleId, Error> {
+        let mut timer = PhaseTimer::new(self.startup_trace.is_some());
+        let module_specifier = module.filename().to_module_specifier(&self.cwd)?;
+        let code = self
+            .module_loader
+            .apply_defines(&module_specifier, module.contents())?;
+        let minify = module
+            .minify()
+            .unwrap_or_else(|| self.module_loader.minifies());
+        let (code, sourcemap) = {
+            let specifier = module_specifier.clone();
+            crate::transpiler::off_thread(code.len(), move || -> Result<_, Error> {
+                let (code, sourcemap) = transpile(&specifier, &code)?;
+                if minify {
+                    Ok((crate::transpiler::minify(&specifier, &code)?, None))
+                } else {
+                    Ok((code, sourcemap))
+                }
+            })
+            .await??
+        };
+        let transpile = timer.lap();
+
+        // Now CJS translation, for node
+        #[cfg(feature = "node_experimental")]
+        let code = self
+            .module_loader
+            .translate_cjs(&module_specifier, &code)
+            .await?;
+        let code = self.module_loader.with_import_meta(&module_specifier, code);
+
+        self.module_loader
+            .record_module(module_specifier.as_str(), code.len())?;
+        let fast_code = deno_core::FastString::from(code.clone());
+
+        let module_id = if is_main {
+            budget
+                .limit(async {
+                    Ok(self
+                        .deno_runtime()
+                        .load_main_es_module_from_code(&module_specifier, fast_code)
+                        .await?)
+                })
+                .await
+        } else {
+            budget
+                .limit(async {
+                    Ok(self
+                        .deno_runtime()
+                        .load_side_es_module_from_code(&module_specifier, fast_code)
+                        .await?)
+                })
+                .await
+        };
+
+        // Static imports over the module limits surface as generic load errors
+        let module_id =
+            module_id.map_err(|e| self.module_loader.take_limit_error().unwrap_or(e))?;
+        let load = timer.lap();
+
+        // Update source map cache
+        self.module_loader.insert_source_map(
+            module_specifier.as_str(),
+            code,
+            sourcemap.map(|s| s.to_vec()),
+        );
+
+        // Finish execution
+        budget.begin_eval(self.deno_runtime());
+        let mod_load = self.deno_runtime().mod_evaluate(module_id);
+        budget
+            .limit(self.with_event_loop_future(mod_load, PollEventLoopOptions::default()))
+            .await?;
+
+        if let Some(trace) = &mut self.startup_trace {
+            trace.modules.push(crate::ModuleLoadTrace {
+                specifier: module_specifier.to_string(),
+                transpile,
+                load,
+                evaluate: timer.lap(),
+            });
+        }
+
+        Ok(module_id)
+    }
 }
 
 #[cfg(test)]
@@ -865,28 +2061,33 @@ mod test_inner_runtime {
         deno_core::scope!(scope, &mut runtime.deno_runtime);
 
         // empty
-        let args = decode_args(&json_args!(), scope).expect("Could not decode args");
+        let args = decode_args(&json_args!(), LargeIntegers::Number, scope)
+            .expect("Could not decode args");
         assert_eq!(args.len(), 0);
 
         // single
-        let args = decode_args(&json_args!(2), scope).expect("Could not decode args");
+        let args = decode_args(&json_args!(2), LargeIntegers::Number, scope)
+            .expect("Could not decode args");
         assert_eq!(args.len(), 1);
 
         // single raw
-        let args = decode_args(&2, scope).expect("Could not decode args");
+        let args = decode_args(&2, LargeIntegers::Number, scope).expect("Could not decode args");
         assert_eq!(args.len(), 1);
 
         // multiple heterogeneous
-        let args = decode_args(&json_args!(2, "test"), scope).expect("Could not decode args");
+        let args = decode_args(&json_args!(2, "test"), LargeIntegers::Number, scope)
+            .expect("Could not decode args");
         assert_eq!(args.len(), 2);
 
         // multiple homogeneous
-        let args = decode_args(&json_args!(2, 3), scope).expect("Could not decode args");
+        let args = decode_args(&json_args!(2, 3), LargeIntegers::Number, scope)
+            .expect("Could not decode args");
         assert_eq!(args.len(), 2);
 
         // 16 args
         let args = decode_args(
             &(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15),
+            LargeIntegers::Number,
             scope,
         )
         .expect("Could not decode args");
@@ -898,6 +2099,7 @@ mod test_inner_runtime {
                 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
                 10, 11, 12, 13, 14, 15
             ),
+            LargeIntegers::Number,
             scope,
         )
         .expect("Could not decode args");
@@ -943,6 +2145,50 @@ mod test_inner_runtime {
         assert_v8!(result, 5, usize, runtime);
     }
 
+    #[test]
+    fn test_register_blocking_function() {
+        let mut runtime =
+            InnerRuntime::<JsRuntime>::new(RuntimeOptions::default(), CancellationToken::new())
+                .expect("Could not load runtime");
+        let main_thread = std::thread::current().id();
+        runtime
+            .register_blocking_function(
+                "test",
+                sync_callback!(|a: i64, b: i64| {
+                    assert_ne!(std::thread::current().id(), main_thread);
+                    std::thread::sleep(Duration::from_millis(50));
+                    Ok::<i64, Error>(a + b)
+                }),
+            )
+            .expect("Could not register function");
+
+        // Calls return a promise, rather than blocking the script
+        let module = Module::new(
+            "test.js",
+            "
+            let settled = false;
+            const calls = Promise.all([
+                rustyscript.async_functions.test(2, 3),
+                rustyscript.async_functions.test(4, 5),
+            ]).finally(() => settled = true);
+            globalThis.pending = !settled;
+            globalThis.v = await calls;
+            ",
+        );
+
+        let rt = &mut runtime;
+        let module = run_async_task(|| async move { rt.load_modules(Some(&module), vec![]).await });
+
+        let result = runtime
+            .get_value_ref(Some(&module), "v")
+            .expect("Could not find global");
+        assert_v8!(result, vec![5, 9], Vec<usize>, runtime);
+        let pending = runtime
+            .get_value_ref(Some(&module), "pending")
+            .expect("Could not find global");
+        assert_v8!(pending, true, bool, runtime);
+    }
+
     #[test]
     fn test_register_function() {
         let mut runtime =
@@ -3,12 +3,16 @@
 //!
 //! [Function] and [Promise] are both specializations of [Value] providing deserialize-time type checking
 //! and additional utility functions for interacting with the runtime
+use std::cell::Cell;
+
 use deno_core::{
     serde_v8::GlobalValue,
     v8::{self, HandleScope},
 };
 use serde::Deserialize;
 
+use crate::RuntimeId;
+
 /// A macro to implement the common functions for [Function], [Promise], and [Value]
 macro_rules! impl_v8 {
     ($name:ident$(<$generic:ident>)?, $checker:ident $(,)?) => {
@@ -47,7 +51,43 @@ macro_rules! impl_v8 {
                 v8::Local<'a, v8::Value>: From<v8::Local<'a, H>>,
             {
                 let local: v8::Local<v8::Value> = v8::Local::new(scope, value).into();
-                v8::Global::new(scope, local).try_into()
+                let mut value: Self = v8::Global::new(scope, local).try_into()?;
+                value.0 .2 = $crate::js_value::scope_runtime_id(scope);
+                Ok(value)
+            }
+
+            /// Returns the id of the runtime this value was created in, if known
+            ///
+            /// Values decoded by a runtime are always tagged; those constructed
+            /// directly from a `v8::Global` are not, and cannot be checked
+            #[must_use]
+            pub fn runtime_id(&self) -> Option<$crate::RuntimeId> {
+                self.0 .2
+            }
+
+            /// Creates a weak reference to this value, which will not prevent it from being garbage collected
+            ///
+            /// # Errors
+            /// Will return an error if the value belongs to a different runtime
+            pub fn downgrade(
+                &self,
+                runtime: &mut $crate::Runtime,
+            ) -> Result<$crate::js_value::Weak<Self>, $crate::Error> {
+                $crate::js_value::Weak::new(runtime, &self.0 .0, self.0 .2, None)
+            }
+
+            /// Creates a weak reference to this value, calling `finalizer` once it has been garbage collected
+            ///
+            /// The finalizer is cancelled if the weak reference is dropped first
+            ///
+            /// # Errors
+            /// Will return an error if the value belongs to a different runtime
+            pub fn downgrade_with_finalizer(
+                &self,
+                runtime: &mut $crate::Runtime,
+                finalizer: impl FnOnce() + 'static,
+            ) -> Result<$crate::js_value::Weak<Self>, $crate::Error> {
+                $crate::js_value::Weak::new(runtime, &self.0 .0, self.0 .2, Some(Box::new(finalizer)))
             }
 
             /// Creates a new instance of this struct from a global value
@@ -60,10 +100,29 @@ macro_rules! impl_v8 {
             /// It is recommended to use [`Self::try_from_v8`] instead
             #[must_use]
             pub unsafe fn from_v8_unchecked(value: v8::Global<v8::Value>) -> Self {
-                let inner = V8Value::<$checker>(value, std::marker::PhantomData);
+                let inner = V8Value::<$checker>(value, std::marker::PhantomData, None);
                 Self(inner $(, std::marker::PhantomData::<$generic>)?)
             }
         }
+        impl $(<$generic>)? $crate::js_value::Weak<$name $(<$generic>)?> $(where $generic: serde::de::DeserializeOwned)? {
+            /// Returns a strong handle to the value, or `None` if it has been garbage collected
+            ///
+            /// # Errors
+            /// Will return an error if the value belongs to a different runtime
+            pub fn upgrade(
+                &self,
+                runtime: &mut $crate::Runtime,
+            ) -> Result<Option<$name $(<$generic>)?>, $crate::Error> {
+                let Some(global) = self.upgrade_global(runtime)? else {
+                    return Ok(None);
+                };
+
+                let mut value: $name $(<$generic>)? = global.try_into()?;
+                value.0 .2 = Some(runtime.id());
+                Ok(Some(value))
+            }
+        }
+
         impl<'de$(, $generic)?> serde::Deserialize<'de> for $name $(<$generic>)?
         $(where $generic: serde::de::DeserializeOwned,)?
         {
@@ -76,6 +135,23 @@ macro_rules! impl_v8 {
             }
         }
 
+        /// Allows the value to be passed back into its runtime, such as in function arguments
+        ///
+        /// Only meaningful to the runtime's own serializer - other formats will receive an opaque handle.
+        /// Passing the value to a different runtime fails with [`crate::Error::WrongRuntime`]
+        impl $(<$generic>)? serde::Serialize for $name $(<$generic>)? $(where $generic: serde::de::DeserializeOwned)? {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                $crate::js_value::check_encoding_runtime(self.0 .2).map_err(serde::ser::Error::custom)?;
+                let value = deno_core::serde_v8::GlobalValue {
+                    v8_value: self.0 .0.clone(),
+                };
+                serde::Serialize::serialize(&value, serializer)
+            }
+        }
+
         #[allow(clippy::from_over_into)]
         impl $(<$generic>)? Into<v8::Global<v8::Value>> for $name $(<$generic>)? $(where $generic: serde::de::DeserializeOwned)? {
             fn into(self) -> v8::Global<v8::Value> {
@@ -87,7 +163,7 @@ macro_rules! impl_v8 {
             type Error = crate::Error;
             fn try_from(value: v8::Global<v8::Value>) -> Result<Self, Self::Error> {
                 <$checker as $crate::js_value::V8TypeChecker>::validate(value.clone())?;
-                let inner = V8Value::<$checker>(value, std::marker::PhantomData);
+                let inner = V8Value::<$checker>(value, std::marker::PhantomData, None);
                 Ok(Self(inner $(, std::marker::PhantomData::<$generic>)?))
             }
         }
@@ -153,13 +229,25 @@ impl_checker!(DefaultTypeChecker, Value);
 ///
 /// A Deserializable javascript object, that can be stored and used later
 /// Must live as long as the runtime it was birthed from
+///
+/// Tagged with the id of the runtime that decoded it, where known, so that use
+/// against a different runtime is reported as [`crate::Error::WrongRuntime`]
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub(crate) struct V8Value<V8TypeChecker>(
     v8::Global<v8::Value>,
     std::marker::PhantomData<V8TypeChecker>,
+    Option<RuntimeId>,
 );
 
 impl<T: V8TypeChecker> V8Value<T> {
+    /// Ensures this value is being used with the runtime it was created in
+    pub(crate) fn check_runtime(&self, runtime: RuntimeId) -> Result<(), crate::Error> {
+        match self.2 {
+            Some(owner) if owner != runtime => Err(crate::Error::WrongRuntime(owner, runtime)),
+            _ => Ok(()),
+        }
+    }
+
     /// Returns the underlying global as a local in the type configured by the type checker
     pub(crate) fn as_local<'a, 'i>(
         &self,
@@ -194,10 +282,83 @@ impl<'de, T: V8TypeChecker> serde::Deserialize<'de> for V8Value<T> {
     {
         let value = GlobalValue::deserialize(deserializer)?;
         T::validate(value.v8_value.clone()).map_err(serde::de::Error::custom)?;
-        Ok(Self(value.v8_value, std::marker::PhantomData))
+        let runtime = DECODING_RUNTIME.with(Cell::get);
+        Ok(Self(value.v8_value, std::marker::PhantomData, runtime))
+    }
+}
+
+thread_local! {
+    /// The runtime currently decoding values on this thread, used to tag any `V8Value`s produced
+    static DECODING_RUNTIME: Cell<Option<RuntimeId>> = const { Cell::new(None) };
+
+    /// The runtime currently encoding values on this thread, and the owner of the first value
+    /// found to belong to a different one
+    static ENCODING_RUNTIME: Cell<Option<RuntimeId>> = const { Cell::new(None) };
+    static FOREIGN_OWNER: Cell<Option<RuntimeId>> = const { Cell::new(None) };
+}
+
+/// Returns the id of the runtime owning the given scope's isolate
+pub(crate) fn scope_runtime_id(scope: &v8::Isolate) -> Option<RuntimeId> {
+    scope.get_slot::<RuntimeId>().copied()
+}
+
+/// Deserializes a v8 value, tagging any `V8Value`s produced with the id of the owning runtime
+pub(crate) fn from_v8<'a, 'i, T>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    value: v8::Local<'a, v8::Value>,
+) -> Result<T, deno_core::serde_v8::Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let previous = DECODING_RUNTIME.with(|r| r.replace(scope_runtime_id(scope)));
+    let result = deno_core::serde_v8::from_v8(scope, value);
+    DECODING_RUNTIME.with(|r| r.set(previous));
+    result
+}
+
+/// Fails if a value owned by `owner` is being encoded by a different runtime
+pub(crate) fn check_encoding_runtime(owner: Option<RuntimeId>) -> Result<(), crate::Error> {
+    match (owner, ENCODING_RUNTIME.with(Cell::get)) {
+        (Some(owner), Some(runtime)) if owner != runtime => {
+            FOREIGN_OWNER.with(|f| f.set(Some(owner)));
+            Err(crate::Error::WrongRuntime(owner, runtime))
+        }
+        _ => Ok(()),
     }
 }
 
+/// Serializes a value into v8, failing with [`crate::Error::WrongRuntime`] if it contains
+/// a [`Value`] belonging to a different runtime
+pub(crate) fn to_v8<'a, 'i, T>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    value: T,
+) -> Result<v8::Local<'a, v8::Value>, crate::Error>
+where
+    T: serde::Serialize,
+{
+    let runtime = scope_runtime_id(scope);
+    let previous = ENCODING_RUNTIME.with(|r| r.replace(runtime));
+    let result = deno_core::serde_v8::to_v8(scope, value);
+    ENCODING_RUNTIME.with(|r| r.set(previous));
+
+    match (result, FOREIGN_OWNER.with(Cell::take), runtime) {
+        (Err(_), Some(owner), Some(runtime)) => Err(crate::Error::WrongRuntime(owner, runtime)),
+        (result, ..) => Ok(result?),
+    }
+}
+
+/// Deserializes a v8 value as [`from_v8`] does, explaining any failure with the path to the
+/// part of the value that caused it - see [`crate::Error::TypeMismatch`]
+pub(crate) fn decode<'a, 'i, T>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    value: v8::Local<'a, v8::Value>,
+) -> Result<T, crate::Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_v8(scope, value).map_err(|e| decode_path::explain::<T>(scope, value, e))
+}
+
 /// A Deserializable javascript value, that can be stored and used later
 /// Can only be used on the same runtime it was created on
 ///
@@ -217,19 +378,22 @@ impl Value {
     where
         T: serde::de::DeserializeOwned,
     {
+        self.0.check_runtime(runtime.id())?;
         let rt = runtime.deno_runtime();
         deno_core::scope!(scope, rt);
         let local = self.0.as_local(scope);
-        Ok(deno_core::serde_v8::from_v8(scope, local)?)
+        decode(scope, local)
     }
 
     /// Contructs a new Value from a `v8::Value` global
     #[must_use]
     pub fn from_v8(value: v8::Global<v8::Value>) -> Self {
-        Self(V8Value(value, std::marker::PhantomData))
+        Self(V8Value(value, std::marker::PhantomData, None))
     }
 }
 
+mod decode_path;
+
 mod function;
 pub use function::*;
 
@@ -242,6 +406,15 @@ pub use string::*;
 mod map;
 pub use map::*;
 
+mod durable;
+pub use durable::*;
+
+mod weak;
+pub use weak::*;
+
+mod typed;
+pub use typed::*;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -254,6 +427,7 @@ mod test {
             "
             export const f = 42;
             export const g = () => 42;
+            export const a = (x) => x.a;
         ",
         );
 
@@ -278,5 +452,21 @@ mod test {
             let _local = f.into_inner().as_local(scope);
             // Use the local value within the scope
         }
+
+        // Values can only be passed back to the runtime they came from
+        let value: Value = runtime.eval("({ a: 1 })").unwrap();
+        let mut other = Runtime::new(RuntimeOptions::default()).unwrap();
+        let other_handle = other
+            .load_module(&Module::new("other.js", "export const id = (x) => x;"))
+            .unwrap();
+        let e = other
+            .call_function::<Value>(Some(&other_handle), "id", &(value.clone(),))
+            .unwrap_err();
+        assert!(matches!(e, crate::Error::WrongRuntime(..)), "{e}");
+
+        let a: usize = runtime
+            .call_function(Some(&handle), "a", &(value,))
+            .unwrap();
+        assert_eq!(a, 1);
     }
 }
@@ -0,0 +1,527 @@
+//! Explains failed conversions from javascript values, by pointing at the offending part of the value
+//!
+//! `serde_v8` reports what it expected, but not where - so when a conversion fails the value is
+//! decoded again as JSON, and replayed into the target type through a deserializer that tracks
+//! the path it is visiting (`.items[3].price`)
+use std::fmt::Display;
+
+use deno_core::{serde_json, v8};
+use serde::de::{
+    self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, Expected, IntoDeserializer,
+    Unexpected, Visitor,
+};
+
+use crate::Error;
+
+/// Names of the structs `serde_v8` uses to pass raw v8 values through serde start with this
+const V8_MAGIC_PREFIX: &str = "$__v8_magic";
+
+/// Converts a failed conversion into an error pointing at the offending part of the value
+///
+/// Falls back to the original error if the value cannot be represented as JSON, if the failure
+/// cannot be reproduced against it or differs from the original, or if it is at the root of the value
+pub(crate) fn explain<'a, 'i, T>(
+    scope: &mut v8::PinScope<'a, 'i>,
+    value: v8::Local<'a, v8::Value>,
+    error: deno_core::serde_v8::Error,
+) -> Error
+where
+    T: DeserializeOwned,
+{
+    let Ok(json) = super::from_v8::<serde_json::Value>(scope, value) else {
+        return error.into();
+    };
+
+    match T::deserialize(PathDeserializer(&json)) {
+        // Failures at the root are already described as well as can be by `serde_v8`
+        Err(e) if !e.path.is_empty() && e.failure.agrees_with(&error) => e.into(),
+        _ => error.into(),
+    }
+}
+
+/// One step into a value
+#[derive(Debug)]
+enum Segment {
+    Index(usize),
+    Key(String),
+}
+
+#[derive(Debug)]
+enum Failure {
+    Mismatch {
+        expected: String,
+        found: String,
+    },
+    Other(String),
+
+    /// Reached a type only `serde_v8` can produce, such as a `js_value::Function`
+    Opaque,
+}
+
+impl Failure {
+    /// Returns true if this could be the failure `serde_v8` ran into
+    ///
+    /// JSON is not an exact copy of the value, so a replay can fail somewhere the original did not
+    fn agrees_with(&self, original: &deno_core::serde_v8::Error) -> bool {
+        use deno_core::serde_v8::Error as V8Error;
+        match (self, original) {
+            (Self::Opaque, _) => false,
+            (Self::Other(message), V8Error::Message(original)) => message == original,
+            (Self::Mismatch { .. }, V8Error::Message(original)) => {
+                original.starts_with("invalid type") || original.starts_with("invalid value")
+            }
+
+            // `serde_v8`'s own type checks
+            (Self::Mismatch { .. }, _) => true,
+            (Self::Other(_), _) => false,
+        }
+    }
+}
+
+/// A conversion failure, and the path to the part of the value that caused it
+///
+/// Segments are pushed as the error bubbles up, so are stored innermost first
+#[derive(Debug)]
+struct PathError {
+    path: Vec<Segment>,
+    failure: Failure,
+}
+
+impl PathError {
+    fn opaque() -> Self {
+        Self {
+            path: Vec::new(),
+            failure: Failure::Opaque,
+        }
+    }
+
+    fn at(mut self, segment: Segment) -> Self {
+        self.path.push(segment);
+        self
+    }
+
+    fn path(&self) -> String {
+        if self.path.is_empty() {
+            return ".".to_string();
+        }
+
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                Segment::Index(i) => path.push_str(&format!("[{i}]")),
+                Segment::Key(key) if is_identifier(key) => path.push_str(&format!(".{key}")),
+                Segment::Key(key) => path.push_str(&format!("[{key:?}]")),
+            }
+        }
+        path
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.failure {
+            Failure::Mismatch { expected, found } => {
+                write!(f, "expected {expected} at {}, found {found}", self.path())
+            }
+            Failure::Other(message) => write!(f, "{message} at {}", self.path()),
+            Failure::Opaque => write!(f, "unsupported value at {}", self.path()),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl de::Error for PathError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            path: Vec::new(),
+            failure: Failure::Other(msg.to_string()),
+        }
+    }
+
+    fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        Self {
+            path: Vec::new(),
+            failure: Failure::Mismatch {
+                expected: describe_expected(exp),
+                found: describe_unexpected(unexp),
+            },
+        }
+    }
+
+    fn invalid_value(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        Self {
+            path: Vec::new(),
+            failure: Failure::Mismatch {
+                expected: exp.to_string(),
+                found: unexp.to_string(),
+            },
+        }
+    }
+}
+
+impl From<PathError> for Error {
+    fn from(e: PathError) -> Self {
+        let path = e.path();
+        match e.failure {
+            Failure::Mismatch { expected, found } => Error::TypeMismatch(path, expected, found),
+            Failure::Other(message) => Error::JsonDecode(format!("{message} at {path}")),
+            Failure::Opaque => Error::JsonDecode(format!("unsupported value at {path}")),
+        }
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Names the expected rust type the way javascript would, where there is an equivalent
+fn describe_expected(exp: &dyn Expected) -> String {
+    let expected = exp.to_string();
+    match expected.as_str() {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" | "f32"
+        | "f64" => "number".to_string(),
+        "a string" | "a borrowed string" | "a character" => "string".to_string(),
+        "a boolean" => "boolean".to_string(),
+        "a sequence" => "array".to_string(),
+        "a map" => "object".to_string(),
+        "unit" => "null".to_string(),
+        _ => expected,
+    }
+}
+
+/// Names the type that was found the way javascript would
+fn describe_unexpected(unexp: Unexpected) -> String {
+    match unexp {
+        Unexpected::Bool(_) => "boolean".to_string(),
+        Unexpected::Unsigned(_) | Unexpected::Signed(_) | Unexpected::Float(_) => {
+            "number".to_string()
+        }
+        Unexpected::Char(_) | Unexpected::Str(_) => "string".to_string(),
+        Unexpected::Unit | Unexpected::Option => "null".to_string(),
+        Unexpected::Seq => "array".to_string(),
+        Unexpected::Map => "object".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn unexpected(value: &serde_json::Value) -> Unexpected<'_> {
+    match value {
+        serde_json::Value::Null => Unexpected::Unit,
+        serde_json::Value::Bool(b) => Unexpected::Bool(*b),
+        serde_json::Value::Number(n) => Unexpected::Float(n.as_f64().unwrap_or(f64::NAN)),
+        serde_json::Value::String(s) => Unexpected::Str(s),
+        serde_json::Value::Array(_) => Unexpected::Seq,
+        serde_json::Value::Object(_) => Unexpected::Map,
+    }
+}
+
+/// Visits a float as an integer if it has no fractional part, as `serde_v8` would
+///
+/// Javascript has no integer type, so integers above `u32::MAX` reach JSON as floats
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn visit_float<'de, V: Visitor<'de>>(n: f64, visitor: V) -> Result<V::Value, PathError> {
+    const U64_END: f64 = 18_446_744_073_709_551_616.0; // 2^64
+    const I64_START: f64 = -9_223_372_036_854_775_808.0; // -2^63
+
+    if n.fract() != 0.0 {
+        visitor.visit_f64(n)
+    } else if (0.0..U64_END).contains(&n) {
+        visitor.visit_u64(n as u64)
+    } else if (I64_START..0.0).contains(&n) {
+        visitor.visit_i64(n as i64)
+    } else {
+        visitor.visit_f64(n)
+    }
+}
+
+/// Deserializes a JSON value, recording the path to any failure
+struct PathDeserializer<'a>(&'a serde_json::Value);
+
+impl<'de> de::Deserializer<'de> for PathDeserializer<'_> {
+    type Error = PathError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            serde_json::Value::Null => visitor.visit_unit(),
+            serde_json::Value::Bool(b) => visitor.visit_bool(*b),
+            serde_json::Value::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    visitor.visit_u64(n)
+                } else if let Some(n) = n.as_i64() {
+                    visitor.visit_i64(n)
+                } else {
+                    visit_float(n.as_f64().unwrap_or(f64::NAN), visitor)
+                }
+            }
+            serde_json::Value::String(s) => visitor.visit_str(s),
+            serde_json::Value::Array(items) => visitor.visit_seq(PathSeqAccess {
+                items: items.iter().enumerate(),
+            }),
+            serde_json::Value::Object(entries) => visitor.visit_map(PathMapAccess {
+                entries: entries.iter(),
+                next: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            serde_json::Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name.starts_with(V8_MAGIC_PREFIX) {
+            return Err(PathError::opaque());
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name.starts_with(V8_MAGIC_PREFIX) {
+            return Err(PathError::opaque());
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            serde_json::Value::String(variant) => {
+                visitor.visit_enum(variant.as_str().into_deserializer())
+            }
+            serde_json::Value::Object(entries) if entries.len() == 1 => {
+                let (variant, value) = entries.iter().next().expect("entry count checked above");
+                visitor.visit_enum(PathEnumAccess { variant, value })
+            }
+            other => Err(de::Error::invalid_type(unexpected(other), &visitor)),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+struct PathSeqAccess<'a> {
+    items: std::iter::Enumerate<std::slice::Iter<'a, serde_json::Value>>,
+}
+
+impl<'de> de::SeqAccess<'de> for PathSeqAccess<'_> {
+    type Error = PathError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let Some((i, item)) = self.items.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(PathDeserializer(item))
+            .map(Some)
+            .map_err(|e| e.at(Segment::Index(i)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct PathMapAccess<'a> {
+    entries: serde_json::map::Iter<'a>,
+    next: Option<(&'a String, &'a serde_json::Value)>,
+}
+
+impl<'de> de::MapAccess<'de> for PathMapAccess<'_> {
+    type Error = PathError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.next = Some((key, value));
+        seed.deserialize(StrDeserializer::<PathError>::new(key))
+            .map(Some)
+            .map_err(|e| e.at(Segment::Key(key.clone())))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .next
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before its key"))?;
+        seed.deserialize(PathDeserializer(value))
+            .map_err(|e| e.at(Segment::Key(key.clone())))
+    }
+}
+
+struct PathEnumAccess<'a> {
+    variant: &'a String,
+    value: &'a serde_json::Value,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for PathEnumAccess<'a> {
+    type Error = PathError;
+    type Variant = PathVariantAccess<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(StrDeserializer::<PathError>::new(self.variant))?;
+        Ok((
+            variant,
+            PathVariantAccess {
+                variant: self.variant,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct PathVariantAccess<'a> {
+    variant: &'a String,
+    value: &'a serde_json::Value,
+}
+
+impl<'de> de::VariantAccess<'de> for PathVariantAccess<'_> {
+    type Error = PathError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(PathDeserializer(self.value))
+            .map_err(|e: PathError| e.at(Segment::Key(self.variant.clone())))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(PathDeserializer(self.value))
+            .map_err(|e| e.at(Segment::Key(self.variant.clone())))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(PathDeserializer(self.value), visitor)
+            .map_err(|e| e.at(Segment::Key(self.variant.clone())))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(PathDeserializer(self.value), visitor)
+            .map_err(|e| e.at(Segment::Key(self.variant.clone())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{js_value::Value, Error, Module, Runtime, RuntimeOptions};
+
+    #[derive(serde::Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Order {
+        id: String,
+        items: Vec<Item>,
+    }
+
+    #[derive(serde::Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Item {
+        name: String,
+        price: f64,
+    }
+
+    #[derive(serde::Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Ledger {
+        total: u64,
+        entries: Vec<i64>,
+        label: String,
+    }
+
+    #[test]
+    fn test_decode_path() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const wrongType = {
+                id: 'a',
+                items: [
+                    { name: 'a', price: 1 },
+                    { name: 'b', price: 2 },
+                    { name: 'c', price: 3 },
+                    { name: 'd', price: '4' },
+                ],
+            };
+            export const missingField = { id: 'a', items: [{ name: 'a' }] };
+            export const largeIntegers = { total: 5000000000, entries: [-5000000000, 1], label: 1 };
+            export const getOrder = () => wrongType;
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let value: Value = runtime.get_value(Some(&handle), "wrongType").unwrap();
+        let error = value.try_into::<Order>(&mut runtime).unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::TypeMismatch(path, expected, found)
+                if path == ".items[3].price" && expected == "number" && found == "string"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "expected number at .items[3].price, found string"
+        );
+
+        let value: Value = runtime.get_value(Some(&handle), "missingField").unwrap();
+        let error = value.try_into::<Order>(&mut runtime).unwrap_err();
+        assert!(matches!(error, Error::JsonDecode(message) if message.ends_with("at .items[0]")));
+
+        // Integers beyond 32 bits are not mistaken for the failure
+        let value: Value = runtime.get_value(Some(&handle), "largeIntegers").unwrap();
+        let error = value.try_into::<Ledger>(&mut runtime).unwrap_err();
+        assert!(
+            matches!(&error, Error::TypeMismatch(path, ..) if path == ".label"),
+            "{error}"
+        );
+
+        // Return values from calls are explained the same way
+        let error = runtime
+            .call_function::<Order>(Some(&handle), "getOrder", crate::json_args!())
+            .unwrap_err();
+        assert!(matches!(error, Error::TypeMismatch(..)));
+    }
+}
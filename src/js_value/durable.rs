@@ -0,0 +1,125 @@
+use deno_core::v8;
+use serde::{Deserialize, Serialize};
+
+use crate::{ext::rustyscript::DurableRegistry, Error, Runtime};
+
+/// A serializable reference to a JS value that survives runtime recreation
+///
+/// Scripts register durable values by name with `rustyscript.durable(name, value)`,
+/// typically at module load time. The handle itself only stores that name - so it can be
+/// persisted, sent across threads, or kept while a pooled runtime is recycled.
+///
+/// Resolving the handle against a runtime that has loaded the registering module
+/// returns a fresh [`super::Function`] (or other value) bound to *that* runtime,
+/// instead of a dangling reference into a dead isolate
+///
+/// ```rust
+/// use rustyscript::{js_value::{DurableHandle, Function}, json_args, Module, Runtime};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let module = Module::new("test.js", "rustyscript.durable('greet', (n) => `Hi ${n}`);");
+///
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.load_module(&module)?;
+/// let handle = runtime.durable_handle("greet")?;
+///
+/// // Later, in a new runtime that loaded the same module
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.load_module(&module)?;
+/// let greet: Function = handle.resolve(&mut runtime)?;
+/// let value: String = greet.call(&mut runtime, None, &json_args!("Bob"))?;
+/// assert_eq!(value, "Hi Bob");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DurableHandle {
+    name: String,
+}
+
+impl DurableHandle {
+    /// Create a handle for the durable value registered under the given name
+    ///
+    /// No check is made that such a value exists - see [`crate::Runtime::durable_handle`]
+    #[must_use]
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+
+    /// The name the value is registered under
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Resolve the handle against the given runtime
+    ///
+    /// # Errors
+    /// Will return an error if no value has been registered under this name in the runtime,
+    /// or if the registered value is the wrong type
+    pub fn resolve<T>(&self, runtime: &mut Runtime) -> Result<T, Error>
+    where
+        T: TryFrom<v8::Global<v8::Value>, Error = Error>,
+    {
+        let value = Self::lookup(runtime, &self.name)?;
+        T::try_from(value)
+    }
+
+    pub(crate) fn lookup(
+        runtime: &mut Runtime,
+        name: &str,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let state = runtime.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        state
+            .try_borrow::<DurableRegistry>()
+            .and_then(|registry| registry.0.get(name))
+            .cloned()
+            .ok_or_else(|| Error::ValueNotFound(format!("durable value `{name}`")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{js_value::Function, json_args, serde_json, Module, RuntimeOptions};
+
+    #[test]
+    fn test_durable_handle() {
+        let module = Module::new(
+            "test.js",
+            "
+            let count = 0;
+            rustyscript.durable('counter', () => ++count);
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime.load_module(&module).unwrap();
+        let handle = runtime.durable_handle("counter").unwrap();
+
+        let f: Function = handle.resolve(&mut runtime).unwrap();
+        let value: usize = f.call(&mut runtime, None, &json_args!()).unwrap();
+        assert_eq!(value, 1);
+
+        // Survives serialization and runtime recreation
+        let token = serde_json::to_string(&handle).unwrap();
+        let handle: DurableHandle = serde_json::from_str(&token).unwrap();
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        handle
+            .resolve::<Function>(&mut runtime)
+            .expect_err("Resolved before registration");
+
+        runtime.load_module(&module).unwrap();
+        let f: Function = handle.resolve(&mut runtime).unwrap();
+        let value: usize = f.call(&mut runtime, None, &json_args!()).unwrap();
+        assert_eq!(value, 1);
+
+        runtime
+            .durable_handle("missing")
+            .expect_err("Created a handle for a missing value");
+    }
+}
@@ -13,10 +13,18 @@ impl_checker!(FunctionTypeChecker, Function, is_function, |e| {
 });
 
 impl Function {
-    pub(crate) fn as_global<'a, 'i>(&self, scope: &mut v8::PinScope<'a, 'i>) -> v8::Global<v8::Function> {
+    pub(crate) fn as_global<'a, 'i>(
+        &self,
+        scope: &mut v8::PinScope<'a, 'i>,
+    ) -> v8::Global<v8::Function> {
         self.0.as_global(scope)
     }
 
+    /// Ensures this function is being used with the runtime it was created in
+    pub(crate) fn check_runtime(&self, runtime: crate::RuntimeId) -> Result<(), crate::Error> {
+        self.0.check_runtime(runtime)
+    }
+
     /// Returns true if the function is async
     #[must_use]
     pub fn is_async(&self) -> bool {
@@ -114,5 +122,11 @@ mod test {
             .unwrap();
         let value = value.into_value(&mut runtime).unwrap();
         assert_eq!(value, 42);
+
+        let mut other = Runtime::new(RuntimeOptions::default()).unwrap();
+        let e = f
+            .call::<usize>(&mut other, None, &json_args!())
+            .unwrap_err();
+        assert!(matches!(e, crate::Error::WrongRuntime(..)));
     }
 }
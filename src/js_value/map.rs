@@ -20,10 +20,18 @@ impl_checker!(ObjectTypeChecker, Object, is_object, |e| {
 impl Map {
     /// Gets a value from the map
     /// Warning: If a key is not valid UTF-8, the value may be inaccessible
-    pub fn get(&self, key: &str, runtime: &mut crate::Runtime) -> Option<crate::js_value::Value> {
+    ///
+    /// # Errors
+    /// Will return [`crate::Error::WrongRuntime`] if the map belongs to a different runtime
+    pub fn get(
+        &self,
+        key: &str,
+        runtime: &mut crate::Runtime,
+    ) -> Result<Option<crate::js_value::Value>, crate::Error> {
+        self.0.check_runtime(runtime.id())?;
         let rt = runtime.deno_runtime();
         deno_core::scope!(scope, rt);
-        self.get_property_by_name(scope, key)
+        Ok(self.get_property_by_name(scope, key))
     }
 
     /// Converts the map to a hashmap
@@ -80,7 +88,9 @@ impl Map {
         let value = local.get(scope, key.into())?;
 
         let value = v8::Global::new(scope, value);
-        Some(crate::js_value::Value::from_v8(value))
+        let mut value = crate::js_value::Value::from_v8(value);
+        value.0 .2 = super::scope_runtime_id(scope);
+        Some(value)
     }
 
     pub(crate) fn get_string_keys<'a, 'i>(&self, scope: &mut v8::PinScope<'a, 'i>) -> Vec<String> {
@@ -129,12 +139,18 @@ mod test {
         let m: Map = runtime.get_value(Some(&handle), "m").expect("oops");
         assert_eq!(m.len(&mut runtime), 4);
 
-        let a = m.get("a", &mut runtime).unwrap();
+        let a = m.get("a", &mut runtime).unwrap().unwrap();
         let a: usize = a.try_into(&mut runtime).unwrap();
         assert_eq!(a, 1);
 
-        let zero = m.get("0", &mut runtime).unwrap();
+        let zero = m.get("0", &mut runtime).unwrap().unwrap();
         let zero: usize = zero.try_into(&mut runtime).unwrap();
         assert_eq!(zero, 4);
+
+        let mut other = Runtime::new(RuntimeOptions::default()).unwrap();
+        assert!(matches!(
+            m.get("a", &mut other),
+            Err(crate::Error::WrongRuntime(..))
+        ));
     }
 }
@@ -35,7 +35,7 @@ where
             .await?;
         deno_core::scope!(scope, runtime);
         let local = v8::Local::new(scope, &result);
-        Ok(deno_core::serde_v8::from_v8(scope, local)?)
+        super::decode(scope, local)
     }
 
     /// Returns a future that resolves the promise
@@ -44,6 +44,7 @@ where
     /// Will return an error if the promise cannot be resolved into the given type,
     /// or if a runtime error occurs
     pub async fn into_future(self, runtime: &mut crate::Runtime) -> Result<T, crate::Error> {
+        self.0.check_runtime(runtime.id())?;
         self.resolve(runtime.deno_runtime()).await
     }
 
@@ -81,10 +82,7 @@ where
             }
             PromiseState::Fulfilled => {
                 let result = value.result(scope);
-                match deno_core::serde_v8::from_v8::<T>(scope, result) {
-                    Ok(value) => std::task::Poll::Ready(Ok(value)),
-                    Err(e) => std::task::Poll::Ready(Err(e.into())),
-                }
+                std::task::Poll::Ready(super::decode::<T>(scope, result))
             }
         }
     }
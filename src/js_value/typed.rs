@@ -0,0 +1,172 @@
+use serde::de::DeserializeOwned;
+
+use super::Value;
+use crate::{Error, Runtime};
+
+/// A [`Value`] paired with its conversion to a rust type, decoded at most once
+///
+/// Useful for values read over and over, such as a configuration object exported by a module;
+/// the first read validates and decodes the value, and later reads return the decoded copy
+/// without crossing into V8 again.
+///
+/// Changes made to the value from javascript afterwards are not seen until [`TypedValue::refresh`]
+/// is called - freezing the object on the javascript side (`Object.freeze`) makes that explicit
+///
+/// ```rust
+/// use rustyscript::{js_value::{TypedValue, Value}, Module, Runtime, RuntimeOptions};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let module = Module::new("config.js", "export const limits = Object.freeze({ depth: 3 });");
+/// let mut runtime = Runtime::new(RuntimeOptions::default())?;
+/// let handle = runtime.load_module(&module)?;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Limits { depth: u32 }
+///
+/// let limits: Value = runtime.get_value(Some(&handle), "limits")?;
+/// let mut limits: TypedValue<Limits> = limits.typed();
+/// for _ in 0..100 {
+///     assert_eq!(limits.get(&mut runtime)?.depth, 3);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedValue<T> {
+    value: Value,
+    decoded: Option<T>,
+}
+
+impl<T> TypedValue<T>
+where
+    T: DeserializeOwned,
+{
+    /// Wraps a value, without decoding it yet
+    #[must_use]
+    pub fn new(value: Value) -> Self {
+        Self {
+            value,
+            decoded: None,
+        }
+    }
+
+    /// Returns the value as a `T`, decoding it on the first call only
+    ///
+    /// # Errors
+    /// Will return an error if the value belongs to a different runtime,
+    /// or cannot be deserialized into `T`
+    pub fn get(&mut self, runtime: &mut Runtime) -> Result<&T, Error> {
+        if self.decoded.is_none() {
+            self.decoded = Some(self.value.clone().try_into(runtime)?);
+        }
+
+        Ok(self.decoded.as_ref().expect("value was decoded above"))
+    }
+
+    /// Decodes the value again, picking up any changes made to it from javascript
+    ///
+    /// On failure the previously decoded copy is discarded
+    ///
+    /// # Errors
+    /// Will return an error if the value belongs to a different runtime,
+    /// or cannot be deserialized into `T`
+    pub fn refresh(&mut self, runtime: &mut Runtime) -> Result<&T, Error> {
+        self.decoded = None;
+        self.get(runtime)
+    }
+
+    /// Returns the decoded copy, if the value has been decoded
+    #[must_use]
+    pub fn cached(&self) -> Option<&T> {
+        self.decoded.as_ref()
+    }
+
+    /// Returns true if the value has been decoded
+    #[must_use]
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.is_some()
+    }
+
+    /// Returns the underlying value
+    #[must_use]
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Consumes the handle, returning the underlying value
+    #[must_use]
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+impl<T> From<Value> for TypedValue<T>
+where
+    T: DeserializeOwned,
+{
+    fn from(value: Value) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Value {
+    /// Wraps the value in a [`TypedValue`], which decodes it into `T` once and reuses the result
+    #[must_use]
+    pub fn typed<T>(self) -> TypedValue<T>
+    where
+        T: DeserializeOwned,
+    {
+        TypedValue::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Module, RuntimeOptions};
+
+    #[test]
+    fn test_typed_value() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Config {
+            depth: u32,
+            name: String,
+        }
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const config = { depth: 3, name: 'test' };
+            export const bump = () => config.depth++;
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let config: Value = runtime.get_value(Some(&handle), "config").unwrap();
+        let mut config: TypedValue<Config> = config.typed();
+        assert!(config.cached().is_none());
+        assert_eq!(
+            config.get(&mut runtime).unwrap(),
+            &Config {
+                depth: 3,
+                name: "test".to_string()
+            }
+        );
+        assert!(config.is_decoded());
+
+        // Changes are only seen after a refresh
+        let _: u32 = runtime
+            .call_function(Some(&handle), "bump", crate::json_args!())
+            .unwrap();
+        assert_eq!(config.get(&mut runtime).unwrap().depth, 3);
+        assert_eq!(config.refresh(&mut runtime).unwrap().depth, 4);
+
+        // Shape errors are reported, and not cached
+        let config_value: Value = runtime.get_value(Some(&handle), "config").unwrap();
+        let mut wrong: TypedValue<u32> = config_value.typed();
+        assert!(wrong.get(&mut runtime).is_err());
+        assert!(!wrong.is_decoded());
+    }
+}
@@ -0,0 +1,107 @@
+use deno_core::v8;
+
+use crate::{Error, Runtime, RuntimeId};
+
+/// A weak reference to a stored javascript value
+///
+/// Unlike [`super::Value`], [`super::Function`] and friends, a `Weak` does not keep
+/// its target alive - V8 is free to garbage collect the value once nothing else
+/// refers to it. Use `upgrade` to get a strong handle back, if the value still exists.
+///
+/// Created with `downgrade` or `downgrade_with_finalizer` on any of the `js_value` types
+pub struct Weak<T> {
+    inner: v8::Weak<v8::Value>,
+    runtime: RuntimeId,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> Weak<T> {
+    pub(crate) fn new(
+        runtime: &mut Runtime,
+        value: &v8::Global<v8::Value>,
+        owner: Option<RuntimeId>,
+        finalizer: Option<Box<dyn FnOnce()>>,
+    ) -> Result<Self, Error> {
+        let id = runtime.id();
+        match owner {
+            Some(owner) if owner != id => return Err(Error::WrongRuntime(owner, id)),
+            _ => {}
+        }
+
+        let isolate = runtime.deno_runtime().v8_isolate();
+        let inner = match finalizer {
+            Some(finalizer) => {
+                v8::Weak::with_finalizer(isolate, value, Box::new(move |_| finalizer()))
+            }
+            None => v8::Weak::new(isolate, value),
+        };
+
+        Ok(Self {
+            inner,
+            runtime: id,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns true if the value has been garbage collected
+    #[must_use]
+    pub fn is_collected(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the id of the runtime the value belongs to
+    #[must_use]
+    pub fn runtime_id(&self) -> RuntimeId {
+        self.runtime
+    }
+
+    pub(crate) fn upgrade_global(
+        &self,
+        runtime: &mut Runtime,
+    ) -> Result<Option<v8::Global<v8::Value>>, Error> {
+        let id = runtime.id();
+        if id != self.runtime {
+            return Err(Error::WrongRuntime(self.runtime, id));
+        }
+
+        Ok(self.inner.to_global(runtime.deno_runtime().v8_isolate()))
+    }
+}
+
+impl<T> std::fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Weak")
+            .field("runtime", &self.runtime)
+            .field("collected", &self.is_collected())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{js_value::Map, RuntimeOptions};
+
+    #[test]
+    fn test_weak() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+
+        let map: Map = runtime.eval("({ a: 1 })").unwrap();
+        let weak = map.downgrade_with_finalizer(&mut runtime, || {}).unwrap();
+
+        let upgraded = weak.upgrade(&mut runtime).unwrap().unwrap();
+        assert_eq!(upgraded.keys(&mut runtime), vec!["a".to_string()]);
+
+        let mut other = Runtime::new(RuntimeOptions::default()).unwrap();
+        assert!(matches!(
+            weak.upgrade(&mut other),
+            Err(Error::WrongRuntime(..))
+        ));
+
+        drop(map);
+        drop(upgraded);
+        runtime.request_gc(crate::GcKind::Compact);
+        assert!(weak.upgrade(&mut runtime).unwrap().is_none());
+        assert!(weak.is_collected());
+    }
+}
@@ -0,0 +1,486 @@
+//! Precision-preserving conversion of large integers passed from Rust to JS
+//!
+//! JS numbers are doubles, so integers beyond `Number.MAX_SAFE_INTEGER` (2^53 - 1) cannot be
+//! represented exactly - by default, `u64` ids and the like are silently rounded as they are
+//! passed to a function. [`LargeIntegers`] lets a runtime pass them as `BigInt`s or strings instead
+//!
+//! Round-trips:
+//! - [`LargeIntegers::BigInt`] - a `BigInt` returned to Rust decodes exactly into any integer type
+//!   wide enough to hold it. Requires the `large_integers` feature
+//! - [`LargeIntegers::String`] - the string decodes into a `String`, to be parsed by the host
+//!
+//! Integers within the safe range are always passed as numbers
+use serde::ser::{self, Serialize, Serializer};
+
+/// The largest integer a JS number holds exactly - `Number.MAX_SAFE_INTEGER`
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// serde_v8's own types, such as buffers and JS values, smuggle a pointer through their fields
+/// under a name starting with this - they are passed through untouched
+const MAGIC_PREFIX: &str = "$__v8_magic";
+
+/// How integers outside the range JS numbers hold exactly are passed to scripts
+///
+/// See [`crate::RuntimeOptions::large_integers`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LargeIntegers {
+    /// As numbers, rounding them to the nearest double
+    #[default]
+    Number,
+
+    /// As `BigInt`s - scripts must then use `BigInt` arithmetic on them
+    ///
+    /// Requires the `large_integers` feature
+    #[cfg(feature = "large_integers")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "large_integers")))]
+    BigInt,
+
+    /// As decimal strings
+    String,
+}
+
+/// Serializes a value, with large integers converted according to a [`LargeIntegers`]
+///
+/// Without a mode, the value is serialized untouched
+pub(crate) struct Precise<'a, T: ?Sized> {
+    value: &'a T,
+    mode: Option<LargeIntegers>,
+}
+
+impl<'a, T: ?Sized + Serialize> Precise<'a, T> {
+    pub fn new(value: &'a T, mode: LargeIntegers) -> Self {
+        Self {
+            value,
+            mode: Some(mode),
+        }
+    }
+}
+
+impl<T: ?Sized + Serialize> Serialize for Precise<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.mode {
+            Some(mode) => self.value.serialize(PreciseSerializer {
+                inner: serializer,
+                mode,
+            }),
+            None => self.value.serialize(serializer),
+        }
+    }
+}
+
+struct PreciseSerializer<S> {
+    inner: S,
+    mode: LargeIntegers,
+}
+
+/// An integer outside the range JS numbers hold exactly
+#[derive(Clone, Copy)]
+enum Large {
+    Signed(i128),
+    Unsigned(u128),
+}
+
+impl std::fmt::Display for Large {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Signed(v) => v.fmt(f),
+            Self::Unsigned(v) => v.fmt(f),
+        }
+    }
+}
+
+impl<S: Serializer> PreciseSerializer<S> {
+    fn large(self, value: Large) -> Result<S::Ok, S::Error> {
+        match self.mode {
+            LargeIntegers::String => self.inner.serialize_str(&value.to_string()),
+
+            #[cfg(feature = "large_integers")]
+            LargeIntegers::BigInt => {
+                let value = match value {
+                    Large::Signed(v) => num_bigint::BigInt::from(v),
+                    Large::Unsigned(v) => num_bigint::BigInt::from(v),
+                };
+                deno_core::serde_v8::BigInt::from(value).serialize(self.inner)
+            }
+
+            // Values are never wrapped for `Number` - rounded as serde_v8 would round them
+            #[allow(clippy::cast_precision_loss)]
+            LargeIntegers::Number => match value {
+                Large::Signed(v) => self.inner.serialize_f64(v as f64),
+                Large::Unsigned(v) => self.inner.serialize_f64(v as f64),
+            },
+        }
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<S::Ok, S::Error> {
+                self.inner.$method(v)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for PreciseSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    );
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) {
+            self.inner.serialize_i64(v)
+        } else {
+            self.large(Large::Signed(v.into()))
+        }
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => self.large(Large::Unsigned(v.into())),
+        }
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => self.large(Large::Signed(v)),
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => self.large(Large::Unsigned(v)),
+        }
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&Precise::new(value, self.mode))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        if name.starts_with(MAGIC_PREFIX) {
+            return self.inner.serialize_newtype_struct(name, value);
+        }
+        self.inner
+            .serialize_newtype_struct(name, &Precise::new(value, self.mode))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(
+            name,
+            variant_index,
+            variant,
+            &Precise::new(value, self.mode),
+        )
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        let inner = self.inner.serialize_seq(len)?;
+        Ok(Compound::new(inner, self.mode))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        let inner = self.inner.serialize_tuple(len)?;
+        Ok(Compound::new(inner, self.mode))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        let inner = self.inner.serialize_tuple_struct(name, len)?;
+        let mode = (!name.starts_with(MAGIC_PREFIX)).then_some(self.mode);
+        Ok(Compound { inner, mode })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        let inner = self
+            .inner
+            .serialize_tuple_variant(name, variant_index, variant, len)?;
+        Ok(Compound::new(inner, self.mode))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        let inner = self.inner.serialize_map(len)?;
+        Ok(Compound::new(inner, self.mode))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        let inner = self.inner.serialize_struct(name, len)?;
+        let mode = (!name.starts_with(MAGIC_PREFIX)).then_some(self.mode);
+        Ok(Compound { inner, mode })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        let inner = self
+            .inner
+            .serialize_struct_variant(name, variant_index, variant, len)?;
+        Ok(Compound::new(inner, self.mode))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// A compound value whose elements are serialized with a [`PreciseSerializer`]
+///
+/// Without a mode, elements are passed through untouched
+struct Compound<C> {
+    inner: C,
+    mode: Option<LargeIntegers>,
+}
+
+impl<C> Compound<C> {
+    fn new(inner: C, mode: LargeIntegers) -> Self {
+        Self {
+            inner,
+            mode: Some(mode),
+        }
+    }
+
+    fn precise<'a, T: ?Sized>(&self, value: &'a T) -> Precise<'a, T> {
+        Precise {
+            value,
+            mode: self.mode,
+        }
+    }
+}
+
+macro_rules! compound {
+    ($trait:ident, $method:ident) => {
+        impl<C: ser::$trait> ser::$trait for Compound<C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn $method<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+                let value = self.precise(value);
+                self.inner.$method(&value)
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        }
+    };
+    ($trait:ident) => {
+        impl<C: ser::$trait> ser::$trait for Compound<C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn serialize_field<T: ?Sized + Serialize>(
+                &mut self,
+                key: &'static str,
+                value: &T,
+            ) -> Result<(), C::Error> {
+                let value = self.precise(value);
+                self.inner.serialize_field(key, &value)
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        }
+    };
+}
+
+compound!(SerializeSeq, serialize_element);
+compound!(SerializeTuple, serialize_element);
+compound!(SerializeTupleStruct, serialize_field);
+compound!(SerializeTupleVariant, serialize_field);
+compound!(SerializeStruct);
+compound!(SerializeStructVariant);
+
+impl<C: ser::SerializeMap> ser::SerializeMap for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), C::Error> {
+        let key = self.precise(key);
+        self.inner.serialize_key(&key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.precise(value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    const BIG: u64 = (1 << 60) + 1;
+
+    #[test]
+    fn test_large_integers() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const describe = (...values) => values.map((v) => `${typeof v}:${v}`);
+            export const increment = (n) => n + 1n;
+            export const identity = (v) => v;
+            export const idType = ({ id }) => typeof id;
+            ",
+        );
+
+        // By default, large integers are rounded to the nearest double
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        let described: Vec<String> = runtime
+            .call_function(Some(&handle), "describe", json_args!(BIG))
+            .unwrap();
+        assert_eq!(described, vec!["number:1152921504606846976"]);
+
+        #[cfg(feature = "large_integers")]
+        test_bigints(&module);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            large_integers: LargeIntegers::String,
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        let value: String = runtime
+            .call_function(Some(&handle), "identity", json_args!(BIG))
+            .unwrap();
+        assert_eq!(value.parse::<u64>().ok(), Some(BIG));
+        let value: u32 = runtime
+            .call_function(Some(&handle), "identity", json_args!(7_u64))
+            .unwrap();
+        assert_eq!(value, 7);
+
+        // serde_v8's own types keep their pointers intact
+        let array: crate::js_value::Value = runtime.eval("[1, 2]").unwrap();
+        let buffer = deno_core::ToJsBuffer::from(vec![3_u8, 4]);
+        let described: Vec<String> = runtime
+            .call_function(Some(&handle), "describe", &(array, buffer))
+            .unwrap();
+        assert_eq!(described, vec!["object:1,2", "object:3,4"]);
+    }
+
+    #[cfg(feature = "large_integers")]
+    fn test_bigints(module: &Module) {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            large_integers: LargeIntegers::BigInt,
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(module).unwrap();
+        let described: Vec<String> = runtime
+            .call_function(
+                Some(&handle),
+                "describe",
+                json_args!(BIG, -(1_i64 << 60), 42, u128::MAX),
+            )
+            .unwrap();
+        assert_eq!(
+            described,
+            vec![
+                "bigint:1152921504606846977",
+                "bigint:-1152921504606846976",
+                "number:42",
+                "bigint:340282366920938463463374607431768211455",
+            ]
+        );
+
+        // BigInts decode back exactly
+        let value: u64 = runtime
+            .call_function(Some(&handle), "increment", json_args!(BIG))
+            .unwrap();
+        assert_eq!(value, BIG + 1);
+        let value: i64 = runtime
+            .call_function(Some(&handle), "identity", json_args!(i64::MIN))
+            .unwrap();
+        assert_eq!(value, i64::MIN);
+
+        // Nested values are converted too
+        let nested: String = runtime
+            .call_function(
+                Some(&handle),
+                "idType",
+                &(deno_core::serde_json::json!({ "id": BIG }),),
+            )
+            .unwrap();
+        assert_eq!(nested, "bigint");
+    }
+}
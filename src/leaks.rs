@@ -0,0 +1,91 @@
+//! Reporting of background work scripts leave alive between host calls
+//!
+//! When one runtime serves many requests, a timer, interval or global listener registered while
+//! handling one request keeps running into the next. [`crate::Runtime::report_leaks`] and
+//! [`crate::Runtime::clear_leaks`] split the runtime's life into invocations, and report the work
+//! each one left behind
+use serde::Deserialize;
+
+/// Work registered during an invocation that was still alive when it was checked
+///
+/// See [`crate::Runtime::report_leaks`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct LeakReport {
+    /// Timeouts that have neither fired nor been cleared
+    pub timeouts: usize,
+
+    /// Intervals that have not been cleared
+    pub intervals: usize,
+
+    /// Event types of global listeners, such as `message`, that are still registered - one entry per listener
+    ///
+    /// Only tracked with the `events` feature
+    pub listeners: Vec<String>,
+}
+
+impl LeakReport {
+    /// Returns true if the invocation left nothing behind
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.timeouts == 0 && self.intervals == 0 && self.listeners.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions, Undefined};
+
+    #[cfg(feature = "events")]
+    #[test]
+    fn test_leak_reports() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime.report_leaks().unwrap();
+
+        runtime
+            .eval::<Undefined>(
+                "globalThis.ticks = 0;
+                setInterval(() => ticks++, 1000);
+                setTimeout(() => {}, 1000);
+                clearTimeout(setTimeout(() => {}, 1000));
+                addEventListener('message', () => {});
+                const handler = () => {};
+                addEventListener('queue', handler);
+                removeEventListener('queue', handler);",
+            )
+            .unwrap();
+
+        let report = runtime.report_leaks().unwrap();
+        assert_eq!(
+            report,
+            LeakReport {
+                timeouts: 1,
+                intervals: 1,
+                listeners: vec!["message".to_string()],
+            }
+        );
+
+        // Work is only reported for the invocation that created it
+        assert!(runtime.report_leaks().unwrap().is_empty());
+
+        runtime
+            .eval::<Undefined>(
+                "setInterval(() => ticks++, 1000); addEventListener('fetch', () => {})",
+            )
+            .unwrap();
+        let cleared = runtime.clear_leaks().unwrap();
+        assert_eq!(cleared.intervals, 1);
+        assert_eq!(cleared.listeners, vec!["fetch".to_string()]);
+
+        let outcome = runtime.dispatch_event("fetch", &()).unwrap();
+        assert!(!outcome.is_handled());
+
+        // The first invocation's timers were only reported, so they are still pending
+        runtime.assert_idle().unwrap_err();
+
+        // Scripts cannot replace the checker with their own
+        runtime
+            .eval::<Undefined>("Deno.core.ops.op_bind_leak_checker(() => ({ timeouts: 0 }))")
+            .unwrap_err();
+    }
+}
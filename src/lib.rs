@@ -228,6 +228,8 @@
 //! - `resolve_path`; Resolve a relative path to the current working dir
 //! - `validate`; Validate the syntax of a JS expression
 //! - `init_platform`; Initialize the V8 platform for multi-threaded applications
+//! - `init_platform_with`; Initialize the V8 platform with control over its worker threads
+//! - `init_shared_heap`; Reduce the memory held by each runtime, when many are resident at once
 //!
 //! Commonly used features have been grouped into the following feature-sets:
 //! - **`safe_extensions`** - On by default, these extensions are safe to use in a sandboxed environment
@@ -257,9 +259,15 @@
 //! |`fs`               |Provides ops for interacting with the file system.                                                         |**NO**            |`deno_fs`, `web`,  `io`                                                                        |
 //! |`http`             |Implements the fetch standard                                                                              |**NO**            |`deno_http`, `web`, `websocket`                                                                |
 //! |`kv`               |Implements the Deno KV Connect protocol                                                                    |**NO**            |`deno_kv`, `web`, `console`                                                                    |
+//! |`arrow`            |Passes Apache Arrow record batches to and from scripts as zero-copy IPC buffers, through [`ArrowTable`]    |yes               |`arrow-array`, `arrow-ipc`, `arrow-schema`                                                     |
+//! |`database`         |Provides `rustyscript.database`, backed by host-side connections through [`DbProvider`]                    |yes               |None                                                                                           |
+//! |`host_cache`       |Provides `rustyscript.cache`, backed by a host-managed, namespaced [`CacheProvider`]                       |yes               |None                                                                                           |
+//! |`secrets`          |Provides `rustyscript.secrets`, resolved on demand by a [`SecretsProvider`], with audit logging            |yes               |None                                                                                           |
+//! |`sqlite`           |Provides `rustyscript.sqlite`, a minimal SQLite API gated by `WebPermissions::check_open`                  |**NO**            |`rusqlite`, `web`                                                                              |
 //! |`url`              |Provides the `URL`, and `URLPattern` APIs from within JS                                                   |yes               |`deno_webidl`, `deno_url`                                                                      |
 //! |`io`               |Provides IO primitives such as stdio streams and abstraction over File System files.                       |**NO**            |`deno_io`, `rustyline`, `winapi`, `nix`, `libc`, `once_cell`                                   |
 //! |`web`              |Provides the `Event`, `TextEncoder`, `TextDecoder`, `File`, Web Cryptography, and fetch APIs from within JS|**NO**            |`deno_webidl`, `deno_web`, `deno_crypto`, `deno_fetch`, `deno_url`, `deno_net`                 |
+//! |`brotli`           |Adds the `brotli` format to `CompressionStream`, `DecompressionStream` and [`CompressionFormat`]          |**NO**            |`brotli`, `web`                                                                                |
 //! |`webgpu`           |Implements the WebGPU API                                                                                  |**NO**            |`deno_webgpu`, `web`                                                                           |
 //! |`webstorage`       |Provides the `WebStorage` API                                                                              |**NO**            |`deno_webidl`, `deno_webstorage`                                                               |
 //! |`websocket`        |Provides the `WebSocket` API                                                                               |**NO**            |`deno_web`, `deno_websocket`                                                                   |
@@ -276,7 +284,24 @@
 //! |                   |                                                                                                           |                  |                                                                                               |
 //! |`worker`           |Enables access to the threaded worker API [`worker`]                                                       |yes               |None                                                                                           |
 //! |`snapshot_builder` |Enables access to [`SnapshotBuilder`], a runtime for creating snapshots that can improve start-times       |yes               |None                                                                                           |
+//! |`sandbox_process`  |Enables [`sandbox_process`], running a runtime in a child process so crashes cannot take down the host     |yes               |`winapi`                                                                                       |
+//! |`hardening`        |Enables [`Hardening`], backing up permissions with landlock and seccomp on runtime threads (Linux only)    |yes               |`landlock`, `seccompiler`, `libc`                                                              |
 //! |`web_stub`         |Enables a subset of `web` features that do not break sandboxing                                            |yes               |`deno_webidl`                                                                                  |
+//! |`otel`             |Reports calls, module loads, op latencies, fetches and console errors through OpenTelemetry                |yes               |`opentelemetry`                                                                                |
+//! |`intl`             |Embeds the full ICU data set backing `Intl` (see [`init_icu_data`] for slimmed data sets)                  |yes               |None                                                                                           |
+//! |`host_extensions`  |Enables all of rustyscript's own host integrations below - included in `default`                           |yes               |`num-bigint`, `rmp-serde`, `ciborium`                                                          |
+//! |`env`              |Provides a virtual `Deno.env`, filled by the host - see [`VirtualEnv`]                                     |yes               |None                                                                                           |
+//! |`signals`          |Provides `Deno.addSignalListener`, for signals raised by [`Runtime::raise_signal`]                         |yes               |None                                                                                           |
+//! |`events`           |Lets the host dispatch DOM events to scripts - see [`Runtime::dispatch_event`]                             |yes               |None                                                                                           |
+//! |`wasm`             |Enforces [`WasmLimits`] on WebAssembly modules compiled by scripts                                         |yes               |None                                                                                           |
+//! |`json`             |Streams JSON values between the host and scripts - see [`JsonFormat`]                                      |yes               |None                                                                                           |
+//! |`codecs`           |Adds the MessagePack and CBOR argument encodings of [`Codec`]                                              |yes               |`rmp-serde`, `ciborium`                                                                        |
+//! |`pipeline`         |Runs byte-buffer transforms registered by scripts - see [`Runtime::run_transform`]                         |yes               |None                                                                                           |
+//! |`large_integers`   |Adds [`LargeIntegers::BigInt`], passing integers beyond 2^53 to scripts as `BigInt`s                       |yes               |`num-bigint`                                                                                   |
+//! |`chrono`           |Adds [`serde_adapters`] for `chrono` date-times                                                            |yes               |`chrono`                                                                                       |
+//! |`time`             |Adds [`serde_adapters`] for `time` date-times                                                              |yes               |`time`                                                                                         |
+//! |`uuid`             |Adds [`serde_adapters`] for `Uuid`s                                                                        |yes               |`uuid`                                                                                         |
+//! |`decimal`          |Adds [`serde_adapters`] for `rust_decimal` decimals                                                        |yes               |`rust_decimal`                                                                                 |
 //!
 //! ----
 //!
@@ -301,31 +326,104 @@ pub use snapshot_builder::SnapshotBuilder;
 mod runtime_builder;
 pub use runtime_builder::RuntimeBuilder;
 
+mod activity;
+pub use activity::{PendingOpInfo, PendingOpKind, ResourceInfo};
+
+mod watchdog;
+pub use watchdog::{StallReport, WatchdogOptions};
+
+mod heartbeat;
+pub use heartbeat::{Heartbeat, HeartbeatAction, HeartbeatOptions};
+
+mod cancellation;
+pub use cancellation::CallCanceller;
+
+mod rate_limit;
+pub use rate_limit::{RateLimit, RateLimiter};
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod circuit_breaker;
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+
+mod cookie_jar;
+pub use cookie_jar::{Cookie, CookieJar};
+
+mod time_slice;
+pub use time_slice::{SliceId, TimeSliceOptions, TimeSlicer};
+
+#[cfg(all(feature = "hardening", target_os = "linux"))]
+mod hardening;
+
+#[cfg(all(feature = "hardening", target_os = "linux"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "hardening", target_os = "linux"))))]
+pub use hardening::{Enforcement, Hardening, HardeningReport};
+
+#[cfg(feature = "otel")]
+mod telemetry;
+
+#[cfg(feature = "otel")]
+#[cfg_attr(docsrs, doc(cfg(feature = "otel")))]
+pub use telemetry::OtelOptions;
+
 pub mod error;
 pub mod js_value;
 pub mod module_loader;
+pub mod serde_adapters;
 pub mod static_runtime;
+pub mod workflow;
 
 mod async_bridge;
+mod bindgen;
+mod build_info;
+mod entrypoint_wrap;
+mod envelope;
+mod export_freezer;
 mod ext;
 mod inner_runtime;
+mod large_integers;
+mod leaks;
+mod manifest;
 mod module;
+mod module_budget;
 mod module_handle;
+mod module_interface;
 mod module_wrapper;
+mod profile;
+mod project;
 mod runtime;
+mod script_events;
+mod startup_trace;
+mod timer_limits;
 mod traits;
 mod transpiler;
+mod tree_shake;
 mod utilities;
 
 #[cfg(feature = "worker")]
 #[cfg_attr(docsrs, doc(cfg(feature = "worker")))]
 pub mod worker;
 
+#[cfg(feature = "sandbox_process")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sandbox_process")))]
+pub mod sandbox_process;
+
+#[cfg(feature = "arrow")]
+mod arrow;
+
 // Expose a few dependencies that could be useful
 pub use deno_core;
 pub use deno_core::serde_json;
 pub use tokio;
 
+#[cfg(any(feature = "database", feature = "host_cache", feature = "secrets"))]
+pub use async_trait;
+
+#[cfg(feature = "arrow")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arrow")))]
+pub use {arrow_array, arrow_schema};
+
 /// Re-exports of the deno extension crates used by this library
 pub mod extensions {
     #[cfg(feature = "broadcast_channel")]
@@ -393,6 +491,18 @@ pub mod extensions {
 #[cfg_attr(docsrs, doc(cfg(feature = "kv")))]
 pub use ext::kv::{KvConfig, KvStore};
 
+#[cfg(feature = "database")]
+#[cfg_attr(docsrs, doc(cfg(feature = "database")))]
+pub use ext::database::{DbProvider, DbRows};
+
+#[cfg(feature = "host_cache")]
+#[cfg_attr(docsrs, doc(cfg(feature = "host_cache")))]
+pub use ext::host_cache::{CacheProvider, HostCache, MemoryCache, MAX_CACHE_TTL};
+
+#[cfg(feature = "secrets")]
+#[cfg_attr(docsrs, doc(cfg(feature = "secrets")))]
+pub use ext::secrets::{SecretAccess, SecretOutcome, SecretStore, SecretsProvider};
+
 //#[cfg(feature = "cache")]
 //#[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
 //pub use ext::cache::CacheBackend;
@@ -401,23 +511,92 @@ pub use ext::kv::{KvConfig, KvStore};
 #[cfg_attr(docsrs, doc(cfg(feature = "node_experimental")))]
 pub use ext::node::resolvers::RustyResolver;
 
+#[cfg(feature = "arrow")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arrow")))]
+pub use arrow::ArrowTable;
+
+pub use ext::actions::HostAction;
+
+#[cfg(feature = "codecs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "codecs")))]
+pub use ext::codecs::Codec;
+
+#[cfg(feature = "events")]
+#[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+pub use ext::events::{EventDispatchOutcome, EventListenerInfo};
+
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+pub use ext::json::JsonFormat;
+
+#[cfg(feature = "wasm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "wasm")))]
+pub use ext::wasm::WasmLimits;
+
 #[cfg(feature = "web")]
 #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
 pub use ext::web::{
-    AllowlistWebPermissions, CheckedPath, DefaultWebPermissions, PermissionCheckError,
-    PermissionDeniedError, SystemsPermissionKind, WebOptions, WebPermissions,
+    AllowlistWebPermissions, CachedResponse, CheckedPath, ClientCert, CompressionFormat,
+    DefaultWebPermissions, DirectoryFixtureStore, FetchFixtures, FetchHttpVersion, FetchMode,
+    FetchPoolOptions, FixtureStore, FormDataEntry, HostFile, HttpBody, HttpCache, HttpCacheStore,
+    MemoryFixtureStore, MemoryHttpCacheStore, PermissionCheckError, PermissionDeniedError,
+    RecordedResponse, SchemeHandlerFn, SchemeRequest, SchemeResponse, SystemsPermissionKind,
+    TlsProvider, WebOptions, WebPermissions,
 };
 pub use ext::ExtensionOptions;
 
+#[cfg(feature = "websocket")]
+#[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+pub use ext::websocket::{WebSocketDirection, WebSocketFrame, WebSocketInfo, WebSocketPayload};
+
+#[cfg(feature = "io")]
+#[cfg_attr(docsrs, doc(cfg(feature = "io")))]
+pub use ext::io::VirtualStdio;
+
+#[cfg(feature = "fs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+pub use ext::fs::TempDirOptions;
+
+#[cfg(all(feature = "env", not(feature = "node_experimental")))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "env", not(feature = "node_experimental"))))
+)]
+pub use ext::env::VirtualEnv;
+
 // Expose some important stuff from us
 pub use async_bridge::TokioRuntime;
-pub use error::Error;
-pub use inner_runtime::{RsAsyncFunction, RsFunction};
+pub use bindgen::Bindgen;
+pub use build_info::{build_info, BuildInfo};
+pub use entrypoint_wrap::{EntrypointFailure, EntrypointFailureKind, EntrypointStats};
+pub use envelope::Envelope;
+pub use error::{Error, HostError, IntoJsError, ScriptError};
+pub use inner_runtime::{
+    GcKind, ImportMetaFn, MemoryPressure, MissingValues, RsAsyncFunction, RsBlockingFunction,
+    RsFunction, RuntimeId, Tz,
+};
+pub use large_integers::LargeIntegers;
+pub use leaks::LeakReport;
+pub use manifest::{read_manifest, Manifest};
 pub use module::Module;
-pub use module_handle::ModuleHandle;
+pub use module_budget::{ModuleBudget, ModuleLimit, ModuleLimits, ModuleUsage};
+pub use module_handle::{CallQuota, ModuleHandle};
+pub use module_interface::{FunctionDescriptor, ParamDescriptor};
 pub use module_wrapper::ModuleWrapper;
+pub use profile::Profile;
+pub use project::{ProjectHandle, ProjectModule, ProjectOptions};
 pub use runtime::{Runtime, RuntimeOptions, Undefined};
-pub use utilities::{evaluate, import, init_platform, resolve_path, validate};
+pub use script_events::{EventReceiver, Overflow, SubscribeOptions};
+pub use startup_trace::{ModuleLoadTrace, StartupTrace};
+pub use timer_limits::{LongTimerPolicy, TimerLimit, TimerLimits};
+pub use utilities::{
+    evaluate, import, init_platform, init_platform_with, init_shared_heap, platform_options,
+    resolve_path, validate, PlatformOptions, SharedHeapOptions,
+};
+
+#[cfg(feature = "intl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "intl")))]
+pub use utilities::init_icu_data;
 
 #[cfg(feature = "broadcast_channel")]
 #[cfg_attr(docsrs, doc(cfg(feature = "broadcast_channel")))]
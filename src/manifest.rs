@@ -0,0 +1,306 @@
+//! Capability manifests, declaring the permissions a script expects to be granted
+//!
+//! A script declares its manifest by exporting a literal object named `permissions`:
+//! ```js
+//! export const permissions = {
+//!     net: ["api.example.com"],
+//!     env: ["API_KEY"],
+//!     functions: ["send_email"],
+//! };
+//! ```
+//!
+//! Or, for a module loaded from disk, in a JSON sidecar beside it - `script.permissions.json` for
+//! `script.ts`. The manifest is read without running the script, so hosts can compare what is
+//! requested against what they are willing to grant before loading it
+use deno_ast::{
+    swc::ast::{
+        Decl, Expr, Lit, ModuleDecl, ModuleItem, Pat, Prop, PropName, PropOrSpread, UnaryOp,
+    },
+    ProgramRef,
+};
+use deno_core::serde_json::{self, Map, Number, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Module};
+
+/// The name of the export holding a script's manifest
+const MANIFEST_EXPORT: &str = "permissions";
+
+/// The capabilities a script requests - see [`read_manifest`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    /// Hosts, or URLs, the script connects to
+    pub net: Vec<String>,
+
+    /// Paths the script reads
+    pub read: Vec<String>,
+
+    /// Paths the script writes
+    pub write: Vec<String>,
+
+    /// Environment variables the script reads
+    pub env: Vec<String>,
+
+    /// System information the script queries, such as `hostname` or `osRelease`
+    pub sys: Vec<String>,
+
+    /// True if the script needs high-resolution timers
+    pub hrtime: bool,
+
+    /// True if the script runs subprocesses or native code
+    pub exec: bool,
+
+    /// Host functions the script calls, by name
+    pub functions: Vec<String>,
+
+    /// Secrets the script reads, by name
+    pub secrets: Vec<String>,
+
+    /// Any other entries, left for the host to interpret
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Manifest {
+    /// Returns the capabilities this manifest requests that `granted` does not include
+    ///
+    /// Entries are compared exactly, and entries in `other` by value - the result is empty if
+    /// everything requested was granted
+    #[must_use]
+    pub fn missing_from(&self, granted: &Manifest) -> Manifest {
+        let missing = |requested: &[String], granted: &[String]| {
+            requested
+                .iter()
+                .filter(|r| !granted.contains(r))
+                .cloned()
+                .collect()
+        };
+
+        Manifest {
+            net: missing(&self.net, &granted.net),
+            read: missing(&self.read, &granted.read),
+            write: missing(&self.write, &granted.write),
+            env: missing(&self.env, &granted.env),
+            sys: missing(&self.sys, &granted.sys),
+            hrtime: self.hrtime && !granted.hrtime,
+            exec: self.exec && !granted.exec,
+            functions: missing(&self.functions, &granted.functions),
+            secrets: missing(&self.secrets, &granted.secrets),
+            other: self
+                .other
+                .iter()
+                .filter(|(k, v)| granted.other.get(*k) != Some(*v))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns true if the manifest requests nothing
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Manifest::default()
+    }
+}
+
+/// Reads the capability manifest of a script, without running it
+///
+/// The manifest is taken from the module's `permissions` export if it has one, and otherwise from a
+/// JSON sidecar beside the module's file. Returns `None` if it has neither
+///
+/// # Errors
+/// Will return an error if the module cannot be parsed, if its `permissions` export is not a
+/// literal, or if the manifest is malformed
+///
+/// # Example
+///
+/// ```rust
+/// use rustyscript::{read_manifest, Module};
+///
+/// let module = Module::new("script.js", "export const permissions = { net: ['example.com'] };");
+/// let manifest = read_manifest(&module).unwrap().unwrap();
+/// assert_eq!(manifest.net, vec!["example.com"]);
+/// ```
+pub fn read_manifest(module: &Module) -> Result<Option<Manifest>, Error> {
+    let value = match exported_manifest(module)? {
+        Some(value) => value,
+        None => {
+            let sidecar = module.filename().with_extension("permissions.json");
+            match std::fs::read_to_string(&sidecar) {
+                Ok(text) => serde_json::from_str(&text).map_err(|e| {
+                    Error::Runtime(format!("Invalid manifest {}: {e}", sidecar.display()))
+                })?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => {
+                    return Err(Error::Runtime(format!(
+                        "Could not read manifest {}: {e}",
+                        sidecar.display()
+                    )))
+                }
+            }
+        }
+    };
+
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|e| Error::Runtime(format!("Invalid manifest for {module}: {e}")))
+}
+
+/// Finds the module's `permissions` export, as JSON
+fn exported_manifest(module: &Module) -> Result<Option<Value>, Error> {
+    let parsed = crate::module_interface::parse(module)?;
+    let ProgramRef::Module(program) = parsed.program_ref() else {
+        return Ok(None);
+    };
+
+    for item in &program.body {
+        let ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) = item else {
+            continue;
+        };
+        let Decl::Var(var) = &export.decl else {
+            continue;
+        };
+        for declarator in &var.decls {
+            let Pat::Ident(ident) = &declarator.name else {
+                continue;
+            };
+            if ident.id.sym.as_ref() != MANIFEST_EXPORT {
+                continue;
+            }
+
+            let value = declarator
+                .init
+                .as_deref()
+                .and_then(literal)
+                .ok_or_else(|| {
+                    Error::Runtime(format!(
+                        "The `{MANIFEST_EXPORT}` export of {module} must be a literal object"
+                    ))
+                })?;
+            return Ok(Some(value));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Converts a literal expression to JSON - returns `None` for anything that would need evaluating
+fn literal(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => Some(Value::String(s.value.to_string())),
+        Expr::Lit(Lit::Num(n)) => Number::from_f64(n.value).map(Value::Number),
+        Expr::Lit(Lit::Bool(b)) => Some(Value::Bool(b.value)),
+        Expr::Lit(Lit::Null(_)) => Some(Value::Null),
+        Expr::Unary(unary) if unary.op == UnaryOp::Minus => match literal(&unary.arg)? {
+            Value::Number(n) => Number::from_f64(-n.as_f64()?).map(Value::Number),
+            _ => None,
+        },
+        Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+            let quasi = tpl.quasis.first()?;
+            Some(Value::String(quasi.cooked.as_ref()?.to_string()))
+        }
+        Expr::Paren(paren) => literal(&paren.expr),
+        Expr::TsAs(ts_as) => literal(&ts_as.expr),
+        Expr::TsConstAssertion(assertion) => literal(&assertion.expr),
+        Expr::TsSatisfies(satisfies) => literal(&satisfies.expr),
+
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .map(|e| match e {
+                Some(e) if e.spread.is_none() => literal(&e.expr),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array),
+
+        Expr::Object(object) => {
+            let mut map = Map::new();
+            for prop in &object.props {
+                let PropOrSpread::Prop(prop) = prop else {
+                    return None;
+                };
+                let Prop::KeyValue(kv) = &**prop else {
+                    return None;
+                };
+                let key = match &kv.key {
+                    PropName::Ident(ident) => ident.sym.to_string(),
+                    PropName::Str(s) => s.value.to_string(),
+                    _ => return None,
+                };
+                map.insert(key, literal(&kv.value)?);
+            }
+            Some(Value::Object(map))
+        }
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_manifest() {
+        let module = Module::new(
+            "script.ts",
+            "
+            export const permissions = {
+                net: ['api.example.com'],
+                env: [`API_KEY`],
+                hrtime: true,
+                'queue': { max: -1 },
+            } as const;
+            export default () => 1;
+            ",
+        );
+        let manifest = read_manifest(&module).unwrap().unwrap();
+        assert_eq!(manifest.net, vec!["api.example.com"]);
+        assert_eq!(manifest.env, vec!["API_KEY"]);
+        assert!(manifest.hrtime);
+        assert_eq!(manifest.other["queue"], serde_json::json!({ "max": -1.0 }));
+
+        let granted = Manifest {
+            net: vec!["api.example.com".to_string()],
+            ..Default::default()
+        };
+        let missing = manifest.missing_from(&granted);
+        assert!(missing.net.is_empty());
+        assert_eq!(missing.env, vec!["API_KEY"]);
+        assert!(missing.hrtime);
+        assert!(!missing.is_empty());
+        assert!(manifest.missing_from(&manifest).is_empty());
+
+        // Only literals can be read without running the script
+        let module = Module::new(
+            "script.js",
+            "export const permissions = { net: [process.env.HOST] };",
+        );
+        assert!(read_manifest(&module).is_err());
+
+        // No manifest, and no sidecar
+        let module = Module::new("missing/script.js", "export const x = 1;");
+        assert!(read_manifest(&module).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sidecar_manifest() {
+        let dir = std::env::temp_dir().join("rustyscript_test_sidecar_manifest");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("script.js");
+        std::fs::write(&path, "export default () => 1;").unwrap();
+        std::fs::write(
+            dir.join("script.permissions.json"),
+            r#"{ "functions": ["send_email"], "exec": true }"#,
+        )
+        .unwrap();
+
+        let module = Module::load(&path).unwrap();
+        let manifest = read_manifest(&module).unwrap().unwrap();
+        assert_eq!(manifest.functions, vec!["send_email"]);
+        assert!(manifest.exec);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
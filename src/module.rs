@@ -9,6 +9,8 @@ use std::{
 use maybe_path::MaybePathBuf;
 use serde::{Deserialize, Serialize};
 
+use crate::ModuleBudget;
+
 /// Creates a static module
 ///
 /// This is just a macro around [`Module::new_static`]
@@ -60,6 +62,12 @@ macro_rules! include_module {
 pub struct Module {
     filename: MaybePathBuf<'static>,
     contents: Cow<'static, str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget: Option<ModuleBudget>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minify: Option<bool>,
 }
 
 impl<'de> Deserialize<'de> for Module {
@@ -71,10 +79,22 @@ impl<'de> Deserialize<'de> for Module {
         struct OwnedModule {
             filename: PathBuf,
             contents: String,
+            #[serde(default)]
+            budget: Option<ModuleBudget>,
+            #[serde(default)]
+            minify: Option<bool>,
         }
 
-        let OwnedModule { filename, contents } = OwnedModule::deserialize(deserializer)?;
-        Ok(Module::new(filename, contents))
+        let OwnedModule {
+            filename,
+            contents,
+            budget,
+            minify,
+        } = OwnedModule::deserialize(deserializer)?;
+        let mut module = Module::new(filename, contents);
+        module.budget = budget;
+        module.minify = minify;
+        Ok(module)
     }
 }
 
@@ -108,7 +128,12 @@ impl Module {
         let filename = MaybePathBuf::Owned(filename.as_ref().to_path_buf());
         let contents = Cow::Owned(contents.to_string());
 
-        Self { filename, contents }
+        Self {
+            filename,
+            contents,
+            budget: None,
+            minify: None,
+        }
     }
 
     /// Creates a new `Module` instance with the given filename and contents.  
@@ -135,6 +160,8 @@ impl Module {
         Self {
             filename: MaybePathBuf::new_str(filename),
             contents: Cow::Borrowed(contents),
+            budget: None,
+            minify: None,
         }
     }
 
@@ -245,6 +272,113 @@ impl Module {
     pub fn contents(&self) -> &str {
         &self.contents
     }
+
+    /// Attaches resource limits to the module, enforced while it is loaded
+    ///
+    /// These apply to the module's top-level code, separately from per-call limits
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{Module, ModuleBudget};
+    /// use std::time::Duration;
+    ///
+    /// let module = Module::new("module.js", "export const value = 42;").with_budget(ModuleBudget {
+    ///     max_eval_time: Some(Duration::from_millis(100)),
+    ///     ..Default::default()
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_budget(mut self, budget: ModuleBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Returns the resource limits attached to the module, if any
+    #[must_use]
+    pub fn budget(&self) -> Option<&ModuleBudget> {
+        self.budget.as_ref()
+    }
+
+    /// Minify the module after it is transpiled, or not, overriding [`crate::RuntimeOptions::minify`]
+    ///
+    /// Minified modules are emitted without whitespace or comments, and without a source map -
+    /// errors will point into the minified code
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// let module = Module::new("vendor.js", "export const value = 42;").with_minify(true);
+    /// ```
+    #[must_use]
+    pub fn with_minify(mut self, minify: bool) -> Self {
+        self.minify = Some(minify);
+        self
+    }
+
+    /// Returns a copy of this module without the top-level declarations that none of `exports` can
+    /// reach
+    ///
+    /// Shrinks large libraries down to the parts a host uses - before they are baked into a snapshot,
+    /// for example. Statements that may have side effects, imports, and `export *` are always kept.
+    /// Only this module is shaken; the modules it imports are left as they are
+    ///
+    /// # Errors
+    /// Will return an error if the module cannot be parsed, or does not export one of `exports`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new(
+    ///     "lib.js",
+    ///     "export const used = () => 1; export const unused = () => 2;",
+    /// );
+    /// let shaken = module.tree_shaken(&["used"])?;
+    /// assert!(!shaken.contents().contains("unused"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tree_shaken(&self, exports: &[&str]) -> Result<Self, crate::Error> {
+        let contents = crate::tree_shake::tree_shake(self, exports)?;
+        Ok(Self {
+            filename: self.filename.clone(),
+            contents: Cow::Owned(contents),
+            budget: self.budget,
+            minify: self.minify,
+        })
+    }
+
+    /// Returns whether the module overrides the runtime's minification setting
+    #[must_use]
+    pub fn minify(&self) -> Option<bool> {
+        self.minify
+    }
+
+    /// Describes the functions the module exports, with their parameter types as JSON Schema
+    ///
+    /// The module is parsed, but not run - see [`crate::FunctionDescriptor`]
+    ///
+    /// # Errors
+    /// Will return an error if the module cannot be parsed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// let module = Module::new("module.ts", "export function f(n: number): string { return `${n}`; }");
+    /// let functions = module.exported_functions().unwrap();
+    /// assert_eq!(functions[0].name, "f");
+    /// assert_eq!(functions[0].params[0].schema["type"], "number");
+    /// ```
+    pub fn exported_functions(&self) -> Result<Vec<crate::FunctionDescriptor>, crate::Error> {
+        crate::module_interface::exported_functions(self)
+    }
 }
 
 #[cfg(test)]
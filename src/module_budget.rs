@@ -0,0 +1,422 @@
+//! Limits on the modules loaded into a runtime
+//!
+//! Hostile code often does its damage at the top level of a module, before any function is called.
+//! A [`ModuleBudget`] bounds that phase separately from per-call limits like [`crate::RuntimeOptions::timeout`],
+//! while [`ModuleLimits`] caps how much code a runtime will accept overall - including from dynamic imports
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use deno_core::{v8, JsRuntime};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Module};
+
+/// Resource limits for loading a single module - see [`Module::with_budget`]
+///
+/// Time limits interrupt the module's top-level code if it runs past them.
+/// Heap growth is measured once top-level evaluation has settled, so it is not a hard cap -
+/// use [`crate::RuntimeOptions::max_heap_size`] as a backstop
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModuleBudget {
+    /// Maximum time to transpile, link and evaluate the module, including its static imports
+    pub max_load_time: Option<Duration>,
+
+    /// Maximum time for the module's top-level code to run, including top-level `await`
+    pub max_eval_time: Option<Duration>,
+
+    /// Maximum growth of the used heap, in bytes, between starting and finishing the load
+    pub max_heap_growth: Option<usize>,
+}
+
+impl ModuleBudget {
+    /// Returns true if no limits are set
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_load_time.is_none()
+            && self.max_eval_time.is_none()
+            && self.max_heap_growth.is_none()
+    }
+}
+
+/// Runtime-wide caps on loaded code - see [`crate::RuntimeOptions::module_limits`]
+///
+/// These cover every module the runtime loads, whether from the host, static imports or dynamic imports.
+/// Exceeding one fails the load with [`Error::ModuleLimitExceeded`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModuleLimits {
+    /// Maximum number of modules the runtime will load
+    pub max_modules: Option<usize>,
+
+    /// Maximum total size, in bytes, of the transpiled source of all loaded modules
+    pub max_total_source_bytes: Option<usize>,
+
+    /// Maximum size, in bytes, of the transpiled source of a single module
+    pub max_module_size: Option<usize>,
+}
+
+/// Identifies which of the [`ModuleLimits`] was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModuleLimit {
+    /// [`ModuleLimits::max_modules`]
+    ModuleCount,
+
+    /// [`ModuleLimits::max_total_source_bytes`]
+    TotalSourceSize,
+
+    /// [`ModuleLimits::max_module_size`]
+    ModuleSize,
+}
+
+impl std::fmt::Display for ModuleLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ModuleCount => write!(f, "maximum module count"),
+            Self::TotalSourceSize => write!(f, "maximum total source size"),
+            Self::ModuleSize => write!(f, "maximum module size"),
+        }
+    }
+}
+
+/// The modules loaded into a runtime so far - see [`crate::Runtime::module_usage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ModuleUsage {
+    /// Number of modules loaded
+    pub modules: usize,
+
+    /// Total size, in bytes, of their transpiled source
+    pub source_bytes: usize,
+}
+
+impl ModuleUsage {
+    /// Records a module of the given size, unless doing so would exceed the limits
+    pub(crate) fn record(
+        &mut self,
+        limits: &ModuleLimits,
+        specifier: &str,
+        size: usize,
+    ) -> Result<(), Error> {
+        let exceeded = if limits.max_module_size.is_some_and(|max| size > max) {
+            Some(ModuleLimit::ModuleSize)
+        } else if limits.max_modules.is_some_and(|max| self.modules >= max) {
+            Some(ModuleLimit::ModuleCount)
+        } else if limits
+            .max_total_source_bytes
+            .is_some_and(|max| self.source_bytes + size > max)
+        {
+            Some(ModuleLimit::TotalSourceSize)
+        } else {
+            None
+        };
+
+        if let Some(limit) = exceeded {
+            return Err(Error::ModuleLimitExceeded(limit, specifier.to_string()));
+        }
+
+        self.modules += 1;
+        self.source_bytes += size;
+        Ok(())
+    }
+}
+
+/// Terminates the isolate at a deadline, unless disarmed first
+struct Terminator {
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    fired: Arc<AtomicBool>,
+}
+
+impl Terminator {
+    fn arm(isolate: v8::IsolateHandle, deadline: Instant) -> Self {
+        let (cancel, rx) = channel::<()>();
+        let fired = Arc::new(AtomicBool::new(false));
+        let thread_fired = fired.clone();
+        let thread = std::thread::spawn(move || {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(wait) {
+                thread_fired.store(true, Ordering::SeqCst);
+                isolate.terminate_execution();
+            }
+        });
+
+        Self {
+            cancel: Some(cancel),
+            thread: Some(thread),
+            fired,
+        }
+    }
+
+    /// Stops the timer, returning true if it had already fired
+    fn disarm(mut self) -> bool {
+        self.cancel.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+/// Tracks a module's budget over the course of its load
+pub(crate) struct BudgetGuard {
+    module: String,
+    budget: ModuleBudget,
+    load_started: Instant,
+    eval_started: Option<Instant>,
+    heap_before: usize,
+    terminator: Option<Terminator>,
+    fired: bool,
+}
+
+impl BudgetGuard {
+    /// Begins tracking the load of a module - a no-op if the module has no budget
+    pub fn start(runtime: &mut JsRuntime, module: &Module) -> Self {
+        let budget = module.budget().copied().unwrap_or_default();
+        let mut guard = Self {
+            module: module.filename().to_string_lossy().to_string(),
+            budget,
+            load_started: Instant::now(),
+            eval_started: None,
+            heap_before: 0,
+            terminator: None,
+            fired: false,
+        };
+
+        if budget.max_heap_growth.is_some() {
+            guard.heap_before = used_heap(runtime);
+        }
+        guard.rearm(runtime);
+        guard
+    }
+
+    /// Marks the start of top-level evaluation
+    pub fn begin_eval(&mut self, runtime: &mut JsRuntime) {
+        self.eval_started = Some(Instant::now());
+        self.rearm(runtime);
+    }
+
+    /// Runs part of the load, failing if it is still pending when the budget runs out
+    pub async fn limit<T>(
+        &self,
+        future: impl std::future::Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        match self.deadline() {
+            Some(deadline) => {
+                let deadline = tokio::time::Instant::from_std(deadline);
+                match tokio::time::timeout_at(deadline, future).await {
+                    Ok(result) => result,
+                    Err(_) => Err(self.exceeded()),
+                }
+            }
+            None => future.await,
+        }
+    }
+
+    /// Ends tracking, restoring the runtime if its execution was interrupted,
+    /// and checks the limits that can only be measured after the fact
+    pub fn finish<T>(
+        mut self,
+        runtime: &mut JsRuntime,
+        result: Result<T, Error>,
+    ) -> Result<T, Error> {
+        if let Some(terminator) = self.terminator.take() {
+            self.fired |= terminator.disarm();
+        }
+
+        if self.fired {
+            runtime.v8_isolate().cancel_terminate_execution();
+            return Err(self.exceeded());
+        }
+
+        let value = result?;
+        if let Some(max) = self.budget.max_heap_growth {
+            let growth = used_heap(runtime).saturating_sub(self.heap_before);
+            if growth > max {
+                return Err(Error::ModuleBudgetExceeded(
+                    self.module,
+                    format!("heap grew by {growth} bytes, over the limit of {max}"),
+                ));
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// The earliest of the active time limits
+    fn deadline(&self) -> Option<Instant> {
+        let load = self.budget.max_load_time.map(|max| self.load_started + max);
+        let eval = self
+            .eval_started
+            .zip(self.budget.max_eval_time)
+            .map(|(started, max)| started + max);
+
+        match (load, eval) {
+            (Some(load), Some(eval)) => Some(load.min(eval)),
+            (deadline, None) | (None, deadline) => deadline,
+        }
+    }
+
+    fn rearm(&mut self, runtime: &mut JsRuntime) {
+        if let Some(terminator) = self.terminator.take() {
+            self.fired |= terminator.disarm();
+        }
+
+        if let Some(deadline) = self.deadline() {
+            let isolate = runtime.v8_isolate().thread_safe_handle();
+            self.terminator = Some(Terminator::arm(isolate, deadline));
+        }
+    }
+
+    fn exceeded(&self) -> Error {
+        let eval_over = self
+            .eval_started
+            .zip(self.budget.max_eval_time)
+            .filter(|(started, max)| started.elapsed() >= *max);
+
+        let reason = match (eval_over, self.budget.max_load_time) {
+            (Some((_, max)), _) => format!("top-level evaluation took longer than {max:?}"),
+            (None, Some(max)) => format!("loading took longer than {max:?}"),
+            (None, None) => "time budget exhausted".to_string(),
+        };
+        Error::ModuleBudgetExceeded(self.module.clone(), reason)
+    }
+}
+
+fn used_heap(runtime: &mut JsRuntime) -> usize {
+    runtime.v8_isolate().get_heap_statistics().used_heap_size()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_module_budget() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+
+        // Synchronous top-level code is interrupted
+        let spin = Module::new("spin.js", "while (true) {}").with_budget(ModuleBudget {
+            max_eval_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let e = runtime.load_module(&spin).unwrap_err();
+        assert!(matches!(e, Error::ModuleBudgetExceeded(..)), "{e}");
+        assert!(e.to_string().contains("top-level evaluation"));
+
+        // As is top-level await
+        let wait = Module::new(
+            "wait.js",
+            "await new Promise((resolve) => setTimeout(resolve, 500));",
+        )
+        .with_budget(ModuleBudget {
+            max_load_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let e = runtime.load_module(&wait).unwrap_err();
+        assert!(e.to_string().contains("loading took longer"), "{e}");
+
+        let hog = Module::new(
+            "hog.js",
+            "globalThis.hog = Array.from({ length: 1_000_000 }, (_, i) => ({ i }));",
+        )
+        .with_budget(ModuleBudget {
+            max_heap_growth: Some(1024 * 1024),
+            ..Default::default()
+        });
+        let e = runtime.load_module(&hog).unwrap_err();
+        assert!(e.to_string().contains("heap grew"), "{e}");
+
+        // The runtime is still usable, and modules within budget load normally
+        let ok = Module::new("ok.js", "export const value = 1 + 1;").with_budget(ModuleBudget {
+            max_load_time: Some(Duration::from_secs(5)),
+            max_eval_time: Some(Duration::from_secs(5)),
+            max_heap_growth: Some(16 * 1024 * 1024),
+        });
+        let handle = runtime.load_module(&ok).unwrap();
+        let value: i32 = runtime.get_value(Some(&handle), "value").unwrap();
+        assert_eq!(value, 2);
+    }
+
+    /// Serves `virtual:` imports from memory
+    struct VirtualImports;
+    impl crate::module_loader::ImportProvider for VirtualImports {
+        fn resolve(
+            &mut self,
+            specifier: &deno_core::ModuleSpecifier,
+            _: &str,
+            _: deno_core::ResolutionKind,
+        ) -> Option<Result<deno_core::ModuleSpecifier, deno_core::error::ModuleLoaderError>>
+        {
+            (specifier.scheme() == "virtual").then(|| Ok(specifier.clone()))
+        }
+
+        fn import(
+            &mut self,
+            specifier: &deno_core::ModuleSpecifier,
+            _: Option<&deno_core::ModuleSpecifier>,
+            _: bool,
+            _: deno_core::RequestedModuleType,
+        ) -> Option<Result<String, deno_core::error::ModuleLoaderError>> {
+            (specifier.scheme() == "virtual").then(|| Ok("export default 1;".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_module_limits() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            module_limits: ModuleLimits {
+                max_modules: Some(3),
+                max_module_size: Some(1024),
+                ..Default::default()
+            },
+            import_provider: Some(Box::new(VirtualImports)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let big = Module::new(
+            "big.js",
+            format!("export const s = '{}';", "x".repeat(2048)),
+        );
+        let e = runtime.load_module(&big).unwrap_err();
+        assert!(
+            matches!(e, Error::ModuleLimitExceeded(ModuleLimit::ModuleSize, _)),
+            "{e}"
+        );
+
+        runtime
+            .load_module(&Module::new("one.js", "export const a = 1;"))
+            .unwrap();
+        let usage = runtime.module_usage();
+        assert_eq!(usage.modules, 1);
+        assert!(usage.source_bytes > 0);
+
+        // Dynamic imports count too
+        let chain = Module::new(
+            "chain.js",
+            "export const load = (n) => import(`virtual:${n}`);",
+        );
+        let handle = runtime.load_module(&chain).unwrap();
+        runtime
+            .call_function::<crate::js_value::Value>(Some(&handle), "load", crate::json_args!(1))
+            .unwrap();
+        let e = runtime
+            .call_function::<crate::js_value::Value>(Some(&handle), "load", crate::json_args!(2))
+            .unwrap_err();
+        assert!(e.to_string().contains("maximum module count"), "{e}");
+
+        let e = runtime
+            .load_module(&Module::new("two.js", "export const b = 2;"))
+            .unwrap_err();
+        assert!(
+            matches!(e, Error::ModuleLimitExceeded(ModuleLimit::ModuleCount, _)),
+            "{e}"
+        );
+    }
+}
@@ -1,6 +1,143 @@
-use deno_core::{v8, ModuleId};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use crate::Module;
+use deno_core::{v8, JsRuntime, ModuleId};
+
+use crate::{Error, Module};
+
+/// Limits on how often the functions of a module may be called
+///
+/// See [`ModuleHandle::with_call_quota`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CallQuota {
+    /// The most calls to a function that may be in progress at once
+    ///
+    /// A call remains in progress until the promise it returned, if any, has settled
+    pub max_concurrent: Option<usize>,
+
+    /// The most calls to a function that may start in any one-second window
+    pub max_per_second: Option<usize>,
+}
+
+/// The name entrypoint calls are counted under
+pub(crate) const ENTRYPOINT_CALL: &str = "<entrypoint>";
+
+/// The calls made to one function through a handle
+#[derive(Default)]
+struct FunctionCalls {
+    running: usize,
+    pending: Vec<v8::Global<v8::Promise>>,
+    recent: VecDeque<Instant>,
+}
+
+/// Enforces a [`CallQuota`], shared by every clone of a handle
+pub(crate) struct CallTracker {
+    quota: CallQuota,
+    calls: RefCell<HashMap<String, FunctionCalls>>,
+}
+
+impl CallTracker {
+    /// Admits a call to `name`, or fails with [`Error::QuotaExceeded`]
+    ///
+    /// The call counts as in progress until the returned permit is finished or dropped
+    pub fn admit(
+        self: &Rc<Self>,
+        runtime: &mut JsRuntime,
+        name: &str,
+    ) -> Result<CallPermit, Error> {
+        let mut calls = self.calls.borrow_mut();
+        let calls = calls.entry(name.to_string()).or_default();
+
+        // Forget promises that have settled since the last call
+        if !calls.pending.is_empty() {
+            deno_core::scope!(scope, runtime);
+            calls
+                .pending
+                .retain(|p| v8::Local::new(scope, p).state() == v8::PromiseState::Pending);
+        }
+
+        if let Some(max) = self.quota.max_concurrent {
+            if calls.running + calls.pending.len() >= max {
+                return Err(Error::QuotaExceeded(format!(
+                    "`{name}` already has {max} calls in progress"
+                )));
+            }
+        }
+
+        if let Some(max) = self.quota.max_per_second {
+            let now = Instant::now();
+            while calls
+                .recent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(1))
+            {
+                calls.recent.pop_front();
+            }
+            if calls.recent.len() >= max {
+                return Err(Error::QuotaExceeded(format!(
+                    "`{name}` is limited to {max} calls per second"
+                )));
+            }
+            calls.recent.push_back(now);
+        }
+
+        calls.running += 1;
+        Ok(CallPermit {
+            tracker: self.clone(),
+            name: name.to_string(),
+        })
+    }
+}
+
+// Clones of a handle share their tracker - handles are equal only if they share it too
+impl PartialEq for CallTracker {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+impl Eq for CallTracker {}
+
+impl std::fmt::Debug for CallTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallTracker")
+            .field("quota", &self.quota)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A call admitted by a [`CallTracker`]
+pub(crate) struct CallPermit {
+    tracker: Rc<CallTracker>,
+    name: String,
+}
+
+impl CallPermit {
+    /// Ends the call - if it returned a pending promise, it stays in progress until that settles
+    pub fn finish(self, runtime: &mut JsRuntime, value: &v8::Global<v8::Value>) {
+        deno_core::scope!(scope, runtime);
+        let local = v8::Local::new(scope, value);
+        if let Ok(promise) = v8::Local::<v8::Promise>::try_from(local) {
+            if promise.state() == v8::PromiseState::Pending {
+                let promise = v8::Global::new(scope, promise);
+                if let Some(calls) = self.tracker.calls.borrow_mut().get_mut(&self.name) {
+                    calls.pending.push(promise);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for CallPermit {
+    fn drop(&mut self) {
+        if let Some(calls) = self.tracker.calls.borrow_mut().get_mut(&self.name) {
+            calls.running -= 1;
+        }
+    }
+}
 
 /// Represents a loaded instance of a module within a runtime
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
@@ -8,6 +145,7 @@ pub struct ModuleHandle {
     entrypoint: Option<v8::Global<v8::Function>>,
     module_id: ModuleId,
     module: Module,
+    quota: Option<Rc<CallTracker>>,
 }
 
 impl ModuleHandle {
@@ -21,6 +159,7 @@ impl ModuleHandle {
             module_id,
             entrypoint,
             module: module.clone(),
+            quota: None,
         }
     }
 
@@ -56,4 +195,105 @@ impl ModuleHandle {
     pub fn entrypoint(&self) -> &Option<v8::Global<v8::Function>> {
         &self.entrypoint
     }
+
+    /// Limit how often the module's functions may be called through this handle, and its clones
+    ///
+    /// Applies to the entrypoint, and to functions called by name with this handle as the module
+    /// context. Each function is counted separately; calls over the quota fail with
+    /// [`Error::QuotaExceeded`] without running
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, CallQuota, Error, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export function f() { return 2; }");
+    /// let module = runtime.load_module(&module)?.with_call_quota(CallQuota {
+    ///     max_per_second: Some(1),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// let value: usize = runtime.call_function(Some(&module), "f", json_args!())?;
+    /// assert!(runtime.call_function::<usize>(Some(&module), "f", json_args!()).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_call_quota(mut self, quota: CallQuota) -> Self {
+        self.quota = Some(Rc::new(CallTracker {
+            quota,
+            calls: RefCell::default(),
+        }));
+        self
+    }
+
+    /// Return this module's call quota, if it has one
+    #[must_use]
+    pub fn call_quota(&self) -> Option<CallQuota> {
+        self.quota.as_ref().map(|tracker| tracker.quota)
+    }
+
+    /// Admit a call to one of the module's functions against its quota
+    pub(crate) fn admit_call(
+        &self,
+        runtime: &mut JsRuntime,
+        name: &str,
+    ) -> Result<Option<CallPermit>, Error> {
+        self.quota
+            .as_ref()
+            .map(|tracker| tracker.admit(runtime, name))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{js_value::Promise, json_args, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_call_quota() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            export const wait = () => new Promise((resolve) => setTimeout(resolve, 10));
+            export const add = (a, b) => a + b;
+            ",
+        );
+        let module = runtime
+            .load_module(&module)
+            .unwrap()
+            .with_call_quota(CallQuota {
+                max_concurrent: Some(1),
+                max_per_second: Some(2),
+            });
+
+        // The first call is in progress until its promise settles
+        let pending: Promise<()> = runtime
+            .call_function_immediate(Some(&module), "wait", json_args!())
+            .unwrap();
+        let e = runtime
+            .call_function_immediate::<Promise<()>>(Some(&module), "wait", json_args!())
+            .unwrap_err();
+        assert!(matches!(e, Error::QuotaExceeded(_)), "{e}");
+
+        // Other functions are counted separately
+        let sum: usize = runtime
+            .call_function(Some(&module), "add", json_args!(1, 2))
+            .unwrap();
+        assert_eq!(sum, 3);
+
+        pending.into_value(&mut runtime).unwrap();
+        runtime
+            .call_function::<()>(Some(&module), "wait", json_args!())
+            .unwrap();
+
+        // Clones share the quota
+        let clone = module.clone();
+        let e = runtime
+            .call_function::<()>(Some(&clone), "wait", json_args!())
+            .unwrap_err();
+        assert!(e.to_string().contains("2 calls per second"), "{e}");
+    }
 }
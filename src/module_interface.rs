@@ -0,0 +1,689 @@
+//! Machine-readable descriptions of the functions a module exports
+//!
+//! Signatures are read from the module's syntax tree, without running it. Parameter and return
+//! types written in TypeScript are translated to JSON Schema; anything without an annotation, or
+//! with a type that has no JSON equivalent, is described by the empty schema `{}`, which accepts
+//! any value
+use std::{cell::Cell, collections::HashMap};
+
+use deno_ast::{
+    swc::ast::{
+        Decl, DefaultDecl, ExportSpecifier, Expr, Function, Lit, ModuleDecl, ModuleExportName,
+        ModuleItem, Pat, TsEntityName, TsKeywordTypeKind, TsLit, TsTupleType, TsType, TsTypeAnn,
+        TsTypeElement, TsTypeRef, TsUnionOrIntersectionType,
+    },
+    MediaType, ParseParams, ProgramRef,
+};
+use deno_core::serde_json::{json, Map, Number, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{traits::ToModuleSpecifier, Error, Module};
+
+/// How deeply type aliases and interfaces are expanded, to guard against recursive types
+const MAX_DEPTH: usize = 16;
+
+/// How many schema nodes are generated for each function, to guard against aliases that refer to
+/// each other more than once, and so grow exponentially with depth
+const MAX_NODES: usize = 4096;
+
+/// A function exported by a module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDescriptor {
+    /// The name the function is exported under - `default` for a default export
+    pub name: String,
+
+    /// The function's parameters, in order
+    pub params: Vec<ParamDescriptor>,
+
+    /// A schema for the function's return value - for async functions, the value it resolves to
+    pub returns: Value,
+
+    /// True if the function is declared `async`, or returns a `Promise`
+    pub is_async: bool,
+}
+
+impl FunctionDescriptor {
+    /// A schema for the function's arguments, as an array - suitable for validating `json_args!`
+    #[must_use]
+    pub fn args_schema(&self) -> Value {
+        let mut prefix = vec![];
+        let mut rest = None;
+        for param in &self.params {
+            if param.rest {
+                rest = Some(param.schema.get("items").cloned().unwrap_or(json!({})));
+            } else {
+                prefix.push(param.schema.clone());
+            }
+        }
+
+        let required = self
+            .params
+            .iter()
+            .take_while(|p| !p.optional && !p.rest)
+            .count();
+        let mut schema = json!({
+            "type": "array",
+            "prefixItems": prefix,
+            "minItems": required,
+            "items": rest.unwrap_or(Value::Bool(false)),
+        });
+        if self.params.iter().all(|p| !p.rest) {
+            schema["maxItems"] = json!(self.params.len());
+        }
+        schema
+    }
+}
+
+/// A parameter of an exported function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamDescriptor {
+    /// The parameter's name - `arg0`, `arg1`, etc for destructured parameters
+    pub name: String,
+
+    /// A schema for the parameter's value - for rest parameters, an array of the values
+    pub schema: Value,
+
+    /// True if the parameter is optional, or has a default value
+    pub optional: bool,
+
+    /// True for a rest parameter (`...args`)
+    pub rest: bool,
+}
+
+/// Parses a module without transpiling it, to read its syntax tree
+pub(crate) fn parse(module: &Module) -> Result<deno_ast::ParsedSource, Error> {
+    let specifier = module
+        .filename()
+        .to_module_specifier(&std::env::current_dir()?)?;
+    let media_type = match MediaType::from_specifier(&specifier) {
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+    deno_ast::parse_module(ParseParams {
+        specifier,
+        text: module.contents().into(),
+        media_type,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })
+    .map_err(|e| Error::Runtime(e.to_string()))
+}
+
+/// Reads the signatures of the functions a module exports
+pub(crate) fn exported_functions(module: &Module) -> Result<Vec<FunctionDescriptor>, Error> {
+    let parsed = parse(module)?;
+    let ProgramRef::Module(program) = parsed.program_ref() else {
+        return Ok(vec![]);
+    };
+
+    let (types, locals) = declarations(&program.body);
+    let mut functions = vec![];
+    for item in &program.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        match decl {
+            ModuleDecl::ExportDecl(export) => match &export.decl {
+                Decl::Fn(f) => {
+                    let name = f.ident.sym.to_string();
+                    functions.push(types.describe(name, Callable::Function(&f.function)));
+                }
+                Decl::Var(var) => {
+                    for declarator in &var.decls {
+                        if let (Pat::Ident(ident), Some(init)) =
+                            (&declarator.name, &declarator.init)
+                        {
+                            if let Some(callable) = Callable::from_expr(init) {
+                                functions.push(types.describe(ident.id.sym.to_string(), callable));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            },
+
+            ModuleDecl::ExportDefaultDecl(export) => {
+                if let DefaultDecl::Fn(f) = &export.decl {
+                    functions.push(
+                        types.describe("default".to_string(), Callable::Function(&f.function)),
+                    );
+                }
+            }
+
+            ModuleDecl::ExportDefaultExpr(export) => {
+                let callable = match &*export.expr {
+                    Expr::Ident(ident) => locals.get(ident.sym.as_ref()).copied(),
+                    expr => Callable::from_expr(expr),
+                };
+                if let Some(callable) = callable {
+                    functions.push(types.describe("default".to_string(), callable));
+                }
+            }
+
+            // `export { a, b as c }` - re-exports from other modules are not followed
+            ModuleDecl::ExportNamed(export) if export.src.is_none() => {
+                for specifier in &export.specifiers {
+                    let ExportSpecifier::Named(specifier) = specifier else {
+                        continue;
+                    };
+                    let ModuleExportName::Ident(orig) = &specifier.orig else {
+                        continue;
+                    };
+                    let name = match &specifier.exported {
+                        Some(ModuleExportName::Ident(exported)) => exported.sym.to_string(),
+                        Some(ModuleExportName::Str(exported)) => exported.value.to_string(),
+                        None => orig.sym.to_string(),
+                    };
+                    if let Some(callable) = locals.get(orig.sym.as_ref()) {
+                        functions.push(types.describe(name, *callable));
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(functions)
+}
+
+/// The module's type declarations, and its top-level functions by name
+///
+/// Named exports and type references may refer to either
+fn declarations(body: &[ModuleItem]) -> (Types<'_>, HashMap<String, Callable<'_>>) {
+    let mut types = Types::default();
+    let mut locals = HashMap::new();
+    for item in body {
+        let decl = match item {
+            ModuleItem::Stmt(stmt) => stmt.as_decl(),
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => Some(&export.decl),
+            ModuleItem::ModuleDecl(_) => None,
+        };
+        match decl {
+            Some(Decl::TsTypeAlias(alias)) => {
+                types
+                    .aliases
+                    .insert(alias.id.sym.to_string(), &*alias.type_ann);
+            }
+            Some(Decl::TsInterface(interface)) => {
+                types
+                    .interfaces
+                    .insert(interface.id.sym.to_string(), &interface.body.body);
+            }
+            Some(Decl::Fn(f)) => {
+                locals.insert(f.ident.sym.to_string(), Callable::Function(&f.function));
+            }
+            Some(Decl::Var(var)) => {
+                for declarator in &var.decls {
+                    if let (Pat::Ident(ident), Some(init)) = (&declarator.name, &declarator.init) {
+                        if let Some(callable) = Callable::from_expr(init) {
+                            locals.insert(ident.id.sym.to_string(), callable);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (types, locals)
+}
+
+/// Something with a call signature
+#[derive(Clone, Copy)]
+enum Callable<'a> {
+    Function(&'a Function),
+    Arrow(&'a deno_ast::swc::ast::ArrowExpr),
+}
+
+impl<'a> Callable<'a> {
+    fn from_expr(expr: &'a Expr) -> Option<Self> {
+        match expr {
+            Expr::Fn(f) => Some(Self::Function(&f.function)),
+            Expr::Arrow(arrow) => Some(Self::Arrow(arrow)),
+            Expr::Paren(paren) => Self::from_expr(&paren.expr),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> Vec<&'a Pat> {
+        match self {
+            Self::Function(f) => f.params.iter().map(|p| &p.pat).collect(),
+            Self::Arrow(arrow) => arrow.params.iter().collect(),
+        }
+    }
+
+    fn return_type(&self) -> Option<&'a TsTypeAnn> {
+        match self {
+            Self::Function(f) => f.return_type.as_deref(),
+            Self::Arrow(arrow) => arrow.return_type.as_deref(),
+        }
+    }
+
+    fn is_async(&self) -> bool {
+        match self {
+            Self::Function(f) => f.is_async,
+            Self::Arrow(arrow) => arrow.is_async,
+        }
+    }
+}
+
+/// The type aliases and interfaces declared by a module
+#[derive(Default)]
+struct Types<'a> {
+    aliases: HashMap<String, &'a TsType>,
+    interfaces: HashMap<String, &'a Vec<TsTypeElement>>,
+
+    /// Schema nodes left to generate for the function being described - see [`MAX_NODES`]
+    budget: Cell<usize>,
+}
+
+impl Types<'_> {
+    fn describe(&self, name: String, callable: Callable) -> FunctionDescriptor {
+        self.budget.set(MAX_NODES);
+        let params = callable
+            .params()
+            .into_iter()
+            .enumerate()
+            .map(|(i, pat)| self.param(i, pat))
+            .collect();
+
+        let mut is_async = callable.is_async();
+        let returns = match callable.return_type().map(|t| &*t.type_ann) {
+            Some(ty) => match promised(ty) {
+                Some(resolved) => {
+                    is_async = true;
+                    resolved.map_or(json!({}), |ty| self.schema(ty, 0))
+                }
+                None => self.schema(ty, 0),
+            },
+            None => json!({}),
+        };
+
+        FunctionDescriptor {
+            name,
+            params,
+            returns,
+            is_async,
+        }
+    }
+
+    fn param(&self, index: usize, pat: &Pat) -> ParamDescriptor {
+        let annotated = |type_ann: &Option<Box<TsTypeAnn>>| {
+            type_ann
+                .as_ref()
+                .map_or(json!({}), |t| self.schema(&t.type_ann, 0))
+        };
+        let unnamed = || format!("arg{index}");
+
+        match pat {
+            Pat::Ident(ident) => ParamDescriptor {
+                name: ident.id.sym.to_string(),
+                schema: annotated(&ident.type_ann),
+                optional: ident.id.optional,
+                rest: false,
+            },
+            Pat::Assign(assign) => ParamDescriptor {
+                optional: true,
+                ..self.param(index, &assign.left)
+            },
+            Pat::Rest(rest) => {
+                let name = match &*rest.arg {
+                    Pat::Ident(ident) => ident.id.sym.to_string(),
+                    _ => unnamed(),
+                };
+                let schema = match &rest.type_ann {
+                    Some(_) => annotated(&rest.type_ann),
+                    None => json!({ "type": "array" }),
+                };
+                ParamDescriptor {
+                    name,
+                    schema,
+                    optional: true,
+                    rest: true,
+                }
+            }
+            Pat::Object(object) => ParamDescriptor {
+                name: unnamed(),
+                schema: annotated(&object.type_ann),
+                optional: object.optional,
+                rest: false,
+            },
+            Pat::Array(array) => ParamDescriptor {
+                name: unnamed(),
+                schema: annotated(&array.type_ann),
+                optional: array.optional,
+                rest: false,
+            },
+            _ => ParamDescriptor {
+                name: unnamed(),
+                schema: json!({}),
+                optional: false,
+                rest: false,
+            },
+        }
+    }
+
+    fn schema(&self, ty: &TsType, depth: usize) -> Value {
+        if depth > MAX_DEPTH || self.budget.get() == 0 {
+            return json!({});
+        }
+        self.budget.set(self.budget.get() - 1);
+        let depth = depth + 1;
+
+        match ty {
+            TsType::TsKeywordType(keyword) => match keyword.kind {
+                TsKeywordTypeKind::TsNumberKeyword => json!({ "type": "number" }),
+                TsKeywordTypeKind::TsStringKeyword => json!({ "type": "string" }),
+                TsKeywordTypeKind::TsBooleanKeyword => json!({ "type": "boolean" }),
+                TsKeywordTypeKind::TsBigIntKeyword => json!({ "type": "integer" }),
+                TsKeywordTypeKind::TsNullKeyword => json!({ "type": "null" }),
+                TsKeywordTypeKind::TsObjectKeyword => json!({ "type": "object" }),
+                TsKeywordTypeKind::TsNeverKeyword => Value::Bool(false),
+                _ => json!({}),
+            },
+
+            TsType::TsLitType(lit) => match &lit.lit {
+                TsLit::Number(n) => Number::from_f64(n.value)
+                    .map_or(json!({ "type": "number" }), |n| json!({ "const": n })),
+                TsLit::Str(s) => json!({ "const": s.value.to_string() }),
+                TsLit::Bool(b) => json!({ "const": b.value }),
+                _ => json!({}),
+            },
+
+            TsType::TsArrayType(array) => {
+                json!({ "type": "array", "items": self.schema(&array.elem_type, depth) })
+            }
+
+            TsType::TsTupleType(tuple) => self.tuple(tuple, depth),
+
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(union)) => {
+                let options: Vec<_> = union.types.iter().map(|t| self.schema(t, depth)).collect();
+                let consts: Option<Vec<_>> = options
+                    .iter()
+                    .map(|o| {
+                        o.as_object()
+                            .filter(|o| o.len() == 1)?
+                            .get("const")
+                            .cloned()
+                    })
+                    .collect();
+                match consts {
+                    Some(consts) => json!({ "enum": consts }),
+                    None => json!({ "anyOf": options }),
+                }
+            }
+
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsIntersectionType(
+                intersection,
+            )) => {
+                let parts: Vec<_> = intersection
+                    .types
+                    .iter()
+                    .map(|t| self.schema(t, depth))
+                    .collect();
+                json!({ "allOf": parts })
+            }
+
+            TsType::TsTypeLit(lit) => self.object(&lit.members, depth),
+
+            TsType::TsParenthesizedType(paren) => self.schema(&paren.type_ann, depth),
+            TsType::TsOptionalType(optional) => self.schema(&optional.type_ann, depth),
+            TsType::TsTypeOperator(operator) => self.schema(&operator.type_ann, depth),
+
+            TsType::TsTypeRef(reference) => self.reference(reference, depth),
+
+            _ => json!({}),
+        }
+    }
+
+    fn tuple(&self, tuple: &TsTupleType, depth: usize) -> Value {
+        let mut required = 0;
+        let mut items = vec![];
+        let mut rest = None;
+        for element in &tuple.elem_types {
+            match &*element.ty {
+                TsType::TsOptionalType(optional) => {
+                    items.push(self.schema(&optional.type_ann, depth));
+                }
+                TsType::TsRestType(r) => {
+                    let array = self.schema(&r.type_ann, depth);
+                    rest = Some(array.get("items").cloned().unwrap_or(json!({})));
+                }
+                ty => {
+                    items.push(self.schema(ty, depth));
+                    required = items.len();
+                }
+            }
+        }
+
+        let mut schema = json!({ "type": "array", "minItems": required });
+        if rest.is_none() {
+            schema["maxItems"] = json!(items.len());
+        }
+        schema["prefixItems"] = Value::Array(items);
+        schema["items"] = rest.unwrap_or(Value::Bool(false));
+        schema
+    }
+
+    fn reference(&self, reference: &TsTypeRef, depth: usize) -> Value {
+        let TsEntityName::Ident(ident) = &reference.type_name else {
+            return json!({});
+        };
+        let args: Vec<&TsType> = reference
+            .type_params
+            .as_ref()
+            .map(|p| p.params.iter().map(|t| &**t).collect())
+            .unwrap_or_default();
+
+        match (ident.sym.as_ref(), args.as_slice()) {
+            ("Array" | "ReadonlyArray" | "Set", [item]) => {
+                json!({ "type": "array", "items": self.schema(item, depth) })
+            }
+            ("Record" | "Map", [_, value]) => {
+                json!({ "type": "object", "additionalProperties": self.schema(value, depth) })
+            }
+            ("Readonly", [inner]) => self.schema(inner, depth),
+            ("Partial", [inner]) => {
+                let mut schema = self.schema(inner, depth);
+                if let Some(schema) = schema.as_object_mut() {
+                    schema.remove("required");
+                }
+                schema
+            }
+            ("Date", []) => json!({ "type": "string", "format": "date-time" }),
+            ("Uint8Array", []) => {
+                json!({ "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } })
+            }
+            (name, _) => {
+                if let Some(alias) = self.aliases.get(name) {
+                    self.schema(alias, depth)
+                } else if let Some(members) = self.interfaces.get(name) {
+                    self.object(members, depth)
+                } else {
+                    json!({})
+                }
+            }
+        }
+    }
+
+    fn object(&self, members: &[TsTypeElement], depth: usize) -> Value {
+        let mut properties = Map::new();
+        let mut required = vec![];
+        let mut additional = None;
+
+        for member in members {
+            match member {
+                TsTypeElement::TsPropertySignature(property) => {
+                    let name = match &*property.key {
+                        Expr::Ident(ident) => ident.sym.to_string(),
+                        Expr::Lit(Lit::Str(s)) => s.value.to_string(),
+                        _ => continue,
+                    };
+                    let schema = property
+                        .type_ann
+                        .as_ref()
+                        .map_or(json!({}), |t| self.schema(&t.type_ann, depth));
+                    if !property.optional {
+                        required.push(Value::String(name.clone()));
+                    }
+                    properties.insert(name, schema);
+                }
+                TsTypeElement::TsIndexSignature(index) => {
+                    additional = Some(
+                        index
+                            .type_ann
+                            .as_ref()
+                            .map_or(json!({}), |t| self.schema(&t.type_ann, depth)),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            schema["required"] = Value::Array(required);
+        }
+        if let Some(additional) = additional {
+            schema["additionalProperties"] = additional;
+        }
+        schema
+    }
+}
+
+/// If the type is a `Promise`, returns the type it resolves to, if given
+fn promised(ty: &TsType) -> Option<Option<&TsType>> {
+    let TsType::TsTypeRef(reference) = ty else {
+        return None;
+    };
+    let TsEntityName::Ident(ident) = &reference.type_name else {
+        return None;
+    };
+    if ident.sym.as_ref() != "Promise" {
+        return None;
+    }
+    Some(
+        reference
+            .type_params
+            .as_ref()
+            .and_then(|p| p.params.first())
+            .map(|t| &**t),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exported_functions() {
+        let module = Module::new(
+            "test.ts",
+            "
+            type Level = 'low' | 'high';
+            interface Options {
+                level: Level;
+                tags?: string[];
+            }
+
+            export function add(a: number, b?: number): number { return a + (b ?? 0); }
+            export const greet = async (name: string, opts: Options = { level: 'low' }) => `hi ${name}`;
+            export async function fetchAll(...ids: number[]): Promise<Record<string, boolean>> { return {}; }
+            function hidden(x: Date) {}
+            export { hidden as visible };
+            export default function (input: { id: number; pair: [string, number?] }) {}
+            ",
+        );
+
+        let functions = module.exported_functions().unwrap();
+        let names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["add", "greet", "fetchAll", "visible", "default"]
+        );
+
+        let add = &functions[0];
+        assert_eq!(add.params[0].schema, json!({ "type": "number" }));
+        assert!(add.params[1].optional);
+        assert_eq!(add.returns, json!({ "type": "number" }));
+        assert_eq!(
+            add.args_schema(),
+            json!({
+                "type": "array",
+                "prefixItems": [{ "type": "number" }, { "type": "number" }],
+                "minItems": 1,
+                "maxItems": 2,
+                "items": false,
+            })
+        );
+
+        let greet = &functions[1];
+        assert!(greet.is_async);
+        assert!(greet.params[1].optional);
+        assert_eq!(
+            greet.params[1].schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "level": { "enum": ["low", "high"] },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                },
+                "required": ["level"],
+            })
+        );
+
+        let fetch_all = &functions[2];
+        assert!(fetch_all.params[0].rest);
+        assert_eq!(
+            fetch_all.returns,
+            json!({ "type": "object", "additionalProperties": { "type": "boolean" } })
+        );
+        assert_eq!(
+            fetch_all.args_schema()["items"],
+            json!({ "type": "number" })
+        );
+
+        assert_eq!(
+            functions[3].params[0].schema,
+            json!({ "type": "string", "format": "date-time" })
+        );
+
+        let input = &functions[4].params[0];
+        assert_eq!(input.name, "arg0");
+        assert_eq!(
+            input.schema["properties"]["pair"],
+            json!({
+                "type": "array",
+                "prefixItems": [{ "type": "string" }, { "type": "number" }],
+                "minItems": 1,
+                "maxItems": 2,
+                "items": false,
+            })
+        );
+
+        // Plain javascript has no types to read
+        let module = Module::new("test.js", "export const f = (a, b) => a + b;");
+        let functions = module.exported_functions().unwrap();
+        assert_eq!(functions[0].params[1].schema, json!({}));
+    }
+
+    #[test]
+    fn test_alias_chain() {
+        let mut source = "type T0 = { a: number, b: string };\n".to_string();
+        for i in 1..=16 {
+            let t = format!("T{}", i - 1);
+            source.push_str(&format!("type T{i} = {t} | {t} | {t} | {t};\n"));
+        }
+        source.push_str("export function f(x: T16) {}\n");
+
+        // Expanding every alias would take 4^16 copies of `T0` - the schema is cut short instead
+        let functions = Module::new("test.ts", &source)
+            .exported_functions()
+            .unwrap();
+        let schema = functions[0].params[0].schema.to_string();
+        assert!(schema.contains(r#""type":"number""#));
+        assert!(schema.matches("anyOf").count() < MAX_NODES);
+    }
+}
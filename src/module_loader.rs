@@ -4,7 +4,10 @@
 
 use std::{borrow::Cow, cell::RefCell, path::PathBuf, rc::Rc};
 
-use deno_core::{error::ModuleLoaderError, ModuleLoader, ModuleLoadReferrer, ModuleSpecifier};
+use deno_core::{
+    error::ModuleLoaderError, ImportMetaResolveCallback, ModuleLoadReferrer, ModuleLoader,
+    ModuleSpecifier,
+};
 
 mod inner_loader;
 use inner_loader::InnerRustyLoader;
@@ -14,7 +17,11 @@ mod cache_provider;
 pub use cache_provider::{ClonableSource, ModuleCacheProvider};
 
 mod import_provider;
-pub use import_provider::ImportProvider;
+pub use import_provider::{ImportFuture, ImportProvider};
+
+mod rewrites;
+pub(crate) use rewrites::Rewrite;
+pub use rewrites::SpecifierRewrites;
 
 use crate::transpiler::ExtensionTranspiler;
 
@@ -50,6 +57,66 @@ impl RustyLoader {
         self.inner_mut().add_source_map(file_name, code, source_map);
     }
 
+    /// Returns the code a module was loaded with, after transpiling, if it is in the source map cache
+    #[cfg(feature = "snapshot_builder")]
+    pub fn loaded_code(&self, file_name: &str) -> Option<String> {
+        self.inner()
+            .get_source_map(file_name)
+            .map(|(code, _)| code.clone())
+    }
+
+    /// Counts a module loaded by the host against the runtime's module limits
+    pub fn record_module(&self, specifier: &str, size: usize) -> Result<(), crate::Error> {
+        self.inner_mut().record_module(specifier, size)
+    }
+
+    /// Returns the modules loaded so far
+    pub fn module_usage(&self) -> crate::ModuleUsage {
+        self.inner().module_usage()
+    }
+
+    /// Takes the most recent module limit error, if any
+    pub fn take_limit_error(&self) -> Option<crate::Error> {
+        self.inner_mut().take_limit_error()
+    }
+
+    /// Allows a file to be imported even if `fs_import` is disabled
+    pub fn whitelist_add(&self, specifier: &str) {
+        self.inner_mut().whitelist_add(specifier);
+    }
+
+    /// Substitutes the runtime's defines into a module's code
+    pub fn apply_defines(
+        &self,
+        specifier: &ModuleSpecifier,
+        code: &str,
+    ) -> Result<String, crate::Error> {
+        self.inner().apply_defines(specifier, code)
+    }
+
+    /// Prepends the `import.meta` prologue to a transpiled module, if the runtime has a provider
+    pub fn with_import_meta(&self, specifier: &ModuleSpecifier, code: String) -> String {
+        self.inner().with_import_meta(specifier, code)
+    }
+
+    /// Returns true if modules are minified unless they say otherwise
+    pub fn minifies(&self) -> bool {
+        self.inner().minifies()
+    }
+
+    /// Adds rules to the specifier rewrite table
+    pub fn rewrites_extend(&self, rewrites: SpecifierRewrites) {
+        self.inner_mut().rewrites_extend(rewrites);
+    }
+
+    /// Get an `import.meta.resolve` implementation that can be injected into a `deno_core::JsRuntime`
+    pub fn as_import_meta_resolver(self: &Rc<Self>) -> ImportMetaResolveCallback {
+        let loader = self.clone();
+        Box::new(move |_, specifier, referrer| {
+            loader.inner_mut().resolve_meta(&specifier, &referrer)
+        })
+    }
+
     /// Get an extension transpiler that can be injected into a `deno_core::JsRuntime`
     pub fn as_extension_transpiler(self: &Rc<Self>) -> ExtensionTranspiler {
         let loader = self.clone();
@@ -266,4 +333,102 @@ mod test {
             }
         }
     }
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_blob_urls() {
+        use crate::{Module, Runtime, RuntimeOptions};
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            const source = new Blob(['export const answer = 42;'], { type: 'text/javascript' });
+            const url = URL.createObjectURL(source);
+            const { answer } = await import(url);
+            const text = await fetch(url).then((r) => r.text());
+
+            URL.revokeObjectURL(url);
+            const revoked = await fetch(url).then(() => false, (e) => e instanceof TypeError);
+            export const result = { answer, text, revoked, scheme: url.split(':')[0] };
+        ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        let result: std::collections::HashMap<String, crate::serde_json::Value> =
+            runtime.get_value(Some(&module), "result").unwrap();
+        assert_eq!(result["answer"], 42);
+        assert_eq!(result["text"], "export const answer = 42;");
+        assert_eq!(result["revoked"], true);
+        assert_eq!(result["scheme"], "blob");
+    }
+
+    /// Fetches `slow://` modules after a delay, tracking how many are in flight
+    #[derive(Default)]
+    struct SlowImportProvider {
+        in_flight: Rc<std::cell::Cell<usize>>,
+        peak: Rc<std::cell::Cell<usize>>,
+    }
+    impl ImportProvider for SlowImportProvider {
+        fn resolve(
+            &mut self,
+            specifier: &ModuleSpecifier,
+            _referrer: &str,
+            _kind: ResolutionKind,
+        ) -> Option<Result<ModuleSpecifier, ModuleLoaderError>> {
+            (specifier.scheme() == "slow").then(|| Ok(specifier.clone()))
+        }
+
+        fn import_async(
+            &mut self,
+            specifier: &ModuleSpecifier,
+            _referrer: Option<&ModuleSpecifier>,
+            _is_dyn_import: bool,
+            _requested_module_type: deno_core::RequestedModuleType,
+        ) -> Option<ImportFuture> {
+            use deno_core::futures::FutureExt;
+
+            let name = specifier.host_str()?.to_string();
+            let (in_flight, peak) = (self.in_flight.clone(), self.peak.clone());
+            let future = async move {
+                in_flight.set(in_flight.get() + 1);
+                peak.set(peak.get().max(in_flight.get()));
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                in_flight.set(in_flight.get() - 1);
+                Ok(format!("export default '{name}';"))
+            };
+            Some(future.boxed_local())
+        }
+    }
+
+    #[test]
+    fn test_fetch_concurrency() {
+        use crate::{Module, Runtime, RuntimeOptions};
+
+        let provider = SlowImportProvider::default();
+        let peak = provider.peak.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_provider: Some(Box::new(provider)),
+            module_fetch_concurrency: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let names = ["a", "b", "c", "d", "e", "f"];
+        let imports: String = names
+            .iter()
+            .map(|name| format!("import {name} from 'slow://{name}';\n"))
+            .collect();
+        let module = Module::new(
+            "test.js",
+            format!(
+                "{imports}export const joined = [{}].join('');",
+                names.join(", ")
+            ),
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        let joined: String = runtime.get_value(Some(&module), "joined").unwrap();
+        assert_eq!(joined, "abcdef");
+        assert_eq!(peak.get(), 2);
+    }
 }
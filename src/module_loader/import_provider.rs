@@ -1,4 +1,10 @@
-use deno_core::{error::ModuleLoaderError, ModuleSource, ModuleSpecifier, RequestedModuleType};
+use deno_core::{
+    error::ModuleLoaderError, futures::future::LocalBoxFuture, ModuleSource, ModuleSpecifier,
+    RequestedModuleType,
+};
+
+/// A pending fetch of a module's source - see [`ImportProvider::import_async`]
+pub type ImportFuture = LocalBoxFuture<'static, Result<String, ModuleLoaderError>>;
 
 /// A trait that can be implemented to modify the behavior of the module loader
 /// Allows for custom schemes, caching, and more granular permissions
@@ -49,6 +55,31 @@ pub trait ImportProvider {
         None
     }
 
+    /// Begin retrieving a module from a given URL, for backends that fetch sources asynchronously
+    ///
+    /// Called when [`ImportProvider::import`] falls back to the default behavior. The imports of a
+    /// module graph are fetched concurrently, up to [`crate::RuntimeOptions::module_fetch_concurrency`]
+    /// at a time, so slow backends should prefer this over blocking in `import`
+    ///
+    /// # Arguments
+    /// - `specifier`: The module specifier to import, as an absolute URL
+    /// - `referrer`: The URL of the module that is importing the specifier
+    /// - `is_dyn_import`: Whether the import is a dynamic import or not
+    /// - `requested_module_type`: The type of module being requested
+    ///
+    /// # Returns
+    /// - Some(future): A future resolving to the module source code, or to an error
+    /// - None: Fall back to the default import behavior
+    fn import_async(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        referrer: Option<&ModuleSpecifier>,
+        is_dyn_import: bool,
+        requested_module_type: RequestedModuleType,
+    ) -> Option<ImportFuture> {
+        None
+    }
+
     /// Apply an optional transform to the source code after it has been imported
     /// This can be used to modify the source code before it is executed
     /// Or to cache the source code for later use
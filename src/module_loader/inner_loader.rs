@@ -11,9 +11,12 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
 use deno_core::{
     error::{AnyError, ModuleLoaderError},
     futures::FutureExt,
+    serde_json,
     url::ParseError,
     FastString, ModuleLoadResponse, ModuleResolutionError, ModuleSource, ModuleSourceCode,
     ModuleSpecifier, ModuleType,
@@ -23,8 +26,10 @@ use deno_error::JsErrorBox;
 use crate::{
     module_loader::{ClonableSource, ModuleCacheProvider},
     traits::ToModuleSpecifier,
-    transpiler::{transpile, transpile_extension, ExtensionTranspilation},
-    Error,
+    transpiler::{
+        apply_defines, minify, off_thread, transpile, transpile_extension, ExtensionTranspilation,
+    },
+    Error, ImportMetaFn, ModuleLimits, ModuleUsage,
 };
 
 #[cfg(feature = "node_experimental")]
@@ -38,7 +43,10 @@ use node_resolver::InNpmPackageChecker;
 #[cfg(feature = "node_experimental")]
 use node_resolver::{NodeResolutionKind, ResolutionMode};
 
-use super::ImportProvider;
+use super::{
+    rewrites::{Rewrite, SpecifierRewrites},
+    ImportProvider,
+};
 
 /// Stores the source code and source ma#![allow(deprecated)]p for loaded modules
 type SourceMapCache = HashMap<String, (String, Option<Vec<u8>>)>;
@@ -69,6 +77,28 @@ pub struct LoaderOptions {
 
     /// The current working directory for the loader
     pub cwd: PathBuf,
+
+    /// Caps on the number and size of modules loaded
+    pub module_limits: ModuleLimits,
+
+    /// Rules rewriting specifiers before they are resolved
+    pub rewrites: SpecifierRewrites,
+
+    /// Constants substituted into modules before they are transpiled
+    pub defines: HashMap<String, String>,
+
+    /// Minify modules after they are transpiled
+    pub minify: bool,
+
+    /// Supplies the extra `import.meta` fields each module's prologue assigns
+    pub import_meta: Option<Box<ImportMetaFn>>,
+
+    /// Maximum number of module sources fetched at once
+    pub fetch_concurrency: Option<usize>,
+
+    /// The store `URL.createObjectURL` registers blobs in, so `blob:` URLs can be imported
+    #[cfg(feature = "web")]
+    pub blob_store: Option<Arc<deno_web::BlobStore>>,
 }
 
 #[cfg(feature = "node_experimental")]
@@ -108,6 +138,21 @@ pub struct InnerRustyLoader {
     import_provider: Option<Box<dyn ImportProvider>>,
     schema_whlist: HashSet<String>,
     cwd: PathBuf,
+    rewrites: SpecifierRewrites,
+    defines: HashMap<String, String>,
+    minify: bool,
+    import_meta: Option<Box<ImportMetaFn>>,
+    fetch_limit: Option<Arc<Semaphore>>,
+
+    #[cfg(feature = "url_import")]
+    http_client: Option<reqwest::Client>,
+
+    module_limits: ModuleLimits,
+    module_usage: ModuleUsage,
+    limit_error: Option<Error>,
+
+    #[cfg(feature = "web")]
+    blob_store: Option<Arc<deno_web::BlobStore>>,
 
     #[cfg(feature = "node_experimental")]
     node: NodeProvider,
@@ -124,12 +169,53 @@ impl InnerRustyLoader {
             import_provider: options.import_provider,
             schema_whlist: options.schema_whlist,
             cwd: options.cwd,
+            rewrites: options.rewrites,
+            defines: options.defines,
+            minify: options.minify,
+            import_meta: options.import_meta,
+            fetch_limit: options
+                .fetch_concurrency
+                .map(|permits| Arc::new(Semaphore::new(permits.max(1)))),
+
+            #[cfg(feature = "url_import")]
+            http_client: None,
+
+            module_limits: options.module_limits,
+            module_usage: ModuleUsage::default(),
+            limit_error: None,
+
+            #[cfg(feature = "web")]
+            blob_store: options.blob_store,
 
             #[cfg(feature = "node_experimental")]
             node: NodeProvider::new(options.node_resolver),
         }
     }
 
+    /// Counts a loaded module against the module limits
+    ///
+    /// On failure the error is also kept, so the typed error can be recovered
+    /// after deno reports it as a generic load failure
+    pub fn record_module(&mut self, specifier: &str, size: usize) -> Result<(), Error> {
+        let result = self
+            .module_usage
+            .record(&self.module_limits, specifier, size);
+        if let Err(e) = &result {
+            self.limit_error = Some(e.clone());
+        }
+        result
+    }
+
+    /// Returns the modules loaded so far
+    pub fn module_usage(&self) -> ModuleUsage {
+        self.module_usage
+    }
+
+    /// Takes the most recent module limit error, if any
+    pub fn take_limit_error(&mut self) -> Option<Error> {
+        self.limit_error.take()
+    }
+
     /// Sets the current working directory for the loader
     pub fn set_current_dir(&mut self, cwd: PathBuf) {
         self.cwd = cwd;
@@ -142,6 +228,45 @@ impl InnerRustyLoader {
         self.fs_whlist.insert(specifier.to_string());
     }
 
+    /// Adds rules to the specifier rewrite table
+    pub fn rewrites_extend(&mut self, rewrites: SpecifierRewrites) {
+        self.rewrites.extend(rewrites);
+    }
+
+    /// Substitutes the loader's defines into a module's code
+    pub fn apply_defines(&self, specifier: &ModuleSpecifier, code: &str) -> Result<String, Error> {
+        Ok(apply_defines(specifier, code, &self.defines)?.into_owned())
+    }
+
+    /// Prepends the `import.meta` prologue to a transpiled module, if the runtime has a provider
+    ///
+    /// The module's fields are embedded in its own prologue, so no module can read another's.  
+    /// The prologue shares the module's first line, after any hashbang, so line numbers are kept
+    pub fn with_import_meta(&self, specifier: &ModuleSpecifier, code: String) -> String {
+        let Some(provider) = &self.import_meta else {
+            return code;
+        };
+
+        // Parsed rather than written as an object literal, so a `__proto__` field stays a field
+        let fields = serde_json::to_string(&provider(specifier))
+            .and_then(|fields| serde_json::to_string(&fields))
+            .unwrap_or_else(|_| "'{}'".to_string());
+        let prologue = format!("Object.assign(import.meta, JSON.parse({fields}));");
+
+        let split = if code.starts_with("#!") {
+            code.find('\n').map_or(code.len(), |i| i + 1)
+        } else {
+            0
+        };
+        let (hashbang, body) = code.split_at(split);
+        format!("{hashbang}{prologue}{body}")
+    }
+
+    /// Returns true if modules are minified unless they say otherwise
+    pub fn minifies(&self) -> bool {
+        self.minify
+    }
+
     /// Checks if a module specifier is in the whitelist
     /// Used to determine if a module can be loaded from the filesystem
     /// or not if `fs_import` is disabled
@@ -163,12 +288,64 @@ impl InnerRustyLoader {
         transpile_extension(&specifier, code)
     }
 
+    /// Resolves a specifier for `import.meta.resolve`
+    ///
+    /// Follows the same rewrites, node resolution and import provider as [`Self::resolve`], but
+    /// nothing is loaded, so no permission checks apply and the target is not whitelisted - a
+    /// script can locate an asset it would not be allowed to import
+    pub fn resolve_meta(
+        &mut self,
+        specifier: &str,
+        referrer: &str,
+    ) -> Result<ModuleSpecifier, ModuleLoaderError> {
+        let kind = deno_core::ResolutionKind::DynamicImport;
+        let specifier = match self.rewrites.rewrite(specifier) {
+            Some(Rewrite::Virtual(url)) => return Ok(url),
+            Some(rewrite) => {
+                rewrite.resolve(referrer).map_err(JsErrorBox::from_err)?;
+                rewrite.specifier().unwrap_or_default().to_string()
+            }
+            None => specifier.to_string(),
+        };
+
+        // Node resolution has no side effects worth avoiding
+        #[cfg(feature = "node_experimental")]
+        if is_builtin_node_module(&specifier)
+            || specifier.starts_with("npm:")
+            || specifier.starts_with("node:")
+        {
+            return self.resolve(&specifier, referrer, kind);
+        }
+
+        let url =
+            deno_core::resolve_import(&specifier, referrer).map_err(ModuleLoaderError::from_err)?;
+        if let Some(import_provider) = &mut self.import_provider {
+            if let Some(result) = import_provider.resolve(&url, referrer, kind) {
+                return result;
+            }
+        }
+        Ok(url)
+    }
+
     pub fn resolve(
         &mut self,
         specifier: &str,
         referrer: &str,
         kind: deno_core::ResolutionKind,
     ) -> Result<ModuleSpecifier, ModuleLoaderError> {
+        //
+        // Apply the rewrite table - its targets are trusted like modules loaded from rust
+        let rewritten = self.rewrites.rewrite(specifier);
+        let specifier = match &rewritten {
+            Some(Rewrite::Virtual(url)) => return Ok(url.clone()),
+            Some(rewrite) => {
+                let url = rewrite.resolve(referrer).map_err(JsErrorBox::from_err)?;
+                self.whitelist_add(url.as_str());
+                rewrite.specifier().unwrap_or_default()
+            }
+            None => specifier,
+        };
+
         #[cfg(feature = "node_experimental")]
         let referrer_specifier = if deno_core::specifier_has_uri_scheme(referrer) {
             deno_core::resolve_url(referrer).map_err(JsErrorBox::from_err)?
@@ -253,6 +430,10 @@ impl InnerRustyLoader {
                 // Extension import - allow
             }
 
+            // Object URLs created by the script itself
+            #[cfg(feature = "web")]
+            "blob" if self.blob_store.is_some() => {}
+
             #[cfg(feature = "node_experimental")]
             _ if specifier.starts_with("npm:") || specifier.starts_with("node:") => {
                 return self.load_npm(specifier, referrer);
@@ -282,10 +463,30 @@ impl InnerRustyLoader {
         let maybe_referrer = maybe_referrer.cloned();
 
         // Check if the module is in the cache first
-        if let Some(cache) = &inner.borrow().cache_provider {
-            if let Some(source) = cache.get(&module_specifier) {
-                return deno_core::ModuleLoadResponse::Sync(Ok(source));
-            }
+        let cached = inner
+            .borrow()
+            .cache_provider
+            .as_ref()
+            .and_then(|cache| cache.get(&module_specifier));
+        if let Some(source) = cached {
+            let result = inner
+                .borrow_mut()
+                .record_module(module_specifier.as_str(), source_len(&source))
+                .map(|()| source)
+                .map_err(JsErrorBox::from_err);
+            return deno_core::ModuleLoadResponse::Sync(result);
+        }
+
+        // Then virtual modules from the rewrite table
+        let virtual_source = inner.borrow().rewrites.virtual_source(&module_specifier);
+        if let Some(source) = virtual_source {
+            return ModuleLoadResponse::Async(
+                async move {
+                    Self::handle_load(inner, module_specifier, |_, _| async move { Ok(source) })
+                        .await
+                }
+                .boxed_local(),
+            );
         }
 
         // Next check the import provider
@@ -294,7 +495,7 @@ impl InnerRustyLoader {
                 &module_specifier,
                 maybe_referrer.as_ref(),
                 is_dyn_import,
-                requested_module_type,
+                requested_module_type.clone(),
             )
         });
         if let Some(result) = provider_result {
@@ -306,6 +507,28 @@ impl InnerRustyLoader {
             );
         }
 
+        // Or have it fetch the module asynchronously
+        let provider_future = inner.borrow_mut().import_provider.as_mut().and_then(|p| {
+            p.import_async(
+                &module_specifier,
+                maybe_referrer.as_ref(),
+                is_dyn_import,
+                requested_module_type,
+            )
+        });
+        if let Some(future) = provider_future {
+            return ModuleLoadResponse::Async(
+                async move {
+                    Self::handle_load(inner, module_specifier, |inner, _| async move {
+                        let _permit = Self::fetch_permit(&inner).await?;
+                        future.await
+                    })
+                    .await
+                }
+                .boxed_local(),
+            );
+        }
+
         // We check permissions next
         match module_specifier.scheme() {
             // Remote fetch imports
@@ -321,6 +544,13 @@ impl InnerRustyLoader {
                     .boxed_local(),
             ),
 
+            // Object URL imports
+            #[cfg(feature = "web")]
+            "blob" => ModuleLoadResponse::Async(
+                async move { Self::handle_load(inner, module_specifier, Self::load_blob).await }
+                    .boxed_local(),
+            ),
+
             // Default deny-all
             x => {
                 let error =
@@ -462,9 +692,11 @@ impl InnerRustyLoader {
                 "{module_specifier} is not a file path"
             )))
         })?;
+        let permit = Self::fetch_permit(&inner).await?;
         let content = tokio::fs::read_to_string(path)
             .await
             .map_err(ModuleLoaderError::from_err)?;
+        drop(permit);
         let content = Self::translate_cjs(inner, module_specifier, content)
             .await
             .map_err(ModuleLoaderError::from_err)?;
@@ -472,12 +704,42 @@ impl InnerRustyLoader {
         Ok(content)
     }
 
+    #[cfg(feature = "web")]
+    async fn load_blob(
+        inner: Rc<RefCell<Self>>,
+        module_specifier: ModuleSpecifier,
+    ) -> Result<String, ModuleLoaderError> {
+        let blob = inner
+            .borrow()
+            .blob_store
+            .as_ref()
+            .and_then(|store| store.get_object_url(module_specifier.clone()))
+            .ok_or_else(|| {
+                JsErrorBox::from_err(Error::Runtime(format!(
+                    "{module_specifier} is not a valid object URL"
+                )))
+            })?;
+
+        let content = blob.read_all().await;
+        String::from_utf8(content).map_err(|e| ModuleLoaderError::generic(e.to_string()))
+    }
+
     #[cfg(feature = "url_import")]
     async fn load_remote(
-        _: Rc<RefCell<Self>>,
+        inner: Rc<RefCell<Self>>,
         module_specifier: ModuleSpecifier,
     ) -> Result<String, ModuleLoaderError> {
-        let response = reqwest::get(module_specifier)
+        // One client for every fetch, so imports from the same host share connections
+        let client = inner
+            .borrow_mut()
+            .http_client
+            .get_or_insert_with(reqwest::Client::new)
+            .clone();
+
+        let _permit = Self::fetch_permit(&inner).await?;
+        let response = client
+            .get(module_specifier)
+            .send()
             .await
             .map_err(|e| ModuleLoaderError::generic(e.to_string()))?;
         let response = response
@@ -487,12 +749,46 @@ impl InnerRustyLoader {
         Ok(response)
     }
 
+    /// Waits for a slot under the fetch concurrency cap, if there is one
+    ///
+    /// Held while a module's source is fetched, but not while it is transpiled
+    async fn fetch_permit(
+        inner: &Rc<RefCell<Self>>,
+    ) -> Result<Option<OwnedSemaphorePermit>, ModuleLoaderError> {
+        let Some(limit) = inner.borrow().fetch_limit.clone() else {
+            return Ok(None);
+        };
+        let permit = limit
+            .acquire_owned()
+            .await
+            .map_err(|e| ModuleLoaderError::generic(e.to_string()))?;
+        Ok(Some(permit))
+    }
+
     /// Loads a module's source code from the cache or from the provided handler
     async fn handle_load<F, Fut>(
         inner: Rc<RefCell<Self>>,
         module_specifier: ModuleSpecifier,
         handler: F,
     ) -> Result<ModuleSource, ModuleLoaderError>
+    where
+        F: FnOnce(Rc<RefCell<Self>>, ModuleSpecifier) -> Fut,
+        Fut: std::future::Future<Output = Result<String, ModuleLoaderError>>,
+    {
+        let source =
+            Self::handle_load_inner(inner.clone(), module_specifier.clone(), handler).await?;
+        inner
+            .borrow_mut()
+            .record_module(module_specifier.as_str(), source_len(&source))
+            .map_err(JsErrorBox::from_err)?;
+        Ok(source)
+    }
+
+    async fn handle_load_inner<F, Fut>(
+        inner: Rc<RefCell<Self>>,
+        module_specifier: ModuleSpecifier,
+        handler: F,
+    ) -> Result<ModuleSource, ModuleLoaderError>
     where
         F: FnOnce(Rc<RefCell<Self>>, ModuleSpecifier) -> Fut,
         Fut: std::future::Future<Output = Result<String, ModuleLoaderError>>,
@@ -523,8 +819,29 @@ impl InnerRustyLoader {
 
         // Load the module code, and transpile it if necessary
         let code = handler(inner.clone(), module_specifier.clone()).await?;
-        let (tcode, source_map) =
-            transpile(&module_specifier, &code).map_err(ModuleLoaderError::from_err)?;
+        let code = inner
+            .borrow()
+            .apply_defines(&module_specifier, &code)
+            .map_err(JsErrorBox::from_err)?;
+        let transpiled = {
+            let specifier = module_specifier.clone();
+            let code = code.clone();
+            let minifies = inner.borrow().minify;
+            off_thread(code.len(), move || -> Result<_, ModuleLoaderError> {
+                let (tcode, source_map) =
+                    transpile(&specifier, &code).map_err(ModuleLoaderError::from_err)?;
+                if minifies {
+                    let tcode = minify(&specifier, &tcode).map_err(JsErrorBox::from_err)?;
+                    Ok((tcode, None))
+                } else {
+                    Ok((tcode, source_map))
+                }
+            })
+        };
+        let (mut tcode, source_map) = transpiled.await.map_err(JsErrorBox::from_err)??;
+        if module_type == ModuleType::JavaScript {
+            tcode = inner.borrow().with_import_meta(&module_specifier, tcode);
+        }
 
         // Create the module source
         let mut source = ModuleSource::new(
@@ -574,6 +891,14 @@ impl InnerRustyLoader {
     }
 }
 
+/// Size of a module's code, as counted against [`ModuleLimits`]
+fn source_len(source: &ModuleSource) -> usize {
+    match &source.code {
+        ModuleSourceCode::String(code) => code.as_str().len(),
+        ModuleSourceCode::Bytes(code) => code.as_bytes().len(),
+    }
+}
+
 #[cfg(feature = "node_experimental")]
 fn is_builtin_node_module(specifier: &str) -> bool {
     use node_resolver::IsBuiltInNodeModuleChecker;
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use deno_core::ModuleSpecifier;
+
+use crate::Error;
+
+/// A table of rules rewriting import specifiers before they are resolved
+///
+/// Lets a host inject virtual modules, alias bare specifiers, or pin external dependencies to a
+/// local mirror - see [`crate::RuntimeOptions::specifier_rewrites`]
+///
+/// Rules are matched against specifiers exactly as they are written in the importing module.
+/// Exact rules take precedence over prefix rules, and the longest matching prefix wins. The targets
+/// of rules are trusted as though they were loaded from rust, so a rewrite to a local file is
+/// allowed even if `fs_import` is disabled - a prefix rewrite that resolves outside of its target,
+/// such as `https://cdn.example.com/../../etc/passwd`, fails instead
+///
+/// ```rust
+/// use rustyscript::{module_loader::SpecifierRewrites, Module, Runtime, RuntimeOptions};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let rewrites = SpecifierRewrites::new()
+///     .virtual_module("app:config", "export const name = 'demo';")
+///     .prefix("https://cdn.example.com/*", "file:///opt/mirror/*");
+///
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     specifier_rewrites: rewrites,
+///     ..Default::default()
+/// })?;
+/// let module = Module::new("test.js", "export { name } from 'app:config';");
+/// let module = runtime.load_module(&module)?;
+/// let name: String = runtime.get_value(Some(&module), "name")?;
+/// assert_eq!(name, "demo");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SpecifierRewrites {
+    exact: HashMap<String, String>,
+    prefixes: Vec<(String, String)>,
+    virtual_modules: HashMap<String, (ModuleSpecifier, String)>,
+}
+
+/// The result of applying a [`SpecifierRewrites`] table to a specifier
+pub(crate) enum Rewrite {
+    /// Resolve this specifier instead
+    Specifier(String),
+
+    /// Resolve this specifier instead, which must resolve under `target` - see [`Rewrite::resolve`]
+    Prefixed { specifier: String, target: String },
+
+    /// An in-memory module, resolved to this URL
+    Virtual(ModuleSpecifier),
+}
+
+impl SpecifierRewrites {
+    /// Create an empty table, rewriting nothing
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rewrite `from` to `to`, when imported exactly as `from`
+    #[must_use]
+    pub fn exact(mut self, from: impl ToString, to: impl ToString) -> Self {
+        self.exact.insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Rewrite any specifier starting with `from`, replacing that prefix with `to`
+    ///
+    /// A trailing `*` is ignored on either side, so `https://cdn.x/*` is the same as `https://cdn.x/`
+    #[must_use]
+    pub fn prefix(mut self, from: impl ToString, to: impl ToString) -> Self {
+        let from = from.to_string().trim_end_matches('*').to_string();
+        let to = to.to_string().trim_end_matches('*').to_string();
+        self.prefixes.retain(|(f, _)| *f != from);
+        self.prefixes.push((from, to));
+        self
+    }
+
+    /// Serve `source` from memory when `specifier` is imported
+    ///
+    /// The specifier is used as the module's URL if it is one, such as `app:config`, and is
+    /// otherwise given the `virtual:` scheme
+    #[must_use]
+    pub fn virtual_module(mut self, specifier: impl ToString, source: impl ToString) -> Self {
+        let specifier = specifier.to_string();
+        let url = ModuleSpecifier::parse(&specifier)
+            .or_else(|_| ModuleSpecifier::parse(&format!("virtual:{specifier}")));
+        if let Ok(url) = url {
+            self.virtual_modules
+                .insert(specifier, (url, source.to_string()));
+        }
+        self
+    }
+
+    /// Adds the rules of another table, replacing any of this table's rules for the same specifiers
+    pub fn extend(&mut self, other: SpecifierRewrites) {
+        self.exact.extend(other.exact);
+        for (from, to) in other.prefixes {
+            self.prefixes.retain(|(f, _)| *f != from);
+            self.prefixes.push((from, to));
+        }
+        self.virtual_modules.extend(other.virtual_modules);
+    }
+
+    /// Returns true if the table has no rules
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.prefixes.is_empty() && self.virtual_modules.is_empty()
+    }
+
+    /// Applies the table to a specifier, as written in the importing module
+    pub(crate) fn rewrite(&self, specifier: &str) -> Option<Rewrite> {
+        if let Some((url, _)) = self.virtual_modules.get(specifier) {
+            return Some(Rewrite::Virtual(url.clone()));
+        }
+        if let Some(to) = self.exact.get(specifier) {
+            return Some(Rewrite::Specifier(to.clone()));
+        }
+
+        self.prefixes
+            .iter()
+            .filter(|(from, _)| specifier.starts_with(from.as_str()))
+            .max_by_key(|(from, _)| from.len())
+            .map(|(from, to)| Rewrite::Prefixed {
+                specifier: format!("{to}{}", &specifier[from.len()..]),
+                target: to.clone(),
+            })
+    }
+
+    /// Returns the source of the virtual module resolved to this URL, if there is one
+    pub(crate) fn virtual_source(&self, url: &ModuleSpecifier) -> Option<String> {
+        self.virtual_modules
+            .values()
+            .find(|(u, _)| u == url)
+            .map(|(_, source)| source.clone())
+    }
+}
+
+impl Rewrite {
+    /// Returns the specifier to resolve instead, or `None` for a virtual module
+    pub fn specifier(&self) -> Option<&str> {
+        match self {
+            Self::Specifier(specifier) | Self::Prefixed { specifier, .. } => Some(specifier),
+            Self::Virtual(_) => None,
+        }
+    }
+
+    /// Resolves the rewritten specifier against the importing module
+    ///
+    /// A prefix rule copies the rest of the specifier verbatim, so `..` segments could otherwise
+    /// escape the rule's target - and since targets are trusted, reach any file
+    ///
+    /// # Errors
+    /// Fails if the specifier cannot be resolved, or resolves outside of its prefix rule's target
+    pub fn resolve(&self, referrer: &str) -> Result<ModuleSpecifier, Error> {
+        match self {
+            Self::Virtual(url) => Ok(url.clone()),
+            Self::Specifier(specifier) => Ok(deno_core::resolve_import(specifier, referrer)?),
+            Self::Prefixed { specifier, target } => {
+                let url = deno_core::resolve_import(specifier, referrer)?;
+                let target = deno_core::resolve_import(target, referrer)?;
+                if url.as_str().starts_with(target.as_str()) {
+                    Ok(url)
+                } else {
+                    Err(Error::Runtime(format!(
+                        "{specifier} resolves outside of {target}"
+                    )))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let rewrites = SpecifierRewrites::new()
+            .exact("lodash", "https://cdn.x/lodash@4/mod.js")
+            .prefix("https://cdn.x/*", "file:///mirror/*")
+            .prefix("https://cdn.x/pinned/", "file:///pinned/")
+            .virtual_module("app:config", "export default 1;")
+            .virtual_module("settings", "export default 2;");
+
+        let rewritten = |s| match rewrites.rewrite(s) {
+            Some(Rewrite::Virtual(url)) => Some(format!("virtual {url}")),
+            Some(rewrite) => rewrite.specifier().map(str::to_string),
+            None => None,
+        };
+        assert_eq!(
+            rewritten("lodash").as_deref(),
+            Some("https://cdn.x/lodash@4/mod.js")
+        );
+        assert_eq!(
+            rewritten("https://cdn.x/a/b.js").as_deref(),
+            Some("file:///mirror/a/b.js")
+        );
+        assert_eq!(
+            rewritten("https://cdn.x/pinned/c.js").as_deref(),
+            Some("file:///pinned/c.js")
+        );
+        assert_eq!(
+            rewritten("app:config").as_deref(),
+            Some("virtual app:config")
+        );
+        assert_eq!(
+            rewritten("settings").as_deref(),
+            Some("virtual virtual:settings")
+        );
+        assert_eq!(rewritten("./local.js"), None);
+
+        let url = ModuleSpecifier::parse("virtual:settings").unwrap();
+        assert_eq!(
+            rewrites.virtual_source(&url).as_deref(),
+            Some("export default 2;")
+        );
+
+        // Prefix rewrites cannot climb out of their target
+        let resolve = |s| rewrites.rewrite(s).unwrap().resolve("file:///app/main.js");
+        assert_eq!(
+            resolve("https://cdn.x/a/../b.js").unwrap().as_str(),
+            "file:///mirror/b.js"
+        );
+        assert!(resolve("https://cdn.x/../etc/passwd").is_err());
+        assert!(resolve("https://cdn.x/a/%2e%2e/%2e%2e/etc/passwd").is_err());
+    }
+}
@@ -23,8 +23,28 @@ whitelist!(
         stubs = [],
 
         op_register_entrypoint,
+        op_register_durable,
+        op_workflow_step,
         call_registered_function,
         call_registered_function_async,
+        op_rate_limit_acquire,
+        op_rate_limit_try_acquire,
+        op_circuit_check,
+        op_circuit_record,
+        op_timer_check,
+        op_bind_leak_checker,
+        op_build_info,
+        op_entrypoint_validate,
+        op_entrypoint_record,
+        op_expose_function,
+        op_unexpose_function,
+        op_emit,
+        op_has_subscribers,
+        op_heartbeat,
+        op_current_call,
+        op_call_signal,
+        op_bind_call_context,
+        op_bind_call_signal,
         op_panic2,
     ],
     "deno_core" => [
@@ -223,6 +243,81 @@ whitelist!(
         op_urlpattern_parse,
         op_urlpattern_process_match_input,
     ],
+    "init_env" => [
+        stubs = [],
+        op_env_get,
+        op_env_set,
+        op_env_delete,
+        op_env_to_object,
+    ],
+    "init_signals" => [
+        stubs = [],
+        op_signal_bind_dispatcher,
+    ],
+    "init_cli" => [
+        stubs = [],
+        op_cli_args,
+        op_cli_exit,
+    ],
+    "init_events" => [
+        stubs = [],
+        op_events_listener_added,
+        op_events_bind_dispatcher,
+    ],
+    "init_sqlite" => [
+        stubs = [],
+        op_sqlite_open,
+        op_sqlite_close,
+        op_sqlite_exec,
+        op_sqlite_prepare,
+        op_sqlite_run,
+        op_sqlite_query,
+    ],
+    "init_database" => [
+        stubs = [],
+        op_database_query,
+        op_database_execute,
+    ],
+    "init_host_cache" => [
+        stubs = [],
+        op_cache_get,
+        op_cache_set,
+        op_cache_delete,
+        op_cache_expire,
+    ],
+    "init_actions" => [
+        stubs = [],
+        op_actions_invoke,
+        op_actions_list,
+    ],
+    "init_json" => [
+        stubs = [],
+        op_json_parser_new,
+        op_json_parser_feed,
+        op_json_parser_finish,
+        op_json_source_read,
+        op_json_sink_write,
+        op_json_sink_close,
+    ],
+    "init_pipeline" => [
+        stubs = [],
+        op_bind_pipeline_runner,
+        op_pipeline_input,
+        op_pipeline_output,
+    ],
+    "init_secrets" => [
+        stubs = [],
+        op_secrets_get,
+    ],
+    "init_wasm" => [
+        stubs = [],
+        op_wasm_check_module,
+        op_wasm_check_descriptor,
+    ],
+    "init_otel" => [
+        stubs = [],
+        op_otel_console_error,
+    ],
     "web_stub" => [
         stubs = [],
         op_now,
@@ -0,0 +1,210 @@
+//! Preset runtime configurations - see [`Profile`]
+use std::time::Duration;
+
+use crate::{Error, RuntimeOptions};
+
+/// A preset combination of extensions, permissions and hardening options
+///
+/// Extensions are chosen when the crate is compiled, so a profile cannot add or remove them -
+/// instead it names the crate features it expects, and a runtime created from it fails if any
+/// are missing. Everything else is configured at runtime:
+///
+/// | Profile        | Features                            | Permissions           | Hardening                                       |
+/// |----------------|-------------------------------------|-----------------------|-------------------------------------------------|
+/// | `Minimal`      | -                                   | Nothing allowed       | Frozen intrinsics                               |
+/// | `Web`          | `web`, `console`, `url`, `crypto`   | Network only          | -                                               |
+/// | `NodeCompat`   | `node_experimental`                 | Everything            | -                                               |
+/// | `EdgeFunction` | `web`, `console`, `url`, `crypto`   | Public network only   | Frozen intrinsics and exports, 30s, 128MiB heap |
+///
+/// `EdgeFunction` scripts cannot reach this machine, or private and link-local addresses such as
+/// cloud metadata services - see [`crate::AllowlistWebPermissions::set_deny_private_net`]
+///
+/// ```rust
+/// use rustyscript::{Profile, Runtime, RuntimeOptions};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     default_entrypoint: Some("handler".to_string()),
+///     ..RuntimeOptions::from_profile(Profile::Minimal)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Pure computation - no network, filesystem, environment or system access
+    Minimal,
+
+    /// Browser-like scripts - `fetch`, timers and the other web APIs, but no filesystem access
+    Web,
+
+    /// Node.js packages and builtins, with full access - only for trusted code
+    NodeCompat,
+
+    /// Untrusted request handlers - web APIs with access to the public network only, under tight limits
+    EdgeFunction,
+}
+
+impl Profile {
+    /// The crate features the profile's extensions need
+    #[must_use]
+    pub fn features(self) -> &'static [&'static str] {
+        match self {
+            Self::Minimal => &[],
+            Self::Web | Self::EdgeFunction => &["web", "console", "url", "crypto"],
+            Self::NodeCompat => &["node_experimental"],
+        }
+    }
+
+    /// The features the profile needs that the crate was compiled without
+    #[must_use]
+    pub fn missing_features(self) -> Vec<&'static str> {
+        self.features()
+            .iter()
+            .copied()
+            .filter(|feature| !is_enabled(feature))
+            .collect()
+    }
+
+    /// Fails if the crate was compiled without a feature the profile needs
+    ///
+    /// # Errors
+    /// Will return an error naming the missing features
+    pub fn check_features(self) -> Result<(), Error> {
+        let missing = self.missing_features();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Runtime(format!(
+                "The {self:?} profile requires these crate features: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    /// The runtime options for this profile
+    ///
+    /// Override individual fields with `..RuntimeOptions::from_profile(profile)`
+    #[must_use]
+    pub fn options(self) -> RuntimeOptions {
+        let mut options = RuntimeOptions {
+            profile: Some(self),
+            ..Default::default()
+        };
+
+        match self {
+            Self::Minimal => {
+                options.freeze_intrinsics = true;
+            }
+            Self::Web | Self::NodeCompat => {}
+            Self::EdgeFunction => {
+                options.freeze_intrinsics = true;
+                options.freeze_exports = true;
+                options.timeout = Duration::from_secs(30);
+                options.max_heap_size = Some(128 * 1024 * 1024);
+            }
+        }
+
+        #[cfg(feature = "web")]
+        {
+            options.extension_options.web.permissions = self.web_permissions();
+        }
+
+        options
+    }
+
+    #[cfg(feature = "web")]
+    fn web_permissions(self) -> std::sync::Arc<dyn crate::WebPermissions> {
+        use crate::{AllowlistWebPermissions, DefaultWebPermissions};
+
+        match self {
+            Self::Minimal => std::sync::Arc::new(AllowlistWebPermissions::new()),
+            Self::Web | Self::EdgeFunction => {
+                let permissions = AllowlistWebPermissions::new();
+                permissions.set_net_all(true);
+                permissions.set_deny_private_net(self == Self::EdgeFunction);
+                std::sync::Arc::new(permissions)
+            }
+            Self::NodeCompat => std::sync::Arc::new(DefaultWebPermissions),
+        }
+    }
+}
+
+impl RuntimeOptions {
+    /// The runtime options for a preset profile - see [`Profile`]
+    #[must_use]
+    pub fn from_profile(profile: Profile) -> Self {
+        profile.options()
+    }
+}
+
+/// Returns true if the crate was compiled with a feature named by a profile
+fn is_enabled(feature: &str) -> bool {
+    match feature {
+        "web" => cfg!(feature = "web"),
+        "console" => cfg!(feature = "console"),
+        "url" => cfg!(feature = "url"),
+        "crypto" => cfg!(feature = "crypto"),
+        "node_experimental" => cfg!(feature = "node_experimental"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn test_profiles() {
+        assert!(Profile::Minimal.missing_features().is_empty());
+        assert_eq!(
+            Profile::NodeCompat.check_features().is_ok(),
+            cfg!(feature = "node_experimental")
+        );
+
+        let options = RuntimeOptions::from_profile(Profile::EdgeFunction);
+        assert_eq!(options.profile, Some(Profile::EdgeFunction));
+        assert!(options.freeze_exports);
+        assert_eq!(options.timeout, Duration::from_secs(30));
+
+        let mut runtime = Runtime::new(Profile::Minimal.options()).unwrap();
+        let frozen: bool = runtime.eval("Object.isFrozen(Array.prototype)").unwrap();
+        assert!(frozen);
+
+        #[cfg(feature = "web")]
+        {
+            let denied: bool = runtime
+                .eval("fetch('https://example.com').then(() => false, () => true)")
+                .unwrap();
+            assert!(denied);
+
+            // Edge functions can't reach internal services
+            let permissions = Profile::EdgeFunction.web_permissions();
+            for url in [
+                "http://169.254.169.254/latest/meta-data/",
+                "http://127.0.0.1:8080/",
+                "http://2130706433/",
+                "http://localhost/",
+                "http://10.0.0.1/",
+                "http://[::1]/",
+                "http://[::ffff:192.168.0.1]/",
+            ] {
+                let url = deno_core::url::Url::parse(url).unwrap();
+                assert!(permissions.check_url(&url, "fetch()").is_err(), "{url}");
+            }
+            assert!(permissions
+                .check_host("169.254.169.254", None, "Deno.connect()")
+                .is_err());
+            assert!(permissions
+                .check_host("::1", None, "Deno.connect()")
+                .is_err());
+
+            let url = deno_core::url::Url::parse("https://example.com/").unwrap();
+            assert!(permissions.check_url(&url, "fetch()").is_ok());
+            assert!(permissions
+                .check_host("example.com", None, "Deno.connect()")
+                .is_ok());
+        }
+    }
+}
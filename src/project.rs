@@ -0,0 +1,376 @@
+//! Loading a multi-module project from a directory - see [`crate::Runtime::load_project`]
+use std::{
+    collections::{HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use deno_ast::{
+    swc::ast::{ModuleDecl, ModuleItem},
+    ProgramRef,
+};
+use deno_core::serde_json;
+
+use crate::{
+    module_loader::{Rewrite, SpecifierRewrites},
+    traits::ToModuleSpecifier,
+    Error, Module, ModuleHandle,
+};
+
+/// Import map files looked for in a project's directory, in order
+const IMPORT_MAP_FILES: [&str; 2] = ["import_map.json", "deno.json"];
+
+/// Options for [`crate::Runtime::load_project`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectOptions {
+    /// Refuse to load test files - `*.test.*`, `*_test.*`, `*.spec.*`, `*_spec.*`, or anything under
+    /// a `__tests__` directory - as part of the project
+    ///
+    /// Default: true
+    pub ignore_tests: bool,
+
+    /// Resolve bare specifiers with the `imports` of an `import_map.json` or `deno.json` found in
+    /// the project's directory
+    ///
+    /// Default: true
+    pub import_map: bool,
+}
+
+impl Default for ProjectOptions {
+    fn default() -> Self {
+        Self {
+            ignore_tests: true,
+            import_map: true,
+        }
+    }
+}
+
+/// A module belonging to a loaded project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectModule {
+    module: Module,
+    imports: Vec<PathBuf>,
+}
+
+impl ProjectModule {
+    /// The module, as read from disk
+    #[must_use]
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// The path of the module's file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.module.filename()
+    }
+
+    /// The paths of the project modules this module statically imports
+    #[must_use]
+    pub fn imports(&self) -> &[PathBuf] {
+        &self.imports
+    }
+}
+
+/// A handle to a project loaded with [`crate::Runtime::load_project`]
+#[derive(Debug, Clone)]
+pub struct ProjectHandle {
+    entry: ModuleHandle,
+    modules: Vec<ProjectModule>,
+}
+
+impl ProjectHandle {
+    pub(crate) fn new(entry: ModuleHandle, modules: Vec<ProjectModule>) -> Self {
+        Self { entry, modules }
+    }
+
+    /// The handle of the project's entrypoint, for extracting values and calling functions
+    #[must_use]
+    pub fn entry(&self) -> &ModuleHandle {
+        &self.entry
+    }
+
+    /// Every module in the project's graph - the entrypoint first, then its imports breadth-first
+    #[must_use]
+    pub fn modules(&self) -> &[ProjectModule] {
+        &self.modules
+    }
+
+    /// Find a module in the project's graph by the path of its file
+    #[must_use]
+    pub fn module(&self, path: impl AsRef<Path>) -> Option<&ProjectModule> {
+        let path = path.as_ref().canonicalize().ok()?;
+        self.modules.iter().find(|m| m.path() == path)
+    }
+}
+
+/// A project's import graph, discovered before it is loaded
+pub(crate) struct Project {
+    pub modules: Vec<ProjectModule>,
+    pub import_map: SpecifierRewrites,
+}
+
+impl Project {
+    /// Reads the entrypoint and every local module it statically imports, directly or not
+    ///
+    /// # Errors
+    /// Will return an error if a module cannot be read or parsed, or if a module imports a local
+    /// file outside the project - one outside `dir`, or an ignored test file
+    pub fn discover(dir: &Path, entry: &Path, options: ProjectOptions) -> Result<Self, Error> {
+        let dir = dir.canonicalize()?;
+        let import_map = if options.import_map {
+            read_import_map(&dir)?
+        } else {
+            SpecifierRewrites::new()
+        };
+        let mut project = Self {
+            modules: vec![],
+            import_map,
+        };
+
+        let entry = dir.join(entry).canonicalize()?;
+        check_member(&dir, &entry, options)?;
+
+        let mut seen = HashSet::from([entry.clone()]);
+        let mut pending = VecDeque::from([entry]);
+        while let Some(path) = pending.pop_front() {
+            let module = Module::load(&path)?;
+            let mut imports = vec![];
+            for specifier in static_imports(&module)? {
+                let Some(import) = project.resolve_local(&path, &specifier)? else {
+                    continue;
+                };
+                check_member(&dir, &import, options)?;
+                if seen.insert(import.clone()) {
+                    pending.push_back(import.clone());
+                }
+                imports.push(import);
+            }
+
+            project.modules.push(ProjectModule { module, imports });
+        }
+
+        Ok(project)
+    }
+
+    /// Resolves an import to a local file, or `None` if it is remote, bare, or built-in
+    fn resolve_local(&self, referrer: &Path, specifier: &str) -> Result<Option<PathBuf>, Error> {
+        let referrer = referrer.to_module_specifier(Path::new("/"))?;
+        let specifier = match self.import_map.rewrite(specifier) {
+            Some(Rewrite::Virtual(_)) => return Ok(None),
+            Some(rewrite) => {
+                rewrite.resolve(referrer.as_str())?;
+                rewrite.specifier().unwrap_or_default().to_string()
+            }
+            None => specifier.to_string(),
+        };
+        if !["./", "../", "/", "file:"]
+            .iter()
+            .any(|prefix| specifier.starts_with(prefix))
+        {
+            return Ok(None);
+        }
+
+        let url = deno_core::resolve_import(&specifier, referrer.as_str())?;
+
+        if url.scheme() != "file" {
+            return Ok(None);
+        }
+        let path = url
+            .to_file_path()
+            .map_err(|()| Error::Runtime(format!("Invalid file URL: {url}")))?;
+        Ok(Some(path.canonicalize().map_err(|e| {
+            Error::ModuleNotFound(format!("{}: {e}", path.display()))
+        })?))
+    }
+}
+
+/// Fails if the file at `path` cannot be part of the project
+fn check_member(dir: &Path, path: &Path, options: ProjectOptions) -> Result<(), Error> {
+    if !path.starts_with(dir) {
+        return Err(Error::Runtime(format!(
+            "{} is outside the project directory {}",
+            path.display(),
+            dir.display()
+        )));
+    }
+    if options.ignore_tests && is_test_file(path) {
+        return Err(Error::Runtime(format!(
+            "{} is a test file, and cannot be loaded as part of the project",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the `imports` of the first import map found in the directory, resolving their targets
+///
+/// Keys ending in `/` map every specifier they prefix, other keys map a specifier exactly
+fn read_import_map(dir: &Path) -> Result<SpecifierRewrites, Error> {
+    let Some(path) = IMPORT_MAP_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(SpecifierRewrites::new());
+    };
+
+    let text = std::fs::read_to_string(&path)?;
+    let json: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| Error::Runtime(format!("Invalid import map {}: {e}", path.display())))?;
+    let Some(imports) = json.get("imports").and_then(serde_json::Value::as_object) else {
+        return Ok(SpecifierRewrites::new());
+    };
+
+    let base = path.to_module_specifier(dir)?;
+    let mut import_map = SpecifierRewrites::new();
+    for (key, target) in imports {
+        let target = target.as_str().ok_or_else(|| {
+            Error::Runtime(format!(
+                "Invalid import map {}: `{key}` must map to a string",
+                path.display()
+            ))
+        })?;
+        let target = deno_core::resolve_import(target, base.as_str())?;
+        import_map = if key.ends_with('/') {
+            import_map.prefix(key, target)
+        } else {
+            import_map.exact(key, target)
+        };
+    }
+    Ok(import_map)
+}
+
+/// The specifiers a module statically imports or re-exports from, skipping type-only imports
+fn static_imports(module: &Module) -> Result<Vec<String>, Error> {
+    let parsed = crate::module_interface::parse(module)?;
+    let ProgramRef::Module(program) = parsed.program_ref() else {
+        return Ok(vec![]);
+    };
+
+    let mut specifiers = vec![];
+    for item in &program.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        let src = match decl {
+            ModuleDecl::Import(import) if !import.type_only => &import.src,
+            ModuleDecl::ExportAll(export) if !export.type_only => &export.src,
+            ModuleDecl::ExportNamed(export) if !export.type_only => match &export.src {
+                Some(src) => src,
+                None => continue,
+            },
+            _ => continue,
+        };
+        specifiers.push(src.value.to_string());
+    }
+    Ok(specifiers)
+}
+
+/// Returns true if the path follows a common naming convention for tests
+fn is_test_file(path: &Path) -> bool {
+    let in_tests_dir = path
+        .components()
+        .any(|c| c.as_os_str() == std::ffi::OsStr::new("__tests__"));
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let named_as_test = [".test", "_test", ".spec", "_spec"]
+        .iter()
+        .any(|suffix| stem.ends_with(suffix));
+
+    in_tests_dir || named_as_test
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_load_project() {
+        let dir = std::env::temp_dir().join("rustyscript_test_load_project");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("main.ts"),
+            "
+            import { greet } from './lib/greet.ts';
+            import { NAME } from 'config';
+            export const message: string = greet(NAME);
+            ",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lib/greet.ts"),
+            "export * from './shout.js'; export const greet = (n: string) => `Hello, ${n}`;",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lib/shout.js"),
+            "export const shout = (s) => s + '!';",
+        )
+        .unwrap();
+        std::fs::write(dir.join("config.js"), "export const NAME = 'World';").unwrap();
+        std::fs::write(
+            dir.join("import_map.json"),
+            r#"{ "imports": { "config": "./config.js" } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main_test.ts"),
+            "import { message } from './main.ts'; export const ok = message.length > 0;",
+        )
+        .unwrap();
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let project = runtime
+            .load_project(&dir, "main.ts", ProjectOptions::default())
+            .unwrap();
+
+        let message: String = runtime.get_value(Some(project.entry()), "message").unwrap();
+        assert_eq!(message, "Hello, World");
+
+        let paths: Vec<_> = project
+            .modules()
+            .iter()
+            .map(|m| {
+                m.path()
+                    .strip_prefix(dir.canonicalize().unwrap())
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("main.ts"),
+                PathBuf::from("lib/greet.ts"),
+                PathBuf::from("config.js"),
+                PathBuf::from("lib/shout.js"),
+            ]
+        );
+        let greet = project.module(dir.join("lib/greet.ts")).unwrap();
+        assert_eq!(greet.imports().len(), 1);
+
+        // Test files are not part of the project, unless asked for
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        assert!(runtime
+            .load_project(&dir, "main_test.ts", ProjectOptions::default())
+            .is_err());
+        let tests = runtime
+            .load_project(
+                &dir,
+                "main_test.ts",
+                ProjectOptions {
+                    ignore_tests: false,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let ok: bool = runtime.get_value(Some(tests.entry()), "ok").unwrap();
+        assert!(ok);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+//! Token-bucket rate limits, shared between the host and the scripts it runs
+//!
+//! A [`RateLimiter`] holds one bucket per key. The host gives a [`RateLimit`] to each key it wants
+//! to throttle, and can draw from the buckets itself; scripts draw from them with
+//! `rustyscript.rateLimit.acquire(key)`. Keys without a limit are never throttled.
+//!
+//! Clones of a limiter share its buckets - give the same limiter to every runtime belonging to a
+//! tenant to throttle that tenant as a whole. Once a runtime has a limiter (see
+//! [`crate::RuntimeOptions::rate_limiter`]), some keys are also enforced automatically:
+//! - [`RateLimiter::FETCH`] - a token for each URL checked by `fetch` or `WebSocket`
+//! - [`RateLimiter::NET`] - a token for each host or socket connected to by `Deno.connect` and the like
+//! - [`RateLimiter::HOST_CALLS`] - a token for each call to a function registered with the runtime
+//!
+//! Other ops are not counted individually - deno provides no hook to refuse an arbitrary op
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+/// The rate at which a key's bucket refills
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The most tokens the bucket can hold - the largest burst allowed
+    pub capacity: u32,
+
+    /// The time taken to refill an empty bucket
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Allow bursts of up to `capacity`, refilling completely over `per`
+    #[must_use]
+    pub const fn new(capacity: u32, per: Duration) -> Self {
+        Self { capacity, per }
+    }
+
+    /// Allow `n` per second
+    #[must_use]
+    pub const fn per_second(n: u32) -> Self {
+        Self::new(n, Duration::from_secs(1))
+    }
+
+    /// Allow `n` per minute
+    #[must_use]
+    pub const fn per_minute(n: u32) -> Self {
+        Self::new(n, Duration::from_secs(60))
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.capacity),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let capacity = f64::from(self.limit.capacity);
+        if self.limit.per.is_zero() {
+            self.tokens = capacity;
+        } else {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            let refilled = elapsed / self.limit.per.as_secs_f64() * capacity;
+            self.tokens = (self.tokens + refilled).min(capacity);
+        }
+        self.updated = now;
+    }
+
+    /// Takes the tokens, or returns how long until enough will be available
+    fn take(&mut self, tokens: u32) -> Result<(), Duration> {
+        self.refill(Instant::now());
+
+        let wanted = f64::from(tokens);
+        if self.tokens >= wanted {
+            self.tokens -= wanted;
+            return Ok(());
+        }
+
+        if tokens > self.limit.capacity {
+            return Err(Duration::MAX);
+        }
+        let missing = (wanted - self.tokens) / f64::from(self.limit.capacity);
+        Err(self.limit.per.mul_f64(missing))
+    }
+}
+
+/// A set of token buckets, one per key
+///
+/// ```rust
+/// use rustyscript::{RateLimit, RateLimiter};
+///
+/// let limiter = RateLimiter::new()
+///     .with_limit(RateLimiter::FETCH, RateLimit::per_minute(60))
+///     .with_limit("emails", RateLimit::per_second(2));
+///
+/// assert!(limiter.try_acquire("emails", 2).is_ok());
+/// assert!(limiter.try_acquire("emails", 1).is_err());
+/// assert!(limiter.try_acquire("unlimited", 1000).is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter(Arc<Mutex<HashMap<String, Bucket>>>);
+
+impl RateLimiter {
+    /// Consumed by `fetch` and `WebSocket`, once per URL
+    pub const FETCH: &'static str = "fetch";
+
+    /// Consumed by network connections, once per host or socket
+    pub const NET: &'static str = "net";
+
+    /// Consumed by calls to functions registered with the runtime
+    pub const HOST_CALLS: &'static str = "host_calls";
+
+    /// Create a limiter without any limits
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit a key, starting with a full bucket
+    #[must_use]
+    pub fn with_limit(self, key: impl ToString, limit: RateLimit) -> Self {
+        self.set_limit(key, limit);
+        self
+    }
+
+    /// Limit a key, replacing any existing limit and starting with a full bucket
+    pub fn set_limit(&self, key: impl ToString, limit: RateLimit) {
+        self.buckets().insert(key.to_string(), Bucket::new(limit));
+    }
+
+    /// Stop limiting a key
+    pub fn remove_limit(&self, key: &str) {
+        self.buckets().remove(key);
+    }
+
+    /// Returns the tokens currently available for a key, or `None` if it is not limited
+    #[must_use]
+    pub fn available(&self, key: &str) -> Option<u32> {
+        let mut buckets = self.buckets();
+        let bucket = buckets.get_mut(key)?;
+        bucket.refill(Instant::now());
+
+        // Always within 0..=capacity
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Some(bucket.tokens.floor() as u32)
+    }
+
+    /// Take tokens from a key's bucket without waiting
+    ///
+    /// # Errors
+    /// If too few tokens are available, none are taken, and the time until there will be enough is
+    /// returned instead - [`Duration::MAX`] if the request exceeds the bucket's capacity
+    pub fn try_acquire(&self, key: &str, tokens: u32) -> Result<(), Duration> {
+        match self.buckets().get_mut(key) {
+            Some(bucket) => bucket.take(tokens),
+            None => Ok(()),
+        }
+    }
+
+    /// Take tokens from a key's bucket, waiting until enough are available
+    ///
+    /// # Errors
+    /// Will return an error if the request exceeds the bucket's capacity, and so can never succeed
+    pub async fn acquire(&self, key: &str, tokens: u32) -> Result<(), Error> {
+        loop {
+            match self.try_acquire(key, tokens) {
+                Ok(()) => return Ok(()),
+                Err(Duration::MAX) => {
+                    return Err(Error::Runtime(format!(
+                        "Cannot acquire {tokens} tokens at once for `{key}`: exceeds its capacity"
+                    )));
+                }
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Take a single token, failing with [`Error::RateLimited`] if none is available
+    pub(crate) fn check(&self, key: &str) -> Result<(), Error> {
+        self.try_acquire(key, 1)
+            .map_err(|_| Error::RateLimited(key.to_string()))
+    }
+
+    fn buckets(&self) -> MutexGuard<'_, HashMap<String, Bucket>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter =
+            RateLimiter::new().with_limit("a", RateLimit::new(2, Duration::from_secs(60)));
+        let shared = limiter.clone();
+
+        assert_eq!(limiter.available("a"), Some(2));
+        assert!(limiter.try_acquire("a", 1).is_ok());
+        assert!(shared.try_acquire("a", 1).is_ok());
+
+        let wait = limiter.try_acquire("a", 1).unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+        assert_eq!(limiter.try_acquire("a", 3), Err(Duration::MAX));
+
+        assert_eq!(limiter.available("b"), None);
+        assert!(limiter.try_acquire("b", u32::MAX).is_ok());
+
+        limiter.remove_limit("a");
+        assert!(shared.try_acquire("a", 1).is_ok());
+    }
+
+    #[test]
+    fn test_script_rate_limit() {
+        let limiter = RateLimiter::new()
+            .with_limit("jobs", RateLimit::new(2, Duration::from_millis(50)))
+            .with_limit(RateLimiter::HOST_CALLS, RateLimit::per_minute(1));
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            rate_limiter: Some(limiter.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        runtime
+            .register_function("ping", |_| Ok(crate::serde_json::Value::Null))
+            .unwrap();
+
+        let module = runtime
+            .load_module(&Module::new(
+                "test.js",
+                "
+                const limit = rustyscript.rateLimit;
+                export const taken = [limit.tryAcquire('jobs'), limit.tryAcquire('jobs'), limit.tryAcquire('jobs')];
+
+                const start = Date.now();
+                await limit.acquire('jobs');
+                export const waited = Date.now() - start;
+
+                export const tooMany = await limit.acquire('jobs', 5).catch((e) => e.message);
+
+                rustyscript.functions.ping();
+                export let throttled;
+                try {
+                    rustyscript.functions.ping();
+                } catch (e) {
+                    throttled = e.message;
+                }
+            ",
+            ))
+            .unwrap();
+
+        let taken: Vec<bool> = runtime.get_value(Some(&module), "taken").unwrap();
+        assert_eq!(taken, vec![true, true, false]);
+        let waited: u64 = runtime.get_value(Some(&module), "waited").unwrap();
+        assert!(waited >= 10, "{waited}");
+        let too_many: String = runtime.get_value(Some(&module), "tooMany").unwrap();
+        assert!(too_many.contains("exceeds its capacity"));
+        let throttled: String = runtime.get_value(Some(&module), "throttled").unwrap();
+        assert!(throttled.contains("host_calls"), "{throttled}");
+    }
+}
@@ -0,0 +1,276 @@
+//! Retries with jittered exponential backoff for transient failures
+//!
+//! Once a runtime has a [`RetryPolicy`] (see [`crate::RuntimeOptions::retry_policy`]), it is applied to:
+//! - Calls to async and blocking functions registered with the runtime, when they fail with an
+//!   error accepted by [`RetryPolicy::retry_if`]
+//! - `fetch` requests that can safely be replayed - `GET`, `HEAD`, `OPTIONS`, `PUT` and `DELETE`
+//!   without a streaming body - when the connection fails, or the response has one of
+//!   [`RetryPolicy::retry_statuses`] (by default, the 5xx statuses that describe a passing failure)
+//!
+//! Synchronous registered functions are never retried, since waiting would block the event loop
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use crate::Error;
+
+/// How, and how often, a failed operation is retried
+///
+/// ```rust
+/// use rustyscript::{RetryPolicy, Runtime, RuntimeOptions};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let runtime = Runtime::new(RuntimeOptions {
+///     retry_policy: Some(RetryPolicy {
+///         max_attempts: 5,
+///         base_delay: Duration::from_millis(50),
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The most times an operation is attempted, including the first
+    ///
+    /// Default: 3
+    pub max_attempts: u32,
+
+    /// The delay before the first retry - it doubles for every retry after that
+    ///
+    /// Default: 100ms
+    pub base_delay: Duration,
+
+    /// The longest delay between two attempts
+    ///
+    /// Default: 5s
+    pub max_delay: Duration,
+
+    /// Response statuses for which a `fetch` is retried
+    ///
+    /// Default: 500, 502, 503 and 504 - the other 5xx statuses, such as `501 Not Implemented`,
+    /// describe failures that another attempt will not fix
+    pub retry_statuses: Vec<u16>,
+
+    /// Decides which errors returned by registered functions are transient
+    ///
+    /// Default: [`RetryPolicy::is_transient`]
+    pub retry_if: fn(&Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            retry_statuses: vec![500, 502, 503, 504],
+            retry_if: Self::is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default for [`RetryPolicy::retry_if`] - timeouts, and [`Error::Io`] failures of the
+    /// connection, such as a reset or a refused connection
+    ///
+    /// Missing files, denied permissions and other I/O errors are not retried
+    #[must_use]
+    pub fn is_transient(error: &Error) -> bool {
+        use std::io::ErrorKind;
+        match error {
+            Error::Timeout(_) => true,
+            Error::Io(kind, _) => matches!(
+                kind,
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    /// The delay before retrying after the given attempt, starting from 1
+    ///
+    /// Exponential, capped at `max_delay`, then jittered randomly into its upper half - so that
+    /// runtimes failing together do not retry together
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+
+        let floor = delay / 2;
+        let span = u64::try_from((delay - floor).as_millis()).unwrap_or(u64::MAX);
+        let jitter = RandomState::new().build_hasher().finish() % span.saturating_add(1);
+        floor + Duration::from_millis(jitter)
+    }
+
+    /// Returns true if an operation that failed on the given attempt, starting from 1, may be retried
+    #[must_use]
+    pub fn allows_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Runs an operation until it succeeds, fails with an error that is not transient, or runs out
+    /// of attempts - returning the last error
+    pub(crate) async fn run<F, Fut, T>(&self, mut operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if self.allows_retry(attempt) && (self.retry_if)(&e) => {
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            ..Default::default()
+        };
+
+        for _ in 0..20 {
+            let first = policy.delay(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+            let capped = policy.delay(10);
+            assert!(capped >= Duration::from_millis(150) && capped <= Duration::from_millis(300));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        // Transient errors are retried until they succeed
+        let calls = Cell::new(0);
+        let result = policy
+            .run(|| {
+                calls.set(calls.get() + 1);
+                let n = calls.get();
+                async move {
+                    if n < 3 {
+                        Err(Error::Timeout("slow".to_string()))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        // ... but give up once out of attempts
+        calls.set(0);
+        let result: Result<(), Error> = policy
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err(Error::Timeout("slow".to_string())) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 3);
+
+        // Other errors are returned at once
+        calls.set(0);
+        let result: Result<(), Error> = policy
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err(Error::Runtime("bad input".to_string())) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+
+        // Including I/O errors that are not about the connection, such as a missing module
+        for error in [
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        ] {
+            calls.set(0);
+            let error = Error::from(error);
+            let result: Result<(), Error> = policy
+                .run(|| {
+                    calls.set(calls.get() + 1);
+                    let error = error.clone();
+                    async move { Err(error) }
+                })
+                .await;
+            assert!(result.is_err());
+            assert_eq!(calls.get(), 1);
+        }
+        assert!(matches!(
+            Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)),
+            Error::ModuleNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn test_retried_functions() {
+        use crate::{json_args, Module, Runtime, RuntimeOptions};
+        use std::rc::Rc;
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            retry_policy: Some(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let calls = Rc::new(Cell::new(0));
+        let calls_ = calls.clone();
+        runtime
+            .register_async_function("flaky", move |_| {
+                calls_.set(calls_.get() + 1);
+                let n = calls_.get();
+                Box::pin(async move {
+                    if n < 3 {
+                        Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into())
+                    } else {
+                        Ok(n.into())
+                    }
+                })
+            })
+            .unwrap();
+
+        let module = Module::new(
+            "test.js",
+            "export const call = () => rustyscript.async_functions.flaky();",
+        );
+        let module = runtime.load_module(&module).unwrap();
+        let result: u32 = runtime
+            .call_function(Some(&module), "call", json_args!())
+            .unwrap();
+        assert_eq!(result, 3);
+        assert_eq!(calls.get(), 3);
+    }
+}
@@ -1,13 +1,19 @@
-use std::{path::Path, rc::Rc, time::Duration};
+use std::{collections::HashMap, path::Path, rc::Rc, time::Duration};
 
-use deno_core::PollEventLoopOptions;
+use deno_core::{JsBuffer, PollEventLoopOptions, ToJsBuffer};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     async_bridge::{AsyncBridge, AsyncBridgeExt, TokioRuntime},
-    inner_runtime::{InnerRuntime, RsAsyncFunction, RsFunction},
-    js_value::Function,
-    Error, Module, ModuleHandle,
+    inner_runtime::{
+        GcKind, InnerRuntime, MemoryPressure, RsAsyncFunction, RsBlockingFunction, RsFunction,
+        RuntimeId,
+    },
+    js_value::{DurableHandle, Function},
+    module_handle::ENTRYPOINT_CALL,
+    project::{Project, ProjectHandle, ProjectOptions},
+    traits::ToModuleSpecifier,
+    Error, HostAction, Module, ModuleHandle, PendingOpInfo, PendingOpKind, ResourceInfo,
 };
 
 /// Represents the set of options accepted by the runtime constructor
@@ -80,8 +86,7 @@ impl Runtime {
     /// Or if the deno runtime initialization fails (usually issues with extensions)
     pub fn new(options: RuntimeOptions) -> Result<Self, Error> {
         let tokio = AsyncBridge::new(options.timeout)?;
-        let inner = InnerRuntime::new(options, tokio.heap_exhausted_token())?;
-        Ok(Self { inner, tokio })
+        Self::with_bridge(options, tokio)
     }
 
     /// Creates a new instance of the runtime with the provided options and a pre-configured tokio runtime.  
@@ -94,8 +99,7 @@ impl Runtime {
         tokio: Rc<tokio::runtime::Runtime>,
     ) -> Result<Self, Error> {
         let tokio = AsyncBridge::with_tokio_runtime(options.timeout, tokio);
-        let inner = InnerRuntime::new(options, tokio.heap_exhausted_token())?;
-        Ok(Self { inner, tokio })
+        Self::with_bridge(options, tokio)
     }
 
     /// Creates a new instance of the runtime with the provided options and a borrowed tokio runtime handle.  
@@ -108,7 +112,27 @@ impl Runtime {
         handle: tokio::runtime::Handle,
     ) -> Result<Self, Error> {
         let tokio = AsyncBridge::with_runtime_handle(options.timeout, handle);
-        let inner = InnerRuntime::new(options, tokio.heap_exhausted_token())?;
+        Self::with_bridge(options, tokio)
+    }
+
+    fn with_bridge(mut options: RuntimeOptions, tokio: AsyncBridge) -> Result<Self, Error> {
+        // Virtual stdio is driven by the tokio runtime, so it must be connected here
+        #[cfg(feature = "io")]
+        if let Some(stdio) = options.extension_options.stdio.as_mut() {
+            let pipes = options.extension_options.io_pipes.take();
+            options.extension_options.io_pipes =
+                Some(stdio.attach(&tokio.tokio_runtime().handle(), pipes)?);
+        }
+
+        let heartbeat = crate::heartbeat::HeartbeatMonitor {
+            options: options.heartbeat.take(),
+            deadline: tokio.deadline(),
+        };
+        tokio.deadline().set_grace(options.cancel_grace);
+
+        let mut inner = InnerRuntime::new(options, tokio.heap_exhausted_token())?;
+        inner.put(heartbeat)?;
+        inner.put(tokio.deadline())?;
         Ok(Self { inner, tokio })
     }
 
@@ -117,6 +141,12 @@ impl Runtime {
         self.inner.deno_runtime()
     }
 
+    /// Returns the unique id of this runtime instance
+    #[must_use]
+    pub fn id(&self) -> RuntimeId {
+        self.inner.id
+    }
+
     /// Access the underlying tokio runtime used for blocking operations
     #[must_use]
     pub fn tokio_runtime(&self) -> TokioRuntime {
@@ -136,6 +166,13 @@ impl Runtime {
         self.tokio.heap_exhausted_token()
     }
 
+    /// Returns a handle that cancels the call the runtime is running, from any thread  
+    /// See [`crate::CallCanceller`]
+    #[must_use]
+    pub fn canceller(&self) -> crate::CallCanceller {
+        self.tokio.deadline().canceller()
+    }
+
     /// Destroy the v8 runtime, releasing all resources  
     /// Then the internal tokio runtime will be returned
     #[must_use]
@@ -163,6 +200,183 @@ impl Runtime {
         self.inner.current_dir()
     }
 
+    /// Returns the number of modules loaded into the runtime, and the size of their source
+    ///
+    /// These are the quantities capped by [`crate::RuntimeOptions::module_limits`]
+    #[must_use]
+    pub fn module_usage(&self) -> crate::ModuleUsage {
+        self.inner.module_usage()
+    }
+
+    /// Returns a breakdown of the time spent creating the runtime, and loading each module since
+    ///
+    /// Only recorded if [`crate::RuntimeOptions::trace_startup`] was set
+    ///
+    /// ```rust
+    /// use rustyscript::{Module, Runtime, RuntimeOptions};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     trace_startup: true,
+    ///     ..Default::default()
+    /// })?;
+    /// runtime.load_module(&Module::new("test.ts", "export const x: number = 1;"))?;
+    ///
+    /// let trace = runtime.startup_trace().unwrap();
+    /// assert_eq!(trace.modules.len(), 1);
+    /// println!("isolate: {:?}, total: {:?}", trace.isolate_init, trace.total());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn startup_trace(&self) -> Option<&crate::StartupTrace> {
+        self.inner.startup_trace.as_ref()
+    }
+
+    /// Captures the state of the modules loaded so far as a snapshot, which can seed future
+    /// runtimes through [`crate::RuntimeOptions::startup_snapshot`]
+    ///
+    /// V8 can only snapshot an isolate created for the purpose, so this does not capture this
+    /// runtime's isolate. Instead, the code of every module loaded with [`Runtime::load_module`]
+    /// or [`Runtime::load_modules`] is run again, in the same order, in a [`crate::SnapshotBuilder`]
+    /// created with `options`:
+    /// - Each module's top-level code is re-executed, repeating any side effects it has, such as
+    ///   requests, timers or writes
+    /// - The snapshot holds module state as it stands once loading finishes - changes made since,
+    ///   by calling into this runtime, are not captured
+    /// - Code run with [`Runtime::eval`], and modules imported dynamically after loading, are not
+    ///   replayed
+    ///
+    /// `options` should match those given to this runtime, and a runtime seeded from the snapshot
+    /// must be given the same extensions
+    ///
+    /// Capture the snapshot once, then leak it to seed every runtime in a pool:
+    /// ```rust
+    /// use rustyscript::{Error, Module, Runtime, RuntimeOptions};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("tenant.js", "globalThis.routes = new Map([['/', 'index']]);");
+    /// let mut runtime = Runtime::new(RuntimeOptions::default())?;
+    /// runtime.load_module(&module)?;
+    ///
+    /// let snapshot: &'static [u8] = Box::leak(runtime.snapshot(RuntimeOptions::default())?);
+    /// let mut seeded = Runtime::new(RuntimeOptions {
+    ///     startup_snapshot: Some(snapshot),
+    ///     ..Default::default()
+    /// })?;
+    /// let route: String = seeded.eval("globalThis.routes.get('/')")?;
+    /// assert_eq!(route, "index");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Can fail if the snapshot runtime cannot be created, or if one of the modules fails to load
+    /// into it
+    #[cfg(feature = "snapshot_builder")]
+    pub fn snapshot(&self, options: RuntimeOptions) -> Result<Box<[u8]>, Error> {
+        let mut builder = crate::SnapshotBuilder::new(options)?;
+        for specifier in &self.inner.loaded_modules {
+            let not_found = || Error::Runtime(format!("Cannot snapshot {specifier}"));
+            let code = self
+                .inner
+                .module_loader
+                .loaded_code(specifier.as_str())
+                .ok_or_else(not_found)?;
+            let path = specifier.to_file_path().map_err(|()| not_found())?;
+            builder.load_module(&Module::new(path, code))?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// Returns the ops, resources and timers the event loop is currently waiting on
+    ///
+    /// Useful for reporting what a script is blocked on, or for deciding when a runtime
+    /// has drained during shutdown
+    pub fn pending_ops(&mut self) -> Vec<PendingOpInfo> {
+        self.inner.pending_ops()
+    }
+
+    /// Run the promise reactions queued so far, and any they queue in turn, until none remain
+    ///
+    /// Ops and timers are not polled, so nothing that waits on the event loop makes progress.  
+    /// Rejections left unhandled are reported the next time the event loop is run
+    pub fn drain_microtasks(&mut self) {
+        self.inner.drain_microtasks();
+    }
+
+    /// Run a single turn of the event loop, without waiting for anything - completed ops and
+    /// expired timers have their callbacks run, followed by the microtasks they queue
+    ///
+    /// Returns true if the event loop still has pending work
+    ///
+    /// # Errors
+    /// Can fail if a runtime error occurs during the turn
+    pub fn tick(&mut self) -> Result<bool, Error> {
+        self.advance_event_loop(PollEventLoopOptions::default())
+    }
+
+    /// Fails if the event loop is still waiting on async ops or timers
+    ///
+    /// Open resources, such as the standard streams, are not counted - see [`Runtime::resources`]  
+    /// The error lists what is still pending, as reported by [`Runtime::pending_ops`]
+    ///
+    /// # Errors
+    /// Will return an error if any async op, timer or interval is still pending
+    pub fn assert_idle(&mut self) -> Result<(), Error> {
+        let pending: Vec<_> = self
+            .pending_ops()
+            .into_iter()
+            .filter(|op| op.kind != PendingOpKind::Resource)
+            .map(|op| op.name)
+            .collect();
+
+        if pending.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Runtime(format!(
+                "Event loop is not idle - still pending: {}",
+                pending.join(", ")
+            )))
+        }
+    }
+
+    /// Ask V8 to garbage collect the runtime's heap
+    ///
+    /// Useful for pool managers compacting idle isolates between bursts of work  
+    /// A [`GcKind::Full`] collection is usually enough; [`GcKind::Compact`] also releases cached data
+    pub fn request_gc(&mut self, kind: GcKind) {
+        self.inner.request_gc(kind);
+    }
+
+    /// Inform V8 of the memory pressure the host is under
+    ///
+    /// Under [`MemoryPressure::Moderate`] or above, V8 collects more eagerly;
+    /// send [`MemoryPressure::None`] once the pressure has been relieved
+    pub fn notify_memory_pressure(&mut self, level: MemoryPressure) {
+        self.inner.notify_memory_pressure(level);
+    }
+
+    /// Returns the open entries in the runtime's resource table - files, sockets, streams, etc
+    ///
+    /// Timers are not resources; see [`Runtime::pending_ops`] for those
+    ///
+    /// # Errors
+    /// Will return an error if the op state is currently borrowed
+    pub fn resources(&mut self) -> Result<Vec<ResourceInfo>, Error> {
+        self.inner.resources()
+    }
+
+    /// Forcibly close a resource by its id, such as a file or socket leaked by a script
+    ///
+    /// Any pending ops on the resource will be cancelled
+    ///
+    /// # Errors
+    /// Will return an error if no resource exists with the given id
+    pub fn close_resource(&mut self, rid: deno_core::ResourceId) -> Result<(), Error> {
+        self.inner.close_resource(rid)
+    }
+
     /// Advance the JS event loop by a single tick  
     /// See [`Runtime::block_on_event_loop`] for fully running the event loop
     ///
@@ -330,6 +544,178 @@ impl Runtime {
         self.inner.register_async_function(name, callback)
     }
 
+    /// Register a host-mediated action, invoked from JS with `rustyscript.actions.invoke(name, payload)`
+    ///
+    /// Gives scripts a narrow, audited way to cause side effects - sending an email, posting a webhook -
+    /// without granting them network access. Payloads are checked against the action's schema and
+    /// rate limit before the handler is called. See [`HostAction`]
+    ///
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    ///
+    /// ```rust
+    /// use rustyscript::{serde_json::json, HostAction, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_action(
+    ///     "webhook",
+    ///     HostAction::new(|payload| async move { Ok(json!({ "delivered": payload["event"] })) }),
+    /// )?;
+    ///
+    /// let module = Module::new(
+    ///     "test.js",
+    ///     "export const result = await rustyscript.actions.invoke('webhook', { event: 'signup' });",
+    /// );
+    /// let module = runtime.load_module(&module)?;
+    /// let result: rustyscript::serde_json::Value = runtime.get_value(Some(&module), "result")?;
+    /// assert_eq!(result, json!({ "delivered": "signup" }));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_action(&mut self, name: &str, action: HostAction) -> Result<(), Error> {
+        self.inner.register_action(name, action)
+    }
+
+    /// Register a source of JSON values for scripts to read with `rustyscript.json.read(name)`
+    ///
+    /// The reader is consumed a chunk at a time as the script iterates, so the whole payload never
+    /// needs to be in memory. It may hold a single top-level array, whose elements are yielded one
+    /// by one, or a sequence of values such as newline-delimited JSON. Registering under an existing
+    /// name replaces the earlier source
+    ///
+    /// Requires the `json` feature
+    ///
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    ///
+    /// ```rust
+    /// use rustyscript::Runtime;
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_json_source("rows", std::io::Cursor::new("[1, 2, 3]"))?;
+    ///
+    /// let total: i64 = runtime.eval("[...rustyscript.json.read('rows')].reduce((a, b) => a + b)")?;
+    /// assert_eq!(total, 6);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn register_json_source(
+        &mut self,
+        name: &str,
+        reader: impl std::io::Read + 'static,
+    ) -> Result<(), Error> {
+        self.inner.register_json_source(name, reader)
+    }
+
+    /// Register a sink for scripts to write JSON values to with `rustyscript.json.write(name, values)`
+    ///
+    /// Values are serialized straight to the writer, laid out according to `format`. The sink is
+    /// flushed when the script calls `rustyscript.json.close(name)`. Registering under an existing
+    /// name replaces the earlier sink
+    ///
+    /// Requires the `json` feature
+    ///
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn register_json_sink(
+        &mut self,
+        name: &str,
+        writer: impl std::io::Write + 'static,
+        format: crate::JsonFormat,
+    ) -> Result<(), Error> {
+        self.inner.register_json_sink(name, writer, format)
+    }
+
+    /// Register TypeScript declarations describing part of the host API
+    ///
+    /// Declarations are not checked or used by the runtime - they are collected for
+    /// [`Runtime::host_api_declarations`], so that platforms can offer accurate types to users writing
+    /// scripts. Registering under an existing name replaces the earlier declarations
+    ///
+    /// ```rust
+    /// use rustyscript::{serde_json::Value, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_function("add", |args| {
+    ///     let sum = args.iter().filter_map(Value::as_i64).sum::<i64>();
+    ///     Ok(sum.into())
+    /// })?;
+    /// runtime.register_declarations(
+    ///     "add",
+    ///     "declare namespace rustyscript.functions { function add(...n: number[]): number; }",
+    /// );
+    ///
+    /// assert!(runtime.host_api_declarations().contains("function add"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_declarations(&mut self, name: &str, declarations: impl ToString) {
+        let declarations = declarations.to_string();
+        match self.inner.declarations.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = declarations,
+            None => self
+                .inner
+                .declarations
+                .push((name.to_string(), declarations)),
+        }
+    }
+
+    /// Returns every set of declarations registered with [`Runtime::register_declarations`], as the
+    /// text of a single `.d.ts` file
+    ///
+    /// Each set is preceded by a comment with the name it was registered under, in the order they
+    /// were first registered
+    #[must_use]
+    pub fn host_api_declarations(&self) -> String {
+        let mut dts = String::from("// Host API declarations\n");
+        for (name, declarations) in &self.inner.declarations {
+            dts.push_str(&format!("\n// {name}\n{}\n", declarations.trim_end()));
+        }
+        dts
+    }
+
+    /// Register a blocking rust function to be callable from JS
+    /// - The [`crate::sync_callback`] macro can be used to simplify this process
+    ///
+    /// The function runs on tokio's blocking thread pool, so long-running calls such as database
+    /// queries do not stall the event loop. From JS it is called like any other async function,
+    /// through `rustyscript.async_functions`, and returns a promise
+    ///
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    ///
+    /// ```rust
+    /// use rustyscript::{sync_callback, Error, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new("test.js", " await rustyscript.async_functions.query('users'); ");
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_blocking_function(
+    ///     "query",
+    ///     sync_callback!(|table: String| {
+    ///         std::thread::sleep(std::time::Duration::from_millis(10));
+    ///         Ok::<usize, Error>(table.len())
+    ///     }),
+    /// )?;
+    /// runtime.load_module(&module)?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_blocking_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsBlockingFunction,
+    {
+        self.inner.register_blocking_function(name, callback)
+    }
+
     /// Evaluate a piece of non-ECMAScript-module JavaScript code  
     /// The expression is evaluated in the global context, so changes persist
     ///
@@ -415,9 +801,13 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
-        let result = self.inner.eval(expr.to_string()).await?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        let result = async {
+            let result = self.inner.eval(expr.to_string()).await?;
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+        self.labeled(result)
     }
 
     /// Evaluate a piece of non-ECMAScript-module JavaScript code  
@@ -457,8 +847,12 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
-        let result = self.inner.eval(expr.to_string()).await?;
-        self.inner.decode_value(result)
+        let result = async {
+            let result = self.inner.eval(expr.to_string()).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+        self.labeled(result)
     }
 
     /// Calls a stored javascript function and deserializes its return value.
@@ -492,16 +886,34 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
+        #[cfg(feature = "otel")]
+        let span = self
+            .inner
+            .call_span("call_stored_function", "<stored>", module_context);
+
+        if let Err(e) = function.check_runtime(self.id()) {
+            return self.labeled(Err(e));
+        }
         let function = {
             let rt = self.deno_runtime();
             deno_core::scope!(scope, rt);
             function.as_global(scope)
         };
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        let result = async {
+            let result = self
+                .inner
+                .call_function_by_ref(module_context, &function, args)?;
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span {
+            span.finish(&result);
+        }
+
+        self.labeled(result)
     }
 
     /// Calls a stored javascript function and deserializes its return value.
@@ -569,6 +981,9 @@ impl Runtime {
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
+        if let Err(e) = function.check_runtime(self.id()) {
+            return self.labeled(Err(e));
+        }
         let function = {
             let rt = self.deno_runtime();
             deno_core::scope!(scope, rt);
@@ -576,8 +991,9 @@ impl Runtime {
         };
         let result = self
             .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        self.inner.decode_value(result)
+            .call_function_by_ref(module_context, &function, args)
+            .and_then(|result| self.inner.decode_value(result));
+        self.labeled(result)
     }
 
     /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
@@ -612,12 +1028,23 @@ impl Runtime {
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        #[cfg(feature = "otel")]
+        let span = self.inner.call_span("call_function", name, module_context);
+
+        let result = async {
+            let _permit = self.inner.admit_call(module_context, name)?;
+            let result = self.inner.call_function_warm(module_context, name, args)?;
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span {
+            span.finish(&result);
+        }
+
+        self.labeled(result)
     }
 
     /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
@@ -669,64 +1096,251 @@ impl Runtime {
         })
     }
 
-    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
-    ///
-    /// Will not attempt to resolve promises, or run the event loop  
-    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]  
-    /// The event loop should be run using [`Runtime::await_event_loop`]
-    ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the javascript function to call.
-    /// * `args` - The arguments to pass to the function
+    /// Calls a javascript function by its name, as [`Runtime::call_function`] does, with a context
+    /// the script reads with `rustyscript.context()`
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)  
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
+    /// Useful for request IDs, user identity and other metadata that should reach the script
+    /// without being added to every function's arguments. The context is frozen, and follows the
+    /// call across `await`s and timers - work started by other calls does not see it
     ///
     /// # Errors
-    /// Fails if the function cannot be found, if there are issues with calling the function,  
-    /// Or if the result cannot be deserialized into the requested type
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// or if the result cannot be deserialized into the requested type
     ///
     /// # Example
     ///
     /// ```rust
-    /// use rustyscript::{json_args, Error, Module, Runtime};
+    /// use rustyscript::{json_args, serde_json::json, Module, Runtime};
     ///
-    /// # fn main() -> Result<(), Error> {
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new(
+    ///     "test.js",
+    ///     "
+    ///     export async function greet(greeting) {
+    ///         await new Promise(r => setTimeout(r, 10));
+    ///         return `${greeting}, ${rustyscript.context().user}`;
+    ///     }
+    ///     ",
+    /// );
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
     /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
+    ///
+    /// let context = json!({ "requestId": "abc123", "user": "alice" });
+    /// let greeting: String = runtime
+    ///     .call_function_with_context(Some(&module), "greet", &context, json_args!("Hello"))?;
+    /// assert_eq!(greeting, "Hello, alice");
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_function_immediate<T>(
+    pub fn call_function_with_context<T>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         name: &str,
+        context: &impl serde::ser::Serialize,
         args: &impl serde::ser::Serialize,
     ) -> Result<T, Error>
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        self.inner.decode_value(result)
+        self.block_on(|runtime| async move {
+            runtime
+                .call_function_with_context_async(module_context, name, context, args)
+                .await
+        })
     }
 
-    /// Get a value from a runtime instance
-    ///
-    /// Blocks until:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Calls a javascript function by its name with a context the script reads with `rustyscript.context()`
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// See [`Runtime::call_function_with_context`]
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// or if the result cannot be deserialized into the requested type
+    pub async fn call_function_with_context_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        context: &impl serde::ser::Serialize,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let result = async {
+            let (enter, exit) = {
+                let state = self.deno_runtime().op_state();
+                let state = state.try_borrow()?;
+                crate::ext::rustyscript::CallContext::get(&state)?
+            };
+
+            let _permit = self.inner.admit_call(module_context, name)?;
+            self.inner.call_function_by_ref(None, &enter, &[context])?;
+            let result = self.inner.call_function_warm(module_context, name, args);
+            self.inner.call_function_by_ref(None, &exit, &())?;
+
+            let result = self.inner.resolve_with_event_loop(result?).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+
+        self.labeled(result)
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    ///
+    /// Will not attempt to resolve promises, or run the event loop  
+    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]  
+    /// The event loop should be run using [`Runtime::await_event_loop`]
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)  
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,  
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Error, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_function_immediate<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let result = self
+            .inner
+            .admit_call(module_context, name)
+            .and_then(|permit| {
+                let result = self.inner.call_function_warm(module_context, name, args)?;
+                self.inner.finish_call(permit, &result);
+                self.inner.decode_value(result)
+            });
+        self.labeled(result)
+    }
+
+    /// Calls a javascript function by its name, passing its arguments and result in a binary encoding
+    ///
+    /// The arguments are encoded with `codec`, and passed to the function as a single `Uint8Array`.
+    /// The function decodes them with the matching `rustyscript.codecs` helper, and must return
+    /// (or resolve to) its result encoded the same way - see [`crate::Codec`]
+    ///
+    /// Requires the `codecs` feature
+    ///
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// or if the arguments or result cannot be encoded or decoded
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{Codec, Error, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new(
+    ///     "/path/to/module.js",
+    ///     "
+    ///     const { encode, decode } = rustyscript.codecs.msgpack;
+    ///     export function add(payload) {
+    ///         const [a, b] = decode(payload);
+    ///         return encode(a + b);
+    ///     }
+    ///     ",
+    /// );
+    /// let module = runtime.load_module(&module)?;
+    /// let value: u32 = runtime.call_function_encoded(Codec::Msgpack, Some(&module), "add", &(2, 3))?;
+    /// assert_eq!(value, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "codecs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codecs")))]
+    pub fn call_function_encoded<T>(
+        &mut self,
+        codec: crate::Codec,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.block_on(|runtime| async move {
+            runtime
+                .call_function_encoded_async(codec, module_context, name, args)
+                .await
+        })
+    }
+
+    /// Calls a javascript function by its name, passing its arguments and result in a binary encoding
+    ///
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// See [`Runtime::call_function_encoded`] for an example
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// or if the arguments or result cannot be encoded or decoded
+    #[cfg(feature = "codecs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "codecs")))]
+    pub async fn call_function_encoded_async<T>(
+        &mut self,
+        codec: crate::Codec,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let payload: ToJsBuffer = match codec.encode(args) {
+            Ok(payload) => payload.into(),
+            Err(e) => return self.labeled(Err(e)),
+        };
+
+        let result: JsBuffer = self
+            .call_function_async(module_context, name, &(payload,))
+            .await?;
+        let result = codec.decode(&result);
+        self.labeled(result)
+    }
+
+    /// Get a value from a runtime instance
+    ///
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
     ///
     /// # Returns
     /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
@@ -785,9 +1399,16 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        let result = async {
+            let result = match self.inner.get_value_ref(module_context, name) {
+                Err(e @ Error::ValueNotFound(_)) => return self.inner.decode_missing(e),
+                result => result?,
+            };
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+        self.labeled(result)
     }
 
     /// Get a value from a runtime instance
@@ -828,8 +1449,12 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        self.inner.decode_value(result)
+        let result = match self.inner.get_value_ref(module_context, name) {
+            Ok(result) => self.inner.decode_value(result),
+            Err(e @ Error::ValueNotFound(_)) => self.inner.decode_missing(e),
+            Err(e) => Err(e),
+        };
+        self.labeled(result)
     }
 
     /// Executes the given module, and returns a handle allowing you to extract values
@@ -879,6 +1504,9 @@ impl Runtime {
     /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
     /// to resolve background tasks and async listeners
     ///
+    /// Large modules, and the modules they import, are transpiled on tokio's blocking thread pool,
+    /// so the calling thread is not held up while they are transformed
+    ///
     /// # Arguments
     /// * `module` - A `Module` object containing the module's filename and contents.
     ///
@@ -891,7 +1519,8 @@ impl Runtime {
     ///
     /// See [`Runtime::load_module`] for an example
     pub async fn load_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(None, vec![module]).await
+        let result = self.inner.load_modules(None, vec![module]).await;
+        self.labeled(result)
     }
 
     /// Executes the given module, and returns a handle allowing you to extract values
@@ -969,7 +1598,89 @@ impl Runtime {
         module: &Module,
         side_modules: Vec<&Module>,
     ) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(Some(module), side_modules).await
+        let result = self.inner.load_modules(Some(module), side_modules).await;
+        self.labeled(result)
+    }
+
+    /// Loads a project from a directory - an entrypoint, and every local module it imports
+    ///
+    /// The entrypoint's static imports are followed before anything runs, so that the whole graph
+    /// can be allowed to load from disk even if `fs_import` is disabled, and so that a project
+    /// importing files outside `dir` fails before any of it is executed
+    ///
+    /// Blocks until the project has been executed AND the event loop has fully resolved  
+    /// See [`Runtime::load_project_async`] for a non-blocking variant
+    ///
+    /// # Arguments
+    /// * `dir` - The project's directory
+    /// * `entry` - The path of the entrypoint, relative to `dir`
+    /// * `options` - Whether to refuse test files, and to follow an import map found in `dir`
+    ///
+    /// # Returns
+    /// A handle to the entrypoint, and to the modules in the project's graph
+    ///
+    /// # Errors
+    /// Can fail if a module cannot be read or parsed, if a module imports a file outside the
+    /// project, or if execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use rustyscript::{Error, ProjectOptions, Runtime};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let project = runtime.load_project("scripts", "main.ts", ProjectOptions::default())?;
+    /// for module in project.modules() {
+    ///     println!("{}", module.path().display());
+    /// }
+    /// let value: String = runtime.call_entrypoint(project.entry(), rustyscript::json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_project(
+        &mut self,
+        dir: impl AsRef<Path>,
+        entry: impl AsRef<Path>,
+        options: ProjectOptions,
+    ) -> Result<ProjectHandle, Error> {
+        self.block_on(move |runtime| async move {
+            let handle = runtime.load_project_async(dir, entry, options).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Loads a project from a directory - an entrypoint, and every local module it imports
+    ///
+    /// Returns a future that resolves to the project's handle  
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// See [`Runtime::load_project`] for details
+    ///
+    /// # Errors
+    /// Can fail if a module cannot be read or parsed, if a module imports a file outside the
+    /// project, or if execution fails
+    pub async fn load_project_async(
+        &mut self,
+        dir: impl AsRef<Path>,
+        entry: impl AsRef<Path>,
+        options: ProjectOptions,
+    ) -> Result<ProjectHandle, Error> {
+        let project = Project::discover(dir.as_ref(), entry.as_ref(), options)?;
+
+        let loader = &self.inner.module_loader;
+        loader.rewrites_extend(project.import_map);
+        for module in &project.modules {
+            let specifier = module.path().to_module_specifier(Path::new("/"))?;
+            loader.whitelist_add(specifier.as_str());
+        }
+
+        let entry = self.load_module_async(project.modules[0].module()).await?;
+        Ok(ProjectHandle::new(entry, project.modules))
     }
 
     /// Executes the entrypoint function of a module within the Deno runtime.
@@ -1047,15 +1758,34 @@ impl Runtime {
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self
-                .inner
-                .call_function_by_ref(Some(module_context), entrypoint, args)?;
-            let result = self.inner.resolve_with_event_loop(result).await?;
-            self.inner.decode_value(result)
-        } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
+        #[cfg(feature = "otel")]
+        let span = self
+            .inner
+            .call_span("call_entrypoint", "<entrypoint>", Some(module_context));
+
+        let result = async {
+            if let Some(entrypoint) = module_context.entrypoint() {
+                let _permit = self
+                    .inner
+                    .admit_call(Some(module_context), ENTRYPOINT_CALL)?;
+                let result =
+                    self.inner
+                        .call_function_by_ref(Some(module_context), entrypoint, args)?;
+                let result = self.inner.resolve_with_event_loop(result).await?;
+                self.inner.decode_value(result)
+            } else {
+                let error = Error::MissingEntrypoint(module_context.module().clone());
+                self.inner.decode_missing(error)
+            }
+        }
+        .await;
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span {
+            span.finish(&result);
         }
+
+        self.labeled(result)
     }
 
     /// Executes the entrypoint function of a module within the Deno runtime.
@@ -1099,77 +1829,1152 @@ impl Runtime {
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self.block_on(|runtime| async move {
-                runtime
-                    .inner
-                    .call_function_by_ref(Some(module_context), entrypoint, args)
-            })?;
-            self.inner.decode_value(result)
+        let result = if let Some(entrypoint) = module_context.entrypoint() {
+            self.inner
+                .admit_call(Some(module_context), ENTRYPOINT_CALL)
+                .and_then(|permit| {
+                    let result = self.block_on(|runtime| async move {
+                        runtime
+                            .inner
+                            .call_function_by_ref(Some(module_context), entrypoint, args)
+                    })?;
+                    self.inner.finish_call(permit, &result);
+                    self.inner.decode_value(result)
+                })
         } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
-        }
+            let error = Error::MissingEntrypoint(module_context.module().clone());
+            self.inner.decode_missing(error)
+        };
+        self.labeled(result)
     }
 
-    /// Loads a module into a new runtime, executes the entry function and returns the
-    /// result of the module's execution, deserialized into the specified Rust type (`T`).
+    /// Returns a durable handle to a value registered by a script with `rustyscript.durable(name, value)`
     ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
-    /// * `runtime_options` - Options for the creation of the runtime
-    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
+    /// The handle can be serialized, and resolved again in any runtime that has
+    /// loaded the registering module - see [`DurableHandle`]
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)  
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// # Errors
+    /// Will return an error if no value has been registered under the given name
+    pub fn durable_handle(&mut self, name: &str) -> Result<DurableHandle, Error> {
+        DurableHandle::lookup(self, name)?;
+        Ok(DurableHandle::new(name))
+    }
+
+    /// Returns the names of the functions scripts have exposed with `rustyscript.expose(name, fn)`, in order
+    ///
+    /// Scripts can expose functions at any time - such as from within a call - so the list
+    /// reflects the moment it is read. See [`Runtime::call_exposed`]
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,  
-    /// Or if the result cannot be deserialized into the requested type
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn exposed_functions(&mut self) -> Result<Vec<String>, Error> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        Ok(state
+            .try_borrow::<crate::ext::rustyscript::ExposedFunctions>()
+            .map(|exposed| exposed.0.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Subscribes to the payloads scripts emit for an event with `rustyscript.emit(event, payload)`
     ///
-    /// # Example
+    /// Payloads are buffered as they are emitted - up to 1024 per subscriber, before the oldest are
+    /// discarded; see [`Runtime::subscribe_with`] to change that. Scripts can check for subscribers
+    /// with `rustyscript.hasSubscribers(event)` before building an expensive payload
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
     ///
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{json_args, Error, Module, Runtime};
+    /// use rustyscript::{json_args, Module, Runtime, Undefined};
     ///
-    /// # fn main() -> Result<(), Error> {
-    /// let module = Module::new("test.js", "export default () => 2");
-    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new(
+    ///     "test.js",
+    ///     "
+    ///     export function work() {
+    ///         for (let done = 25; done <= 100; done += 25) {
+    ///             rustyscript.emit('progress', { done });
+    ///         }
+    ///     }
+    ///     ",
+    /// );
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let progress = runtime.subscribe("progress")?;
+    /// runtime.call_function::<Undefined>(Some(&module), "work", json_args!())?;
+    /// assert_eq!(progress.drain().len(), 4);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn execute_module<T>(
-        module: &Module,
-        side_modules: Vec<&Module>,
-        runtime_options: RuntimeOptions,
-        entrypoint_args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        let mut runtime = Runtime::new(runtime_options)?;
-        let module = runtime.load_modules(module, side_modules)?;
-        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
-        Ok(value)
+    pub fn subscribe(&mut self, event: &str) -> Result<crate::EventReceiver, Error> {
+        self.subscribe_with(event, crate::SubscribeOptions::default())
     }
-}
 
-impl AsyncBridgeExt for Runtime {
-    fn bridge(&self) -> &AsyncBridge {
-        &self.tokio
+    /// Subscribes to the payloads scripts emit for an event, buffered according to `options`
+    ///
+    /// See [`Runtime::subscribe`]
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn subscribe_with(
+        &mut self,
+        event: &str,
+        options: crate::SubscribeOptions,
+    ) -> Result<crate::EventReceiver, Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+        if !state.has::<crate::script_events::EventSubscribers>() {
+            state.put(crate::script_events::EventSubscribers::default());
+        }
+        Ok(state
+            .borrow_mut::<crate::script_events::EventSubscribers>()
+            .subscribe(event, options))
     }
-}
-
-#[cfg(test)]
-mod test_runtime {
-    use crate::json_args;
-    use std::time::Duration;
-
-    use super::*;
-    use deno_core::extension;
+
+    /// Calls a function a script has exposed with `rustyscript.expose(name, fn)`, and deserializes its return value
+    ///
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Errors
+    /// Fails if no function is exposed under the name, if it throws,
+    /// or if the result cannot be deserialized into the requested type
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new(
+    ///     "plugin.js",
+    ///     "
+    ///     rustyscript.expose('onSave', (doc) => `saved ${doc}`);
+    ///     rustyscript.expose('onLoad', async (doc) => `loaded ${doc}`);
+    ///     ",
+    /// );
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.load_module(&module)?;
+    ///
+    /// assert_eq!(runtime.exposed_functions()?, ["onLoad", "onSave"]);
+    /// let value: String = runtime.call_exposed("onLoad", json_args!("a.txt"))?;
+    /// assert_eq!(value, "loaded a.txt");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_exposed<T>(
+        &mut self,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.block_on(|runtime| async move { runtime.call_exposed_async(name, args).await })
+    }
+
+    /// Calls a function a script has exposed with `rustyscript.expose(name, fn)`, and deserializes its return value
+    ///
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// See [`Runtime::call_exposed`] for an example
+    ///
+    /// # Errors
+    /// Fails if no function is exposed under the name, if it throws,
+    /// or if the result cannot be deserialized into the requested type
+    pub async fn call_exposed_async<T>(
+        &mut self,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let function = {
+            let state = self.deno_runtime().op_state();
+            let state = state.try_borrow()?;
+            crate::ext::rustyscript::ExposedFunctions::get(&state, name)
+        };
+
+        let result = async {
+            let function = function?;
+            let result = self.inner.call_function_by_ref(None, &function, args)?;
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// **Experimental** - runs an exported workflow function until it returns, or suspends at a `rustyscript.step` call
+    ///
+    /// Suspended workflows are resumed with [`Runtime::resume_workflow`], on this or any other runtime
+    /// that has loaded the same module. See [`crate::workflow`] for how suspension works
+    ///
+    /// # Errors
+    /// Will return an error if the function cannot be found, or if it throws
+    pub async fn start_workflow_async<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<crate::workflow::WorkflowStatus<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        use deno_core::serde_json::{to_value, Value};
+
+        let args = match to_value(args).map_err(|e| Error::Runtime(e.to_string()))? {
+            Value::Array(args) => args,
+            Value::Null => vec![],
+            args => vec![args],
+        };
+        let state = crate::workflow::WorkflowState::new(module_context, name, args);
+        self.resume_workflow_async(module_context, state).await
+    }
+
+    /// **Experimental** - runs an exported workflow function until it returns, or suspends at a `rustyscript.step` call
+    ///
+    /// Blocking variant of [`Runtime::start_workflow_async`]
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, workflow::WorkflowStatus, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = runtime.load_module(&Module::new(
+    ///     "workflow.js",
+    ///     "export const greet = async (name) => `${await rustyscript.step('title', name)} ${name}`;",
+    /// ))?;
+    ///
+    /// let WorkflowStatus::Suspended(mut state) = runtime.start_workflow::<String>(&module, "greet", json_args!("Bob"))? else {
+    ///     unreachable!();
+    /// };
+    /// state.complete(state.pending()[0].sequence, &"Dr.")?;
+    ///
+    /// let status = runtime.resume_workflow::<String>(&module, state)?;
+    /// assert!(matches!(status, WorkflowStatus::Complete(s) if s == "Dr. Bob"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Will return an error if the function cannot be found, or if it throws
+    pub fn start_workflow<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<crate::workflow::WorkflowStatus<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.block_on(|runtime| async move {
+            runtime
+                .start_workflow_async(module_context, name, args)
+                .await
+        })
+    }
+
+    /// **Experimental** - resumes a suspended workflow, once the host has completed some of its pending steps
+    ///
+    /// The workflow function is replayed from the start, with completed steps returning their recorded results
+    ///
+    /// # Errors
+    /// Will return an error if the state belongs to a different module, if the workflow
+    /// is no longer deterministic, or if it throws
+    pub async fn resume_workflow_async<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        mut state: crate::workflow::WorkflowState,
+    ) -> Result<crate::workflow::WorkflowStatus<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        use crate::workflow::{Settled, WorkflowState, WorkflowStatus};
+
+        let result = async {
+            state.check_module(module_context)?;
+            state.rewind();
+
+            let function = self
+                .inner
+                .get_function_by_name(Some(module_context), state.function())?;
+            let args = state.args().to_vec();
+            self.deno_runtime().op_state().try_borrow_mut()?.put(state);
+
+            let result = self
+                .inner
+                .call_function_by_ref(Some(module_context), &function, &args);
+            let result = match result {
+                Ok(value) => self
+                    .inner
+                    .await_event_loop(PollEventLoopOptions::default(), None)
+                    .await
+                    .map(|()| value),
+                Err(e) => Err(e),
+            };
+
+            let state = self
+                .deno_runtime()
+                .op_state()
+                .try_borrow_mut()?
+                .try_take::<WorkflowState>()
+                .ok_or_else(|| Error::Runtime("Workflow state was lost".to_string()))?;
+
+            match Settled::of(self.deno_runtime(), result?) {
+                Settled::Fulfilled(value) => {
+                    Ok(WorkflowStatus::Complete(self.inner.decode_value(value)?))
+                }
+                Settled::Rejected(e) => Err(e),
+                Settled::Pending if !state.pending().is_empty() => {
+                    Ok(WorkflowStatus::Suspended(state))
+                }
+                Settled::Pending => Err(Error::Runtime(
+                    "Workflow stalled without reaching a step".to_string(),
+                )),
+            }
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// **Experimental** - resumes a suspended workflow, once the host has completed some of its pending steps
+    ///
+    /// Blocking variant of [`Runtime::resume_workflow_async`]
+    ///
+    /// # Errors
+    /// Will return an error if the state belongs to a different module, if the workflow
+    /// is no longer deterministic, or if it throws
+    pub fn resume_workflow<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        state: crate::workflow::WorkflowState,
+    ) -> Result<crate::workflow::WorkflowStatus<T>, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.block_on(|runtime| async move {
+            runtime.resume_workflow_async(module_context, state).await
+        })
+    }
+
+    /// Deliver a virtual signal, such as `SIGTERM`, to the listeners registered with `Deno.addSignalListener`
+    ///
+    /// Listeners run synchronously; any async work they start proceeds when the event loop is next run  
+    /// Real process signals are never delivered to scripts - this is the only way to raise them
+    ///
+    /// Returns true if at least one listener received the signal
+    ///
+    /// Requires the `signals` feature. Not available with the `node_experimental` feature, which
+    /// binds real signals instead
+    ///
+    /// # Errors
+    /// Will return an error if the signal name is unknown, or if a listener throws
+    #[cfg(all(feature = "signals", not(feature = "node_experimental")))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "signals", not(feature = "node_experimental"))))
+    )]
+    pub fn raise_signal(&mut self, signal: &str) -> Result<bool, Error> {
+        let dispatcher = {
+            let state = self.deno_runtime().op_state();
+            let state = state.try_borrow()?;
+            crate::ext::signals::SignalDispatcher::get(&state)
+        };
+
+        let result = dispatcher.and_then(|dispatcher| {
+            let result = self
+                .inner
+                .call_function_by_ref(None, &dispatcher, &[signal])?;
+            self.inner.decode_value(result)
+        });
+        self.labeled(result)
+    }
+
+    /// Returns the versions, features and extensions this runtime was built with
+    ///
+    /// See [`crate::build_info`] for the same without a runtime. Scripts can read the versions,
+    /// but not the features or extensions, with `rustyscript.buildInfo()`
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn build_info(&mut self) -> Result<crate::BuildInfo, Error> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        state
+            .try_borrow::<crate::BuildInfo>()
+            .cloned()
+            .ok_or_else(|| Error::Runtime("Build info not initialized".to_string()))
+    }
+
+    /// Returns the timings of every function wrapped with `rustyscript.wrap` called so far, by name
+    ///
+    /// See [`crate::EntrypointStats`]
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn entrypoint_stats(&mut self) -> Result<HashMap<String, crate::EntrypointStats>, Error> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        Ok(state
+            .try_borrow::<crate::entrypoint_wrap::EntrypointTimings>()
+            .map(|timings| timings.0.clone())
+            .unwrap_or_default())
+    }
+
+    /// Reports the timers, intervals and global listeners registered since the last call to this
+    /// or [`Runtime::clear_leaks`] that are still alive
+    ///
+    /// Each call starts a new invocation - call it between host calls, so that request-scoped
+    /// work on a shared runtime can be checked for background work it left running  
+    /// Timeouts stop counting once they fire, and `once` listeners once they run
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn report_leaks(&mut self) -> Result<crate::LeakReport, Error> {
+        self.check_leaks(false)
+    }
+
+    /// Like [`Runtime::report_leaks`], but also clears every timer and removes every listener it reports
+    ///
+    /// Work registered by earlier invocations is left alone
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    pub fn clear_leaks(&mut self) -> Result<crate::LeakReport, Error> {
+        self.check_leaks(true)
+    }
+
+    fn check_leaks(&mut self, clear: bool) -> Result<crate::LeakReport, Error> {
+        let checker = {
+            let state = self.deno_runtime().op_state();
+            let state = state.try_borrow()?;
+            crate::ext::rustyscript::LeakChecker::get(&state)
+        };
+
+        let result = checker.and_then(|checker| {
+            let result = self.inner.call_function_by_ref(None, &checker, &[clear])?;
+            self.inner.decode_value(result)
+        });
+        self.labeled(result)
+    }
+
+    /// Runs a byte-buffer transform registered by a script with `rustyscript.pipeline.register`
+    ///
+    /// The input is copied into a buffer from the script's pool, and the transform's result is
+    /// copied back out - see [`Runtime::run_transform_into`] to also reuse the output buffer
+    ///
+    /// Blocks until the transform completes, running the event loop if it is asynchronous
+    ///
+    /// Requires the `pipeline` feature
+    ///
+    /// # Errors
+    /// Fails if no such transform is registered, if it throws, or if it does not return a buffer
+    ///
+    /// ```rust
+    /// use rustyscript::{Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.load_module(&Module::new(
+    ///     "grayscale.js",
+    ///     "rustyscript.pipeline.register('grayscale', (rgb, { alloc }) => {
+    ///         const gray = alloc(rgb.length / 3);
+    ///         for (let i = 0; i < gray.length; i++) {
+    ///             gray[i] = (rgb[i * 3] + rgb[i * 3 + 1] + rgb[i * 3 + 2]) / 3;
+    ///         }
+    ///         return gray;
+    ///     });",
+    /// ))?;
+    ///
+    /// let gray = runtime.run_transform("grayscale", &[30, 60, 90, 255, 255, 255])?;
+    /// assert_eq!(gray, vec![60, 255]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "pipeline")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pipeline")))]
+    pub fn run_transform(&mut self, name: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut output = Vec::new();
+        self.run_transform_into(name, input, &mut output)?;
+        Ok(output)
+    }
+
+    /// Runs a byte-buffer transform, swapping its result into `output`
+    ///
+    /// The previous contents of `output` are discarded, and its allocation is kept by the runtime
+    /// to stage the next result - so a caller passing the same buffer to every invocation causes
+    /// no allocation on the host side once buffers have grown to size
+    ///
+    /// Blocks until the transform completes, running the event loop if it is asynchronous
+    ///
+    /// # Errors
+    /// Fails if no such transform is registered, if it throws, or if it does not return a buffer
+    #[cfg(feature = "pipeline")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pipeline")))]
+    pub fn run_transform_into(
+        &mut self,
+        name: &str,
+        input: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        self.block_on(|runtime| async move {
+            runtime.run_transform_into_async(name, input, output).await
+        })
+    }
+
+    /// Runs a byte-buffer transform, swapping its result into `output`
+    ///
+    /// Returns a future that resolves when the transform completes.
+    /// See [`Runtime::run_transform_into`]
+    ///
+    /// # Errors
+    /// Fails if no such transform is registered, if it throws, or if it does not return a buffer
+    #[cfg(feature = "pipeline")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pipeline")))]
+    pub async fn run_transform_into_async(
+        &mut self,
+        name: &str,
+        input: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        use crate::ext::pipeline::{PipelineIo, PipelineRunner};
+
+        let result = async {
+            let runner = {
+                let state = self.deno_runtime().op_state();
+                let mut state = state.try_borrow_mut()?;
+                let runner = PipelineRunner::get(&state)?;
+
+                if !state.has::<PipelineIo>() {
+                    state.put(PipelineIo::default());
+                }
+                let io = state.borrow_mut::<PipelineIo>();
+                io.input.clear();
+                io.input.extend_from_slice(input);
+                io.written = false;
+                runner
+            };
+
+            let result = self
+                .inner
+                .call_function_by_ref(None, &runner, &(name, input.len()))?;
+            self.inner.resolve_with_event_loop(result).await?;
+
+            let state = self.deno_runtime().op_state();
+            let mut state = state.try_borrow_mut()?;
+            let io = state.borrow_mut::<PipelineIo>();
+            if !io.written {
+                return Err(Error::Runtime(format!(
+                    "Transform '{name}' did not produce a result"
+                )));
+            }
+            std::mem::swap(&mut io.output, output);
+            Ok(())
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Closes the idle connections kept open by `fetch`, so a pooled runtime starts its next
+    /// invocation without sockets left over from the last one
+    ///
+    /// Requests still in flight are unaffected. Clients created by the script itself, with
+    /// `Deno.createHttpClient`, keep their own pools - see [`crate::FetchPoolOptions`]
+    ///
+    /// # Errors
+    /// Will return an error if a new client cannot be created for later fetches
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn close_idle_connections(&mut self) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+        crate::ext::web::fetch_client::close_idle_connections(&mut state)
+    }
+
+    /// Returns the `WebSocket` connections scripts currently have open, oldest first
+    ///
+    /// Sockets are listed from the moment they are created, while still connecting, until they have closed.  
+    /// Connections made with `WebSocketStream` are listed too
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is already borrowed
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub fn websockets(&mut self) -> Result<Vec<crate::WebSocketInfo>, Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+        Ok(crate::ext::websocket::WebSocketRegistry::open(&mut state))
+    }
+
+    /// Closes one of the `WebSocket` connections listed by [`Runtime::websockets`], as if the
+    /// script had called `close(code, reason)` - the script's `close` listeners are notified
+    ///
+    /// `code` defaults to 1000, and must otherwise be in the range 3000-4999.  
+    /// The closing handshake completes while the event loop runs - a connection that is still
+    /// being made is aborted instead
+    ///
+    /// Returns false if the connection has already closed
+    ///
+    /// # Errors
+    /// Will return an error if the code is not allowed, or the reason is longer than 123 bytes
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub fn close_websocket(
+        &mut self,
+        id: u32,
+        code: Option<u16>,
+        reason: &str,
+    ) -> Result<bool, Error> {
+        let state = self.deno_runtime().op_state();
+        let reason = reason.to_string();
+        self.block_on(|_| crate::ext::websocket::close(state, id, code, reason))
+    }
+
+    /// Converts a `Response` object produced by a script into an `http::Response`
+    ///
+    /// Status and headers are copied immediately; the body is streamed from the sandbox as the
+    /// script produces it, preserving streaming responses such as server-sent events.  
+    /// Chunks arrive while the event loop runs - see [`crate::HttpBody`]
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `Response`, if its body has already been used,
+    /// or if the value belongs to a different runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn into_http_response(
+        &mut self,
+        response: &crate::js_value::Value,
+    ) -> Result<http::Response<crate::HttpBody>, Error> {
+        use crate::ext::web::http_interop::{prepare_response, ResponseParts};
+
+        let id = self.id();
+        let result = (|| {
+            if let Some(owner) = response.runtime_id().filter(|owner| *owner != id) {
+                return Err(Error::WrongRuntime(owner, id));
+            }
+
+            let (helper, rid, body) = {
+                let state = self.deno_runtime().op_state();
+                let mut state = state.try_borrow_mut()?;
+                prepare_response(&mut state)?
+            };
+
+            let parts = self
+                .inner
+                .call_function_by_ref(None, &helper, &(response, rid))?;
+            let parts: ResponseParts = self.inner.decode_value(parts)?;
+            parts.into_response(body)
+        })();
+        self.labeled(result)
+    }
+
+    /// Builds a sandbox `Request` object from a host `http::Request`, such as one received by axum or hyper
+    ///
+    /// The body is streamed into the sandbox as the script reads it. Relative URIs, as seen by servers,
+    /// are resolved against the request's `Host` header, or `localhost` if it has none.  
+    /// The returned value can be passed directly to a script's handler as a function argument
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = runtime.load_module(&Module::new(
+    ///     "handler.js",
+    ///     "export const handle = (request) => new URL(request.url).pathname;",
+    /// ))?;
+    ///
+    /// let body = rustyscript::deno_core::futures::stream::empty::<Result<Vec<u8>, rustyscript::Error>>();
+    /// let request = http::Request::get("/users/1").body(body).unwrap();
+    /// let request = runtime.create_request(request)?;
+    ///
+    /// let path: String = runtime.call_function(Some(&module), "handle", json_args!(request))?;
+    /// assert_eq!(path, "/users/1");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Will return an error if a header value is not valid UTF-8, or if the request cannot be constructed
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn create_request<B, D, E>(
+        &mut self,
+        request: http::Request<B>,
+    ) -> Result<crate::js_value::Value, Error>
+    where
+        B: deno_core::futures::Stream<Item = Result<D, E>> + 'static,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        use crate::ext::web::http_interop::{prepare_request, RequestParts};
+
+        let result = (|| {
+            let (parts, body) = RequestParts::new(request)?;
+            let (helper, rid) = {
+                let state = self.deno_runtime().op_state();
+                let mut state = state.try_borrow_mut()?;
+                prepare_request(&mut state, &parts, body)?
+            };
+
+            let value = self
+                .inner
+                .call_function_by_ref(None, &helper, &(parts, rid))?;
+            self.inner.decode_value(value)
+        })();
+        self.labeled(result)
+    }
+
+    /// Dispatches a `FetchEvent` to the scripts' `addEventListener('fetch', ...)` listeners,
+    /// service-worker style, returning the response passed to `event.respondWith`
+    ///
+    /// Work registered with `event.waitUntil` continues after the response is returned -
+    /// keep driving the event loop (for example with [`Runtime::await_event_loop`]) to let it finish
+    ///
+    /// # Errors
+    /// Will return an error if no listener called `respondWith`, or if the response is not valid.  
+    /// If a listener threw before anything responded, that error is returned
+    #[cfg(all(feature = "web", feature = "events"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "web", feature = "events"))))]
+    pub async fn dispatch_fetch_event_async<B, D, E>(
+        &mut self,
+        request: http::Request<B>,
+    ) -> Result<http::Response<crate::HttpBody>, Error>
+    where
+        B: deno_core::futures::Stream<Item = Result<D, E>> + 'static,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        use crate::ext::events::EventDispatchers;
+
+        let result = async {
+            let request = self.create_request(request)?;
+            let dispatchers =
+                EventDispatchers::get(&*self.deno_runtime().op_state().try_borrow()?)?;
+
+            let response = self
+                .inner
+                .call_function_by_ref(None, &dispatchers.fetch, &[request])?;
+            let response = self.inner.resolve_with_event_loop(response).await?;
+            let response: crate::js_value::Value = self.inner.decode_value(response)?;
+            self.into_http_response(&response)
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Dispatches a `FetchEvent` to the scripts' `addEventListener('fetch', ...)` listeners,
+    /// service-worker style, returning the response passed to `event.respondWith`
+    ///
+    /// Blocks until the response is available - its body is streamed while the event loop is driven.  
+    /// See [`Runtime::dispatch_fetch_event_async`]
+    ///
+    /// # Errors
+    /// Will return an error if no listener called `respondWith`, or if the response is not valid
+    #[cfg(all(feature = "web", feature = "events"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "web", feature = "events"))))]
+    pub fn dispatch_fetch_event<B, D, E>(
+        &mut self,
+        request: http::Request<B>,
+    ) -> Result<http::Response<crate::HttpBody>, Error>
+    where
+        B: deno_core::futures::Stream<Item = Result<D, E>> + 'static,
+        D: Into<Vec<u8>>,
+        E: std::fmt::Display,
+    {
+        self.block_on(|runtime| async move { runtime.dispatch_fetch_event_async(request).await })
+    }
+
+    /// Creates a sandbox `Blob` from host bytes - or a `File`, if the given file has a name
+    ///
+    /// The returned value can be passed directly to a script as a function argument,
+    /// or appended to a `FormData`
+    ///
+    /// # Errors
+    /// Will return an error if the file conversions are not initialized
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn create_file(&mut self, file: crate::HostFile) -> Result<crate::js_value::Value, Error> {
+        use crate::ext::web::files::FileHelpers;
+
+        let result = (|| {
+            let helpers = FileHelpers::get(&*self.deno_runtime().op_state().try_borrow()?)?;
+            let value =
+                self.inner
+                    .call_function_by_ref(None, &helpers.create_file, &file.into_args())?;
+            self.inner.decode_value(value)
+        })();
+        self.labeled(result)
+    }
+
+    /// Reads the contents of a sandbox `Blob` or `File`
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `Blob`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub async fn read_file_async(
+        &mut self,
+        file: &crate::js_value::Value,
+    ) -> Result<crate::HostFile, Error> {
+        use crate::ext::web::files::WireFile;
+
+        let result = async {
+            let helpers = self.file_helpers(file)?;
+            let file: WireFile = self.call_file_helper(&helpers.read_file, file).await?;
+            Ok(file.into())
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Reads the contents of a sandbox `Blob` or `File`
+    ///
+    /// Blocks until the contents have been read
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `Blob`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn read_file(&mut self, file: &crate::js_value::Value) -> Result<crate::HostFile, Error> {
+        self.block_on(|runtime| async move { runtime.read_file_async(file).await })
+    }
+
+    /// Reads the entries of a sandbox `FormData`, such as one produced by `request.formData()`
+    ///
+    /// File entries are read in full, as raw bytes
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `FormData`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub async fn form_data_entries_async(
+        &mut self,
+        form: &crate::js_value::Value,
+    ) -> Result<Vec<crate::FormDataEntry>, Error> {
+        use crate::ext::web::files::WireEntry;
+
+        let result = async {
+            let helpers = self.file_helpers(form)?;
+            let entries: Vec<WireEntry> = self
+                .call_file_helper(&helpers.form_data_entries, form)
+                .await?;
+            Ok(entries.into_iter().map(Into::into).collect())
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Reads the entries of a sandbox `FormData`, such as one produced by `request.formData()`
+    ///
+    /// Blocks until all entries have been read
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `FormData`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn form_data_entries(
+        &mut self,
+        form: &crate::js_value::Value,
+    ) -> Result<Vec<crate::FormDataEntry>, Error> {
+        self.block_on(|runtime| async move { runtime.form_data_entries_async(form).await })
+    }
+
+    /// Reads the trailers sent after the body of a sandbox `Response`, such as gRPC's `grpc-status`
+    ///
+    /// Trailers are only known once the body has been read or dropped, so this waits for that.  
+    /// Returns no trailers if the response had none, or did not come from the network
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `Response`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub async fn response_trailers_async(
+        &mut self,
+        response: &crate::js_value::Value,
+    ) -> Result<Vec<(String, String)>, Error> {
+        use crate::ext::web::trailers::TrailerHelper;
+
+        let result = async {
+            self.check_owner(response)?;
+            let helper = TrailerHelper::get(&*self.deno_runtime().op_state().try_borrow()?)?;
+            self.call_file_helper(&helper.0, response).await
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Reads the trailers sent after the body of a sandbox `Response`, such as gRPC's `grpc-status`
+    ///
+    /// Blocks until the body has been read or dropped - see [`Runtime::response_trailers_async`]
+    ///
+    /// # Errors
+    /// Will return an error if the value is not a `Response`, or belongs to another runtime
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    pub fn response_trailers(
+        &mut self,
+        response: &crate::js_value::Value,
+    ) -> Result<Vec<(String, String)>, Error> {
+        self.block_on(|runtime| async move { runtime.response_trailers_async(response).await })
+    }
+
+    #[cfg(feature = "web")]
+    fn check_owner(&self, value: &crate::js_value::Value) -> Result<(), Error> {
+        let id = self.id();
+        match value.runtime_id().filter(|owner| *owner != id) {
+            Some(owner) => Err(Error::WrongRuntime(owner, id)),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "web")]
+    fn file_helpers(
+        &mut self,
+        value: &crate::js_value::Value,
+    ) -> Result<crate::ext::web::files::FileHelpers, Error> {
+        self.check_owner(value)?;
+
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        crate::ext::web::files::FileHelpers::get(&state)
+    }
+
+    #[cfg(feature = "web")]
+    async fn call_file_helper<T>(
+        &mut self,
+        helper: &deno_core::v8::Global<deno_core::v8::Function>,
+        value: &crate::js_value::Value,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let result = self.inner.call_function_by_ref(None, helper, &[value])?;
+        let result = self.inner.resolve_with_event_loop(result).await?;
+        self.inner.decode_value(result)
+    }
+
+    /// Returns the labels identifying this runtime
+    ///
+    /// See [`RuntimeOptions::labels`]
+    #[must_use]
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.inner.labels
+    }
+
+    /// Returns the path of the runtime's scratch directory, if one was configured
+    ///
+    /// See [`RuntimeOptions::temp_dir`]
+    #[cfg(feature = "fs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+    #[must_use]
+    pub fn temp_dir(&self) -> Option<&Path> {
+        self.inner
+            .temp_dir
+            .as_deref()
+            .map(crate::ext::fs::TempSandbox::path)
+    }
+
+    /// Returns the code passed to `Deno.exit`, if the script has called it
+    ///
+    /// `Deno.exit` stops the runtime rather than the host process; calls interrupted by it
+    /// return [`Error::Exit`]. The runtime should be dropped once this is set
+    #[must_use]
+    pub fn exit_code(&self) -> Option<i32> {
+        self.inner.exit_code.get()
+    }
+
+    /// Dispatches an event to the scripts' global listeners, such as `addEventListener('queue', ...)`
+    ///
+    /// Listeners receive an `ExtendableEvent` with the payload as `event.data`.  
+    /// Resolves once every listener has completed - including promises returned by async listeners,
+    /// and those passed to `event.waitUntil`. Errors from listeners are collected rather than returned
+    ///
+    /// Requires the `events` feature
+    ///
+    /// # Errors
+    /// Will return an error if the payload cannot be serialized, or the event cannot be dispatched
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    pub async fn dispatch_event_async(
+        &mut self,
+        event: &str,
+        payload: &impl serde::ser::Serialize,
+    ) -> Result<crate::EventDispatchOutcome, Error> {
+        use crate::ext::events::{EventDispatchers, WireOutcome};
+
+        let result = async {
+            let dispatchers =
+                EventDispatchers::get(&*self.deno_runtime().op_state().try_borrow()?)?;
+            let outcome =
+                self.inner
+                    .call_function_by_ref(None, &dispatchers.event, &(event, payload))?;
+            let outcome = self.inner.resolve_with_event_loop(outcome).await?;
+            let outcome: WireOutcome = self.inner.decode_value(outcome)?;
+            Ok(outcome.decode(self.deno_runtime()))
+        }
+        .await;
+        self.labeled(result)
+    }
+
+    /// Dispatches an event to the scripts' global listeners, such as `addEventListener('queue', ...)`
+    ///
+    /// Blocks until every listener has completed. See [`Runtime::dispatch_event_async`]
+    ///
+    /// Requires the `events` feature
+    ///
+    /// ```rust
+    /// use rustyscript::{Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.load_module(&Module::new(
+    ///     "consumer.js",
+    ///     "addEventListener('queue', async (event) => { globalThis.lastId = event.data.id; });",
+    /// ))?;
+    ///
+    /// let outcome = runtime.dispatch_event("queue", &rustyscript::serde_json::json!({ "id": 1 }))?;
+    /// assert!(outcome.is_handled() && outcome.is_ok());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Will return an error if the payload cannot be serialized, or the event cannot be dispatched
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    pub fn dispatch_event(
+        &mut self,
+        event: &str,
+        payload: &impl serde::ser::Serialize,
+    ) -> Result<crate::EventDispatchOutcome, Error> {
+        self.block_on(|runtime| async move { runtime.dispatch_event_async(event, payload).await })
+    }
+
+    /// Returns the global event types, such as `fetch` or `message`, that scripts have registered listeners for
+    ///
+    /// Registrations are recorded as they happen, and are not forgotten if the listener is later removed
+    ///
+    /// Requires the `events` feature
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    pub fn event_listeners(&mut self) -> Result<Vec<String>, Error> {
+        self.registered_events(None)
+    }
+
+    /// Returns the global event types that the given module registered listeners for
+    ///
+    /// Useful for service-worker style hosts, routing work only to the scripts that declared it.  
+    /// Listeners registered on behalf of a module by one of its imports are attributed to the import
+    ///
+    /// Requires the `events` feature
+    ///
+    /// # Errors
+    /// Will return an error if the runtime's state is currently borrowed
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    pub fn module_event_listeners(&mut self, module: &ModuleHandle) -> Result<Vec<String>, Error> {
+        use crate::traits::ToModuleSpecifier;
+
+        let specifier = module
+            .module()
+            .filename()
+            .to_module_specifier(self.inner.current_dir())?;
+        self.registered_events(Some(specifier.as_str()))
+    }
+
+    #[cfg(feature = "events")]
+    fn registered_events(&mut self, module: Option<&str>) -> Result<Vec<String>, Error> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        Ok(state
+            .try_borrow::<crate::ext::events::EventListenerRegistry>()
+            .map(|registry| registry.events(module))
+            .unwrap_or_default())
+    }
+
+    /// Attach the runtime's labels to an error result
+    ///
+    /// The call interrupted by the script calling `Deno.exit` fails with [`Error::Exit`] instead -
+    /// errors after that keep their own variant
+    fn labeled<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        result.map_err(|e| match self.exit_code() {
+            Some(code) if !self.inner.exit_reported.replace(true) => Error::Exit(code),
+            _ => e.with_labels(&self.inner.labels),
+        })
+    }
+
+    /// Loads a module into a new runtime, executes the entry function and returns the
+    /// result of the module's execution, deserialized into the specified Rust type (`T`).
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `runtime_options` - Options for the creation of the runtime
+    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)  
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,  
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{json_args, Error, Module, Runtime};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("test.js", "export default () => 2");
+    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute_module<T>(
+        module: &Module,
+        side_modules: Vec<&Module>,
+        runtime_options: RuntimeOptions,
+        entrypoint_args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let mut runtime = Runtime::new(runtime_options)?;
+        let module = runtime.load_modules(module, side_modules)?;
+        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
+        Ok(value)
+    }
+}
+
+impl AsyncBridgeExt for Runtime {
+    fn bridge(&self) -> &AsyncBridge {
+        &self.tokio
+    }
+
+    fn error_labels(&self) -> Option<Rc<HashMap<String, String>>> {
+        Some(self.inner.labels.clone())
+    }
+}
+
+#[cfg(test)]
+mod test_runtime {
+    use crate::json_args;
+    use std::time::Duration;
+
+    use super::*;
+    use deno_core::extension;
 
     #[test]
     fn test_new() {
@@ -1183,6 +2988,78 @@ mod test_runtime {
         .expect("Could not create runtime with extensions");
     }
 
+    #[test]
+    fn test_missing_values() {
+        let module = Module::new(
+            "test.js",
+            "export const present = 1; export function f() {}",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        runtime
+            .get_value::<Option<usize>>(Some(&handle), "absent")
+            .unwrap_err();
+        runtime
+            .call_entrypoint::<Option<usize>>(&handle, json_args!())
+            .unwrap_err();
+
+        // Functions that return nothing decode as `None` either way
+        let value: Option<usize> = runtime
+            .call_function(Some(&handle), "f", json_args!())
+            .unwrap();
+        assert_eq!(value, None);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            missing_values: crate::MissingValues::Undefined,
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        let value: Option<usize> = runtime.get_value(Some(&handle), "absent").unwrap();
+        assert_eq!(value, None);
+        let value: Option<usize> = runtime
+            .get_value_immediate(Some(&handle), "present")
+            .unwrap();
+        assert_eq!(value, Some(1));
+        let value: Option<String> = runtime.call_entrypoint(&handle, json_args!()).unwrap();
+        assert_eq!(value, None);
+
+        // Targets that cannot hold `undefined` still fail
+        let error = runtime
+            .get_value::<String>(Some(&handle), "absent")
+            .unwrap_err();
+        assert!(matches!(error, Error::ValueNotFound(_)), "{error}");
+    }
+
+    #[test]
+    fn test_event_loop_steps() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime.assert_idle().unwrap();
+
+        runtime
+            .eval::<Undefined>(
+                "globalThis.log = [];
+                setTimeout(() => log.push('timer'), 0);
+                Promise.resolve().then(() => log.push('micro')).then(() => log.push('chained'));
+                undefined",
+            )
+            .unwrap();
+
+        // Promise reactions run without the timer firing
+        runtime.drain_microtasks();
+        let log: Vec<String> = runtime.eval("log").unwrap();
+        assert_eq!(log, vec!["micro", "chained"]);
+
+        let error = runtime.assert_idle().unwrap_err();
+        assert!(error.to_string().contains("timer"), "{error}");
+
+        while runtime.tick().unwrap() {}
+        let log: Vec<String> = runtime.eval("log").unwrap();
+        assert_eq!(log, vec!["micro", "chained", "timer"]);
+        runtime.assert_idle().unwrap();
+    }
+
     #[test]
     fn test_get_value() {
         let module = Module::new(
@@ -1451,6 +3328,165 @@ mod test_runtime {
             .expect("Did not allow undefined return");
     }
 
+    #[test]
+    fn test_request_gc() {
+        fn used_heap(runtime: &mut Runtime) -> usize {
+            let isolate = runtime.deno_runtime().v8_isolate();
+            isolate.get_heap_statistics().used_heap_size()
+        }
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let allocate = "globalThis.junk = Array.from({ length: 100000 }, (_, i) => ({ i }));";
+
+        // Each kind of collection frees the garbage left behind
+        for kind in [GcKind::Full, GcKind::Compact] {
+            runtime
+                .eval::<Undefined>(allocate)
+                .expect("Could not evaluate");
+            runtime.request_gc(kind);
+            let before = used_heap(&mut runtime);
+
+            runtime
+                .eval::<Undefined>("delete globalThis.junk;")
+                .expect("Could not evaluate");
+            runtime.request_gc(kind);
+            let after = used_heap(&mut runtime);
+            assert!(
+                after < before,
+                "{kind:?} collection did not free the heap: {before} -> {after}"
+            );
+        }
+
+        runtime.notify_memory_pressure(MemoryPressure::Moderate);
+        runtime.notify_memory_pressure(MemoryPressure::None);
+
+        let value: usize = runtime.eval("1 + 1").expect("Could not evaluate");
+        assert_eq!(2, value);
+    }
+
+    #[cfg(feature = "intl")]
+    #[test]
+    fn test_default_locale() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            default_locale: Some("de-DE".to_string()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let value: String = runtime
+            .eval("(1234.5).toLocaleString()")
+            .expect("Could not evaluate");
+        assert_eq!("1.234,5", value);
+
+        let value: String = runtime
+            .eval("new Intl.NumberFormat().resolvedOptions().locale")
+            .expect("Could not evaluate");
+        assert_eq!("de-DE", value);
+
+        // Explicit locales still win
+        let value: String = runtime
+            .eval("(1234.5).toLocaleString('en-US')")
+            .expect("Could not evaluate");
+        assert_eq!("1,234.5", value);
+
+        let value: bool = runtime
+            .eval("Intl.DateTimeFormat() instanceof Intl.DateTimeFormat")
+            .expect("Could not evaluate");
+        assert!(value);
+    }
+
+    #[cfg(feature = "intl")]
+    #[test]
+    fn test_timezone() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timezone: Some(crate::Tz::new("America/Toronto")),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let value: Vec<i64> = runtime
+            .eval("const d = new Date(Date.UTC(2024, 0, 1, 12)); [d.getHours(), d.getTimezoneOffset()]")
+            .expect("Could not evaluate");
+        assert_eq!(vec![7, 300], value);
+
+        let value: String = runtime
+            .eval("new Date(2024, 6, 1, 8, 30).toISOString()")
+            .expect("Could not evaluate");
+        assert_eq!("2024-07-01T12:30:00.000Z", value);
+
+        let value: String = runtime
+            .eval("new Intl.DateTimeFormat().resolvedOptions().timeZone")
+            .expect("Could not evaluate");
+        assert_eq!("America/Toronto", value);
+
+        Runtime::new(RuntimeOptions {
+            timezone: Some(crate::Tz::new("Not/AZone")),
+            ..Default::default()
+        })
+        .expect_err("Accepted an unknown timezone");
+    }
+
+    #[test]
+    fn test_freeze_intrinsics() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            freeze_intrinsics: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let polluted: bool = runtime
+            .eval(
+                "
+                Object.prototype.polluted = true;
+                Array.prototype.map = () => 'hijacked';
+                ({}).polluted === true || [1].map(x => x)[0] !== 1
+            ",
+            )
+            .expect("Could not evaluate");
+        assert!(!polluted);
+
+        // Own properties can still be defined on user objects
+        let value: usize = runtime
+            .eval("const o = {}; Object.defineProperty(o, 'toString', { value: () => 'x' }); 2")
+            .expect("Could not evaluate");
+        assert_eq!(2, value);
+    }
+
+    #[test]
+    fn test_background_error_handler() {
+        let errors = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let errors_ref = errors.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            on_background_error: Some(Box::new(move |e| {
+                errors_ref.borrow_mut().push(e.to_string());
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "test.js",
+            "
+            export function f() {
+                setTimeout(() => { throw new Error('background failure'); }, 10);
+                return 1;
+            }
+        ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let value: usize = runtime
+            .call_function_immediate(Some(&module), "f", json_args!())
+            .expect("Could not call function");
+        assert_eq!(1, value);
+
+        runtime
+            .block_on_event_loop(PollEventLoopOptions::default(), None)
+            .expect_err("Background error was not surfaced");
+        assert_eq!(1, errors.borrow().len());
+        assert!(errors.borrow()[0].contains("background failure"));
+    }
+
     #[test]
     fn test_heap_exhaustion_handled() {
         let mut runtime = Runtime::new(RuntimeOptions {
@@ -1466,4 +3502,517 @@ mod test_runtime {
             .load_modules(&module, vec![])
             .expect_err("Did not detect heap exhaustion");
     }
+
+    #[test]
+    fn test_host_api_declarations() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime.register_declarations("kv", "declare function get(key: string): string;");
+        runtime.register_declarations(
+            "math",
+            "declare function add(a: number, b: number): number;\n",
+        );
+        runtime.register_declarations("kv", "declare function get(key: string): string | null;");
+
+        assert_eq!(
+            runtime.host_api_declarations(),
+            "// Host API declarations\n\
+             \n// kv\ndeclare function get(key: string): string | null;\n\
+             \n// math\ndeclare function add(a: number, b: number): number;\n"
+        );
+    }
+
+    #[test]
+    fn test_virtual_modules() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            virtual_modules: vec![(
+                "host:api".to_string(),
+                "export default { double: (n) => rustyscript.functions.double(n) };".to_string(),
+            )],
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .register_function("double", |args| {
+                let n = args
+                    .first()
+                    .and_then(crate::serde_json::Value::as_i64)
+                    .unwrap_or(0);
+                Ok(crate::serde_json::Value::from(n * 2))
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "import host from 'host:api'; export const value = host.double(21);",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .get_value(Some(&module), "value")
+            .expect("Could not get value");
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_defines() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            defines: HashMap::from([
+                ("__DEBUG__".to_string(), "false".to_string()),
+                (
+                    "process.env.NODE_ENV".to_string(),
+                    "\"production\"".to_string(),
+                ),
+            ]),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "test.ts",
+            "
+            if (__DEBUG__) {
+                throw new Error('debug build');
+            }
+            export const env: string = process.env.NODE_ENV;
+            export const length = process.env.NODE_ENV.length;
+            const obj = { __DEBUG__: 1 };
+            export const key = obj.__DEBUG__;
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let env: String = runtime
+            .get_value(Some(&module), "env")
+            .expect("Could not get value");
+        assert_eq!(env, "production");
+        let length: usize = runtime
+            .get_value(Some(&module), "length")
+            .expect("Could not get value");
+        assert_eq!(length, 10);
+        let key: usize = runtime
+            .get_value(Some(&module), "key")
+            .expect("Could not get value");
+        assert_eq!(key, 1);
+    }
+
+    #[test]
+    fn test_minify() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            minify: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let source = "
+            export function add(a, b) {
+                // Adds two numbers
+                return a + b;
+            }
+            export const source = add.toString();
+        ";
+
+        let module = Module::new("minified.js", source);
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let minified: String = runtime
+            .get_value(Some(&module), "source")
+            .expect("Could not get value");
+        assert!(!minified.contains("Adds two numbers"), "{minified}");
+        assert!(!minified.contains('\n'), "{minified}");
+
+        let result: usize = runtime
+            .call_function(Some(&module), "add", json_args!(1, 2))
+            .expect("Could not call function");
+        assert_eq!(result, 3);
+
+        // Modules can opt out
+        let module = Module::new("original.js", source).with_minify(false);
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let original: String = runtime
+            .get_value(Some(&module), "source")
+            .expect("Could not get value");
+        assert!(original.contains("Adds two numbers"), "{original}");
+    }
+
+    #[test]
+    fn test_warmup_scripts() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            warmup_scripts: vec![
+                Module::new("polyfill.js", "globalThis.double = (x) => 2 * x;"),
+                Module::new("warm.js", "for (let i = 0; i < 100; i++) double(i);"),
+            ],
+            freeze_intrinsics: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let result: usize = runtime.eval("double(21)").expect("Could not eval");
+        assert_eq!(result, 42);
+
+        let err = Runtime::new(RuntimeOptions {
+            warmup_scripts: vec![Module::new("broken.js", "throw new Error('nope');")],
+            ..Default::default()
+        })
+        .err()
+        .expect("Warm-up script should fail");
+        assert!(
+            matches!(&err, Error::Warmup(name, _) if name.ends_with("broken.js")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_exposed_functions() {
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.expose('double', (n) => n * 2);
+            export function registerLate() {
+                rustyscript.expose('late', async () => 'late');
+                rustyscript.unexpose('double');
+            }
+            ",
+        );
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        assert_eq!(runtime.exposed_functions().unwrap(), ["double"]);
+        let value: usize = runtime.call_exposed("double", json_args!(21)).unwrap();
+        assert_eq!(value, 42);
+
+        runtime
+            .call_function::<Undefined>(Some(&module), "registerLate", json_args!())
+            .unwrap();
+        assert_eq!(runtime.exposed_functions().unwrap(), ["late"]);
+        let value: String = runtime.call_exposed("late", json_args!()).unwrap();
+        assert_eq!(value, "late");
+
+        let err = runtime
+            .call_exposed::<usize>("double", json_args!(1))
+            .unwrap_err();
+        assert!(matches!(err, Error::ValueNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn test_call_context() {
+        let module = Module::new(
+            "test.js",
+            "
+            export async function handle() {
+                const before = rustyscript.context();
+                await new Promise(r => setTimeout(r, 5));
+                const late = await new Promise(r => setTimeout(() => r(rustyscript.context()), 5));
+                return { before, after: rustyscript.context(), late, frozen: Object.isFrozen(before.user) };
+            }
+            export const outside = () => rustyscript.context() ?? null;
+            ",
+        );
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let context = crate::serde_json::json!({ "requestId": 7, "user": { "name": "alice" } });
+        let seen: crate::serde_json::Value = runtime
+            .call_function_with_context(Some(&module), "handle", &context, json_args!())
+            .unwrap();
+        assert_eq!(seen["before"], context);
+        assert_eq!(seen["after"], context);
+        assert_eq!(seen["late"], context);
+        assert_eq!(seen["frozen"], true);
+
+        // The context is gone once the call returns
+        let seen: crate::serde_json::Value = runtime
+            .call_function(Some(&module), "outside", json_args!())
+            .unwrap();
+        assert!(seen.is_null());
+    }
+
+    #[test]
+    fn test_warm_calls() {
+        let module = Module::new(
+            "test.js",
+            "
+            export let handler = () => 1;
+            export function swap() { handler = () => 2; }
+            globalThis.global_fn = () => 'a';
+
+            export let shadowed;
+            export function shadow() { shadowed = () => 'export'; }
+            globalThis.shadowed = () => 'global';
+            ",
+        );
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        for _ in 0..3 {
+            let value: usize = runtime
+                .call_function(Some(&module), "handler", json_args!())
+                .unwrap();
+            assert_eq!(value, 1);
+        }
+
+        // Reassigned names are picked up by the warm path
+        runtime
+            .call_function::<Undefined>(Some(&module), "swap", json_args!())
+            .unwrap();
+        let value: usize = runtime
+            .call_function(Some(&module), "handler", json_args!())
+            .unwrap();
+        assert_eq!(value, 2);
+
+        let value: String = runtime
+            .call_function(Some(&module), "global_fn", json_args!())
+            .unwrap();
+        assert_eq!(value, "a");
+        runtime
+            .eval::<Undefined>("globalThis.global_fn = () => 'b'")
+            .unwrap();
+        let value: String = runtime
+            .call_function(None, "global_fn", json_args!())
+            .unwrap();
+        assert_eq!(value, "b");
+        let value: String = runtime
+            .call_function(Some(&module), "global_fn", json_args!())
+            .unwrap();
+        assert_eq!(value, "b");
+
+        // An export defined since the last call takes over from the global it was hiding behind
+        let value: String = runtime
+            .call_function(Some(&module), "shadowed", json_args!())
+            .unwrap();
+        assert_eq!(value, "global");
+        runtime
+            .call_function::<Undefined>(Some(&module), "shadow", json_args!())
+            .unwrap();
+        let value: String = runtime
+            .call_function(Some(&module), "shadowed", json_args!())
+            .unwrap();
+        assert_eq!(value, "export");
+    }
+
+    #[test]
+    fn test_import_meta_provider() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_meta_provider: Some(Box::new(|specifier| {
+                HashMap::from([
+                    ("tenant".to_string(), specifier.scheme().into()),
+                    (
+                        "env".to_string(),
+                        crate::serde_json::json!({ "MODE": "test" }),
+                    ),
+                ])
+            })),
+            virtual_modules: vec![(
+                "app:child".to_string(),
+                "export const tenant = import.meta.tenant;".to_string(),
+            )],
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "main.js",
+            "
+            export { tenant as child } from 'app:child';
+            export const tenant = import.meta.tenant;
+            export const mode = import.meta.env.MODE;
+            export const url = import.meta.url;
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let get = |runtime: &mut Runtime, name: &str| -> String {
+            runtime
+                .get_value(Some(&module), name)
+                .expect("Could not get value")
+        };
+        assert_eq!(get(&mut runtime, "tenant"), "file");
+        assert_eq!(get(&mut runtime, "child"), "app");
+        assert_eq!(get(&mut runtime, "mode"), "test");
+        assert!(get(&mut runtime, "url").ends_with("main.js"));
+    }
+
+    #[test]
+    fn test_import_meta_resolve() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            specifier_rewrites: crate::module_loader::SpecifierRewrites::new()
+                .exact("lib", "https://cdn.example.com/lib.js")
+                .virtual_module("app:config", "export default {};"),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "assets/main.js",
+            "
+            export const asset = import.meta.resolve('./data/model.bin');
+            export const aliased = import.meta.resolve('lib');
+            export const config = import.meta.resolve('app:config');
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let get = |runtime: &mut Runtime, name: &str| -> String {
+            runtime
+                .get_value(Some(&module), name)
+                .expect("Could not get value")
+        };
+        assert!(get(&mut runtime, "asset").ends_with("/assets/data/model.bin"));
+        assert_eq!(
+            get(&mut runtime, "aliased"),
+            "https://cdn.example.com/lib.js"
+        );
+        assert_eq!(get(&mut runtime, "config"), "app:config");
+    }
+
+    #[test]
+    fn test_host_error_classes() {
+        struct OutOfStock(u32);
+        impl crate::IntoJsError for OutOfStock {
+            fn into_js_error(self) -> crate::HostError {
+                crate::HostError::new(format!("Only {} left", self.0)).with_code("OUT_OF_STOCK")
+            }
+        }
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_function("validate", |_| {
+                Err(crate::HostError::type_error("Expected a number").into())
+            })
+            .expect("Could not register function");
+        runtime
+            .register_function("order", |_| Err(OutOfStock(3).into()))
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            const attempt = (f) => {
+                try {
+                    f();
+                } catch (e) {
+                    return [e.constructor.name, e instanceof rustyscript.HostError, e.code ?? null, e.message];
+                }
+            };
+            export const validate = attempt(() => rustyscript.functions.validate());
+            export const order = attempt(() => rustyscript.functions.order());
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let validate: (String, bool, Option<String>, String) = runtime
+            .get_value(Some(&module), "validate")
+            .expect("Could not get value");
+        assert_eq!(
+            validate,
+            (
+                "TypeError".to_string(),
+                false,
+                None,
+                "Expected a number".to_string()
+            )
+        );
+
+        let order: (String, bool, Option<String>, String) = runtime
+            .get_value(Some(&module), "order")
+            .expect("Could not get value");
+        assert_eq!(
+            order,
+            (
+                "HostError".to_string(),
+                true,
+                Some("OUT_OF_STOCK".to_string()),
+                "Only 3 left".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_thrown_values() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct PaymentError {
+            name: String,
+            code: u32,
+        }
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            class PaymentError extends Error {
+                constructor({ code }) {
+                    super('Payment failed');
+                    this.name = 'PaymentError';
+                    this.code = code;
+                }
+            }
+            export const pay = () => { throw new PaymentError({ code: 42 }); };
+            export const payLater = async () => { throw new PaymentError({ code: 7 }); };
+            export const reject = async () => { throw { retry: true }; };
+            export const plain = () => { throw new Error('plain'); };
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let mut call = |name: &str| {
+            runtime
+                .call_function::<Undefined>(Some(&module), name, json_args!())
+                .expect_err("Expected the call to throw")
+        };
+
+        let e = call("pay");
+        assert_eq!(
+            e.try_downcast_thrown::<PaymentError>(),
+            Some(PaymentError {
+                name: "PaymentError".to_string(),
+                code: 42
+            })
+        );
+        assert!(e.to_string().contains("Payment failed"), "{e}");
+        assert!(matches!(e, Error::JsError(_)), "{e:?}");
+
+        let e = call("payLater");
+        assert_eq!(
+            e.try_downcast_thrown::<PaymentError>().map(|p| p.code),
+            Some(7)
+        );
+
+        let e = call("reject");
+        assert_eq!(
+            e.thrown_value(),
+            Some(&crate::serde_json::json!({ "retry": true }))
+        );
+
+        // Plain errors are left as they were
+        let e = call("plain");
+        assert!(e.thrown_value().is_none(), "{e:?}");
+    }
+
+    #[cfg(feature = "snapshot_builder")]
+    #[test]
+    fn test_snapshot() {
+        let library = Module::new("lib.js", "export const scale = (n) => n * 10;");
+        let tenant = Module::new(
+            "tenant.js",
+            "
+            import { scale } from './lib.js';
+            globalThis.table = [1, 2, 3].map(scale);
+            ",
+        );
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        runtime.load_modules(&tenant, vec![&library]).unwrap();
+        runtime.eval::<()>("globalThis.table.push(-1)").unwrap();
+
+        let snapshot: &'static [u8] =
+            Box::leak(runtime.snapshot(RuntimeOptions::default()).unwrap());
+        for _ in 0..2 {
+            let mut seeded = Runtime::new(RuntimeOptions {
+                startup_snapshot: Some(snapshot),
+                ..Default::default()
+            })
+            .unwrap();
+
+            // Changes made after loading are not captured
+            let table: Vec<i64> = seeded.eval("globalThis.table").unwrap();
+            assert_eq!(table, vec![10, 20, 30]);
+        }
+    }
 }
@@ -1,4 +1,8 @@
-use crate::{module_loader::ImportProvider, Error, RuntimeOptions};
+use crate::{
+    module_loader::{ImportProvider, SpecifierRewrites},
+    Error, Heartbeat, HeartbeatAction, HeartbeatOptions, RuntimeOptions, StallReport, Tz,
+    WatchdogOptions,
+};
 
 /// A builder for creating a new runtime
 ///
@@ -23,6 +27,14 @@ impl RuntimeBuilder {
         Self(RuntimeOptions::default())
     }
 
+    /// Create a new runtime builder starting from a preset profile - see [`crate::Profile`]
+    ///
+    /// Later calls override the profile's choices
+    #[must_use]
+    pub fn from_profile(profile: crate::Profile) -> Self {
+        Self(RuntimeOptions::from_profile(profile))
+    }
+
     /// Add an extension to the runtime
     ///
     /// This can be used to add custom functionality to the runtime
@@ -50,6 +62,24 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set how integers beyond `Number.MAX_SAFE_INTEGER` are passed to functions
+    ///
+    /// See [`crate::LargeIntegers`]
+    #[must_use]
+    pub fn with_large_integers(mut self, large_integers: crate::LargeIntegers) -> Self {
+        self.0.large_integers = large_integers;
+        self
+    }
+
+    /// Set whether missing values and entrypoints are errors, or read as `undefined`
+    ///
+    /// See [`crate::MissingValues`]
+    #[must_use]
+    pub fn with_missing_values(mut self, missing_values: crate::MissingValues) -> Self {
+        self.0.missing_values = missing_values;
+        self
+    }
+
     /// Set the timeout for the runtime
     ///
     /// This is the maximum time a script can run before it is terminated
@@ -66,6 +96,50 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Optional stack size limit for scripts run by the runtime
+    #[must_use]
+    pub fn with_stack_size(mut self, stack_size: usize) -> Self {
+        self.0.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Limits on the WebAssembly scripts may compile and instantiate
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "wasm")))]
+    #[must_use]
+    pub fn with_wasm_limits(mut self, limits: crate::WasmLimits) -> Self {
+        self.0.wasm_limits = limits;
+        self
+    }
+
+    /// Limits on the timers scripts may have pending, and on their delays
+    #[must_use]
+    pub fn with_timer_limits(mut self, limits: crate::TimerLimits) -> Self {
+        self.0.timer_limits = limits;
+        self
+    }
+
+    /// Token buckets shared with scripts, also throttling fetches and connections
+    #[must_use]
+    pub fn with_rate_limiter(mut self, limiter: crate::RateLimiter) -> Self {
+        self.0.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Record the time spent creating the runtime and loading each module - see [`crate::Runtime::startup_trace`]
+    #[must_use]
+    pub fn with_startup_trace(mut self) -> Self {
+        self.0.trace_startup = true;
+        self
+    }
+
+    /// Freeze the values exported by each module once it has loaded
+    #[must_use]
+    pub fn with_frozen_exports(mut self) -> Self {
+        self.0.freeze_exports = true;
+        self
+    }
+
     /// Optional import provider for the module loader
     #[must_use]
     pub fn with_import_provider(mut self, import_provider: Box<dyn ImportProvider>) -> Self {
@@ -73,6 +147,83 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Rewrite import specifiers before they are resolved - see [`SpecifierRewrites`]
+    #[must_use]
+    pub fn with_specifier_rewrites(mut self, rewrites: SpecifierRewrites) -> Self {
+        self.0.specifier_rewrites = rewrites;
+        self
+    }
+
+    /// Provide a built-in module, importable by scripts as `specifier`
+    ///
+    /// See [`RuntimeOptions::virtual_modules`]
+    #[must_use]
+    pub fn with_virtual_module(mut self, specifier: impl ToString, source: impl ToString) -> Self {
+        self.0
+            .virtual_modules
+            .push((specifier.to_string(), source.to_string()));
+        self
+    }
+
+    /// Substitute a compile-time constant into every module before it is transpiled
+    ///
+    /// See [`RuntimeOptions::defines`]
+    #[must_use]
+    pub fn with_define(mut self, name: impl ToString, value: impl ToString) -> Self {
+        self.0.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Minify modules after they are transpiled - see [`RuntimeOptions::minify`]
+    #[must_use]
+    pub fn with_minify(mut self) -> Self {
+        self.0.minify = true;
+        self
+    }
+
+    /// Cap the number of module sources fetched at once - see [`RuntimeOptions::module_fetch_concurrency`]
+    #[must_use]
+    pub fn with_module_fetch_concurrency(mut self, max: usize) -> Self {
+        self.0.module_fetch_concurrency = Some(max);
+        self
+    }
+
+    /// Retry transient failures of `fetch` and of async registered functions
+    ///
+    /// See [`RuntimeOptions::retry_policy`]
+    #[must_use]
+    pub fn with_retry_policy(mut self, policy: crate::RetryPolicy) -> Self {
+        self.0.retry_policy = Some(policy);
+        self
+    }
+
+    /// Short-circuit requests to hosts that keep failing
+    ///
+    /// See [`RuntimeOptions::circuit_breaker`]
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, breaker: crate::CircuitBreaker) -> Self {
+        self.0.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Send and keep cookies for `fetch`, in a jar the host can also use
+    ///
+    /// See [`RuntimeOptions::cookie_jar`]
+    #[must_use]
+    pub fn with_cookie_jar(mut self, jar: crate::CookieJar) -> Self {
+        self.0.cookie_jar = Some(jar);
+        self
+    }
+
+    /// Add a script to execute as soon as the runtime's context is created
+    ///
+    /// See [`RuntimeOptions::warmup_scripts`]
+    #[must_use]
+    pub fn with_warmup_script(mut self, script: crate::Module) -> Self {
+        self.0.warmup_scripts.push(script);
+        self
+    }
+
     /// Set the startup snapshot for the runtime
     ///
     /// This will reduce load times, but requires the same extensions to be loaded as when the snapshot was created
@@ -107,6 +258,154 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Add a label identifying the runtime
+    ///
+    /// Labels are attached to the exceptions its scripts throw (see [`crate::Error::labels`])
+    /// and to any telemetry it produces - other errors, and audit events, do not carry them
+    #[must_use]
+    pub fn with_label(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.0.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set a callback invoked when the event loop fails while being driven in the background
+    ///
+    /// See [`RuntimeOptions::on_background_error`]
+    #[must_use]
+    pub fn with_background_error_handler(mut self, handler: impl Fn(&Error) + 'static) -> Self {
+        self.0.on_background_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Set a callback supplying extra `import.meta` fields for each module
+    ///
+    /// See [`RuntimeOptions::import_meta_provider`]
+    #[must_use]
+    pub fn with_import_meta_provider(
+        mut self,
+        provider: impl Fn(
+                &deno_core::ModuleSpecifier,
+            ) -> std::collections::HashMap<String, deno_core::serde_json::Value>
+            + 'static,
+    ) -> Self {
+        self.0.import_meta_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Set a callback invoked when a script registers a listener on the global scope
+    ///
+    /// See [`RuntimeOptions::on_event_listener`]
+    #[cfg(feature = "events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "events")))]
+    #[must_use]
+    pub fn with_event_listener_hook(
+        mut self,
+        hook: impl Fn(&crate::EventListenerInfo) + 'static,
+    ) -> Self {
+        self.0.on_event_listener = Some(Box::new(hook));
+        self
+    }
+
+    /// Set a callback that sees every message sent or received by a script's `WebSocket`
+    ///
+    /// See [`RuntimeOptions::on_websocket_frame`]
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    #[must_use]
+    pub fn with_websocket_tap(mut self, tap: impl Fn(&crate::WebSocketFrame) + 'static) -> Self {
+        self.0.on_websocket_frame = Some(Box::new(tap));
+        self
+    }
+
+    /// Deep-freeze the built-in prototypes once the runtime has started
+    ///
+    /// See [`RuntimeOptions::freeze_intrinsics`]
+    #[must_use]
+    pub fn with_frozen_intrinsics(mut self) -> Self {
+        self.0.freeze_intrinsics = true;
+        self
+    }
+
+    /// Set the default locale used by `Intl` and the `toLocale*` methods
+    ///
+    /// See [`RuntimeOptions::default_locale`]
+    #[must_use]
+    pub fn with_default_locale(mut self, locale: impl ToString) -> Self {
+        self.0.default_locale = Some(locale.to_string());
+        self
+    }
+
+    /// Set the timezone used for local time by `Date` and `Intl`
+    ///
+    /// See [`RuntimeOptions::timezone`]
+    #[must_use]
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.0.timezone = Some(timezone);
+        self
+    }
+
+    /// Give the runtime its own scratch directory for temp files, deleted when the runtime is dropped
+    ///
+    /// See [`RuntimeOptions::temp_dir`]
+    #[cfg(feature = "fs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+    #[must_use]
+    pub fn with_temp_dir(mut self, options: crate::TempDirOptions) -> Self {
+        self.0.temp_dir = Some(options);
+        self
+    }
+
+    /// Set the command-line arguments for the script, surfaced as `Deno.args`
+    #[must_use]
+    pub fn with_argv(mut self, argv: impl IntoIterator<Item = impl ToString>) -> Self {
+        self.0.argv = argv.into_iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    /// Enable the event-loop watchdog
+    ///
+    /// `on_stall` is called with a dump of the pending ops whenever the event loop goes
+    /// `stall_timeout` without making progress - see [`RuntimeOptions::watchdog`]
+    #[must_use]
+    pub fn with_watchdog(
+        mut self,
+        stall_timeout: std::time::Duration,
+        on_stall: impl Fn(&StallReport) + 'static,
+    ) -> Self {
+        self.0.watchdog = Some(WatchdogOptions::new(stall_timeout, on_stall));
+        self
+    }
+
+    /// Handle heartbeats sent by scripts with `rustyscript.heartbeat` and `rustyscript.progress`
+    ///
+    /// `on_heartbeat` decides whether to extend or abort the call - see [`RuntimeOptions::heartbeat`]
+    #[must_use]
+    pub fn with_heartbeat(
+        mut self,
+        on_heartbeat: impl Fn(&Heartbeat) -> HeartbeatAction + 'static,
+    ) -> Self {
+        self.0.heartbeat = Some(HeartbeatOptions::new(on_heartbeat));
+        self
+    }
+
+    /// Set how long scripts are given to clean up once their call is cancelled or times out
+    ///
+    /// See [`RuntimeOptions::cancel_grace`]
+    #[must_use]
+    pub fn with_cancel_grace(mut self, grace: std::time::Duration) -> Self {
+        self.0.cancel_grace = grace;
+        self
+    }
+
+    /// Pass the `AbortSignal` of the current call as the last argument to every function called
+    ///
+    /// See [`RuntimeOptions::call_signal_argument`]
+    #[must_use]
+    pub fn with_call_signal_argument(mut self) -> Self {
+        self.0.call_signal_argument = true;
+        self
+    }
+
     /// Add to a whitelist of custom schema prefixes that are allowed to be loaded from javascript
     ///
     /// By default only http/https (`url_import` crate feature), and file (`fs_import` crate feature) are allowed
@@ -116,6 +415,17 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Enable OpenTelemetry instrumentation for the runtime
+    ///
+    /// Spans and metrics are reported through the global `opentelemetry` providers
+    #[cfg(feature = "otel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otel")))]
+    #[must_use]
+    pub fn with_otel(mut self, options: crate::OtelOptions) -> Self {
+        self.0.otel = Some(options);
+        self
+    }
+
     //
     // Extension options
     //
@@ -138,6 +448,15 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set host-provided endpoints for the script's standard streams
+    #[cfg(feature = "io")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "io")))]
+    #[must_use]
+    pub fn with_stdio(mut self, stdio: crate::VirtualStdio) -> Self {
+        self.0.extension_options.stdio = Some(stdio);
+        self
+    }
+
     /// Set the options for the webstorage extension
     #[cfg(feature = "webstorage")]
     #[cfg_attr(docsrs, doc(cfg(feature = "webstorage")))]
@@ -177,6 +496,33 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set the provider backing `rustyscript.database`
+    #[cfg(feature = "database")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "database")))]
+    #[must_use]
+    pub fn with_db_provider(mut self, provider: impl crate::DbProvider + 'static) -> Self {
+        self.0.extension_options.db_provider = Some(std::rc::Rc::new(provider));
+        self
+    }
+
+    /// Set the backend and key namespace for `rustyscript.cache`
+    #[cfg(feature = "host_cache")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "host_cache")))]
+    #[must_use]
+    pub fn with_host_cache(mut self, cache: crate::HostCache) -> Self {
+        self.0.extension_options.host_cache = Some(cache);
+        self
+    }
+
+    /// Set the secrets available through `rustyscript.secrets`
+    #[cfg(feature = "secrets")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secrets")))]
+    #[must_use]
+    pub fn with_secrets(mut self, store: crate::SecretStore) -> Self {
+        self.0.extension_options.secrets = Some(store);
+        self
+    }
+
     /// Set the options for the node extension
     #[cfg(feature = "node_experimental")]
     #[cfg_attr(docsrs, doc(cfg(feature = "node_experimental")))]
@@ -186,6 +532,32 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set the virtual environment backing `Deno.env`
+    ///
+    /// Keep a clone of the `VirtualEnv` to read back any changes made by scripts
+    #[cfg(all(feature = "env", not(feature = "node_experimental")))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "env", not(feature = "node_experimental"))))
+    )]
+    #[must_use]
+    pub fn with_env(mut self, env: crate::VirtualEnv) -> Self {
+        self.0.extension_options.env = env;
+        self
+    }
+
+    /// Set a single variable in the virtual environment backing `Deno.env`
+    #[cfg(all(feature = "env", not(feature = "node_experimental")))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "env", not(feature = "node_experimental"))))
+    )]
+    #[must_use]
+    pub fn with_env_var(self, key: impl ToString, value: impl ToString) -> Self {
+        self.0.extension_options.env.set(key, value);
+        self
+    }
+
     //
     // Web options
     //
@@ -220,6 +592,42 @@ impl RuntimeBuilder {
         self
     }
 
+    /// HTTP versions used by fetch, unless a request supplies its own client
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    #[must_use]
+    pub fn with_fetch_http_version(mut self, version: crate::FetchHttpVersion) -> Self {
+        self.0.extension_options.web.fetch_http_version = version;
+        self
+    }
+
+    /// Connection pool settings for fetch, unless a request supplies its own client
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    #[must_use]
+    pub fn with_fetch_pool(mut self, pool: crate::FetchPoolOptions) -> Self {
+        self.0.extension_options.web.fetch_pool = pool;
+        self
+    }
+
+    /// Cache responses to `GET` fetches, honoring `Cache-Control` and validators
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    #[must_use]
+    pub fn with_http_cache(mut self, cache: crate::HttpCache) -> Self {
+        self.0.extension_options.web.http_cache = Some(cache);
+        self
+    }
+
+    /// Record responses to, or replay them from, a fixture store - see [`crate::FetchFixtures`]
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    #[must_use]
+    pub fn with_fetch_fixtures(mut self, fixtures: crate::FetchFixtures) -> Self {
+        self.0.extension_options.web.fetch_fixtures = Some(fixtures);
+        self
+    }
+
     /// Proxy for fetch
     #[cfg(feature = "web")]
     #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
@@ -266,6 +674,20 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Embedder-provided TLS configuration for fetch, `Deno.connectTls` and `Deno.startTls`
+    ///
+    /// See [`crate::TlsProvider`]
+    #[cfg(feature = "web")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
+    #[must_use]
+    pub fn with_web_tls_provider(
+        mut self,
+        provider: std::sync::Arc<dyn crate::TlsProvider>,
+    ) -> Self {
+        self.0.extension_options.web.tls_provider = Some(provider);
+        self
+    }
+
     /// Client certificate and key for fetch
     #[cfg(feature = "web")]
     #[cfg_attr(docsrs, doc(cfg(feature = "web")))]
@@ -0,0 +1,832 @@
+//! Runs a runtime in a child process, behind a proxy with a `Runtime`-like API
+//!
+//! A V8 crash, or a script exhausting the child's memory, takes down only the child - the host sees
+//! [`Error::ProcessExited`], and the [`RestartPolicy`] decides whether the next call starts a fresh
+//! child, with the modules loaded so far loaded into it again
+//!
+//! The child is a copy of the host program, so `main` must hand control to [`serve_if_child`]
+//! before doing anything else. Arguments and results cross the process boundary as JSON
+//!
+//! [`ResourceLimits`] confine the child to a cgroup (Linux) or Job Object (Windows), capping its
+//! memory and CPU, with [`ProcessRuntime::usage`] reporting what it used
+//! ```rust,no_run
+//! use rustyscript::{
+//!     sandbox_process::{self, ProcessRuntime, ProcessRuntimeOptions, RestartPolicy},
+//!     Error, Module,
+//! };
+//!
+//! fn main() -> Result<(), Error> {
+//!     // Never returns when this process was started as a sandbox
+//!     sandbox_process::serve_if_child();
+//!
+//!     let mut runtime = ProcessRuntime::new(ProcessRuntimeOptions {
+//!         restart: RestartPolicy::Limited(3),
+//!         ..Default::default()
+//!     })?;
+//!
+//!     let module = Module::new("test.js", "export const add = (a, b) => a + b;");
+//!     let module = runtime.load_module(&module)?;
+//!     let sum: i64 = runtime.call_function(Some(module), "add", &(2, 3))?;
+//!     assert_eq!(sum, 5);
+//!     Ok(())
+//! }
+//! ```
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant, SystemTime},
+};
+
+use deno_core::serde_json;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Error, Module, ModuleHandle, Runtime, RuntimeOptions};
+
+mod confinement;
+use confinement::{describe_exit, Confinement};
+pub use confinement::{ResourceLimits, ResourceUsage};
+
+/// The environment variable holding the address a sandbox process connects back to
+const ADDRESS_VAR: &str = "RUSTYSCRIPT_SANDBOX_ADDRESS";
+
+/// The environment variable holding the token a sandbox process authenticates with
+const TOKEN_VAR: &str = "RUSTYSCRIPT_SANDBOX_TOKEN";
+
+/// The largest message either side will accept
+const MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+/// The largest message the host accepts from a connection that has not yet authenticated
+const MAX_TOKEN_FRAME_LEN: usize = 256;
+
+/// How long each connection to the host has to authenticate, so a stray one cannot stall startup
+const AUTH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Environment variables the child inherits from the host - everything else is cleared
+const INHERITED_ENV: &[&str] = &[
+    "PATH",
+    "LANG",
+    "LC_ALL",
+    "TZ",
+    "TMPDIR",
+    "RUST_BACKTRACE",
+    "RUST_LOG",
+    // Needed by Windows processes to start at all
+    "SYSTEMROOT",
+    "TEMP",
+    "TMP",
+];
+
+/// Whether a [`ProcessRuntime`] starts a new child after the previous one exits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Once the child exits, every call fails with [`Error::ProcessExited`]
+    #[default]
+    Never,
+
+    /// Start a new child whenever the previous one has exited
+    Always,
+
+    /// Start a new child up to this many times over the life of the runtime
+    Limited(u32),
+}
+
+/// Options for a [`ProcessRuntime`]
+#[derive(Debug, Clone)]
+pub struct ProcessRuntimeOptions {
+    /// The program to run as the child - defaults to the current executable
+    ///
+    /// It must call [`serve_if_child`] on startup
+    pub program: Option<PathBuf>,
+
+    /// Arguments passed to the child
+    pub args: Vec<String>,
+
+    /// Environment variables set for the child
+    ///
+    /// The child does not inherit the host's environment, apart from a few variables such as
+    /// `PATH`, `LANG` and `TZ` - so secrets in the host's environment stay out of its reach
+    pub env: Vec<(String, String)>,
+
+    /// The default entrypoint function of the child's runtime
+    pub default_entrypoint: Option<String>,
+
+    /// The timeout of the child's runtime - see [`RuntimeOptions::timeout`]
+    pub timeout: Duration,
+
+    /// The heap limit of the child's runtime - see [`RuntimeOptions::max_heap_size`]
+    pub max_heap_size: Option<usize>,
+
+    /// How long a child may take to start and connect back to the host
+    pub startup_timeout: Duration,
+
+    /// How long to wait for the child to answer a call before killing it
+    ///
+    /// Guards against a child wedged outside of the runtime's own `timeout`
+    pub call_timeout: Option<Duration>,
+
+    /// Whether to start a new child after the previous one exits
+    pub restart: RestartPolicy,
+
+    /// Memory and CPU caps enforced on the child by the OS
+    ///
+    /// Unlike `max_heap_size`, these cover everything the child allocates, not just the V8 heap
+    pub limits: ResourceLimits,
+
+    /// OS-level restrictions the child applies to itself before creating its runtime
+    ///
+    /// The connection to the host is opened first, so it is unaffected
+    #[cfg(all(feature = "hardening", target_os = "linux"))]
+    pub hardening: Option<crate::Hardening>,
+}
+
+impl Default for ProcessRuntimeOptions {
+    fn default() -> Self {
+        Self {
+            program: None,
+            args: Vec::new(),
+            env: Vec::new(),
+            default_entrypoint: None,
+            timeout: Duration::MAX,
+            max_heap_size: None,
+            startup_timeout: Duration::from_secs(10),
+            call_timeout: None,
+            restart: RestartPolicy::default(),
+            limits: ResourceLimits::default(),
+
+            #[cfg(all(feature = "hardening", target_os = "linux"))]
+            hardening: None,
+        }
+    }
+}
+
+/// A module loaded into a [`ProcessRuntime`]
+///
+/// Remains valid across restarts, as the module is loaded into each new child
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessModule(usize);
+
+/// The options the child builds its runtime from
+#[derive(Serialize, Deserialize)]
+struct ChildOptions {
+    default_entrypoint: Option<String>,
+    timeout: Duration,
+    max_heap_size: Option<usize>,
+
+    #[cfg(all(feature = "hardening", target_os = "linux"))]
+    hardening: Option<crate::Hardening>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Init(ChildOptions),
+    Eval(String),
+    LoadModule(Module),
+    CallEntrypoint(usize, serde_json::Value),
+    CallFunction(Option<usize>, String, serde_json::Value),
+    GetValue(Option<usize>, String),
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Ok,
+    Value(serde_json::Value),
+    Module(usize),
+    Error(Error),
+}
+
+/// A running child, and the connection to it
+struct ChildProcess {
+    process: Child,
+    stream: TcpStream,
+    confinement: Option<Confinement>,
+}
+
+impl ChildProcess {
+    /// Kills the child if it is still running, describing how it exited, and what it used
+    fn reap(mut self) -> (String, Option<ResourceUsage>) {
+        self.process.kill().ok();
+        let status = match self.process.wait() {
+            Ok(status) => status.to_string(),
+            Err(e) => format!("unknown status ({e})"),
+        };
+
+        let usage = self.confinement.as_ref().map(Confinement::usage);
+        (describe_exit(status, usage.as_ref()), usage)
+    }
+}
+
+/// A proxy to a runtime running in a child process
+///
+/// Each call blocks until the child answers. See the [module documentation](self)
+pub struct ProcessRuntime {
+    options: ProcessRuntimeOptions,
+    child: Option<ChildProcess>,
+    modules: Vec<Module>,
+    restarts: u32,
+    last_exit: Option<String>,
+    last_usage: Option<ResourceUsage>,
+}
+
+impl ProcessRuntime {
+    /// Starts a child process and the runtime within it
+    ///
+    /// # Errors
+    /// Can fail if the child cannot be started, does not connect back within
+    /// [`ProcessRuntimeOptions::startup_timeout`], or cannot create its runtime
+    pub fn new(options: ProcessRuntimeOptions) -> Result<Self, Error> {
+        let child = spawn(&options)?;
+        Ok(Self {
+            options,
+            child: Some(child),
+            modules: Vec::new(),
+            restarts: 0,
+            last_exit: None,
+            last_usage: None,
+        })
+    }
+
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code in the child
+    ///
+    /// # Errors
+    /// Can fail if the expression cannot be evaluated, if the result cannot be deserialized into
+    /// the requested type, or if the child exits
+    pub fn eval<T>(&mut self, expr: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.request(&Request::Eval(expr.to_string()))?;
+        value(response)
+    }
+
+    /// Executes the given module in the child, and returns a handle to call its functions
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if execution fails, or if the child exits
+    pub fn load_module(&mut self, module: &Module) -> Result<ProcessModule, Error> {
+        match self.request(&Request::LoadModule(module.clone()))? {
+            Response::Module(_) => {
+                self.modules.push(module.clone());
+                Ok(ProcessModule(self.modules.len() - 1))
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Calls the entrypoint of a module loaded with [`ProcessRuntime::load_module`]
+    ///
+    /// # Errors
+    /// Can fail if the module has no entrypoint, if the call fails, if the result cannot be
+    /// deserialized into the requested type, or if the child exits
+    pub fn call_entrypoint<T>(
+        &mut self,
+        module: ProcessModule,
+        args: &impl Serialize,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let args = serde_json::to_value(args)?;
+        let response = self.request(&Request::CallEntrypoint(module.0, args))?;
+        value(response)
+    }
+
+    /// Calls a function exported by a module, or found in the global scope if `module` is None
+    ///
+    /// # Errors
+    /// Can fail if the function is not found, if the call fails, if the result cannot be
+    /// deserialized into the requested type, or if the child exits
+    pub fn call_function<T>(
+        &mut self,
+        module: Option<ProcessModule>,
+        name: &str,
+        args: &impl Serialize,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let args = serde_json::to_value(args)?;
+        let request = Request::CallFunction(module.map(|m| m.0), name.to_string(), args);
+        let response = self.request(&request)?;
+        value(response)
+    }
+
+    /// Gets a value exported by a module, or found in the global scope if `module` is None
+    ///
+    /// # Errors
+    /// Can fail if the value is not found, if it cannot be deserialized into the requested type,
+    /// or if the child exits
+    pub fn get_value<T>(&mut self, module: Option<ProcessModule>, name: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let request = Request::GetValue(module.map(|m| m.0), name.to_string());
+        let response = self.request(&request)?;
+        value(response)
+    }
+
+    /// Returns true if the child is still running
+    pub fn is_running(&mut self) -> bool {
+        let running = match &mut self.child {
+            Some(child) => matches!(child.process.try_wait(), Ok(None)),
+            None => false,
+        };
+        if !running {
+            self.reap();
+        }
+        running
+    }
+
+    /// The process id of the current child, if it is running
+    #[must_use]
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.process.id())
+    }
+
+    /// The resources used by the current child, or by the last one if none is running
+    ///
+    /// Only reported when [`ProcessRuntimeOptions::limits`] are set
+    #[must_use]
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        match &self.child {
+            Some(child) => child.confinement.as_ref().map(Confinement::usage),
+            None => self.last_usage,
+        }
+    }
+
+    /// The number of times a new child has been started to replace one that exited
+    #[must_use]
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Kills the current child
+    ///
+    /// The next call starts a new one, if the [`RestartPolicy`] allows it
+    pub fn kill(&mut self) {
+        self.reap();
+    }
+
+    /// Sends a request to the child, starting a new one first if needed
+    fn request(&mut self, request: &Request) -> Result<Response, Error> {
+        let child = self.ensure_running()?;
+        child
+            .stream
+            .set_read_timeout(self.options.call_timeout)
+            .map_err(transport_error)?;
+
+        match write_frame(&mut child.stream, request).and_then(|()| read_frame(&mut child.stream)) {
+            Ok(Response::Error(e)) => Err(e),
+            Ok(response) => Ok(response),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                self.reap();
+                Err(Error::Timeout(format!(
+                    "sandbox process did not answer within {:?}",
+                    self.options.call_timeout.unwrap_or_default()
+                )))
+            }
+            Err(_) => {
+                let status = self.reap().unwrap_or_default();
+                Err(Error::ProcessExited(status))
+            }
+        }
+    }
+
+    /// Returns the running child, or starts a new one if the restart policy allows
+    fn ensure_running(&mut self) -> Result<&mut ChildProcess, Error> {
+        if self.child.is_none() {
+            let allowed = match self.options.restart {
+                RestartPolicy::Never => false,
+                RestartPolicy::Always => true,
+                RestartPolicy::Limited(max) => self.restarts < max,
+            };
+            if !allowed {
+                let status = self.last_exit.clone().unwrap_or_default();
+                return Err(Error::ProcessExited(status));
+            }
+
+            self.restarts += 1;
+            let mut child = spawn(&self.options)?;
+            for module in &self.modules {
+                write_frame(&mut child.stream, &Request::LoadModule(module.clone()))
+                    .map_err(transport_error)?;
+                if let Response::Error(e) =
+                    read_frame(&mut child.stream).map_err(transport_error)?
+                {
+                    child.reap();
+                    return Err(e);
+                }
+            }
+            self.child = Some(child);
+        }
+
+        self.child
+            .as_mut()
+            .ok_or_else(|| Error::Runtime("Sandbox process could not be started".to_string()))
+    }
+
+    /// Kills the current child, if any, recording how it exited
+    fn reap(&mut self) -> Option<String> {
+        let (status, usage) = self.child.take()?.reap();
+        self.last_exit = Some(status.clone());
+        self.last_usage = usage;
+        Some(status)
+    }
+}
+
+impl Drop for ProcessRuntime {
+    fn drop(&mut self) {
+        self.reap();
+    }
+}
+
+/// Serves the host that started this process, if it was started as a sandbox by a
+/// [`ProcessRuntime`] - otherwise returns immediately
+///
+/// Call this at the very start of `main`. When serving, the process exits once the host goes away,
+/// and this function never returns
+pub fn serve_if_child() {
+    let Ok(address) = std::env::var(ADDRESS_VAR) else {
+        return;
+    };
+    let token = std::env::var(TOKEN_VAR).unwrap_or_default();
+
+    let code = i32::from(serve(&address, &token).is_err());
+    std::process::exit(code);
+}
+
+/// Connects back to the host, then answers its requests until it disconnects
+fn serve(address: &str, token: &str) -> Result<(), Error> {
+    let mut stream = TcpStream::connect(address).map_err(transport_error)?;
+    stream.set_nodelay(true).map_err(transport_error)?;
+    write_frame(&mut stream, &token).map_err(transport_error)?;
+
+    let Request::Init(options) = read_frame(&mut stream).map_err(transport_error)? else {
+        return Err(Error::Runtime(
+            "Sandbox process was not initialized".to_string(),
+        ));
+    };
+
+    #[cfg(all(feature = "hardening", target_os = "linux"))]
+    if let Some(hardening) = &options.hardening {
+        if let Err(e) = hardening.apply() {
+            write_frame(&mut stream, &Response::Error(e)).map_err(transport_error)?;
+            return Ok(());
+        }
+    }
+
+    let runtime = Runtime::new(RuntimeOptions {
+        default_entrypoint: options.default_entrypoint,
+        timeout: options.timeout,
+        max_heap_size: options.max_heap_size,
+        ..Default::default()
+    });
+    let mut runtime = match runtime {
+        Ok(runtime) => {
+            write_frame(&mut stream, &Response::Ok).map_err(transport_error)?;
+            runtime
+        }
+        Err(e) => {
+            write_frame(&mut stream, &Response::Error(e)).map_err(transport_error)?;
+            return Ok(());
+        }
+    };
+
+    let mut modules = Vec::new();
+    loop {
+        let request = match read_frame(&mut stream) {
+            Ok(request) => request,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(transport_error(e)),
+        };
+        let response =
+            handle_request(&mut runtime, &mut modules, request).unwrap_or_else(Response::Error);
+        write_frame(&mut stream, &response).map_err(transport_error)?;
+    }
+}
+
+fn handle_request(
+    runtime: &mut Runtime,
+    modules: &mut Vec<ModuleHandle>,
+    request: Request,
+) -> Result<Response, Error> {
+    let module = |modules: &[ModuleHandle], id: usize| {
+        modules
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::Runtime("Module not found".to_string()))
+    };
+
+    let value = match request {
+        Request::Init(_) => {
+            return Err(Error::Runtime(
+                "Sandbox process is already initialized".to_string(),
+            ))
+        }
+
+        Request::LoadModule(module) => {
+            modules.push(runtime.load_module(&module)?);
+            return Ok(Response::Module(modules.len() - 1));
+        }
+
+        Request::Eval(expr) => runtime.eval(expr)?,
+
+        Request::CallEntrypoint(id, args) => {
+            runtime.call_entrypoint(&module(modules, id)?, &args)?
+        }
+
+        Request::CallFunction(id, name, args) => {
+            let handle = id.map(|id| module(modules, id)).transpose()?;
+            runtime.call_function(handle.as_ref(), &name, &args)?
+        }
+
+        Request::GetValue(id, name) => {
+            let handle = id.map(|id| module(modules, id)).transpose()?;
+            runtime.get_value(handle.as_ref(), &name)?
+        }
+    };
+
+    Ok(Response::Value(value))
+}
+
+/// Starts a child, waits for it to connect back, and initializes its runtime
+fn spawn(options: &ProcessRuntimeOptions) -> Result<ChildProcess, Error> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(transport_error)?;
+    let address = listener.local_addr().map_err(transport_error)?;
+    let token = new_token();
+
+    let confinement = if options.limits.is_empty() {
+        None
+    } else {
+        Some(Confinement::new(&options.limits)?)
+    };
+
+    let program = match &options.program {
+        Some(program) => program.clone(),
+        None => std::env::current_exe().map_err(transport_error)?,
+    };
+    let inherited = INHERITED_ENV
+        .iter()
+        .filter_map(|name| Some((*name, std::env::var_os(name)?)));
+    let mut process = Command::new(program)
+        .args(&options.args)
+        .env_clear()
+        .envs(inherited)
+        .envs(options.env.iter().map(|(k, v)| (k, v)))
+        .env(ADDRESS_VAR, address.to_string())
+        .env(TOKEN_VAR, &token)
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(transport_error)?;
+
+    // The child is sent its options only once attached, so its runtime is always confined
+    let attached = confinement
+        .as_ref()
+        .map_or(Ok(()), |confinement| confinement.attach(&process));
+
+    match attached.and_then(|()| connect(&listener, &mut process, &token, options)) {
+        Ok(stream) => Ok(ChildProcess {
+            process,
+            stream,
+            confinement,
+        }),
+        Err(e) => {
+            process.kill().ok();
+            process.wait().ok();
+            Err(e)
+        }
+    }
+}
+
+/// Accepts the child's connection, then sends it the runtime's options
+fn connect(
+    listener: &TcpListener,
+    process: &mut Child,
+    token: &str,
+    options: &ProcessRuntimeOptions,
+) -> Result<TcpStream, Error> {
+    let deadline = Instant::now() + options.startup_timeout;
+    let timed_out = || {
+        Error::Timeout(format!(
+            "sandbox process did not start within {:?}",
+            options.startup_timeout
+        ))
+    };
+
+    listener.set_nonblocking(true).map_err(transport_error)?;
+    let mut stream = loop {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                stream.set_nonblocking(false).map_err(transport_error)?;
+                stream
+                    .set_read_timeout(Some(
+                        remaining.clamp(Duration::from_millis(1), AUTH_TIMEOUT),
+                    ))
+                    .map_err(transport_error)?;
+
+                // Ignore anything else that found the port
+                let authenticated = read_frame_within::<String>(&mut stream, MAX_TOKEN_FRAME_LEN)
+                    .is_ok_and(|t| t == token);
+                if authenticated {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    stream
+                        .set_read_timeout(Some(remaining.max(Duration::from_millis(1))))
+                        .map_err(transport_error)?;
+                    break stream;
+                }
+            }
+
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if let Some(status) = process.try_wait().map_err(transport_error)? {
+                    return Err(Error::ProcessExited(status.to_string()));
+                }
+                if Instant::now() >= deadline {
+                    return Err(timed_out());
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            Err(e) => return Err(transport_error(e)),
+        }
+    };
+    stream.set_nodelay(true).map_err(transport_error)?;
+
+    let init = Request::Init(ChildOptions {
+        default_entrypoint: options.default_entrypoint.clone(),
+        timeout: options.timeout,
+        max_heap_size: options.max_heap_size,
+
+        #[cfg(all(feature = "hardening", target_os = "linux"))]
+        hardening: options.hardening.clone(),
+    });
+    write_frame(&mut stream, &init).map_err(transport_error)?;
+    match read_frame(&mut stream) {
+        Ok(Response::Ok) => Ok(stream),
+        Ok(Response::Error(e)) => Err(e),
+        Ok(response) => Err(unexpected(response)),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Err(timed_out())
+        }
+        Err(e) => Err(transport_error(e)),
+    }
+}
+
+/// A token the child proves it was started by this host with
+fn new_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    (0..2)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u32(std::process::id());
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Writes a length-prefixed JSON message
+fn write_frame(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "message too large"))?;
+
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+/// Reads a length-prefixed JSON message
+fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    read_frame_within(reader, MAX_FRAME_LEN)
+}
+
+/// Reads a length-prefixed JSON message, of up to `max_len` bytes
+fn read_frame_within<T: DeserializeOwned>(reader: &mut impl Read, max_len: usize) -> io::Result<T> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+fn transport_error(error: io::Error) -> Error {
+    Error::Runtime(format!("Sandbox process connection failed: {error}"))
+}
+
+fn unexpected(response: Response) -> Error {
+    match response {
+        Response::Error(e) => e,
+        _ => Error::Runtime("Unexpected response from the sandbox process".to_string()),
+    }
+}
+
+fn value<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    match response {
+        Response::Value(value) => Ok(serde_json::from_value(value)?),
+        response => Err(unexpected(response)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The entrypoint of sandbox processes started by these tests - a no-op in the test run itself
+    #[test]
+    fn child_entry() {
+        serve_if_child();
+    }
+
+    fn options(restart: RestartPolicy) -> ProcessRuntimeOptions {
+        ProcessRuntimeOptions {
+            args: [
+                "sandbox_process::test::child_entry",
+                "--exact",
+                "--nocapture",
+            ]
+            .map(String::from)
+            .to_vec(),
+            timeout: Duration::from_secs(5),
+            restart,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_limits_need_cgroup() {
+        let mut options = options(RestartPolicy::Never);
+        options.limits.memory = Some(64 * 1024 * 1024);
+        let e = ProcessRuntime::new(options).err().unwrap();
+        assert!(e.to_string().contains("cgroup_parent"), "{e}");
+    }
+
+    #[test]
+    fn test_process_runtime() {
+        let module = Module::new(
+            "counter.js",
+            "
+            let count = 0;
+            export const increment = (by) => count += by;
+            export const fail = () => { throw new Error('nope'); };
+            ",
+        );
+        let mut runtime = ProcessRuntime::new(options(RestartPolicy::Limited(1))).unwrap();
+        assert_ne!(runtime.id(), Some(std::process::id()));
+
+        let counter = runtime.load_module(&module).unwrap();
+        let count: i64 = runtime
+            .call_function(Some(counter), "increment", &(5,))
+            .unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(runtime.eval::<i64>("2 ** 10").unwrap(), 1024);
+
+        let e = runtime
+            .call_function::<()>(Some(counter), "fail", &())
+            .unwrap_err();
+        assert!(e.to_string().contains("nope"), "{e}");
+        assert!(runtime.is_running());
+
+        // A new child has the module loaded again, with fresh state
+        runtime.kill();
+        assert!(!runtime.is_running());
+        let count: i64 = runtime
+            .call_function(Some(counter), "increment", &(1,))
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(runtime.restarts(), 1);
+
+        // Out of restarts
+        runtime.kill();
+        let e = runtime.eval::<i64>("1").unwrap_err();
+        assert!(matches!(e, Error::ProcessExited(_)), "{e:?}");
+    }
+}
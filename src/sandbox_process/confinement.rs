@@ -0,0 +1,344 @@
+//! Confines sandbox processes to a cgroup (Linux) or a Job Object (Windows)
+//!
+//! Each child gets its own group, created before it starts and removed once it has been reaped,
+//! so usage is reported per child
+use std::time::Duration;
+
+use crate::Error;
+
+/// Memory and CPU caps for a sandbox process - see [`super::ProcessRuntimeOptions::limits`]
+///
+/// Enforced by the OS: a child exceeding its memory cap is killed, and one exceeding its CPU share
+/// is throttled. Supported on Linux, with cgroups v2, and on Windows
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// The most memory the child may use, in bytes
+    pub memory: Option<u64>,
+
+    /// The share of CPU time the child may use, in cores - `0.5` is half of one core
+    pub cpu: Option<f64>,
+
+    /// The cgroup each child's own cgroup is created beneath (Linux only)
+    ///
+    /// Must be writable by the host, with the `memory` and `cpu` controllers listed in its
+    /// `cgroup.subtree_control` - for example a directory delegated by systemd
+    pub cgroup_parent: Option<std::path::PathBuf>,
+}
+
+impl ResourceLimits {
+    /// Returns true if no caps are set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpu.is_none()
+    }
+}
+
+/// Resources used by a sandbox process - see [`super::ProcessRuntime::usage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Memory in use, in bytes - not reported on Windows
+    pub memory: Option<u64>,
+
+    /// The most memory in use at once, in bytes, if the OS reports it
+    pub peak_memory: Option<u64>,
+
+    /// CPU time used, across all threads
+    pub cpu_time: Duration,
+
+    /// Whether the memory cap was hit, and the child killed for it - not reported on Windows
+    pub memory_exceeded: bool,
+}
+
+#[cfg(target_os = "linux")]
+pub(super) use linux::Confinement;
+
+#[cfg(windows)]
+pub(super) use windows::Confinement;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(super) use unsupported::Confinement;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs,
+        path::PathBuf,
+        process::Child,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::{confinement_error, ResourceLimits, ResourceUsage};
+    use crate::Error;
+
+    /// The period CPU quotas are measured over, in microseconds
+    const CPU_PERIOD: u64 = 100_000;
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    /// A cgroup holding a single child - removed on drop
+    pub struct Confinement {
+        path: PathBuf,
+    }
+
+    impl Confinement {
+        pub fn new(limits: &ResourceLimits) -> Result<Self, Error> {
+            let parent = limits.cgroup_parent.as_ref().ok_or_else(|| {
+                Error::Runtime(
+                    "Resource limits for sandbox processes require `cgroup_parent` on Linux"
+                        .to_string(),
+                )
+            })?;
+
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let path = parent.join(format!("rustyscript-{}-{id}", std::process::id()));
+            fs::create_dir(&path).map_err(confinement_error)?;
+            let confinement = Self { path };
+
+            if let Some(memory) = limits.memory {
+                confinement.write("memory.max", &memory.to_string())?;
+                confinement.write("memory.oom.group", "1")?;
+
+                // Not every kernel has swap accounting
+                confinement.write("memory.swap.max", "0").ok();
+            }
+
+            if let Some(cpu) = limits.cpu {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let quota = ((cpu * CPU_PERIOD as f64) as u64).max(1000);
+                confinement.write("cpu.max", &format!("{quota} {CPU_PERIOD}"))?;
+            }
+
+            Ok(confinement)
+        }
+
+        /// Moves the child into the cgroup
+        pub fn attach(&self, child: &Child) -> Result<(), Error> {
+            self.write("cgroup.procs", &child.id().to_string())
+        }
+
+        pub fn usage(&self) -> ResourceUsage {
+            let cpu_usec = self
+                .read("cpu.stat")
+                .and_then(|stat| stat_value(&stat, "usage_usec"))
+                .unwrap_or_default();
+            let oom_kills = self
+                .read("memory.events")
+                .and_then(|events| stat_value(&events, "oom_kill"))
+                .unwrap_or_default();
+
+            ResourceUsage {
+                memory: self
+                    .read("memory.current")
+                    .and_then(|v| v.trim().parse().ok()),
+                peak_memory: self.read("memory.peak").and_then(|v| v.trim().parse().ok()),
+                cpu_time: Duration::from_micros(cpu_usec),
+                memory_exceeded: oom_kills > 0,
+            }
+        }
+
+        fn read(&self, file: &str) -> Option<String> {
+            fs::read_to_string(self.path.join(file)).ok()
+        }
+
+        fn write(&self, file: &str, value: &str) -> Result<(), Error> {
+            fs::write(self.path.join(file), value).map_err(confinement_error)
+        }
+    }
+
+    impl Drop for Confinement {
+        fn drop(&mut self) {
+            fs::remove_dir(&self.path).ok();
+        }
+    }
+
+    /// Reads `key value` lines, as found in `cpu.stat` and `memory.events`
+    fn stat_value(stat: &str, key: &str) -> Option<u64> {
+        stat.lines().find_map(|line| {
+            let (k, v) = line.split_once(' ')?;
+            (k == key).then(|| v.trim().parse().ok()).flatten()
+        })
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{os::windows::io::AsRawHandle, process::Child, ptr, time::Duration};
+
+    use winapi::{
+        shared::minwindef::{DWORD, FALSE, LPVOID},
+        um::{
+            handleapi::CloseHandle,
+            jobapi2::{
+                AssignProcessToJobObject, CreateJobObjectW, QueryInformationJobObject,
+                SetInformationJobObject,
+            },
+            winnt::{
+                JobObjectBasicAccountingInformation, JobObjectCpuRateControlInformation,
+                JobObjectExtendedLimitInformation, HANDLE, JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
+                JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+                JOB_OBJECT_LIMIT_JOB_MEMORY, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            },
+        },
+    };
+
+    use super::{confinement_error, ResourceLimits, ResourceUsage};
+    use crate::Error;
+
+    /// A Job Object holding a single child - closing it kills the child
+    pub struct Confinement {
+        job: HANDLE,
+    }
+
+    // The handle is only used through the kernel, which synchronizes access to it
+    unsafe impl Send for Confinement {}
+
+    impl Confinement {
+        pub fn new(limits: &ResourceLimits) -> Result<Self, Error> {
+            // SAFETY: winapi call, with no security attributes and an unnamed job
+            let job = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+            if job.is_null() {
+                return Err(confinement_error(std::io::Error::last_os_error()));
+            }
+            let confinement = Self { job };
+
+            // SAFETY: plain-old-data struct, for which all zeroes is valid
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            if let Some(memory) = limits.memory {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+                info.JobMemoryLimit = usize::try_from(memory).unwrap_or(usize::MAX);
+            }
+            confinement.set(JobObjectExtendedLimitInformation, &mut info)?;
+
+            if let Some(cpu) = limits.cpu {
+                let cores = std::thread::available_parallelism().map_or(1, usize::from);
+
+                // A share of the whole machine, in hundredths of a percent
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let rate = ((cpu / cores as f64) * 10_000.0).clamp(1.0, 10_000.0) as DWORD;
+
+                // SAFETY: plain-old-data struct, for which all zeroes is valid
+                let mut info: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION =
+                    unsafe { std::mem::zeroed() };
+                info.ControlFlags =
+                    JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+                // SAFETY: `CpuRate` is the member selected by the flags above
+                unsafe { *info.u.CpuRate_mut() = rate };
+                confinement.set(JobObjectCpuRateControlInformation, &mut info)?;
+            }
+
+            Ok(confinement)
+        }
+
+        /// Moves the child into the job
+        pub fn attach(&self, child: &Child) -> Result<(), Error> {
+            // SAFETY: both handles are valid for the duration of the call
+            let ok = unsafe { AssignProcessToJobObject(self.job, child.as_raw_handle().cast()) };
+            if ok == FALSE {
+                return Err(confinement_error(std::io::Error::last_os_error()));
+            }
+            Ok(())
+        }
+
+        pub fn usage(&self) -> ResourceUsage {
+            let accounting: Option<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION> =
+                self.query(JobObjectBasicAccountingInformation);
+            let limits: Option<JOBOBJECT_EXTENDED_LIMIT_INFORMATION> =
+                self.query(JobObjectExtendedLimitInformation);
+
+            // Reported in 100ns units
+            let cpu_time = accounting.map_or(Duration::ZERO, |info| {
+                // SAFETY: LARGE_INTEGER is always readable as a 64-bit integer
+                let ticks =
+                    unsafe { *info.TotalUserTime.QuadPart() + *info.TotalKernelTime.QuadPart() };
+                Duration::from_nanos(u64::try_from(ticks).unwrap_or_default() * 100)
+            });
+
+            ResourceUsage {
+                memory: None,
+                peak_memory: limits.map(|info| info.PeakJobMemoryUsed as u64),
+                cpu_time,
+                memory_exceeded: false,
+            }
+        }
+
+        fn set<T>(&self, class: DWORD, info: &mut T) -> Result<(), Error> {
+            // SAFETY: `info` is the struct matching `class`, and outlives the call
+            let ok = unsafe {
+                SetInformationJobObject(
+                    self.job,
+                    class,
+                    ptr::from_mut(info).cast::<std::ffi::c_void>() as LPVOID,
+                    std::mem::size_of::<T>() as DWORD,
+                )
+            };
+            if ok == FALSE {
+                return Err(confinement_error(std::io::Error::last_os_error()));
+            }
+            Ok(())
+        }
+
+        fn query<T>(&self, class: DWORD) -> Option<T> {
+            // SAFETY: plain-old-data struct matching `class`, for which all zeroes is valid
+            let mut info: T = unsafe { std::mem::zeroed() };
+            let ok = unsafe {
+                QueryInformationJobObject(
+                    self.job,
+                    class,
+                    ptr::from_mut(&mut info).cast::<std::ffi::c_void>() as LPVOID,
+                    std::mem::size_of::<T>() as DWORD,
+                    ptr::null_mut(),
+                )
+            };
+            (ok != FALSE).then_some(info)
+        }
+    }
+
+    impl Drop for Confinement {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened by `new`, and is closed only here
+            unsafe { CloseHandle(self.job) };
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod unsupported {
+    use std::process::Child;
+
+    use super::{ResourceLimits, ResourceUsage};
+    use crate::Error;
+
+    pub struct Confinement;
+
+    impl Confinement {
+        pub fn new(_limits: &ResourceLimits) -> Result<Self, Error> {
+            Err(Error::Runtime(
+                "Resource limits for sandbox processes are not supported on this platform"
+                    .to_string(),
+            ))
+        }
+
+        pub fn attach(&self, _child: &Child) -> Result<(), Error> {
+            Ok(())
+        }
+
+        pub fn usage(&self) -> ResourceUsage {
+            ResourceUsage::default()
+        }
+    }
+}
+
+fn confinement_error(error: std::io::Error) -> Error {
+    Error::Runtime(format!("Could not confine the sandbox process: {error}"))
+}
+
+/// Describes how a confined child exited, noting if it hit its memory cap
+pub(super) fn describe_exit(status: String, usage: Option<&ResourceUsage>) -> String {
+    match usage {
+        Some(usage) if usage.memory_exceeded => format!("{status} (memory limit exceeded)"),
+        _ => status,
+    }
+}
@@ -0,0 +1,304 @@
+//! Events pushed from scripts to the host with `rustyscript.emit(event, payload)`
+//!
+//! The host subscribes with [`crate::Runtime::subscribe`], and receives each payload as a
+//! `serde_json::Value` - as soon as it is emitted, even while the call that emitted it is still
+//! running, so long computations can report on their progress rather than only at the end
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use deno_core::serde_json;
+
+/// What a subscription does with an event once its buffer is full - see [`SubscribeOptions`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Discard the oldest buffered event to make room - a slow reader sees the latest events
+    #[default]
+    DropOldest,
+
+    /// Discard the new event - a slow reader sees the earliest events
+    DropNewest,
+}
+
+/// Buffering for a subscription - see [`crate::Runtime::subscribe_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscribeOptions {
+    /// The most events held for the subscriber at once
+    pub capacity: usize,
+
+    /// What to do with events once `capacity` is reached
+    pub overflow: Overflow,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    events: VecDeque<serde_json::Value>,
+    dropped: u64,
+    closed: bool,
+}
+
+struct Channel {
+    buffer: Mutex<Buffer>,
+    ready: Condvar,
+    options: SubscribeOptions,
+}
+
+impl Channel {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buffer> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn push(&self, payload: serde_json::Value) {
+        let mut buffer = self.lock();
+        if buffer.events.len() >= self.options.capacity.max(1) {
+            buffer.dropped += 1;
+            match self.options.overflow {
+                Overflow::DropNewest => return,
+                Overflow::DropOldest => {
+                    buffer.events.pop_front();
+                }
+            }
+        }
+        buffer.events.push_back(payload);
+        self.ready.notify_all();
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.ready.notify_all();
+    }
+}
+
+/// Receives the payloads a script emits for one event - see [`crate::Runtime::subscribe`]
+///
+/// Can be moved to another thread, to read events while the runtime is busy with a call.
+/// Dropping the receiver ends the subscription
+pub struct EventReceiver {
+    event: String,
+    channel: Arc<Channel>,
+}
+
+impl EventReceiver {
+    /// The event this receiver is subscribed to
+    #[must_use]
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    /// Returns the next buffered payload, without waiting
+    #[must_use]
+    pub fn try_recv(&self) -> Option<serde_json::Value> {
+        self.channel.lock().events.pop_front()
+    }
+
+    /// Waits for the next payload
+    ///
+    /// Returns `None` once the runtime has been dropped and every buffered payload has been read
+    #[must_use]
+    pub fn recv(&self) -> Option<serde_json::Value> {
+        let mut buffer = self.channel.lock();
+        loop {
+            if let Some(payload) = buffer.events.pop_front() {
+                return Some(payload);
+            }
+            if buffer.closed {
+                return None;
+            }
+            buffer = self
+                .channel
+                .ready
+                .wait(buffer)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+    }
+
+    /// Waits up to `timeout` for the next payload
+    ///
+    /// Returns `None` if none arrived in time, or if the runtime has been dropped
+    #[must_use]
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<serde_json::Value> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = self.channel.lock();
+        loop {
+            if let Some(payload) = buffer.events.pop_front() {
+                return Some(payload);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if buffer.closed || remaining.is_zero() {
+                return None;
+            }
+            buffer = self
+                .channel
+                .ready
+                .wait_timeout(buffer, remaining)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Returns every buffered payload, without waiting
+    #[must_use]
+    pub fn drain(&self) -> Vec<serde_json::Value> {
+        self.channel.lock().events.drain(..).collect()
+    }
+
+    /// The number of payloads buffered
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.channel.lock().events.len()
+    }
+
+    /// Returns true if no payloads are buffered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of payloads discarded because the buffer was full
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.channel.lock().dropped
+    }
+
+    /// Returns true if the runtime has been dropped - buffered payloads can still be read
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.channel.lock().closed
+    }
+}
+
+/// Subscriptions to script events, by event name
+#[derive(Default)]
+pub(crate) struct EventSubscribers(HashMap<String, Vec<Weak<Channel>>>);
+
+impl EventSubscribers {
+    pub fn subscribe(&mut self, event: &str, options: SubscribeOptions) -> EventReceiver {
+        let channel = Arc::new(Channel {
+            buffer: Mutex::new(Buffer::default()),
+            ready: Condvar::new(),
+            options,
+        });
+        self.0
+            .entry(event.to_string())
+            .or_default()
+            .push(Arc::downgrade(&channel));
+
+        EventReceiver {
+            event: event.to_string(),
+            channel,
+        }
+    }
+
+    /// Delivers a payload to every live subscriber, returning how many there were
+    pub fn emit(&mut self, event: &str, payload: &serde_json::Value) -> usize {
+        let Some(subscribers) = self.0.get_mut(event) else {
+            return 0;
+        };
+
+        subscribers.retain(|subscriber| subscriber.strong_count() > 0);
+        for channel in subscribers.iter().filter_map(Weak::upgrade) {
+            channel.push(payload.clone());
+        }
+
+        let delivered = subscribers.len();
+        if delivered == 0 {
+            self.0.remove(event);
+        }
+        delivered
+    }
+
+    /// Returns true if anything is subscribed to the event
+    pub fn has_subscribers(&self, event: &str) -> bool {
+        self.0
+            .get(event)
+            .is_some_and(|subscribers| subscribers.iter().any(|s| s.strong_count() > 0))
+    }
+}
+
+impl Drop for EventSubscribers {
+    fn drop(&mut self) {
+        for channel in self.0.values().flatten().filter_map(Weak::upgrade) {
+            channel.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_script_events() {
+        let module = Module::new(
+            "test.js",
+            "
+            export function work(steps) {
+                for (let i = 1; i <= steps; i++) {
+                    rustyscript.emit('progress', { step: i, of: steps });
+                }
+                return rustyscript.emit('done', null);
+            }
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let progress = runtime.subscribe("progress").unwrap();
+        let latest = runtime
+            .subscribe_with(
+                "progress",
+                SubscribeOptions {
+                    capacity: 2,
+                    overflow: Overflow::DropOldest,
+                },
+            )
+            .unwrap();
+
+        // Nothing is subscribed to `done`
+        let delivered: usize = runtime
+            .call_function(Some(&handle), "work", json_args!(3))
+            .unwrap();
+        assert_eq!(delivered, 0);
+
+        let steps: Vec<_> = progress
+            .drain()
+            .into_iter()
+            .map(|p| p["step"].clone())
+            .collect();
+        assert_eq!(steps, [1, 2, 3]);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest.dropped(), 1);
+        assert_eq!(latest.try_recv().unwrap()["step"], 2);
+
+        // Dropped receivers are unsubscribed, and receivers are closed with the runtime
+        drop(latest);
+        let thread = std::thread::spawn(move || {
+            let first = progress.recv();
+            while progress.recv().is_some() {}
+            (first, progress.is_closed())
+        });
+        runtime
+            .call_function::<usize>(Some(&handle), "work", json_args!(1))
+            .unwrap();
+        drop(runtime);
+
+        let (first, closed) = thread.join().unwrap();
+        assert_eq!(first.unwrap()["of"], 1);
+        assert!(closed);
+    }
+}
@@ -0,0 +1,625 @@
+//! Agreed JS representations for common Rust types that have no direct JS equivalent
+//!
+//! Each type has a wrapper, which can be passed in `json_args!` or requested as a return type, and
+//! a module of the same name for use with `#[serde(with = "...")]` on struct fields:
+//!
+//! | Rust type                       | Wrapper              | Passed to JS as                      | Accepted from JS                          |
+//! |---------------------------------|----------------------|--------------------------------------|-------------------------------------------|
+//! | `std::time::Duration`           | [`JsDuration`]       | milliseconds                         | milliseconds                              |
+//! | `std::time::SystemTime`         | [`JsSystemTime`]     | ISO-8601 string, in UTC              | ISO-8601 string, or epoch milliseconds    |
+//! | `chrono::DateTime<Utc>`         | `JsDateTime`         | ISO-8601 string, in UTC              | ISO-8601 string, or epoch milliseconds    |
+//! | `time::OffsetDateTime`          | `JsOffsetDateTime`   | ISO-8601 string, with its offset     | ISO-8601 string, or epoch milliseconds    |
+//! | `uuid::Uuid`                    | `JsUuid`             | lowercase hyphenated string          | any string `Uuid` can parse               |
+//! | `rust_decimal::Decimal`         | `JsDecimal`          | decimal string, to stay exact        | decimal string, or number                 |
+//!
+//! The last four require the `chrono`, `time`, `uuid` and `decimal` features respectively
+//!
+//! Scripts get matching helpers under `rustyscript.types`:
+//! - `date(value)` - a `Date` from a date-time passed by the host
+//! - `toHost(value)` - a copy of the value with any `Date`s, however deeply nested, replaced by
+//!   ISO-8601 strings, ready to be returned to the host
+//! - `isUuid(value)` - whether the value is a UUID string
+//!
+//! ```rust
+//! use std::time::{Duration, SystemTime};
+//!
+//! use rustyscript::{
+//!     json_args,
+//!     serde_adapters::{JsDuration, JsSystemTime},
+//!     Module, Runtime,
+//! };
+//!
+//! # fn main() -> Result<(), rustyscript::Error> {
+//! let module = Module::new(
+//!     "test.js",
+//!     "
+//!     export const deadline = (start, timeout) => {
+//!         const date = rustyscript.types.date(start);
+//!         return new Date(date.getTime() + timeout);
+//!     };
+//!     export const later = (start, timeout) => rustyscript.types.toHost(deadline(start, timeout));
+//!     ",
+//! );
+//! let mut runtime = Runtime::new(Default::default())?;
+//! let module = runtime.load_module(&module)?;
+//!
+//! let start = SystemTime::UNIX_EPOCH;
+//! let timeout = Duration::from_secs(90);
+//! let deadline: JsSystemTime = runtime.call_function(
+//!     Some(&module),
+//!     "later",
+//!     json_args!(JsSystemTime(start), JsDuration(timeout)),
+//! )?;
+//! assert_eq!(deadline.0, start + timeout);
+//! # Ok(())
+//! # }
+//! ```
+use std::time::{Duration, SystemTime};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Generates a `#[serde(with = "...")]` module delegating to a wrapper
+macro_rules! with_module {
+    ($module:ident, $ty:ty, $wrapper:ident) => {
+        #[doc = concat!("Use with `#[serde(with = \"rustyscript::serde_adapters::", stringify!($module), "\")]` - see [`", stringify!($wrapper), "`](crate::serde_adapters::", stringify!($wrapper), ")")]
+        pub mod $module {
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            /// Serializes the value in its agreed JS representation
+            ///
+            /// # Errors
+            /// Will return an error if the value cannot be represented
+            pub fn serialize<S: Serializer>(value: &$ty, serializer: S) -> Result<S::Ok, S::Error> {
+                super::$wrapper(*value).serialize(serializer)
+            }
+
+            /// Deserializes the value from any of its accepted JS representations
+            ///
+            /// # Errors
+            /// Will return an error if the value is not in an accepted representation
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<$ty, D::Error> {
+                super::$wrapper::deserialize(deserializer).map(|value| value.0)
+            }
+        }
+    };
+}
+
+/// A [`Duration`], passed to and from JS as a number of milliseconds
+///
+/// Matches the unit of `setTimeout` and `Date` arithmetic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct JsDuration(pub Duration);
+
+impl Serialize for JsDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.0.as_secs_f64() * 1000.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let millis = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(millis / 1000.0)
+            .map(Self)
+            .map_err(|_| de::Error::custom(format!("invalid duration: {millis}ms")))
+    }
+}
+
+impl From<Duration> for JsDuration {
+    fn from(value: Duration) -> Self {
+        Self(value)
+    }
+}
+
+with_module!(duration, std::time::Duration, JsDuration);
+
+/// A [`SystemTime`], passed to JS as an ISO-8601 string in UTC (as `Date.prototype.toISOString` gives)
+///
+/// Accepted back as an ISO-8601 string, or as milliseconds since the epoch (as `Date.now` gives)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JsSystemTime(pub SystemTime);
+
+impl Serialize for JsSystemTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (seconds, nanos) = match self.0.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => (i128::from(since.as_secs()), since.subsec_nanos()),
+            Err(e) => {
+                let before = e.duration();
+                let nanos = before.subsec_nanos();
+                let seconds = -i128::from(before.as_secs());
+                if nanos == 0 {
+                    (seconds, 0)
+                } else {
+                    (seconds - 1, 1_000_000_000 - nanos)
+                }
+            }
+        };
+        let seconds = i64::try_from(seconds).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&format_iso(seconds, nanos))
+    }
+}
+
+impl<'de> Deserialize<'de> for JsSystemTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let time = match deserializer.deserialize_any(TimeVisitor)? {
+            TimeRepr::Millis(millis) => {
+                let offset = Duration::try_from_secs_f64(millis.abs() / 1000.0)
+                    .map_err(|_| de::Error::custom(format!("invalid timestamp: {millis}")))?;
+                if millis < 0.0 {
+                    SystemTime::UNIX_EPOCH.checked_sub(offset)
+                } else {
+                    SystemTime::UNIX_EPOCH.checked_add(offset)
+                }
+            }
+            TimeRepr::Iso(value) => {
+                let (seconds, nanos) = parse_iso(&value).ok_or_else(|| {
+                    de::Error::custom(format!("invalid ISO-8601 date-time: `{value}`"))
+                })?;
+                let offset = Duration::new(seconds.unsigned_abs(), 0);
+                let time = if seconds < 0 {
+                    SystemTime::UNIX_EPOCH.checked_sub(offset)
+                } else {
+                    SystemTime::UNIX_EPOCH.checked_add(offset)
+                };
+                time.and_then(|time| time.checked_add(Duration::from_nanos(u64::from(nanos))))
+            }
+        };
+
+        time.map(Self)
+            .ok_or_else(|| de::Error::custom("date-time is out of range"))
+    }
+}
+
+impl From<SystemTime> for JsSystemTime {
+    fn from(value: SystemTime) -> Self {
+        Self(value)
+    }
+}
+
+with_module!(system_time, std::time::SystemTime, JsSystemTime);
+
+/// A date-time accepted from JS, before conversion to the target type
+enum TimeRepr {
+    Millis(f64),
+    Iso(String),
+}
+
+struct TimeVisitor;
+
+impl de::Visitor<'_> for TimeVisitor {
+    type Value = TimeRepr;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an ISO-8601 string, or milliseconds since the epoch")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(TimeRepr::Iso(v.to_string()))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(TimeRepr::Millis(v as f64))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(TimeRepr::Millis(v as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        if v.is_finite() {
+            Ok(TimeRepr::Millis(v))
+        } else {
+            Err(E::custom("invalid timestamp: `Invalid Date`"))
+        }
+    }
+}
+
+/// Formats seconds and nanoseconds since the epoch the way `Date.prototype.toISOString` does -
+/// with nanosecond precision, if there is any below the millisecond
+fn format_iso(seconds: i64, nanos: u32) -> String {
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (time / 3600, time % 3600 / 60, time % 60);
+
+    let date = if (0..=9999).contains(&year) {
+        format!("{year:04}-{month:02}-{day:02}")
+    } else {
+        format!("{year:+07}-{month:02}-{day:02}")
+    };
+    let fraction = if nanos % 1_000_000 == 0 {
+        format!("{:03}", nanos / 1_000_000)
+    } else {
+        format!("{nanos:09}")
+    };
+    format!("{date}T{hour:02}:{minute:02}:{second:02}.{fraction}Z")
+}
+
+/// Parses an ISO-8601 date-time into seconds and nanoseconds since the epoch
+///
+/// Accepts `YYYY-MM-DD`, optionally followed by `THH:MM`, seconds, a fraction, and an offset -
+/// a missing offset is taken as UTC, rather than local time as `Date.parse` would
+fn parse_iso(value: &str) -> Option<(i64, u32)> {
+    fn number(digits: &str) -> Option<i64> {
+        (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| digits.parse().ok())
+            .flatten()
+    }
+
+    let (date, rest) = value.split_at(value.find(['T', 't', ' ']).unwrap_or(value.len()));
+
+    // Expanded years are written with a sign, `+275760-09-13`
+    let (sign, date) = match date.as_bytes().first() {
+        Some(b'-') => (-1, &date[1..]),
+        Some(b'+') => (1, &date[1..]),
+        _ => (1, date),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year = number(parts.next()?)?.checked_mul(sign)?;
+    let month = number(parts.next()?)?;
+    let day = number(parts.next()?)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day)?.checked_mul(86_400)?;
+
+    let mut nanos = 0;
+    if let Some(rest) = rest.get(1..) {
+        let offset_at = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+        let (time, offset) = rest.split_at(offset_at);
+
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+        let mut parts = time.splitn(3, ':');
+        let hour = number(parts.next()?)?;
+        let minute = number(parts.next()?)?;
+        let second = parts.next().map_or(Some(0), number)?;
+        if hour > 24 || minute > 59 || second > 60 {
+            return None;
+        }
+        seconds = seconds.checked_add(hour * 3600 + minute * 60 + second)?;
+
+        if !fraction.is_empty() {
+            number(fraction)?;
+            let digits = &fraction[..fraction.len().min(9)];
+            nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - u32::try_from(digits.len()).ok()?);
+        }
+
+        match offset {
+            "" | "Z" | "z" => {}
+            _ => {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let offset = offset[1..].replace(':', "");
+                if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                let hours = number(&offset[..2])?;
+                let minutes = number(&offset[2..])?;
+                seconds = seconds.checked_sub(sign * (hours * 3600 + minutes * 60))?;
+            }
+        }
+    }
+
+    Some((seconds, nanos))
+}
+
+/// Days since the epoch to a (year, month, day) date, in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A (year, month, day) date to days since the epoch, in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era.checked_mul(146_097)?
+        .checked_add(day_of_era)?
+        .checked_sub(719_468)
+}
+
+/// Splits milliseconds since the epoch into whole seconds and nanoseconds
+#[cfg(any(feature = "chrono", feature = "time"))]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn split_millis(millis: f64) -> Option<(i64, u32)> {
+    if !millis.is_finite() || millis.abs() > (i64::MAX / 2) as f64 {
+        return None;
+    }
+    let whole = millis.floor() as i64;
+    let nanos = ((millis - millis.floor()) * 1_000_000.0).round() as u32;
+    Some((
+        whole.div_euclid(1000),
+        u32::try_from(whole.rem_euclid(1000)).ok()? * 1_000_000 + nanos.min(999_999),
+    ))
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_adapters {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{split_millis, TimeRepr, TimeVisitor};
+
+    /// A `chrono::DateTime<Utc>`, passed to JS as an ISO-8601 string
+    ///
+    /// Accepted back as an ISO-8601 string with any offset, or as milliseconds since the epoch
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct JsDateTime(pub DateTime<Utc>);
+
+    impl Serialize for JsDateTime {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for JsDateTime {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let time = match deserializer.deserialize_any(TimeVisitor)? {
+                TimeRepr::Millis(millis) => split_millis(millis)
+                    .and_then(|(seconds, nanos)| DateTime::from_timestamp(seconds, nanos)),
+                TimeRepr::Iso(value) => DateTime::parse_from_rfc3339(&value)
+                    .ok()
+                    .map(|time| time.with_timezone(&Utc)),
+            };
+            time.map(Self)
+                .ok_or_else(|| de::Error::custom("invalid or out of range date-time"))
+        }
+    }
+
+    impl From<DateTime<Utc>> for JsDateTime {
+        fn from(value: DateTime<Utc>) -> Self {
+            Self(value)
+        }
+    }
+
+    with_module!(chrono_datetime, chrono::DateTime<chrono::Utc>, JsDateTime);
+}
+
+#[cfg(feature = "chrono")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chrono")))]
+pub use chrono_adapters::{chrono_datetime, JsDateTime};
+
+#[cfg(feature = "time")]
+mod time_adapters {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+    use super::{split_millis, TimeRepr, TimeVisitor};
+
+    /// A `time::OffsetDateTime`, passed to JS as an ISO-8601 string keeping its offset
+    ///
+    /// Accepted back as an ISO-8601 string with any offset, or as milliseconds since the epoch (in UTC)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct JsOffsetDateTime(pub OffsetDateTime);
+
+    impl Serialize for JsOffsetDateTime {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let value = self.0.format(&Rfc3339).map_err(serde::ser::Error::custom)?;
+            serializer.serialize_str(&value)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for JsOffsetDateTime {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let time = match deserializer.deserialize_any(TimeVisitor)? {
+                TimeRepr::Millis(millis) => split_millis(millis).and_then(|(seconds, nanos)| {
+                    let nanos = i128::from(seconds) * 1_000_000_000 + i128::from(nanos);
+                    OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+                }),
+                TimeRepr::Iso(value) => OffsetDateTime::parse(&value, &Rfc3339).ok(),
+            };
+            time.map(Self)
+                .ok_or_else(|| de::Error::custom("invalid or out of range date-time"))
+        }
+    }
+
+    impl From<OffsetDateTime> for JsOffsetDateTime {
+        fn from(value: OffsetDateTime) -> Self {
+            Self(value)
+        }
+    }
+
+    with_module!(offset_datetime, time::OffsetDateTime, JsOffsetDateTime);
+}
+
+#[cfg(feature = "time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "time")))]
+pub use time_adapters::{offset_datetime, JsOffsetDateTime};
+
+#[cfg(feature = "uuid")]
+mod uuid_adapters {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A `uuid::Uuid`, passed to JS as a lowercase hyphenated string (as `crypto.randomUUID` gives)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    pub struct JsUuid(pub ::uuid::Uuid);
+
+    impl Serialize for JsUuid {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut buffer = ::uuid::Uuid::encode_buffer();
+            serializer.serialize_str(self.0.hyphenated().encode_lower(&mut buffer))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for JsUuid {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let value = std::string::String::deserialize(deserializer)?;
+            ::uuid::Uuid::parse_str(&value)
+                .map(Self)
+                .map_err(|e| de::Error::custom(format!("invalid UUID `{value}`: {e}")))
+        }
+    }
+
+    impl From<::uuid::Uuid> for JsUuid {
+        fn from(value: ::uuid::Uuid) -> Self {
+            Self(value)
+        }
+    }
+
+    with_module!(uuid, ::uuid::Uuid, JsUuid);
+}
+
+#[cfg(feature = "uuid")]
+#[cfg_attr(docsrs, doc(cfg(feature = "uuid")))]
+pub use uuid_adapters::{uuid, JsUuid};
+
+#[cfg(feature = "decimal")]
+mod decimal_adapters {
+    use rust_decimal::Decimal;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A `rust_decimal::Decimal`, passed to JS as a string so that no precision is lost
+    ///
+    /// Accepted back as a string, or as a number - which may already have been rounded by JS
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    pub struct JsDecimal(pub Decimal);
+
+    impl Serialize for JsDecimal {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.0.to_string())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for JsDecimal {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(DecimalVisitor).map(Self)
+        }
+    }
+
+    struct DecimalVisitor;
+
+    impl de::Visitor<'_> for DecimalVisitor {
+        type Value = Decimal;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a decimal string, or a number")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.trim()
+                .parse()
+                .map_err(|e| E::custom(format!("invalid decimal `{v}`: {e}")))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            Ok(Decimal::from(v))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(Decimal::from(v))
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+            Decimal::try_from(v).map_err(|e| E::custom(format!("invalid decimal `{v}`: {e}")))
+        }
+    }
+
+    impl From<Decimal> for JsDecimal {
+        fn from(value: Decimal) -> Self {
+            Self(value)
+        }
+    }
+
+    with_module!(decimal, rust_decimal::Decimal, JsDecimal);
+}
+
+#[cfg(feature = "decimal")]
+#[cfg_attr(docsrs, doc(cfg(feature = "decimal")))]
+pub use decimal_adapters::{decimal, JsDecimal};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_iso_dates() {
+        assert_eq!(format_iso(0, 0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_iso(-1, 999_000_000), "1969-12-31T23:59:59.999Z");
+        assert_eq!(format_iso(951_782_400, 1), "2000-02-29T00:00:00.000000001Z");
+        assert_eq!(
+            parse_iso("2000-02-29T00:00:00.000000001Z"),
+            Some((951_782_400, 1))
+        );
+        assert_eq!(parse_iso("1970-01-01T01:00:00+01:00"), Some((0, 0)));
+        assert_eq!(parse_iso("1970-01-02"), Some((86_400, 0)));
+        assert_eq!(parse_iso("not a date"), None);
+        assert_eq!(days_from_civil(1970, 1, 1), Some(0));
+
+        // Malformed or out of range input is rejected, rather than panicking or overflowing
+        assert_eq!(parse_iso("1970-01-01T00:00+1\u{e9}1"), None);
+        assert_eq!(parse_iso("+9223372036854775807-01-01"), None);
+        assert_eq!(parse_iso("-9223372036854775807-01-01"), None);
+        assert_eq!(parse_iso("+25252734927766554-12-31T23:59:59Z"), None);
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Deadline {
+        #[serde(with = "system_time")]
+        at: SystemTime,
+    }
+
+    #[test]
+    fn test_adapters() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const describe = (time, timeout) => `${typeof time} ${typeof timeout}`;
+            export const addTimeout = (time, timeout) => {
+                const date = rustyscript.types.date(time);
+                return rustyscript.types.toHost({ at: new Date(date.getTime() + timeout) });
+            };
+            export const now = () => Date.now();
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let timeout = Duration::from_millis(1500);
+        let args = json_args!(JsSystemTime(time), JsDuration(timeout));
+
+        let description: String = runtime
+            .call_function(Some(&handle), "describe", args)
+            .unwrap();
+        assert_eq!(description, "string number");
+
+        let deadline: Deadline = runtime
+            .call_function(Some(&handle), "addTimeout", args)
+            .unwrap();
+        assert_eq!(deadline.at, time + timeout);
+
+        let now: JsSystemTime = runtime
+            .call_function(Some(&handle), "now", json_args!())
+            .unwrap();
+        assert!(now.0 > time);
+    }
+}
@@ -791,6 +791,48 @@ impl SnapshotBuilder {
         Ok(self)
     }
 
+    /// Tree-shakes the given module down to the declarations `exports` need, then executes it
+    ///
+    /// Keeps embedded libraries from bloating the snapshot with code the host never calls - see
+    /// [`Module::tree_shaken`]
+    ///
+    /// This is a blocking operation, and will run the event loop to completion
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `exports` - The exports to keep
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be parsed, does not export one of `exports`, or if execution
+    /// fails
+    pub fn with_tree_shaken_module(
+        mut self,
+        module: &Module,
+        exports: &[&str],
+    ) -> Result<Self, Error> {
+        self.load_module(&module.tree_shaken(exports)?)?;
+        Ok(self)
+    }
+
+    /// Executes a warm-up script on the runtime, so its effects are captured in the snapshot
+    /// Use it to pre-initialize polyfills, or to build lookup tables once at build time
+    ///
+    /// The script runs as a classic script, then the event loop is run to completion
+    ///
+    /// # Arguments
+    /// * `script` - A `Module` object containing the script's filename and contents.
+    ///
+    /// # Errors
+    /// Will return [`Error::Warmup`] if the script throws, or its pending work fails
+    pub fn with_warmup_script(mut self, script: &Module) -> Result<Self, Error> {
+        self.inner.run_warmup_script(script)?;
+        self.block_on_event_loop(PollEventLoopOptions::default(), None)
+            .map_err(|e| {
+                Error::Warmup(script.filename().to_string_lossy().to_string(), Box::new(e))
+            })?;
+        Ok(self)
+    }
+
     /// Executes a piece of non-ECMAScript-module JavaScript code on the runtime
     /// This code can be used to set up the runtime state before creating the snapshot
     ///
@@ -0,0 +1,92 @@
+//! Breakdown of the time spent starting a runtime and loading its modules
+//!
+//! Enabled with [`crate::RuntimeOptions::trace_startup`], and read back through
+//! [`crate::Runtime::startup_trace`] - useful to see which part of a cold start is worth optimizing
+use std::time::{Duration, Instant};
+
+/// Time spent in each phase of creating a runtime, and of loading each module since
+///
+/// See [`crate::Runtime::startup_trace`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupTrace {
+    /// Initializing the V8 platform - only non-zero for the first runtime in the process, and
+    /// never if the platform was initialized with [`crate::init_platform_with`]
+    pub platform_init: Duration,
+
+    /// Preparing the module loader and the extensions, on the host side
+    pub extension_setup: Duration,
+
+    /// Creating the isolate - deserializing the snapshot if one was used, or otherwise
+    /// initializing each extension and evaluating its sources
+    pub isolate_init: Duration,
+
+    /// Whether the isolate was created from a snapshot
+    pub from_snapshot: bool,
+
+    /// Everything else before the runtime was ready, such as warm-up scripts
+    pub runtime_setup: Duration,
+
+    /// Each module loaded so far, in order
+    pub modules: Vec<ModuleLoadTrace>,
+}
+
+impl StartupTrace {
+    /// The time taken to create the runtime, excluding module loads
+    #[must_use]
+    pub fn runtime_total(&self) -> Duration {
+        self.platform_init + self.extension_setup + self.isolate_init + self.runtime_setup
+    }
+
+    /// The time taken to create the runtime, and to load every module so far
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        let modules: Duration = self.modules.iter().map(ModuleLoadTrace::total).sum();
+        self.runtime_total() + modules
+    }
+}
+
+/// Time spent loading a single module - see [`StartupTrace::modules`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleLoadTrace {
+    /// The module's specifier
+    pub specifier: String,
+
+    /// Transpiling (and minifying, if enabled) the module's own source
+    pub transpile: Duration,
+
+    /// Compiling the module, and resolving, fetching and transpiling its imports
+    pub load: Duration,
+
+    /// Evaluating the module and its imports
+    pub evaluate: Duration,
+}
+
+impl ModuleLoadTrace {
+    /// The time taken to load the module
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.transpile + self.load + self.evaluate
+    }
+}
+
+/// Measures consecutive phases - yields zero for every phase when tracing is disabled
+pub(crate) struct PhaseTimer(Option<Instant>);
+
+impl PhaseTimer {
+    pub fn new(enabled: bool) -> Self {
+        Self(enabled.then(Instant::now))
+    }
+
+    /// The time since the previous phase ended
+    pub fn lap(&mut self) -> Duration {
+        match &mut self.0 {
+            Some(last) => {
+                let now = Instant::now();
+                let elapsed = now - *last;
+                *last = now;
+                elapsed
+            }
+            None => Duration::ZERO,
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! OpenTelemetry instrumentation for the runtime
+//!
+//! Spans and metrics are emitted through the global `opentelemetry` providers.
+//! Installing an exporter (OTLP, stdout, etc) is left to the host application,
+//! so this module only depends on the `opentelemetry` API crate.
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    time::{Instant, SystemTime},
+};
+
+use deno_core::{OpDecl, OpMetricsEvent, OpMetricsFactoryFn, OpMetricsFn};
+use opentelemetry::{
+    global::{self, BoxedSpan, BoxedTracer},
+    metrics::{Counter, Histogram},
+    trace::{Span, Status, Tracer},
+    KeyValue,
+};
+
+use crate::{Error, ModuleHandle};
+
+/// Options for the OpenTelemetry instrumentation of a runtime
+///
+/// Requires the `otel` feature to be enabled
+#[derive(Debug, Clone)]
+pub struct OtelOptions {
+    /// Name of the instrumentation scope used for the tracer and meter
+    pub scope_name: Cow<'static, str>,
+
+    /// Attributes attached to every span and metric emitted by the runtime
+    ///
+    /// Useful for attributing telemetry to a tenant, deployment, etc  
+    /// The runtime's labels (`RuntimeOptions::labels`) are included automatically
+    pub resource_attributes: HashMap<String, String>,
+
+    /// Record the latency of every op call as a histogram
+    pub op_metrics: bool,
+
+    /// Emit a span for each outgoing fetch request (requires the `web` feature)
+    pub fetch_spans: bool,
+
+    /// Emit a log event and increment a counter for each `console.error` / `console.warn` call
+    pub console_errors: bool,
+}
+
+impl Default for OtelOptions {
+    fn default() -> Self {
+        Self {
+            scope_name: Cow::Borrowed("rustyscript"),
+            resource_attributes: HashMap::new(),
+            op_metrics: true,
+            fetch_spans: true,
+            console_errors: true,
+        }
+    }
+}
+
+impl OtelOptions {
+    /// Add a resource attribute that will be attached to all telemetry
+    #[must_use]
+    pub fn with_attribute(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.resource_attributes
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Instruments owned by a single runtime
+pub(crate) struct Telemetry {
+    options: OtelOptions,
+    attributes: Vec<KeyValue>,
+    tracer: BoxedTracer,
+
+    op_duration: Histogram<f64>,
+    op_errors: Counter<u64>,
+    console_errors: Counter<u64>,
+}
+
+impl Telemetry {
+    pub fn new(options: OtelOptions, labels: &HashMap<String, String>) -> Self {
+        let mut attributes: Vec<_> = labels
+            .iter()
+            .filter(|(k, _)| !options.resource_attributes.contains_key(*k))
+            .chain(options.resource_attributes.iter())
+            .map(|(k, v)| KeyValue::new(k.clone(), v.clone()))
+            .collect();
+        attributes.sort_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+
+        let tracer = global::tracer(options.scope_name.clone());
+        let meter = global::meter_with_scope(
+            opentelemetry::InstrumentationScope::builder(options.scope_name.clone()).build(),
+        );
+
+        let op_duration = meter
+            .f64_histogram("rustyscript.op.duration")
+            .with_unit("ms")
+            .with_description("Time taken for an op to complete")
+            .build();
+        let op_errors = meter
+            .u64_counter("rustyscript.op.errors")
+            .with_description("Number of ops that completed with an error")
+            .build();
+        let console_errors = meter
+            .u64_counter("rustyscript.console.errors")
+            .with_description("Number of errors and warnings written to the console")
+            .build();
+
+        Self {
+            options,
+            attributes,
+            tracer,
+            op_duration,
+            op_errors,
+            console_errors,
+        }
+    }
+
+    /// Attributes shared by all telemetry from this runtime, plus the given extras
+    fn attributes_with(&self, extra: impl IntoIterator<Item = KeyValue>) -> Vec<KeyValue> {
+        let mut attributes = self.attributes.clone();
+        attributes.extend(extra);
+        attributes
+    }
+
+    /// Start a span covering a host call into the runtime
+    pub fn call_span(
+        &self,
+        kind: &'static str,
+        name: &str,
+        module_context: Option<&ModuleHandle>,
+    ) -> CallSpan {
+        let mut extra = vec![KeyValue::new("rustyscript.function", name.to_string())];
+        if let Some(module) = module_context {
+            extra.push(KeyValue::new(
+                "rustyscript.module",
+                module.module().filename().to_string_lossy().to_string(),
+            ));
+        }
+
+        let mut span = self.tracer.start(kind);
+        span.set_attributes(self.attributes_with(extra));
+        CallSpan(span)
+    }
+
+    /// Start a span covering the loading of a module
+    pub fn module_span(&self, specifier: &str) -> CallSpan {
+        let mut span = self.tracer.start("load_module");
+        span.set_attributes(
+            self.attributes_with([KeyValue::new("rustyscript.module", specifier.to_string())]),
+        );
+        CallSpan(span)
+    }
+
+    /// Record a console error or warning
+    pub fn console_error(&self, level: &str, message: &str) {
+        if !self.options.console_errors {
+            return;
+        }
+
+        let attributes = self.attributes_with([KeyValue::new("level", level.to_string())]);
+        self.console_errors.add(1, &attributes);
+
+        let mut span = self.tracer.start("console");
+        span.set_attributes(attributes);
+        span.add_event(
+            "console",
+            vec![KeyValue::new("message", message.to_string())],
+        );
+        span.end();
+    }
+
+    /// Builds the hook used by `deno_core` to report op activity
+    pub fn op_metrics_factory(self: &Rc<Self>) -> Option<OpMetricsFactoryFn> {
+        if !self.options.op_metrics && !self.options.fetch_spans {
+            return None;
+        }
+
+        let telemetry = self.clone();
+        Some(Box::new(move |_, _, decl: &OpDecl| {
+            let is_fetch = decl.name == "op_fetch_send";
+            if !telemetry.options.op_metrics && !(is_fetch && telemetry.options.fetch_spans) {
+                return None;
+            }
+
+            let metrics = OpMetrics {
+                telemetry: telemetry.clone(),
+                name: decl.name,
+                is_fetch,
+                pending: RefCell::default(),
+            };
+            let f: OpMetricsFn = Rc::new(move |_, event, _| metrics.on_event(event));
+            Some(f)
+        }))
+    }
+}
+
+/// Tracks in-flight calls to a single op
+///
+/// Op events carry no call identifier, so sync calls are matched last-in-first-out,
+/// and async calls first-in-first-out. Durations for interleaved async calls of the
+/// same op are therefore approximate.
+struct OpMetrics {
+    telemetry: Rc<Telemetry>,
+    name: &'static str,
+    is_fetch: bool,
+    pending: RefCell<VecDeque<(Instant, SystemTime)>>,
+}
+
+impl OpMetrics {
+    fn on_event(&self, event: OpMetricsEvent) {
+        let start = match event {
+            OpMetricsEvent::Dispatched => {
+                self.pending
+                    .borrow_mut()
+                    .push_back((Instant::now(), SystemTime::now()));
+                return;
+            }
+            OpMetricsEvent::Completed | OpMetricsEvent::Error => {
+                self.pending.borrow_mut().pop_back()
+            }
+            OpMetricsEvent::CompletedAsync | OpMetricsEvent::ErrorAsync => {
+                self.pending.borrow_mut().pop_front()
+            }
+        };
+        let Some((started, started_at)) = start else {
+            return;
+        };
+
+        let failed = matches!(event, OpMetricsEvent::Error | OpMetricsEvent::ErrorAsync);
+        let attributes = self
+            .telemetry
+            .attributes_with([KeyValue::new("rustyscript.op", self.name)]);
+
+        if self.telemetry.options.op_metrics {
+            let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+            self.telemetry.op_duration.record(elapsed, &attributes);
+            if failed {
+                self.telemetry.op_errors.add(1, &attributes);
+            }
+        }
+
+        if self.is_fetch && self.telemetry.options.fetch_spans {
+            let mut span = self
+                .telemetry
+                .tracer
+                .span_builder("fetch")
+                .with_start_time(started_at)
+                .with_attributes(attributes)
+                .start(&self.telemetry.tracer);
+            if failed {
+                span.set_status(Status::error("fetch failed"));
+            }
+            span.end();
+        }
+    }
+}
+
+/// A span covering a call into the runtime
+pub(crate) struct CallSpan(BoxedSpan);
+impl CallSpan {
+    /// End the span, recording the error if the call failed
+    pub fn finish<T>(mut self, result: &Result<T, Error>) {
+        if let Err(e) = result {
+            self.0.set_status(Status::error(e.to_string()));
+        }
+        self.0.end();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Module, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_instrumented_runtime() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            otel: Some(OtelOptions::default().with_attribute("tenant.id", "test")),
+            ..Default::default()
+        })
+        .expect("Could not create runtime");
+
+        let module = Module::new(
+            "test.js",
+            "
+            export function f() { console.error('oops'); return 2; }
+            export function g() { throw new Error('boom'); }
+        ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let value: usize = runtime
+            .call_function(Some(&module), "f", json_args!())
+            .expect("Could not call function");
+        assert_eq!(value, 2);
+
+        runtime
+            .call_function::<usize>(Some(&module), "g", json_args!())
+            .expect_err("Error was not propagated");
+    }
+}
@@ -0,0 +1,405 @@
+//! Fair sharing of a single thread between several runtimes
+//!
+//! A [`TimeSlicer`] owns a set of runtimes and drives their event loops in turn, one tick each.
+//! A runtime whose tick runs past the configured slice accrues a debt, and sits out later turns
+//! until it has been repaid - so a tenant doing heavy computation gets proportionally fewer turns,
+//! rather than starving the others.
+//!
+//! V8 cannot suspend a running script part-way and resume it later - a single tick runs until the
+//! script yields to the event loop (`await` on a timer, an async op, etc). Scripts which never yield
+//! can only be stopped: with [`TimeSliceOptions::max_overrun`] set, a tick running that far past its
+//! slice is interrupted, and fails with [`Error::Timeout`]. The runtime itself remains usable
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use deno_core::{v8, PollEventLoopOptions};
+
+use crate::{Error, Runtime};
+
+/// Options for a [`TimeSlicer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSliceOptions {
+    /// The time each runtime may run per turn before it starts accruing a debt
+    pub slice: Duration,
+
+    /// How far past its slice a tick may run before it is interrupted
+    ///
+    /// If `None`, ticks are never interrupted
+    pub max_overrun: Option<Duration>,
+}
+
+impl Default for TimeSliceOptions {
+    fn default() -> Self {
+        Self {
+            slice: Duration::from_millis(10),
+            max_overrun: None,
+        }
+    }
+}
+
+/// Identifies a runtime added to a [`TimeSlicer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SliceId(usize);
+
+struct Entry {
+    runtime: Runtime,
+    debt: Duration,
+    pending: bool,
+}
+
+/// Drives the event loops of several runtimes on one thread, giving each a fair share of time
+///
+/// ```rust
+/// use rustyscript::{Runtime, TimeSliceOptions, TimeSlicer};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut slicer = TimeSlicer::new(TimeSliceOptions {
+///     slice: Duration::from_millis(5),
+///     max_overrun: Some(Duration::from_millis(100)),
+/// });
+///
+/// let heavy = slicer.add(Runtime::new(Default::default())?);
+/// let light = slicer.add(Runtime::new(Default::default())?);
+/// slicer.get_mut(heavy).unwrap().eval::<()>("setTimeout(() => { while (true) {} })")?;
+/// slicer.get_mut(light).unwrap().eval::<()>("setTimeout(() => globalThis.done = true)")?;
+///
+/// let errors = slicer.run_until_idle();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].0, heavy);
+///
+/// let done: bool = slicer.get_mut(light).unwrap().eval("globalThis.done")?;
+/// assert!(done);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TimeSlicer {
+    options: TimeSliceOptions,
+    entries: Vec<Option<Entry>>,
+    timer: Option<SliceTimer>,
+}
+
+impl TimeSlicer {
+    /// Create a new, empty scheduler
+    #[must_use]
+    pub fn new(options: TimeSliceOptions) -> Self {
+        Self {
+            timer: options.max_overrun.map(|_| SliceTimer::new()),
+            options,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the scheduler's options
+    #[must_use]
+    pub fn options(&self) -> &TimeSliceOptions {
+        &self.options
+    }
+
+    /// Add a runtime to the scheduler
+    pub fn add(&mut self, runtime: Runtime) -> SliceId {
+        self.entries.push(Some(Entry {
+            runtime,
+            debt: Duration::ZERO,
+            pending: true,
+        }));
+        SliceId(self.entries.len() - 1)
+    }
+
+    /// Remove a runtime from the scheduler, returning it
+    pub fn remove(&mut self, id: SliceId) -> Option<Runtime> {
+        self.entries
+            .get_mut(id.0)?
+            .take()
+            .map(|entry| entry.runtime)
+    }
+
+    /// Access one of the scheduler's runtimes, to load modules or call functions
+    ///
+    /// Work started this way is driven by the scheduler on subsequent turns
+    pub fn get_mut(&mut self, id: SliceId) -> Option<&mut Runtime> {
+        let entry = self.entries.get_mut(id.0)?.as_mut()?;
+        entry.pending = true;
+        Some(&mut entry.runtime)
+    }
+
+    /// Returns the number of runtimes in the scheduler
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    /// Returns true if the scheduler has no runtimes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Give each runtime with pending work one turn
+    ///
+    /// Returns true if any runtime still has pending work, along with any errors raised by the runtimes
+    pub fn run_round(&mut self) -> (bool, Vec<(SliceId, Error)>) {
+        let mut errors = Vec::new();
+        let mut pending = false;
+
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            let Some(entry) = entry.as_mut().filter(|entry| entry.pending) else {
+                continue;
+            };
+
+            // Repay the debt from an earlier overrun, instead of running
+            if entry.debt >= self.options.slice {
+                entry.debt -= self.options.slice;
+                pending = true;
+                continue;
+            }
+
+            let started = Instant::now();
+            let overrun = self.timer.as_ref().zip(self.options.max_overrun);
+            let fired = overrun.map(|(timer, max_overrun)| {
+                let isolate = entry
+                    .runtime
+                    .deno_runtime()
+                    .v8_isolate()
+                    .thread_safe_handle();
+                timer.arm(started + self.options.slice + max_overrun, isolate)
+            });
+
+            let result = entry
+                .runtime
+                .advance_event_loop(PollEventLoopOptions::default());
+
+            if let Some(timer) = &self.timer {
+                timer.disarm();
+            }
+            let elapsed = started.elapsed();
+            entry.debt += elapsed.saturating_sub(self.options.slice);
+
+            // The timer can fire just as the script yields, so the isolate is reset even if the
+            // round succeeded - otherwise its next round would be terminated as soon as it starts
+            let interrupted = fired.is_some_and(|fired| fired.load(Ordering::SeqCst));
+            if interrupted {
+                entry
+                    .runtime
+                    .deno_runtime()
+                    .v8_isolate()
+                    .cancel_terminate_execution();
+            }
+
+            match result {
+                Ok(has_work) => entry.pending = has_work,
+                Err(e) => {
+                    let e = if interrupted {
+                        entry.debt = Duration::ZERO;
+                        Error::Timeout(format!(
+                            "Script ran for {elapsed:?} without yielding, exceeding its time slice"
+                        ))
+                    } else {
+                        e
+                    };
+
+                    entry.pending = false;
+                    errors.push((SliceId(index), e));
+                }
+            }
+
+            pending |= entry.pending;
+        }
+
+        (pending, errors)
+    }
+
+    /// Run rounds until no runtime has pending work, returning the errors raised along the way
+    pub fn run_until_idle(&mut self) -> Vec<(SliceId, Error)> {
+        let mut errors = Vec::new();
+        loop {
+            let (pending, round_errors) = self.run_round();
+            errors.extend(round_errors);
+            if !pending {
+                return errors;
+            }
+
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// A tick that will be interrupted at its deadline
+struct Armed {
+    deadline: Instant,
+    isolate: v8::IsolateHandle,
+    fired: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct TimerState {
+    armed: Option<Armed>,
+    shutdown: bool,
+}
+
+/// Background thread interrupting ticks that overrun their slice
+struct SliceTimer {
+    state: Arc<(Mutex<TimerState>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SliceTimer {
+    fn new() -> Self {
+        let state = Arc::new((Mutex::new(TimerState::default()), Condvar::new()));
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let (lock, condvar) = &*thread_state;
+            let Ok(mut state) = lock.lock() else {
+                return;
+            };
+
+            while !state.shutdown {
+                let Some(deadline) = state.armed.as_ref().map(|armed| armed.deadline) else {
+                    state = match condvar.wait(state) {
+                        Ok(state) => state,
+                        Err(_) => return,
+                    };
+                    continue;
+                };
+
+                let now = Instant::now();
+                if now >= deadline {
+                    if let Some(armed) = state.armed.take() {
+                        armed.fired.store(true, Ordering::SeqCst);
+                        armed.isolate.terminate_execution();
+                    }
+                    continue;
+                }
+
+                state = match condvar.wait_timeout(state, deadline - now) {
+                    Ok((state, _)) => state,
+                    Err(_) => return,
+                };
+            }
+        });
+
+        Self {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    /// Interrupt the isolate at the deadline, unless disarmed first
+    ///
+    /// Returns a flag set if the interrupt fired
+    fn arm(&self, deadline: Instant, isolate: v8::IsolateHandle) -> Arc<AtomicBool> {
+        let fired = Arc::new(AtomicBool::new(false));
+        let (lock, condvar) = &*self.state;
+        if let Ok(mut state) = lock.lock() {
+            state.armed = Some(Armed {
+                deadline,
+                isolate,
+                fired: fired.clone(),
+            });
+            condvar.notify_one();
+        }
+        fired
+    }
+
+    fn disarm(&self) {
+        let (lock, condvar) = &*self.state;
+        if let Ok(mut state) = lock.lock() {
+            state.armed = None;
+            condvar.notify_one();
+        }
+    }
+}
+
+impl Drop for SliceTimer {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.state;
+        if let Ok(mut state) = lock.lock() {
+            state.shutdown = true;
+            condvar.notify_one();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RuntimeOptions;
+
+    #[test]
+    fn test_time_slicer() {
+        let mut slicer = TimeSlicer::new(TimeSliceOptions {
+            slice: Duration::from_millis(2),
+            max_overrun: Some(Duration::from_millis(50)),
+        });
+
+        // Ticks of ~10ms each, against a slice of 2ms
+        let heavy = slicer.add(Runtime::new(RuntimeOptions::default()).unwrap());
+        slicer
+            .get_mut(heavy)
+            .unwrap()
+            .eval::<()>(
+                "
+                globalThis.turns = 0;
+                const tick = () => {
+                    const end = Date.now() + 10;
+                    while (Date.now() < end) {}
+                    if (++globalThis.turns < 5) setTimeout(tick);
+                };
+                setTimeout(tick);
+            ",
+            )
+            .unwrap();
+
+        let light = slicer.add(Runtime::new(RuntimeOptions::default()).unwrap());
+        slicer
+            .get_mut(light)
+            .unwrap()
+            .eval::<()>(
+                "
+                globalThis.turns = 0;
+                const tick = () => { if (++globalThis.turns < 5) setTimeout(tick); };
+                setTimeout(tick);
+            ",
+            )
+            .unwrap();
+
+        let stuck = slicer.add(Runtime::new(RuntimeOptions::default()).unwrap());
+        slicer
+            .get_mut(stuck)
+            .unwrap()
+            .eval::<()>("setTimeout(() => { while (true) {} })")
+            .unwrap();
+
+        let errors = slicer.run_until_idle();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, stuck);
+        assert!(matches!(errors[0].1, Error::Timeout(_)));
+
+        for id in [heavy, light] {
+            let turns: usize = slicer
+                .get_mut(id)
+                .unwrap()
+                .eval("globalThis.turns")
+                .unwrap();
+            assert_eq!(turns, 5);
+        }
+
+        // Still usable after being interrupted
+        let runtime = slicer.get_mut(stuck).unwrap();
+        assert_eq!(runtime.eval::<usize>("1 + 1").unwrap(), 2);
+
+        assert_eq!(slicer.len(), 3);
+        assert!(slicer.remove(stuck).is_some());
+        assert_eq!(slicer.len(), 2);
+    }
+}
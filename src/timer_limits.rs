@@ -0,0 +1,180 @@
+//! Runtime-wide limits on the timers scripts may schedule
+//!
+//! Keeps pooled runtimes from being held open by stray timers - a forgotten week-long `setTimeout`
+//! or a leak of intervals would otherwise keep the event loop alive indefinitely
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// What happens to a timer whose delay is over the limit set by [`TimerLimits`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LongTimerPolicy {
+    /// `setTimeout` and `setInterval` throw a `rustyscript.TimerLimitError`
+    #[default]
+    Reject,
+
+    /// The delay is shortened to the limit, and the timer is scheduled as normal
+    Clamp,
+}
+
+/// Caps on the timers a runtime's scripts may schedule - see [`crate::RuntimeOptions::timer_limits`]
+///
+/// Exceeding one throws a `rustyscript.TimerLimitError` from `setTimeout` or `setInterval`,
+/// carrying [`Error::TimerLimitExceeded`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TimerLimits {
+    /// Maximum number of timeouts and intervals pending at once
+    ///
+    /// Timeouts stop counting once they fire or are cleared, intervals once they are cleared
+    pub max_timers: Option<usize>,
+
+    /// Maximum delay of a `setTimeout`
+    pub max_timeout: Option<Duration>,
+
+    /// Maximum period of a `setInterval`
+    pub max_interval: Option<Duration>,
+
+    /// Whether delays over `max_timeout` or `max_interval` are rejected or clamped
+    pub long_timers: LongTimerPolicy,
+}
+
+/// Identifies which of the [`TimerLimits`] was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimerLimit {
+    /// [`TimerLimits::max_timers`]
+    TimerCount,
+
+    /// [`TimerLimits::max_timeout`]
+    Timeout,
+
+    /// [`TimerLimits::max_interval`]
+    Interval,
+}
+
+impl std::fmt::Display for TimerLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TimerCount => write!(f, "maximum timer count"),
+            Self::Timeout => write!(f, "maximum timeout"),
+            Self::Interval => write!(f, "maximum interval"),
+        }
+    }
+}
+
+impl TimerLimits {
+    /// Returns true if this places no restrictions on timers
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_timers.is_none() && self.max_timeout.is_none() && self.max_interval.is_none()
+    }
+
+    /// Checks a new timer against the limits, given the number already pending
+    ///
+    /// Returns the delay, in milliseconds, the timer should be scheduled with
+    pub(crate) fn check(&self, pending: usize, delay: f64, interval: bool) -> Result<f64, Error> {
+        let (name, limit, max) = if interval {
+            ("setInterval", TimerLimit::Interval, self.max_interval)
+        } else {
+            ("setTimeout", TimerLimit::Timeout, self.max_timeout)
+        };
+
+        if self.max_timers.is_some_and(|max| pending >= max) {
+            return Err(Error::TimerLimitExceeded(
+                TimerLimit::TimerCount,
+                format!("{name} with {pending} timers pending"),
+            ));
+        }
+
+        let Some(max) = max.map(|max| max.as_secs_f64() * 1000.0) else {
+            return Ok(delay);
+        };
+        if delay <= max {
+            return Ok(delay);
+        }
+
+        match self.long_timers {
+            LongTimerPolicy::Clamp => Ok(max),
+            LongTimerPolicy::Reject => Err(Error::TimerLimitExceeded(
+                limit,
+                format!("{name} of {delay}ms"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_timer_limits() {
+        let limits = TimerLimits {
+            max_timers: Some(2),
+            max_timeout: Some(Duration::from_secs(60)),
+            max_interval: Some(Duration::from_secs(1)),
+            long_timers: LongTimerPolicy::Reject,
+        };
+        assert_eq!(limits.check(0, 1000.0, false).ok(), Some(1000.0));
+        assert!(matches!(
+            limits.check(0, 120_000.0, false),
+            Err(Error::TimerLimitExceeded(TimerLimit::Timeout, _))
+        ));
+        assert!(matches!(
+            limits.check(2, 0.0, false),
+            Err(Error::TimerLimitExceeded(TimerLimit::TimerCount, _))
+        ));
+
+        let clamped = TimerLimits {
+            long_timers: LongTimerPolicy::Clamp,
+            ..limits
+        };
+        assert_eq!(clamped.check(0, 5000.0, true).ok(), Some(1000.0));
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timer_limits: limits,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let rejected: String = runtime
+            .eval(
+                "try {
+                    setTimeout(() => {}, 7 * 24 * 60 * 60 * 1000);
+                    'scheduled'
+                } catch (e) {
+                    e instanceof rustyscript.TimerLimitError ? e.name : String(e)
+                }",
+            )
+            .unwrap();
+        assert_eq!(rejected, "TimerLimitError");
+
+        // Fired and cleared timers stop counting towards the limit
+        let fired: usize = runtime
+            .eval(
+                "(async () => {
+                    let fired = 0;
+                    for (let i = 0; i < 3; i++) {
+                        await new Promise((resolve) => setTimeout(() => resolve(fired++), 0));
+                    }
+
+                    const interval = setInterval(() => {}, 10);
+                    clearInterval(interval);
+                    const a = setTimeout(() => {}, 10);
+                    const b = setTimeout(() => {}, 10);
+                    try {
+                        setTimeout(() => {}, 10);
+                    } catch {
+                        fired += 100;
+                    }
+                    clearTimeout(a);
+                    clearTimeout(b);
+                    return fired;
+                })()",
+            )
+            .unwrap();
+        assert_eq!(fired, 103);
+    }
+}
@@ -3,9 +3,16 @@
 //! modules.
 //!
 //! It will only transpile, not typecheck (like Deno's `--no-check` flag).
-use std::{borrow::Cow, rc::Rc};
+use std::{borrow::Cow, collections::HashMap, rc::Rc};
 
-use deno_ast::{MediaType, ParseDiagnosticsError, ParseParams, SourceTextInfo, TranspileError};
+use deno_ast::{
+    swc::{
+        ast::{Expr, MemberProp},
+        ecma_visit::{Visit, VisitWith},
+    },
+    MediaType, ParseDiagnosticsError, ParseParams, ProgramRef, SourceRange, SourceRanged,
+    SourceTextInfo, TranspileError,
+};
 use deno_core::{FastString, ModuleSpecifier, SourceMapData};
 use deno_error::JsErrorBox;
 
@@ -83,6 +90,172 @@ pub fn transpile(
     Ok(code)
 }
 
+/// Modules shorter than this are transformed on the calling thread - for them, handing the work
+/// to another thread costs more than it saves
+const OFF_THREAD_MIN_LEN: usize = 16 * 1024;
+
+///
+/// Runs a transformation of a module's source on tokio's blocking thread pool
+///
+/// Keeps the calling thread - and the JS event loop it drives - free while large modules are
+/// transpiled, and lets the modules of an import graph, which are loaded concurrently, be
+/// transpiled in parallel. Short sources, and calls made outside a tokio runtime, run in place
+pub async fn off_thread<T, F>(source_len: usize, job: F) -> Result<T, crate::Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let handle = match tokio::runtime::Handle::try_current() {
+        Ok(handle) if source_len >= OFF_THREAD_MIN_LEN => handle,
+        _ => return Ok(job()),
+    };
+
+    match handle.spawn_blocking(job).await {
+        Ok(result) => Ok(result),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(crate::Error::Runtime(
+            "Transpilation was cancelled".to_string(),
+        )),
+    }
+}
+
+///
+/// Minifies transpiled code, removing whitespace and comments
+///
+/// Names are kept as they are - identifiers are not mangled
+pub fn minify(module_specifier: &ModuleSpecifier, code: &str) -> Result<String, crate::Error> {
+    use deno_ast::swc::{
+        codegen::{text_writer::JsWriter, Config, Emitter},
+        common::{sync::Lrc, SourceMap},
+    };
+
+    if matches!(
+        MediaType::from_specifier(module_specifier),
+        MediaType::Json | MediaType::Wasm | MediaType::Css
+    ) {
+        return Ok(code.to_string());
+    }
+
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: module_specifier.clone(),
+        text: code.into(),
+        media_type: MediaType::JavaScript,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })
+    .map_err(|e| crate::Error::Runtime(e.to_string()))?;
+
+    let source_map: Lrc<SourceMap> = Lrc::default();
+    let mut output = vec![];
+    {
+        let mut emitter = Emitter {
+            cfg: Config::default().with_minify(true),
+            cm: source_map.clone(),
+            comments: None,
+            wr: JsWriter::new(source_map, "\n", &mut output, None),
+        };
+        match parsed.program_ref() {
+            ProgramRef::Module(module) => emitter.emit_module(module),
+            ProgramRef::Script(script) => emitter.emit_script(script),
+        }
+        .map_err(|e| crate::Error::Runtime(format!("Could not minify {module_specifier}: {e}")))?;
+    }
+
+    String::from_utf8(output).map_err(|e| crate::Error::Runtime(e.to_string()))
+}
+
+///
+/// Substitutes compile-time constants into source code, before it is transpiled
+///
+/// Keys are global identifiers or dotted paths, like `__DEBUG__` or `process.env.NODE_ENV`, and
+/// values are the JS expressions replacing them. Only reads are replaced - assignment targets,
+/// property names and object shorthands are left alone, and a local binding with the same name as
+/// a define is not told apart from the global
+pub fn apply_defines<'a>(
+    module_specifier: &ModuleSpecifier,
+    code: &'a str,
+    defines: &HashMap<String, String>,
+) -> Result<Cow<'a, str>, TranspileError> {
+    let media_type = match MediaType::from_specifier(module_specifier) {
+        MediaType::Json | MediaType::Wasm | MediaType::Css => return Ok(Cow::Borrowed(code)),
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+
+    // Most modules mention none of the defines - skip parsing them
+    if !defines
+        .keys()
+        .any(|key| code.contains(key.split('.').next().unwrap_or(key)))
+    {
+        return Ok(Cow::Borrowed(code));
+    }
+
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: module_specifier.clone(),
+        text: code.into(),
+        media_type,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })
+    .map_err(|e| TranspileError::ParseErrors(ParseDiagnosticsError(vec![e])))?;
+
+    let mut finder = DefineFinder {
+        defines,
+        found: vec![],
+    };
+    match parsed.program_ref() {
+        ProgramRef::Module(module) => module.visit_with(&mut finder),
+        ProgramRef::Script(script) => script.visit_with(&mut finder),
+    }
+    if finder.found.is_empty() {
+        return Ok(Cow::Borrowed(code));
+    }
+
+    let start = parsed.text_info_lazy().range().start;
+    let mut output = String::with_capacity(code.len());
+    let mut last = 0;
+    for (range, value) in finder.found {
+        let range = range.as_byte_range(start);
+        output.push_str(&code[last..range.start]);
+        output.push('(');
+        output.push_str(value);
+        output.push(')');
+        last = range.end;
+    }
+    output.push_str(&code[last..]);
+    Ok(Cow::Owned(output))
+}
+
+/// Collects the expressions to replace with defines, in source order
+struct DefineFinder<'a> {
+    defines: &'a HashMap<String, String>,
+    found: Vec<(SourceRange, &'a str)>,
+}
+
+impl Visit for DefineFinder<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Some(value) = dotted_path(expr).and_then(|path| self.defines.get(&path)) {
+            self.found.push((expr.range(), value));
+        } else {
+            expr.visit_children_with(self);
+        }
+    }
+}
+
+/// The dotted path of an identifier, or a chain of static property accesses on one
+fn dotted_path(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Ident(ident) => Some(ident.sym.to_string()),
+        Expr::Member(member) => match &member.prop {
+            MemberProp::Ident(prop) => Some(format!("{}.{}", dotted_path(&member.obj)?, prop.sym)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 ///
 /// Transpile an extension
 #[allow(clippy::type_complexity)]
@@ -99,3 +272,41 @@ pub type ExtensionTranspiler = Rc<
     dyn Fn(FastString, FastString) -> Result<(FastString, Option<Cow<'static, [u8]>>), JsErrorBox>,
 >;
 pub type ExtensionTranspilation = (FastString, Option<Cow<'static, [u8]>>);
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::{Module, Runtime, RuntimeOptions};
+
+    #[tokio::test]
+    async fn test_off_thread() {
+        let caller = thread::current().id();
+        let short = off_thread(10, move || thread::current().id())
+            .await
+            .unwrap();
+        assert_eq!(short, caller);
+
+        let long = off_thread(OFF_THREAD_MIN_LEN, move || thread::current().id())
+            .await
+            .unwrap();
+        assert_ne!(long, caller);
+    }
+
+    #[test]
+    fn test_large_module() {
+        let padding: String = (0..OFF_THREAD_MIN_LEN / 16)
+            .map(|i| format!("const pad{i}: number = {i};\n"))
+            .collect();
+        let module = Module::new(
+            "large.ts",
+            format!("{padding}export const value: string = 'transpiled';"),
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = runtime.load_module(&module).unwrap();
+        let value: String = runtime.get_value(Some(&module), "value").unwrap();
+        assert_eq!(value, "transpiled");
+    }
+}
@@ -0,0 +1,422 @@
+//! Tree-shaking of a single module - see [`crate::Module::tree_shaken`]
+use std::{
+    collections::{HashSet, VecDeque},
+    ops::Range,
+};
+
+use deno_ast::{
+    swc::{
+        ast::{
+            BindingIdent, Class, ClassMember, Decl, DefaultDecl, ExportSpecifier, Expr, Ident,
+            ModuleDecl, ModuleExportName, ModuleItem, Prop, PropName, PropOrSpread, Stmt, UnaryOp,
+        },
+        ecma_visit::{Visit, VisitWith},
+    },
+    ProgramRef, SourceRanged, StartSourcePos,
+};
+
+use crate::{Error, Module};
+
+/// A top-level statement of the module being shaken
+struct Item {
+    range: Range<usize>,
+
+    /// The local bindings the statement declares
+    declares: Vec<String>,
+
+    /// The names the statement exports
+    exports: Vec<String>,
+
+    /// The identifiers the statement mentions
+    references: HashSet<String>,
+
+    /// False if running the statement could have side effects
+    removable: bool,
+
+    /// For `export { a as b }` - the `(exported, local, source)` of each specifier
+    specifiers: Option<Vec<(String, String, Range<usize>)>>,
+}
+
+/// Removes the top-level declarations of a module that the given exports cannot reach
+///
+/// Statements that may have side effects are always kept, along with `export *` and imports
+pub(crate) fn tree_shake(module: &Module, keep: &[&str]) -> Result<String, Error> {
+    let parsed = crate::module_interface::parse(module)?;
+    let ProgramRef::Module(program) = parsed.program_ref() else {
+        return Ok(module.contents().to_string());
+    };
+    let start = parsed.text_info_lazy().range().start;
+    let code = module.contents();
+
+    let items: Vec<Item> = program
+        .body
+        .iter()
+        .map(|item| describe(item, item.range().as_byte_range(start), start))
+        .collect();
+
+    // Every requested export must exist, unless it could come from an `export *`
+    let has_export_all = program
+        .body
+        .iter()
+        .any(|item| matches!(item, ModuleItem::ModuleDecl(ModuleDecl::ExportAll(_))));
+    if !has_export_all {
+        for name in keep {
+            let exported = items.iter().any(|item| {
+                item.exports.iter().any(|e| e == name)
+                    || item
+                        .specifiers
+                        .iter()
+                        .flatten()
+                        .any(|(exported, _, _)| exported == name)
+            });
+            if !exported {
+                return Err(Error::ValueNotFound(format!(
+                    "{module} does not export `{name}`"
+                )));
+            }
+        }
+    }
+
+    // Start from the requested exports, and whatever must run anyway
+    let mut kept = vec![false; items.len()];
+    let mut needed = VecDeque::new();
+    let keep_item = |i: usize, kept: &mut Vec<bool>, needed: &mut VecDeque<String>| {
+        if !kept[i] {
+            kept[i] = true;
+            needed.extend(items[i].references.iter().cloned());
+        }
+    };
+    for (i, item) in items.iter().enumerate() {
+        if let Some(specifiers) = &item.specifiers {
+            for (exported, local, _) in specifiers {
+                if keep.contains(&exported.as_str()) {
+                    kept[i] = true;
+                    needed.push_back(local.clone());
+                }
+            }
+        } else if !item.removable || item.exports.iter().any(|e| keep.contains(&e.as_str())) {
+            keep_item(i, &mut kept, &mut needed);
+        }
+    }
+
+    // Then everything they refer to
+    let mut seen = HashSet::new();
+    while let Some(name) = needed.pop_front() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        for (i, item) in items.iter().enumerate() {
+            if item.specifiers.is_none() && item.declares.contains(&name) {
+                keep_item(i, &mut kept, &mut needed);
+            }
+        }
+    }
+
+    let mut output = String::with_capacity(code.len());
+    for (item, _) in items.iter().zip(kept).filter(|(_, kept)| *kept) {
+        match &item.specifiers {
+            Some(specifiers) => {
+                let kept: Vec<&str> = specifiers
+                    .iter()
+                    .filter(|(exported, _, _)| keep.contains(&exported.as_str()))
+                    .map(|(_, _, range)| &code[range.clone()])
+                    .collect();
+                output.push_str(&format!("export {{ {} }};", kept.join(", ")));
+            }
+            None => output.push_str(&code[item.range.clone()]),
+        }
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Works out what a top-level statement declares, exports and refers to
+fn describe(item: &ModuleItem, range: Range<usize>, start: StartSourcePos) -> Item {
+    let mut references = References::default();
+    item.visit_with(&mut references);
+
+    let mut described = Item {
+        range,
+        declares: vec![],
+        exports: vec![],
+        references: references.0,
+        removable: false,
+        specifiers: None,
+    };
+
+    match item {
+        ModuleItem::Stmt(Stmt::Decl(decl)) => {
+            described.declares = declared_names(decl);
+            described.removable = is_pure_decl(decl);
+        }
+
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => {
+            described.declares = declared_names(&export.decl);
+            described.exports.clone_from(&described.declares);
+            described.removable = is_pure_decl(&export.decl);
+        }
+
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export)) => {
+            described.exports = vec!["default".to_string()];
+            described.removable = true;
+            match &export.decl {
+                DefaultDecl::Fn(f) => described.declares.extend(f.ident.as_ref().map(ident_name)),
+                DefaultDecl::Class(c) => {
+                    described.declares.extend(c.ident.as_ref().map(ident_name));
+                    described.removable = is_pure_class(&c.class);
+                }
+                DefaultDecl::TsInterfaceDecl(_) => {}
+            }
+        }
+
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export)) => {
+            described.exports = vec!["default".to_string()];
+            described.removable = is_pure(&export.expr);
+        }
+
+        // `export { a as b }` - shaken specifier by specifier
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)) if export.src.is_none() => {
+            let specifiers = export
+                .specifiers
+                .iter()
+                .filter_map(|specifier| match specifier {
+                    ExportSpecifier::Named(named) => {
+                        let local = export_name(&named.orig);
+                        let exported = named.exported.as_ref().map_or(local.clone(), export_name);
+                        Some((exported, local, named.range().as_byte_range(start)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            described.specifiers = Some(specifiers);
+            described.removable = true;
+        }
+
+        // `export { a } from './b.js'` - dropped if none of its names are needed
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)) => {
+            described.exports = export
+                .specifiers
+                .iter()
+                .map(|specifier| match specifier {
+                    ExportSpecifier::Named(named) => named
+                        .exported
+                        .as_ref()
+                        .map_or_else(|| export_name(&named.orig), export_name),
+                    ExportSpecifier::Default(default) => ident_name(&default.exported),
+                    ExportSpecifier::Namespace(namespace) => export_name(&namespace.name),
+                })
+                .collect();
+            described.removable = true;
+        }
+
+        // Imports, `export *`, and any other statement are kept as they are
+        _ => {}
+    }
+
+    described
+}
+
+/// The local bindings a declaration introduces
+fn declared_names(decl: &Decl) -> Vec<String> {
+    match decl {
+        Decl::Fn(f) => vec![ident_name(&f.ident)],
+        Decl::Class(c) => vec![ident_name(&c.ident)],
+        Decl::TsInterface(i) => vec![ident_name(&i.id)],
+        Decl::TsTypeAlias(t) => vec![ident_name(&t.id)],
+        Decl::TsEnum(e) => vec![ident_name(&e.id)],
+        Decl::Var(var) => {
+            let mut bindings = Bindings::default();
+            for declarator in &var.decls {
+                declarator.name.visit_with(&mut bindings);
+            }
+            bindings.0
+        }
+        _ => vec![],
+    }
+}
+
+/// Returns true if a declaration can be removed without losing side effects
+fn is_pure_decl(decl: &Decl) -> bool {
+    match decl {
+        Decl::Fn(_) | Decl::TsInterface(_) | Decl::TsTypeAlias(_) | Decl::TsEnum(_) => true,
+        Decl::Class(class) => is_pure_class(&class.class),
+        Decl::Var(var) => var.decls.iter().all(|d| match d.init.as_deref() {
+            Some(init) => is_pure(init),
+            None => true,
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if evaluating an expression cannot have side effects
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) | Expr::Arrow(_) | Expr::Fn(_) | Expr::Ident(_) => true,
+        Expr::Class(class) => is_pure_class(&class.class),
+        Expr::Tpl(tpl) => tpl.exprs.iter().all(|e| is_pure(e)),
+        Expr::Paren(paren) => is_pure(&paren.expr),
+        Expr::Unary(unary) => unary.op != UnaryOp::Delete && is_pure(&unary.arg),
+        Expr::TsAs(ts_as) => is_pure(&ts_as.expr),
+        Expr::TsConstAssertion(assertion) => is_pure(&assertion.expr),
+        Expr::TsSatisfies(satisfies) => is_pure(&satisfies.expr),
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .flatten()
+            .all(|e| e.spread.is_none() && is_pure(&e.expr)),
+        Expr::Object(object) => object.props.iter().all(|prop| match prop {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(kv) => {
+                    !matches!(kv.key, PropName::Computed(_)) && is_pure(&kv.value)
+                }
+                Prop::Shorthand(_) | Prop::Method(_) | Prop::Getter(_) | Prop::Setter(_) => true,
+                _ => false,
+            },
+            PropOrSpread::Spread(_) => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if defining a class cannot have side effects
+///
+/// Its `extends` expression, decorators, computed keys, static blocks and static initializers
+/// all run as soon as the class is defined
+fn is_pure_class(class: &Class) -> bool {
+    class.super_class.is_none()
+        && class.decorators.is_empty()
+        && class.body.iter().all(|member| match member {
+            ClassMember::StaticBlock(_) | ClassMember::AutoAccessor(_) => false,
+            ClassMember::Method(method) => {
+                !matches!(method.key, PropName::Computed(_))
+                    && method.function.decorators.is_empty()
+            }
+            ClassMember::PrivateMethod(method) => method.function.decorators.is_empty(),
+            ClassMember::ClassProp(prop) => {
+                !matches!(prop.key, PropName::Computed(_))
+                    && prop.decorators.is_empty()
+                    && !(prop.is_static && prop.value.is_some())
+            }
+            ClassMember::PrivateProp(prop) => {
+                prop.decorators.is_empty() && !(prop.is_static && prop.value.is_some())
+            }
+            _ => true,
+        })
+}
+
+fn ident_name(ident: &Ident) -> String {
+    ident.sym.to_string()
+}
+
+fn export_name(name: &ModuleExportName) -> String {
+    match name {
+        ModuleExportName::Ident(ident) => ident_name(ident),
+        ModuleExportName::Str(s) => s.value.to_string(),
+    }
+}
+
+/// Collects every identifier mentioned - an over-approximation of what a statement refers to
+#[derive(Default)]
+struct References(HashSet<String>);
+
+impl Visit for References {
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.0.insert(ident_name(ident));
+    }
+}
+
+/// Collects the names bound by a pattern
+#[derive(Default)]
+struct Bindings(Vec<String>);
+
+impl Visit for Bindings {
+    fn visit_binding_ident(&mut self, binding: &BindingIdent) {
+        self.0.push(ident_name(&binding.id));
+    }
+
+    // Default values are expressions, not bindings
+    fn visit_expr(&mut self, _: &Expr) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_tree_shake() {
+        let module = Module::new(
+            "lib.ts",
+            "
+            import { helper } from './helper.js';
+            const PI = 3.14;
+            const unusedConst = { big: [1, 2, 3] };
+            function square(n: number) { return n * n; }
+            function unusedHelper() { return helper(); }
+            export function area(r: number) { return PI * square(r); }
+            export function unused() { return unusedHelper(); }
+            export class Shape {}
+            export class Registered { static { console.log('static block'); } }
+            export class Counted { static count = register(); }
+            export class Derived extends mixin(Base) {}
+            interface Options { verbose: boolean }
+            console.log('side effect');
+            delete globalThis.removed;
+            const local = 1, other = 2;
+            export { local as one, other as two };
+            ",
+        );
+
+        let shaken = tree_shake(&module, &["area", "one"]).unwrap();
+        assert!(shaken.contains("import { helper }"));
+        assert!(shaken.contains("const PI"));
+        assert!(shaken.contains("function square"));
+        assert!(shaken.contains("export function area"));
+        assert!(shaken.contains("console.log('side effect')"));
+        assert!(shaken.contains("delete globalThis.removed"));
+
+        // Classes that run code as they are defined are kept
+        assert!(shaken.contains("class Registered"));
+        assert!(shaken.contains("class Counted"));
+        assert!(shaken.contains("class Derived"));
+        assert!(shaken.contains("export { local as one };"));
+        assert!(shaken.contains("const local = 1, other = 2;"));
+        for removed in [
+            "unusedConst",
+            "unusedHelper",
+            "unused()",
+            "Shape",
+            "Options",
+            "two",
+        ] {
+            assert!(!shaken.contains(removed), "{removed} in {shaken}");
+        }
+
+        assert!(tree_shake(&module, &["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_tree_shaken_module() {
+        let module = Module::new(
+            "lib.js",
+            "
+            const table = [1, 2, 3];
+            export const sum = () => table.reduce((a, b) => a + b, 0);
+            export const fail = () => { throw new Error('unused'); };
+            ",
+        )
+        .tree_shaken(&["sum"])
+        .unwrap();
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        let sum: usize = runtime
+            .call_function(Some(&handle), "sum", crate::json_args!())
+            .unwrap();
+        assert_eq!(sum, 6);
+        assert!(runtime
+            .get_value::<crate::js_value::Function>(Some(&handle), "fail")
+            .is_err());
+    }
+}
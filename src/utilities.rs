@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{path::Path, sync::OnceLock};
 
 use deno_core::ModuleSpecifier;
 
@@ -56,7 +56,7 @@ pub fn validate(javascript: &str) -> Result<bool, Error> {
     let mut runtime = Runtime::new(RuntimeOptions::default())?;
     match runtime.load_modules(&module, vec![]) {
         Ok(_) => Ok(true),
-        Err(Error::Runtime(_) | Error::JsError(_)) => Ok(false),
+        Err(Error::Runtime(_) | Error::JsError(_) | Error::StackOverflow(_)) => Ok(false),
         Err(e) => Err(e),
     }
 }
@@ -114,14 +114,279 @@ pub fn resolve_path(
     Ok(url)
 }
 
+/// Provide the ICU data used by `Intl`, in place of the full data set embedded by default  
+/// Must be called before the first runtime is created
+///
+/// Use this to ship a slimmed `.dat` file containing only the locales you need,
+/// built with the ICU data build tool - and disable the default features of `deno_core`
+/// (`include_icu_data`) to drop the embedded copy from the binary
+///
+/// Requires the `intl` feature to be enabled
+///
+/// # Errors
+/// Will return an error if the data could not be loaded
+#[cfg(feature = "intl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "intl")))]
+pub fn init_icu_data(data: &'static [u8]) -> Result<(), Error> {
+    deno_core::v8::icu::set_common_data_77(data)
+        .map_err(|code| Error::Runtime(format!("Could not load ICU data (error {code})")))
+}
+
 /// Explicitly initialize the V8 platform  
 /// Note that all runtimes must have a common parent thread that initalized the V8 platform
 ///
 /// This is done automatically the first time [`Runtime::new`] is called,
 /// but for multi-threaded applications, it may be necessary to call this function manually
+///
+/// Has no effect if the platform is already initialized - see [`init_platform_with`] for more options
 pub fn init_platform(thread_pool_size: u32, idle_task_support: bool) {
-    let platform = deno_core::v8::Platform::new(thread_pool_size, idle_task_support);
-    deno_core::JsRuntime::init_platform(Some(platform.into()), true);
+    init_platform_with(PlatformOptions {
+        thread_pool_size,
+        idle_task_support,
+        ..Default::default()
+    })
+    .ok();
+}
+
+/// Options for the V8 platform - see [`init_platform_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PlatformOptions {
+    /// The number of worker threads V8 runs background tasks on, such as garbage collection
+    /// and optimizing compilation
+    ///
+    /// Default: 0 - chosen by V8 from the number of cores
+    pub thread_pool_size: u32,
+
+    /// Whether to support idle tasks, run by V8 when a runtime has nothing else to do
+    ///
+    /// Default: false
+    pub idle_task_support: bool,
+
+    /// Run all of V8's work on the threads using it, with no worker pool (`--single-threaded`)
+    ///
+    /// Default: false
+    pub single_threaded: bool,
+
+    /// Whether garbage collection may run on the worker pool (`--single-threaded-gc` when unset)
+    ///
+    /// Default: true
+    pub concurrent_gc: bool,
+
+    /// Whether optimizing compilation may run on the worker pool (`--concurrent-recompilation`)
+    ///
+    /// Default: true
+    pub concurrent_compilation: bool,
+}
+
+impl Default for PlatformOptions {
+    fn default() -> Self {
+        Self {
+            thread_pool_size: 0,
+            idle_task_support: false,
+            single_threaded: false,
+            concurrent_gc: true,
+            concurrent_compilation: true,
+        }
+    }
+}
+
+static PLATFORM: OnceLock<PlatformOptions> = OnceLock::new();
+
+/// Set once V8 has been initialized - explicitly, or by the first runtime
+static V8_INITIALIZED: OnceLock<()> = OnceLock::new();
+
+/// Initialize the V8 platform with V8's defaults, unless it already was
+///
+/// Returns true if this call initialized it
+pub(crate) fn init_default_platform() -> bool {
+    let mut initialized = false;
+    V8_INITIALIZED.get_or_init(|| {
+        deno_core::JsRuntime::init_platform(None, true);
+        initialized = true;
+    });
+    initialized
+}
+
+/// Explicitly initialize the V8 platform with the given options  
+/// Must be called before the first runtime is created, from a thread that outlives every runtime
+///
+/// Otherwise the platform is initialized with V8's defaults when the first runtime is created
+///
+/// ```rust
+/// use rustyscript::{init_platform_with, PlatformOptions, Runtime};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// init_platform_with(PlatformOptions {
+///     thread_pool_size: 2,
+///     concurrent_compilation: false,
+///     ..Default::default()
+/// })?;
+///
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let value: i64 = runtime.eval("1 + 1")?;
+/// assert_eq!(value, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// Will return an error if V8 was already initialized - by a call to this function or to
+/// [`init_platform`], or by creating a runtime
+pub fn init_platform_with(options: PlatformOptions) -> Result<(), Error> {
+    let mut initialized = false;
+    V8_INITIALIZED.get_or_init(|| {
+        let mut flags = Vec::new();
+        if options.single_threaded {
+            flags.push("--single-threaded");
+        }
+        if !options.concurrent_gc {
+            flags.push("--single-threaded-gc");
+        }
+        if !options.concurrent_compilation {
+            flags.push("--no-concurrent-recompilation");
+        }
+        if !flags.is_empty() {
+            deno_core::v8::V8::set_flags_from_string(&flags.join(" "));
+        }
+
+        let platform = if options.single_threaded {
+            deno_core::v8::Platform::new_single_threaded(options.idle_task_support)
+        } else {
+            deno_core::v8::Platform::new(options.thread_pool_size, options.idle_task_support)
+        };
+        deno_core::JsRuntime::init_platform(Some(platform.into()), true);
+        PLATFORM.set(options.clone()).ok();
+        initialized = true;
+    });
+
+    if initialized {
+        Ok(())
+    } else {
+        Err(Error::Runtime(
+            "The V8 platform is already initialized".to_string(),
+        ))
+    }
+}
+
+/// The options the platform was initialized with, if it was initialized explicitly
+#[must_use]
+pub fn platform_options() -> Option<PlatformOptions> {
+    PLATFORM.get().cloned()
+}
+
+/// Process-wide memory options for runtimes - see [`init_shared_heap`]
+#[derive(Debug, Clone, Default)]
+pub struct SharedHeapOptions {
+    /// A snapshot used by every runtime that does not set its own `startup_snapshot`
+    ///
+    /// All runtimes then deserialize their heap from one copy of the snapshot, and skip loading
+    /// extension sources of their own
+    pub startup_snapshot: Option<&'static [u8]>,
+
+    /// Favour memory over speed when compiling and collecting garbage (`--optimize-for-size`)
+    pub optimize_for_size: bool,
+
+    /// The most memory each isolate's young generation may use, in megabytes (`--max-semi-space-size`)
+    ///
+    /// Smaller values mean more frequent, but smaller, collections
+    pub max_semi_space_size: Option<usize>,
+
+    /// Any other V8 flags to apply to every isolate
+    pub v8_flags: Vec<String>,
+}
+
+static SHARED_HEAP: OnceLock<SharedHeapOptions> = OnceLock::new();
+
+/// Configure the process for many resident runtimes, reducing the memory each one holds  
+/// Must be called once, before [`init_platform`] and before the first runtime is created
+///
+/// V8 already shares its read-only heap and builtins between every isolate in the process;
+/// what remains per-isolate is the mutable heap each runtime builds on startup. Starting every
+/// runtime from a common snapshot, and tuning the flags below, keeps that as small as possible
+///
+/// ```rust
+/// use rustyscript::{init_shared_heap, Runtime, SharedHeapOptions};
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// init_shared_heap(SharedHeapOptions {
+///     optimize_for_size: true,
+///     max_semi_space_size: Some(1),
+///     ..Default::default()
+/// })?;
+///
+/// let mut runtimes = (0..8)
+///     .map(|_| Runtime::new(Default::default()))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let value: i64 = runtimes[0].eval("1 + 1")?;
+/// assert_eq!(value, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// Will return an error if called more than once
+pub fn init_shared_heap(options: SharedHeapOptions) -> Result<(), Error> {
+    let mut flags = options.v8_flags.clone();
+    if options.optimize_for_size {
+        flags.push("--optimize-for-size".to_string());
+    }
+    if let Some(size) = options.max_semi_space_size {
+        flags.push(format!("--max-semi-space-size={size}"));
+    }
+
+    SHARED_HEAP
+        .set(options)
+        .map_err(|_| Error::Runtime("The shared heap is already initialized".to_string()))?;
+    if !flags.is_empty() {
+        deno_core::v8::V8::set_flags_from_string(&flags.join(" "));
+    }
+    Ok(())
+}
+
+/// The snapshot set with [`init_shared_heap`], if any
+pub(crate) fn shared_startup_snapshot() -> Option<&'static [u8]> {
+    SHARED_HEAP.get()?.startup_snapshot
+}
+
+/// The lowest address of the current thread's stack, where the platform can report it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn thread_stack_bottom() -> Option<usize> {
+    // SAFETY: `attr` is initialized by `pthread_getattr_np` before it is read, and destroyed after
+    unsafe {
+        let mut attr = std::mem::MaybeUninit::<libc::pthread_attr_t>::uninit();
+        if libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(attr.as_ptr(), &mut addr, &mut size);
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+        (result == 0).then_some(addr as usize)
+    }
+}
+
+/// The lowest address of the current thread's stack, where the platform can report it
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub(crate) fn thread_stack_bottom() -> Option<usize> {
+    // SAFETY: both calls only read the attributes of the calling thread
+    unsafe {
+        let thread = libc::pthread_self();
+        let top = libc::pthread_get_stackaddr_np(thread) as usize;
+        top.checked_sub(libc::pthread_get_stacksize_np(thread))
+    }
+}
+
+/// The lowest address of the current thread's stack, where the platform can report it
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+pub(crate) fn thread_stack_bottom() -> Option<usize> {
+    None
 }
 
 #[macro_use]
@@ -280,6 +545,13 @@ mod test_runtime {
         assert_eq!(serde_json::Value::Number(10.into()), result);
     }
 
+    #[test]
+    fn test_init_platform_after_runtime() {
+        Runtime::new(RuntimeOptions::default()).unwrap();
+        init_platform_with(PlatformOptions::default())
+            .expect_err("V8 is already initialized by the runtime");
+    }
+
     #[test]
     fn test_evaluate() {
         assert_eq!(5, evaluate::<i64>("3 + 2").expect("invalid expression"));
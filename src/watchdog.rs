@@ -0,0 +1,155 @@
+//! Detection of stalled event loops
+//!
+//! The watchdog samples the runtime's pending activity (async ops, resources and timers)
+//! while the event loop is being driven. If that set does not change for longer than the
+//! configured timeout, a diagnostic callback is invoked with a dump of what is pending.
+use std::{
+    task::Context,
+    time::{Duration, Instant},
+};
+
+use deno_core::JsRuntime;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::activity::{ActivityKey, ActivityTracker, PendingOpInfo};
+
+/// Options for the event-loop watchdog (see [`crate::RuntimeOptions::watchdog`])
+pub struct WatchdogOptions {
+    /// How long the pending activity of the event loop may remain unchanged
+    /// before the runtime is considered stalled
+    pub stall_timeout: Duration,
+
+    /// Callback invoked once each time a stall is detected
+    ///
+    /// It will not fire again until the event loop makes progress, and then stalls again
+    pub on_stall: Box<dyn Fn(&StallReport)>,
+}
+
+impl WatchdogOptions {
+    /// Create a new set of watchdog options
+    pub fn new(stall_timeout: Duration, on_stall: impl Fn(&StallReport) + 'static) -> Self {
+        Self {
+            stall_timeout,
+            on_stall: Box::new(on_stall),
+        }
+    }
+}
+
+/// Diagnostic information about a stalled event loop
+#[derive(Debug, Clone)]
+pub struct StallReport {
+    /// How long the event loop has gone without making progress
+    pub stalled_for: Duration,
+
+    /// The ops, resources and timers the event loop is waiting on
+    pub pending_ops: Vec<PendingOpInfo>,
+}
+
+/// Watchdog state owned by a single runtime
+pub(crate) struct Watchdog {
+    options: WatchdogOptions,
+    interval: Option<Interval>,
+
+    last_activity: Vec<ActivityKey>,
+    unchanged_since: Instant,
+    fired: bool,
+}
+
+impl Watchdog {
+    pub fn new(options: WatchdogOptions) -> Self {
+        Self {
+            options,
+            interval: None,
+            last_activity: Vec::new(),
+            unchanged_since: Instant::now(),
+            fired: false,
+        }
+    }
+
+    /// Restart stall detection - called whenever the host begins driving the event loop
+    pub fn reset(&mut self) {
+        self.last_activity.clear();
+        self.unchanged_since = Instant::now();
+        self.fired = false;
+    }
+
+    /// Check for a stall if a sample is due, and register the context to be woken for the next one
+    pub fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        runtime: &JsRuntime,
+        activity: &mut ActivityTracker,
+    ) {
+        let period = (self.options.stall_timeout / 4)
+            .clamp(Duration::from_millis(1), Duration::from_secs(1));
+        let mut interval = self.interval.take().unwrap_or_else(|| {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            interval
+        });
+
+        while interval.poll_tick(cx).is_ready() {
+            self.sample(activity.capture(runtime));
+        }
+
+        self.interval = Some(interval);
+    }
+
+    fn sample(&mut self, pending_ops: Vec<PendingOpInfo>) {
+        let keys: Vec<_> = pending_ops.iter().map(|op| op.key).collect();
+
+        if keys != self.last_activity {
+            self.last_activity = keys;
+            self.unchanged_since = Instant::now();
+            self.fired = false;
+            return;
+        }
+
+        let stalled_for = self.unchanged_since.elapsed();
+        if self.fired || pending_ops.is_empty() || stalled_for < self.options.stall_timeout {
+            return;
+        }
+
+        self.fired = true;
+        (self.options.on_stall)(&StallReport {
+            stalled_for,
+            pending_ops,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{Module, PendingOpKind, Runtime, RuntimeOptions};
+
+    #[test]
+    fn test_watchdog() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reports_ref = reports.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(500),
+            watchdog: Some(WatchdogOptions::new(
+                Duration::from_millis(100),
+                move |report| reports_ref.borrow_mut().push(report.clone()),
+            )),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new("test.js", "await new Promise(r => setTimeout(r, 10000));");
+        runtime
+            .load_module(&module)
+            .expect_err("Module did not time out");
+
+        let reports = reports.borrow();
+        assert_eq!(1, reports.len());
+        assert!(reports[0].stalled_for >= Duration::from_millis(100));
+        assert!(reports[0]
+            .pending_ops
+            .iter()
+            .any(|op| op.kind == PendingOpKind::Timer));
+    }
+}
@@ -15,11 +15,35 @@
 //!     assert_eq!(result, 10);
 //!     Ok(())
 //! }
+//! ```
+//!
+//! Queries are queued by [`Priority`] - [`WorkerHandle`]s can be shared between threads,
+//! and let latency-sensitive work jump ahead of queued background work:
+//! ```rust
+//! use rustyscript::{Error, worker::{DefaultWorker, DefaultWorkerOptions, DefaultWorkerQuery, DefaultWorkerResponse, Priority}};
+//!
+//! fn main() -> Result<(), Error> {
+//!     let worker = DefaultWorker::new(DefaultWorkerOptions::default())?;
+//!     let handle = worker.as_worker().handle();
+//!
+//!     let query = DefaultWorkerQuery::Eval("5 + 5".to_string());
+//!     let response = std::thread::spawn(move || handle.call(query, Priority::Interactive))
+//!         .join()
+//!         .unwrap()?;
+//!     assert!(matches!(response, DefaultWorkerResponse::Value(v) if v == 10));
+//!     Ok(())
+//! }
+//! ```
 use std::{
     cell::RefCell,
+    collections::VecDeque,
     rc::Rc,
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
     thread::{spawn, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use crate::{Error, RuntimeOptions};
@@ -116,7 +140,7 @@ where
     where
         T: serde::de::DeserializeOwned + Send + 'static,
     {
-        deno_core::JsRuntime::init_platform(None, true);
+        crate::utilities::init_default_platform();
         std::thread::spawn(move || {
             let mut runtime = crate::Runtime::new(RuntimeOptions::default())?;
             runtime.eval(&code)
@@ -131,12 +155,15 @@ where
 /// This allows flexibility in the runtime used by the worker, as well as the types of queries and responses that can be used
 ///
 /// For a simple worker that uses the default runtime, see [`DefaultWorker`]
+///
+/// Queries wait in a queue until the worker is free - see [`Priority`] and [`WorkerQueueOptions`]
 pub struct Worker<W>
 where
     W: InnerWorker,
 {
     handle: Option<JoinHandle<()>>,
-    tx: Option<Sender<W::Query>>,
+    dispatcher: Option<JoinHandle<()>>,
+    queue: Arc<WorkQueue<W>>,
     rx: Receiver<W::Response>,
 }
 
@@ -149,8 +176,20 @@ where
     /// # Errors
     /// Can fail if the runtime cannot be initialized (usually due to extension issues)
     pub fn new(options: W::RuntimeOptions) -> Result<Self, Error> {
+        Self::with_queue_options(options, WorkerQueueOptions::default())
+    }
+
+    /// Create a new worker instance, with the given options for its queue of pending queries
+    ///
+    /// # Errors
+    /// Can fail if the runtime cannot be initialized (usually due to extension issues)
+    pub fn with_queue_options(
+        options: W::RuntimeOptions,
+        queue_options: WorkerQueueOptions,
+    ) -> Result<Self, Error> {
         let (qtx, qrx) = channel();
         let (rtx, rrx) = channel();
+        let (otx, orx) = channel();
         let (init_tx, init_rx) = channel::<Option<Error>>();
 
         let handle = spawn(move || {
@@ -171,22 +210,27 @@ where
             }
         });
 
-        let worker = Self {
+        let mut worker = Self {
             handle: Some(handle),
-            tx: Some(qtx),
-            rx: rrx,
+            dispatcher: None,
+            queue: Arc::new(WorkQueue::new(queue_options)),
+            rx: orx,
         };
 
         // Wait for initialization to complete
         match init_rx.recv() {
-            Ok(None) => Ok(worker),
+            Ok(None) => {
+                let queue = Arc::clone(&worker.queue);
+                worker.dispatcher = Some(spawn(move || queue.dispatch(&qtx, &rrx, &otx)));
+                Ok(worker)
+            }
 
             // Initialization failed
             Ok(Some(e)) => Err(e),
 
             // Parser crashed on startup
             _ => {
-                let Some(handle) = worker.handle else {
+                let Some(handle) = worker.handle.take() else {
                     return Err(Error::Runtime(
                         "Could not start runtime thread: Worker handle missing".to_string(),
                     ));
@@ -219,31 +263,69 @@ where
     }
 
     /// Stop the worker and wait for it to finish
-    /// Stops by destroying the sender, which will cause the thread to exit the loop and finish
+    /// Queries already queued are still run, then the sender is destroyed,
+    /// which will cause the thread to exit the loop and finish
     ///
     /// WARNING: If implementing a custom `thread` function, make sure to handle rx failures gracefully
     ///          Otherwise this will block indefinitely
     pub fn shutdown(&mut self) {
-        if let (Some(tx), Some(hnd)) = (self.tx.take(), self.handle.take()) {
-            // We can stop the thread by destroying the sender
+        if let (Some(dispatcher), Some(hnd)) = (self.dispatcher.take(), self.handle.take()) {
+            // Once the queue drains, the dispatcher destroys the sender
             // This will cause the thread to exit the loop and finish
-            drop(tx);
+            self.queue.close();
+            dispatcher.join().ok();
             hnd.join().ok();
         }
     }
 
+    /// Returns a handle that can be used to send queries to the worker from any thread
+    ///
+    /// Responses to queries sent through a handle are returned to the caller,
+    /// rather than through [`Worker::receive`]
+    #[must_use]
+    pub fn handle(&self) -> WorkerHandle<W> {
+        WorkerHandle {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+
+    /// Returns the current depth of the worker's queue, and how long queries have waited in it
+    #[must_use]
+    pub fn queue_stats(&self) -> WorkerQueueStats {
+        self.queue.stats()
+    }
+
     /// Send a request to the worker
-    /// This will not block the current thread
+    /// This will not block the current thread, unless the queue is full
+    ///
+    /// The request is queued as [`Priority::Batch`] work
     ///
     /// # Errors
     /// Will return an error if the worker has already been stopped, or if the worker thread panicked
     pub fn send(&self, query: W::Query) -> Result<(), Error> {
-        match &self.tx {
-            None => return Err(Error::WorkerHasStopped),
-            Some(tx) => tx,
-        }
-        .send(query)
-        .map_err(|e| Error::Runtime(e.to_string()))
+        self.send_with_priority(query, Priority::Batch)
+    }
+
+    /// Send a request to the worker, queued with the given priority
+    /// This will not block the current thread, unless the queue is full
+    ///
+    /// Responses are still received in the order the worker runs the requests,
+    /// which may differ from the order they were sent in
+    ///
+    /// # Errors
+    /// Will return an error if the worker has already been stopped, or if the worker thread panicked
+    pub fn send_with_priority(&self, query: W::Query, priority: Priority) -> Result<(), Error> {
+        self.queue.push(priority, Job::new(query, None), true)
+    }
+
+    /// Send a request to the worker, queued with the given priority
+    /// This will never block the current thread
+    ///
+    /// # Errors
+    /// Will return [`Error::Overloaded`] if the queue is full (see [`WorkerQueueOptions::max_queued`]),
+    /// or an error if the worker has already been stopped, or if the worker thread panicked
+    pub fn try_send(&self, query: W::Query, priority: Priority) -> Result<(), Error> {
+        self.queue.push(priority, Job::new(query, None), false)
     }
 
     /// Receive a response from the worker
@@ -290,7 +372,7 @@ where
     /// Will return an error if the worker has already been stopped, or if the worker thread panicked
     pub fn join(mut self) -> Result<(), Error> {
         self.shutdown();
-        match self.handle {
+        match self.handle.take() {
             Some(hnd) => hnd
                 .join()
                 .map_err(|_| Error::Runtime("Worker thread panicked".to_string())),
@@ -299,6 +381,340 @@ where
     }
 }
 
+/// Scheduling class of a query queued for a [`Worker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    /// Latency-sensitive work, such as UI callbacks
+    /// Runs ahead of any queued batch work
+    Interactive,
+
+    /// Background work, such as bulk jobs
+    #[default]
+    Batch,
+}
+
+/// Options for the queue of queries waiting on a [`Worker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerQueueOptions {
+    /// Maximum number of interactive queries run in a row while batch work is waiting
+    ///
+    /// Ensures batch work still progresses under a constant stream of interactive queries
+    pub interactive_burst: usize,
+
+    /// Maximum number of queries waiting in the queue, not counting the one running
+    ///
+    /// Once full, `try_send` and `try_call` fail with [`Error::Overloaded`],
+    /// and other sends block until there is room. If `None`, the queue is unbounded
+    pub max_queued: Option<usize>,
+}
+
+impl Default for WorkerQueueOptions {
+    fn default() -> Self {
+        Self {
+            interactive_burst: 8,
+            max_queued: None,
+        }
+    }
+}
+
+/// A snapshot of the state of a [`Worker`]'s queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerQueueStats {
+    /// Interactive queries currently waiting
+    pub interactive: usize,
+
+    /// Batch queries currently waiting
+    pub batch: usize,
+
+    /// Queries handed to the worker so far
+    pub dispatched: u64,
+
+    /// Queries refused by `try_send` or `try_call` because the queue was full
+    pub rejected: u64,
+
+    /// Total time dispatched queries spent waiting in the queue
+    pub total_wait: Duration,
+
+    /// Longest time a dispatched query spent waiting in the queue
+    pub max_wait: Duration,
+}
+
+impl WorkerQueueStats {
+    /// Number of queries currently waiting
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.interactive + self.batch
+    }
+
+    /// Average time dispatched queries spent waiting in the queue
+    #[must_use]
+    pub fn mean_wait(&self) -> Duration {
+        if self.dispatched == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait / u32::try_from(self.dispatched).unwrap_or(u32::MAX)
+    }
+}
+
+/// A cloneable, thread-safe handle used to send queries to a [`Worker`]
+///
+/// Obtained from [`Worker::handle`]
+pub struct WorkerHandle<W>
+where
+    W: InnerWorker,
+{
+    queue: Arc<WorkQueue<W>>,
+}
+
+impl<W> Clone for WorkerHandle<W>
+where
+    W: InnerWorker,
+{
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+impl<W> WorkerHandle<W>
+where
+    W: InnerWorker,
+{
+    /// Queue a query with the given priority, and wait for its response
+    /// This will block the current thread until the response is received,
+    /// waiting for room first if the queue is full
+    ///
+    /// # Errors
+    /// Will return an error if the worker has already been stopped, or if the worker thread panicked
+    pub fn call(&self, query: W::Query, priority: Priority) -> Result<W::Response, Error> {
+        self.call_inner(query, priority, true)
+    }
+
+    /// Queue a query with the given priority, and wait for its response
+    /// Fails immediately instead of waiting if the queue is full
+    ///
+    /// # Errors
+    /// Will return [`Error::Overloaded`] if the queue is full (see [`WorkerQueueOptions::max_queued`]),
+    /// or an error if the worker has already been stopped, or if the worker thread panicked
+    pub fn try_call(&self, query: W::Query, priority: Priority) -> Result<W::Response, Error> {
+        self.call_inner(query, priority, false)
+    }
+
+    /// Returns the current depth of the worker's queue, and how long queries have waited in it
+    #[must_use]
+    pub fn queue_stats(&self) -> WorkerQueueStats {
+        self.queue.stats()
+    }
+
+    fn call_inner(
+        &self,
+        query: W::Query,
+        priority: Priority,
+        block: bool,
+    ) -> Result<W::Response, Error> {
+        let (tx, rx) = channel();
+        self.queue
+            .push(priority, Job::new(query, Some(tx)), block)?;
+        rx.recv().map_err(|_| Error::WorkerHasStopped)
+    }
+}
+
+/// A queued query, and where to send its response
+/// Responses without a dedicated channel go to [`Worker::receive`]
+struct Job<W>
+where
+    W: InnerWorker,
+{
+    query: W::Query,
+    reply: Option<Sender<W::Response>>,
+    queued_at: Instant,
+}
+
+impl<W> Job<W>
+where
+    W: InnerWorker,
+{
+    fn new(query: W::Query, reply: Option<Sender<W::Response>>) -> Self {
+        Self {
+            query,
+            reply,
+            queued_at: Instant::now(),
+        }
+    }
+}
+
+struct QueueState<W>
+where
+    W: InnerWorker,
+{
+    interactive: VecDeque<Job<W>>,
+    batch: VecDeque<Job<W>>,
+    streak: usize,
+    closed: bool,
+    stats: WorkerQueueStats,
+}
+
+impl<W> QueueState<W>
+where
+    W: InnerWorker,
+{
+    /// Returns the next job to run, interactive first unless batch work has waited out a full burst
+    fn next(&mut self, burst: usize) -> Option<Job<W>> {
+        if self.batch.is_empty() {
+            self.streak = 0;
+            return self.interactive.pop_front();
+        }
+
+        if self.streak < burst.max(1) {
+            if let Some(job) = self.interactive.pop_front() {
+                self.streak += 1;
+                return Some(job);
+            }
+        }
+
+        self.streak = 0;
+        self.batch.pop_front()
+    }
+}
+
+/// Queries waiting for a worker, run one at a time by a dispatcher thread
+struct WorkQueue<W>
+where
+    W: InnerWorker,
+{
+    state: Mutex<QueueState<W>>,
+    ready: Condvar,
+    space: Condvar,
+    options: WorkerQueueOptions,
+}
+
+impl<W> WorkQueue<W>
+where
+    W: InnerWorker,
+{
+    fn new(options: WorkerQueueOptions) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                interactive: VecDeque::new(),
+                batch: VecDeque::new(),
+                streak: 0,
+                closed: false,
+                stats: WorkerQueueStats::default(),
+            }),
+            ready: Condvar::new(),
+            space: Condvar::new(),
+            options,
+        }
+    }
+
+    /// Queues a job - if the queue is full, waits for room if `block` is set, or fails otherwise
+    fn push(&self, priority: Priority, job: Job<W>, block: bool) -> Result<(), Error> {
+        let poisoned = |_| Error::Runtime("Worker queue poisoned".to_string());
+        let mut state = self.state.lock().map_err(poisoned)?;
+        loop {
+            if state.closed {
+                return Err(Error::WorkerHasStopped);
+            }
+
+            let depth = state.interactive.len() + state.batch.len();
+            match self.options.max_queued {
+                Some(max) if depth >= max => {
+                    if !block {
+                        state.stats.rejected += 1;
+                        return Err(Error::Overloaded(depth));
+                    }
+                    state = self.space.wait(state).map_err(poisoned)?;
+                }
+                _ => break,
+            }
+        }
+
+        match priority {
+            Priority::Interactive => state.interactive.push_back(job),
+            Priority::Batch => state.batch.push_back(job),
+        }
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            self.ready.notify_all();
+            self.space.notify_all();
+        }
+    }
+
+    fn stats(&self) -> WorkerQueueStats {
+        self.state
+            .lock()
+            .map(|state| WorkerQueueStats {
+                interactive: state.interactive.len(),
+                batch: state.batch.len(),
+                ..state.stats
+            })
+            .unwrap_or_default()
+    }
+
+    /// Blocks until a job is available, or returns `None` once the queue is closed and empty
+    fn pop(&self) -> Option<Job<W>> {
+        let mut state = self.state.lock().ok()?;
+        loop {
+            if let Some(job) = state.next(self.options.interactive_burst) {
+                let wait = job.queued_at.elapsed();
+                state.stats.dispatched += 1;
+                state.stats.total_wait += wait;
+                state.stats.max_wait = state.stats.max_wait.max(wait);
+                self.space.notify_one();
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.ready.wait(state).ok()?;
+        }
+    }
+
+    /// Feeds queued jobs to the worker thread one at a time, routing each response to its caller
+    fn dispatch(
+        &self,
+        tx: &Sender<W::Query>,
+        rx: &Receiver<W::Response>,
+        worker_tx: &Sender<W::Response>,
+    ) {
+        while let Some(job) = self.pop() {
+            if tx.send(job.query).is_err() {
+                break;
+            }
+
+            let Ok(response) = rx.recv() else {
+                break;
+            };
+
+            // The caller may have stopped waiting - no need to check for errors
+            job.reply.as_ref().unwrap_or(worker_tx).send(response).ok();
+        }
+
+        // Reject anything sent from here on, and fail the callers still waiting
+        self.close();
+        if let Ok(mut state) = self.state.lock() {
+            state.interactive.clear();
+            state.batch.clear();
+        }
+    }
+}
+
+impl<W> Drop for Worker<W>
+where
+    W: InnerWorker,
+{
+    fn drop(&mut self) {
+        // Let the thread finish once the queue drains, without waiting for it
+        self.queue.close();
+    }
+}
+
 /// An implementation of the worker trait for a specific runtime
 /// This allows flexibility in the runtime used by the worker
 /// As well as the types of queries and responses that can be used
@@ -337,6 +753,8 @@ where
 
     /// Handle a query sent to the worker
     /// Must always return a response of some kind
+    ///
+    /// Custom `thread` functions must likewise send exactly one response per query
     fn handle_query(runtime: &mut Self::Runtime, query: Self::Query) -> Self::Response;
 
     /// The main thread function that will be run by the worker
@@ -372,6 +790,11 @@ impl InnerWorker for DefaultWorker {
     type Response = DefaultWorkerResponse;
 
     fn init_runtime(options: Self::RuntimeOptions) -> Result<Self::Runtime, Error> {
+        #[cfg(all(feature = "hardening", target_os = "linux"))]
+        if let Some(hardening) = &options.hardening {
+            hardening.apply()?;
+        }
+
         let runtime = crate::Runtime::new(crate::RuntimeOptions {
             default_entrypoint: options.default_entrypoint,
             timeout: options.timeout,
@@ -639,6 +1062,10 @@ pub struct DefaultWorkerOptions {
     /// Optional shared array buffer store to use for the runtime
     /// Allows data-sharing between runtimes across threads
     pub shared_array_buffer_store: Option<deno_core::SharedArrayBufferStore>,
+
+    /// OS-level restrictions applied to the worker's thread before its runtime is created
+    #[cfg(all(feature = "hardening", target_os = "linux"))]
+    pub hardening: Option<crate::Hardening>,
 }
 
 /// Query types for the default worker
@@ -682,3 +1109,131 @@ pub enum DefaultWorkerResponse {
     /// An error response
     Error(Error),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A worker that echoes its queries, or holds the queue until released
+    struct GatedWorker;
+    enum GatedQuery {
+        Echo(&'static str),
+        Gate(Sender<()>, Receiver<()>),
+    }
+
+    impl InnerWorker for GatedWorker {
+        type Runtime = ();
+        type RuntimeOptions = ();
+        type Query = GatedQuery;
+        type Response = &'static str;
+
+        fn init_runtime(_options: ()) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn handle_query(_runtime: &mut (), query: GatedQuery) -> &'static str {
+            match query {
+                GatedQuery::Echo(value) => value,
+                GatedQuery::Gate(started, release) => {
+                    started.send(()).unwrap();
+                    release.recv().unwrap();
+                    "gate"
+                }
+            }
+        }
+    }
+
+    /// Occupies the worker until the returned sender is used, so that later queries stay queued
+    fn hold(worker: &Worker<GatedWorker>) -> Sender<()> {
+        let (started_tx, started) = channel();
+        let (release, release_rx) = channel();
+        worker
+            .send(GatedQuery::Gate(started_tx, release_rx))
+            .unwrap();
+        started.recv().unwrap();
+        release
+    }
+
+    #[test]
+    fn test_priority_queue() {
+        let worker = Worker::<GatedWorker>::with_queue_options(
+            (),
+            WorkerQueueOptions {
+                interactive_burst: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let release = hold(&worker);
+        for value in ["b0", "b1", "b2"] {
+            worker
+                .send_with_priority(GatedQuery::Echo(value), Priority::Batch)
+                .unwrap();
+        }
+        for value in ["i0", "i1", "i2"] {
+            worker
+                .send_with_priority(GatedQuery::Echo(value), Priority::Interactive)
+                .unwrap();
+        }
+        release.send(()).unwrap();
+
+        let order: Vec<_> = (0..7).map(|_| worker.receive().unwrap()).collect();
+        assert_eq!(order, vec!["gate", "i0", "i1", "b0", "i2", "b1", "b2"]);
+
+        // Handles receive their own responses, from any thread
+        let handle = worker.handle();
+        let response =
+            std::thread::spawn(move || handle.call(GatedQuery::Echo("2"), Priority::Interactive))
+                .join()
+                .unwrap()
+                .unwrap();
+        assert_eq!(response, "2");
+
+        let handle = worker.handle();
+        worker.join().unwrap();
+        handle
+            .call(GatedQuery::Echo("1"), Priority::Batch)
+            .expect_err("Called a stopped worker");
+    }
+
+    #[test]
+    fn test_queue_limits() {
+        let worker = Worker::<GatedWorker>::with_queue_options(
+            (),
+            WorkerQueueOptions {
+                max_queued: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let release = hold(&worker);
+        worker
+            .try_send(GatedQuery::Echo("1"), Priority::Batch)
+            .unwrap();
+        let handle = worker.handle();
+        let e = handle
+            .try_call(GatedQuery::Echo("2"), Priority::Interactive)
+            .unwrap_err();
+        assert!(matches!(e, Error::Overloaded(1)));
+
+        let stats = worker.queue_stats();
+        assert_eq!(stats.depth(), 1);
+        assert_eq!(stats.rejected, 1);
+
+        // Blocks until there is room, rather than failing
+        let blocked =
+            std::thread::spawn(move || handle.call(GatedQuery::Echo("3"), Priority::Batch));
+        release.send(()).unwrap();
+        assert_eq!(blocked.join().unwrap().unwrap(), "3");
+
+        assert_eq!(worker.receive().unwrap(), "gate");
+        assert_eq!(worker.receive().unwrap(), "1");
+        let stats = worker.queue_stats();
+        assert_eq!(stats.depth(), 0);
+        assert_eq!(stats.dispatched, 3);
+        assert!(stats.max_wait > std::time::Duration::ZERO);
+        assert!(stats.mean_wait() <= stats.max_wait);
+    }
+}
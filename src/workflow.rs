@@ -0,0 +1,302 @@
+//! Experimental durable workflows, suspended at host-designated steps and resumed later
+//!
+//! V8 cannot serialize a suspended call stack - instead, workflows are resumed by deterministic replay.
+//! Scripts call `await rustyscript.step(name, ...args)` at each point the host should carry out.
+//! The first time a step is reached the workflow suspends, and the host receives a [`WorkflowState`]
+//! describing the pending steps. Once the host has performed them and recorded their results,
+//! the workflow function is run again from the start - on this or any other runtime - with completed
+//! steps returning their recorded results, until it reaches the next unfinished step or returns.
+//!
+//! Workflow functions must therefore be deterministic between `step` calls: no direct I/O,
+//! timers, randomness or clock reads that influence which steps are called
+use std::collections::BTreeMap;
+
+use deno_core::{serde_json, v8, JsRuntime};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, ModuleHandle};
+
+/// A step a suspended workflow is waiting for the host to carry out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingStep {
+    /// Position of the step in the workflow's sequence of `rustyscript.step` calls
+    pub sequence: u64,
+
+    /// The name passed to `rustyscript.step`
+    pub name: String,
+
+    /// The remaining arguments passed to `rustyscript.step`
+    pub args: Vec<serde_json::Value>,
+}
+
+/// The recorded outcome of a completed step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum StepOutcome {
+    Value(serde_json::Value),
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct JournalEntry {
+    name: String,
+    outcome: StepOutcome,
+}
+
+/// The serializable state of a suspended workflow
+///
+/// Holds the identity of the workflow's module and function, its arguments, and the results
+/// of every step completed so far. Persist it however is convenient, and resume it with
+/// [`crate::Runtime::resume_workflow`] once the pending steps have been completed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowState {
+    module: String,
+    source_hash: u64,
+    function: String,
+    args: Vec<serde_json::Value>,
+    journal: BTreeMap<u64, JournalEntry>,
+    pending: Vec<PendingStep>,
+
+    #[serde(skip)]
+    cursor: u64,
+}
+
+impl WorkflowState {
+    pub(crate) fn new(module: &ModuleHandle, function: &str, args: Vec<serde_json::Value>) -> Self {
+        Self {
+            module: module_name(module),
+            source_hash: source_hash(module.module().contents()),
+            function: function.to_string(),
+            args,
+            journal: BTreeMap::new(),
+            pending: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// The name of the workflow function
+    #[must_use]
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    /// The steps the workflow is waiting on
+    #[must_use]
+    pub fn pending(&self) -> &[PendingStep] {
+        &self.pending
+    }
+
+    /// Records the result of a pending step, returned from `rustyscript.step` when the workflow resumes
+    ///
+    /// # Errors
+    /// Will return an error if no step with that sequence number is pending,
+    /// or if the result cannot be serialized
+    pub fn complete(&mut self, sequence: u64, result: &impl Serialize) -> Result<(), Error> {
+        let result = serde_json::to_value(result).map_err(|e| Error::Runtime(e.to_string()))?;
+        self.record(sequence, StepOutcome::Value(result))
+    }
+
+    /// Records the failure of a pending step, thrown from `rustyscript.step` when the workflow resumes
+    ///
+    /// # Errors
+    /// Will return an error if no step with that sequence number is pending
+    pub fn fail(&mut self, sequence: u64, error: impl ToString) -> Result<(), Error> {
+        self.record(sequence, StepOutcome::Error(error.to_string()))
+    }
+
+    fn record(&mut self, sequence: u64, outcome: StepOutcome) -> Result<(), Error> {
+        let index = self
+            .pending
+            .iter()
+            .position(|step| step.sequence == sequence)
+            .ok_or_else(|| Error::Runtime(format!("No workflow step {sequence} is pending")))?;
+
+        let step = self.pending.remove(index);
+        self.journal.insert(
+            sequence,
+            JournalEntry {
+                name: step.name,
+                outcome,
+            },
+        );
+        Ok(())
+    }
+
+    /// Checks that the state was produced by the given module
+    pub(crate) fn check_module(&self, module: &ModuleHandle) -> Result<(), Error> {
+        if self.module != module_name(module)
+            || self.source_hash != source_hash(module.module().contents())
+        {
+            return Err(Error::Runtime(format!(
+                "Workflow state belongs to a different version of `{}`",
+                self.module
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn args(&self) -> &[serde_json::Value] {
+        &self.args
+    }
+
+    /// Prepares the state for a new run of the workflow function
+    pub(crate) fn rewind(&mut self) {
+        self.cursor = 0;
+        self.pending.clear();
+    }
+
+    /// Called by `rustyscript.step` - returns the recorded outcome, or `None` if the workflow must suspend
+    pub(crate) fn step(
+        &mut self,
+        name: String,
+        args: Vec<serde_json::Value>,
+    ) -> Result<Option<StepOutcome>, Error> {
+        let sequence = self.cursor;
+        self.cursor += 1;
+
+        match self.journal.get(&sequence) {
+            Some(entry) if entry.name == name => Ok(Some(entry.outcome.clone())),
+            Some(entry) => Err(Error::Runtime(format!(
+                "Non-deterministic workflow: step {sequence} was `{}`, but is now `{name}`",
+                entry.name
+            ))),
+            None => {
+                self.pending.push(PendingStep {
+                    sequence,
+                    name,
+                    args,
+                });
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The result of running a workflow until it returns or suspends
+#[derive(Debug)]
+pub enum WorkflowStatus<T> {
+    /// The workflow function returned
+    Complete(T),
+
+    /// The workflow is waiting for the host to complete its pending steps
+    Suspended(WorkflowState),
+}
+
+/// The state of a workflow function's result once the event loop is idle
+pub(crate) enum Settled {
+    Pending,
+    Fulfilled(v8::Global<v8::Value>),
+    Rejected(Error),
+}
+
+impl Settled {
+    pub fn of(runtime: &mut JsRuntime, value: v8::Global<v8::Value>) -> Self {
+        deno_core::scope!(scope, runtime);
+        let local = v8::Local::new(scope, &value);
+        let Ok(promise) = v8::Local::<v8::Promise>::try_from(local) else {
+            return Self::Fulfilled(value);
+        };
+
+        match promise.state() {
+            v8::PromiseState::Pending => Self::Pending,
+            v8::PromiseState::Fulfilled => {
+                let result = promise.result(scope);
+                Self::Fulfilled(v8::Global::new(scope, result))
+            }
+            v8::PromiseState::Rejected => {
+                let error = promise.result(scope);
+                let error = deno_core::error::JsError::from_v8_exception(scope, error);
+                Self::Rejected(error.into())
+            }
+        }
+    }
+}
+
+fn module_name(module: &ModuleHandle) -> String {
+    module.module().filename().to_string_lossy().to_string()
+}
+
+/// FNV-1a, so the hash is stable across processes and compiler versions
+fn source_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, serde_json::json, Module, Runtime, RuntimeOptions};
+
+    const WORKFLOW: &str = "
+        export async function order(id) {
+            const [stock, price] = await Promise.all([
+                rustyscript.step('checkStock', id),
+                rustyscript.step('getPrice', id),
+            ]);
+            if (!stock) return 'out of stock';
+
+            try {
+                await rustyscript.step('charge', price);
+            } catch (e) {
+                return `failed: ${e.message}`;
+            }
+            return `charged ${price}`;
+        }
+    ";
+
+    #[test]
+    fn test_workflow() {
+        let module = Module::new("workflow.js", WORKFLOW);
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+        let status = runtime
+            .start_workflow::<String>(&handle, "order", json_args!(7))
+            .unwrap();
+        let WorkflowStatus::Suspended(mut state) = status else {
+            panic!("Workflow did not suspend");
+        };
+
+        let names: Vec<_> = state.pending().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["checkStock", "getPrice"]);
+        assert_eq!(state.pending()[0].args, vec![json!(7)]);
+        state.complete(0, &true).unwrap();
+        state.complete(1, &25).unwrap();
+        state.complete(1, &25).expect_err("Completed a step twice");
+
+        // Persist, and resume on a fresh runtime
+        let state = serde_json::to_string(&state).unwrap();
+        let state: WorkflowState = serde_json::from_str(&state).unwrap();
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        let WorkflowStatus::Suspended(mut state) =
+            runtime.resume_workflow::<String>(&handle, state).unwrap()
+        else {
+            panic!("Workflow did not suspend");
+        };
+        assert_eq!(state.pending()[0].name, "charge");
+        assert_eq!(state.pending()[0].args, vec![json!(25)]);
+
+        let mut failed = state.clone();
+        state.complete(2, &()).unwrap();
+        let status = runtime
+            .resume_workflow::<String>(&handle, state.clone())
+            .unwrap();
+        assert!(matches!(status, WorkflowStatus::Complete(s) if s == "charged 25"));
+
+        failed.fail(2, "card declined").unwrap();
+        let status = runtime.resume_workflow::<String>(&handle, failed).unwrap();
+        assert!(matches!(status, WorkflowStatus::Complete(s) if s == "failed: card declined"));
+
+        // Different source
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let other = runtime
+            .load_module(&Module::new("workflow.js", "export const order = () => 1;"))
+            .unwrap();
+        runtime
+            .resume_workflow::<String>(&other, state)
+            .expect_err("Resumed against a different module");
+    }
+}